@@ -1,7 +1,11 @@
 //! Predecessors to [ChrisClient] for getting _ChRIS_ authorization
 //! tokens or creating _ChRIS_ accounts.
 
+use crate::errors::{check, CubeError};
+use crate::models::BaseResponse;
 use crate::types::{CubeUrl, ItemUrl, UserId, Username};
+use crate::UserResponse;
+use reqwest::header::{ACCEPT, AUTHORIZATION};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
@@ -52,7 +56,7 @@ impl<'a> Account<'a> {
         }
     }
 
-    pub async fn get_token(&self) -> Result<String, reqwest::Error> {
+    pub async fn get_token(&self) -> Result<String, CubeError> {
         let auth_url = format!("{}auth-token/", &self.url);
         let req = self
             .client
@@ -62,13 +66,12 @@ impl<'a> Account<'a> {
                 username: self.username,
                 password: self.password,
             });
-        let res = req.send().await?;
-        res.error_for_status_ref()?;
+        let res = check(req.send().await?).await?;
         let token_object: AuthTokenResponse = res.json().await?;
         Ok(token_object.token)
     }
 
-    pub async fn create_account(&self, email: &str) -> Result<UserCreatedResponse, reqwest::Error> {
+    pub async fn create_account(&self, email: &str) -> Result<UserCreatedResponse, CubeError> {
         let users_url = format!("{}users/", &self.url);
         let req = self
             .client
@@ -79,9 +82,25 @@ impl<'a> Account<'a> {
                 password: self.password,
                 email,
             });
-        let res = req.send().await?;
-        res.error_for_status_ref()?;
+        let res = check(req.send().await?).await?;
         let created_user: UserCreatedResponse = res.json().await?;
         Ok(created_user)
     }
+
+    /// Get the details of the account which `token` belongs to.
+    pub async fn get_details(&self, token: &str) -> Result<UserResponse, CubeError> {
+        let auth = format!("token {}", token);
+        let get = |url: &str| {
+            self.client
+                .get(url)
+                .header(ACCEPT, "application/json")
+                .header(AUTHORIZATION, &auth)
+                .send()
+        };
+        let res = check(get(self.url.as_str()).await?).await?;
+        let base: BaseResponse = res.json().await?;
+        let user_url = base.collection_links.user.ok_or(CubeError::UnknownUser)?;
+        let res = check(get(user_url.as_str()).await?).await?;
+        Ok(res.json().await?)
+    }
 }
@@ -2,17 +2,18 @@ use async_trait::async_trait;
 use reqwest::header::{HeaderMap, ACCEPT};
 use serde::de::DeserializeOwned;
 
-use crate::errors::{check, CubeError};
-use crate::models::{BaseResponse, CubeLinks};
+use crate::errors::CubeError;
+use crate::models::CubeLinks;
 use crate::search::{
-    FeedSearchBuilder, PipelineSearchBuilder, PluginSearchBuilder, QueryBuilder, LIMIT_ZERO,
+    FeedSearchBuilder, PacsFilesSearchBuilder, PaginationOrigin, PipelineSearchBuilder,
+    PluginSearchBuilder, QueryBuilder,
 };
 use crate::types::*;
 use crate::{FeedResponse, LinkedModel, PluginInstanceResponse};
 
 use super::access::RoAccess;
-use super::base::fetch_id;
 use super::base::BaseChrisClient;
+use super::base::{connect_to, fetch_id};
 use super::filebrowser::FileBrowser;
 
 /// Anonymous _ChRIS_ client.
@@ -20,11 +21,13 @@ pub struct AnonChrisClient {
     client: reqwest_middleware::ClientWithMiddleware,
     url: CubeUrl,
     links: CubeLinks,
+    version: Option<String>,
 }
 
 pub struct AnonChrisClientBuilder {
     url: CubeUrl,
     builder: reqwest_middleware::ClientBuilder,
+    rewrite_next_urls: bool,
 }
 
 impl AnonChrisClientBuilder {
@@ -33,30 +36,44 @@ impl AnonChrisClientBuilder {
             .default_headers(accept_json())
             .build()?;
         let builder = reqwest_middleware::ClientBuilder::new(client);
-        Ok(Self { url, builder })
+        Ok(Self {
+            url,
+            builder,
+            rewrite_next_urls: true,
+        })
     }
 
     /// Add middleware to the HTTP client.
     pub fn with<M: reqwest_middleware::Middleware>(self, middleware: M) -> Self {
         Self {
-            url: self.url,
             builder: self.builder.with(middleware),
+            ..self
+        }
+    }
+
+    /// Whether to change the scheme and host of the `next` links of paginated responses
+    /// to those of the CUBE URL. Enabled by default.
+    pub fn rewrite_next_urls(self, rewrite: bool) -> Self {
+        Self {
+            rewrite_next_urls: rewrite,
+            ..self
         }
     }
 
     /// Connect to the ChRIS API.
     pub async fn connect(self) -> Result<AnonChrisClient, CubeError> {
-        let client = self.builder.build();
-        let res = client
-            .get(self.url.as_str())
-            .query(&LIMIT_ZERO)
-            .send()
-            .await?;
-        let base_response: BaseResponse = check(res).await?.json().await?;
+        let builder = if self.rewrite_next_urls {
+            PaginationOrigin::init(self.builder, &self.url)
+        } else {
+            self.builder
+        };
+        let client = builder.build();
+        let info = connect_to(&client, &self.url).await?;
         Ok(AnonChrisClient {
             client,
             url: self.url,
-            links: base_response.collection_links,
+            links: info.links,
+            version: info.version,
         })
     }
 }
@@ -76,6 +93,12 @@ impl AnonChrisClient {
     fn query<T: DeserializeOwned>(&self, url: &CollectionUrl) -> QueryBuilder<T, RoAccess> {
         QueryBuilder::query(self.client.clone(), url.clone())
     }
+
+    /// Search for PACSFiles. Only works if the _CUBE_ allows anonymous users
+    /// to see PACSFiles, otherwise nothing is found.
+    pub fn pacsfiles(&self) -> PacsFilesSearchBuilder<RoAccess> {
+        self.query(&self.links.pacsfiles)
+    }
 }
 
 #[async_trait]
@@ -88,6 +111,10 @@ impl BaseChrisClient<RoAccess> for AnonChrisClient {
         &self.url
     }
 
+    fn server_version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
     fn plugin(&self) -> PluginSearchBuilder<RoAccess> {
         self.query(&self.links.plugins)
     }
@@ -111,3 +138,68 @@ impl BaseChrisClient<RoAccess> for AnonChrisClient {
         fetch_id(&self.client, &self.links.plugin_instances, id.0).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCube;
+    use futures::TryStreamExt;
+    use rstest::*;
+
+    /// Where the `next` links of the mock point to. Nothing is listening there.
+    const INTERNAL_HOST: &str = "https://127.0.0.2:1/api/v1/";
+
+    /// A mock _CUBE_ with three plugins, one per page, behind a misconfigured proxy.
+    async fn mock_behind_proxy() -> MockCube {
+        let mock = MockCube::start().await;
+        for (id, name) in [
+            (1, "pl-dircopy"),
+            (2, "pl-tsdircopy"),
+            (3, "pl-mri10yr06mo01da_normal"),
+        ] {
+            mock.add_plugin(mock.plugin(id, name, "1.0.0"));
+        }
+        mock.set_max_limit(1);
+        mock.set_pagination_base(INTERNAL_HOST);
+        mock
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_next_urls_are_rewritten_by_default() {
+        let mock = mock_behind_proxy().await;
+        let client = mock.anon_client().await;
+        let plugins: Vec<_> = client
+            .plugin()
+            .search()
+            .stream()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(plugins.len(), 3);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_rewrite_next_urls_disabled() {
+        let mock = mock_behind_proxy().await;
+        let client = AnonChrisClient::build(mock.url().clone())
+            .unwrap()
+            .rewrite_next_urls(false)
+            .connect()
+            .await
+            .unwrap();
+        let requests_before = mock.requests().len();
+        let error = client
+            .plugin()
+            .search()
+            .stream()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+        // the second page was requested from the `next` link as-is
+        assert!(matches!(error, CubeError::Raw(_)), "{error:?}");
+        assert!(error.to_string().contains("127.0.0.2:1"), "{error}");
+        assert_eq!(mock.requests().len(), requests_before + 1);
+    }
+}
@@ -1,22 +1,25 @@
 use super::access::RoAccess;
-use super::base::fetch_id;
-use crate::errors::{check, CubeError, FileIOError};
-use crate::models::{BaseResponse, CubeLinks, FileUploadResponse};
+use super::base::{connect_to, fetch_id};
+use crate::errors::{check, CubeError, FileIOError, ForeignObjectError};
+use crate::models::{CubeLinks, FileUploadResponse};
+use crate::pipeline::CanonPipeline;
 use crate::search::*;
 use crate::types::*;
 use crate::{
-    Access, BaseChrisClient, FeedResponse, FileBrowser, LinkedModel, PluginInstanceResponse,
-    RwAccess,
+    Access, Account, BaseChrisClient, FeedResponse, FileBrowser, LazyLinkedModel, LinkedModel,
+    PipelineRw, PluginInstanceResponse, Resource, RwAccess, TagRw, UserResponse,
 };
 use async_trait::async_trait;
-use bytes::Bytes;
+use bytes::{BufMut, Bytes, BytesMut};
 use camino::Utf8Path;
 use fs_err::tokio::File;
-use futures::TryStream;
-use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION};
+use futures::{TryStream, TryStreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
 use reqwest::multipart::{Form, Part};
 use reqwest::Body;
+use reqwest::StatusCode;
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::borrow::Cow;
 use std::marker::PhantomData;
 use tokio_util::codec::{BytesCodec, FramedRead};
@@ -31,63 +34,113 @@ pub struct AuthedChrisClient<A: Access> {
     url: CubeUrl,
     username: Username,
     links: CubeLinks,
+    version: Option<String>,
     phantom: PhantomData<A>,
     feeds_url: CollectionUrl,
+    replayable_upload_limit: u64,
+    /// Client without middleware, for uploads which cannot be sent again.
+    stream_client: reqwest::Client,
 }
 
 pub struct ChrisClientBuilder {
     url: CubeUrl,
-    username: Username,
+    /// `None` if the username should be gotten from _CUBE_.
+    username: Option<Username>,
     builder: reqwest_middleware::ClientBuilder,
+    stream_client: reqwest::Client,
+    replayable_upload_limit: u64,
+    rewrite_next_urls: bool,
 }
 
 impl ChrisClientBuilder {
     pub(crate) fn new(
         url: CubeUrl,
-        username: Username,
+        username: Option<Username>,
         token: &str,
     ) -> Result<Self, reqwest::Error> {
         let client = reqwest::ClientBuilder::new()
             .default_headers(token2header(token))
             .build()?;
-        let builder = reqwest_middleware::ClientBuilder::new(client);
+        let builder = reqwest_middleware::ClientBuilder::new(client.clone());
         Ok(Self {
             url,
             username,
             builder,
+            stream_client: client,
+            replayable_upload_limit: 0,
+            rewrite_next_urls: true,
         })
     }
 
     /// Add middleware to the HTTP client.
     pub fn with<M: reqwest_middleware::Middleware>(self, middleware: M) -> Self {
         Self {
-            url: self.url,
-            username: self.username,
             builder: self.builder.with(middleware),
+            ..self
+        }
+    }
+
+    /// Buffer uploads of at most `limit` bytes in memory, so that they can be sent
+    /// again by retry middleware. Larger uploads are streamed without going through
+    /// middleware, since retry middleware would refuse them. By default, no uploads
+    /// are buffered.
+    pub fn replayable_upload_limit(self, limit: u64) -> Self {
+        Self {
+            replayable_upload_limit: limit,
+            ..self
+        }
+    }
+
+    /// Whether to change the scheme and host of the `next` links of paginated responses
+    /// to those of the CUBE URL, in case _CUBE_ is behind a reverse proxy which makes it
+    /// give links to its internal hostname. Enabled by default. Disable it for
+    /// deployments of _CUBE_ which span several hosts.
+    pub fn rewrite_next_urls(self, rewrite: bool) -> Self {
+        Self {
+            rewrite_next_urls: rewrite,
+            ..self
         }
     }
 
     /// Connect to the ChRIS API.
     pub async fn connect(self) -> Result<ChrisClient, CubeError> {
-        let client = self.builder.build();
-        let res = client
-            .get(self.url.as_str())
-            .query(&LIMIT_ZERO)
-            .send()
-            .await?;
-        let base_response: BaseResponse = check(res).await?.json().await?;
+        let builder = if self.rewrite_next_urls {
+            PaginationOrigin::init(self.builder, &self.url)
+        } else {
+            self.builder
+        };
+        let client = builder.build();
+        let info = connect_to(&client, &self.url).await?;
+        let username = match self.username {
+            Some(username) => username,
+            None => get_username(&client, &info.links).await?,
+        };
         let feeds_url = CollectionUrl::new(self.url.clone().take());
         Ok(ChrisClient {
             client,
-            username: self.username,
+            username,
             url: self.url,
-            links: base_response.collection_links,
+            links: info.links,
+            version: info.version,
             phantom: Default::default(),
             feeds_url,
+            replayable_upload_limit: self.replayable_upload_limit,
+            stream_client: self.stream_client,
         })
     }
 }
 
+/// Get the username of the user who the client is authorized as.
+async fn get_username(
+    client: &reqwest_middleware::ClientWithMiddleware,
+    links: &CubeLinks,
+) -> Result<Username, CubeError> {
+    let url = links.user.as_ref().ok_or(CubeError::UnknownUser)?;
+    let res = client.get(url.as_str()).send().await?;
+    let user: UserResponse = check(res).await?.json().await?;
+    Ok(user.username)
+}
+
 fn token2header(token: &str) -> HeaderMap {
     let mut headers = HeaderMap::new();
     let auth_data = format!("token {}", token);
@@ -108,6 +161,10 @@ impl<A: Access> BaseChrisClient<A> for AuthedChrisClient<A> {
         &self.url
     }
 
+    fn server_version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
     fn plugin(&self) -> PluginSearchBuilder<A> {
         self.query(&self.links.plugins)
     }
@@ -143,7 +200,18 @@ impl<A: Access> AuthedChrisClient<A> {
         username: Username,
         token: impl AsRef<str>,
     ) -> Result<ChrisClientBuilder, reqwest::Error> {
-        ChrisClientBuilder::new(url, username, token.as_ref())
+        ChrisClientBuilder::new(url, Some(username), token.as_ref())
+    }
+
+    /// Create a client builder for a token of an unknown user.
+    ///
+    /// When connecting, the username is gotten from _CUBE_, which also
+    /// checks that the token is valid.
+    pub fn build_from_token(
+        url: CubeUrl,
+        token: impl AsRef<str>,
+    ) -> Result<ChrisClientBuilder, reqwest::Error> {
+        ChrisClientBuilder::new(url, None, token.as_ref())
     }
 
     /// Get username
@@ -151,6 +219,30 @@ impl<A: Access> AuthedChrisClient<A> {
         &self.username
     }
 
+    /// Size of the largest uploads which are buffered in memory so that
+    /// they can be retried, see [ChrisClientBuilder::replayable_upload_limit].
+    pub fn replayable_upload_limit(&self) -> u64 {
+        self.replayable_upload_limit
+    }
+
+    /// Get the account of this user. Also useful for checking whether the
+    /// authorization token is still valid.
+    ///
+    /// Returns `None` if _CUBE_ does not link to the user.
+    pub async fn user(&self) -> Result<Option<LinkedModel<UserResponse, A>>, CubeError> {
+        match &self.links.user {
+            Some(url) => {
+                let lazy: LazyLinkedModel<UserResponse, A> = LazyLinkedModel {
+                    url,
+                    client: &self.client,
+                    phantom: Default::default(),
+                };
+                lazy.get().await.map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Search for feeds
     pub fn feeds(&self) -> FeedSearchBuilder<A> {
         self.query(&self.feeds_url)
@@ -166,11 +258,26 @@ impl<A: Access> AuthedChrisClient<A> {
         self.query(&self.links.files)
     }
 
+    /// Search for uploaded files
+    pub fn userfiles(&self) -> UserFilesSearchBuilder<A> {
+        self.query(&self.links.userfiles)
+    }
+
     /// Search for workflows
     pub fn workflows(&self) -> WorkflowSearchBuilder<A> {
         self.query(&self.links.workflows)
     }
 
+    /// Search for tags of feeds
+    pub fn tags(&self) -> TagSearchBuilder<A> {
+        self.query(&self.links.tags)
+    }
+
+    /// Search for compute resources
+    pub fn compute_resources(&self) -> ComputeResourceSearchBuilder<A> {
+        self.query(&self.links.compute_resources)
+    }
+
     /// Search for PACSFiles
     pub fn pacsfiles(&self) -> PacsFilesSearchBuilder<A> {
         self.query(&self.links.pacsfiles)
@@ -191,6 +298,10 @@ impl<A: Access> AuthedChrisClient<A> {
     /// - stream: stream of byte data
     /// - filename: included in the multi-part post request (not the _ChRIS_ file path)
     /// - path: _ChRIS_ file path starting with `"<username>/uploads/"`
+    ///
+    /// If `content_length` is at most [ChrisClientBuilder::replayable_upload_limit],
+    /// the stream is read into memory before the request is sent, so that retry
+    /// middleware can send it again. Otherwise, the stream is sent without middleware.
     pub async fn upload_stream<S, F, P>(
         &self,
         stream: S,
@@ -205,17 +316,37 @@ impl<A: Access> AuthedChrisClient<A> {
         F: Into<Cow<'static, str>>,
         P: Into<Cow<'static, str>>,
     {
-        // https://github.com/seanmonstar/reqwest/issues/646#issuecomment-616985015
-        let reader = Body::wrap_stream(stream);
-        let form = Form::new().text("upload_path", path).part(
-            "fname",
-            Part::stream_with_length(reader, content_length).file_name(filename),
-        );
-        let req = self
-            .client
-            .post(self.links.userfiles.as_str())
-            .multipart(form);
-        let res = req.send().await?;
+        let url = self.links.userfiles.as_str();
+        let res = if content_length <= self.replayable_upload_limit {
+            let data = stream
+                .map_err(|e| FileIOError::IO(std::io::Error::other(e)))
+                .try_fold(BytesMut::new(), |mut data, chunk| async move {
+                    data.extend_from_slice(&Bytes::from(chunk));
+                    Ok(data)
+                })
+                .await?;
+            // reqwest streams multipart forms, so the body is encoded here instead
+            let boundary = uuid::Uuid::new_v4().simple().to_string();
+            let (path, filename): (Cow<str>, Cow<str>) = (path.into(), filename.into());
+            let body = multipart_body(&boundary, &path, &filename, &data);
+            self.client
+                .post(url)
+                .header(
+                    CONTENT_TYPE,
+                    format!("multipart/form-data; boundary={}", boundary),
+                )
+                .body(body)
+                .send()
+                .await?
+        } else {
+            // https://github.com/seanmonstar/reqwest/issues/646#issuecomment-616985015
+            let reader = Body::wrap_stream(stream);
+            let form = Form::new().text("upload_path", path).part(
+                "fname",
+                Part::stream_with_length(reader, content_length).file_name(filename),
+            );
+            self.stream_client.post(url).multipart(form).send().await?
+        };
         Ok(check(res).await?.json().await?)
     }
 
@@ -248,6 +379,81 @@ impl<A: Access> AuthedChrisClient<A> {
 }
 
 impl ChrisClient {
+    /// Upload a pipeline.
+    pub async fn create_pipeline(&self, pipeline: &CanonPipeline) -> Result<PipelineRw, CubeError> {
+        let res = self
+            .client
+            .post(self.links.pipelines.as_str())
+            .json(pipeline)
+            .send()
+            .await?;
+        let data = check(res).await?.json().await?;
+        Ok(LinkedModel {
+            client: self.client.clone(),
+            object: data,
+            phantom: Default::default(),
+        })
+    }
+
+    /// Create a tag, which can be put on feeds using [crate::FeedRw::tag].
+    ///
+    /// `color` is how the tag is displayed by the _ChRIS_ UI, e.g. `"#2196f3"`.
+    pub async fn create_tag(&self, name: &str, color: &str) -> Result<TagRw, CubeError> {
+        let res = self
+            .client
+            .post(self.links.tags.as_str())
+            .json(&TagRequest { name, color })
+            .send()
+            .await?;
+        let data = check(res).await?.json().await?;
+        Ok(LinkedModel {
+            client: self.client.clone(),
+            object: data,
+            phantom: Default::default(),
+        })
+    }
+
+    /// Change the password of this user.
+    ///
+    /// _CUBE_ does not ask for the old password, so it is checked by getting a token
+    /// using it before the password is changed.
+    pub async fn change_password(
+        &self,
+        old: &str,
+        new: &str,
+    ) -> Result<LinkedModel<UserResponse, RwAccess>, CubeError> {
+        Account::new(&self.url, &self.username, old)
+            .get_token()
+            .await
+            .map_err(|e| match e {
+                CubeError::Error { status, .. } if status == StatusCode::BAD_REQUEST => {
+                    CubeError::IncorrectPassword
+                }
+                e => e,
+            })?;
+        let user = self.user().await?.ok_or(CubeError::UnknownUser)?;
+        let update = UserUpdate {
+            username: &user.object.username,
+            email: &user.object.email,
+            password: Some(new),
+        };
+        user.put(&user.object.url, &update).await
+    }
+
+    /// Change the email address of this user.
+    pub async fn change_email(
+        &self,
+        email: &str,
+    ) -> Result<LinkedModel<UserResponse, RwAccess>, CubeError> {
+        let user = self.user().await?.ok_or(CubeError::UnknownUser)?;
+        let update = UserUpdate {
+            username: &user.object.username,
+            email,
+            password: None,
+        };
+        user.put(&user.object.url, &update).await
+    }
+
     /// Convert to a [RoAccess] client.
     pub fn into_ro(self) -> AuthedChrisClient<RoAccess> {
         AuthedChrisClient::<RoAccess> {
@@ -255,8 +461,179 @@ impl ChrisClient {
             url: self.url,
             username: self.username,
             links: self.links,
+            version: self.version,
             phantom: Default::default(),
             feeds_url: self.feeds_url,
+            replayable_upload_limit: self.replayable_upload_limit,
+            stream_client: self.stream_client,
+        }
+    }
+
+    /// Get [RwAccess] to an object which was obtained with [RoAccess].
+    ///
+    /// The object data is reused as-is and no HTTP request is made. Since the returned
+    /// [LinkedModel] makes requests using the authorization of this client, an object
+    /// whose URL is not on the same origin as the URL of this client is refused.
+    pub fn upgrade<T: Resource + DeserializeOwned>(
+        &self,
+        ro: LinkedModel<T, RoAccess>,
+    ) -> Result<LinkedModel<T, RwAccess>, ForeignObjectError> {
+        if !same_origin(ro.object.url().as_str(), self.url.as_str()) {
+            return Err(ForeignObjectError {
+                url: ro.object.url().clone(),
+                cube: self.url.clone(),
+            });
         }
+        Ok(LinkedModel {
+            object: ro.object,
+            client: self.client.clone(),
+            phantom: Default::default(),
+        })
+    }
+}
+
+/// Whether two URLs have the same scheme, host, and port.
+fn same_origin(a: &str, b: &str) -> bool {
+    match (reqwest::Url::parse(a), reqwest::Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+#[derive(Serialize)]
+struct TagRequest<'a> {
+    name: &'a str,
+    color: &'a str,
+}
+
+#[derive(Serialize)]
+struct UserUpdate<'a> {
+    username: &'a Username,
+    email: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+}
+
+/// Encode the same `multipart/form-data` request body as [ChrisClient::upload_stream]
+/// does for streamed uploads.
+fn multipart_body(boundary: &str, path: &str, filename: &str, data: &[u8]) -> Bytes {
+    // same escaping of file names as browsers, see
+    // https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#multipart-form-data
+    let filename = filename
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    let mut body = BytesMut::with_capacity(data.len() + 256);
+    body.put_slice(
+        format!(
+            "--{boundary}\r\n\
+             Content-Disposition: form-data; name=\"upload_path\"\r\n\r\n\
+             {path}\r\n\
+             --{boundary}\r\n\
+             Content-Disposition: form-data; name=\"fname\"; filename=\"{filename}\"\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.put_slice(data);
+    body.put_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCube;
+    use crate::Downloadable;
+    use reqwest_retry::policies::ExponentialBackoff;
+    use reqwest_retry::RetryTransientMiddleware;
+    use rstest::*;
+    use std::time::Duration;
+
+    /// Create a client of `mock` which retries failed requests twice.
+    async fn retrying_client(mock: &MockCube, replayable_upload_limit: u64) -> ChrisClient {
+        let policy = ExponentialBackoff::builder()
+            .retry_bounds(Duration::from_millis(1), Duration::from_millis(10))
+            .build_with_max_retries(2);
+        ChrisClient::build(mock.url().clone(), Username::from("chris"), "mock-token")
+            .unwrap()
+            .with(RetryTransientMiddleware::new_with_policy(policy))
+            .replayable_upload_limit(replayable_upload_limit)
+            .connect()
+            .await
+            .unwrap()
+    }
+
+    fn hello_stream() -> impl TryStream<Ok = Bytes, Error = std::io::Error> + Send + Sync {
+        futures::stream::iter([
+            Ok(Bytes::from_static(b"hel")),
+            Ok(Bytes::from_static(b"lo")),
+        ])
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_buffered_upload_is_retried() {
+        let mock = MockCube::start().await;
+        mock.fail_next_posts(1);
+        let client = retrying_client(&mock, 1024).await;
+        let uploaded = client
+            .upload_stream(hello_stream(), "hello.txt", "chris/uploads/hello.txt", 5)
+            .await
+            .unwrap();
+        assert_eq!(uploaded.fname().as_str(), "chris/uploads/hello.txt");
+        assert_eq!(uploaded.fsize(), 5);
+        assert_eq!(mock.requests_of("POST").len(), 2);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_streamed_upload_is_not_retried_by_middleware() {
+        let mock = MockCube::start().await;
+        mock.fail_next_posts(1);
+        let client = retrying_client(&mock, 4).await;
+        let result = client
+            .upload_stream(hello_stream(), "hello.txt", "chris/uploads/hello.txt", 5)
+            .await;
+        assert!(result.is_err());
+        assert_eq!(mock.requests_of("POST").len(), 1);
+    }
+
+    #[rstest]
+    fn test_multipart_body() {
+        let actual = multipart_body("xyz", "chris/uploads/a\"b.txt", "a\"b.txt", b"data");
+        let expected = "--xyz\r\n\
+            Content-Disposition: form-data; name=\"upload_path\"\r\n\r\n\
+            chris/uploads/a\"b.txt\r\n\
+            --xyz\r\n\
+            Content-Disposition: form-data; name=\"fname\"; filename=\"a%22b.txt\"\r\n\r\n\
+            data\r\n\
+            --xyz--\r\n";
+        assert_eq!(actual, expected.as_bytes())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_upgrade_makes_no_requests() {
+        let mock = MockCube::start().await;
+        mock.add_feed(mock.feed(1, "fetched earlier"));
+        let ro = mock.anon_client().await.get_feed(FeedId(1)).await.unwrap();
+        let client = mock.client("chris").await;
+        let before = mock.requests().len();
+        let rw = client.upgrade(ro).unwrap();
+        assert_eq!(mock.requests().len(), before);
+        assert_eq!(rw.object.name, "fetched earlier");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_upgrade_refuses_object_of_other_cube() {
+        let other = MockCube::start().await;
+        other.add_feed(other.feed(1, "elsewhere"));
+        let ro = other.anon_client().await.get_feed(FeedId(1)).await.unwrap();
+        let mock = MockCube::start().await;
+        let client = mock.client("chris").await;
+        let error = client.upgrade(ro).err().unwrap();
+        assert_eq!(error.url.as_str(), format!("{}1/", other.url()));
+        assert_eq!(&error.cube, mock.url());
     }
 }
@@ -1,6 +1,7 @@
 use super::access::{Access, RoAccess};
 use super::filebrowser::FileBrowser;
 use crate::errors::{check, CubeError};
+use crate::models::{BaseResponse, CubeLinks};
 use crate::search::*;
 use crate::types::{CubeUrl, FeedId, PipelineId, PluginId, PluginInstanceId};
 use crate::{FeedResponse, LinkedModel, PipelineResponse, PluginInstanceResponse, PluginResponse};
@@ -18,6 +19,11 @@ pub trait BaseChrisClient<A: Access> {
     /// Get the CUBE API URL.
     fn url(&self) -> &CubeUrl;
 
+    /// Get the version of CUBE, if it was advertised when the client connected.
+    fn server_version(&self) -> Option<&str> {
+        None
+    }
+
     /// Search for ChRIS plugins.
     fn plugin(&self) -> PluginSearchBuilder<A>;
 
@@ -71,6 +77,35 @@ pub trait BaseChrisClient<A: Access> {
     }
 }
 
+/// Name of the response header which might contain the version of CUBE.
+const VERSION_HEADER: &str = "X-CUBE-Version";
+
+/// What a client learns about CUBE when connecting to it.
+pub(crate) struct ConnectInfo {
+    pub links: CubeLinks,
+    pub version: Option<String>,
+}
+
+/// Get the API root, which has links to the collection APIs and possibly the version of CUBE,
+/// either in the response body or headers.
+pub(crate) async fn connect_to(
+    client: &ClientWithMiddleware,
+    url: &CubeUrl,
+) -> Result<ConnectInfo, CubeError> {
+    let res = client.get(url.as_str()).query(&LIMIT_ZERO).send().await?;
+    let res = check(res).await?;
+    let header_version = res
+        .headers()
+        .get(VERSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let base_response: BaseResponse = res.json().await?;
+    Ok(ConnectInfo {
+        links: base_response.collection_links,
+        version: base_response.version.or(header_version),
+    })
+}
+
 pub(crate) async fn fetch_id<A: Access, T: DeserializeOwned>(
     client: &ClientWithMiddleware,
     url: impl Display,
@@ -1,11 +1,12 @@
 use crate::errors::CubeError;
-use crate::search::{FeedSearchBuilder, PipelineSearchBuilder, PluginSearchBuilder};
-use crate::types::{CubeUrl, FeedId, PluginInstanceId, Username};
+use crate::search::{FeedSearchBuilder, GetOnlyError, PipelineSearchBuilder, PluginSearchBuilder};
+use crate::types::{CubeUrl, FeedId, PluginId, PluginInstanceId, Username};
 use crate::{
     AnonChrisClient, BaseChrisClient, ChrisClient, FeedResponse, FileBrowser, LinkedModel,
-    PluginInstanceResponse, RoAccess,
+    PluginInstanceResponse, PluginResponse, Resource, RoAccess, RwAccess,
 };
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
 
 /// Either an anonymous client or a logged in user. A shoddy workaround for combining how enums
 /// work and how [crate::RwAccess] and [RoAccess] could be represented using an enum.
@@ -50,6 +51,16 @@ impl EitherClient {
             _ => None,
         }
     }
+
+    /// Get [RwAccess] to an object which was obtained from this client, without re-fetching it.
+    /// Returns [None] if this client is anonymous, or if the object is from another _CUBE_.
+    /// See [ChrisClient::upgrade].
+    pub fn upgrade<T: Resource + DeserializeOwned>(
+        &self,
+        ro: LinkedModel<T, RoAccess>,
+    ) -> Option<LinkedModel<T, RwAccess>> {
+        self.logged_in_ref().and_then(|c| c.upgrade(ro).ok())
+    }
 }
 
 #[async_trait]
@@ -68,6 +79,13 @@ impl BaseChrisClient<RoAccess> for EitherClient {
         }
     }
 
+    fn server_version(&self) -> Option<&str> {
+        match self {
+            Self::Anon(c) => c.server_version(),
+            Self::LoggedIn(c) => c.server_version(),
+        }
+    }
+
     fn plugin(&self) -> PluginSearchBuilder<RoAccess> {
         match self {
             Self::Anon(c) => c.plugin(),
@@ -108,4 +126,41 @@ impl BaseChrisClient<RoAccess> for EitherClient {
             Self::LoggedIn(c) => c.get_plugin_instance(id).await.map(|p| p.into()),
         }
     }
+
+    /// Get a plugin by ID. Plugins are public, so this works whether or not
+    /// the client is logged in.
+    async fn get_plugin(
+        &self,
+        id: PluginId,
+    ) -> Result<LinkedModel<PluginResponse, RoAccess>, GetOnlyError> {
+        match self {
+            Self::Anon(c) => c.get_plugin(id).await,
+            Self::LoggedIn(c) => c.get_plugin(id).await.map(|p| p.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCube;
+    use rstest::*;
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    #[tokio::test]
+    async fn test_get_plugin(#[case] logged_in: bool) {
+        let mock = MockCube::start().await;
+        mock.add_plugin(mock.plugin(1, "pl-dircopy", "2.1.1"));
+        mock.add_plugin(mock.plugin(2, "pl-simpledsapp", "2.1.0"));
+        let client = if logged_in {
+            EitherClient::LoggedIn(mock.client("chris").await)
+        } else {
+            EitherClient::Anon(mock.anon_client().await)
+        };
+        let plugin = client.get_plugin(PluginId(2)).await.unwrap();
+        assert_eq!(plugin.object.name.as_str(), "pl-simpledsapp");
+        assert!(client.get_plugin(PluginId(3)).await.is_err());
+    }
 }
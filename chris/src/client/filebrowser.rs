@@ -5,10 +5,17 @@ use crate::errors::{check, CubeError};
 use crate::models::BasicFileResponse;
 use crate::search::Search;
 use crate::types::*;
+use async_stream::stream;
+use futures::{Stream, StreamExt};
 use serde::Deserialize;
 use serde::Serialize;
 use serde_with::json::JsonString;
 use serde_with::serde_as;
+use std::collections::HashSet;
+use time::OffsetDateTime;
+
+/// Maximum number of concurrent requests made by [FileBrowser::walk].
+const WALK_CONCURRENCY: usize = 4;
 
 /// A client for the _ChRIS_ filebrowser API.
 #[derive(Clone)]
@@ -33,11 +40,12 @@ impl FileBrowser {
     ///
     /// You can think of this method like the `ls` UNIX command.
     ///
-    /// Returns `None` if path not found.
+    /// Returns `None` if path not found. Files are not listed until
+    /// [FileBrowserDir::iter_files] is used.
     pub async fn readdir(
         &self,
         path: impl AsRef<str>,
-    ) -> Result<Option<FileBrowserEntry>, CubeError> {
+    ) -> Result<Option<FileBrowserDir>, CubeError> {
         let res = self
             .client
             .get(self.search.as_str())
@@ -51,7 +59,117 @@ impl FileBrowser {
             return Ok(None);
         }
         let dir = data.results.swap_remove(0);
-        Ok(Some(FileBrowserEntry::new(dir, self.client.clone())))
+        Ok(Some(FileBrowserDir::new(dir, self.client.clone())))
+    }
+
+    /// Recursively list the folders under `path`, breadth-first.
+    ///
+    /// Every folder is produced with its depth relative to `path`, which itself has
+    /// depth 0. Folders deeper than `depth_limit` are not listed. Files are not
+    /// listed, call [FileBrowserDir::iter_files] on the folders you are interested in.
+    ///
+    /// The stream is empty if `path` is not found. If a folder cannot be listed,
+    /// its error is produced and the walk continues with the other folders.
+    pub fn walk(
+        &self,
+        path: impl AsRef<str>,
+        depth_limit: Option<usize>,
+    ) -> impl Stream<Item = Result<FileBrowserWalkEntry, CubeError>> {
+        let filebrowser = self.clone();
+        let root = normalize_path(path.as_ref());
+        stream! {
+            let mut visited: HashSet<String> = HashSet::new();
+            let mut level: Vec<String> = root.into_iter().collect();
+            let mut depth = 0;
+            visited.extend(level.iter().cloned());
+            while !level.is_empty() {
+                let mut next_level = Vec::new();
+                let mut listings = futures::stream::iter(level)
+                    .map(|path| {
+                        let filebrowser = &filebrowser;
+                        async move { (filebrowser.readdir(&path).await, path) }
+                    })
+                    .buffered(WALK_CONCURRENCY);
+                while let Some((result, path)) = listings.next().await {
+                    let dir = match result {
+                        Ok(Some(dir)) => dir,
+                        // folder was deleted since its parent was listed
+                        Ok(None) => continue,
+                        Err(e) => {
+                            yield Err(e);
+                            continue;
+                        }
+                    };
+                    if depth_limit.is_none_or(|limit| depth < limit) {
+                        let subfolders = dir
+                            .subfolders()
+                            .iter()
+                            .filter_map(|name| normalize_path(&format!("{}/{}", path, name)))
+                            .filter(|subfolder| subfolder != &path);
+                        for subfolder in subfolders {
+                            if visited.insert(subfolder.clone()) {
+                                next_level.push(subfolder);
+                            }
+                        }
+                    }
+                    yield Ok(FileBrowserWalkEntry {
+                        depth,
+                        path: FileBrowserPath::new(path),
+                        dir,
+                    });
+                }
+                level = next_level;
+                depth += 1;
+            }
+        }
+    }
+}
+
+/// Remove empty and `.` components from a path. Returns `None` if it contains `..`,
+/// so that weird folder names cannot make [FileBrowser::walk] go in circles.
+fn normalize_path(path: &str) -> Option<String> {
+    let mut components = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return None,
+            _ => components.push(component),
+        }
+    }
+    Some(components.join("/"))
+}
+
+/// Former name of [FileBrowserDir].
+#[deprecated(since = "0.5.0", note = "renamed to `FileBrowserDir`")]
+pub type FileBrowserEntry = FileBrowserDir;
+
+/// A folder found by [FileBrowser::walk].
+pub struct FileBrowserWalkEntry {
+    depth: usize,
+    path: FileBrowserPath,
+    dir: FileBrowserDir,
+}
+
+impl FileBrowserWalkEntry {
+    /// Depth of this folder relative to the path being walked.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Full path of this folder.
+    pub fn path(&self) -> &FileBrowserPath {
+        &self.path
+    }
+
+    /// Get the listing of this folder.
+    pub fn dir(&self) -> &FileBrowserDir {
+        &self.dir
+    }
+
+    /// Iterate over files immediately under this folder. No request is made until
+    /// the search is used.
+    pub fn iter_files(&self) -> Search<BasicFileResponse, RoAccess> {
+        self.dir.iter_files()
     }
 }
 
@@ -61,21 +179,29 @@ struct FileBrowserSearch {
     // count: u8,
     // next: Option<String>,
     // previous: Option<String>,
-    results: Vec<FileBrowserDir>,
+    results: Vec<FileBrowserDirResponse>,
 }
 
 #[serde_as]
 #[derive(Deserialize)]
-struct FileBrowserDir {
+struct FileBrowserDirResponse {
     path: FileBrowserPath,
     #[serde_as(as = "JsonString")]
     subfolders: Vec<String>,
     // url: String,
     files: Option<CollectionUrl>,
+    #[serde(default, with = "time::serde::iso8601::option")]
+    creation_date: Option<OffsetDateTime>,
+    #[serde(default)]
+    owner_username: Option<Username>,
 }
 
-/// A filebrowser API response, which contains a listing for a _ChRIS_ file path.
-pub struct FileBrowserEntry {
+/// The listing of a folder of _ChRIS_ storage, from the filebrowser API.
+///
+/// Subfolders are listed in the filebrowser API response. The files immediately under
+/// the folder can be many, so they are not listed until [FileBrowserDir::iter_files]
+/// is used, which gets them page by page.
+pub struct FileBrowserDir {
     client: reqwest_middleware::ClientWithMiddleware,
     path: FileBrowserPath,
     subfolders: Vec<String>,
@@ -83,16 +209,20 @@ pub struct FileBrowserEntry {
     /// API Url for files immediately under this path.
     /// Is `None` if path is `""` (root).
     files: Option<CollectionUrl>,
+    creation_date: Option<OffsetDateTime>,
+    owner_username: Option<Username>,
 }
 
-impl FileBrowserEntry {
-    fn new(dir: FileBrowserDir, client: reqwest_middleware::ClientWithMiddleware) -> Self {
-        FileBrowserEntry {
+impl FileBrowserDir {
+    fn new(dir: FileBrowserDirResponse, client: reqwest_middleware::ClientWithMiddleware) -> Self {
+        FileBrowserDir {
             client,
             path: dir.path,
             subfolders: dir.subfolders,
             // url: dir.url,
             files: dir.files,
+            creation_date: dir.creation_date,
+            owner_username: dir.owner_username,
         }
     }
 
@@ -106,6 +236,11 @@ impl FileBrowserEntry {
         &self.subfolders
     }
 
+    /// Get the number of subfolders immediately under this path.
+    pub fn subfolder_count(&self) -> usize {
+        self.subfolders.len()
+    }
+
     /// Get absolute paths of subfolders.
     pub fn absolute_subfolders(&self) -> impl Iterator<Item = FileBrowserPath> + '_ {
         self.subfolders()
@@ -114,7 +249,23 @@ impl FileBrowserEntry {
             .map(FileBrowserPath::new)
     }
 
-    /// Iterate over files.
+    /// Get the creation date of this folder. Not reported by old versions of _CUBE_.
+    pub fn creation_date(&self) -> Option<OffsetDateTime> {
+        self.creation_date
+    }
+
+    /// Get the username of the owner of this folder. Not reported by old versions of _CUBE_.
+    pub fn owner_username(&self) -> Option<&Username> {
+        self.owner_username.as_ref()
+    }
+
+    /// Get the number of files immediately under this path, not counting subfolders.
+    pub async fn file_count(&self) -> Result<usize, CubeError> {
+        self.iter_files().get_count().await
+    }
+
+    /// Iterate over files immediately under this path. Every page of files is
+    /// requested, unless limited by [Search::max_items].
     pub fn iter_files(&self) -> Search<BasicFileResponse, RoAccess> {
         if let Some(url) = &self.files {
             Search::collection(self.client.clone(), url.clone())
@@ -128,3 +279,94 @@ impl FileBrowserEntry {
 struct FileBrowserQuery<'a> {
     path: &'a str,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Downloadable;
+    use crate::testing::MockCube;
+    use crate::BaseChrisClient;
+    use futures::TryStreamExt;
+    use rstest::*;
+    use time::macros::datetime;
+
+    #[rstest]
+    #[case("chris/feed_1", Some("chris/feed_1"))]
+    #[case("/chris//feed_1/", Some("chris/feed_1"))]
+    #[case("chris/./feed_1", Some("chris/feed_1"))]
+    #[case("chris/feed_1/..", None)]
+    #[case("", Some(""))]
+    fn test_normalize_path(#[case] path: &str, #[case] expected: Option<&str>) {
+        assert_eq!(normalize_path(path).as_deref(), expected)
+    }
+
+    #[rstest]
+    fn test_deserialize_old_dir() {
+        let data = r#"{
+            "path": "chris/feed_12",
+            "subfolders": "[\"pl-dircopy_24\"]",
+            "url": "http://localhost:8000/api/v1/filebrowser/chris/feed_12/",
+            "files": "http://localhost:8000/api/v1/filebrowser/chris/feed_12/files/"
+        }"#;
+        let dir: FileBrowserDirResponse = serde_json::from_str(data).unwrap();
+        assert_eq!(dir.subfolders, vec!["pl-dircopy_24".to_string()]);
+        assert_eq!(dir.creation_date, None);
+        assert_eq!(dir.owner_username, None);
+    }
+
+    #[rstest]
+    fn test_deserialize_dir_with_owner() {
+        let data = r#"{
+            "path": "sandip117/feed_45",
+            "subfolders": "[\"pl-dircopy_214\"]",
+            "files": "https://cube.chrisproject.org/api/v1/filebrowser/sandip117/feed_45/files/",
+            "creation_date": "2024-02-28T05:41:31.825161-05:00",
+            "owner_username": "sandip117"
+        }"#;
+        let dir: FileBrowserDirResponse = serde_json::from_str(data).unwrap();
+        assert_eq!(
+            dir.creation_date,
+            Some(datetime!(2024-02-28 05:41:31.825161 -5))
+        );
+        assert_eq!(dir.owner_username, Some(Username::from("sandip117")));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_readdir_paginates_files() {
+        let mock = MockCube::start().await;
+        mock.set_max_limit(10);
+        for i in 0..25 {
+            mock.add_file(format!("chris/uploads/many/{i:02}.txt"), "hello");
+        }
+        mock.add_file("chris/uploads/many/sub/a.txt", "hello");
+        let chris = mock.client("chris").await;
+        let dir = chris
+            .filebrowser()
+            .readdir("chris/uploads/many")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(dir.subfolders(), &vec!["sub".to_string()]);
+        assert_eq!(dir.subfolder_count(), 1);
+        assert_eq!(dir.file_count().await.unwrap(), 25);
+
+        let before = mock.requests().len();
+        let files: Vec<_> = dir.iter_files().stream().try_collect().await.unwrap();
+        assert_eq!(mock.requests().len() - before, 3, "should request 3 pages");
+        let names: Vec<_> = files.iter().map(|f| f.fname().to_string()).collect();
+        let expected: Vec<_> = (0..25)
+            .map(|i| format!("chris/uploads/many/{i:02}.txt"))
+            .collect();
+        assert_eq!(names, expected);
+
+        let some: Vec<_> = dir
+            .iter_files()
+            .max_items(12)
+            .stream()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(some.len(), 12);
+    }
+}
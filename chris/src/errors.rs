@@ -13,14 +13,51 @@ pub enum InvalidCubeUrl {
 
 aliri_braid::from_infallible!(InvalidCubeUrl);
 
+/// Error when parsing a [crate::types::CpuLimit] or [crate::types::MemoryLimit].
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum InvalidResourceLimit {
+    #[error("\"{0}\" does not start with a number")]
+    NotANumber(String),
+
+    #[error("unknown unit \"{unit}\", expected {expected}")]
+    UnknownUnit {
+        unit: String,
+        expected: &'static str,
+    },
+
+    #[error("a unit is required, e.g. \"{0}Mi\" or \"{0}Gi\"")]
+    MissingUnit(String),
+
+    #[error("{0} must be greater than zero")]
+    Zero(&'static str),
+
+    #[error("{what} must be a whole number of {unit}")]
+    Fractional {
+        what: &'static str,
+        unit: &'static str,
+    },
+
+    #[error("{0} is too large")]
+    TooLarge(&'static str),
+}
+
+/// Error when parsing a string which is not a known plugin instance status.
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("unknown plugin instance status: \"{0}\"")]
+pub struct InvalidStatus(pub String);
+
 /// Errors representing failed interactions with CUBE.
 #[derive(thiserror::Error, Debug)]
 pub enum CubeError {
     /// Error response with an explanation from CUBE.
-    #[error("({status:?} {reason:?}): {text}")]
+    ///
+    /// Django REST Framework error messages in the response body are displayed
+    /// one per line, e.g. `title: This field may not be blank.`
+    #[error("{}", format_error_response(*.status, .reason, .text))]
     Error {
         status: StatusCode,
         reason: &'static str,
+        /// Raw body of the response.
         text: String,
         source: reqwest::Error,
     },
@@ -34,6 +71,21 @@ pub enum CubeError {
     /// Note about anyhow: see <https://github.com/TrueLayer/reqwest-middleware/issues/119>
     #[error(transparent)]
     Middleware(anyhow::Error),
+
+    /// CUBE does not link to the user of a client whose username is unknown.
+    #[error("CUBE did not say which user the token belongs to")]
+    UnknownUser,
+
+    /// The password which was given to confirm a change to an account is incorrect.
+    #[error("The password is incorrect")]
+    IncorrectPassword,
+
+    /// CUBE gave a `next` link for a page of results which is not a valid URL.
+    #[error("Invalid URL of the next page of results: \"{url}\"")]
+    InvalidNextUrl {
+        url: String,
+        source: url::ParseError,
+    },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -63,6 +115,130 @@ pub(crate) async fn check(res: reqwest::Response) -> Result<reqwest::Response, C
     }
 }
 
+/// Display an error response, with its body formatted by [error_messages] if possible.
+fn format_error_response(status: StatusCode, reason: &str, text: &str) -> String {
+    let prefix = format!("({} {:?})", status.as_u16(), reason);
+    match error_messages(text).as_deref() {
+        Some([message]) => format!("{}: {}", prefix, message),
+        Some(messages) => format!("{}:\n  {}", prefix, messages.join("\n  ")),
+        None => format!("{}: {}", prefix, text),
+    }
+}
+
+/// Parse the messages of a Django REST Framework error response body.
+///
+/// DRF errors are JSON objects mapping field names to lists of messages, where
+/// the field names `detail` and `non_field_errors` are for errors about the whole
+/// request. Messages about a field are prefixed by its name.
+///
+/// Returns `None` if the body does not contain any messages.
+fn error_messages(text: &str) -> Option<Vec<String>> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    let mut messages = Vec::new();
+    collect_messages(&value, None, &mut messages);
+    if messages.is_empty() {
+        None
+    } else {
+        Some(messages)
+    }
+}
+
+fn collect_messages(value: &serde_json::Value, field: Option<&str>, messages: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(message) => messages.push(match field {
+            Some(field) => format!("{}: {}", field, message),
+            None => message.to_string(),
+        }),
+        serde_json::Value::Array(values) => {
+            for value in values {
+                collect_messages(value, field, messages);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for (name, value) in fields {
+                let name = match (field, name.as_str()) {
+                    (_, "detail" | "non_field_errors") => field.map(|f| f.to_string()),
+                    (Some(field), name) => Some(format!("{}.{}", field, name)),
+                    (None, name) => Some(name.to_string()),
+                };
+                collect_messages(value, name.as_deref(), messages);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Error when trying to give [crate::RwAccess] to an object of a different _CUBE_
+/// than the client, see [crate::ChrisClient::upgrade].
+#[derive(thiserror::Error, Debug)]
+#[error("{url} is not from the CUBE at {cube}")]
+pub struct ForeignObjectError {
+    pub url: crate::types::ItemUrl,
+    pub cube: crate::types::CubeUrl,
+}
+
+/// Error when trying to stop sharing a feed with a user.
+#[derive(thiserror::Error, Debug)]
+pub enum UnshareError {
+    #[error(transparent)]
+    CUBEError(#[from] CubeError),
+
+    /// The feed is not shared with the user.
+    #[error("feed is not shared with \"{0}\"")]
+    NotShared(String),
+
+    /// Old versions of _CUBE_ cannot stop sharing a feed.
+    #[error("this version of CUBE does not support unsharing feeds")]
+    Unsupported,
+}
+
+/// Error when adding or removing a tag of a feed.
+#[derive(thiserror::Error, Debug)]
+pub enum TaggingError {
+    #[error(transparent)]
+    CUBEError(#[from] CubeError),
+
+    /// The feed does not have the tag.
+    #[error("feed does not have tag {}", .0 .0)]
+    NotTagged(crate::types::TagId),
+
+    /// Old versions of _CUBE_ do not link to the tags of a feed which can be changed.
+    #[error("this version of CUBE does not support tagging feeds")]
+    Unsupported,
+}
+
+/// Error when running a pipeline with per-piping options.
+#[derive(thiserror::Error, Debug)]
+pub enum WorkflowError {
+    #[error(transparent)]
+    CUBEError(#[from] CubeError),
+
+    /// The pipeline does not have a piping with the given title.
+    #[error("pipeline has no piping titled \"{0}\"")]
+    UnknownPiping(String),
+
+    /// The plugin of a piping does not have the given parameter.
+    #[error("piping \"{piping}\" has no parameter \"{name}\"")]
+    UnknownParameter { piping: String, name: String },
+
+    /// A parameter value cannot be converted to the type of the parameter.
+    #[error("value \"{value}\" of parameter \"{name}\" of piping \"{piping}\" is not of type {expected:?}")]
+    InvalidValue {
+        piping: String,
+        name: String,
+        value: String,
+        expected: crate::types::PluginParameterType,
+    },
+
+    /// The plugin of a piping cannot run on the given compute resource.
+    #[error("plugin of piping \"{piping}\" cannot run on compute resource \"{name}\"")]
+    UnknownComputeResource { piping: String, name: String },
+
+    /// The plugin of a piping cannot run on any compute resource.
+    #[error("plugin of piping \"{0}\" is not available on any compute resource")]
+    NoComputeResource(String),
+}
+
 /// An error which might occur while uploading or downloading files.
 #[derive(thiserror::Error, Debug)]
 pub enum FileIOError {
@@ -106,3 +282,66 @@ impl From<reqwest_middleware::Error> for CubeError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(
+        r#"{"title":["This field may not be blank."]}"#,
+        Some(vec!["title: This field may not be blank."])
+    )]
+    #[case(
+        r#"{"detail":"You do not have permission to perform this action."}"#,
+        Some(vec!["You do not have permission to perform this action."])
+    )]
+    #[case(
+        r#"{"non_field_errors":["The fields feed, name must make a unique set."]}"#,
+        Some(vec!["The fields feed, name must make a unique set."])
+    )]
+    #[case(
+        r#"{"name":["Required."],"cpu_limit":["Invalid format.","Too high."]}"#,
+        Some(vec!["cpu_limit: Invalid format.", "cpu_limit: Too high.", "name: Required."])
+    )]
+    #[case(
+        r#"{"plugin_tree":{"plugin_parameter_defaults":["Invalid default."],"non_field_errors":["Invalid tree."]}}"#,
+        Some(vec![
+            "plugin_tree: Invalid tree.",
+            "plugin_tree.plugin_parameter_defaults: Invalid default."
+        ])
+    )]
+    #[case(r#"["Invalid value."]"#, Some(vec!["Invalid value."]))]
+    #[case(r#"{}"#, None)]
+    #[case("<h1>Server Error (500)</h1>", None)]
+    fn test_error_messages(#[case] text: &str, #[case] expected: Option<Vec<&str>>) {
+        let expected = expected.map(|v| v.into_iter().map(String::from).collect());
+        assert_eq!(error_messages(text), expected)
+    }
+
+    #[rstest]
+    #[case(
+        StatusCode::BAD_REQUEST,
+        r#"{"title":["This field may not be blank."]}"#,
+        r#"(400 "Bad Request"): title: This field may not be blank."#
+    )]
+    #[case(
+        StatusCode::BAD_REQUEST,
+        r#"{"title":["This field may not be blank."],"name":["Required."]}"#,
+        "(400 \"Bad Request\"):\n  name: Required.\n  title: This field may not be blank."
+    )]
+    #[case(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "<h1>Server Error (500)</h1>",
+        r#"(500 "Internal Server Error"): <h1>Server Error (500)</h1>"#
+    )]
+    fn test_format_error_response(
+        #[case] status: StatusCode,
+        #[case] text: &str,
+        #[case] expected: &str,
+    ) {
+        let reason = status.canonical_reason().unwrap();
+        assert_eq!(format_error_response(status, reason, text), expected)
+    }
+}
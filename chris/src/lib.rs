@@ -104,26 +104,38 @@
 //! Instead, a [LazyLinkedModel] is returned, which works the same as a [LinkedModel] but
 //! is missing the actual object data. The object data can be obtained by calling
 //! [LazyLinkedModel::get].
+//!
+//! ### Testing
+//!
+//! With the `testing` feature, the [testing] module provides a mock _CUBE_ server,
+//! so that programs using this crate can be tested without a real _CUBE_.
 
 mod client;
 mod models;
 mod requests;
 
 // pub mod auth;
-pub mod errors;
-// pub mod pipeline;
 mod account;
+pub mod errors;
+pub mod pipeline;
 pub mod search;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod types;
 
 pub use account::Account;
 pub use client::access::{Access, RoAccess, RwAccess};
 pub use client::anon::AnonChrisClient;
-pub use client::authed::{ChrisClient, AuthedChrisClient};
+pub use client::authed::{AuthedChrisClient, ChrisClient};
 pub use client::base::BaseChrisClient;
 pub use client::either::{EitherClient, RoClient};
-pub use client::filebrowser::{FileBrowser, FileBrowserEntry};
+#[allow(deprecated)]
+pub use client::filebrowser::FileBrowserEntry;
+pub use client::filebrowser::{FileBrowser, FileBrowserDir, FileBrowserWalkEntry};
 pub use models::*;
 
 // re-export
 pub use reqwest;
+
+/// Version of this crate.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,12 +1,15 @@
 //! Definitions of structs describing response data from the *CUBE* API.
 
 use crate::types::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 #[derive(Deserialize)]
 pub(crate) struct BaseResponse {
     pub collection_links: CubeLinks,
+    /// Version of CUBE, if advertised.
+    #[serde(default)]
+    pub version: Option<String>,
     // unused
     // /// Number of feeds. Is `None` if client is not logged in.
     // pub count: Option<u32>,
@@ -40,7 +43,19 @@ pub(crate) struct CubeLinks {
     pub admin: Option<CollectionUrl>,
 }
 
-#[derive(Debug, Deserialize)]
+/// A _ChRIS_ user account.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserResponse {
+    pub url: ItemUrl,
+    pub id: UserId,
+    pub username: Username,
+    pub email: String,
+    /// Whether the user is an administrator. Not advertised by older versions of _CUBE_.
+    #[serde(default)]
+    pub is_staff: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PipelineResponse {
     pub url: ItemUrl,
     pub id: PipelineId,
@@ -61,7 +76,38 @@ pub struct PipelineResponse {
     pub workflows: CollectionUrl,
 }
 
-#[derive(Debug, Deserialize)]
+/// A piping of a pipeline, i.e. a plugin and which piping comes before it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PipingResponse {
+    pub url: ItemUrl,
+    pub id: PipingId,
+    pub title: String,
+    pub previous_id: Option<PipingId>,
+    pub plugin_id: PluginId,
+    pub plugin_name: PluginName,
+    pub plugin_version: PluginVersion,
+    pub pipeline_id: PipelineId,
+}
+
+/// The value of a plugin parameter which a piping of a pipeline is run with by default.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PipingParameterResponse {
+    pub url: ItemUrl,
+    pub id: PipingParameterId,
+    pub param_name: String,
+    pub param_id: PluginParameterId,
+    /// `None` for versions of _CUBE_ which do not say.
+    #[serde(rename = "type", default)]
+    pub parameter_type: Option<PluginParameterType>,
+    /// `None` if the parameter is optional and has no default.
+    pub value: Option<PluginParameterValue>,
+    pub plugin_piping_id: PipingId,
+    pub plugin_piping_title: String,
+    pub plugin_name: PluginName,
+    pub plugin_version: PluginVersion,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PluginResponse {
     pub url: ItemUrl,
     pub id: PluginId,
@@ -90,8 +136,12 @@ pub struct PluginResponse {
     pub max_cpu_limit: u32,
     pub min_memory_limit: u32,
     pub max_memory_limit: u32,
-    pub min_gpu_limit: u32,
-    pub max_gpu_limit: u32,
+    /// Not reported by old versions of _CUBE_.
+    #[serde(default)]
+    pub min_gpu_limit: Option<u32>,
+    /// Not reported by old versions of _CUBE_.
+    #[serde(default)]
+    pub max_gpu_limit: Option<u32>,
     pub meta: ItemUrl,
     pub parameters: CollectionUrl,
     pub instances: CollectionUrl,
@@ -99,7 +149,9 @@ pub struct PluginResponse {
 }
 
 /// _CUBE_ feed data.
-#[derive(Deserialize)]
+///
+/// Fields which are `Option` were added in newer versions of _CUBE_.
+#[derive(Serialize, Deserialize, Clone)]
 pub struct FeedResponse {
     pub url: ItemUrl,
     pub name: String,
@@ -109,21 +161,31 @@ pub struct FeedResponse {
     pub creation_date: OffsetDateTime,
     #[serde(with = "time::serde::iso8601")]
     pub modification_date: OffsetDateTime,
-    pub public: bool,
+    /// Public feeds were added in _CUBE_ version 4.
+    #[serde(default)]
+    pub public: Option<bool>,
     pub created_jobs: u32,
     pub waiting_jobs: u32,
     pub scheduled_jobs: u32,
     pub started_jobs: u32,
-    pub registering_jobs: u32,
+    #[serde(default)]
+    pub registering_jobs: Option<u32>,
     pub finished_jobs: u32,
     pub errored_jobs: u32,
     pub cancelled_jobs: u32,
     pub owner: Vec<ItemUrl>,
     pub note: ItemUrl,
     pub tags: CollectionUrl,
+    /// Taggings of the feed, i.e. the links between the feed and its tags.
+    #[serde(default)]
+    pub taggings: Option<CollectionUrl>,
     pub comments: CollectionUrl,
     pub files: CollectionUrl,
     pub plugin_instances: CollectionUrl,
+    /// Users who the feed is shared with. Added in _CUBE_ version 6. Before then,
+    /// feeds were shared by adding users to `owner`.
+    #[serde(default)]
+    pub user_permissions: Option<CollectionUrl>,
 }
 
 #[derive(Deserialize)]
@@ -135,13 +197,28 @@ pub struct NoteResponse {
     pub feed: CollectionUrl,
 }
 
+/// Permission of a user to access a feed which is shared with them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeedUserPermissionResponse {
+    pub url: ItemUrl,
+    pub id: u32,
+    pub feed_id: FeedId,
+    pub user_id: UserId,
+    pub user_username: Username,
+}
+
 impl FeedResponse {
     pub fn pending_jobs(&self) -> u32 {
         self.created_jobs + self.waiting_jobs + self.scheduled_jobs
     }
 
     pub fn running_jobs(&self) -> u32 {
-        self.started_jobs + self.registering_jobs
+        self.started_jobs + self.registering_jobs.unwrap_or_default()
+    }
+
+    /// Whether the feed is public. Feeds of old versions of _CUBE_ are never public.
+    pub fn is_public(&self) -> bool {
+        self.public.unwrap_or_default()
     }
 
     pub fn unfinished_jobs(&self) -> u32 {
@@ -155,9 +232,42 @@ impl FeedResponse {
     pub fn has_unfinished_jobs(&self) -> bool {
         self.unfinished_jobs() > 0
     }
+
+    /// Total number of plugin instances in this feed.
+    pub fn num_plugin_instances(&self) -> u32 {
+        self.unfinished_jobs() + self.finished_jobs + self.errored_jobs + self.cancelled_jobs
+    }
 }
 
-#[derive(Deserialize, Debug)]
+/// A tag which a user can put on their feeds.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TagResponse {
+    pub url: ItemUrl,
+    pub id: TagId,
+    pub name: String,
+    pub owner_username: Username,
+    pub color: String,
+    pub feeds: CollectionUrl,
+    pub taggings: CollectionUrl,
+}
+
+/// A tag of a feed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaggingResponse {
+    pub url: ItemUrl,
+    pub id: TaggingId,
+    pub tag_id: TagId,
+    pub tag_name: String,
+    pub feed_id: FeedId,
+    pub feed_name: String,
+    pub tag: ItemUrl,
+    pub feed: ItemUrl,
+}
+
+/// _CUBE_ plugin instance data.
+///
+/// Fields which are `Option` were added in newer versions of _CUBE_.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PluginInstanceResponse {
     pub url: ItemUrl,
     pub id: PluginInstanceId,
@@ -176,17 +286,24 @@ pub struct PluginInstanceResponse {
     pub start_date: OffsetDateTime,
     #[serde(with = "time::serde::iso8601")]
     pub end_date: OffsetDateTime,
-    pub output_path: String,
+    /// Path of the output folder, e.g. `chris/feed_1/pl-dircopy_1/data`.
+    #[serde(default)]
+    pub output_path: Option<String>,
     pub status: Status,
-    pub summary: String,
-    pub raw: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub raw: Option<String>,
     pub owner_username: Username,
     pub cpu_limit: u32,
     pub memory_limit: u32,
     pub number_of_workers: u32,
     pub gpu_limit: u32,
-    pub size: u64,
-    pub error_code: String,
+    /// Total size of the output files in bytes.
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub error_code: Option<String>,
     pub previous: Option<ItemUrl>,
     pub previous_id: Option<PluginInstanceId>,
     pub feed: ItemUrl,
@@ -200,7 +317,8 @@ pub struct PluginInstanceResponse {
 impl PluginInstanceResponse {
     pub(crate) fn logs(&self) -> String {
         // note: summary object is empty for cancelled plugin instances
-        if let Ok(summary) = serde_json::from_str::<PluginInstanceSummary>(&self.summary) {
+        let summary = self.summary.as_deref().unwrap_or_default();
+        if let Ok(summary) = serde_json::from_str::<PluginInstanceSummary>(summary) {
             summary.compute.return_status.job_logs
         } else {
             "".to_string()
@@ -248,7 +366,7 @@ pub struct PluginInstanceParameterResponse {
     pub plugin_param: ItemUrl,
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PluginParameter {
     pub url: ItemUrl,
     pub id: PluginParameterId,
@@ -263,6 +381,9 @@ pub struct PluginParameter {
     pub help: String,
     pub ui_exposed: bool,
     pub plugin: ItemUrl,
+    /// Values which the parameter is allowed to have. Only declared by some plugins.
+    #[serde(default)]
+    pub choices: Option<Vec<PluginParameterValue>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -284,10 +405,29 @@ pub struct WorkflowResponse {
     pub errored_jobs: u32,
     pub cancelled_jobs: u32,
     pub plugin_instances: CollectionUrl,
+    /// Comma-separated IDs of the plugin instances created by the workflow.
+    /// Not reported by every version of _CUBE_.
+    #[serde(default)]
+    pub created_plugin_inst_ids: Option<String>,
+}
+
+impl WorkflowResponse {
+    /// Get the IDs of the plugin instances created by this workflow, if reported by _CUBE_.
+    pub fn created_plugin_instance_ids(&self) -> Option<Vec<PluginInstanceId>> {
+        self.created_plugin_inst_ids.as_deref().map(|ids| {
+            ids.split(',')
+                .filter_map(|id| id.trim().parse().ok())
+                .map(PluginInstanceId)
+                .collect()
+        })
+    }
 }
 
 /// _CUBE_ compute resource data.
-#[derive(Debug, Deserialize)]
+///
+/// Resource limits (CPU, memory, GPUs, workers) are properties of plugins,
+/// see [PluginResponse].
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ComputeResourceResponse {
     pub url: ItemUrl,
     pub id: ComputeResourceId,
@@ -302,3 +442,41 @@ pub struct ComputeResourceResponse {
     pub description: String,
     pub max_job_exec_seconds: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(None, None)]
+    #[case(Some("5,6,8"), Some(vec![5, 6, 8]))]
+    #[case(Some(""), Some(vec![]))]
+    fn test_created_plugin_instance_ids(
+        #[case] created: Option<&str>,
+        #[case] expected: Option<Vec<u32>>,
+    ) {
+        let workflow: WorkflowResponse = serde_json::from_value(serde_json::json!({
+            "url": "https://cube.example.org/api/v1/pipelines/workflows/3/",
+            "id": 3,
+            "title": "example",
+            "creation_date": "2024-03-01T14:06:10.372393-05:00",
+            "pipeline_id": 2,
+            "pipeline_name": "Example pipeline",
+            "owner_username": "alice",
+            "pipeline": "https://cube.example.org/api/v1/pipelines/2/",
+            "created_jobs": 0,
+            "waiting_jobs": 0,
+            "scheduled_jobs": 0,
+            "started_jobs": 0,
+            "registering_jobs": 0,
+            "errored_jobs": 0,
+            "cancelled_jobs": 0,
+            "plugin_instances": "https://cube.example.org/api/v1/pipelines/workflows/3/plugininstances/",
+            "created_plugin_inst_ids": created
+        }))
+        .unwrap();
+        let expected = expected.map(|ids| ids.into_iter().map(PluginInstanceId).collect());
+        assert_eq!(workflow.created_plugin_instance_ids(), expected)
+    }
+}
@@ -1,8 +1,8 @@
 use crate::models::Downloadable;
 use crate::search::Search;
 use crate::types::*;
-use crate::Access;
-use serde::Deserialize;
+use crate::{Access, LinkedModel};
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 /// The common data from any response object, and what comes back from the filebrowser API.
@@ -11,6 +11,12 @@ pub struct BasicFileResponse {
     file_resource: FileResourceUrl,
     fname: FileResourceFname,
     fsize: u64,
+    /// Not included by the filebrowser API of old versions of _CUBE_.
+    #[serde(default, with = "time::serde::iso8601::option")]
+    pub creation_date: Option<OffsetDateTime>,
+    /// Not included by old versions of _CUBE_.
+    #[serde(default)]
+    pub owner_username: Option<Username>,
 }
 
 /// A file created by a plugin instance.
@@ -41,8 +47,8 @@ pub struct FileUploadResponse {
     pub owner: Username,
 }
 
-/// A PACSFile.
-#[derive(Debug, Deserialize)]
+/// A PACSFile, i.e. a DICOM file retrieved from a PACS, with its DICOM metadata.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PacsFileResponse {
     pub url: ItemUrl,
     pub id: PacsFileId,
@@ -147,8 +153,64 @@ impl<A: Access> Search<FeedFileResponse, A> {
     }
 }
 
+impl<A: Access> Search<FileUploadResponse, A> {
+    /// Produce [BasicFileResponse] instead of [FileUploadResponse]
+    pub fn basic(self) -> Search<BasicFileResponse, A> {
+        self.downgrade()
+    }
+}
+
+impl<A: Access> Search<PacsFileResponse, A> {
+    /// Produce [BasicFileResponse] instead of [PacsFileResponse]
+    pub fn basic(self) -> Search<BasicFileResponse, A> {
+        self.downgrade()
+    }
+}
+
+/// A file uploaded to userfiles.
+pub type UserFile<A> = LinkedModel<FileUploadResponse, A>;
+
 impl From<BasicFileResponse> for FileResourceFname {
     fn from(value: BasicFileResponse) -> Self {
         value.fname
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn test_deserialize_pacs_file() {
+        let data = r#"{
+            "url": "https://cube.example.org/api/v1/pacsfiles/7/",
+            "id": 7,
+            "creation_date": "2024-03-01T14:06:10.372393-05:00",
+            "fname": "SERVICES/PACS/MINICHRISORTHANC/1449c1d-anonymized-20090701/MR-Brain_w_o_Contrast-98edede8b2-20130308/00005-SAG_MPRAGE_220_FOV-a27cf06/0001-1.3.12.2.1107.5.2.19.45152.2013030808110261698786039.dcm",
+            "fsize": 307062,
+            "file_resource": "https://cube.example.org/api/v1/pacsfiles/7/0001.dcm",
+            "pacs_identifier": "MINICHRISORTHANC",
+            "PatientID": "1449c1d",
+            "PatientName": "anonymized",
+            "PatientBirthDate": "2009-07-01",
+            "PatientAge": 1351,
+            "PatientSex": "M",
+            "StudyDate": "2013-03-08",
+            "AccessionNumber": "98edede8b2",
+            "Modality": "MR",
+            "ProtocolName": "SAG MPRAGE 220 FOV",
+            "StudyInstanceUID": "1.2.840.113845.11.1000000001785349915.20130308061609.6346698",
+            "StudyDescription": "MR-Brain w/o Contrast",
+            "SeriesInstanceUID": "1.3.12.2.1107.5.2.19.45152.2013030808061520200285270.0.0.0",
+            "SeriesDescription": null
+        }"#;
+        let file: PacsFileResponse = serde_json::from_str(data).unwrap();
+        assert_eq!(file.id, PacsFileId(7));
+        assert_eq!(file.patient_id, "1449c1d");
+        assert_eq!(file.modality.as_deref(), Some("MR"));
+        assert_eq!(file.series_description, None);
+        assert_eq!(file.fsize(), 307062);
+        assert!(file.fname().as_str().ends_with(".dcm"));
+    }
+}
@@ -33,6 +33,16 @@ impl<T: DeserializeOwned, A: Access> LinkedModel<T, A> {
         }
     }
 
+    /// Use the client of this object for other data which was obtained previously,
+    /// e.g. from a cache.
+    pub fn with_object<R: DeserializeOwned>(&self, object: R) -> LinkedModel<R, A> {
+        LinkedModel {
+            object,
+            client: self.client.clone(),
+            phantom: Default::default(),
+        }
+    }
+
     /// Get items in a collection
     pub(crate) fn get_collection<R: DeserializeOwned>(&self, url: &CollectionUrl) -> Search<R, A> {
         Search::collection(self.client.clone(), url.clone())
@@ -120,5 +130,14 @@ impl<T: DeserializeOwned, A: Access> LazyLinkedModel<'_, T, A> {
     }
 }
 
+impl<T: DeserializeOwned> LazyLinkedModel<'_, T, RwAccess> {
+    /// Delete this resource.
+    pub async fn delete(self) -> Result<(), CubeError> {
+        let res = self.client.delete(self.url.as_str()).send().await?;
+        check(res).await?;
+        Ok(())
+    }
+}
+
 // Future work:
 // LinkedModel should have a get method too, which "refreshes" its data.
@@ -4,9 +4,15 @@ mod feed;
 mod pipeline;
 mod plugin;
 mod plugininstance;
+mod resource;
+mod tag;
+mod workflow;
 
 pub use downloadable::*;
 pub use feed::*;
 pub use pipeline::*;
 pub use plugin::*;
 pub use plugininstance::*;
+pub use resource::*;
+pub use tag::*;
+pub use workflow::*;
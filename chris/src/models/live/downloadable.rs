@@ -40,6 +40,31 @@ impl<D: Downloadable + DeserializeOwned, A: Access> LinkedModel<D, A> {
         Ok(stream)
     }
 
+    /// Stream the bytes data of a file from _ChRIS_, starting from the byte `start`.
+    ///
+    /// Returns the bytestream and whether the server honored the range request.
+    /// If it did not, the bytestream starts from the beginning of the file.
+    pub async fn stream_range(
+        &self,
+        start: u64,
+    ) -> Result<
+        (
+            impl Stream<Item = Result<bytes::Bytes, reqwest::Error>>,
+            bool,
+        ),
+        CubeError,
+    > {
+        let res = self
+            .client
+            .get(self.object.file_resource_url().as_str())
+            .header(reqwest::header::RANGE, format!("bytes={}-", start))
+            .send()
+            .await?;
+        let res = check(res).await?;
+        let is_partial = res.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        Ok((res.bytes_stream(), is_partial))
+    }
+
     /// Download a file from _ChRIS_ to a local path.
     pub async fn download(&self, dst: &Utf8Path, clobber: bool) -> Result<(), FileIOError> {
         let mut file = if clobber {
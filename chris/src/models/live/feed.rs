@@ -1,11 +1,14 @@
 use serde_with::serde_derive::Serialize;
 
-use crate::errors::CubeError;
+use futures::TryStreamExt;
+
+use crate::errors::{CubeError, TaggingError, UnshareError};
 use crate::models::data::FeedResponse;
 use crate::search::Search;
+use crate::types::{TagId, Username};
 use crate::{
-    Access, BasicFileResponse, LazyLinkedModel, LinkedModel, NoteResponse, PluginInstanceResponse,
-    RoAccess, RwAccess,
+    Access, BasicFileResponse, FeedUserPermissionResponse, LazyLinkedModel, LinkedModel,
+    NoteResponse, PluginInstanceResponse, RoAccess, RwAccess, TagResponse, TaggingResponse,
 };
 
 /// ChRIS feed note.
@@ -31,6 +34,21 @@ impl<A: Access> Feed<A> {
     pub fn files(&self) -> Search<BasicFileResponse, A> {
         self.get_collection(&self.object.files)
     }
+
+    /// Get the tags of this feed.
+    pub fn tags(&self) -> Search<TagResponse, A> {
+        self.get_collection(&self.object.tags)
+    }
+
+    /// Get the taggings of this feed, i.e. the links between this feed and its tags.
+    ///
+    /// Returns `None` for old versions of _CUBE_.
+    pub fn taggings(&self) -> Option<Search<TaggingResponse, A>> {
+        self.object
+            .taggings
+            .as_ref()
+            .map(|url| self.get_collection(url))
+    }
 }
 
 impl<A: Access> Note<A> {
@@ -87,6 +105,78 @@ impl FeedRw {
     pub async fn set_name(&self, name: &str) -> Result<Self, CubeError> {
         self.put(&self.object.url, &Name { name }).await
     }
+
+    /// Make this feed public, so that anyone can see it without logging in, or private.
+    pub async fn set_public(&self, public: bool) -> Result<Self, CubeError> {
+        self.put(&self.object.url, &Public { public }).await
+    }
+
+    /// Get the users who this feed is shared with.
+    ///
+    /// Returns `None` for versions of _CUBE_ before 6, where users who a feed is
+    /// shared with are instead in [FeedResponse::owner].
+    pub fn user_permissions(&self) -> Option<Search<FeedUserPermissionResponse, RwAccess>> {
+        self.object
+            .user_permissions
+            .as_ref()
+            .map(|url| self.get_collection(url))
+    }
+
+    /// Share this feed with a user, giving them access to see it.
+    ///
+    /// Old versions of _CUBE_ share a feed by making the user an owner of the feed.
+    pub async fn share_with(&self, username: &Username) -> Result<(), CubeError> {
+        if let Some(url) = &self.object.user_permissions {
+            let _: LinkedModel<FeedUserPermissionResponse, RwAccess> =
+                self.post(url, &UserPermissionRequest { username }).await?;
+        } else {
+            self.put(&self.object.url, &Owner { owner: username })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Stop sharing this feed with a user.
+    pub async fn unshare(&self, username: &Username) -> Result<(), UnshareError> {
+        let permissions = self.user_permissions().ok_or(UnshareError::Unsupported)?;
+        let all: Vec<_> = permissions.stream().try_collect().await?;
+        let permission = all
+            .into_iter()
+            .find(|p| &p.user_username == username)
+            .ok_or_else(|| UnshareError::NotShared(username.to_string()))?;
+        self.get_lazy::<FeedUserPermissionResponse>(&permission.url)
+            .delete()
+            .await?;
+        Ok(())
+    }
+
+    /// Put a tag on this feed.
+    pub async fn tag(
+        &self,
+        tag_id: TagId,
+    ) -> Result<LinkedModel<TaggingResponse, RwAccess>, TaggingError> {
+        let url = self
+            .object
+            .taggings
+            .as_ref()
+            .ok_or(TaggingError::Unsupported)?;
+        let tagging = self.post(url, &TaggingRequest { tag_id }).await?;
+        Ok(tagging)
+    }
+
+    /// Remove a tag from this feed. The tag itself is not deleted.
+    pub async fn untag(&self, tag_id: TagId) -> Result<(), TaggingError> {
+        let taggings = self.taggings().ok_or(TaggingError::Unsupported)?;
+        let all: Vec<_> = taggings.stream().try_collect().await?;
+        let tagging = all
+            .into_iter()
+            .find(|t| t.tag_id == tag_id)
+            .ok_or(TaggingError::NotTagged(tag_id))?;
+        self.get_lazy::<TaggingResponse>(&tagging.url)
+            .delete()
+            .await?;
+        Ok(())
+    }
 }
 
 impl<'a> LazyFeedRw<'a> {
@@ -101,6 +191,26 @@ struct Name<'a> {
     name: &'a str,
 }
 
+#[derive(Serialize)]
+struct Public {
+    public: bool,
+}
+
+#[derive(Serialize)]
+struct Owner<'a> {
+    owner: &'a Username,
+}
+
+#[derive(Serialize)]
+struct UserPermissionRequest<'a> {
+    username: &'a Username,
+}
+
+#[derive(Serialize)]
+struct TaggingRequest {
+    tag_id: TagId,
+}
+
 #[derive(Serialize)]
 struct NoteRequest<'a> {
     title: &'a str,
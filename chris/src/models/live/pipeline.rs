@@ -1,9 +1,10 @@
+use super::CreateWorkflow;
 use crate::errors::CubeError;
 use crate::search::Search;
 use crate::types::PluginInstanceId;
 use crate::{
-    Access, LinkedModel, PipelineResponse, PluginInstanceResponse, RoAccess, RwAccess,
-    WorkflowResponse,
+    Access, LazyLinkedModel, LinkedModel, PipelineResponse, PipingParameterResponse,
+    PipingResponse, PluginInstanceResponse, RoAccess, RwAccess, WorkflowResponse,
 };
 
 /// A _ChRIS_ pipeline.
@@ -15,13 +16,25 @@ pub type PipelineRo = LinkedModel<PipelineResponse, RoAccess>;
 /// A _ChRIS_ pipeline you can run.
 pub type PipelineRw = LinkedModel<PipelineResponse, RwAccess>;
 
+impl<A: Access> Pipeline<A> {
+    /// Get the pipings of this pipeline.
+    pub fn pipings(&self) -> Search<PipingResponse, A> {
+        self.get_collection(&self.object.plugin_pipings)
+    }
+
+    /// Get the default parameter values of every piping of this pipeline.
+    pub fn default_parameters(&self) -> Search<PipingParameterResponse, A> {
+        self.get_collection(&self.object.default_parameters)
+    }
+}
+
 impl PipelineRw {
     /// Get workflows (instances) of this pipeline.
     pub fn get_workflows(&self) -> Search<WorkflowResponse, RwAccess> {
         self.get_collection(&self.object.workflows)
     }
 
-    /// Run this pipeline.
+    /// Run this pipeline. To set options of its pipings, use [PipelineRw::workflow_builder].
     pub async fn create_workflow(
         &self,
         prev: PluginInstanceId,
@@ -29,19 +42,13 @@ impl PipelineRw {
     ) -> Result<LinkedModel<WorkflowResponse, RwAccess>, CubeError> {
         let body = CreateWorkflow {
             previous_plugin_inst_id: prev,
-            title,
+            title: title.map(String::from),
+            nodes_info: None,
         };
         self.post(&self.object.workflows, &body).await
     }
 }
 
-#[derive(serde::Serialize)]
-struct CreateWorkflow<'a> {
-    previous_plugin_inst_id: PluginInstanceId,
-    title: Option<&'a str>,
-    // nodes_info: idk
-}
-
 pub type Workflow<A> = LinkedModel<WorkflowResponse, A>;
 
 impl<A: Access> Workflow<A> {
@@ -49,4 +56,9 @@ impl<A: Access> Workflow<A> {
     pub fn plugin_instances(&self) -> Search<PluginInstanceResponse, A> {
         self.get_collection(&self.object.plugin_instances)
     }
+
+    /// Get the pipeline which this workflow is a run of.
+    pub fn pipeline(&self) -> LazyLinkedModel<'_, PipelineResponse, A> {
+        self.get_lazy(&self.object.pipeline)
+    }
 }
@@ -1,4 +1,8 @@
+use serde::Serialize;
+
+use crate::errors::CubeError;
 use crate::search::Search;
+use crate::types::Status;
 use crate::{
     Access, BasicFileResponse, LazyFeed, LazyLinkedModel, LinkedModel,
     PluginInstanceParameterResponse, PluginInstanceResponse, PluginParameter, PluginResponse,
@@ -20,6 +24,11 @@ impl<A: Access> LinkedModel<PluginInstanceResponse, A> {
         self.get_collection(&self.object.parameters)
     }
 
+    /// Previous plugin instance of this plugin instance.
+    pub fn previous(&self) -> Option<LazyLinkedModel<'_, PluginInstanceResponse, A>> {
+        self.object.previous.as_ref().map(|url| self.get_lazy(url))
+    }
+
     /// Plugin of this plugin instance.
     pub fn plugin(&self) -> LazyLinkedModel<PluginResponse, A> {
         self.get_lazy(&self.object.plugin)
@@ -36,6 +45,31 @@ impl<A: Access> LinkedModel<PluginInstanceResponse, A> {
     }
 }
 
+impl PluginInstanceRw {
+    /// Cancel this plugin instance. Returns the plugin instance with its updated status.
+    pub async fn cancel(&self) -> Result<Self, CubeError> {
+        let body = StatusRequest {
+            status: Status::Cancelled,
+        };
+        self.put(&self.object.url, &body).await
+    }
+
+    /// Set the title of this plugin instance. An empty title clears it.
+    pub async fn set_title(&self, title: &str) -> Result<Self, CubeError> {
+        self.put(&self.object.url, &TitleRequest { title }).await
+    }
+}
+
+#[derive(Serialize)]
+struct StatusRequest {
+    status: Status,
+}
+
+#[derive(Serialize)]
+struct TitleRequest<'a> {
+    title: &'a str,
+}
+
 pub type PluginInstanceParameter<A> = LinkedModel<PluginInstanceParameterResponse, A>;
 
 impl<A: Access> PluginInstanceParameter<A> {
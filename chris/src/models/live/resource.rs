@@ -0,0 +1,155 @@
+use crate::client::access::Access;
+use crate::errors::CubeError;
+use crate::models::linked::*;
+use crate::types::*;
+use crate::{
+    ComputeResourceResponse, FeedFileResponse, FeedResponse, FeedUserPermissionResponse,
+    FileUploadResponse, NoteResponse, PacsFileResponse, PipelineResponse, PipingParameterResponse,
+    PipingResponse, PluginInstanceParameterResponse, PluginInstanceResponse, PluginParameter,
+    PluginResponse, RwAccess, TagResponse, TaggingResponse, UserResponse, WorkflowResponse,
+};
+use serde::de::DeserializeOwned;
+
+/// A CUBE resource which has a `url` to itself.
+pub trait Resource {
+    fn url(&self) -> &ItemUrl;
+}
+
+impl<T: Resource + DeserializeOwned, A: Access> LinkedModel<T, A> {
+    /// Get the current data of this object from _CUBE_, e.g. to see whether the status
+    /// of a plugin instance changed.
+    pub async fn refresh(&self) -> Result<Self, CubeError> {
+        self.get_lazy(self.object.url()).get().await
+    }
+}
+
+impl<T: Resource + DeserializeOwned> LinkedModel<T, RwAccess> {
+    /// Delete this object from _CUBE_.
+    pub async fn delete(self) -> Result<(), CubeError> {
+        self.get_lazy::<T>(self.object.url()).delete().await
+    }
+}
+
+macro_rules! impl_resource {
+    ($($t:ty),+) => {
+        $(
+            impl Resource for $t {
+                fn url(&self) -> &ItemUrl {
+                    &self.url
+                }
+            }
+        )+
+    };
+}
+
+impl_resource!(
+    FeedResponse,
+    FeedUserPermissionResponse,
+    NoteResponse,
+    PluginResponse,
+    PluginParameter,
+    PluginInstanceResponse,
+    PluginInstanceParameterResponse,
+    PipelineResponse,
+    PipingResponse,
+    PipingParameterResponse,
+    WorkflowResponse,
+    ComputeResourceResponse,
+    FeedFileResponse,
+    FileUploadResponse,
+    PacsFileResponse,
+    TagResponse,
+    TaggingResponse,
+    UserResponse
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RoAccess;
+    use rstest::*;
+    use serde::Deserialize;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[derive(Deserialize)]
+    struct Counter {
+        url: ItemUrl,
+        status: String,
+        count: u32,
+    }
+
+    impl Resource for Counter {
+        fn url(&self) -> &ItemUrl {
+            &self.url
+        }
+    }
+
+    /// A resource which counts how many times it was requested. Its status is
+    /// "started" the first time, and "finishedSuccessfully" after that.
+    async fn counter_server() -> ItemUrl {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/api/v1/counters/1/",
+            listener.local_addr().unwrap()
+        );
+        let requests = Arc::new(AtomicU32::new(0));
+        let body_url = url.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(&mut socket);
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let count = requests.fetch_add(1, Ordering::SeqCst) + 1;
+                let status = if count == 1 {
+                    "started"
+                } else {
+                    "finishedSuccessfully"
+                };
+                let body = serde_json::json!({
+                    "url": body_url,
+                    "status": status,
+                    "count": count
+                })
+                .to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        ItemUrl::new(url)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_refresh() {
+        let url = counter_server().await;
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+        let lazy: LazyLinkedModel<Counter, RoAccess> = LazyLinkedModel {
+            url: &url,
+            client: &client,
+            phantom: Default::default(),
+        };
+        let counter = lazy.get().await.unwrap();
+        assert_eq!(counter.object.status, "started");
+        assert_eq!(counter.object.count, 1);
+        let refreshed = counter.refresh().await.unwrap();
+        assert_eq!(refreshed.object.status, "finishedSuccessfully");
+        assert_eq!(refreshed.object.count, 2);
+        assert_eq!(refreshed.object.url, url);
+        // the original object is unchanged
+        assert_eq!(counter.object.count, 1);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::search::Search;
+use crate::{Access, FeedResponse, LinkedModel, RoAccess, RwAccess, TagResponse, TaggingResponse};
+
+/// A tag of feeds.
+pub type Tag<A> = LinkedModel<TagResponse, A>;
+
+/// A tag which you can edit.
+pub type TagRw = Tag<RwAccess>;
+
+/// A tag which you can read but not edit.
+pub type TagRo = Tag<RoAccess>;
+
+impl<A: Access> Tag<A> {
+    /// Get the feeds which have this tag.
+    pub fn feeds(&self) -> Search<FeedResponse, A> {
+        self.get_collection(&self.object.feeds)
+    }
+
+    /// Get the taggings of this tag, i.e. which feeds have this tag.
+    pub fn taggings(&self) -> Search<TaggingResponse, A> {
+        self.get_collection(&self.object.taggings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::TaggingError;
+    use crate::testing::MockCube;
+    use crate::types::{FeedId, TagId};
+    use crate::BaseChrisClient;
+    use futures::TryStreamExt;
+    use rstest::*;
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_tags() {
+        let mock = MockCube::start().await;
+        mock.add_tagged_feeds();
+        let client = mock.client("chris").await;
+        let tag = client
+            .tags()
+            .name("brain")
+            .search()
+            .get_only()
+            .await
+            .unwrap();
+        assert_eq!(tag.object.id, TagId(1));
+        let feed_ids: Vec<_> = tag
+            .feeds()
+            .stream()
+            .map_ok(|f| f.id)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(feed_ids, vec![FeedId(1), FeedId(3)]);
+        let taggings: Vec<_> = tag.taggings().stream().try_collect().await.unwrap();
+        assert!(taggings.iter().all(|t| t.tag_name == "brain"));
+
+        let feed = client.get_feed(FeedId(2)).await.unwrap();
+        let tags: Vec<_> = feed
+            .tags()
+            .stream()
+            .map_ok(|t| t.name)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(tags, vec!["lung"]);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_untag_not_tagged() {
+        let mock = MockCube::start().await;
+        mock.add_tagged_feeds();
+        let client = mock.client("chris").await;
+        let feed = client.get_feed(FeedId(2)).await.unwrap();
+        let error = feed.untag(TagId(1)).await.unwrap_err();
+        assert!(matches!(error, TaggingError::NotTagged(TagId(1))));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_tagging_unsupported() {
+        let mock = MockCube::start().await;
+        mock.add_feed(FeedResponse {
+            taggings: None,
+            ..mock.feed(1, "old")
+        });
+        let client = mock.client("chris").await;
+        let feed = client.get_feed(FeedId(1)).await.unwrap();
+        assert!(feed.taggings().is_none());
+        let result = feed.tag(TagId(1)).await;
+        assert!(matches!(result, Err(TaggingError::Unsupported)));
+        assert!(mock.requests().iter().all(|r| !r.contains("tag")));
+    }
+}
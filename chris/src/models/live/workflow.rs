@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+
+use futures::TryStreamExt;
+use serde::Serialize;
+use serde_with::json::JsonString;
+use serde_with::serde_as;
+
+use crate::errors::WorkflowError;
+use crate::types::{
+    ComputeResourceName, PipingId, PluginId, PluginInstanceId, PluginParameterType,
+    PluginParameterValue,
+};
+use crate::{
+    PipelineRw, PipingParameterResponse, PipingResponse, PluginResponse, RwAccess, Workflow,
+};
+
+/// Builder for running a pipeline with options for each of its pipings,
+/// see [PipelineRw::workflow_builder].
+///
+/// Pipings are referred to by their titles. Options are checked against the pipings
+/// of the pipeline before the workflow is created.
+#[must_use]
+pub struct WorkflowBuilder<'a> {
+    pipeline: &'a PipelineRw,
+    previous_id: PluginInstanceId,
+    title: Option<String>,
+    params: Vec<(String, String, PluginParameterValue)>,
+    compute_resources: Vec<(String, ComputeResourceName)>,
+}
+
+/// The request body for creating a workflow.
+#[serde_as]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CreateWorkflow {
+    pub previous_plugin_inst_id: PluginInstanceId,
+    pub title: Option<String>,
+    /// Options of every piping, which _CUBE_ expects as a JSON string.
+    #[serde_as(as = "Option<JsonString>")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nodes_info: Option<Vec<WorkflowNode>>,
+}
+
+/// Options for the plugin instance created from a piping.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WorkflowNode {
+    pub piping_id: PipingId,
+    pub compute_resource_name: ComputeResourceName,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub plugin_parameter_defaults: Vec<NodeParameter>,
+}
+
+/// A parameter value which overrides the default of a piping.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct NodeParameter {
+    pub name: String,
+    pub default: PluginParameterValue,
+}
+
+impl PipelineRw {
+    /// Run this pipeline after the plugin instance `previous_id`, with options
+    /// for each of its pipings.
+    pub fn workflow_builder(&self, previous_id: PluginInstanceId) -> WorkflowBuilder<'_> {
+        WorkflowBuilder {
+            pipeline: self,
+            previous_id,
+            title: None,
+            params: Vec::new(),
+            compute_resources: Vec::new(),
+        }
+    }
+}
+
+impl<'a> WorkflowBuilder<'a> {
+    /// Set the title of the workflow.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Set the value of a parameter of the piping titled `piping`, instead of the
+    /// default of the pipeline. A [PluginParameterValue::Stringish] value is converted
+    /// to the type of the parameter, e.g. `"5"` to an integer.
+    pub fn set_param(
+        mut self,
+        piping: impl Into<String>,
+        name: impl Into<String>,
+        value: impl Into<PluginParameterValue>,
+    ) -> Self {
+        self.params.push((piping.into(), name.into(), value.into()));
+        self
+    }
+
+    /// Set the compute resource which the piping titled `piping` runs on.
+    /// Pipings without a compute resource run on the first compute resource
+    /// of their plugin.
+    pub fn compute_resource(
+        mut self,
+        piping: impl Into<String>,
+        name: impl Into<ComputeResourceName>,
+    ) -> Self {
+        self.compute_resources.push((piping.into(), name.into()));
+        self
+    }
+
+    /// Get the request body for creating the workflow. Pipings and their parameters
+    /// are only retrieved if options were given for any piping.
+    pub async fn body(&self) -> Result<CreateWorkflow, WorkflowError> {
+        let nodes_info = if self.params.is_empty() && self.compute_resources.is_empty() {
+            None
+        } else {
+            Some(self.nodes_info().await?)
+        };
+        Ok(CreateWorkflow {
+            previous_plugin_inst_id: self.previous_id,
+            title: self.title.clone(),
+            nodes_info,
+        })
+    }
+
+    /// Create the workflow.
+    pub async fn create(self) -> Result<Workflow<RwAccess>, WorkflowError> {
+        let body = self.body().await?;
+        let workflow = self
+            .pipeline
+            .post(&self.pipeline.object.workflows, &body)
+            .await?;
+        Ok(workflow)
+    }
+
+    async fn nodes_info(&self) -> Result<Vec<WorkflowNode>, WorkflowError> {
+        let pipings_search = self.pipeline.pipings();
+        let defaults_search = self.pipeline.default_parameters();
+        let (pipings, defaults): (Vec<_>, Vec<_>) = futures::try_join!(
+            pipings_search.stream().try_collect(),
+            defaults_search.stream().try_collect()
+        )?;
+        let params = self.checked_params(&pipings, &defaults)?;
+        let available = self.available_compute_resources().await?;
+        self.assemble(&pipings, params, &available)
+    }
+
+    /// Check that the parameters set by [WorkflowBuilder::set_param] exist, and
+    /// convert their values to the types of the parameters.
+    fn checked_params(
+        &self,
+        pipings: &[PipingResponse],
+        defaults: &[PipingParameterResponse],
+    ) -> Result<HashMap<PipingId, Vec<NodeParameter>>, WorkflowError> {
+        let mut params: HashMap<PipingId, Vec<NodeParameter>> = HashMap::new();
+        for (title, name, value) in &self.params {
+            let piping = find_piping(pipings, title)?;
+            let parameter = defaults
+                .iter()
+                .find(|p| p.plugin_piping_id == piping.id && &p.param_name == name)
+                .ok_or_else(|| WorkflowError::UnknownParameter {
+                    piping: title.to_string(),
+                    name: name.to_string(),
+                })?;
+            let default = match parameter.parameter_type {
+                Some(expected) => {
+                    convert(value, expected).ok_or_else(|| WorkflowError::InvalidValue {
+                        piping: title.to_string(),
+                        name: name.to_string(),
+                        value: value.to_string(),
+                        expected,
+                    })?
+                }
+                None => value.clone(),
+            };
+            let node_params = params.entry(piping.id).or_default();
+            node_params.retain(|p| &p.name != name);
+            node_params.push(NodeParameter {
+                name: name.to_string(),
+                default,
+            });
+        }
+        Ok(params)
+    }
+
+    /// Get the names of the compute resources which each plugin of the pipeline can run on.
+    async fn available_compute_resources(
+        &self,
+    ) -> Result<HashMap<PluginId, Vec<ComputeResourceName>>, WorkflowError> {
+        let plugins: Vec<PluginResponse> = self
+            .pipeline
+            .get_collection(&self.pipeline.object.plugins)
+            .stream()
+            .try_collect()
+            .await?;
+        let mut available = HashMap::with_capacity(plugins.len());
+        for plugin in plugins {
+            let id = plugin.id;
+            let names = self
+                .pipeline
+                .with_object(plugin)
+                .compute_resources()
+                .stream()
+                .map_ok(|c| ComputeResourceName::from(c.name))
+                .try_collect()
+                .await?;
+            available.insert(id, names);
+        }
+        Ok(available)
+    }
+
+    /// Create the options of every piping.
+    fn assemble(
+        &self,
+        pipings: &[PipingResponse],
+        mut params: HashMap<PipingId, Vec<NodeParameter>>,
+        available: &HashMap<PluginId, Vec<ComputeResourceName>>,
+    ) -> Result<Vec<WorkflowNode>, WorkflowError> {
+        let mut chosen = HashMap::new();
+        for (title, name) in &self.compute_resources {
+            let piping = find_piping(pipings, title)?;
+            let is_available = available
+                .get(&piping.plugin_id)
+                .is_some_and(|names| names.contains(name));
+            if !is_available {
+                return Err(WorkflowError::UnknownComputeResource {
+                    piping: title.to_string(),
+                    name: name.to_string(),
+                });
+            }
+            chosen.insert(piping.id, name.clone());
+        }
+        pipings
+            .iter()
+            .map(|piping| {
+                let compute_resource_name = chosen
+                    .remove(&piping.id)
+                    .or_else(|| {
+                        available
+                            .get(&piping.plugin_id)
+                            .and_then(|names| names.first())
+                            .cloned()
+                    })
+                    .ok_or_else(|| WorkflowError::NoComputeResource(piping.title.to_string()))?;
+                Ok(WorkflowNode {
+                    piping_id: piping.id,
+                    compute_resource_name,
+                    plugin_parameter_defaults: params.remove(&piping.id).unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn find_piping<'p>(
+    pipings: &'p [PipingResponse],
+    title: &str,
+) -> Result<&'p PipingResponse, WorkflowError> {
+    pipings
+        .iter()
+        .find(|p| p.title == title)
+        .ok_or_else(|| WorkflowError::UnknownPiping(title.to_string()))
+}
+
+/// Convert a value to the given type of parameter. Returns `None` if it cannot be converted.
+fn convert(
+    value: &PluginParameterValue,
+    expected: PluginParameterType,
+) -> Option<PluginParameterValue> {
+    use PluginParameterType as T;
+    use PluginParameterValue as V;
+    match (value, expected) {
+        (V::Stringish(s), T::Boolean) => s.parse().ok().map(V::Boolean),
+        (V::Stringish(s), T::Integer) => s.parse().ok().map(V::Integer),
+        (V::Stringish(s), T::Float) => s.parse().ok().map(V::Float),
+        (V::Stringish(_), T::String | T::Path | T::Unextpath) => Some(value.clone()),
+        (V::Boolean(_), T::Boolean) | (V::Integer(_), T::Integer) | (V::Float(_), T::Float) => {
+            Some(value.clone())
+        }
+        (V::Integer(n), T::Float) => Some(V::Float(*n as f64)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCube;
+    use crate::types::PipelineId;
+    use crate::BaseChrisClient;
+    use rstest::*;
+    use serde_json::{json, Value};
+
+    /// Add a pipeline of two pipings, "root" running pl-dircopy and "child" running
+    /// pl-simpledsapp, where pl-simpledsapp is available on two compute resources.
+    async fn pipeline(cube: &MockCube) -> PipelineRw {
+        let url = cube.url().to_string();
+        let pipeline_url = format!("{url}pipelines/1/");
+        let compute_resource = |id: u32, name: &str| {
+            json!({
+                "url": format!("{url}computeresources/{id}/"),
+                "id": id,
+                "name": name,
+                "creation_date": "2024-01-01T00:00:00Z",
+                "modification_date": "2024-01-01T00:00:00Z",
+                "compute_url": "http://pfcon/api/v1/",
+                "compute_auth_url": "http://pfcon/api/v1/auth-token/",
+                "compute_innetwork": true,
+                "description": "",
+                "max_job_exec_seconds": 86400
+            })
+        };
+        let dircopy = cube.plugin(1, "pl-dircopy", "2.1.1");
+        let simpledsapp = cube.plugin(2, "pl-simpledsapp", "2.1.0");
+        cube.add_items(
+            &dircopy.compute_resources.as_str()[url.len()..],
+            [compute_resource(1, "host")],
+        );
+        cube.add_items(
+            &simpledsapp.compute_resources.as_str()[url.len()..],
+            [compute_resource(1, "host"), compute_resource(2, "gpu")],
+        );
+        let plugins = cube.add_items("pipelines/1/plugins/", [&dircopy, &simpledsapp]);
+        let piping = |id: u32, title: &str, previous_id: Option<u32>, plugin: &PluginResponse| {
+            json!({
+                "url": format!("{url}pipelines/pipings/{id}/"),
+                "id": id,
+                "title": title,
+                "previous_id": previous_id,
+                "plugin_id": plugin.id,
+                "plugin_name": plugin.name,
+                "plugin_version": plugin.version,
+                "pipeline_id": 1
+            })
+        };
+        let pipings = cube.add_items(
+            "pipelines/1/pipings/",
+            [
+                piping(10, "root", None, &dircopy),
+                piping(11, "child", Some(10), &simpledsapp),
+            ],
+        );
+        let parameter = |id: u32, piping_id: u32, name: &str, param_type: &str, value: Value| {
+            json!({
+                "url": format!("{url}pipelines/{param_type}-parameter/{id}/"),
+                "id": id,
+                "param_name": name,
+                "param_id": id,
+                "type": param_type,
+                "value": value,
+                "plugin_piping_id": piping_id,
+                "plugin_piping_title": if piping_id == 10 { "root" } else { "child" },
+                "plugin_name": "",
+                "plugin_version": ""
+            })
+        };
+        let default_parameters = cube.add_items(
+            "pipelines/1/parameters/",
+            [
+                parameter(1, 10, "dir", "string", json!("")),
+                parameter(2, 11, "prefix", "string", json!("hello")),
+                parameter(3, 11, "sleepLength", "integer", Value::Null),
+                parameter(4, 11, "b_ignoreInputDir", "boolean", json!(false)),
+            ],
+        );
+        cube.add_items(
+            "pipelines/",
+            [json!({
+                "url": pipeline_url,
+                "id": 1,
+                "name": "simple",
+                "locked": false,
+                "authors": "FNNDSC <dev@babyMRI.org>",
+                "category": "",
+                "description": "",
+                "owner_username": "chris",
+                "creation_date": "2024-01-01T00:00:00Z",
+                "modification_date": "2024-01-01T00:00:00Z",
+                "plugins": plugins,
+                "plugin_pipings": pipings,
+                "default_parameters": default_parameters,
+                "instances": format!("{pipeline_url}instances/"),
+                "workflows": format!("{pipeline_url}workflows/")
+            })],
+        );
+        let client = cube.client("chris").await;
+        client.get_pipeline(PipelineId(1)).await.unwrap()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_body_without_options() {
+        let cube = MockCube::start().await;
+        let pipeline = pipeline(&cube).await;
+        let requests = cube.requests().len();
+        let body = pipeline
+            .workflow_builder(PluginInstanceId(5))
+            .title("my workflow")
+            .body()
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            json!({"previous_plugin_inst_id": 5, "title": "my workflow"})
+        );
+        assert_eq!(cube.requests().len(), requests, "pipings were retrieved");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_body_with_options() {
+        let cube = MockCube::start().await;
+        let pipeline = pipeline(&cube).await;
+        let body = pipeline
+            .workflow_builder(PluginInstanceId(5))
+            .set_param(
+                "child",
+                "sleepLength",
+                PluginParameterValue::Stringish("5".to_string()),
+            )
+            .set_param(
+                "child",
+                "prefix",
+                PluginParameterValue::Stringish("hi".to_string()),
+            )
+            .set_param(
+                "child",
+                "b_ignoreInputDir",
+                PluginParameterValue::Boolean(true),
+            )
+            .set_param(
+                "child",
+                "prefix",
+                PluginParameterValue::Stringish("bye".to_string()),
+            )
+            .compute_resource("child", "gpu")
+            .body()
+            .await
+            .unwrap();
+        let expected = vec![
+            WorkflowNode {
+                piping_id: PipingId(10),
+                compute_resource_name: ComputeResourceName::from("host"),
+                plugin_parameter_defaults: vec![],
+            },
+            WorkflowNode {
+                piping_id: PipingId(11),
+                compute_resource_name: ComputeResourceName::from("gpu"),
+                plugin_parameter_defaults: vec![
+                    NodeParameter {
+                        name: "sleepLength".to_string(),
+                        default: PluginParameterValue::Integer(5),
+                    },
+                    NodeParameter {
+                        name: "b_ignoreInputDir".to_string(),
+                        default: PluginParameterValue::Boolean(true),
+                    },
+                    NodeParameter {
+                        name: "prefix".to_string(),
+                        default: PluginParameterValue::Stringish("bye".to_string()),
+                    },
+                ],
+            },
+        ];
+        assert_eq!(body.nodes_info.as_ref(), Some(&expected));
+        let serialized = serde_json::to_value(&body).unwrap();
+        let nodes_info: Value =
+            serde_json::from_str(serialized["nodes_info"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            nodes_info[1],
+            json!({
+                "piping_id": 11,
+                "compute_resource_name": "gpu",
+                "plugin_parameter_defaults": [
+                    {"name": "sleepLength", "default": 5},
+                    {"name": "b_ignoreInputDir", "default": true},
+                    {"name": "prefix", "default": "bye"}
+                ]
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("leaf", "prefix", "hi", None, "pipeline has no piping titled \"leaf\"")]
+    #[case(
+        "child",
+        "dir",
+        "hi",
+        None,
+        "piping \"child\" has no parameter \"dir\""
+    )]
+    #[case(
+        "child",
+        "sleepLength",
+        "soon",
+        None,
+        "value \"soon\" of parameter \"sleepLength\" of piping \"child\" is not of type Integer"
+    )]
+    #[case(
+        "root",
+        "dir",
+        "/tmp",
+        Some("gpu"),
+        "plugin of piping \"root\" cannot run on compute resource \"gpu\""
+    )]
+    #[tokio::test]
+    async fn test_invalid_options(
+        #[case] piping: &str,
+        #[case] name: &str,
+        #[case] value: &str,
+        #[case] compute_resource: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        let cube = MockCube::start().await;
+        let pipeline = pipeline(&cube).await;
+        let mut builder = pipeline.workflow_builder(PluginInstanceId(5)).set_param(
+            piping,
+            name,
+            PluginParameterValue::Stringish(value.to_string()),
+        );
+        if let Some(compute_resource) = compute_resource {
+            builder = builder.compute_resource(piping, compute_resource);
+        }
+        let error = match builder.create().await {
+            Ok(_) => panic!("workflow was created"),
+            Err(e) => e,
+        };
+        assert_eq!(error.to_string(), expected);
+        assert!(cube.requests().iter().all(|r| !r.contains("workflows")));
+    }
+
+    #[rstest]
+    #[case(PluginParameterValue::Stringish("true".to_string()), PluginParameterType::Boolean, Some(PluginParameterValue::Boolean(true)))]
+    #[case(PluginParameterValue::Stringish("1.5".to_string()), PluginParameterType::Float, Some(PluginParameterValue::Float(1.5)))]
+    #[case(
+        PluginParameterValue::Integer(2),
+        PluginParameterType::Float,
+        Some(PluginParameterValue::Float(2.0))
+    )]
+    #[case(PluginParameterValue::Stringish("5".to_string()), PluginParameterType::Path, Some(PluginParameterValue::Stringish("5".to_string())))]
+    #[case(PluginParameterValue::Stringish("yes".to_string()), PluginParameterType::Boolean, None)]
+    #[case(PluginParameterValue::Float(1.5), PluginParameterType::Integer, None)]
+    #[case(PluginParameterValue::Boolean(true), PluginParameterType::String, None)]
+    fn test_convert(
+        #[case] value: PluginParameterValue,
+        #[case] expected_type: PluginParameterType,
+        #[case] expected: Option<PluginParameterValue>,
+    ) {
+        assert_eq!(convert(&value, expected_type), expected)
+    }
+}
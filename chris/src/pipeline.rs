@@ -1,6 +1,7 @@
 //! Models for _ChRIS_ file-based representation of pipelines.
 
 pub mod canon;
+pub mod feed;
 pub mod rfc2;
 
 pub use canon::{CanonPipeline, ExpandedTreePipeline, PossiblyExpandedTreePipeline};
@@ -133,6 +134,6 @@ mod tests {
             return o;
         }
         // not comparing plugin_parameter_defaults
-        return Ordering::Equal;
+        Ordering::Equal
     }
 }
@@ -0,0 +1,141 @@
+//! The canonical representation of a pipeline, i.e. what _CUBE_ accepts as a POST
+//! to `api/v1/pipelines/`.
+//!
+//! In the canonical representation, `plugin_tree` is a JSON _string_ of a list of pipings,
+//! where each piping refers to its previous piping by index. [ExpandedTreePipeline]
+//! is the same, except `plugin_tree` is an actual list.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_with::json::JsonString;
+use serde_with::serde_as;
+
+use crate::types::{PluginName, PluginParameterValue, PluginVersion};
+
+/// A pipeline as it is uploaded to _CUBE_, where `plugin_tree` is serialized as a JSON string.
+#[serde_as]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CanonPipeline {
+    pub authors: String,
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    pub locked: bool,
+    #[serde_as(as = "JsonString")]
+    pub plugin_tree: Vec<ExpandedTreePiping>,
+}
+
+/// A pipeline where `plugin_tree` is a list.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExpandedTreePipeline {
+    pub authors: String,
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    pub locked: bool,
+    pub plugin_tree: Vec<ExpandedTreePiping>,
+}
+
+/// A pipeline where `plugin_tree` may be either a list or a JSON string of a list,
+/// e.g. a pipeline JSON file written by hand or downloaded from _CUBE_.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct PossiblyExpandedTreePipeline {
+    pub authors: String,
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(deserialize_with = "deserialize_possibly_expanded")]
+    pub plugin_tree: Vec<ExpandedTreePiping>,
+}
+
+/// A plugin of a pipeline.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExpandedTreePiping {
+    pub plugin_name: PluginName,
+    pub plugin_version: PluginVersion,
+    /// Index of the previous piping in `plugin_tree`, `None` for the root.
+    pub previous_index: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_parameter_defaults: Option<Vec<ExpandedTreeParameter>>,
+}
+
+/// A default parameter value of a piping.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExpandedTreeParameter {
+    pub name: String,
+    pub default: PluginParameterValue,
+}
+
+fn deserialize_possibly_expanded<'de, D>(
+    deserializer: D,
+) -> Result<Vec<ExpandedTreePiping>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PossiblyExpanded {
+        Expanded(Vec<ExpandedTreePiping>),
+        Canon(String),
+    }
+    match PossiblyExpanded::deserialize(deserializer)? {
+        PossiblyExpanded::Expanded(plugin_tree) => Ok(plugin_tree),
+        PossiblyExpanded::Canon(s) => serde_json::from_str(&s).map_err(serde::de::Error::custom),
+    }
+}
+
+impl CanonPipeline {
+    /// The plugin name and version of every piping, without duplicates.
+    pub fn plugins(&self) -> Vec<(&PluginName, &PluginVersion)> {
+        let mut plugins: Vec<_> = self
+            .plugin_tree
+            .iter()
+            .map(|p| (&p.plugin_name, &p.plugin_version))
+            .collect();
+        plugins.sort();
+        plugins.dedup();
+        plugins
+    }
+}
+
+impl From<PossiblyExpandedTreePipeline> for CanonPipeline {
+    fn from(p: PossiblyExpandedTreePipeline) -> Self {
+        Self {
+            authors: p.authors,
+            name: p.name,
+            description: p.description,
+            category: p.category,
+            locked: p.locked,
+            plugin_tree: p.plugin_tree,
+        }
+    }
+}
+
+impl From<ExpandedTreePipeline> for CanonPipeline {
+    fn from(p: ExpandedTreePipeline) -> Self {
+        Self {
+            authors: p.authors,
+            name: p.name,
+            description: p.description,
+            category: p.category,
+            locked: p.locked,
+            plugin_tree: p.plugin_tree,
+        }
+    }
+}
+
+impl From<CanonPipeline> for ExpandedTreePipeline {
+    fn from(p: CanonPipeline) -> Self {
+        Self {
+            authors: p.authors,
+            name: p.name,
+            description: p.description,
+            category: p.category,
+            locked: p.locked,
+            plugin_tree: p.plugin_tree,
+        }
+    }
+}
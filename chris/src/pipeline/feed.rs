@@ -0,0 +1,373 @@
+//! Reconstructing a pipeline from the plugin instances of a feed.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::rfc2::TitleIndexedPiping;
+use crate::types::PluginInstanceId;
+use crate::{PluginInstanceParameterResponse, PluginInstanceResponse};
+
+/// Reasons why the plugin instances of a feed do not form a tree.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum InvalidFeedTree {
+    #[error("Previous of plugininstance/{} is plugininstance/{}, which is missing", id.0, previous.0)]
+    MissingPrevious {
+        id: PluginInstanceId,
+        previous: PluginInstanceId,
+    },
+
+    #[error("Plugin instances form a cycle: {}", ids_of(.0))]
+    Cycle(Vec<PluginInstanceId>),
+
+    #[error("Pipeline must have exactly one root, found {}: {}", .0.len(), ids_of(.0))]
+    Roots(Vec<PluginInstanceId>),
+}
+
+fn ids_of(ids: &[PluginInstanceId]) -> String {
+    ids.iter()
+        .map(|id| format!("plugininstance/{}", id.0))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A plugin instance of a feed, with its parameters and a title which is unique
+/// among the plugin instances of the feed.
+#[derive(Debug, Clone)]
+pub struct FeedStep {
+    /// Title of the plugin instance, made unique
+    pub title: String,
+    /// Unique title of the previous plugin instance, if it is a step
+    pub previous: Option<String>,
+    pub plugin_instance: PluginInstanceResponse,
+    pub parameters: Vec<PluginInstanceParameterResponse>,
+}
+
+/// Order the plugin instances of a feed so that every plugin instance comes after
+/// its previous, and title them uniquely.
+///
+/// Missing or repeated titles are made unique. The root plugin instance (usually an
+/// _fs_ plugin) is left out unless `include_root` is true. Either way, the steps
+/// must have exactly one root.
+pub fn steps_of_feed(
+    plugin_instances: impl IntoIterator<
+        Item = (PluginInstanceResponse, Vec<PluginInstanceParameterResponse>),
+    >,
+    include_root: bool,
+) -> Result<Vec<FeedStep>, InvalidFeedTree> {
+    let mut plugin_instances: Vec<_> = plugin_instances.into_iter().collect();
+    plugin_instances.sort_by_key(|(p, _)| p.id.0);
+    let ids: HashSet<_> = plugin_instances.iter().map(|(p, _)| p.id).collect();
+    for (p, _) in &plugin_instances {
+        if let Some(previous) = p.previous_id {
+            if !ids.contains(&previous) {
+                return Err(InvalidFeedTree::MissingPrevious { id: p.id, previous });
+            }
+        }
+    }
+    let ordered = order_by_previous(plugin_instances)?;
+
+    let skipped: HashSet<_> = if include_root {
+        HashSet::new()
+    } else {
+        ordered
+            .iter()
+            .filter(|(p, _)| p.previous_id.is_none())
+            .map(|(p, _)| p.id)
+            .collect()
+    };
+    let roots: Vec<_> = ordered
+        .iter()
+        .filter(|(p, _)| !skipped.contains(&p.id))
+        .filter(|(p, _)| {
+            p.previous_id
+                .map(|id| skipped.contains(&id))
+                .unwrap_or(true)
+        })
+        .map(|(p, _)| p.id)
+        .collect();
+    if roots.len() != 1 {
+        return Err(InvalidFeedTree::Roots(roots));
+    }
+
+    let mut titles: HashMap<PluginInstanceId, String> = HashMap::with_capacity(ordered.len());
+    let mut used = HashSet::with_capacity(ordered.len());
+    let mut steps = Vec::with_capacity(ordered.len());
+    for (p, parameters) in ordered {
+        if skipped.contains(&p.id) {
+            continue;
+        }
+        let title = unique_title(&p, &mut used);
+        titles.insert(p.id, title.clone());
+        steps.push(FeedStep {
+            title,
+            previous: p.previous_id.and_then(|id| titles.get(&id)).cloned(),
+            plugin_instance: p,
+            parameters,
+        });
+    }
+    Ok(steps)
+}
+
+/// Create the plugin tree of a pipeline which would run the given plugin instances
+/// with the same parameters.
+///
+/// Pipings are titled and the root plugin instance is left out the same way as by
+/// [steps_of_feed], so that the pipeline can be run on other data.
+pub fn plugin_tree_of_feed(
+    plugin_instances: impl IntoIterator<
+        Item = (PluginInstanceResponse, Vec<PluginInstanceParameterResponse>),
+    >,
+    include_root: bool,
+) -> Result<Vec<TitleIndexedPiping>, InvalidFeedTree> {
+    let pipings = steps_of_feed(plugin_instances, include_root)?
+        .into_iter()
+        .map(|step| {
+            let p = step.plugin_instance;
+            let defaults: BTreeMap<_, _> = step
+                .parameters
+                .into_iter()
+                .map(|param| (param.param_name, param.value))
+                .collect();
+            TitleIndexedPiping {
+                title: step.title,
+                plugin: format!("{} v{}", p.plugin_name, p.plugin_version),
+                previous: step.previous,
+                plugin_parameter_defaults: Some(defaults).filter(|d| !d.is_empty()),
+            }
+        })
+        .collect();
+    Ok(pipings)
+}
+
+/// Title of a plugin instance, or its plugin name if it has none, suffixed by its ID
+/// if the title was already used.
+fn unique_title(p: &PluginInstanceResponse, used: &mut HashSet<String>) -> String {
+    let title = p.title.trim();
+    let title = if title.is_empty() {
+        p.plugin_name.as_str()
+    } else {
+        title
+    };
+    let title = if used.contains(title) {
+        format!("{} ({})", title, p.id.0)
+    } else {
+        title.to_string()
+    };
+    used.insert(title.clone());
+    title
+}
+
+/// Reorder plugin instances so that every plugin instance comes after its previous,
+/// otherwise their order is kept.
+fn order_by_previous<T>(
+    mut remaining: Vec<(PluginInstanceResponse, T)>,
+) -> Result<Vec<(PluginInstanceResponse, T)>, InvalidFeedTree> {
+    let mut done = HashSet::with_capacity(remaining.len());
+    let mut ordered = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let count = remaining.len();
+        let mut not_ready = Vec::new();
+        for item in remaining {
+            if item
+                .0
+                .previous_id
+                .map(|id| done.contains(&id))
+                .unwrap_or(true)
+            {
+                done.insert(item.0.id);
+                ordered.push(item);
+            } else {
+                not_ready.push(item);
+            }
+        }
+        if not_ready.len() == count {
+            let ids = not_ready.into_iter().map(|(p, _)| p.id).collect();
+            return Err(InvalidFeedTree::Cycle(ids));
+        }
+        remaining = not_ready;
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCube;
+    use crate::types::{ItemUrl, PluginParameterValue, PluginType};
+    use crate::PluginResponse;
+    use rstest::*;
+
+    fn plinst(
+        mock: &MockCube,
+        id: u32,
+        title: &str,
+        previous_id: Option<u32>,
+        params: &[(&str, serde_json::Value)],
+    ) -> (PluginInstanceResponse, Vec<PluginInstanceParameterResponse>) {
+        let plugin_type = if previous_id.is_none() {
+            PluginType::Fs
+        } else {
+            PluginType::Ds
+        };
+        let plugin = PluginResponse {
+            plugin_type,
+            ..mock.plugin(2, "pl-simpledsapp", "2.1.0")
+        };
+        let feed = mock.feed(1, "Feed");
+        let plugin_instance = PluginInstanceResponse {
+            title: title.to_string(),
+            previous: previous_id
+                .map(|p| ItemUrl::from(format!("{}plugins/instances/{}/", mock.url(), p))),
+            previous_id: previous_id.map(PluginInstanceId),
+            ..mock.plugin_instance(id, &plugin, &feed, None)
+        };
+        let plinst_url = plugin_instance.url.as_str();
+        let params = params
+            .iter()
+            .map(|(name, value)| {
+                serde_json::from_value(serde_json::json!({
+                    "url": "https://cube.example.org/api/v1/plugins/string-parameter/1/",
+                    "id": 1,
+                    "param_name": name,
+                    "value": value,
+                    "type": "string",
+                    "plugin_inst": plinst_url,
+                    "plugin_param": "https://cube.example.org/api/v1/plugins/parameters/1/",
+                }))
+                .unwrap()
+            })
+            .collect();
+        (plugin_instance, params)
+    }
+
+    fn summary(pipings: &[TitleIndexedPiping]) -> Vec<(&str, Option<&str>)> {
+        pipings
+            .iter()
+            .map(|p| (p.title.as_str(), p.previous.as_deref()))
+            .collect()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_plugin_tree_of_feed() {
+        let mock = MockCube::start().await;
+        let plugin_instances = [
+            plinst(&mock, 4, "b", Some(2), &[]),
+            plinst(
+                &mock,
+                2,
+                "",
+                Some(1),
+                &[("prefix", serde_json::json!("hello"))],
+            ),
+            plinst(
+                &mock,
+                1,
+                "upload",
+                None,
+                &[("dir", serde_json::json!("chris/uploads"))],
+            ),
+            plinst(&mock, 3, "b", Some(2), &[]),
+        ];
+        let pipings = plugin_tree_of_feed(plugin_instances, false).unwrap();
+        assert_eq!(
+            summary(&pipings),
+            vec![
+                ("pl-simpledsapp", None),
+                ("b", Some("pl-simpledsapp")),
+                ("b (4)", Some("pl-simpledsapp"))
+            ]
+        );
+        assert_eq!(pipings[0].plugin, "pl-simpledsapp v2.1.0");
+        assert_eq!(
+            pipings[0].plugin_parameter_defaults,
+            Some(BTreeMap::from([(
+                "prefix".to_string(),
+                PluginParameterValue::Stringish("hello".to_string())
+            )]))
+        );
+        assert_eq!(pipings[1].plugin_parameter_defaults, None);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_plugin_tree_of_feed_include_root() {
+        let mock = MockCube::start().await;
+        let plugin_instances = [
+            plinst(&mock, 1, "upload", None, &[]),
+            plinst(&mock, 2, "a", Some(1), &[]),
+        ];
+        let pipings = plugin_tree_of_feed(plugin_instances, true).unwrap();
+        assert_eq!(
+            summary(&pipings),
+            vec![("upload", None), ("a", Some("upload"))]
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_steps_of_feed_keeps_plugin_instances() {
+        let mock = MockCube::start().await;
+        let plugin_instances = [
+            plinst(
+                &mock,
+                2,
+                "",
+                Some(1),
+                &[("prefix", serde_json::json!("hello"))],
+            ),
+            plinst(&mock, 1, "", None, &[]),
+        ];
+        let steps = steps_of_feed(plugin_instances, true).unwrap();
+        let actual: Vec<_> = steps
+            .iter()
+            .map(|s| {
+                (
+                    s.plugin_instance.id.0,
+                    s.title.as_str(),
+                    s.previous.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                (1, "pl-simpledsapp", None),
+                (2, "pl-simpledsapp (2)", Some("pl-simpledsapp"))
+            ]
+        );
+        assert_eq!(steps[1].parameters[0].param_name, "prefix");
+    }
+
+    #[rstest]
+    #[case(
+        &[(1, "a", None), (2, "b", Some(1)), (3, "c", Some(1))],
+        false,
+        InvalidFeedTree::Roots(vec![PluginInstanceId(2), PluginInstanceId(3)])
+    )]
+    #[case(
+        &[(1, "a", None), (3, "c", Some(2))],
+        true,
+        InvalidFeedTree::MissingPrevious { id: PluginInstanceId(3), previous: PluginInstanceId(2) }
+    )]
+    #[case(
+        &[(1, "a", None), (2, "b", Some(3)), (3, "c", Some(2))],
+        true,
+        InvalidFeedTree::Cycle(vec![PluginInstanceId(2), PluginInstanceId(3)])
+    )]
+    #[tokio::test]
+    async fn test_invalid(
+        #[case] specs: &[(u32, &str, Option<u32>)],
+        #[case] include_root: bool,
+        #[case] expected: InvalidFeedTree,
+    ) {
+        let mock = MockCube::start().await;
+        let plugin_instances: Vec<_> = specs
+            .iter()
+            .map(|(id, title, previous_id)| plinst(&mock, *id, title, *previous_id, &[]))
+            .collect();
+        assert_eq!(
+            plugin_tree_of_feed(plugin_instances, include_root).unwrap_err(),
+            expected
+        )
+    }
+}
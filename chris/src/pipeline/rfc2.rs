@@ -0,0 +1,361 @@
+//! Pipelines in the format of
+//! [RFC #2](https://github.com/FNNDSC/CHRIS_docs/blob/master/rfcs/2-pipeline_yaml.adoc),
+//! where pipings refer to their previous piping by title, and plugins are specified
+//! as a name and version, e.g. `pl-dircopy v2.1.1`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::canon::{
+    CanonPipeline, ExpandedTreeParameter, ExpandedTreePipeline, ExpandedTreePiping,
+};
+use crate::types::{PipingId, PluginName, PluginParameterValue, PluginVersion};
+use crate::{PipelineResponse, PipingParameterResponse, PipingResponse};
+
+/// A pipeline where pipings are identified by their titles.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TitleIndexedPipeline {
+    pub authors: String,
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    #[serde(default)]
+    pub locked: bool,
+    pub plugin_tree: Vec<TitleIndexedPiping>,
+}
+
+/// A piping of a [TitleIndexedPipeline].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TitleIndexedPiping {
+    pub title: String,
+    /// Plugin name and version, e.g. `pl-dircopy v2.1.1`
+    pub plugin: String,
+    /// Title of the previous piping, `None` for the root.
+    #[serde(default)]
+    pub previous: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_parameter_defaults: Option<BTreeMap<String, PluginParameterValue>>,
+}
+
+/// Reasons why a [TitleIndexedPipeline] cannot be converted to an [ExpandedTreePipeline].
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum InvalidTitleIndexedPipeline {
+    #[error("Invalid plugin \"{plugin}\" of \"{title}\", it should be a name and version, e.g. \"pl-dircopy v2.1.1\"")]
+    InvalidPlugin { title: String, plugin: String },
+
+    #[error("Title \"{0}\" is used by more than one piping")]
+    DuplicateTitle(String),
+
+    #[error("Previous of \"{title}\" is \"{previous}\", but no piping has that title")]
+    UnknownPrevious { title: String, previous: String },
+
+    #[error("Pipeline must have exactly one root (a piping without previous), found {}", .0.len())]
+    Roots(Vec<String>),
+
+    #[error("Pipings form a cycle: {}", .0.join(", "))]
+    Cycle(Vec<String>),
+}
+
+impl InvalidTitleIndexedPipeline {
+    /// Title of the piping which is invalid, if the error is about one piping.
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            Self::InvalidPlugin { title, .. } => Some(title),
+            Self::DuplicateTitle(title) => Some(title),
+            Self::UnknownPrevious { title, .. } => Some(title),
+            Self::Roots(titles) => titles.get(1).map(|s| s.as_str()),
+            Self::Cycle(titles) => titles.first().map(|s| s.as_str()),
+        }
+    }
+}
+
+impl TitleIndexedPiping {
+    fn plugin_name_and_version(
+        &self,
+    ) -> Result<(PluginName, PluginVersion), InvalidTitleIndexedPipeline> {
+        self.plugin
+            .trim()
+            .rsplit_once(" v")
+            .map(|(name, version)| (name.trim(), version.trim()))
+            .filter(|(name, version)| !name.is_empty() && !version.is_empty())
+            .map(|(name, version)| (PluginName::from(name), PluginVersion::from(version)))
+            .ok_or_else(|| InvalidTitleIndexedPipeline::InvalidPlugin {
+                title: self.title.clone(),
+                plugin: self.plugin.clone(),
+            })
+    }
+}
+
+impl TitleIndexedPipeline {
+    /// Reconstruct a pipeline from what _CUBE_ has about it: its pipings and their
+    /// default parameters. Pipings are ordered by ID, and parameters without a value
+    /// are left out of `plugin_parameter_defaults`.
+    pub fn from_cube(
+        pipeline: &PipelineResponse,
+        pipings: impl IntoIterator<Item = PipingResponse>,
+        default_parameters: impl IntoIterator<Item = PipingParameterResponse>,
+    ) -> Self {
+        let mut pipings: Vec<_> = pipings.into_iter().collect();
+        pipings.sort_by_key(|p| p.id.0);
+        let titles: HashMap<PipingId, &str> =
+            pipings.iter().map(|p| (p.id, p.title.as_str())).collect();
+        let mut defaults: HashMap<PipingId, BTreeMap<String, PluginParameterValue>> =
+            HashMap::new();
+        for param in default_parameters {
+            if let Some(value) = param.value {
+                defaults
+                    .entry(param.plugin_piping_id)
+                    .or_default()
+                    .insert(param.param_name, value);
+            }
+        }
+        let plugin_tree = pipings
+            .iter()
+            .map(|p| TitleIndexedPiping {
+                title: p.title.clone(),
+                plugin: format!("{} v{}", p.plugin_name, p.plugin_version),
+                previous: p
+                    .previous_id
+                    .and_then(|id| titles.get(&id))
+                    .map(|t| t.to_string()),
+                plugin_parameter_defaults: defaults.remove(&p.id),
+            })
+            .collect();
+        Self {
+            authors: pipeline.authors.clone(),
+            name: pipeline.name.clone(),
+            description: pipeline.description.clone(),
+            category: pipeline.category.clone(),
+            locked: pipeline.locked,
+            plugin_tree,
+        }
+    }
+}
+
+impl TryFrom<TitleIndexedPipeline> for ExpandedTreePipeline {
+    type Error = InvalidTitleIndexedPipeline;
+
+    fn try_from(p: TitleIndexedPipeline) -> Result<Self, Self::Error> {
+        let plugin_tree = expand_tree(p.plugin_tree)?;
+        Ok(Self {
+            authors: p.authors,
+            name: p.name,
+            description: p.description,
+            category: p.category,
+            locked: p.locked,
+            plugin_tree,
+        })
+    }
+}
+
+impl TryFrom<TitleIndexedPipeline> for CanonPipeline {
+    type Error = InvalidTitleIndexedPipeline;
+
+    fn try_from(p: TitleIndexedPipeline) -> Result<Self, Self::Error> {
+        ExpandedTreePipeline::try_from(p).map(CanonPipeline::from)
+    }
+}
+
+/// Replace titles of previous pipings with indices. Pipings are reordered so that every
+/// piping comes after its previous, otherwise their order is kept.
+fn expand_tree(
+    pipings: Vec<TitleIndexedPiping>,
+) -> Result<Vec<ExpandedTreePiping>, InvalidTitleIndexedPipeline> {
+    let mut titles = HashSet::with_capacity(pipings.len());
+    for piping in &pipings {
+        if !titles.insert(piping.title.as_str()) {
+            return Err(InvalidTitleIndexedPipeline::DuplicateTitle(
+                piping.title.clone(),
+            ));
+        }
+    }
+    for piping in &pipings {
+        if let Some(previous) = &piping.previous {
+            if !titles.contains(previous.as_str()) {
+                return Err(InvalidTitleIndexedPipeline::UnknownPrevious {
+                    title: piping.title.clone(),
+                    previous: previous.clone(),
+                });
+            }
+        }
+    }
+    let roots: Vec<_> = pipings
+        .iter()
+        .filter(|p| p.previous.is_none())
+        .map(|p| p.title.clone())
+        .collect();
+    if roots.len() != 1 {
+        return Err(InvalidTitleIndexedPipeline::Roots(roots));
+    }
+
+    let mut indices: HashMap<String, usize> = HashMap::with_capacity(pipings.len());
+    let mut expanded = Vec::with_capacity(pipings.len());
+    let mut remaining = pipings;
+    while !remaining.is_empty() {
+        let count = remaining.len();
+        let mut not_ready = Vec::new();
+        for piping in remaining {
+            let previous_index = match &piping.previous {
+                None => None,
+                Some(previous) => match indices.get(previous) {
+                    Some(i) => Some(*i),
+                    None => {
+                        not_ready.push(piping);
+                        continue;
+                    }
+                },
+            };
+            let (plugin_name, plugin_version) = piping.plugin_name_and_version()?;
+            let plugin_parameter_defaults = piping.plugin_parameter_defaults.map(|defaults| {
+                defaults
+                    .into_iter()
+                    .map(|(name, default)| ExpandedTreeParameter { name, default })
+                    .collect()
+            });
+            indices.insert(piping.title.clone(), expanded.len());
+            expanded.push(ExpandedTreePiping {
+                plugin_name,
+                plugin_version,
+                previous_index,
+                title: Some(piping.title),
+                plugin_parameter_defaults,
+            });
+        }
+        if not_ready.len() == count {
+            let titles = not_ready.into_iter().map(|p| p.title).collect();
+            return Err(InvalidTitleIndexedPipeline::Cycle(titles));
+        }
+        remaining = not_ready;
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn piping(title: &str, plugin: &str, previous: Option<&str>) -> TitleIndexedPiping {
+        TitleIndexedPiping {
+            title: title.to_string(),
+            plugin: plugin.to_string(),
+            previous: previous.map(|s| s.to_string()),
+            plugin_parameter_defaults: None,
+        }
+    }
+
+    #[rstest]
+    fn test_children_before_parents_are_reordered() {
+        let pipings = vec![
+            piping("c", "pl-c v1.0.0", Some("b")),
+            piping("b", "pl-b v1.0.0", Some("a")),
+            piping("a", "pl-a v1.0.0", None),
+        ];
+        let expanded = expand_tree(pipings).unwrap();
+        let actual: Vec<_> = expanded
+            .iter()
+            .map(|p| (p.title.as_deref().unwrap(), p.previous_index))
+            .collect();
+        assert_eq!(actual, vec![("a", None), ("b", Some(0)), ("c", Some(1))]);
+        assert_eq!(expanded[2].plugin_name.as_str(), "pl-c");
+        assert_eq!(expanded[2].plugin_version.as_str(), "1.0.0");
+    }
+
+    #[rstest]
+    fn test_from_cube() {
+        let pipeline: PipelineResponse = serde_json::from_value(serde_json::json!({
+            "url": "https://cube.example.org/api/v1/pipelines/3/",
+            "id": 3,
+            "name": "Example",
+            "locked": false,
+            "authors": "FNNDSC <dev@babyMRI.org>",
+            "category": "Example",
+            "description": "An example pipeline",
+            "owner_username": "chris",
+            "creation_date": "2024-03-01T14:06:10.372393-05:00",
+            "modification_date": "2024-03-01T14:06:10.372393-05:00",
+            "plugins": "https://cube.example.org/api/v1/pipelines/3/plugins/",
+            "plugin_pipings": "https://cube.example.org/api/v1/pipelines/3/pipings/",
+            "default_parameters": "https://cube.example.org/api/v1/pipelines/3/parameters/",
+            "instances": "https://cube.example.org/api/v1/pipelines/3/instances/",
+            "workflows": "https://cube.example.org/api/v1/pipelines/3/workflows/"
+        }))
+        .unwrap();
+        let cube_piping = |id: u32, title: &str, previous_id: Option<u32>| -> PipingResponse {
+            serde_json::from_value(serde_json::json!({
+                "url": format!("https://cube.example.org/api/v1/pipelines/pipings/{id}/"),
+                "id": id,
+                "title": title,
+                "previous_id": previous_id,
+                "plugin_id": 1,
+                "plugin_name": "pl-simpledsapp",
+                "plugin_version": "2.1.0",
+                "pipeline_id": 3
+            }))
+            .unwrap()
+        };
+        let cube_param = |piping_id: u32, name: &str, value: serde_json::Value| {
+            serde_json::from_value::<PipingParameterResponse>(serde_json::json!({
+                "url": "https://cube.example.org/api/v1/pipelines/string-parameter/1/",
+                "id": 1,
+                "param_name": name,
+                "param_id": 1,
+                "value": value,
+                "type": "string",
+                "plugin_piping_id": piping_id,
+                "plugin_piping_title": "",
+                "plugin_name": "pl-simpledsapp",
+                "plugin_version": "2.1.0"
+            }))
+            .unwrap()
+        };
+        let pipings = [
+            cube_piping(12, "child", Some(10)),
+            cube_piping(10, "root", None),
+        ];
+        let params = [
+            cube_param(12, "prefix", serde_json::json!("hello")),
+            cube_param(12, "ignore", serde_json::Value::Null),
+            cube_param(10, "ignore", serde_json::Value::Null),
+        ];
+        let actual = TitleIndexedPipeline::from_cube(&pipeline, pipings, params);
+        assert_eq!(actual.name, "Example");
+        let root = piping("root", "pl-simpledsapp v2.1.0", None);
+        let mut child = piping("child", "pl-simpledsapp v2.1.0", Some("root"));
+        child.plugin_parameter_defaults = Some(BTreeMap::from([(
+            "prefix".to_string(),
+            PluginParameterValue::Stringish("hello".to_string()),
+        )]));
+        assert_eq!(actual.plugin_tree, vec![root, child]);
+        assert!(ExpandedTreePipeline::try_from(actual).is_ok());
+    }
+
+    #[rstest]
+    #[case(
+        vec![piping("a", "pl-a v1", None), piping("a", "pl-b v1", Some("a"))],
+        InvalidTitleIndexedPipeline::DuplicateTitle("a".to_string())
+    )]
+    #[case(
+        vec![piping("a", "pl-a v1", None), piping("b", "pl-b v1", Some("z"))],
+        InvalidTitleIndexedPipeline::UnknownPrevious { title: "b".to_string(), previous: "z".to_string() }
+    )]
+    #[case(
+        vec![piping("a", "pl-a v1", None), piping("b", "pl-b v1", None)],
+        InvalidTitleIndexedPipeline::Roots(vec!["a".to_string(), "b".to_string()])
+    )]
+    #[case(
+        vec![piping("a", "pl-a v1", None), piping("b", "pl-b v1", Some("c")), piping("c", "pl-c v1", Some("b"))],
+        InvalidTitleIndexedPipeline::Cycle(vec!["b".to_string(), "c".to_string()])
+    )]
+    #[case(
+        vec![piping("a", "pl-a", None)],
+        InvalidTitleIndexedPipeline::InvalidPlugin { title: "a".to_string(), plugin: "pl-a".to_string() }
+    )]
+    fn test_invalid(
+        #[case] pipings: Vec<TitleIndexedPiping>,
+        #[case] expected: InvalidTitleIndexedPipeline,
+    ) {
+        assert_eq!(expand_tree(pipings).unwrap_err(), expected)
+    }
+}
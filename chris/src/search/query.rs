@@ -62,4 +62,11 @@ impl<T: DeserializeOwned, A: Access> QueryBuilder<T, A> {
         self.query.insert(key, QueryValue::U32(value));
         self
     }
+
+    /// The query string of this search, with parameters sorted by name.
+    #[cfg(test)]
+    pub(crate) fn query_string(&self) -> String {
+        let sorted: std::collections::BTreeMap<_, _> = self.query.iter().collect();
+        serde_urlencoded::to_string(sorted).unwrap()
+    }
 }
@@ -2,14 +2,17 @@
 
 use crate::errors::{check, CubeError};
 use crate::models::LinkedModel;
-use crate::types::CollectionUrl;
+use crate::types::{CollectionUrl, CubeUrl};
 use crate::{Access, RoAccess, RwAccess};
-use async_stream::{stream, try_stream};
+use async_stream::try_stream;
 use futures::Stream;
 use reqwest_middleware::ClientWithMiddleware;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
 
 /// An abstraction over collection APIs, i.e. paginated API endpoints which return a `results` list.
 ///
@@ -37,6 +40,86 @@ struct ActualSearch<R: DeserializeOwned, A: Access> {
 
     /// Whether to append "search/" to the URL.
     is_search: bool,
+
+    /// Page size enforced by CUBE, if it is lower than what was requested. Zero if unknown.
+    effective_limit: AtomicU32,
+    /// Whether a [SearchWarning::PageLimitCapped] was already emitted.
+    warned: AtomicBool,
+    warning_sink: Option<WarningSink>,
+
+    /// Scheme, host, and port to use for the `next` links of pages, see [PaginationOrigin].
+    origin: Option<reqwest::Url>,
+}
+
+/// The URL of _CUBE_ which a client was configured with, added to all of its
+/// requests unless disabled by its builder.
+///
+/// Behind a misconfigured reverse proxy, _CUBE_ might give `next` links which point to
+/// its internal hostname. Searches of the client rewrite the scheme, host, and port of
+/// these links to those of this URL, keeping their path and query.
+#[derive(Clone, Debug)]
+pub(crate) struct PaginationOrigin(reqwest::Url);
+
+impl PaginationOrigin {
+    /// Add [PaginationOrigin] of `url` to the requests of the client built by `builder`.
+    pub(crate) fn init(
+        builder: reqwest_middleware::ClientBuilder,
+        url: &CubeUrl,
+    ) -> reqwest_middleware::ClientBuilder {
+        match reqwest::Url::parse(url.as_str()) {
+            Ok(url) => builder.with_init(reqwest_middleware::Extension(Self(url))),
+            Err(_) => builder,
+        }
+    }
+
+    /// Get the [PaginationOrigin] which `client` adds to its requests, if any.
+    fn of(client: &ClientWithMiddleware, base_url: &CollectionUrl) -> Option<reqwest::Url> {
+        let mut request = client.get(base_url.as_str());
+        request.extensions().get::<Self>().map(|o| o.0.clone())
+    }
+}
+
+/// Change the scheme, host, and port of `url` to those of `origin`.
+fn set_origin(url: &mut reqwest::Url, origin: &reqwest::Url) {
+    // these only fail for URLs which cannot be the URL of CUBE, e.g. file:// URLs
+    let _ = url.set_scheme(origin.scheme());
+    let _ = url.set_host(origin.host_str());
+    let _ = url.set_port(origin.port());
+}
+
+/// A problem noticed during a search which does not stop it from producing items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchWarning {
+    /// CUBE returned fewer items per page than requested by [Search::page_limit].
+    PageLimitCapped { requested: u32, effective: u32 },
+}
+
+impl Display for SearchWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchWarning::PageLimitCapped {
+                requested,
+                effective,
+            } => write!(
+                f,
+                "CUBE returned {effective} items per page even though {requested} were requested, \
+                subsequent pages will be requested {effective} at a time"
+            ),
+        }
+    }
+}
+
+/// Receiver of [SearchWarning]s.
+pub type WarningSink = Arc<dyn Fn(&SearchWarning) + Send + Sync>;
+
+static DEFAULT_WARNING_SINK: OnceLock<WarningSink> = OnceLock::new();
+
+/// Set the receiver of [SearchWarning]s for searches which were not given
+/// one by [Search::warning_sink]. By default, warnings are discarded.
+///
+/// The default sink can only be set once. Returns `false` if it was already set.
+pub fn set_default_warning_sink(sink: WarningSink) -> bool {
+    DEFAULT_WARNING_SINK.set(sink).is_ok()
 }
 
 impl<R: DeserializeOwned, A: Access> ActualSearch<R, A> {
@@ -64,7 +147,85 @@ impl<R: DeserializeOwned, A: Access> ActualSearch<R, A> {
             query: self.query,
             phantom: Default::default(),
             is_search: self.is_search,
+            effective_limit: self.effective_limit,
+            warned: self.warned,
+            warning_sink: self.warning_sink,
+            origin: self.origin,
+        }
+    }
+
+    /// The page size given to [Search::page_limit].
+    fn requested_limit(&self) -> Option<u32> {
+        match self.query.get("limit") {
+            Some(QueryValue::U32(limit)) if self.is_search => Some(*limit),
+            _ => None,
+        }
+    }
+
+    /// See [Search::effective_page_limit]
+    fn effective_limit(&self) -> Option<u32> {
+        match self.effective_limit.load(Ordering::Relaxed) {
+            0 => self.requested_limit(),
+            cap => Some(cap),
+        }
+    }
+
+    /// Detect whether CUBE returned a page which is smaller than what was asked for,
+    /// despite there being more items after it.
+    fn check_page_size(&self, received: usize, has_next: bool) {
+        let requested = if let Some(limit) = self.effective_limit() {
+            limit
+        } else {
+            return;
+        };
+        if !has_next || received == 0 || received >= requested as usize {
+            return;
+        }
+        let effective = received as u32;
+        self.effective_limit.store(effective, Ordering::Relaxed);
+        if !self.warned.swap(true, Ordering::Relaxed) {
+            let warning = SearchWarning::PageLimitCapped {
+                requested,
+                effective,
+            };
+            if let Some(sink) = self.warning_sink.as_ref().or(DEFAULT_WARNING_SINK.get()) {
+                sink(&warning)
+            }
+        }
+    }
+
+    /// Get the URL of the next page from the `next` link of a page. The link may be
+    /// relative, and its scheme and host are changed to those of [PaginationOrigin].
+    fn next_url(&self, next: String, received: usize) -> Result<reqwest::Url, CubeError> {
+        let parsed = match reqwest::Url::parse(self.base_url.as_str()) {
+            Ok(base) => base.join(&next),
+            Err(_) => reqwest::Url::parse(&next),
+        };
+        let mut url = parsed.map_err(|source| CubeError::InvalidNextUrl { url: next, source })?;
+        if let Some(origin) = &self.origin {
+            set_origin(&mut url, origin);
+        }
+        self.adjust_next_url(&mut url, received);
+        Ok(url)
+    }
+
+    /// If CUBE caps the page size, rewrite the `next` URL to ask for pages of the
+    /// effective size, starting after the `received` items yielded so far.
+    fn adjust_next_url(&self, url: &mut reqwest::Url, received: usize) {
+        let cap = self.effective_limit.load(Ordering::Relaxed);
+        if cap == 0 {
+            return;
         }
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(k, _)| k != "limit" && k != "offset")
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        url.query_pairs_mut()
+            .clear()
+            .extend_pairs(pairs)
+            .append_pair("limit", &cap.to_string())
+            .append_pair("offset", &received.to_string());
     }
 
     /// See [Search::get_count]
@@ -74,6 +235,13 @@ impl<R: DeserializeOwned, A: Access> ActualSearch<R, A> {
         Ok(data.count)
     }
 
+    /// See [Search::is_empty]
+    async fn is_empty(&self) -> Result<bool, CubeError> {
+        let res = self.get_search().query(&LIMIT_ONE).send().await?;
+        let data: HasResults = check(res).await?.json().await?;
+        Ok(data.results.is_empty())
+    }
+
     /// See [Search::get_first]
     async fn get_first(&self) -> Result<Option<LinkedModel<R, A>>, CubeError> {
         let res = self.get_search().query(&LIMIT_ONE).send().await?;
@@ -107,28 +275,46 @@ impl<R: DeserializeOwned, A: Access> ActualSearch<R, A> {
         }
     }
 
-    /// See [Search::stream]
-    fn stream(&self) -> impl Stream<Item = Result<R, CubeError>> + '_ {
+    /// Retrieve the first page.
+    ///
+    /// Retrieval of the first page works a little differently, since we don't know
+    /// what `next_url` is, we call client.get(...).query(...) instead of client.get(next_url)
+    async fn first_page(&self) -> Result<Paginated<R>, CubeError> {
+        let res = self.get_search().send().await?;
+        let page: Paginated<R> = check(res).await?.json().await?;
+        self.check_page_size(page.results.len(), page.next.is_some());
+        Ok(page)
+    }
+
+    /// See [Search::stream_pages]
+    fn stream_pages(&self) -> impl Stream<Item = Result<Vec<R>, CubeError>> + '_ {
         try_stream! {
-            // retrieval of the first page works a little differently, since we
-            // don't know what `next_url` is, we call client.get(...).query(...)
-            // instead of client.get(next_url)
-            let res = self.get_search().send().await?;
-            let page: Paginated<R> = check(res).await?.json().await?;
-            for item in page.results {
-                yield item;
+            let first = self.first_page().await?;
+            for await page in self.pages_from(first) {
+                yield page?;
             }
+        }
+    }
+
+    /// Produce the `first` page, then retrieve and produce the pages after it.
+    fn pages_from(
+        &self,
+        first: Paginated<R>,
+    ) -> impl Stream<Item = Result<Vec<R>, CubeError>> + '_ {
+        try_stream! {
+            let mut received = first.results.len();
+            let mut next_url = first.next;
+            yield first.results;
 
-            let mut next_url = page.next;
             // subsequent pages after the first are retrieved using a loop.
             while let Some(u) = next_url {
-                let res = self.client.get(&u).send().await?;
+                let u = self.next_url(u, received)?;
+                let res = self.client.get(u).send().await?;
                 let page: Paginated<R> = check(res).await?.json().await?;
-
-                for item in page.results {
-                    yield item;
-                }
+                self.check_page_size(page.results.len(), page.next.is_some());
+                received += page.results.len();
                 next_url = page.next;
+                yield page.results;
             }
         }
     }
@@ -141,12 +327,17 @@ impl<R: DeserializeOwned, A: Access> Search<R, A> {
         query: HashMap<&'static str, QueryValue>,
         is_search: bool,
     ) -> Self {
+        let origin = PaginationOrigin::of(&client, &base_url);
         let actual = ActualSearch {
             client,
             base_url,
             query,
             is_search,
             phantom: Default::default(),
+            effective_limit: AtomicU32::new(0),
+            warned: AtomicBool::new(false),
+            warning_sink: None,
+            origin,
         };
         Self {
             actual: Some(actual),
@@ -198,6 +389,33 @@ impl<R: DeserializeOwned, A: Access> Search<R, A> {
         }
     }
 
+    /// Set the number of items per page produced by [Self::stream_pages].
+    /// Same as [Self::page_limit].
+    pub fn page_size(self, size: u32) -> Self {
+        self.page_limit(size)
+    }
+
+    /// Set the receiver of [SearchWarning]s for this search, instead of the default
+    /// set by [set_default_warning_sink].
+    pub fn warning_sink(self, sink: WarningSink) -> Self {
+        Self {
+            actual: self.actual.map(|a| ActualSearch {
+                warning_sink: Some(sink),
+                ..a
+            }),
+            ..self
+        }
+    }
+
+    /// The number of items which are requested per page.
+    ///
+    /// Some deployments of CUBE cap the page size to a value lower than [Self::page_limit].
+    /// After a capped page is received, this returns the cap, and the rest of the pages are
+    /// requested using it. Returns `None` if no page limit was set nor detected.
+    pub fn effective_page_limit(&self) -> Option<u32> {
+        self.actual.as_ref().and_then(|a| a.effective_limit())
+    }
+
     /// Set the maximum number of items to yield.
     ///
     /// See also: [Self::page_limit]
@@ -217,6 +435,18 @@ impl<R: DeserializeOwned, A: Access> Search<R, A> {
         }
     }
 
+    /// Whether this collection has no items.
+    ///
+    /// Unlike `get_count().await? == 0`, this does not depend on the count, which some
+    /// versions of _CUBE_ are slow to compute for large collections.
+    pub async fn is_empty(&self) -> Result<bool, CubeError> {
+        if let Some(search) = &self.actual {
+            search.is_empty().await
+        } else {
+            Ok(true)
+        }
+    }
+
     /// Get the first item from this collection.
     ///
     /// See also: [Search::get_only]
@@ -241,19 +471,50 @@ impl<R: DeserializeOwned, A: Access> Search<R, A> {
         }
     }
 
+    /// Produce the pages of this collection, as they are returned by _CUBE_.
+    /// Useful for processing items in batches.
+    ///
+    /// The size of pages is set by [Self::page_size]. The last page is truncated
+    /// so that no more than [Self::max_items] are produced in total.
+    pub fn stream_pages(&self) -> impl Stream<Item = Result<Vec<R>, CubeError>> + '_ {
+        try_stream! {
+            if let Some(search) = &self.actual {
+                for await page in self.truncate_pages(search.stream_pages()) {
+                    yield page?;
+                }
+            }
+        }
+    }
+
+    /// Truncate `pages` so that no more than [Self::max_items] are produced in total.
+    /// If [Self::max_items] is zero, `pages` is not polled at all.
+    fn truncate_pages<'a>(
+        &'a self,
+        pages: impl Stream<Item = Result<Vec<R>, CubeError>> + 'a,
+    ) -> impl Stream<Item = Result<Vec<R>, CubeError>> + 'a {
+        try_stream! {
+            let mut remaining = self.max_items.unwrap_or(usize::MAX);
+            if remaining > 0 {
+                for await page in pages {
+                    let mut page = page?;
+                    page.truncate(remaining);
+                    remaining -= page.len();
+                    yield page;
+                    if remaining == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
     /// Produce items from this collection. Pagination is handled transparently,
     /// i.e. HTTP GET requests are sent as-needed.
     pub fn stream(&self) -> impl Stream<Item = Result<R, CubeError>> + '_ {
-        stream! {
-            let mut count = 0;
-            let max_count = self.max_items.unwrap_or(usize::MAX);
-            if let Some(search) = &self.actual {
-                for await item in search.stream() {
-                    if count >= max_count {
-                        return;
-                    }
+        try_stream! {
+            for await page in self.stream_pages() {
+                for item in page? {
                     yield item;
-                    count += 1;
                 }
             }
         }
@@ -266,12 +527,59 @@ impl<R: DeserializeOwned, A: Access> Search<R, A> {
     ) -> impl Stream<Item = Result<LinkedModel<R, A>, CubeError>> + '_ {
         try_stream! {
             if let Some(search) = &self.actual {
-                for await item in search.stream() {
+                for await item in self.stream() {
                     yield LinkedModel { client: search.client.clone(), object: item?, phantom: Default::default() }
                 }
             }
         }
     }
+
+    /// Get the count of items in this collection, and produce its items.
+    ///
+    /// The count is read from the first page, which is retrieved before this returns,
+    /// so it costs one request less than calling [Self::get_count] before [Self::stream].
+    /// Like [Self::get_count], the count does not consider [Self::max_items].
+    pub async fn stream_with_count(
+        &self,
+    ) -> Result<(usize, impl Stream<Item = Result<R, CubeError>> + '_), CubeError> {
+        let first = if let Some(search) = &self.actual {
+            Some(search.first_page().await?)
+        } else {
+            None
+        };
+        let count = first.as_ref().map(|page| page.count as usize).unwrap_or(0);
+        let stream = try_stream! {
+            if let (Some(search), Some(first)) = (&self.actual, first) {
+                for await page in self.truncate_pages(search.pages_from(first)) {
+                    for item in page? {
+                        yield item;
+                    }
+                }
+            }
+        };
+        Ok((count, stream))
+    }
+
+    /// Like [Self::stream_with_count], but produces items like [Self::stream_connected].
+    pub async fn stream_connected_with_count(
+        &self,
+    ) -> Result<
+        (
+            usize,
+            impl Stream<Item = Result<LinkedModel<R, A>, CubeError>> + '_,
+        ),
+        CubeError,
+    > {
+        let (count, items) = self.stream_with_count().await?;
+        let stream = try_stream! {
+            if let Some(search) = &self.actual {
+                for await item in items {
+                    yield LinkedModel { client: search.client.clone(), object: item?, phantom: Default::default() }
+                }
+            }
+        };
+        Ok((count, stream))
+    }
 }
 
 impl<R: DeserializeOwned> ActualSearch<R, RwAccess> {
@@ -282,6 +590,10 @@ impl<R: DeserializeOwned> ActualSearch<R, RwAccess> {
             query: self.query,
             phantom: Default::default(),
             is_search: self.is_search,
+            effective_limit: self.effective_limit,
+            warned: self.warned,
+            warning_sink: self.warning_sink,
+            origin: self.origin,
         }
     }
 }
@@ -351,3 +663,300 @@ pub(crate) const LIMIT_ONE: PaginationQuery = PaginationQuery {
 struct HasCount {
     count: usize,
 }
+
+/// A HTTP JSON response which has a results field, the items of which are not needed.
+#[derive(Deserialize)]
+struct HasResults {
+    results: Vec<serde::de::IgnoredAny>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCube;
+    use futures::{StreamExt, TryStreamExt};
+    use rstest::*;
+    use std::sync::Mutex;
+
+    const TOTAL: usize = 230;
+    const CAP: usize = 50;
+
+    /// A collection API of [TOTAL] numbers which, like a CUBE configured with a low
+    /// `max_limit`, never returns more than [CAP] items per page.
+    ///
+    /// Returns the mock CUBE and the URL of the collection.
+    async fn capped_server() -> (MockCube, CollectionUrl) {
+        let mock = MockCube::start().await;
+        mock.set_max_limit(CAP);
+        let url = mock.add_items("numbers/", 0..TOTAL);
+        (mock, url)
+    }
+
+    /// Like [capped_server], but the `next` links start with what `next_base` returns
+    /// given the API URL, like a CUBE behind a misconfigured reverse proxy.
+    async fn capped_server_with_next(
+        next_base: impl FnOnce(&str) -> String,
+    ) -> (MockCube, CollectionUrl) {
+        let (mock, url) = capped_server().await;
+        mock.set_pagination_base(next_base(mock.url().as_str()));
+        (mock, url)
+    }
+
+    fn search_of(
+        url: CollectionUrl,
+        limit: u32,
+    ) -> (Search<usize, RoAccess>, Arc<Mutex<Vec<SearchWarning>>>) {
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+        search_using(client, url, limit)
+    }
+
+    fn search_using(
+        client: ClientWithMiddleware,
+        url: CollectionUrl,
+        limit: u32,
+    ) -> (Search<usize, RoAccess>, Arc<Mutex<Vec<SearchWarning>>>) {
+        let query = HashMap::from([("name", QueryValue::String("numbers".to_string()))]);
+        let warnings: Arc<Mutex<Vec<SearchWarning>>> = Default::default();
+        let sink = Arc::clone(&warnings);
+        let search = Search::with_query(client, url, query)
+            .page_limit(limit)
+            .warning_sink(Arc::new(move |w| sink.lock().unwrap().push(w.clone())));
+        (search, warnings)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_capped_page_limit_retrieves_everything() {
+        let (mock, url) = capped_server().await;
+        let (search, warnings) = search_of(url, 100);
+        assert_eq!(search.effective_page_limit(), Some(100));
+
+        let items: Vec<usize> = search.stream().try_collect().await.unwrap();
+        assert_eq!(items, (0..TOTAL).collect::<Vec<_>>());
+        assert_eq!(search.effective_page_limit(), Some(CAP as u32));
+        assert_eq!(
+            *warnings.lock().unwrap(),
+            vec![SearchWarning::PageLimitCapped {
+                requested: 100,
+                effective: CAP as u32
+            }]
+        );
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 5);
+        for query in &requests[1..] {
+            assert!(query.contains("limit=50"), "{query}");
+            assert!(query.contains("name=numbers"), "{query}");
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_capped_page_limit_max_items() {
+        let (mock, url) = capped_server().await;
+        let (search, warnings) = search_of(url, 100);
+        let items: Vec<usize> = search.max_items(120).stream().try_collect().await.unwrap();
+        assert_eq!(items, (0..120).collect::<Vec<_>>());
+        assert_eq!(mock.requests().len(), 3);
+        assert_eq!(warnings.lock().unwrap().len(), 1);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_stream_pages() {
+        let (mock, url) = capped_server().await;
+        let (search, _) = search_of(url, 40);
+        let pages: Vec<Vec<usize>> = search.stream_pages().try_collect().await.unwrap();
+        let sizes: Vec<_> = pages.iter().map(|page| page.len()).collect();
+        assert_eq!(sizes, vec![40, 40, 40, 40, 40, 30]);
+        assert_eq!(pages.concat(), (0..TOTAL).collect::<Vec<_>>());
+
+        let requests = mock.requests();
+        assert!(!requests[0].contains("offset="), "{}", requests[0]);
+        for (i, query) in requests[1..].iter().enumerate() {
+            let offset = format!("offset={}", (i + 1) * 40);
+            assert!(query.contains(&offset), "{query}");
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_stream_pages_max_items_truncates_page() {
+        let (mock, url) = capped_server().await;
+        let (search, _) = search_of(url, 40);
+        let search = search.max_items(90);
+        let pages: Vec<Vec<usize>> = search.stream_pages().try_collect().await.unwrap();
+        assert_eq!(pages.last().unwrap(), &(80..90).collect::<Vec<_>>());
+        assert_eq!(pages.concat().len(), 90);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_stream_pages_of_page_size() {
+        let (mock, url) = capped_server().await;
+        let (search, _) = search_of(url, 100);
+        let search = search.page_size(50);
+        let pages: Vec<Vec<usize>> = search.stream_pages().try_collect().await.unwrap();
+        let sizes: Vec<_> = pages.iter().map(|page| page.len()).collect();
+        assert_eq!(sizes, vec![50, 50, 50, 50, 30]);
+        assert_eq!(search.effective_page_limit(), Some(50));
+        assert_eq!(mock.requests().len(), 5);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_uncapped_page_limit_does_not_warn() {
+        let (mock, url) = capped_server().await;
+        let (search, warnings) = search_of(url, 40);
+        let items: Vec<usize> = search.stream().try_collect().await.unwrap();
+        assert_eq!(items.len(), TOTAL);
+        assert_eq!(search.effective_page_limit(), Some(40));
+        assert_eq!(mock.requests().len(), 6);
+        assert!(warnings.lock().unwrap().is_empty());
+    }
+
+    #[rstest]
+    #[case(None, TOTAL, 5)]
+    #[case(Some(120), 120, 3)]
+    #[case(Some(0), 0, 1)]
+    #[tokio::test]
+    async fn test_stream_with_count(
+        #[case] max_items: Option<usize>,
+        #[case] expected_items: usize,
+        #[case] expected_requests: usize,
+    ) {
+        let (mock, url) = capped_server().await;
+        let (search, _) = search_of(url, 100);
+        let search = match max_items {
+            Some(max) => search.max_items(max),
+            None => search,
+        };
+        let (count, stream) = search.stream_with_count().await.unwrap();
+        assert_eq!(count, TOTAL);
+        assert_eq!(mock.requests().len(), 1, "first page is retrieved eagerly");
+        let items: Vec<usize> = stream.try_collect().await.unwrap();
+        assert_eq!(items, (0..expected_items).collect::<Vec<_>>());
+        assert_eq!(
+            mock.requests().len(),
+            expected_requests,
+            "count costs no extra request"
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_stream_connected_with_count_of_empty() {
+        let mock = MockCube::start().await;
+        let url = mock.add_items("numbers/", Vec::<usize>::new());
+        let (search, _) = search_of(url, 100);
+        let (count, stream) = search.stream_connected_with_count().await.unwrap();
+        let items: Vec<_> = stream.try_collect().await.unwrap();
+        assert_eq!(count, 0);
+        assert!(items.is_empty());
+        assert_eq!(mock.requests().len(), 1);
+
+        let empty = Search::<usize, RoAccess>::empty();
+        let (count, stream) = empty.stream_connected_with_count().await.unwrap();
+        assert_eq!((count, stream.count().await), (0, 0));
+    }
+
+    #[rstest]
+    #[case(0, true)]
+    #[case(1, false)]
+    #[case(TOTAL, false)]
+    #[tokio::test]
+    async fn test_is_empty(#[case] size: usize, #[case] expected: bool) {
+        let mock = MockCube::start().await;
+        let url = mock.add_items("numbers/", 0..size);
+        let (search, _) = search_of(url, 100);
+        assert_eq!(search.is_empty().await.unwrap(), expected);
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("limit=1"), "{}", requests[0]);
+    }
+
+    /// Search using a client which rewrites `next` links to the origin of the CUBE of `url`.
+    fn search_behind_proxy(url: CollectionUrl, limit: u32) -> Search<usize, RoAccess> {
+        let (cube, _) = url.as_str().split_once("/api/v1/").unwrap();
+        let cube = CubeUrl::new(format!("{cube}/api/v1/")).unwrap();
+        let builder = reqwest_middleware::ClientBuilder::new(reqwest::Client::new());
+        let client = PaginationOrigin::init(builder, &cube).build();
+        search_using(client, url, limit).0
+    }
+
+    /// Replace the scheme, host, and port of a URL with ones where nothing is listening.
+    fn internal_host(base: &str) -> String {
+        let (_, path) = base.split_once("/api/v1/").unwrap();
+        format!("https://127.0.0.2:1/api/v1/{path}")
+    }
+
+    #[rstest]
+    #[case(
+        "https://cube.example.com/api/v1/",
+        "http://cube.example.com/api/v1/feeds/?limit=10&offset=10",
+        "https://cube.example.com/api/v1/feeds/?limit=10&offset=10"
+    )]
+    #[case(
+        "https://cube.example.com/api/v1/",
+        "http://chris-backend:8000/api/v1/plugins/search/?name=pl-dircopy&offset=20",
+        "https://cube.example.com/api/v1/plugins/search/?name=pl-dircopy&offset=20"
+    )]
+    #[case(
+        "http://localhost:8000/api/v1/",
+        "https://cube.internal/api/v1/feeds/?offset=20",
+        "http://localhost:8000/api/v1/feeds/?offset=20"
+    )]
+    fn test_set_origin(#[case] origin: &str, #[case] next: &str, #[case] expected: &str) {
+        let origin = reqwest::Url::parse(origin).unwrap();
+        let mut url = reqwest::Url::parse(next).unwrap();
+        set_origin(&mut url, &origin);
+        assert_eq!(url.as_str(), expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_next_url_is_rewritten() {
+        let (mock, url) = capped_server_with_next(internal_host).await;
+        let search = search_behind_proxy(url, 40);
+        let items: Vec<usize> = search.stream().try_collect().await.unwrap();
+        assert_eq!(items, (0..TOTAL).collect::<Vec<_>>());
+        assert_eq!(mock.requests().len(), 6);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_next_url_rewrite_disabled() {
+        let (mock, url) = capped_server_with_next(internal_host).await;
+        let (search, _) = search_of(url, 40);
+        let pages: Vec<_> = search.stream_pages().collect().await;
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].is_ok());
+        assert!(matches!(pages[1], Err(CubeError::Raw(_))));
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_relative_next_url() {
+        let (_mock, url) = capped_server_with_next(|_| "/api/v1/".to_string()).await;
+        let (search, _) = search_of(url, 40);
+        let items: Vec<usize> = search.stream().try_collect().await.unwrap();
+        assert_eq!(items.len(), TOTAL);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_invalid_next_url() {
+        let (_mock, url) = capped_server_with_next(|_| "http://[cube/".to_string()).await;
+        let search = search_behind_proxy(url, 40);
+        let error = search.stream().try_collect::<Vec<_>>().await.unwrap_err();
+        assert!(matches!(error, CubeError::InvalidNextUrl { .. }));
+        assert!(
+            error
+                .to_string()
+                .contains("http://[cube/numbers/search/?name=numbers&limit=40"),
+            "{error}"
+        );
+    }
+}
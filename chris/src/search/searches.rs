@@ -1,9 +1,14 @@
 use crate::types::{
-    FeedId, PacsFileId, PipelineId, PluginId, PluginInstanceId, Username, WorkflowId,
+    FeedId, PacsFileId, PipelineId, PluginId, PluginInstanceId, PluginType, Status, TagId,
+    Username, WorkflowId,
 };
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+
 use crate::{
-    Access, FeedFileResponse, FeedResponse, PacsFileResponse, PipelineResponse,
-    PluginInstanceResponse, PluginResponse, WorkflowResponse,
+    Access, ComputeResourceResponse, FeedFileResponse, FeedResponse, FileUploadResponse,
+    PacsFileResponse, PipelineResponse, PluginInstanceResponse, PluginResponse, TagResponse,
+    WorkflowResponse,
 };
 
 use super::query::QueryBuilder;
@@ -27,6 +32,12 @@ impl<A: Access> PluginSearchBuilder<A> {
         self.add_string("name_exact", name_exact)
     }
 
+    /// Search for plugins with names starting with a prefix.
+    /// Versions of CUBE which do not support this filter ignore it.
+    pub fn name_startswith(self, prefix: impl Into<String>) -> Self {
+        self.add_string("name_startswith", prefix)
+    }
+
     /// Search for plugin by version
     pub fn version(self, version: impl Into<String>) -> Self {
         self.add_string("version", version)
@@ -36,6 +47,31 @@ impl<A: Access> PluginSearchBuilder<A> {
     pub fn name_title_category(self, name_title_category: impl Into<String>) -> Self {
         self.add_string("name_title_category", name_title_category)
     }
+
+    /// Search for plugins by category
+    pub fn category(self, category: impl Into<String>) -> Self {
+        self.add_string("category", category)
+    }
+
+    /// Search for plugins by authors
+    pub fn authors(self, authors: impl Into<String>) -> Self {
+        self.add_string("authors", authors)
+    }
+
+    /// Search for plugins of a type
+    pub fn plugin_type(self, plugin_type: PluginType) -> Self {
+        self.add_string("type", plugin_type.as_str())
+    }
+
+    /// Search for plugins registered at or after the given time.
+    pub fn min_creation_date(self, date: OffsetDateTime) -> Self {
+        self.add_string("min_creation_date", format_date(date))
+    }
+
+    /// Search for plugins registered at or before the given time.
+    pub fn max_creation_date(self, date: OffsetDateTime) -> Self {
+        self.add_string("max_creation_date", format_date(date))
+    }
 }
 
 /// Plugin search query
@@ -51,6 +87,27 @@ impl<A: Access> FeedSearchBuilder<A> {
     pub fn name_exact(self, name_exact: impl Into<String>) -> Self {
         self.add_string("name_exact", name_exact)
     }
+
+    /// Search for feeds created at or after the given time.
+    pub fn min_creation_date(self, date: OffsetDateTime) -> Self {
+        self.add_string("min_creation_date", format_date(date))
+    }
+
+    /// Search for feeds created at or before the given time.
+    pub fn max_creation_date(self, date: OffsetDateTime) -> Self {
+        self.add_string("max_creation_date", format_date(date))
+    }
+}
+
+/// Format a time as an RFC 3339 string in UTC.
+///
+/// # Panics
+///
+/// If the year is outside the range 0 to 9999.
+fn format_date(date: OffsetDateTime) -> String {
+    date.to_offset(UtcOffset::UTC)
+        .format(&Rfc3339)
+        .expect("year is out of range for RFC 3339")
 }
 
 /// Plugin instance search query
@@ -96,6 +153,11 @@ impl<A: Access> PluginInstanceSearchBuilder<A> {
     pub fn workflow_id(self, workflow_id: WorkflowId) -> Self {
         self.add_u32("workflow_id", workflow_id.0)
     }
+
+    /// Search for plugin instance by status
+    pub fn status(self, status: Status) -> Self {
+        self.add_string("status", status.as_str())
+    }
 }
 
 /// Pipeline search query
@@ -116,6 +178,26 @@ impl<A: Access> PipelineSearchBuilder<A> {
     pub fn description(self, description: impl Into<String>) -> Self {
         self.add_string("description", description)
     }
+
+    /// Search for pipelines by category
+    pub fn category(self, category: impl Into<String>) -> Self {
+        self.add_string("category", category)
+    }
+
+    /// Search for pipelines by authors
+    pub fn authors(self, authors: impl Into<String>) -> Self {
+        self.add_string("authors", authors)
+    }
+
+    /// Search for pipelines created at or after the given time.
+    pub fn min_creation_date(self, date: OffsetDateTime) -> Self {
+        self.add_string("min_creation_date", format_date(date))
+    }
+
+    /// Search for pipelines created at or before the given time.
+    pub fn max_creation_date(self, date: OffsetDateTime) -> Self {
+        self.add_string("max_creation_date", format_date(date))
+    }
 }
 
 /// File search query. Only searches for files produced by plugin instances.
@@ -151,6 +233,31 @@ impl<A: Access> FilesSearchBuilder<A> {
     pub fn fname_nslashes(self, fname_nslashes: u32) -> Self {
         self.add_u32("fname_nslashes", fname_nslashes)
     }
+
+    /// Search for files created at or after the given time.
+    pub fn min_creation_date(self, date: OffsetDateTime) -> Self {
+        self.add_string("min_creation_date", format_date(date))
+    }
+
+    /// Search for files created at or before the given time.
+    pub fn max_creation_date(self, date: OffsetDateTime) -> Self {
+        self.add_string("max_creation_date", format_date(date))
+    }
+}
+
+/// Uploaded files search query
+pub type UserFilesSearchBuilder<A> = QueryBuilder<FileUploadResponse, A>;
+
+impl<A: Access> UserFilesSearchBuilder<A> {
+    /// Search for uploaded files by fname (starts with)
+    pub fn fname(self, fname: impl Into<String>) -> Self {
+        self.add_string("fname", fname)
+    }
+
+    /// Search for uploaded files by fname (exact match)
+    pub fn fname_exact(self, fname_exact: impl Into<String>) -> Self {
+        self.add_string("fname_exact", fname_exact)
+    }
 }
 
 /// Workflow search query
@@ -178,6 +285,51 @@ impl<A: Access> WorkflowSearchBuilder<A> {
     }
 }
 
+/// Feed tag search query
+pub type TagSearchBuilder<A> = QueryBuilder<TagResponse, A>;
+
+impl<A: Access> TagSearchBuilder<A> {
+    /// Search for tag by ID
+    pub fn id(self, id: TagId) -> Self {
+        self.add_u32("id", id.0)
+    }
+
+    /// Search for tag by name
+    pub fn name(self, name: impl Into<String>) -> Self {
+        self.add_string("name", name)
+    }
+
+    /// Search for tags by owner_username
+    pub fn owner_username(self, owner_username: &Username) -> Self {
+        self.add_string("owner_username", owner_username.as_str())
+    }
+
+    /// Search for tags by color
+    pub fn color(self, color: impl Into<String>) -> Self {
+        self.add_string("color", color)
+    }
+}
+
+/// Compute resource search query
+pub type ComputeResourceSearchBuilder<A> = QueryBuilder<ComputeResourceResponse, A>;
+
+impl<A: Access> ComputeResourceSearchBuilder<A> {
+    /// Search for compute resource by name
+    pub fn name(self, name: impl Into<String>) -> Self {
+        self.add_string("name", name)
+    }
+
+    /// Search for compute resource by name_exact
+    pub fn name_exact(self, name_exact: impl Into<String>) -> Self {
+        self.add_string("name_exact", name_exact)
+    }
+
+    /// Search for the compute resources which a plugin is registered to
+    pub fn plugin_id(self, plugin_id: PluginId) -> Self {
+        self.add_u32("plugin_id", plugin_id.0)
+    }
+}
+
 /// PACSFiles search query
 pub type PacsFilesSearchBuilder<A> = QueryBuilder<PacsFileResponse, A>;
 
@@ -250,7 +402,70 @@ impl<A: Access> PacsFilesSearchBuilder<A> {
     pub fn series_description(self, series_description: impl Into<String>) -> Self {
         self.add_string("SeriesDescription", series_description)
     }
+    pub fn modality(self, modality: impl Into<String>) -> Self {
+        self.add_string("Modality", modality)
+    }
     pub fn pacs_identifier(self, pacs_identifier: impl Into<String>) -> Self {
         self.add_string("pacs_identifier", pacs_identifier)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CollectionUrl;
+    use crate::RoAccess;
+    use rstest::*;
+    use time::macros::datetime;
+
+    fn files_query() -> FilesSearchBuilder<RoAccess> {
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+        QueryBuilder::query(
+            client,
+            CollectionUrl::new("https://cube.example.org/api/v1/files/".to_string()),
+        )
+    }
+
+    #[rstest]
+    fn test_files_query_string() {
+        let query = files_query()
+            .fname("alice/feed_1/")
+            .fname_icontains("Lesion Mask")
+            .fname_nslashes(4)
+            .fname_exact("alice/feed_1/pl-dircopy_2/data/lesion.nii.gz")
+            .min_creation_date(datetime!(2024-02-29 23:30:00 -05:00))
+            .max_creation_date(datetime!(2024-03-02 12:00:00.5 UTC));
+        let expected = "fname=alice%2Ffeed_1%2F\
+            &fname_exact=alice%2Ffeed_1%2Fpl-dircopy_2%2Fdata%2Flesion.nii.gz\
+            &fname_icontains=Lesion+Mask\
+            &fname_nslashes=4\
+            &max_creation_date=2024-03-02T12%3A00%3A00.5Z\
+            &min_creation_date=2024-03-01T04%3A30%3A00Z";
+        assert_eq!(query.query_string(), expected)
+    }
+
+    #[rstest]
+    fn test_plugin_query_string() {
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+        let query: PluginSearchBuilder<RoAccess> = QueryBuilder::query(
+            client,
+            CollectionUrl::new("https://cube.example.org/api/v1/plugins/".to_string()),
+        );
+        let query = query
+            .category("Segmentation")
+            .authors("FNNDSC")
+            .plugin_type(PluginType::Ds)
+            .min_creation_date(datetime!(2024-01-31 00:00:00 UTC));
+        let expected = "authors=FNNDSC\
+            &category=Segmentation\
+            &min_creation_date=2024-01-31T00%3A00%3A00Z\
+            &type=ds";
+        assert_eq!(query.query_string(), expected)
+    }
+
+    #[rstest]
+    fn test_query_string_replaces_repeated_key() {
+        let query = files_query().fname_icontains("a").fname_icontains("b");
+        assert_eq!(query.query_string(), "fname_icontains=b")
+    }
+}
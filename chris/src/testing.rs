@@ -0,0 +1,1167 @@
+//! A mock _CUBE_ for testing programs which use this crate, without a real _CUBE_.
+//!
+//! Only available with the `testing` feature.
+//!
+//! [MockCube] is an HTTP server which serves the data added to it by methods such as
+//! [MockCube::add_plugin] and [MockCube::add_feed_with_files] from collection APIs,
+//! search APIs, item APIs, the filebrowser API, and file downloads. Responses of
+//! collection and search APIs are paginated like _CUBE_ does, with `next` and
+//! `previous` links for any page size.
+//!
+//! Items can be deleted by a DELETE request to their URL, and files can be uploaded
+//! by a POST request to the user files API. Requests with other methods are responded
+//! to with status 405. Authentication is not checked: logging in gives every user the
+//! token `mock-token`.
+//!
+//! ```
+//! use chris::testing::MockCube;
+//! use chris::BaseChrisClient;
+//!
+//! # async fn example() {
+//! let mock = MockCube::start().await;
+//! mock.add_plugin(mock.plugin(1, "pl-dircopy", "2.1.1"));
+//! let chris = mock.anon_client().await;
+//! let plugin = chris.plugin().name("pl-dircopy").search().get_only().await.unwrap();
+//! assert_eq!(plugin.object.version.as_str(), "2.1.1");
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use serde::Serialize;
+use serde_json::{json, Value};
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::types::*;
+use crate::{
+    AnonChrisClient, ChrisClient, FeedResponse, PluginInstanceResponse, PluginParameter,
+    PluginResponse, TagResponse, TaggingResponse,
+};
+
+/// Number of items per page when a request does not specify `limit`, same as _CUBE_.
+const DEFAULT_LIMIT: usize = 10;
+
+/// Names and paths of the collection APIs linked to by the API root.
+const COLLECTION_LINKS: [(&str, &str); 15] = [
+    ("public_feeds", "public_feeds/"),
+    ("files", "files/"),
+    ("compute_resources", "computeresources/"),
+    ("plugin_metas", "plugins/metas/"),
+    ("plugins", "plugins/"),
+    ("plugin_instances", "plugins/instances/"),
+    ("pipelines", "pipelines/"),
+    ("pipeline_instances", "pipelines/instances/"),
+    ("workflows", "pipelines/workflows/"),
+    ("tags", "tags/"),
+    ("pipelinesourcefiles", "pipelines/sourcefiles/"),
+    ("pacsfiles", "pacsfiles/"),
+    ("servicefiles", "servicefiles/"),
+    ("filebrowser", "filebrowser/"),
+    ("userfiles", "userfiles/"),
+];
+
+/// A mock _CUBE_ server which serves canned data. See the [module docs](self).
+///
+/// The server runs until the tokio runtime it was started in is shut down.
+pub struct MockCube {
+    url: CubeUrl,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockCube {
+    /// Start a mock _CUBE_ on a random port of localhost.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Could not bind a port for MockCube");
+        let url = format!("http://{}/api/v1/", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State::new(url.clone())));
+        let server = Arc::clone(&state);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, Arc::clone(&server)));
+            }
+        });
+        Self {
+            url: CubeUrl::new(url).unwrap(),
+            state,
+        }
+    }
+
+    /// Get the API URL of this mock _CUBE_.
+    pub fn url(&self) -> &CubeUrl {
+        &self.url
+    }
+
+    /// Create an anonymous client of this mock _CUBE_.
+    pub async fn anon_client(&self) -> AnonChrisClient {
+        AnonChrisClient::build(self.url.clone())
+            .unwrap()
+            .connect()
+            .await
+            .expect("Could not connect to MockCube")
+    }
+
+    /// Create a client of this mock _CUBE_ logged in as `username`.
+    pub async fn client(&self, username: &str) -> ChrisClient {
+        ChrisClient::build(self.url.clone(), Username::from(username), "mock-token")
+            .unwrap()
+            .connect()
+            .await
+            .expect("Could not connect to MockCube")
+    }
+
+    /// Never return more than `limit` items per page, like a _CUBE_ configured
+    /// with a low `max_limit`.
+    pub fn set_max_limit(&self, limit: usize) {
+        self.lock().max_limit = Some(limit);
+    }
+
+    /// Start the `next` and `previous` links of pages with `base` instead of the URL
+    /// of this mock, like a _CUBE_ behind a misconfigured reverse proxy.
+    pub fn set_pagination_base(&self, base: impl Into<String>) {
+        self.lock().pagination_base = Some(base.into());
+    }
+
+    /// Drop the connection after sending the first `bytes` bytes of the contents of files
+    /// which are downloaded, like a proxy which drops connections.
+    pub fn set_download_cutoff(&self, bytes: usize) {
+        self.lock().download_cutoff = Some(bytes);
+    }
+
+    /// Respond to the next `count` POST requests with status 503, like an overloaded _CUBE_.
+    pub fn fail_next_posts(&self, count: usize) {
+        self.lock().failing_posts = count;
+    }
+
+    /// Get the paths and query strings of the requests received so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.lock()
+            .requests
+            .iter()
+            .map(|(_, target)| target.clone())
+            .collect()
+    }
+
+    /// Like [MockCube::requests], but only requests of the given `method`, e.g. `"DELETE"`.
+    pub fn requests_of(&self, method: &str) -> Vec<String> {
+        self.lock()
+            .requests
+            .iter()
+            .filter(|(m, _)| m == method)
+            .map(|(_, target)| target.clone())
+            .collect()
+    }
+
+    /// Add arbitrary items to a collection API at `path` relative to the API root,
+    /// e.g. `"plugins/"`. Returns the URL of the collection.
+    ///
+    /// Searches of the collection filter its items by their fields, see [MockCube::add_plugin].
+    pub fn add_items<T: Serialize>(
+        &self,
+        path: &str,
+        items: impl IntoIterator<Item = T>,
+    ) -> CollectionUrl {
+        let url = format!("{}{}", self.url, path);
+        let mut state = self.lock();
+        state.register(&url);
+        for item in items {
+            state.push(&url, to_value(item));
+        }
+        CollectionUrl::new(url)
+    }
+
+    /// Add a plugin.
+    ///
+    /// Like all collections of the mock, plugins can be found by searching for the
+    /// values of their fields, e.g. `name` and `version`. Query parameters ending with
+    /// `_icontains` match values which contain their value ignoring case, and `fname`
+    /// matches values which start with its value. Query parameters which are not fields
+    /// of the items are ignored.
+    pub fn add_plugin(&self, plugin: PluginResponse) {
+        let mut state = self.lock();
+        for url in [
+            &plugin.parameters,
+            &plugin.instances,
+            &plugin.compute_resources,
+        ] {
+            state.register(url.as_str());
+        }
+        let plugins = format!("{}plugins/", self.url);
+        state.add_item(&plugins, plugin.url.as_str(), to_value(&plugin));
+    }
+
+    /// Add a feed. It is also added to the public feeds if it is public.
+    pub fn add_feed(&self, feed: FeedResponse) {
+        let mut state = self.lock();
+        for url in [
+            &feed.tags,
+            &feed.comments,
+            &feed.files,
+            &feed.plugin_instances,
+        ] {
+            state.register(url.as_str());
+        }
+        for url in [&feed.taggings, &feed.user_permissions]
+            .into_iter()
+            .flatten()
+        {
+            state.register(url.as_str());
+        }
+        if feed.public == Some(true) {
+            let public_feeds = format!("{}public_feeds/", self.url);
+            state.push(&public_feeds, to_value(&feed));
+        }
+        state.add_folder(&feed_folder(&feed));
+        let feeds = self.url.to_string();
+        state.add_item(&feeds, feed.url.as_str(), to_value(&feed));
+    }
+
+    /// Add a feed and files to it. The paths of the files are relative to the folder
+    /// of the feed, and should be under the output folder of a plugin instance,
+    /// e.g. `pl-dircopy_1/data/brain.nii`.
+    ///
+    /// The files are also added to the files of their plugin instance, assuming it
+    /// was created by [MockCube::plugin_instance].
+    pub fn add_feed_with_files<P: AsRef<str>, C: Into<Bytes>>(
+        &self,
+        feed: FeedResponse,
+        files: impl IntoIterator<Item = (P, C)>,
+    ) {
+        let folder = feed_folder(&feed);
+        let feed_files = feed.files.to_string();
+        let feed_id = feed.id;
+        self.add_feed(feed);
+        let mut state = self.lock();
+        for (path, contents) in files {
+            let fname = format!("{}/{}", folder, path.as_ref().trim_start_matches('/'));
+            let mut extra = json!({ "feed_id": feed_id });
+            let mut collections = vec![feed_files.clone()];
+            if let Some(id) = plugin_instance_of(&fname) {
+                let plinst = format!("{}plugins/instances/{}/", self.url, id);
+                extra["plugin_inst_id"] = id.into();
+                extra["plugin_inst"] = plinst.clone().into();
+                collections.push(format!("{}files/", plinst));
+            }
+            state.add_file(&fname, contents.into(), extra, &collections);
+        }
+    }
+
+    /// Add a plugin instance. It is added to the instances of its plugin, the
+    /// plugin instances of its feed, and the descendants of its previous plugin
+    /// instances which were added before it.
+    pub fn add_plugin_instance(&self, plinst: PluginInstanceResponse) {
+        let mut state = self.lock();
+        for url in [
+            &plinst.descendants,
+            &plinst.files,
+            &plinst.parameters,
+            &plinst.splits,
+        ] {
+            state.register(url.as_str());
+        }
+        if let Some(output_path) = &plinst.output_path {
+            state.add_folder(output_path);
+        }
+        let value = to_value(&plinst);
+        let mut collections: Vec<_> = [
+            (plinst.plugin.as_str(), "instances"),
+            (plinst.feed.as_str(), "plugin_instances"),
+        ]
+        .into_iter()
+        .filter_map(|(item, field)| state.linked_collection(item, field))
+        .collect();
+        collections.push(plinst.descendants.to_string());
+        let mut previous = plinst.previous.as_ref().map(|p| p.to_string());
+        while let Some(item) = previous.take() {
+            collections.extend(state.linked_collection(&item, "descendants"));
+            previous = state.linked_item(&item, "previous");
+        }
+        for collection in collections {
+            state.push(&collection, value.clone());
+        }
+        let plugin_instances = format!("{}plugins/instances/", self.url);
+        state.add_item(&plugin_instances, plinst.url.as_str(), value);
+    }
+
+    /// Add a file, which can be found by the filebrowser and downloaded.
+    ///
+    /// Like in _CUBE_, files under `<username>/uploads/` are added to the user files API,
+    /// files under `SERVICES/PACS/` to the PACS files API, and other files to the files API.
+    pub fn add_file(&self, fname: impl AsRef<str>, contents: impl Into<Bytes>) {
+        self.lock()
+            .add_file(fname.as_ref(), contents.into(), json!({}), &[]);
+    }
+
+    /// Add a tag, and put it on `feeds`, which should have been added before.
+    pub fn add_tag(&self, tag: TagResponse, feeds: &[&FeedResponse]) {
+        let mut state = self.lock();
+        state.register(tag.feeds.as_str());
+        state.register(tag.taggings.as_str());
+        for feed in feeds {
+            state.last_tagging_id += 1;
+            let id = state.last_tagging_id;
+            let tagging = TaggingResponse {
+                url: ItemUrl::from(format!("{}tags/taggings/{}/", self.url, id)),
+                id: TaggingId(id),
+                tag_id: tag.id,
+                tag_name: tag.name.clone(),
+                feed_id: feed.id,
+                feed_name: feed.name.clone(),
+                tag: tag.url.clone(),
+                feed: feed.url.clone(),
+            };
+            let value = to_value(&tagging);
+            state.push(tag.taggings.as_str(), value.clone());
+            if let Some(taggings) = &feed.taggings {
+                state.push(taggings.as_str(), value.clone());
+            }
+            let taggings = format!("{}tags/taggings/", self.url);
+            state.add_item(&taggings, tagging.url.as_str(), value);
+            state.push(tag.feeds.as_str(), to_value(feed));
+            state.push(feed.tags.as_str(), to_value(&tag));
+        }
+        let tags = format!("{}tags/", self.url);
+        state.add_item(&tags, tag.url.as_str(), to_value(&tag));
+    }
+
+    /// Add feeds 1, 2, and 3, where the tag "brain" (tag 1) is on feeds 1 and 3,
+    /// and the tag "lung" (tag 2) is on feed 2.
+    pub fn add_tagged_feeds(&self) {
+        let feeds: Vec<_> = (1..=3)
+            .map(|id| self.feed(id, &format!("feed {}", id)))
+            .collect();
+        for feed in &feeds {
+            self.add_feed(feed.clone());
+        }
+        self.add_tag(self.tag(1, "brain"), &[&feeds[0], &feeds[2]]);
+        self.add_tag(self.tag(2, "lung"), &[&feeds[1]]);
+    }
+
+    /// Create the data of a plugin with links to this mock.
+    /// Use struct update syntax to change its other fields.
+    pub fn plugin(&self, id: u32, name: &str, version: &str) -> PluginResponse {
+        let url = format!("{}plugins/{}/", self.url, id);
+        PluginResponse {
+            id: PluginId(id),
+            creation_date: OffsetDateTime::UNIX_EPOCH,
+            name: PluginName::from(name),
+            version: PluginVersion::from(version),
+            dock_image: DockImage::from(format!("ghcr.io/fnndsc/{}:{}", name, version)),
+            public_repo: PluginRepo::from(format!("https://github.com/FNNDSC/{}", name)),
+            icon: String::new(),
+            plugin_type: PluginType::Ds,
+            stars: 0,
+            authors: "FNNDSC <dev@babyMRI.org>".to_string(),
+            title: name.to_string(),
+            category: String::new(),
+            description: String::new(),
+            documentation: String::new(),
+            license: "MIT".to_string(),
+            execshell: "/usr/local/bin/python".to_string(),
+            selfpath: "/usr/local/bin".to_string(),
+            selfexec: name.to_string(),
+            min_number_of_workers: 1,
+            max_number_of_workers: 1,
+            min_cpu_limit: 1000,
+            max_cpu_limit: 2147483647,
+            min_memory_limit: 200,
+            max_memory_limit: 2147483647,
+            min_gpu_limit: Some(0),
+            max_gpu_limit: Some(0),
+            meta: ItemUrl::from(format!("{}plugins/metas/{}/", self.url, id)),
+            parameters: CollectionUrl::from(format!("{}parameters/", url)),
+            instances: CollectionUrl::from(format!("{}instances/", url)),
+            compute_resources: CollectionUrl::from(format!("{}computeresources/", url)),
+            url: ItemUrl::from(url),
+        }
+    }
+
+    /// Create the data of an optional parameter of `plugin` without a default, with
+    /// links to this mock. Use struct update syntax to change its other fields.
+    pub fn plugin_parameter(
+        &self,
+        id: u32,
+        plugin: &PluginResponse,
+        name: &str,
+        parameter_type: PluginParameterType,
+    ) -> PluginParameter {
+        PluginParameter {
+            url: ItemUrl::from(format!("{}plugins/parameters/{}/", self.url, id)),
+            id: PluginParameterId(id),
+            name: name.to_string(),
+            parameter_type,
+            optional: true,
+            default: None,
+            flag: format!("--{}", name.replace('_', "-")),
+            short_flag: String::new(),
+            action: PluginParameterAction::Store,
+            help: String::new(),
+            ui_exposed: true,
+            plugin: plugin.url.clone(),
+            choices: None,
+        }
+    }
+
+    /// Create the data of a feed owned by the user "chris" with links to this mock.
+    /// Use struct update syntax to change its other fields.
+    pub fn feed(&self, id: u32, name: &str) -> FeedResponse {
+        let url = format!("{}{}/", self.url, id);
+        FeedResponse {
+            name: name.to_string(),
+            creator_username: Username::from("chris"),
+            id: FeedId(id),
+            creation_date: OffsetDateTime::UNIX_EPOCH,
+            modification_date: OffsetDateTime::UNIX_EPOCH,
+            public: Some(false),
+            created_jobs: 0,
+            waiting_jobs: 0,
+            scheduled_jobs: 0,
+            started_jobs: 0,
+            registering_jobs: Some(0),
+            finished_jobs: 0,
+            errored_jobs: 0,
+            cancelled_jobs: 0,
+            owner: vec![ItemUrl::from(format!("{}users/1/", self.url))],
+            note: ItemUrl::from(format!("{}note{}/", self.url, id)),
+            tags: CollectionUrl::from(format!("{}tags/", url)),
+            taggings: Some(CollectionUrl::from(format!("{}taggings/", url))),
+            comments: CollectionUrl::from(format!("{}comments/", url)),
+            files: CollectionUrl::from(format!("{}files/", url)),
+            plugin_instances: CollectionUrl::from(format!("{}plugininstances/", url)),
+            user_permissions: None,
+            url: ItemUrl::from(url),
+        }
+    }
+
+    /// Create the data of a tag owned by the user "chris" with links to this mock.
+    /// Use struct update syntax to change its other fields.
+    pub fn tag(&self, id: u32, name: &str) -> TagResponse {
+        let url = format!("{}tags/{}/", self.url, id);
+        TagResponse {
+            id: TagId(id),
+            name: name.to_string(),
+            owner_username: Username::from("chris"),
+            color: "#2196f3".to_string(),
+            feeds: CollectionUrl::from(format!("{}feeds/", url)),
+            taggings: CollectionUrl::from(format!("{}taggings/", url)),
+            url: ItemUrl::from(url),
+        }
+    }
+
+    /// Create the data of a finished plugin instance of `plugin` in `feed`, after
+    /// `previous`, with links to this mock. Use struct update syntax to change its other fields.
+    pub fn plugin_instance(
+        &self,
+        id: u32,
+        plugin: &PluginResponse,
+        feed: &FeedResponse,
+        previous: Option<&PluginInstanceResponse>,
+    ) -> PluginInstanceResponse {
+        let url = format!("{}plugins/instances/{}/", self.url, id);
+        let parent_folder = previous
+            .and_then(|p| p.output_path.as_deref())
+            .map(|p| p.strip_suffix("/data").unwrap_or(p).to_string())
+            .unwrap_or_else(|| feed_folder(feed));
+        PluginInstanceResponse {
+            id: PluginInstanceId(id),
+            title: String::new(),
+            compute_resource: Some(ItemUrl::from(format!("{}computeresources/1/", self.url))),
+            compute_resource_name: Some(ComputeResourceName::from("host")),
+            plugin: plugin.url.clone(),
+            plugin_id: plugin.id,
+            plugin_name: plugin.name.clone(),
+            plugin_version: plugin.version.clone(),
+            plugin_type: plugin.plugin_type,
+            start_date: OffsetDateTime::UNIX_EPOCH,
+            end_date: OffsetDateTime::UNIX_EPOCH,
+            output_path: Some(format!("{}/{}_{}/data", parent_folder, plugin.name, id)),
+            status: Status::FinishedSuccessfully,
+            summary: None,
+            raw: None,
+            owner_username: feed.creator_username.clone(),
+            cpu_limit: 1000,
+            memory_limit: 300,
+            number_of_workers: 1,
+            gpu_limit: 0,
+            size: Some(0),
+            error_code: None,
+            previous: previous.map(|p| p.url.clone()),
+            previous_id: previous.map(|p| p.id),
+            feed: feed.url.clone(),
+            feed_id: feed.id,
+            descendants: CollectionUrl::from(format!("{}descendants/", url)),
+            files: CollectionUrl::from(format!("{}files/", url)),
+            parameters: CollectionUrl::from(format!("{}parameters/", url)),
+            splits: CollectionUrl::from(format!("{}splits/", url)),
+            url: ItemUrl::from(url),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// Data served by a [MockCube].
+struct State {
+    /// API URL of the mock
+    url: String,
+    /// Items of collection APIs, by URL path
+    collections: HashMap<String, Vec<Value>>,
+    /// Item APIs, by URL path
+    items: HashMap<String, Value>,
+    /// Contents of files, by URL path of their `file_resource`
+    downloads: HashMap<String, Bytes>,
+    /// Subfolder names of every folder of the filebrowser
+    folders: BTreeMap<String, BTreeSet<String>>,
+    max_limit: Option<usize>,
+    pagination_base: Option<String>,
+    download_cutoff: Option<usize>,
+    /// Number of POST requests to fail, see [MockCube::fail_next_posts]
+    failing_posts: usize,
+    /// Methods and targets of the requests received
+    requests: Vec<(String, String)>,
+    last_file_id: u32,
+    last_tagging_id: u32,
+}
+
+impl State {
+    fn new(url: String) -> Self {
+        let mut state = Self {
+            collections: Default::default(),
+            items: Default::default(),
+            downloads: Default::default(),
+            folders: Default::default(),
+            max_limit: None,
+            pagination_base: None,
+            download_cutoff: None,
+            failing_posts: 0,
+            requests: Vec::new(),
+            last_file_id: 0,
+            last_tagging_id: 0,
+            url,
+        };
+        // the API root is the collection of feeds
+        state.register(&state.url.clone());
+        for (_, path) in COLLECTION_LINKS {
+            state.register(&format!("{}{}", state.url, path));
+        }
+        state.folders.insert(String::new(), BTreeSet::new());
+        state
+    }
+
+    /// Make an empty collection API at `url`, if it does not exist.
+    fn register(&mut self, url: &str) {
+        self.collections.entry(path_of(url)).or_default();
+    }
+
+    /// Append an item to the collection API at `url`.
+    fn push(&mut self, url: &str, item: Value) {
+        self.collections.entry(path_of(url)).or_default().push(item)
+    }
+
+    /// Add an item to a collection and serve it from its `url`.
+    fn add_item(&mut self, collection: &str, url: &str, item: Value) {
+        self.items.insert(path_of(url), item.clone());
+        self.push(collection, item);
+    }
+
+    /// Get the value of a link `field` of the item at `url`, if the item exists.
+    fn linked_item(&self, url: &str, field: &str) -> Option<String> {
+        self.items
+            .get(&path_of(url))
+            .and_then(|item| item.get(field))
+            .and_then(|link| link.as_str())
+            .map(|link| link.to_string())
+    }
+
+    /// Like [State::linked_item], for links to collections which exist.
+    fn linked_collection(&self, url: &str, field: &str) -> Option<String> {
+        self.linked_item(url, field)
+            .filter(|link| self.collections.contains_key(&path_of(link)))
+    }
+
+    /// Add a folder and its parents to the filebrowser.
+    fn add_folder(&mut self, folder: &str) {
+        let mut parent = String::new();
+        for name in folder.split('/').filter(|name| !name.is_empty()) {
+            self.folders
+                .entry(parent.clone())
+                .or_default()
+                .insert(name.to_string());
+            parent = if parent.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", parent, name)
+            };
+            self.folders.entry(parent.clone()).or_default();
+            self.register(&self.filebrowser_files(&parent));
+        }
+    }
+
+    /// Add a file with the fields of `extra` to the files API, its folder of the
+    /// filebrowser, and the given collections.
+    fn add_file(&mut self, fname: &str, contents: Bytes, extra: Value, collections: &[String]) {
+        self.last_file_id += 1;
+        let components: Vec<_> = fname.split('/').collect();
+        let (api, extra_of_api) = match components.as_slice() {
+            [owner, "uploads", _, ..] => ("userfiles/", json!({ "owner": owner })),
+            ["SERVICES", "PACS", pacs_identifier, _, ..] => (
+                "pacsfiles/",
+                json!({
+                    "pacs_identifier": pacs_identifier,
+                    "PatientID": "",
+                    "StudyDate": "",
+                    "StudyInstanceUID": "",
+                    "SeriesInstanceUID": "",
+                }),
+            ),
+            _ => ("files/", json!({})),
+        };
+        let files = format!("{}{}", self.url, api);
+        let url = format!("{}{}/", files, self.last_file_id);
+        let (folder, basename) = fname.rsplit_once('/').unwrap_or(("", fname));
+        let file_resource = format!("{}{}", url, basename);
+        let mut file = json!({
+            "url": url,
+            "id": self.last_file_id,
+            "creation_date": OffsetDateTime::UNIX_EPOCH.format(&Iso8601::DEFAULT).unwrap(),
+            "fname": fname,
+            "fsize": contents.len(),
+            "file_resource": file_resource,
+            "owner_username": components[0],
+        });
+        for extra in [extra_of_api, extra] {
+            if let (Value::Object(file), Value::Object(extra)) = (&mut file, extra) {
+                file.extend(extra);
+            }
+        }
+        self.add_folder(folder);
+        self.downloads.insert(path_of(&file_resource), contents);
+        self.add_item(&files, &url, file.clone());
+        self.push(&self.filebrowser_files(folder), file.clone());
+        for collection in collections {
+            self.push(collection, file.clone());
+        }
+    }
+
+    /// URL of the files immediately under a folder of the filebrowser.
+    fn filebrowser_files(&self, folder: &str) -> String {
+        format!("{}filebrowser/{}/files/", self.url, folder)
+    }
+
+    fn respond(&mut self, method: &str, target: &str, content_type: &str, body: &[u8]) -> Response {
+        self.requests.push((method.to_string(), target.to_string()));
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        if method == "DELETE" {
+            return self.delete(path);
+        }
+        if method == "POST" {
+            return self.post(path, content_type, body);
+        }
+        if method != "GET" {
+            let detail = format!("Method \"{}\" not allowed.", method);
+            return Response::json(405, json!({ "detail": detail }));
+        }
+        let query: Vec<(String, String)> = serde_urlencoded::from_str(query).unwrap_or_default();
+        if let Some(contents) = self.downloads.get(path) {
+            let sent = self
+                .download_cutoff
+                .unwrap_or(contents.len())
+                .min(contents.len());
+            return Response {
+                status: 200,
+                content_type: "application/octet-stream",
+                content_length: contents.len(),
+                body: contents.slice(..sent),
+            };
+        }
+        if path == path_of(&format!("{}filebrowser/search/", self.url)) {
+            return Response::json(200, self.filebrowser_search(&query));
+        }
+        if let Some(items) = self.collections.get(path) {
+            let items: Vec<_> = items.iter().collect();
+            let mut page = self.page(path, &items, &query);
+            if path == path_of(&self.url) {
+                page["collection_links"] = self.collection_links();
+            }
+            return Response::json(200, page);
+        }
+        let search = path
+            .strip_suffix("search/")
+            .and_then(|collection| self.collections.get(collection));
+        if let Some(items) = search {
+            let items: Vec<_> = items.iter().filter(|i| matches(i, &query)).collect();
+            return Response::json(200, self.page(path, &items, &query));
+        }
+        if let Some(item) = self.items.get(path) {
+            return Response::json(200, item.clone());
+        }
+        Response::json(404, json!({ "detail": "Not found." }))
+    }
+
+    /// Upload a file if `path` is the user files API and `body` is a multipart form
+    /// with the fields `upload_path` and `fname`, like _CUBE_ does. Respond to a
+    /// POST to the auth token API with a token, whatever the username and password.
+    fn post(&mut self, path: &str, content_type: &str, body: &[u8]) -> Response {
+        if self.failing_posts > 0 {
+            self.failing_posts -= 1;
+            return Response::json(503, json!({ "detail": "Service unavailable." }));
+        }
+        if path == path_of(&format!("{}auth-token/", self.url)) {
+            return Response::json(200, json!({ "token": "mock-token" }));
+        }
+        let userfiles = format!("{}userfiles/", self.url);
+        if path != path_of(&userfiles) {
+            return Response::json(405, json!({ "detail": "Method \"POST\" not allowed." }));
+        }
+        let fields = content_type
+            .split_once("boundary=")
+            .map(|(_, boundary)| multipart_fields(body, boundary.trim_matches('"')))
+            .unwrap_or_default();
+        let (Some(upload_path), Some(contents)) = (fields.get("upload_path"), fields.get("fname"))
+        else {
+            return Response::json(400, json!({ "detail": "Missing upload_path or fname." }));
+        };
+        let upload_path = String::from_utf8_lossy(upload_path).to_string();
+        self.add_file(
+            &upload_path,
+            Bytes::copy_from_slice(contents),
+            json!({}),
+            &[],
+        );
+        let url = format!("{}{}/", userfiles, self.last_file_id);
+        Response::json(201, self.items[&path_of(&url)].clone())
+    }
+
+    /// Delete the item at `path`, removing it from every collection.
+    fn delete(&mut self, path: &str) -> Response {
+        let Some(item) = self.items.remove(path) else {
+            return Response::json(404, json!({ "detail": "Not found." }));
+        };
+        for items in self.collections.values_mut() {
+            items.retain(|i| i.get("url") != item.get("url"));
+        }
+        Response {
+            status: 204,
+            content_type: "application/json",
+            content_length: 0,
+            body: Bytes::new(),
+        }
+    }
+
+    /// Paginate `items` like the `LimitOffsetPagination` of Django REST Framework.
+    fn page(&self, path: &str, items: &[&Value], query: &[(String, String)]) -> Value {
+        let param = |name: &str| {
+            query
+                .iter()
+                .find(|(key, _)| key == name)
+                .and_then(|(_, value)| value.parse::<usize>().ok())
+        };
+        let limit = param("limit").filter(|l| *l > 0).unwrap_or(DEFAULT_LIMIT);
+        let limit = self.max_limit.map_or(limit, |max| limit.min(max));
+        let count = items.len();
+        let offset = param("offset").unwrap_or(0).min(count);
+        let end = count.min(offset + limit);
+        let next = (end < count).then(|| self.page_link(path, query, limit, end));
+        let previous =
+            (offset > 0).then(|| self.page_link(path, query, limit, offset.saturating_sub(limit)));
+        json!({
+            "count": count,
+            "next": next,
+            "previous": previous,
+            "results": items[offset..end],
+        })
+    }
+
+    /// URL of the page of a collection or search at `path` starting from `offset`.
+    fn page_link(
+        &self,
+        path: &str,
+        query: &[(String, String)],
+        limit: usize,
+        offset: usize,
+    ) -> String {
+        let mut params: Vec<_> = query
+            .iter()
+            .filter(|(key, _)| key != "limit" && key != "offset")
+            .cloned()
+            .collect();
+        params.push(("limit".to_string(), limit.to_string()));
+        if offset > 0 {
+            params.push(("offset".to_string(), offset.to_string()));
+        }
+        let base = self.pagination_base.as_deref().unwrap_or(&self.url);
+        let relative = path.strip_prefix(&path_of(&self.url)).unwrap_or(path);
+        format!(
+            "{}{}?{}",
+            base,
+            relative,
+            serde_urlencoded::to_string(params).unwrap()
+        )
+    }
+
+    fn collection_links(&self) -> Value {
+        COLLECTION_LINKS
+            .iter()
+            .map(|(name, path)| (name.to_string(), format!("{}{}", self.url, path).into()))
+            .chain([(
+                "chrisinstance".to_string(),
+                format!("{}chrisinstance/1/", self.url).into(),
+            )])
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+
+    fn filebrowser_search(&self, query: &[(String, String)]) -> Value {
+        let path = query
+            .iter()
+            .find(|(key, _)| key == "path")
+            .map(|(_, value)| value.trim_matches('/'))
+            .unwrap_or("");
+        let results: Vec<_> = self
+            .folders
+            .get(path)
+            .map(|subfolders| {
+                let subfolders: Vec<_> = subfolders.iter().collect();
+                json!({
+                    "path": path,
+                    "subfolders": serde_json::to_string(&subfolders).unwrap(),
+                    "url": format!("{}filebrowser/{}/", self.url, path),
+                    "files": (!path.is_empty()).then(|| self.filebrowser_files(path)),
+                    "creation_date": OffsetDateTime::UNIX_EPOCH.format(&Iso8601::DEFAULT).unwrap(),
+                    "owner_username": path.split('/').next().filter(|o| !o.is_empty()),
+                })
+            })
+            .into_iter()
+            .collect();
+        json!({
+            "count": results.len(),
+            "next": null,
+            "previous": null,
+            "results": results,
+        })
+    }
+}
+
+/// Whether an item matches the filters of a search. Like _CUBE_, empty values
+/// do not filter.
+fn matches(item: &Value, query: &[(String, String)]) -> bool {
+    query.iter().all(|(key, expected)| {
+        if expected.is_empty() {
+            true
+        } else if let Some(field) = key.strip_suffix("_icontains") {
+            field_value(item, field)
+                .is_none_or(|actual| actual.to_lowercase().contains(&expected.to_lowercase()))
+        } else if key == "fname" {
+            field_value(item, key).is_none_or(|actual| actual.starts_with(expected.as_str()))
+        } else {
+            let field = key.strip_suffix("_exact").unwrap_or(key);
+            field_value(item, field).is_none_or(|actual| &actual == expected)
+        }
+    })
+}
+
+/// Get the value of a field of an item as it would appear in a query string.
+fn field_value(item: &Value, field: &str) -> Option<String> {
+    match item.get(field)? {
+        Value::String(s) => Some(s.to_string()),
+        Value::Null => Some(String::new()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Get the ID of the plugin instance which created a file from its path,
+/// e.g. 2 for `chris/feed_1/pl-dircopy_1/pl-simpledsapp_2/data/a.txt`.
+fn plugin_instance_of(fname: &str) -> Option<u32> {
+    let components: Vec<_> = fname.split('/').collect();
+    let data = components.iter().position(|c| *c == "data")?;
+    let (_, id) = components.get(data.checked_sub(1)?)?.rsplit_once('_')?;
+    id.parse().ok()
+}
+
+/// Folder of a feed, e.g. `chris/feed_1`.
+fn feed_folder(feed: &FeedResponse) -> String {
+    format!("{}/feed_{}", feed.creator_username, feed.id.0)
+}
+
+/// Get the path of a URL as it would appear in a request, with special characters encoded.
+fn path_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .map(|u| u.path().to_string())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+fn to_value<T: Serialize>(item: T) -> Value {
+    serde_json::to_value(item).expect("Could not serialize item for MockCube")
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    /// Length of the body as told to the client, which is more than the length of
+    /// `body` if the connection is dropped early.
+    content_length: usize,
+    body: Bytes,
+}
+
+impl Response {
+    fn json(status: u16, body: Value) -> Self {
+        let body: Bytes = body.to_string().into();
+        Self {
+            status,
+            content_type: "application/json",
+            content_length: body.len(),
+            body,
+        }
+    }
+}
+
+/// Get the values of the fields of a `multipart/form-data` body, by name.
+fn multipart_fields<'a>(body: &'a [u8], boundary: &str) -> HashMap<String, &'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut fields = HashMap::new();
+    let mut rest = body;
+    while let Some(start) = find(rest, delimiter.as_bytes()) {
+        rest = &rest[start + delimiter.len()..];
+        let Some(end) = find(rest, delimiter.as_bytes()) else {
+            break;
+        };
+        let part = &rest[..end];
+        if let Some(headers_end) = find(part, b"\r\n\r\n") {
+            let headers = String::from_utf8_lossy(&part[..headers_end]);
+            let value = &part[headers_end + 4..];
+            let value = value.strip_suffix(b"\r\n").unwrap_or(value);
+            let name = headers
+                .split("name=\"")
+                .nth(1)
+                .and_then(|s| s.split('"').next());
+            if let Some(name) = name {
+                fields.insert(name.to_string(), value);
+            }
+        }
+    }
+    fields
+}
+
+/// Position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Respond to one request of a connection.
+async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut stream = BufReader::new(stream);
+    let mut request_line = String::new();
+    if stream.read_line(&mut request_line).await.is_err() {
+        return;
+    }
+    let mut content_length = 0;
+    let mut content_type = String::new();
+    loop {
+        let mut header = String::new();
+        if stream.read_line(&mut header).await.is_err() {
+            return;
+        }
+        if header.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("content-type") {
+                content_type = value.trim().to_string();
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    if stream.read_exact(&mut body).await.is_err() {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return,
+    };
+    let response = state
+        .lock()
+        .unwrap()
+        .respond(method, target, &content_type, &body);
+    let reason = match response.status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status, reason, response.content_type, response.content_length
+    );
+    if stream.write_all(head.as_bytes()).await.is_ok() {
+        let _ = stream.write_all(&response.body).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BaseChrisClient, Downloadable};
+    use futures::TryStreamExt;
+    use rstest::*;
+
+    #[rstest]
+    #[case("chris/feed_1/pl-dircopy_1/data/a.txt", Some(1))]
+    #[case("chris/feed_1/pl-dircopy_1/pl-simpledsapp_2/data/sub/b.txt", Some(2))]
+    #[case("chris/uploads/data/c.txt", None)]
+    #[case("chris/feed_1/pl-dircopy_1/d.txt", None)]
+    fn test_plugin_instance_of(#[case] fname: &str, #[case] expected: Option<u32>) {
+        assert_eq!(plugin_instance_of(fname), expected)
+    }
+
+    #[rstest]
+    #[case(0, 10, Some("limit=10&offset=10"), None)]
+    #[case(10, 10, Some("limit=10&offset=20"), Some("limit=10"))]
+    #[case(20, 10, None, Some("limit=10&offset=10"))]
+    #[case(5, 20, None, Some("limit=20"))]
+    #[tokio::test]
+    async fn test_page_links(
+        #[case] offset: usize,
+        #[case] limit: usize,
+        #[case] next: Option<&str>,
+        #[case] previous: Option<&str>,
+    ) {
+        let mock = MockCube::start().await;
+        let url = mock.add_items("numbers/", 0..25);
+        let res = reqwest::get(format!("{}?limit={}&offset={}", url, limit, offset))
+            .await
+            .unwrap();
+        let page: Value = res.json().await.unwrap();
+        let link = |name: &str| {
+            page[name]
+                .as_str()
+                .map(|l| l.strip_prefix(&format!("{}?", url)).unwrap().to_string())
+        };
+        assert_eq!(link("next").as_deref(), next);
+        assert_eq!(link("previous").as_deref(), previous);
+        assert_eq!(page["count"], 25);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_feed_with_files() {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(1, "pl-dircopy", "2.1.1");
+        let feed = mock.feed(1, "My Study");
+        let plinst = mock.plugin_instance(1, &plugin, &feed, None);
+        mock.add_plugin(plugin);
+        mock.add_feed_with_files(
+            feed,
+            [
+                ("pl-dircopy_1/data/a.txt", "hello"),
+                ("pl-dircopy_1/data/my folder/b.txt", "world"),
+            ],
+        );
+        mock.add_plugin_instance(plinst);
+
+        let chris = mock.client("chris").await;
+        let feed = chris.get_feed(FeedId(1)).await.unwrap();
+        assert_eq!(&feed.object.name, "My Study");
+        let plinst = chris
+            .get_plugin_instance(PluginInstanceId(1))
+            .await
+            .unwrap();
+        let search = plinst.files();
+        let files: Vec<_> = search.stream_connected().try_collect().await.unwrap();
+        assert_eq!(files.len(), 2);
+        let contents: Vec<_> = files[1]
+            .stream()
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(contents.concat(), b"world");
+
+        let entry = chris
+            .filebrowser()
+            .readdir("chris/feed_1/pl-dircopy_1/data")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.subfolders(), &vec!["my folder".to_string()]);
+        assert_eq!(entry.file_count().await.unwrap(), 1);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_delete() {
+        let mock = MockCube::start().await;
+        mock.add_plugin(mock.plugin(1, "pl-dircopy", "2.1.1"));
+        let url = format!("{}plugins/1/", mock.url());
+        let client = reqwest::Client::new();
+        let res = client.delete(&url).send().await.unwrap();
+        assert_eq!(res.status(), 204);
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), 404);
+        let res = client.delete(&url).send().await.unwrap();
+        assert_eq!(res.status(), 404);
+        let page: Value = reqwest::get(format!("{}plugins/", mock.url()))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(page["count"], 0);
+        assert_eq!(mock.requests_of("DELETE"), vec!["/api/v1/plugins/1/"; 2]);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_not_found_and_read_only() {
+        let mock = MockCube::start().await;
+        let res = reqwest::get(format!("{}plugins/9/", mock.url()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 404);
+        let res = reqwest::Client::new()
+            .post(format!("{}plugins/", mock.url()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 405);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_upload() {
+        let mock = MockCube::start().await;
+        let client = mock.client("chris").await;
+        let stream = futures::stream::iter([Ok::<_, std::io::Error>(Bytes::from("hello"))]);
+        let uploaded = client
+            .upload_stream(stream, "hello.txt", "chris/uploads/hello.txt", 5)
+            .await
+            .unwrap();
+        assert_eq!(uploaded.fname().as_str(), "chris/uploads/hello.txt");
+        let files: Vec<_> = client
+            .userfiles()
+            .fname_exact("chris/uploads/hello.txt")
+            .search()
+            .stream()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 1);
+    }
+}
@@ -3,6 +3,7 @@
 mod cube_url;
 mod enums;
 mod ids;
+mod resources;
 mod strings;
 /// Primitive ChRIS API data types and NewType-patterns.
 mod urls;
@@ -10,5 +11,6 @@ mod urls;
 pub use cube_url::*;
 pub use enums::*;
 pub use ids::*;
+pub use resources::*;
 pub use strings::*;
 pub use urls::*;
@@ -1,5 +1,7 @@
+use crate::errors::InvalidStatus;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -13,8 +15,7 @@ pub enum PluginParameterType {
 }
 
 /// Plugin instance status
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Status {
     Created,
     Waiting,
@@ -24,12 +25,87 @@ pub enum Status {
     FinishedSuccessfully,
     FinishedWithError,
     Cancelled,
+    /// A status which is not known to this version of the client, e.g. one
+    /// added by a newer version of _CUBE_. It is serialized as the value _CUBE_ sent.
+    Unknown(String),
+}
+
+impl Serialize for Status {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Status {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or(Status::Unknown(s)))
+    }
 }
 
 impl Status {
-    pub fn simplify(self) -> SimplifiedStatus {
+    /// Every status which _CUBE_ is known to send.
+    pub const KNOWN: [Status; 8] = [
+        Status::Created,
+        Status::Waiting,
+        Status::Scheduled,
+        Status::Started,
+        Status::RegisteringFiles,
+        Status::FinishedSuccessfully,
+        Status::FinishedWithError,
+        Status::Cancelled,
+    ];
+
+    pub fn simplify(&self) -> SimplifiedStatus {
         self.into()
     }
+
+    /// Whether the plugin instance is done, i.e. its status will not change anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            Status::FinishedSuccessfully | Status::FinishedWithError | Status::Cancelled
+        )
+    }
+
+    /// Whether the plugin instance finished with an error.
+    pub fn is_error(&self) -> bool {
+        self == &Status::FinishedWithError
+    }
+
+    /// The value of this status as it appears in the API.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Status::Created => "created",
+            Status::Waiting => "waiting",
+            Status::Scheduled => "scheduled",
+            Status::Started => "started",
+            Status::RegisteringFiles => "registeringFiles",
+            Status::FinishedSuccessfully => "finishedSuccessfully",
+            Status::FinishedWithError => "finishedWithError",
+            Status::Cancelled => "cancelled",
+            Status::Unknown(s) => s,
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Status {
+    type Err = InvalidStatus;
+
+    /// Parse a status as it appears in the API. Unlike deserialization,
+    /// fails instead of producing [Status::Unknown].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Status::KNOWN
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| InvalidStatus(s.to_string()))
+    }
 }
 
 /// Simplified variants of [Status].
@@ -42,8 +118,8 @@ pub enum SimplifiedStatus {
     Cancelled,
 }
 
-impl From<Status> for SimplifiedStatus {
-    fn from(value: Status) -> Self {
+impl From<&Status> for SimplifiedStatus {
+    fn from(value: &Status) -> Self {
         match value {
             Status::Created => Self::Waiting,
             Status::Waiting => Self::Waiting,
@@ -53,10 +129,18 @@ impl From<Status> for SimplifiedStatus {
             Status::FinishedSuccessfully => Self::Success,
             Status::FinishedWithError => Self::Error,
             Status::Cancelled => Self::Cancelled,
+            // assume a status which is not known is not final
+            Status::Unknown(_) => Self::Running,
         }
     }
 }
 
+impl From<Status> for SimplifiedStatus {
+    fn from(value: Status) -> Self {
+        (&value).into()
+    }
+}
+
 impl PluginParameterType {
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -103,7 +187,7 @@ pub enum ParameterValue {
 }
 
 /// <https://github.com/FNNDSC/CHRIS_docs/blob/master/specs/ChRIS_Plugins.adoc#plugin-type>
-#[derive(Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum PluginType {
     Fs,
@@ -111,7 +195,18 @@ pub enum PluginType {
     Ts,
 }
 
-#[derive(Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+impl PluginType {
+    /// The value of this plugin type as it appears in the API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PluginType::Fs => "fs",
+            PluginType::Ds => "ds",
+            PluginType::Ts => "ts",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum PluginParameterAction {
     #[serde(rename = "store")]
     Store,
@@ -120,3 +215,66 @@ pub enum PluginParameterAction {
     #[serde(rename = "store_false")]
     StoreFalse,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("created", Status::Created)]
+    #[case("waiting", Status::Waiting)]
+    #[case("scheduled", Status::Scheduled)]
+    #[case("started", Status::Started)]
+    #[case("registeringFiles", Status::RegisteringFiles)]
+    #[case("finishedSuccessfully", Status::FinishedSuccessfully)]
+    #[case("finishedWithError", Status::FinishedWithError)]
+    #[case("cancelled", Status::Cancelled)]
+    fn test_status_round_trip(#[case] s: &str, #[case] expected: Status) {
+        let json = format!("\"{}\"", s);
+        let status: Status = serde_json::from_str(&json).unwrap();
+        assert_eq!(status, expected);
+        assert_eq!(serde_json::to_string(&status).unwrap(), json);
+        assert_eq!(status.to_string(), s);
+        assert_eq!(s.parse::<Status>(), Ok(expected));
+    }
+
+    #[rstest]
+    fn test_every_known_status_round_trips() {
+        for status in Status::KNOWN {
+            assert_eq!(status.as_str().parse::<Status>(), Ok(status));
+        }
+    }
+
+    #[rstest]
+    fn test_unknown_status() {
+        let status: Status = serde_json::from_str("\"hibernating\"").unwrap();
+        assert_eq!(status, Status::Unknown("hibernating".to_string()));
+        assert!(!status.is_terminal());
+        assert_eq!(status.to_string(), "hibernating");
+        assert_eq!(serde_json::to_string(&status).unwrap(), "\"hibernating\"");
+        assert_eq!(
+            "hibernating".parse::<Status>(),
+            Err(InvalidStatus("hibernating".to_string()))
+        );
+        assert!("unknown".parse::<Status>().is_err());
+    }
+
+    #[rstest]
+    #[case(Status::Created, false, false)]
+    #[case(Status::Waiting, false, false)]
+    #[case(Status::Scheduled, false, false)]
+    #[case(Status::Started, false, false)]
+    #[case(Status::RegisteringFiles, false, false)]
+    #[case(Status::FinishedSuccessfully, true, false)]
+    #[case(Status::FinishedWithError, true, true)]
+    #[case(Status::Cancelled, true, false)]
+    fn test_is_terminal_and_is_error(
+        #[case] status: Status,
+        #[case] terminal: bool,
+        #[case] error: bool,
+    ) {
+        assert_eq!(status.is_terminal(), terminal);
+        assert_eq!(status.is_error(), error);
+    }
+}
@@ -48,3 +48,19 @@ pub struct FeedFileId(pub u32);
 /// PACSFile ID
 #[derive(Copy, Clone, Shrinkwrap, Serialize, Deserialize, Debug, Hash, Eq, PartialEq)]
 pub struct PacsFileId(pub u32);
+
+/// Pipeline piping ID
+#[derive(Copy, Clone, Shrinkwrap, Serialize, Deserialize, Debug, Hash, Eq, PartialEq)]
+pub struct PipingId(pub u32);
+
+/// Pipeline piping default parameter ID
+#[derive(Copy, Clone, Shrinkwrap, Serialize, Deserialize, Debug, Hash, Eq, PartialEq)]
+pub struct PipingParameterId(pub u32);
+
+/// Feed tag ID
+#[derive(Copy, Clone, Shrinkwrap, Serialize, Deserialize, Debug, Hash, Eq, PartialEq)]
+pub struct TagId(pub u32);
+
+/// Tagging ID, i.e. the ID of a tag of a feed
+#[derive(Copy, Clone, Shrinkwrap, Serialize, Deserialize, Debug, Hash, Eq, PartialEq)]
+pub struct TaggingId(pub u32);
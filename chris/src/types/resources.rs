@@ -0,0 +1,258 @@
+//! Compute resource requests of plugin instances.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+
+use super::PluginParameterValue;
+use crate::errors::InvalidResourceLimit;
+
+/// CPU resource request of a plugin instance, in millicores.
+///
+/// Parsed from either millicores, e.g. `1500m`, or a number of cores, e.g. `1.5`.
+/// It is written the way _CUBE_ expects, e.g. `1500m`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, SerializeDisplay, DeserializeFromStr)]
+pub struct CpuLimit(pub u32);
+
+/// Memory resource request of a plugin instance, in mebibytes.
+///
+/// Parsed from a number with a unit: `Mi`, `Gi`, or `Ti`. Decimal units such as `G`
+/// and `GB` are accepted as the binary unit of the same magnitude, e.g. `2GB` is `2Gi`.
+/// Units are case-insensitive. It is written the way _CUBE_ expects, e.g. `2Gi` or `1500Mi`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, SerializeDisplay, DeserializeFromStr)]
+pub struct MemoryLimit(pub u32);
+
+impl FromStr for CpuLimit {
+    type Err = InvalidResourceLimit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, unit) = split_unit(s);
+        if number.is_empty() {
+            return Err(InvalidResourceLimit::NotANumber(s.trim().to_string()));
+        }
+        let (factor, unit_name) = match unit {
+            "" => (1000, "millicores"),
+            "m" => (1, "millicores"),
+            _ => {
+                return Err(InvalidResourceLimit::UnknownUnit {
+                    unit: unit.to_string(),
+                    expected: "\"m\" for millicores, or none for cores",
+                })
+            }
+        };
+        scale(s, number, factor, "CPU limit", unit_name).map(Self)
+    }
+}
+
+impl Display for CpuLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}m", self.0)
+    }
+}
+
+impl FromStr for MemoryLimit {
+    type Err = InvalidResourceLimit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, unit) = split_unit(s);
+        if number.is_empty() {
+            return Err(InvalidResourceLimit::NotANumber(s.trim().to_string()));
+        }
+        let factor = match unit.to_ascii_lowercase().as_str() {
+            "mi" | "m" | "mb" => 1,
+            "gi" | "g" | "gb" => 1024,
+            "ti" | "t" | "tb" => 1024 * 1024,
+            "" => return Err(InvalidResourceLimit::MissingUnit(number.to_string())),
+            _ => {
+                return Err(InvalidResourceLimit::UnknownUnit {
+                    unit: unit.to_string(),
+                    expected: "one of Mi, Gi, Ti, M, G, T, MB, GB, or TB",
+                })
+            }
+        };
+        scale(s, number, factor, "memory limit", "Mi").map(Self)
+    }
+}
+
+impl Display for MemoryLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_multiple_of(1024) {
+            write!(f, "{}Gi", self.0 / 1024)
+        } else {
+            write!(f, "{}Mi", self.0)
+        }
+    }
+}
+
+impl From<CpuLimit> for PluginParameterValue {
+    fn from(value: CpuLimit) -> Self {
+        PluginParameterValue::Stringish(value.to_string())
+    }
+}
+
+impl From<MemoryLimit> for PluginParameterValue {
+    fn from(value: MemoryLimit) -> Self {
+        PluginParameterValue::Stringish(value.to_string())
+    }
+}
+
+/// Split a string into its leading number and its unit.
+fn split_unit(s: &str) -> (&str, &str) {
+    let s = s.trim();
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    (&s[..end], s[end..].trim_start())
+}
+
+/// Multiply a decimal `number` by `factor`, without floating point error.
+fn scale(
+    given: &str,
+    number: &str,
+    factor: u128,
+    what: &'static str,
+    unit: &'static str,
+) -> Result<u32, InvalidResourceLimit> {
+    let not_a_number = || InvalidResourceLimit::NotANumber(given.trim().to_string());
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(not_a_number());
+    }
+    let fraction = fraction.trim_end_matches('0');
+    if whole.len() > 20 || fraction.len() > 20 {
+        return Err(not_a_number());
+    }
+    let parse = |digits: &str| -> Result<u128, InvalidResourceLimit> {
+        if digits.is_empty() {
+            Ok(0)
+        } else {
+            digits.parse().map_err(|_| not_a_number())
+        }
+    };
+    let denominator = 10u128.pow(fraction.len() as u32);
+    let numerator = parse(whole)? * denominator + parse(fraction)?;
+    let scaled = numerator * factor;
+    if !scaled.is_multiple_of(denominator) {
+        return Err(InvalidResourceLimit::Fractional { what, unit });
+    }
+    match u32::try_from(scaled / denominator) {
+        Ok(0) => Err(InvalidResourceLimit::Zero(what)),
+        Ok(value) => Ok(value),
+        Err(_) => Err(InvalidResourceLimit::TooLarge(what)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("1500m", 1500)]
+    #[case("1m", 1)]
+    #[case("2", 2000)]
+    #[case("1.5", 1500)]
+    #[case("0.25", 250)]
+    #[case(".5", 500)]
+    #[case("1.500", 1500)]
+    #[case(" 3 ", 3000)]
+    fn test_parse_cpu_limit(#[case] given: &str, #[case] millicores: u32) {
+        assert_eq!(CpuLimit::from_str(given), Ok(CpuLimit(millicores)))
+    }
+
+    #[rstest]
+    #[case("", InvalidResourceLimit::NotANumber("".to_string()))]
+    #[case("m", InvalidResourceLimit::NotANumber("m".to_string()))]
+    #[case("1.2.3", InvalidResourceLimit::NotANumber("1.2.3".to_string()))]
+    #[case(".m", InvalidResourceLimit::NotANumber(".m".to_string()))]
+    #[case("-1", InvalidResourceLimit::NotANumber("-1".to_string()))]
+    #[case("2 cores", InvalidResourceLimit::UnknownUnit {
+        unit: "cores".to_string(),
+        expected: "\"m\" for millicores, or none for cores"
+    })]
+    #[case("1500M", InvalidResourceLimit::UnknownUnit {
+        unit: "M".to_string(),
+        expected: "\"m\" for millicores, or none for cores"
+    })]
+    #[case("0", InvalidResourceLimit::Zero("CPU limit"))]
+    #[case("0m", InvalidResourceLimit::Zero("CPU limit"))]
+    #[case("1.0005", InvalidResourceLimit::Fractional {
+        what: "CPU limit",
+        unit: "millicores"
+    })]
+    #[case("1.5m", InvalidResourceLimit::Fractional {
+        what: "CPU limit",
+        unit: "millicores"
+    })]
+    #[case("5000000000m", InvalidResourceLimit::TooLarge("CPU limit"))]
+    fn test_reject_cpu_limit(#[case] given: &str, #[case] expected: InvalidResourceLimit) {
+        assert_eq!(CpuLimit::from_str(given), Err(expected))
+    }
+
+    #[rstest]
+    #[case("2Gi", 2048)]
+    #[case("2gi", 2048)]
+    #[case("2g", 2048)]
+    #[case("2G", 2048)]
+    #[case("2GB", 2048)]
+    #[case("2 GB", 2048)]
+    #[case("2048Mi", 2048)]
+    #[case("1500Mi", 1500)]
+    #[case("500M", 500)]
+    #[case("500mb", 500)]
+    #[case("1.5Gi", 1536)]
+    #[case("1Ti", 1048576)]
+    fn test_parse_memory_limit(#[case] given: &str, #[case] mebibytes: u32) {
+        assert_eq!(MemoryLimit::from_str(given), Ok(MemoryLimit(mebibytes)))
+    }
+
+    #[rstest]
+    #[case("2048", InvalidResourceLimit::MissingUnit("2048".to_string()))]
+    #[case("", InvalidResourceLimit::NotANumber("".to_string()))]
+    #[case("Gi", InvalidResourceLimit::NotANumber("Gi".to_string()))]
+    #[case("2Ki", InvalidResourceLimit::UnknownUnit {
+        unit: "Ki".to_string(),
+        expected: "one of Mi, Gi, Ti, M, G, T, MB, GB, or TB"
+    })]
+    #[case("2 gigs", InvalidResourceLimit::UnknownUnit {
+        unit: "gigs".to_string(),
+        expected: "one of Mi, Gi, Ti, M, G, T, MB, GB, or TB"
+    })]
+    #[case("0Gi", InvalidResourceLimit::Zero("memory limit"))]
+    #[case("0.5Mi", InvalidResourceLimit::Fractional {
+        what: "memory limit",
+        unit: "Mi"
+    })]
+    #[case("4096Ti", InvalidResourceLimit::TooLarge("memory limit"))]
+    fn test_reject_memory_limit(#[case] given: &str, #[case] expected: InvalidResourceLimit) {
+        assert_eq!(MemoryLimit::from_str(given), Err(expected))
+    }
+
+    #[rstest]
+    #[case(MemoryLimit(2048), "2Gi")]
+    #[case(MemoryLimit(1536), "1536Mi")]
+    #[case(MemoryLimit(1), "1Mi")]
+    fn test_display_memory_limit(#[case] limit: MemoryLimit, #[case] expected: &str) {
+        assert_eq!(limit.to_string(), expected)
+    }
+
+    #[rstest]
+    fn test_into_plugin_parameter_value() {
+        assert_eq!(
+            PluginParameterValue::from(CpuLimit::from_str("1.5").unwrap()),
+            PluginParameterValue::Stringish("1500m".to_string())
+        );
+        assert_eq!(
+            PluginParameterValue::from(MemoryLimit::from_str("2GB").unwrap()),
+            PluginParameterValue::Stringish("2Gi".to_string())
+        );
+    }
+
+    #[rstest]
+    fn test_serde() {
+        let limit: MemoryLimit = serde_json::from_str("\"1024Mi\"").unwrap();
+        assert_eq!(serde_json::to_string(&limit).unwrap(), "\"1Gi\"");
+        assert!(serde_json::from_str::<CpuLimit>("\"lots\"").is_err());
+    }
+}
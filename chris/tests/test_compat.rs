@@ -0,0 +1,98 @@
+//! Deserialization of responses captured from different versions of _CUBE_.
+//! Fields which were added in newer versions of _CUBE_ must be optional.
+
+use chris::types::*;
+use chris::{
+    BasicFileResponse, Downloadable, FeedFileResponse, FeedResponse, PluginInstanceResponse,
+    PluginResponse,
+};
+use rstest::*;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+fn read_response<T: DeserializeOwned>(cube_version: &str, name: &str) -> T {
+    let path = Path::new("tests/data/responses")
+        .join(cube_version)
+        .join(name);
+    let data = fs_err::read_to_string(path).unwrap();
+    serde_json::from_str(&data).unwrap()
+}
+
+#[rstest]
+#[case("cube_3")]
+#[case("cube_6")]
+fn test_deserialize_all(#[case] cube_version: &str) {
+    let _: FeedResponse = read_response(cube_version, "feed.json");
+    let _: PluginInstanceResponse = read_response(cube_version, "plugin_instance.json");
+    let _: PluginResponse = read_response(cube_version, "plugin.json");
+    let _: FeedFileResponse = read_response(cube_version, "file.json");
+}
+
+#[rstest]
+fn test_old_feed() {
+    let feed: FeedResponse = read_response("cube_3", "feed.json");
+    assert_eq!(feed.public, None);
+    assert!(!feed.is_public());
+    assert_eq!(feed.registering_jobs, None);
+    assert_eq!(feed.running_jobs(), 1);
+    assert!(feed.has_errored_job());
+}
+
+#[rstest]
+fn test_new_feed() {
+    let feed: FeedResponse = read_response("cube_6", "feed.json");
+    assert!(feed.is_public());
+    assert_eq!(feed.registering_jobs, Some(0));
+    assert!(!feed.has_unfinished_jobs());
+}
+
+#[rstest]
+fn test_old_plugin_instance() {
+    let plinst: PluginInstanceResponse = read_response("cube_3", "plugin_instance.json");
+    assert_eq!(plinst.id, PluginInstanceId(27));
+    assert_eq!(plinst.status, Status::Started);
+    assert_eq!(plinst.output_path, None);
+    assert_eq!(plinst.size, None);
+    assert_eq!(plinst.error_code, None);
+    assert_eq!(plinst.summary, None);
+}
+
+#[rstest]
+fn test_new_plugin_instance() {
+    let plinst: PluginInstanceResponse = read_response("cube_6", "plugin_instance.json");
+    assert!(plinst
+        .output_path
+        .as_deref()
+        .unwrap()
+        .ends_with("/pl-visual-dataset_318/data"));
+    assert_eq!(plinst.size, Some(453034));
+    assert_eq!(plinst.error_code.as_deref(), Some(""));
+    assert!(plinst.summary.is_some());
+}
+
+#[rstest]
+#[case("cube_3", None)]
+#[case("cube_6", Some(0))]
+fn test_plugin(#[case] cube_version: &str, #[case] gpu_limit: Option<u32>) {
+    let plugin: PluginResponse = read_response(cube_version, "plugin.json");
+    assert_eq!(plugin.min_gpu_limit, gpu_limit);
+    assert_eq!(plugin.max_gpu_limit, gpu_limit);
+}
+
+#[rstest]
+#[case("cube_3", "brain.mgz", 2437265)]
+#[case("cube_6", "index.json", 1211)]
+fn test_file(#[case] cube_version: &str, #[case] basename: &str, #[case] fsize: u64) {
+    let file: FeedFileResponse = read_response(cube_version, "file.json");
+    assert_eq!(file.basename(), basename);
+    assert_eq!(file.fsize(), fsize);
+}
+
+#[rstest]
+#[case("cube_3", None)]
+#[case("cube_6", Some("sandip117"))]
+fn test_basic_file(#[case] cube_version: &str, #[case] owner: Option<&str>) {
+    let file: BasicFileResponse = read_response(cube_version, "file.json");
+    assert!(file.creation_date.is_some());
+    assert_eq!(file.owner_username.as_ref().map(|u| u.as_str()), owner);
+}
@@ -162,6 +162,57 @@ async fn test_count_and_lazy_setters(
     Ok(())
 }
 
+#[rstest]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_title(pl_mri10yr: &PluginRw) -> AnyResult {
+    let title = uuid::Uuid::new_v4().hyphenated().to_string();
+    let plinst = pl_mri10yr.create_instance::<[&str]>(&[]).await?;
+    let changed = plinst.set_title(&title).await?;
+    assert_eq!(changed.object.id, plinst.object.id);
+    assert_eq!(changed.object.title, title);
+    let cleared = changed.set_title("").await?;
+    assert_eq!(cleared.object.title, "");
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_change_password_and_email(cube_url: CubeUrl) -> AnyResult {
+    let username: String = fake::faker::internet::en::Username().fake();
+    let email: String = fake::faker::internet::en::SafeEmail().fake();
+    let password = format!("{}1234", &username.chars().rev().collect::<String>());
+    let username = Username::new(username);
+    let account = Account::new(&cube_url, &username, &password);
+    account.create_account(&email).await?;
+    let token = account.get_token().await?;
+    let details = account.get_details(&token).await?;
+    assert_eq!(details.username, username);
+    assert_eq!(details.email, email);
+
+    let client = ChrisClient::build(cube_url.clone(), username.clone(), &token)?
+        .connect()
+        .await?;
+    let new_password = format!("{}5678", password);
+    assert!(client
+        .change_password("wrong", &new_password)
+        .await
+        .is_err());
+    client.change_password(&password, &new_password).await?;
+    assert!(account.get_token().await.is_err());
+    let new_token = Account::new(&cube_url, &username, &new_password)
+        .get_token()
+        .await?;
+
+    let new_email: String = fake::faker::internet::en::SafeEmail().fake();
+    let client = ChrisClient::build(cube_url.clone(), username.clone(), &new_token)?
+        .connect()
+        .await?;
+    let changed = client.change_email(&new_email).await?;
+    assert_eq!(changed.object.email, new_email);
+    assert_eq!(account.get_details(&new_token).await?.email, new_email);
+    Ok(())
+}
+
 async fn count_feeds_with_name(client: &ChrisClient, name: &str) -> usize {
     client
         .feeds()
@@ -40,6 +40,50 @@ async fn test_filebrowser_subdirs(chris_client: &AnonChrisClient) -> AnyResult {
     Ok(())
 }
 
+#[rstest]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_filebrowser_walk_feed_output(chris_client: &AnonChrisClient) -> AnyResult {
+    let fb = chris_client.filebrowser();
+    let entries: Vec<_> = fb
+        .walk("chrisui/feed_310/pl-dircopy_313", None)
+        .try_collect()
+        .await?;
+    let paths: Vec<_> = entries.iter().map(|e| e.path().as_str()).collect();
+    assert_eq!(paths[0], "chrisui/feed_310/pl-dircopy_313");
+    let data = entries
+        .iter()
+        .find(|e| {
+            e.path().as_str()
+                == "chrisui/feed_310/pl-dircopy_313/pl-unstack-folders_314/pl-mri-preview_875/data"
+        })
+        .expect("Output folder of pl-mri-preview not found");
+    assert_eq!(data.depth(), 3);
+    let fnames: Vec<_> = data
+        .iter_files()
+        .stream()
+        .map_ok(|f| f.fname().to_string())
+        .try_collect()
+        .await?;
+    assert!(fnames.iter().any(|f| f.ends_with("/fetal-template-22.txt")));
+    let unique_paths: HashSet<_> = paths.iter().collect();
+    assert_eq!(unique_paths.len(), paths.len());
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_filebrowser_walk_depth_limit(chris_client: &AnonChrisClient) -> AnyResult {
+    let fb = chris_client.filebrowser();
+    let entries: Vec<_> = fb.walk("chrisui", Some(2)).try_collect().await?;
+    let depths: Vec<_> = entries.iter().map(|e| e.depth()).collect();
+    assert!(depths.windows(2).all(|w| w[0] <= w[1]), "not breadth-first");
+    assert_eq!(depths.last(), Some(&2));
+    assert!(entries
+        .iter()
+        .any(|e| e.path().as_str() == "chrisui/feed_310/pl-dircopy_313"));
+    Ok(())
+}
+
 #[rstest]
 #[tokio::test(flavor = "multi_thread")]
 async fn test_filebrowser_download_file(chris_client: &AnonChrisClient) -> AnyResult {
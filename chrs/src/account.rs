@@ -0,0 +1,151 @@
+//! `chrs account`: change the password or email address of the current user.
+
+use std::io::BufRead;
+
+use clap::Subcommand;
+use color_eyre::eyre::{self, bail, eyre, OptionExt, Result, WrapErr};
+use color_eyre::owo_colors::OwoColorize;
+
+use chris::errors::CubeError;
+use chris::{Account, BaseChrisClient, ChrisClient};
+
+use crate::credentials::{is_unauthorized, Credentials, NO_ARGS};
+use crate::login::state::ChrsSessions;
+
+#[derive(Subcommand)]
+pub enum AccountCommand {
+    /// Change the password of the current user
+    Passwd {
+        /// Read the old password and the new password from stdin, one per line
+        #[clap(long)]
+        stdin: bool,
+    },
+
+    /// Change the email address of the current user
+    Email {
+        /// New email address
+        email: String,
+    },
+}
+
+/// `chrs account` command
+pub async fn account(credentials: Credentials, command: AccountCommand) -> Result<()> {
+    let (client, _, _) = credentials.clone().get_client(NO_ARGS).await?;
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to change your account.")?;
+    match command {
+        AccountCommand::Passwd { stdin } => passwd(credentials, &client, stdin).await,
+        AccountCommand::Email { email } => {
+            let user = client
+                .change_email(&email)
+                .await
+                .wrap_err("Could not change email address")?;
+            eprintln!(
+                "Email address of {} changed to {}.",
+                user.object.username.as_str().bold(),
+                user.object.email.bold()
+            );
+            Ok(())
+        }
+    }
+}
+
+async fn passwd(credentials: Credentials, client: &ChrisClient, stdin: bool) -> Result<()> {
+    let (old, new) = if stdin {
+        read_passwords(std::io::stdin().lock())?
+    } else {
+        prompt_passwords()?
+    };
+    client
+        .change_password(&old, &new)
+        .await
+        .map_err(|e| match e {
+            CubeError::IncorrectPassword => eyre!("The old password is incorrect."),
+            e => eyre::Report::new(e).wrap_err("Could not change password"),
+        })?;
+    eprintln!("Password of {} changed.", client.username().as_str().bold());
+
+    // CUBE may invalidate the tokens of a user whose password was changed
+    let token_is_valid = match client.user().await {
+        Ok(_) => true,
+        Err(e) => {
+            let e = eyre::Report::new(e);
+            if is_unauthorized(&e) {
+                false
+            } else {
+                return Err(e);
+            }
+        }
+    };
+    if token_is_valid {
+        return Ok(());
+    }
+    if credentials.ephemeral {
+        eprintln!(
+            "{} the token was invalidated by the password change. Log in again to get a new token.",
+            "warning:".yellow()
+        );
+        return Ok(());
+    }
+    let token = Account::new(client.url(), client.username(), &new)
+        .get_token()
+        .await
+        .wrap_err("Could not log in using the new password")?;
+    ChrsSessions::modify(credentials.config_path.as_deref(), |sessions| {
+        sessions.replace_token(client.url(), client.username(), &token, &new)
+    })?;
+    eprintln!(
+        "{}",
+        "The token was invalidated by the password change, so a new token was saved.".dimmed()
+    );
+    Ok(())
+}
+
+/// Prompt for the old password, then the new password twice.
+fn prompt_passwords() -> Result<(String, String)> {
+    let old = dialoguer::Password::new()
+        .with_prompt("Old password")
+        .interact()?;
+    let new = dialoguer::Password::new()
+        .with_prompt("New password")
+        .with_confirmation("Confirm new password", "Passwords do not match.")
+        .interact()?;
+    Ok((old, new))
+}
+
+/// Read the old password and the new password, one per line.
+fn read_passwords(input: impl BufRead) -> Result<(String, String)> {
+    let mut lines = input.lines();
+    let mut next_line = |name: &str| {
+        lines
+            .next()
+            .transpose()?
+            .filter(|line| !line.is_empty())
+            .ok_or_else(|| eyre!("The {} password was not given.", name))
+    };
+    let old = next_line("old")?;
+    let new = next_line("new")?;
+    if old == new {
+        bail!("The new password is the same as the old password.")
+    }
+    Ok((old, new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("old\nnew\n", Some(("old", "new")))]
+    #[case("old\r\nnew", Some(("old", "new")))]
+    #[case("old\n", None)]
+    #[case("\nnew\n", None)]
+    #[case("same\nsame\n", None)]
+    fn test_read_passwords(#[case] input: &str, #[case] expected: Option<(&str, &str)>) {
+        let actual = read_passwords(input.as_bytes()).ok();
+        let expected = expected.map(|(old, new)| (old.to_string(), new.to_string()));
+        assert_eq!(actual, expected)
+    }
+}
@@ -1,6 +1,8 @@
-pub use given_plugin_instance::GivenPluginInstanceOrPath;
+pub(crate) use feed_graph::is_file_path;
+pub use given_plugin_instance::{output_path_of, parse_output_root, GivenPluginInstanceOrPath};
 pub use runnable::{GivenRunnable, Runnable};
 
+mod feed_graph;
 mod given_data_node;
 mod given_plugin_instance;
 mod runnable;
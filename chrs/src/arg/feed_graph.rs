@@ -0,0 +1,353 @@
+//! Resolution of relative paths such as `../Title` on the graph of plugin instances
+//! of a feed, instead of on the folders of the filebrowser, which do not exist
+//! for plugin instances which did not produce files (e.g. errored ones).
+
+use color_eyre::eyre::{bail, Result};
+use futures::{future, TryStreamExt};
+use itertools::Itertools;
+
+use chris::types::{FeedId, PluginInstanceId};
+use chris::{BaseChrisClient, ChrisClient, EitherClient, PluginInstanceResponse, PluginInstanceRo};
+
+/// A plugin instance, as a node of the graph of its feed.
+///
+/// `plinst` is the object the node was made from, so that it does not need to be
+/// fetched again after the graph was walked.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GraphNode<P = ()> {
+    pub id: PluginInstanceId,
+    pub previous: Option<PluginInstanceId>,
+    pub feed: FeedId,
+    pub title: String,
+    /// Name of the plugin instance's output folder, e.g. `pl-dircopy_543`.
+    pub folder: String,
+    pub plinst: P,
+}
+
+impl<P> GraphNode<P> {
+    fn new(p: &PluginInstanceResponse, plinst: P) -> Self {
+        Self {
+            id: p.id,
+            previous: p.previous_id,
+            feed: p.feed_id,
+            title: p.title.clone(),
+            folder: format!("{}_{}", p.plugin_name.as_str(), p.id.0),
+            plinst,
+        }
+    }
+}
+
+impl From<PluginInstanceRo> for GraphNode<PluginInstanceRo> {
+    fn from(p: PluginInstanceRo) -> Self {
+        let object = p.object.clone();
+        Self::new(&object, p)
+    }
+}
+
+/// The plugin instance graph of feeds.
+pub(crate) trait FeedGraph {
+    /// The plugin instance objects carried by the nodes, see [GraphNode::plinst].
+    type Plinst;
+
+    async fn node(&self, id: PluginInstanceId) -> Result<GraphNode<Self::Plinst>>;
+
+    /// Plugin instances which come after the plugin instance `id`.
+    async fn children(&self, id: PluginInstanceId) -> Result<Vec<GraphNode<Self::Plinst>>>;
+}
+
+impl FeedGraph for ChrisClient {
+    type Plinst = PluginInstanceRo;
+
+    async fn node(&self, id: PluginInstanceId) -> Result<GraphNode<PluginInstanceRo>> {
+        let plinst: PluginInstanceRo = self.get_plugin_instance(id).await?.into();
+        Ok(plinst.into())
+    }
+
+    async fn children(&self, id: PluginInstanceId) -> Result<Vec<GraphNode<PluginInstanceRo>>> {
+        let query = self.plugin_instances().previous_id(id);
+        let search = query.search().into_ro();
+        let children = search
+            .stream_connected()
+            .map_ok(GraphNode::from)
+            .try_collect()
+            .await?;
+        Ok(children)
+    }
+}
+
+impl FeedGraph for EitherClient {
+    type Plinst = PluginInstanceRo;
+
+    async fn node(&self, id: PluginInstanceId) -> Result<GraphNode<PluginInstanceRo>> {
+        Ok(self.get_plugin_instance(id).await?.into())
+    }
+
+    async fn children(&self, id: PluginInstanceId) -> Result<Vec<GraphNode<PluginInstanceRo>>> {
+        match self {
+            Self::LoggedIn(c) => c.children(id).await,
+            // anonymous users cannot search plugin instances, but they can get
+            // the plugin instances of a public feed from the feed's link to them.
+            Self::Anon(_) => {
+                let plinst = self.get_plugin_instance(id).await?;
+                let feed = plinst.feed().get().await?;
+                let children = feed
+                    .get_plugin_instances()
+                    .stream_connected()
+                    .try_filter(|p| future::ready(p.object.previous_id == Some(id)))
+                    .map_ok(GraphNode::from)
+                    .try_collect()
+                    .await?;
+                Ok(children)
+            }
+        }
+    }
+}
+
+/// Whether a relative path should be resolved as a file path instead of on the
+/// graph of plugin instances, i.e. it goes into a `data` folder.
+pub(crate) fn is_file_path(rel_path: &str) -> bool {
+    rel_path.split('/').any(|segment| segment == "data")
+}
+
+/// Resolve a relative path starting from the plugin instance `from`:
+///
+/// - `..` is the previous plugin instance
+/// - `Title` or `./Title` is a child plugin instance by title
+/// - `../Title` is a sibling plugin instance by title
+///
+/// Besides a title, a segment may also be the name of an output folder (e.g. `pl-dircopy_543`)
+/// or a plugin instance ID.
+pub(crate) async fn resolve_relative<G: FeedGraph>(
+    graph: &G,
+    from: PluginInstanceId,
+    rel_path: &str,
+) -> Result<GraphNode<G::Plinst>> {
+    let mut current = graph.node(from).await?;
+    for segment in rel_path.split('/') {
+        current = match segment {
+            "" | "." => current,
+            ".." => {
+                if let Some(previous) = current.previous {
+                    graph.node(previous).await?
+                } else {
+                    bail!(
+                        "The relative path {} climbs above the root of feed/{}",
+                        rel_path,
+                        current.feed.0
+                    )
+                }
+            }
+            name => child_named(graph, &current, name).await?,
+        };
+    }
+    Ok(current)
+}
+
+async fn child_named<G: FeedGraph>(
+    graph: &G,
+    parent: &GraphNode<G::Plinst>,
+    name: &str,
+) -> Result<GraphNode<G::Plinst>> {
+    let mut children = graph.children(parent.id).await?;
+    let titled: Vec<_> = children.iter().filter(|c| c.title == name).collect();
+    if titled.len() > 1 {
+        bail!(
+            "Multiple plugin instances titled \"{}\" after plugininstance/{}. Please specify: {}",
+            name,
+            parent.id.0,
+            titled
+                .iter()
+                .map(|c| format!("plugininstance/{}", c.id.0))
+                .join(" ")
+        )
+    }
+    if let Some(i) = children.iter().position(|c| c.title == name) {
+        return Ok(children.swap_remove(i));
+    }
+    if let Some(i) = children.iter().position(|c| c.folder == name) {
+        return Ok(children.swap_remove(i));
+    }
+    if let Ok(id) = name.parse().map(PluginInstanceId) {
+        if let Some(child) = children.into_iter().find(|c| c.id == id) {
+            return Ok(child);
+        }
+        let node = graph.node(id).await?;
+        if node.feed == parent.feed {
+            return Ok(node);
+        }
+        bail!(
+            "plugininstance/{} is not in the same feed as plugininstance/{}",
+            id.0,
+            parent.id.0
+        )
+    }
+    bail!(
+        "No plugin instance titled \"{}\" after plugininstance/{}",
+        name,
+        parent.id.0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use color_eyre::eyre::eyre;
+    use rstest::*;
+
+    /// A feed like this, where plugin instance 5 errored and has no output folder:
+    ///
+    /// ```text
+    ///            1 "upload"
+    ///          /            \
+    ///     2 "reconstruct"    3 "segment"
+    ///      |                  |       \
+    ///     4 "stats"          5 "fit"   6 "segment"
+    ///                         |
+    ///                        7 "report"
+    /// ```
+    struct FakeFeed(Vec<GraphNode>);
+
+    impl FeedGraph for FakeFeed {
+        type Plinst = ();
+
+        async fn node(&self, id: PluginInstanceId) -> Result<GraphNode> {
+            self.0
+                .iter()
+                .find(|n| n.id == id)
+                .cloned()
+                .ok_or_else(|| eyre!("Not found"))
+        }
+
+        async fn children(&self, id: PluginInstanceId) -> Result<Vec<GraphNode>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|n| n.previous == Some(id))
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn node(id: u32, previous: Option<u32>, title: &str) -> GraphNode {
+        GraphNode {
+            id: PluginInstanceId(id),
+            previous: previous.map(PluginInstanceId),
+            feed: FeedId(if id < 100 { 1 } else { 2 }),
+            title: title.to_string(),
+            folder: format!("pl-example_{id}"),
+            plinst: (),
+        }
+    }
+
+    #[fixture]
+    fn feed() -> FakeFeed {
+        FakeFeed(vec![
+            node(1, None, "upload"),
+            node(2, Some(1), "reconstruct"),
+            node(3, Some(1), "segment"),
+            node(4, Some(2), "stats"),
+            node(5, Some(3), "fit"),
+            node(6, Some(3), "segment"),
+            node(7, Some(5), "report"),
+            node(101, None, "other feed"),
+        ])
+    }
+
+    #[rstest]
+    #[case(4, ".", 4)]
+    #[case(4, "..", 2)]
+    #[case(4, "../", 2)]
+    #[case(4, "../..", 1)]
+    #[case(2, "../segment", 3)]
+    #[case(2, "../segment/fit", 5)]
+    #[case(7, "../../segment", 6)]
+    #[case(6, "../fit/report", 7)]
+    #[case(5, "../../reconstruct/./stats", 4)]
+    #[case(1, "segment", 3)]
+    #[case(1, "./segment/fit", 5)]
+    #[case(3, "./pl-example_5", 5)]
+    #[case(3, "6", 6)]
+    #[case(4, "../../3", 3)]
+    #[case(4, "7", 7)]
+    #[tokio::test]
+    async fn test_resolve_relative(
+        feed: FakeFeed,
+        #[case] from: u32,
+        #[case] rel_path: &str,
+        #[case] expected: u32,
+    ) {
+        let actual = resolve_relative(&feed, PluginInstanceId(from), rel_path)
+            .await
+            .unwrap();
+        assert_eq!(actual.id, PluginInstanceId(expected))
+    }
+
+    #[rstest]
+    #[case(1, "..", "climbs above the root of feed/1")]
+    #[case(4, "../../..", "climbs above")]
+    #[case(2, "../nonexistent", "No plugin instance titled \"nonexistent\"")]
+    #[case(4, "101", "not in the same feed")]
+    #[tokio::test]
+    async fn test_resolve_relative_fails(
+        feed: FakeFeed,
+        #[case] from: u32,
+        #[case] rel_path: &str,
+        #[case] expected: &str,
+    ) {
+        let error = resolve_relative(&feed, PluginInstanceId(from), rel_path)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains(expected), "{error}")
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_resolve_relative_ambiguous_title(mut feed: FakeFeed) {
+        feed.0.push(node(8, Some(1), "segment"));
+        let error = resolve_relative(&feed, PluginInstanceId(2), "../segment")
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("plugininstance/3 plugininstance/8"))
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_resolve_relative_anonymously() {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(1, "pl-example", "1.0.0");
+        let feed = mock.feed(1, "public feed");
+        let upload = PluginInstanceResponse {
+            title: "upload".to_string(),
+            ..mock.plugin_instance(1, &plugin, &feed, None)
+        };
+        let children =
+            [(2, "reconstruct"), (3, "segment")].map(|(id, title)| PluginInstanceResponse {
+                title: title.to_string(),
+                ..mock.plugin_instance(id, &plugin, &feed, Some(&upload))
+            });
+        mock.add_feed(feed);
+        mock.add_plugin_instance(upload);
+        for plinst in children {
+            mock.add_plugin_instance(plinst);
+        }
+        let client = EitherClient::Anon(mock.anon_client().await);
+        let actual = resolve_relative(&client, PluginInstanceId(2), "../segment")
+            .await
+            .unwrap();
+        assert_eq!(actual.id, PluginInstanceId(3));
+        assert_eq!(actual.plinst.object.title, "segment");
+        assert!(mock.requests().iter().all(|r| !r.contains("previous_id")));
+    }
+
+    #[rstest]
+    #[case("../data", true)]
+    #[case("./data/masks", true)]
+    #[case("../segment", false)]
+    #[case("../database", false)]
+    fn test_is_file_path(#[case] rel_path: &str, #[case] expected: bool) {
+        assert_eq!(is_file_path(rel_path), expected)
+    }
+}
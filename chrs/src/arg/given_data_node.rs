@@ -6,12 +6,11 @@ use itertools::Itertools;
 
 use chris::types::{FeedId, PluginInstanceId};
 use chris::{
-    Access, BaseChrisClient, ChrisClient, EitherClient, Feed, FeedRo, PluginInstance,
+    Access, BaseChrisClient, ChrisClient, EitherClient, Feed, FeedRo, FeedRw, PluginInstance,
     PluginInstanceRo, PluginInstanceRw, RoAccess,
 };
 
-use crate::arg::GivenPluginInstanceOrPath;
-use crate::error_messages::CANNOT_ANONYMOUSLY_SEARCH;
+use crate::arg::{output_path_of, GivenPluginInstanceOrPath};
 
 /// A user-provided string resolved as either a feed, plugin instance, or _ChRIS_ filesystem path.
 #[derive(Debug, Clone)]
@@ -116,6 +115,23 @@ impl GivenDataNode {
         }
     }
 
+    /// Returns `true` if this is a feed ID or name, or ambiguous, which
+    /// [GivenDataNode::into_feed_rw] assumes to be a feed name.
+    pub fn is_feed(&self) -> bool {
+        matches!(
+            self,
+            GivenDataNode::FeedId { .. } | GivenDataNode::FeedName(_) | GivenDataNode::Ambiguous(_)
+        )
+    }
+
+    /// Returns `true` if this is a relative path.
+    pub fn is_relative_path(&self) -> bool {
+        matches!(
+            self,
+            GivenDataNode::PluginInstanceOrPath(GivenPluginInstanceOrPath::RelativePath(_))
+        )
+    }
+
     // /// Returns `true` if this is [GivenDataNode::Ambiguous]
     // pub fn is_ambiguous(&self) -> bool {
     //     match self {
@@ -152,6 +168,27 @@ impl GivenDataNode {
         }
     }
 
+    /// Get the CUBE object interpreted as a feed.
+    ///
+    /// - Plugin instances and paths are resolved to the feed they belong to.
+    /// - Ambiguous value assumed to be a feed name
+    pub async fn into_feed_rw(
+        self,
+        client: &ChrisClient,
+        old: Option<PluginInstanceId>,
+    ) -> eyre::Result<FeedRw> {
+        let id = match self {
+            GivenDataNode::FeedId { id, .. } => id,
+            GivenDataNode::FeedName(name) | GivenDataNode::Ambiguous(name) => {
+                get_feedid_by_name(client, name).await?
+            }
+            GivenDataNode::PluginInstanceOrPath(given) => {
+                given.get_using_rw(client, old).await?.object.feed_id
+            }
+        };
+        client.get_feed(id).await.map_err(Error::new)
+    }
+
     /// Get the CUBE object interpreted as a plugin instance.
     ///
     /// - Plugin instances are returned as plugin instances (duh)
@@ -182,8 +219,8 @@ impl GivenDataNode {
     ///
     /// ## Limitations
     ///
-    /// Does not work in some cases for anonymous users.
-    /// See <https://github.com/FNNDSC/ChRIS_ultron_backEnd/issues/530>
+    /// Anonymous users can only find plugin instances of public feeds by title if the current
+    /// plugin instance is of the same feed. See <https://github.com/FNNDSC/ChRIS_ultron_backEnd/issues/530>
     pub async fn into_plinst_either(
         self,
         client: &EitherClient,
@@ -204,8 +241,14 @@ impl GivenDataNode {
             }
         };
         match self {
-            GivenDataNode::FeedId { .. } => Err(eyre!(CANNOT_ANONYMOUSLY_SEARCH)),
-            GivenDataNode::FeedName(_) => Err(eyre!(CANNOT_ANONYMOUSLY_SEARCH)),
+            GivenDataNode::FeedId { id, .. } => {
+                let feed = client.get_feed(id).await?;
+                get_plinst_of_feed_ro(&feed).await
+            }
+            GivenDataNode::FeedName(name) => {
+                let feed = get_feedro_by_name(client, &name).await?;
+                get_plinst_of_feed_ro(&feed).await
+            }
             GivenDataNode::PluginInstanceOrPath(given) => given.get_using_either(client, old).await,
             GivenDataNode::Ambiguous(given) => {
                 GivenPluginInstanceOrPath::from(given)
@@ -224,8 +267,8 @@ impl GivenDataNode {
     ///
     /// ## Limitations
     ///
-    /// Does not work in some cases for anonymous users.
-    /// See <https://github.com/FNNDSC/ChRIS_ultron_backEnd/issues/530>
+    /// Anonymous users can only find plugin instances of public feeds by title if the current
+    /// plugin instance is of the same feed. See <https://github.com/FNNDSC/ChRIS_ultron_backEnd/issues/530>
     pub async fn into_path(
         self,
         client: &EitherClient,
@@ -234,20 +277,28 @@ impl GivenDataNode {
         if let Some(logged_in) = client.logged_in_ref() {
             match self {
                 GivenDataNode::FeedId { id, .. } => {
-                    return get_plinst_of_feed(logged_in, id).await.map(plinst_path);
+                    return get_plinst_of_feed(logged_in, id)
+                        .await
+                        .and_then(plinst_path);
                 }
                 GivenDataNode::FeedName(name) => {
                     let feed_id = get_feedid_by_name(logged_in, name).await?;
                     return get_plinst_of_feed(logged_in, feed_id)
                         .await
-                        .map(plinst_path);
+                        .and_then(plinst_path);
                 }
                 _ => (),
             }
         }
         match self {
-            GivenDataNode::FeedId { .. } => Err(eyre!(CANNOT_ANONYMOUSLY_SEARCH)),
-            GivenDataNode::FeedName(_) => Err(eyre!(CANNOT_ANONYMOUSLY_SEARCH)),
+            GivenDataNode::FeedId { id, .. } => {
+                let feed = client.get_feed(id).await?;
+                get_plinst_of_feed_ro(&feed).await.and_then(plinst_path)
+            }
+            GivenDataNode::FeedName(name) => {
+                let feed = get_feedro_by_name(client, &name).await?;
+                get_plinst_of_feed_ro(&feed).await.and_then(plinst_path)
+            }
             GivenDataNode::PluginInstanceOrPath(given) => given.into_path(client, old).await,
             GivenDataNode::Ambiguous(given) => {
                 GivenPluginInstanceOrPath::from(given)
@@ -256,14 +307,39 @@ impl GivenDataNode {
             }
         }
     }
+
+    /// Same as [Self::into_path], for logged in users.
+    pub async fn into_path_rw(
+        self,
+        client: &ChrisClient,
+        old: Option<PluginInstanceId>,
+    ) -> eyre::Result<String> {
+        match self {
+            GivenDataNode::FeedId { id, .. } => {
+                get_plinst_of_feed(client, id).await.and_then(plinst_path)
+            }
+            GivenDataNode::FeedName(name) => {
+                let feed_id = get_feedid_by_name(client, name).await?;
+                get_plinst_of_feed(client, feed_id)
+                    .await
+                    .and_then(plinst_path)
+            }
+            GivenDataNode::PluginInstanceOrPath(given) => given.into_path_rw(client, old).await,
+            GivenDataNode::Ambiguous(given) => {
+                GivenPluginInstanceOrPath::from(given)
+                    .into_path_rw(client, old)
+                    .await
+            }
+        }
+    }
 }
 
-fn plinst_path<A: Access>(p: PluginInstance<A>) -> String {
-    p.object
-        .output_path
+fn plinst_path<A: Access>(p: PluginInstance<A>) -> eyre::Result<String> {
+    let output_path = output_path_of(&p.object)?;
+    Ok(output_path
         .strip_suffix("/data")
-        .map(|p| p.to_string())
-        .unwrap_or(p.object.output_path)
+        .unwrap_or(output_path)
+        .to_string())
 }
 
 /// Get the first plugin instance of a feed returned from CUBE's API,
@@ -289,6 +365,22 @@ async fn get_plinst_of_feed(
         })
 }
 
+/// Same as [get_plinst_of_feed], but the plugin instances are listed from the feed's
+/// link to them, which anonymous users can get for public feeds.
+async fn get_plinst_of_feed_ro(feed: &FeedRo) -> eyre::Result<PluginInstanceRo> {
+    feed.get_plugin_instances()
+        .page_limit(1)
+        .max_items(1)
+        .get_first()
+        .await?
+        .ok_or_else(|| {
+            eyre!(
+                "feed/{} does not contain plugin instances. This is a CUBE bug.",
+                feed.object.id.0
+            )
+        })
+}
+
 async fn get_feedid_by_name(client: &ChrisClient, name: String) -> eyre::Result<FeedId> {
     let items: Vec<_> = client
         .feeds()
@@ -1,14 +1,15 @@
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre;
-use color_eyre::eyre::{bail, OptionExt, Result};
+use color_eyre::eyre::{bail, Result};
 use futures::TryStreamExt;
 use itertools::Itertools;
 use std::fmt::Display;
 
+use super::feed_graph::{is_file_path, resolve_relative};
 use crate::error_messages::CANNOT_ANONYMOUSLY_SEARCH;
 use chris::types::PluginInstanceId;
 use chris::{
-    Access, BaseChrisClient, ChrisClient, EitherClient, LinkedModel, PluginInstance,
+    Access, BaseChrisClient, ChrisClient, EitherClient, Feed, LinkedModel, PluginInstance,
     PluginInstanceResponse, PluginInstanceRo, PluginInstanceRw,
 };
 
@@ -16,8 +17,10 @@ use chris::{
 ///
 /// ## Limitations
 ///
-/// A valid absolute path like `rudolph` (which is just his username) will be misidentified as
-/// [GivenPluginInstanceOrPath::Title] instead of [GivenPluginInstanceOrPath::AbsolutePath].
+/// A bare username like `rudolph` cannot be told apart from a title, so it is identified as
+/// [GivenPluginInstanceOrPath::Title]. If no plugin instance has that title, the error says
+/// to write it with a trailing slash, e.g. `rudolph/`, which is always an
+/// [GivenPluginInstanceOrPath::AbsolutePath].
 #[derive(Debug, PartialEq, Clone)]
 pub enum GivenPluginInstanceOrPath {
     Title(String),
@@ -54,8 +57,9 @@ impl From<String> for GivenPluginInstanceOrPath {
         if starts_with_dots(&value) {
             return GivenPluginInstanceOrPath::RelativePath(value);
         }
-        if looks_like_well_known_absolute_path(&value) {
-            return GivenPluginInstanceOrPath::AbsolutePath(value);
+        if value.ends_with('/') || looks_like_well_known_absolute_path(&value) {
+            let path = value.trim_end_matches('/').to_string();
+            return GivenPluginInstanceOrPath::AbsolutePath(path);
         }
         parse_as_id_or_title(&value, &value)
     }
@@ -76,11 +80,13 @@ fn starts_with_dots(value: &str) -> bool {
             .any(|s| value.starts_with(s))
 }
 
+/// Whether the value is a path under a top-level folder of _ChRIS_ storage which
+/// is not a user's folder, e.g. `SERVICES/PACS`, or a path of feed output files.
 fn looks_like_well_known_absolute_path(value: &str) -> bool {
-    value == "SERVICES"
-        || value.starts_with("SERVICES/PACS")
-        || value == "PIPELINES"
-        || value.starts_with("PIPELINES/")
+    value
+        .split('/')
+        .next()
+        .is_some_and(|root| root == "SERVICES" || root == "PIPELINES")
         || looks_like_feed_output_path(value)
 }
 
@@ -128,12 +134,7 @@ impl GivenPluginInstanceOrPath {
                 .await
                 .map_err(eyre::Error::new),
             Self::Title(title) => get_by_title_ro(client, title, old).await,
-            Self::RelativePath(path) => match client {
-                EitherClient::Anon(c) => get_relative_path_as_plinst(c, old, path).await,
-                EitherClient::LoggedIn(c) => get_relative_path_as_plinst(c, old, path)
-                    .await
-                    .map(|p| p.into()),
-            },
+            Self::RelativePath(path) => get_relative_as_plinst_ro(client, old, path).await,
             Self::AbsolutePath(path) => match client {
                 EitherClient::Anon(c) => get_plinst_of_path(c, &path).await,
                 EitherClient::LoggedIn(c) => get_plinst_of_path(c, &path).await.map(|p| p.into()),
@@ -153,7 +154,7 @@ impl GivenPluginInstanceOrPath {
                 .await
                 .map_err(eyre::Error::new),
             GivenPluginInstanceOrPath::RelativePath(path) => {
-                get_relative_path_as_plinst(client, old, path).await
+                get_relative_as_plinst_rw(client, old, path).await
             }
             GivenPluginInstanceOrPath::AbsolutePath(path) => {
                 get_plinst_of_path(client, &path).await
@@ -170,15 +171,31 @@ impl GivenPluginInstanceOrPath {
             GivenPluginInstanceOrPath::Id(id, _) => client
                 .get_plugin_instance(id)
                 .await
-                .map(|p| p.object.output_path)
-                .map_err(eyre::Error::new),
+                .map_err(eyre::Error::new)
+                .and_then(|p| output_path_of(&p.object).map(String::from)),
             GivenPluginInstanceOrPath::Title(title) => get_by_title_ro(client, title, old)
                 .await
-                .map(|p| p.object.output_path),
+                .and_then(|p| output_path_of(&p.object).map(String::from)),
             GivenPluginInstanceOrPath::RelativePath(p) => get_relative_path(client, old, &p).await,
             GivenPluginInstanceOrPath::AbsolutePath(p) => Ok(p),
         }
     }
+
+    /// Same as [Self::into_path], but a title may be of a plugin instance in any feed.
+    pub async fn into_path_rw(
+        self,
+        client: &ChrisClient,
+        old: Option<PluginInstanceId>,
+    ) -> Result<String> {
+        match self {
+            GivenPluginInstanceOrPath::RelativePath(p) => get_relative_path(client, old, &p).await,
+            GivenPluginInstanceOrPath::AbsolutePath(p) => Ok(p),
+            given => given
+                .get_using_rw(client, old)
+                .await
+                .and_then(|p| output_path_of(&p.object).map(String::from)),
+        }
+    }
 }
 
 async fn get_relative_path<A: Access, C: BaseChrisClient<A>>(
@@ -187,9 +204,44 @@ async fn get_relative_path<A: Access, C: BaseChrisClient<A>>(
     rel_path: &str,
 ) -> Result<String> {
     if let Some(id) = old {
-        pwd(client, id, true)
-            .await
-            .map(|p| reconcile_path(&p, rel_path))
+        let wd = pwd(client, id, true).await?;
+        reconcile_path_within_feed(&wd, rel_path)
+    } else {
+        bail!("No current plugin instance context, cannot resolve relative path.")
+    }
+}
+
+/// Resolve a relative path to a plugin instance on the graph of plugin instances
+/// of the current feed, unless it is a path to files.
+async fn get_relative_as_plinst_rw(
+    client: &ChrisClient,
+    old: Option<PluginInstanceId>,
+    rel_path: String,
+) -> Result<PluginInstanceRw> {
+    if is_file_path(&rel_path) {
+        return get_relative_path_as_plinst(client, old, rel_path).await;
+    }
+    if let Some(id) = old {
+        let node = resolve_relative(client, id, &rel_path).await?;
+        Ok(client.upgrade(node.plinst)?)
+    } else {
+        bail!("No current plugin instance context, cannot resolve relative path.")
+    }
+}
+
+/// Same as [get_relative_as_plinst_rw], but anonymous users can resolve relative paths
+/// in public feeds too.
+async fn get_relative_as_plinst_ro(
+    client: &EitherClient,
+    old: Option<PluginInstanceId>,
+    rel_path: String,
+) -> Result<PluginInstanceRo> {
+    if is_file_path(&rel_path) {
+        return get_relative_path_as_plinst(client, old, rel_path).await;
+    }
+    if let Some(id) = old {
+        let node = resolve_relative(client, id, &rel_path).await?;
+        Ok(node.plinst)
     } else {
         bail!("No current plugin instance context, cannot resolve relative path.")
     }
@@ -202,8 +254,8 @@ async fn get_relative_path_as_plinst<A: Access, C: BaseChrisClient<A>>(
 ) -> Result<PluginInstance<A>> {
     if let Some(id) = old {
         let old_output_path = pwd(client, id, true).await?;
-        let requested_path = reconcile_path(&old_output_path, &rel_path);
-        if let Some(id) = parse_plinst_id(&requested_path) {
+        let requested_path = reconcile_path_within_feed(&old_output_path, &rel_path)?;
+        if let Some((id, _)) = parse_output_path(&requested_path) {
             client
                 .get_plugin_instance(id)
                 .await
@@ -216,37 +268,69 @@ async fn get_relative_path_as_plinst<A: Access, C: BaseChrisClient<A>>(
     }
 }
 
+/// Get the plugin instance of a path of feed output files, see [parse_output_path].
 async fn get_plinst_of_path<A: Access, C: BaseChrisClient<A>>(
     client: &C,
     path: &str,
 ) -> Result<PluginInstance<A>> {
-    if let Some(id) = parse_plinst_id(path) {
+    if let Some((id, _)) = parse_output_path(path) {
         client
             .get_plugin_instance(id)
             .await
             .map_err(eyre::Error::new)
     } else {
-        bail!("Path could not be understood as a plugin instance.");
+        bail!(
+            "The path {} is not within the output of a plugin instance.",
+            path
+        );
     }
 }
 
-fn parse_plinst_id(path: &str) -> Option<PluginInstanceId> {
-    path.rsplit_once('_')
-        .map(|(_, r)| r)
-        .and_then(|n| n.parse().ok())
-        .map(PluginInstanceId)
-}
-
+/// Find a plugin instance by title. Anonymous users cannot search for plugin instances,
+/// so they can only find plugin instances of the feed of `old`.
 async fn get_by_title_ro(
     client: &EitherClient,
     name: String,
     old: Option<PluginInstanceId>,
 ) -> Result<PluginInstanceRo> {
     if let EitherClient::LoggedIn(chris) = client {
-        search_title(chris, name, old).await.map(|p| p.into())
-    } else {
-        bail!(CANNOT_ANONYMOUSLY_SEARCH)
+        return search_title(chris, name, old).await.map(|p| p.into());
+    }
+    if let Some(old) = old {
+        let old = client.get_plugin_instance(old).await?;
+        let feed = client.get_feed(old.object.feed_id).await?;
+        if let Some(plinst) = find_title_in_feed(&feed, &name).await? {
+            return Ok(plinst);
+        }
+    }
+    if is_folder(client, &name).await? {
+        return Err(title_is_folder(&name));
     }
+    bail!(CANNOT_ANONYMOUSLY_SEARCH)
+}
+
+/// Find a plugin instance by title among the plugin instances of a feed. Unlike the
+/// search API, the plugin instances of public feeds are available to anonymous users.
+///
+/// Titles are matched the same way as by [search_title].
+async fn find_title_in_feed<A: Access>(
+    feed: &Feed<A>,
+    title: &str,
+) -> Result<Option<PluginInstance<A>>> {
+    let title = title.to_lowercase();
+    let items: Vec<_> = feed
+        .get_plugin_instances()
+        .stream_connected()
+        .try_filter(|p| futures::future::ready(p.object.title.to_lowercase().contains(&title)))
+        .try_collect()
+        .await?;
+    if items.len() > 1 {
+        bail!(
+            "Multiple plugin instances found. Please specify: {}",
+            items.iter().map(plugin_instance_string).join(" ")
+        );
+    }
+    Ok(items.into_iter().next())
 }
 
 async fn search_title(
@@ -288,7 +372,7 @@ async fn search_title_within_feed(
 }
 
 async fn search_title_any_feed(chris: &ChrisClient, title: String) -> Result<PluginInstanceRw> {
-    let query = chris.plugin_instances().title(title);
+    let query = chris.plugin_instances().title(title.as_str());
     let items: Vec<_> = query.search().stream_connected().try_collect().await?;
     if items.len() > 1 {
         bail!(
@@ -296,10 +380,28 @@ async fn search_title_any_feed(chris: &ChrisClient, title: String) -> Result<Plu
             items.iter().map(plugin_instance_string).join(" ")
         );
     }
-    items
-        .into_iter()
-        .next()
-        .ok_or_eyre("Plugin instance not found")
+    if let Some(plinst) = items.into_iter().next() {
+        return Ok(plinst);
+    }
+    if is_folder(chris, &title).await? {
+        return Err(title_is_folder(&title));
+    }
+    bail!("Plugin instance not found")
+}
+
+/// Whether `path` is a folder of _ChRIS_ storage, e.g. the top-level folder of a user.
+async fn is_folder<A: Access>(client: &impl BaseChrisClient<A>, path: &str) -> Result<bool> {
+    Ok(client.filebrowser().readdir(path).await?.is_some())
+}
+
+/// Error for a value which was taken to be a title, though it was meant to be a path.
+fn title_is_folder(title: &str) -> eyre::Report {
+    eyre::eyre!(
+        "No plugin instance is titled \"{}\", but it is the name of a folder. \
+        To specify the folder, write it with a trailing slash: \"{}/\"",
+        title,
+        title
+    )
 }
 
 fn plugin_instance_string<A: Access>(p: &LinkedModel<PluginInstanceResponse, A>) -> String {
@@ -311,14 +413,92 @@ async fn pwd<A: Access, C: BaseChrisClient<A>>(
     id: PluginInstanceId,
     strip_data: bool,
 ) -> Result<String> {
-    let output_path = client.get_plugin_instance(id).await?.object.output_path;
+    let plinst = client.get_plugin_instance(id).await?;
+    let output_path = output_path_of(&plinst.object)?;
     let wd = output_path
         .strip_suffix(if strip_data { "/data" } else { "" })
-        .unwrap_or(&output_path)
+        .unwrap_or(output_path)
         .to_string();
     Ok(wd)
 }
 
+/// Get the output path of a plugin instance, which old versions of _CUBE_ do not report.
+pub fn output_path_of(plinst: &PluginInstanceResponse) -> Result<&str> {
+    plinst.output_path.as_deref().ok_or_else(|| {
+        eyre::eyre!(
+            "CUBE did not report the output path of plugininstance/{}. \
+            Paths of plugin instances are not supported by this version of CUBE.",
+            plinst.id.0
+        )
+    })
+}
+
+/// Like [reconcile_path], but fails if `rel_path` climbs above the feed directory of `wd`.
+fn reconcile_path_within_feed(wd: &str, rel_path: &str) -> Result<String> {
+    let path = reconcile_path(wd, rel_path);
+    let feed_root = wd.splitn(3, '/').take(2).join("/");
+    if Utf8Path::new(&path).starts_with(&feed_root) {
+        Ok(path)
+    } else {
+        bail!(
+            "The relative path {} climbs above the feed directory {}",
+            rel_path,
+            feed_root
+        )
+    }
+}
+
+/// If `path` is the output directory of a plugin instance (with or without the trailing
+/// `/data`), get the plugin instance's ID.
+///
+/// Output directories look like `rudolph/feed_130/pl-dircopy_543/pl-child_544`, where
+/// every folder after `feed_N` is named after a plugin instance.
+pub fn parse_output_root(path: &str) -> Option<PluginInstanceId> {
+    parse_output_path(path)
+        .filter(|(_, within)| within.is_none())
+        .map(|(id, _)| id)
+}
+
+/// If `path` is the output directory of a plugin instance or a path within its `data`
+/// folder, get the plugin instance's ID and the rest of the path after `data`.
+///
+/// The plugin instance is the deepest `*_N` folder before `data`, e.g. the path
+/// `rudolph/feed_130/pl-dircopy_543/pl-child_544/data/masks/mask_6.nii` is the file
+/// `masks/mask_6.nii` of plugin instance 544. Folders within `data` are not plugin
+/// instances, even if their names look like it.
+fn parse_output_path(path: &str) -> Option<(PluginInstanceId, Option<&str>)> {
+    let path = path.trim_end_matches('/');
+    let mut components = path.splitn(3, '/');
+    let _username = components.next()?;
+    components
+        .next()?
+        .strip_prefix("feed_")?
+        .parse::<u32>()
+        .ok()?;
+    let mut id = None;
+    let mut rest = components.next();
+    while let Some(r) = rest {
+        let (folder, after) = r
+            .split_once('/')
+            .map(|(l, r)| (l, Some(r)))
+            .unwrap_or((r, None));
+        if folder == "data" {
+            return id.map(|id| (id, after.filter(|a| !a.is_empty())));
+        }
+        id = Some(parse_plinst_folder(folder)?);
+        rest = after;
+    }
+    id.map(|id| (id, None))
+}
+
+/// Parse the ID from the name of the output folder of a plugin instance, e.g. `pl-dircopy_543`.
+fn parse_plinst_folder(folder: &str) -> Option<PluginInstanceId> {
+    folder
+        .rsplit_once('_')
+        .and_then(|(_, n)| n.parse().ok())
+        .map(PluginInstanceId)
+}
+
 fn reconcile_path(wd: &str, rel_path: &str) -> String {
     let path = Utf8Path::new(wd).to_path_buf();
     rel_path.split('/').fold(path, reduce_path).to_string()
@@ -336,12 +516,17 @@ fn reduce_path(acc: Utf8PathBuf, component: &str) -> Utf8PathBuf {
 
 #[cfg(test)]
 mod tests {
+    use chris::testing::MockCube;
+    use chris::FeedResponse;
     use rstest::*;
 
     use super::*;
 
     #[rstest]
     #[case("hello", "hello")]
+    #[case("rudolph", "rudolph")]
+    #[case("rudolph/uploads", "rudolph/uploads")]
+    #[case("pl-dircopy_543", "pl-dircopy_543")]
     #[case("pi/hello", "hello")]
     #[case("plugininstance/hello", "hello")]
     fn test_given_plugin_instance_is_title(#[case] given: &str, #[case] expected: &str) {
@@ -382,16 +567,54 @@ mod tests {
     #[case("PIPELINES/rudolph/i_am_also_pipeline.yml")]
     #[case("SERVICES/PACS")]
     #[case("SERVICES/PACS/Orthanc/00000_PatientName_000000")]
+    #[case("SERVICES")]
+    #[case("SERVICES/PACS/Orthanc/00000_PatientName_000000/00001-SAG_MPRAGE_220_FOV-a27cf06/0001-1.dcm")]
+    #[case("SERVICES/orthanc")]
+    #[case("PIPELINES")]
+    #[case("PIPELINES/rudolph")]
     #[case("rudolph/feed_130")]
     #[case("rudolph/feed_130/pl-dircopy_543")]
     #[case("rudolph/feed_130/pl-dircopy_543/data")]
     #[case("rudolph/feed_130/pl-dircopy_543/data/output.dat")]
+    #[case("rudolph/feed_130/pl-dircopy_543/pl-child_544/data/masks/mask_6.nii")]
+    #[case("rudolph/feed_130/pl-dircopy_543/data/data/output.dat")]
     fn test_given_plugin_instance_is_absolute_path(#[case] given: &str) {
         let actual: GivenPluginInstanceOrPath = given.to_string().into();
         let expected = GivenPluginInstanceOrPath::AbsolutePath(given.to_string());
         assert_eq!(actual, expected)
     }
 
+    #[rstest]
+    #[case("SERVICES/", "SERVICES")]
+    #[case("SERVICES/PACS/", "SERVICES/PACS")]
+    #[case("PIPELINES/", "PIPELINES")]
+    #[case("PIPELINES/rudolph//", "PIPELINES/rudolph")]
+    #[case("rudolph/feed_130/", "rudolph/feed_130")]
+    #[case("rudolph/", "rudolph")]
+    #[case("rudolph//", "rudolph")]
+    #[case("rudolph/uploads/", "rudolph/uploads")]
+    #[case(
+        "rudolph/feed_130/pl-dircopy_543/data/",
+        "rudolph/feed_130/pl-dircopy_543/data"
+    )]
+    fn test_given_absolute_path_trailing_slash(#[case] given: &str, #[case] expected: &str) {
+        let actual: GivenPluginInstanceOrPath = given.to_string().into();
+        let expected = GivenPluginInstanceOrPath::AbsolutePath(expected.to_string());
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    #[case("SERVICESMAN")]
+    #[case("PIPELINES of mine")]
+    #[case("my SERVICES/PACS")]
+    fn test_given_plugin_instance_is_not_well_known_path(#[case] given: &str) {
+        let actual: GivenPluginInstanceOrPath = given.to_string().into();
+        assert!(!matches!(
+            actual,
+            GivenPluginInstanceOrPath::AbsolutePath(_)
+        ))
+    }
+
     #[rstest]
     #[case("a/b/c", ".", "a/b/c")]
     #[case("a/b/c", "./d", "a/b/c/d")]
@@ -404,4 +627,251 @@ mod tests {
         let actual = reconcile_path(wd, rel_path);
         assert_eq!(&actual, expected)
     }
+
+    #[rstest]
+    #[case(
+        "rudolph/feed_2/pl-dircopy_4",
+        "./data/masks",
+        "rudolph/feed_2/pl-dircopy_4/data/masks"
+    )]
+    #[case(
+        "rudolph/feed_2/pl-dircopy_4",
+        "./data/masks/",
+        "rudolph/feed_2/pl-dircopy_4/data/masks"
+    )]
+    #[case(
+        "rudolph/feed_2/pl-dircopy_4/pl-b_5",
+        "../data",
+        "rudolph/feed_2/pl-dircopy_4/data"
+    )]
+    #[case("rudolph/feed_2/pl-dircopy_4", "..", "rudolph/feed_2")]
+    #[case("rudolph/feed_2/pl-dircopy_4", "../../feed_2/", "rudolph/feed_2")]
+    fn test_reconcile_path_within_feed(
+        #[case] wd: &str,
+        #[case] rel_path: &str,
+        #[case] expected: &str,
+    ) {
+        let actual = reconcile_path_within_feed(wd, rel_path).unwrap();
+        assert_eq!(&actual, expected)
+    }
+
+    #[rstest]
+    #[case("rudolph/feed_2/pl-dircopy_4", "../..")]
+    #[case("rudolph/feed_2/pl-dircopy_4", "../../")]
+    #[case("rudolph/feed_2/pl-dircopy_4", "../../feed_3")]
+    #[case("rudolph/feed_2/pl-dircopy_4/pl-b_5", "../../../../..")]
+    fn test_reconcile_path_above_feed(#[case] wd: &str, #[case] rel_path: &str) {
+        assert!(reconcile_path_within_feed(wd, rel_path).is_err())
+    }
+
+    #[rstest]
+    #[case("rudolph/feed_2/pl-dircopy_4", Some(4))]
+    #[case("rudolph/feed_2/pl-dircopy_4/", Some(4))]
+    #[case("rudolph/feed_2/pl-dircopy_4/data", Some(4))]
+    #[case("rudolph/feed_2/pl-dircopy_4/data/", Some(4))]
+    #[case("rudolph/feed_2/pl-dircopy_4/pl-simpledsapp_5", Some(5))]
+    #[case("rudolph/feed_2/pl-dircopy_4/data/masks", None)]
+    #[case("rudolph/feed_2/pl-dircopy_4/data/mask_6", None)]
+    #[case("rudolph/feed_2", None)]
+    #[case("rudolph/uploads/mask_6", None)]
+    #[case("rudolph/feed_2/pl-dircopy_4/masks/pl-b_5", None)]
+    #[case("rudolph/feed_2/pl-dircopy_4/data/output.dat", None)]
+    fn test_parse_output_root(#[case] path: &str, #[case] expected: Option<u32>) {
+        assert_eq!(parse_output_root(path), expected.map(PluginInstanceId))
+    }
+
+    #[rstest]
+    #[case("rudolph/feed_2/pl-dircopy_4", Some((4, None)))]
+    #[case("rudolph/feed_2/pl-dircopy_4/data", Some((4, None)))]
+    #[case("rudolph/feed_2/pl-dircopy_4/data/", Some((4, None)))]
+    #[case("rudolph/feed_2/pl-dircopy_4/pl-b_5/data", Some((5, None)))]
+    #[case(
+        "rudolph/feed_2/pl-dircopy_4/data/output.dat",
+        Some((4, Some("output.dat")))
+    )]
+    #[case(
+        "rudolph/feed_2/pl-dircopy_4/pl-b_5/data/masks/",
+        Some((5, Some("masks")))
+    )]
+    #[case(
+        "rudolph/feed_2/pl-dircopy_4/pl-b_5/data/masks/mask_6.nii",
+        Some((5, Some("masks/mask_6.nii")))
+    )]
+    #[case(
+        "rudolph/feed_2/pl-dircopy_4/data/mask_6",
+        Some((4, Some("mask_6")))
+    )]
+    #[case(
+        "rudolph/feed_2/pl-dircopy_4/data/pl-b_5/data/x.txt",
+        Some((4, Some("pl-b_5/data/x.txt")))
+    )]
+    #[case(
+        "rudolph/feed_2/pl-dircopy_4/data/data/x.txt",
+        Some((4, Some("data/x.txt")))
+    )]
+    #[case("rudolph/feed_2", None)]
+    #[case("rudolph/feed_2/", None)]
+    #[case("rudolph/feed_2/data", None)]
+    #[case("rudolph/feed_2/data/x_3", None)]
+    #[case("rudolph/feed_2/pl-dircopy_4/masks/x.txt", None)]
+    #[case("rudolph/feed_two/pl-dircopy_4", None)]
+    #[case("rudolph/uploads/mask_6", None)]
+    #[case("rudolph", None)]
+    #[case("SERVICES/PACS/Orthanc/00000_PatientName_000000", None)]
+    fn test_parse_output_path(#[case] path: &str, #[case] expected: Option<(u32, Option<&str>)>) {
+        let expected = expected.map(|(id, within)| (PluginInstanceId(id), within));
+        assert_eq!(parse_output_path(path), expected)
+    }
+
+    #[fixture]
+    async fn public_feed() -> MockCube {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(1, "pl-dircopy", "2.1.1");
+        let feed = FeedResponse {
+            public: Some(true),
+            ..mock.feed(1, "Public Study")
+        };
+        let input = PluginInstanceResponse {
+            title: "Input".to_string(),
+            ..mock.plugin_instance(1, &plugin, &feed, None)
+        };
+        let brain = PluginInstanceResponse {
+            title: "Brain Segmentation".to_string(),
+            ..mock.plugin_instance(2, &plugin, &feed, Some(&input))
+        };
+        let lung = PluginInstanceResponse {
+            title: "Lung Segmentation".to_string(),
+            ..mock.plugin_instance(3, &plugin, &feed, Some(&input))
+        };
+        mock.add_plugin(plugin);
+        mock.add_feed(feed);
+        for plinst in [input, brain, lung] {
+            mock.add_plugin_instance(plinst);
+        }
+        mock
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_anonymous_title_within_public_feed(#[future] public_feed: MockCube) {
+        let mock = public_feed.await;
+        let client = EitherClient::Anon(mock.anon_client().await);
+        let old = Some(PluginInstanceId(1));
+        let given = GivenPluginInstanceOrPath::from("brain".to_string());
+        let actual = given.into_path(&client, old).await.unwrap();
+        assert_eq!(actual, "chris/feed_1/pl-dircopy_1/pl-dircopy_2/data");
+
+        let given = GivenPluginInstanceOrPath::from("segmentation".to_string());
+        let error = given.into_path(&client, old).await.unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Multiple plugin instances found"));
+
+        for old in [old, None] {
+            let given = GivenPluginInstanceOrPath::from("heart".to_string());
+            let error = given.into_path(&client, old).await.unwrap_err();
+            assert_eq!(error.to_string(), CANNOT_ANONYMOUSLY_SEARCH);
+        }
+    }
+
+    #[rstest]
+    #[case("chris/feed_1/pl-dircopy_1", Some(1))]
+    #[case("chris/feed_1/pl-dircopy_1/data/", Some(1))]
+    #[case("chris/feed_1/pl-dircopy_1/pl-dircopy_2/data/output.dat", Some(2))]
+    #[case(
+        "chris/feed_1/pl-dircopy_1/pl-dircopy_3/data/masks/mask_2.nii",
+        Some(3)
+    )]
+    #[case("chris/feed_1/pl-dircopy_1/data/pl-dircopy_3", Some(1))]
+    #[case("chris/feed_1", None)]
+    #[case("chris/feed_1/data/output_3.dat", None)]
+    #[case("chris/uploads/", None)]
+    #[case("SERVICES/PACS/Orthanc/00000_PatientName_000003", None)]
+    #[tokio::test]
+    async fn test_get_plugin_instance_of_absolute_path(
+        #[future] public_feed: MockCube,
+        #[case] given: &str,
+        #[case] expected: Option<u32>,
+    ) {
+        let mock = public_feed.await;
+        let given = GivenPluginInstanceOrPath::from(given.to_string());
+        assert!(matches!(given, GivenPluginInstanceOrPath::AbsolutePath(_)));
+        let client = EitherClient::Anon(mock.anon_client().await);
+        let actual = given
+            .clone()
+            .get_using_either(&client, None)
+            .await
+            .ok()
+            .map(|p| p.object.id.0);
+        assert_eq!(actual, expected);
+        let client = mock.client("chris").await;
+        let actual = given
+            .get_using_rw(&client, None)
+            .await
+            .ok()
+            .map(|p| p.object.id.0);
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    #[case(".", 2, &["plugins/instances/2/"])]
+    #[case("..", 1, &["plugins/instances/2/", "plugins/instances/1/"])]
+    #[case(
+        "../Lung Segmentation",
+        3,
+        &["plugins/instances/2/", "plugins/instances/1/", "plugins/instances/search/"]
+    )]
+    #[tokio::test]
+    async fn test_relative_path_is_not_fetched_again(
+        #[future] public_feed: MockCube,
+        #[case] given: &str,
+        #[case] expected: u32,
+        #[case] expected_requests: &[&str],
+    ) {
+        let mock = public_feed.await;
+        let client = mock.client("chris").await;
+        let before = mock.requests().len();
+        let given = GivenPluginInstanceOrPath::from(given.to_string());
+        let actual = given
+            .get_using_rw(&client, Some(PluginInstanceId(2)))
+            .await
+            .unwrap();
+        assert_eq!(actual.object.id, PluginInstanceId(expected));
+        let requests = &mock.requests()[before..];
+        assert_eq!(requests.len(), expected_requests.len(), "{requests:?}");
+        for (request, expected) in requests.iter().zip(expected_requests) {
+            assert!(request.contains(expected), "{request} != {expected}")
+        }
+    }
+
+    #[rstest]
+    #[case(None)]
+    #[case(Some(PluginInstanceId(1)))]
+    #[tokio::test]
+    async fn test_title_which_is_a_username(
+        #[future] public_feed: MockCube,
+        #[case] old: Option<PluginInstanceId>,
+    ) {
+        let mock = public_feed.await;
+        let expected = "No plugin instance is titled \"chris\", but it is the name of a folder. \
+            To specify the folder, write it with a trailing slash: \"chris/\"";
+        let anon = EitherClient::Anon(mock.anon_client().await);
+        let logged_in = EitherClient::LoggedIn(mock.client("chris").await);
+        for client in [&anon, &logged_in] {
+            let given = GivenPluginInstanceOrPath::from("chris".to_string());
+            let error = given.into_path(client, old).await.unwrap_err();
+            assert_eq!(error.to_string(), expected);
+        }
+
+        let given = GivenPluginInstanceOrPath::from("chris/".to_string());
+        let actual = given.into_path(&anon, old).await.unwrap();
+        assert_eq!(actual, "chris");
+
+        let given = GivenPluginInstanceOrPath::from("rudolph".to_string());
+        let error = given.into_path(&logged_in, old).await.unwrap_err();
+        assert_eq!(error.to_string(), "Plugin instance not found");
+        let given = GivenPluginInstanceOrPath::from("rudolph".to_string());
+        let error = given.into_path(&anon, old).await.unwrap_err();
+        assert_eq!(error.to_string(), CANNOT_ANONYMOUSLY_SEARCH);
+    }
 }
@@ -1,11 +1,11 @@
 use std::str::FromStr;
 
 use color_eyre::eyre;
-use color_eyre::eyre::bail;
+use color_eyre::eyre::{bail, eyre};
 use color_eyre::owo_colors::OwoColorize;
 use futures::TryStreamExt;
+use itertools::Itertools;
 
-use chris::search::PluginSearchBuilder;
 use chris::types::{CubeUrl, PipelineId, PluginId};
 use chris::{Access, BaseChrisClient, LinkedModel, PipelineResponse, PluginResponse};
 
@@ -126,6 +126,22 @@ fn parse_plugin_and_version(value: String) -> GivenRunnable {
 }
 
 impl GivenRunnable {
+    /// Parse a value which is known to be a pipeline. Unlike [GivenRunnable::try_from],
+    /// a name without spaces is not assumed to be a plugin.
+    pub fn pipeline(value: String) -> Result<Self, GivenRunnableEmptyError> {
+        let unqualified =
+            !(value.contains("://") || value.starts_with("pl/") || value.starts_with("plugin/"));
+        match Self::try_from(value)? {
+            GivenRunnable::PluginName { original, .. }
+            | GivenRunnable::PluginId { original, .. }
+                if unqualified =>
+            {
+                Ok(parse_pipeline_name_or_id(original))
+            }
+            given => Ok(given),
+        }
+    }
+
     pub fn as_arg_str(&self) -> &str {
         match self {
             GivenRunnable::PluginId { original, .. } => original,
@@ -162,35 +178,102 @@ impl GivenRunnable {
     }
 }
 
+/// Which version of a plugin to use, given after `@` in `name@version`.
+#[derive(Debug, PartialEq)]
+enum VersionSpec {
+    /// The highest version, when no version or `latest` is given.
+    Latest,
+    /// A semver range such as `^1.2`, `~1.2.0` or `>=1.0, <2`, for which the
+    /// highest matching version is used.
+    Range(semver::VersionReq),
+    /// Any other version is used as-is.
+    Exact(String),
+}
+
+impl VersionSpec {
+    fn parse(version: Option<&str>) -> eyre::Result<Self> {
+        match version {
+            None | Some("latest") => Ok(Self::Latest),
+            Some(v) if v.starts_with(['^', '~', '<', '>', '=', '*']) => {
+                semver::VersionReq::parse(v)
+                    .map(Self::Range)
+                    .map_err(|e| eyre!("Invalid version range \"{}\": {}", v, e))
+            }
+            Some(v) => Ok(Self::Exact(v.to_string())),
+        }
+    }
+
+    /// Choose the best version out of `versions`, returning its index.
+    ///
+    /// Versions which are not semver are never chosen by [VersionSpec::Range], and
+    /// are only chosen by [VersionSpec::Latest] when no version is semver.
+    fn select<S: AsRef<str>>(&self, versions: &[S]) -> Option<usize> {
+        let parsed = || {
+            versions
+                .iter()
+                .enumerate()
+                .filter_map(|(i, v)| semver::Version::parse(v.as_ref()).ok().map(|v| (i, v)))
+        };
+        match self {
+            Self::Exact(version) => versions.iter().position(|v| v.as_ref() == version),
+            Self::Range(req) => parsed()
+                .filter(|(_, v)| req.matches(v))
+                .max_by(|(_, a), (_, b)| a.cmp(b))
+                .map(|(i, _)| i),
+            Self::Latest => parsed()
+                .max_by(|(_, a), (_, b)| a.cmp(b))
+                .map(|(i, _)| i)
+                .or_else(|| (!versions.is_empty()).then_some(0)),
+        }
+    }
+}
+
 async fn get_one_plugin_by_name<A: Access, C: BaseChrisClient<A> + Sync>(
     client: &C,
     name: String,
     version: Option<String>,
 ) -> eyre::Result<LinkedModel<PluginResponse, A>> {
-    let query = plugin_search_query(client, &name, version.as_deref());
-    let search = query.search().page_limit(1).max_items(1);
-    if let Some(plugin) = search.get_first().await? {
-        Ok(plugin)
-    } else {
-        bail!(
-            "Plugin not found: {}",
-            plugin_to_string(&name, version.as_deref())
-        )
+    let spec = VersionSpec::parse(version.as_deref())?;
+    if let VersionSpec::Exact(version) = &spec {
+        let search = client.plugin().name_exact(&name).version(version).search();
+        if let Some(plugin) = search.page_limit(1).max_items(1).get_first().await? {
+            return Ok(plugin);
+        }
     }
+    let mut plugins: Vec<_> = client
+        .plugin()
+        .name_exact(&name)
+        .search()
+        .page_limit(PLUGIN_VERSIONS_PAGE_LIMIT)
+        .stream_connected()
+        .try_collect()
+        .await?;
+    let versions: Vec<_> = plugins.iter().map(|p| p.object.version.as_str()).collect();
+    if let Some(i) = spec.select(&versions) {
+        return Ok(plugins.swap_remove(i));
+    }
+    let wanted = plugin_to_string(&name, version.as_deref());
+    if versions.is_empty() {
+        bail!("Plugin not found: {}", wanted)
+    }
+    bail!(
+        "Plugin not found: {}\nAvailable versions of {}: {}",
+        wanted,
+        name,
+        sorted_versions(&versions).join(", ")
+    )
 }
 
-/// Create a plugin search query returning one result with `name_exact` and maybe `version`.
-fn plugin_search_query<A: Access, C: BaseChrisClient<A> + Sync>(
-    client: &C,
-    name: &str,
-    version: Option<&str>,
-) -> PluginSearchBuilder<A> {
-    let name_query = client.plugin().name_exact(name);
-    if let Some(version) = version {
-        name_query.version(version)
-    } else {
-        name_query
-    }
+/// Page size for getting all versions of a plugin. Few plugins have more versions.
+const PLUGIN_VERSIONS_PAGE_LIMIT: u32 = 50;
+
+/// Sort versions from lowest to highest, putting versions which are not semver first.
+fn sorted_versions<'a>(versions: &[&'a str]) -> Vec<&'a str> {
+    versions
+        .iter()
+        .copied()
+        .sorted_by_key(|v| semver::Version::parse(v).ok())
+        .collect()
 }
 
 fn plugin_to_string(name: &str, version: Option<&str>) -> String {
@@ -211,14 +294,18 @@ async fn get_one_pipeline_by_name<A: Access, C: BaseChrisClient<A> + Sync>(
         .pipeline()
         .name(&name)
         .search()
-        .page_limit(2)
-        .max_items(2)
+        .page_limit(10)
+        .max_items(10)
         .stream_connected()
         .try_collect()
         .await?;
     if pipelines.len() > 1 {
         let cmd = format!("chrs search {}", shlex_quote(&name));
-        bail!("Multiple pipelines found, please be more specific. Try searching for pipelines by running `{}`, and then rerun this command but specify a pipeline/{}", cmd.bold(), "ID".bold().bright_green())
+        let candidates = pipelines
+            .iter()
+            .map(|p| format!("  pipeline/{} {}", p.object.id.0, p.object.name))
+            .join("\n");
+        bail!("Multiple pipelines found, please be more specific:\n{}\nTry searching for pipelines by running `{}`, and then rerun this command but specify a pipeline/{}", candidates, cmd.bold(), "ID".bold().bright_green())
     };
     if let Some(pipeline) = pipelines.into_iter().next() {
         Ok(pipeline)
@@ -277,6 +364,60 @@ mod tests {
         assert_eq!(actual, expected)
     }
 
+    #[rstest]
+    #[case(None, VersionSpec::Latest)]
+    #[case(Some("latest"), VersionSpec::Latest)]
+    #[case(Some("1.2.3"), VersionSpec::Exact("1.2.3".to_string()))]
+    #[case(Some("1.2"), VersionSpec::Exact("1.2".to_string()))]
+    #[case(Some("^1.2"), VersionSpec::Range(semver::VersionReq::parse("^1.2").unwrap()))]
+    #[case(Some(">=1, <2"), VersionSpec::Range(semver::VersionReq::parse(">=1, <2").unwrap()))]
+    fn test_parse_version_spec(#[case] version: Option<&str>, #[case] expected: VersionSpec) {
+        assert_eq!(VersionSpec::parse(version).unwrap(), expected)
+    }
+
+    #[rstest]
+    fn test_parse_invalid_version_range() {
+        let error = VersionSpec::parse(Some("^one")).unwrap_err();
+        assert!(error.to_string().contains("^one"), "{error}")
+    }
+
+    const VERSIONS: [&str; 6] = ["1.2.3", "1.10.0", "2.0.0-rc.1", "1.2.10", "0.9", "2.0.0"];
+
+    #[rstest]
+    #[case(None, Some("2.0.0"))]
+    #[case(Some("latest"), Some("2.0.0"))]
+    #[case(Some("0.9"), Some("0.9"))]
+    #[case(Some("1.2.3"), Some("1.2.3"))]
+    #[case(Some("1.2.4"), None)]
+    #[case(Some("^1.2"), Some("1.10.0"))]
+    #[case(Some("~1.2"), Some("1.2.10"))]
+    #[case(Some("<2"), Some("1.10.0"))]
+    #[case(Some("^3"), None)]
+    fn test_select_version(#[case] version: Option<&str>, #[case] expected: Option<&str>) {
+        let spec = VersionSpec::parse(version).unwrap();
+        let actual = spec.select(&VERSIONS).map(|i| VERSIONS[i]);
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    #[case(&["1.0", "beta"], Some("1.0"))]
+    #[case(&[], None)]
+    fn test_select_latest_without_semver(
+        #[case] versions: &[&str],
+        #[case] expected: Option<&str>,
+    ) {
+        let actual = VersionSpec::Latest.select(versions).map(|i| versions[i]);
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    fn test_sorted_versions() {
+        assert_eq!(
+            sorted_versions(&VERSIONS),
+            vec!["0.9", "1.2.3", "1.2.10", "1.10.0", "2.0.0-rc.1", "2.0.0"]
+        )
+    }
+
     #[rstest]
     #[case("42", 42)]
     #[case("pl/42", 42)]
@@ -312,6 +453,17 @@ mod tests {
         assert_eq!(actual, expected)
     }
 
+    #[rstest]
+    #[case("Brain", GivenRunnable::PipelineName("Brain".to_string()))]
+    #[case("Brain@v2", GivenRunnable::PipelineName("Brain@v2".to_string()))]
+    #[case("Brain processing", GivenRunnable::PipelineName("Brain processing".to_string()))]
+    #[case("42", GivenRunnable::PipelineId { id: PipelineId(42), original: "42".to_string() })]
+    #[case("pl/42", GivenRunnable::PluginId { id: PluginId(42), original: "42".to_string() })]
+    fn test_parse_known_pipeline(#[case] input: &str, #[case] expected: GivenRunnable) {
+        let actual = GivenRunnable::pipeline(input.to_string()).unwrap();
+        assert_eq!(actual, expected)
+    }
+
     #[rstest]
     #[case("https://example.com/api/v1/plugins/42/", 42)]
     #[case("https://example.com/api/v1/plugins/560/", 560)]
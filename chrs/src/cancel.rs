@@ -0,0 +1,89 @@
+//! `chrs cancel`: cancel plugin instances.
+
+use clap::Parser;
+use color_eyre::eyre::{bail, eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+use futures::{future, TryStreamExt};
+
+use chris::types::FeedId;
+use chris::{ChrisClient, PluginInstanceRw};
+
+use crate::arg::{GivenDataNode, GivenPluginInstanceOrPath};
+use crate::credentials::Credentials;
+
+#[derive(Parser)]
+pub struct CancelArgs {
+    /// Cancel the plugin instance even if it is already finished
+    #[clap(short, long)]
+    force: bool,
+
+    /// Cancel every waiting or running plugin instance of a feed
+    #[clap(long, conflicts_with = "plugin_instance")]
+    feed: Option<GivenDataNode>,
+
+    /// Maximum number of concurrent HTTP requests
+    #[clap(short = 'j', long, default_value_t = 4)]
+    threads: usize,
+
+    /// Plugin instance to cancel
+    plugin_instance: Option<GivenPluginInstanceOrPath>,
+}
+
+pub async fn cancel(credentials: Credentials, args: CancelArgs) -> Result<()> {
+    let given = args
+        .feed
+        .as_ref()
+        .map(|f| f.as_arg_str())
+        .or(args.plugin_instance.as_ref().map(|p| p.as_arg_str()));
+    let (client, old, _) = credentials.get_client(given.as_slice()).await?;
+    let client = client.logged_in().ok_or_else(|| {
+        eyre!(
+            "This command is only available for authenticated users. Try running `{}` with a username first.",
+            "chrs login".bold()
+        )
+    })?;
+    if let Some(feed) = args.feed {
+        let feed = feed.into_feed_rw(&client, old).await?;
+        cancel_feed(&client, feed.object.id, args.threads).await
+    } else {
+        let plinst = args
+            .plugin_instance
+            .unwrap_or_default()
+            .get_using_rw(&client, old)
+            .await?;
+        if plinst.object.status.is_terminal() && !args.force {
+            bail!(
+                "plugininstance/{} is already {}. Use --force to cancel it anyway.",
+                plinst.object.id.0,
+                plinst.object.status.as_str()
+            )
+        }
+        print_cancelled(plinst.cancel().await?);
+        Ok(())
+    }
+}
+
+/// Cancel all waiting or running plugin instances of a feed.
+async fn cancel_feed(client: &ChrisClient, feed_id: FeedId, threads: usize) -> Result<()> {
+    let query = client.plugin_instances().feed_id(feed_id);
+    query
+        .search()
+        .stream_connected()
+        .try_filter(|p| future::ready(!p.object.status.is_terminal()))
+        .map_ok(|p| async move { p.cancel().await })
+        .try_buffer_unordered(threads)
+        .try_for_each(|p| {
+            print_cancelled(p);
+            future::ok(())
+        })
+        .await?;
+    Ok(())
+}
+
+fn print_cancelled(plinst: PluginInstanceRw) {
+    println!(
+        "plugininstance/{} {}",
+        plinst.object.id.0,
+        plinst.object.status.as_str()
+    );
+}
@@ -0,0 +1,188 @@
+//! `chrs cat`: print files to stdout.
+
+use std::io::ErrorKind;
+
+use bytes::Bytes;
+use clap::Parser;
+use color_eyre::eyre::{bail, eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+use futures::{Stream, StreamExt, TryStreamExt};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use chris::{BasicFileResponse, Downloadable, LinkedModel, RoAccess};
+
+use crate::arg::GivenDataNode;
+use crate::credentials::Credentials;
+use crate::download::{get_files_search, Files};
+
+/// Number of bytes at the start of a file which are checked for null bytes
+/// to decide whether the file is binary.
+const SNIFF_LEN: usize = 8000;
+
+/// Maximum number of matching files to list when a path matches several files.
+const MAX_LISTED: usize = 10;
+
+#[derive(Parser)]
+pub struct CatArgs {
+    /// Print the first file when a path matches several files, instead of failing
+    #[clap(long)]
+    first: bool,
+
+    /// Print files even if they look like binary data
+    #[clap(long)]
+    binary: bool,
+
+    /// Files to print, one after another
+    #[clap(required = true)]
+    paths: Vec<GivenDataNode>,
+}
+
+/// `chrs cat` command
+pub async fn cat(credentials: Credentials, args: CatArgs) -> Result<()> {
+    let given_args: Vec<_> = args.paths.iter().map(|g| g.as_arg_str()).collect();
+    let (client, old, _) = credentials.get_client(&given_args).await?;
+    let mut stdout = tokio::io::stdout();
+    let mut failed = 0;
+    for given in args.paths {
+        let name = given.as_arg_str().to_string();
+        let result = async {
+            let (files, _, _, _) = get_files_search(&client, given, old, None).await?;
+            let file = only_file(files, args.first).await?;
+            copy_file(&file, &mut stdout, args.binary).await
+        }
+        .await;
+        match result {
+            Ok(()) => (),
+            Err(e) if is_broken_pipe(&e) => return Ok(()),
+            Err(e) => {
+                failed += 1;
+                eprintln!("{} {}: {:#}", "error:".red(), name, e);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of the given paths could not be printed", failed)
+    }
+    Ok(())
+}
+
+/// Get the only file of `files`, or the first one if `first` is true.
+async fn only_file(files: Files, first: bool) -> Result<LinkedModel<BasicFileResponse, RoAccess>> {
+    let limit = if first { 1 } else { MAX_LISTED + 1 };
+    let mut matches: Vec<_> = files.stream_connected().take(limit).try_collect().await?;
+    if matches.len() > 1 {
+        let total = files.get_count().await?;
+        let mut msg = format!(
+            "path matches {} files, use --first to print the first one:",
+            total
+        );
+        for file in matches.iter().take(MAX_LISTED) {
+            msg.push_str("\n    ");
+            msg.push_str(file.object.fname().as_str());
+        }
+        if total > MAX_LISTED {
+            msg.push_str("\n    ...");
+        }
+        bail!(msg)
+    }
+    matches.pop().ok_or_else(|| eyre!("No such file"))
+}
+
+/// Write the contents of a file to `writer`.
+async fn copy_file<W: AsyncWrite + Unpin>(
+    file: &LinkedModel<BasicFileResponse, RoAccess>,
+    writer: &mut W,
+    binary: bool,
+) -> Result<()> {
+    let stream = file.stream().await?;
+    copy_stream(stream, writer, binary).await
+}
+
+/// Write the bytes of `stream` to `writer` as they arrive. Unless `binary` is true,
+/// fails without writing anything if the start of the stream contains a null byte.
+async fn copy_stream<S, E, W>(stream: S, writer: &mut W, binary: bool) -> Result<()>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+    W: AsyncWrite + Unpin,
+{
+    let mut stream = std::pin::pin!(stream);
+    let mut head = Vec::new();
+    while head.len() < SNIFF_LEN {
+        match stream.try_next().await? {
+            Some(chunk) => head.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    if !binary && looks_binary(&head) {
+        bail!("file looks like binary data, use --binary to print it anyway")
+    }
+    writer.write_all(&head).await?;
+    while let Some(chunk) = stream.try_next().await? {
+        writer.write_all(&chunk).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Same heuristic as git: data is binary if its start contains a null byte.
+fn looks_binary(data: &[u8]) -> bool {
+    data[..data.len().min(SNIFF_LEN)].contains(&0)
+}
+
+/// Whether the error is from writing to a closed pipe, e.g. `chrs cat file | head`.
+fn is_broken_pipe(e: &color_eyre::eyre::Error) -> bool {
+    e.downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == ErrorKind::BrokenPipe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn chunks(data: &[&'static [u8]]) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+        let chunks: Vec<_> = data.iter().map(|c| Ok(Bytes::from_static(c))).collect();
+        futures::stream::iter(chunks)
+    }
+
+    #[rstest]
+    #[case(b"hello\nworld\n", false)]
+    #[case(b"", false)]
+    #[case(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", true)]
+    fn test_looks_binary(#[case] data: &[u8], #[case] expected: bool) {
+        assert_eq!(looks_binary(data), expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_copy_stream() {
+        let mut out = Vec::new();
+        copy_stream(chunks(&[b"{\"a\":", b" 1}", b"\n"]), &mut out, false)
+            .await
+            .unwrap();
+        assert_eq!(out, b"{\"a\": 1}\n");
+    }
+
+    #[rstest]
+    #[case(false, b"")]
+    #[case(true, b"text\0more")]
+    #[tokio::test]
+    async fn test_copy_stream_binary(#[case] binary: bool, #[case] expected: &[u8]) {
+        let mut out = Vec::new();
+        let result = copy_stream(chunks(&[b"text", b"\0more"]), &mut out, binary).await;
+        assert_eq!(result.is_ok(), binary);
+        assert_eq!(out, expected);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_copy_stream_null_after_head() {
+        let head: &'static [u8] = Box::leak(vec![b'a'; SNIFF_LEN].into_boxed_slice());
+        let mut out = Vec::new();
+        copy_stream(chunks(&[head, b"\0"]), &mut out, false)
+            .await
+            .unwrap();
+        assert_eq!(out.len(), SNIFF_LEN + 1);
+    }
+}
@@ -1,19 +1,53 @@
 use color_eyre::eyre::{eyre, Result};
 use color_eyre::owo_colors::OwoColorize;
 
-use chris::BaseChrisClient;
+use chris::types::{PluginInstanceId, SimplifiedStatus, Status};
+use chris::{BaseChrisClient, ChrisClient, PluginInstanceResponse};
 
 use crate::arg::GivenDataNode;
 use crate::credentials::Credentials;
-use crate::login::state::ChrsSessions;
+use crate::login::set_cd;
 
-pub async fn cd(credentials: Credentials, given: GivenDataNode) -> Result<()> {
+pub async fn cd(credentials: Credentials, given: GivenDataNode, exact: bool) -> Result<()> {
     let (client, old_plinst, _) = credentials.clone().get_client([given.as_arg_str()]).await?;
-    if let Some(client) = client.logged_in() {
-        let plinst = given.into_plinst_rw(&client, old_plinst).await?;
-        let mut sessions = ChrsSessions::load(credentials.config_path.as_deref())?;
-        sessions.set_plugin_instance(client.url(), client.username(), plinst.object.id);
-        sessions.save(credentials.config_path)
+    if let Some(logged_in) = client.logged_in_ref() {
+        let plinst = given.into_plinst_either(&client, old_plinst).await?;
+        let id = if exact {
+            plinst.object.id
+        } else {
+            let start = Node::from(&plinst.object);
+            match nearest_with_output(logged_in, start).await? {
+                Some(id) if id != plinst.object.id => {
+                    eprintln!(
+                        "{} plugininstance/{} {}, changing into plugininstance/{} instead. \
+                        Use {} to change into it anyway.",
+                        "note:".cyan(),
+                        plinst.object.id.0,
+                        plinst.object.status.as_str(),
+                        id.0,
+                        "--exact".bold()
+                    );
+                    id
+                }
+                Some(id) => id,
+                None => {
+                    eprintln!(
+                        "{} plugininstance/{} {} and neither it nor its previous plugin instances have output files.",
+                        "warning:".yellow(),
+                        plinst.object.id.0,
+                        plinst.object.status.as_str()
+                    );
+                    plinst.object.id
+                }
+            }
+        };
+        set_cd(
+            logged_in.url(),
+            logged_in.username(),
+            id,
+            credentials.config_path,
+            credentials.ephemeral,
+        )
     } else {
         Err(eyre!(
             "This command is only available for authenticated users. Try running `{}` with a username first.",
@@ -21,3 +55,114 @@ pub async fn cd(credentials: Credentials, given: GivenDataNode) -> Result<()> {
         ))
     }
 }
+
+/// The parts of a plugin instance needed to find one with output files.
+pub(crate) struct Node {
+    id: PluginInstanceId,
+    status: Status,
+    previous_id: Option<PluginInstanceId>,
+    output_path: Option<String>,
+}
+
+impl From<&PluginInstanceResponse> for Node {
+    fn from(plinst: &PluginInstanceResponse) -> Self {
+        Self {
+            id: plinst.id,
+            status: plinst.status.clone(),
+            previous_id: plinst.previous_id,
+            output_path: plinst.output_path.clone(),
+        }
+    }
+}
+
+/// Plugin instances of _CUBE_ and their output folders.
+pub(crate) trait OutputFolders {
+    async fn node(&self, id: PluginInstanceId) -> Result<Node>;
+    async fn exists(&self, path: &str) -> Result<bool>;
+}
+
+impl OutputFolders for ChrisClient {
+    async fn node(&self, id: PluginInstanceId) -> Result<Node> {
+        let plinst = self.get_plugin_instance(id).await?;
+        Ok(Node::from(&plinst.object))
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.filebrowser().readdir(path).await?.is_some())
+    }
+}
+
+/// If `start` finished with an error or was cancelled, walk up its previous plugin
+/// instances to the nearest one whose output folder exists. Returns `None` if there
+/// is no such plugin instance.
+async fn nearest_with_output(
+    folders: &impl OutputFolders,
+    start: Node,
+) -> Result<Option<PluginInstanceId>> {
+    if !matches!(
+        start.status.simplify(),
+        SimplifiedStatus::Error | SimplifiedStatus::Cancelled
+    ) {
+        return Ok(Some(start.id));
+    }
+    let mut current = start;
+    loop {
+        let has_output = match current.output_path.as_deref() {
+            Some(path) => folders.exists(path).await?,
+            // old versions of CUBE do not tell us, assume it is fine
+            None => true,
+        };
+        if has_output {
+            return Ok(Some(current.id));
+        }
+        match current.previous_id {
+            Some(previous_id) => current = folders.node(previous_id).await?,
+            None => return Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    /// A feed `1 <- 2 <- 3 <- 4` where only the output folders of 1 and 2 exist.
+    struct FakeFeed;
+
+    fn fake_node(id: u32) -> Node {
+        let status = if id >= 3 {
+            Status::FinishedWithError
+        } else {
+            Status::FinishedSuccessfully
+        };
+        Node {
+            id: PluginInstanceId(id),
+            status,
+            previous_id: (id > 1).then(|| PluginInstanceId(id - 1)),
+            output_path: Some(format!("chris/feed_1/pl_{id}/data")),
+        }
+    }
+
+    impl OutputFolders for FakeFeed {
+        async fn node(&self, id: PluginInstanceId) -> Result<Node> {
+            Ok(fake_node(id.0))
+        }
+
+        async fn exists(&self, path: &str) -> Result<bool> {
+            Ok(path.contains("pl_1/") || path.contains("pl_2/"))
+        }
+    }
+
+    #[rstest]
+    #[case(fake_node(4), Some(2))]
+    #[case(fake_node(2), Some(2))]
+    #[case(Node { output_path: None, ..fake_node(4) }, Some(4))]
+    #[case(Node { previous_id: None, ..fake_node(4) }, None)]
+    #[case(Node { status: Status::Cancelled, ..fake_node(1) }, Some(1))]
+    #[tokio::test]
+    async fn test_nearest_with_output(#[case] start: Node, #[case] expected: Option<u32>) {
+        let actual = nearest_with_output(&FakeFeed, start).await.unwrap();
+        assert_eq!(actual, expected.map(PluginInstanceId))
+    }
+}
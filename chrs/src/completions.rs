@@ -0,0 +1,241 @@
+//! `chrs completions`: shell completion scripts.
+//!
+//! The scripts are generated from the [Command] of `chrs` by [clap_complete]. Plugin names for
+//! `chrs run` and `chrs describe` are completed by calling `chrs __complete-plugins`.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use clap::{Command, ValueEnum};
+use color_eyre::eyre;
+use futures::TryStreamExt;
+
+use chris::{BaseChrisClient, PluginResponse};
+
+use crate::credentials::{Credentials, NO_ARGS};
+
+/// Name of the hidden subcommand which prints plugin names for completion.
+pub const COMPLETE_PLUGINS: &str = "__complete-plugins";
+
+/// Subcommands which take a plugin name as their first argument.
+const PLUGIN_COMMANDS: [&str; 2] = ["run", "describe"];
+
+/// Maximum number of plugin names to complete.
+const PLUGIN_LIMIT: usize = 20;
+
+/// How long to wait for CUBE before giving up on completing plugin names.
+const PLUGIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// `chrs completions` command
+pub fn completions(command: Command, shell: Shell) -> eyre::Result<()> {
+    print!("{}", script_of(command, shell));
+    Ok(())
+}
+
+/// Completion script generated by [clap_complete], plus the completion of plugin names.
+fn script_of(command: Command, shell: Shell) -> String {
+    let mut command = without_hidden_subcommands(command);
+    let mut script = Vec::new();
+    clap_complete::generate(
+        clap_complete::Shell::from(shell),
+        &mut command,
+        "chrs",
+        &mut script,
+    );
+    let mut script = String::from_utf8(script).expect("completion script is UTF-8");
+    script.push_str(&complete_plugins_script(shell));
+    script
+}
+
+/// `chrs __complete-plugins` command: print the names of plugins starting with `prefix`.
+///
+/// Completion must never get in the way, so nothing is printed if there is no saved
+/// login, CUBE is unreachable, or CUBE does not respond within [PLUGIN_TIMEOUT].
+pub async fn complete_plugins(credentials: Credentials, prefix: String) -> eyre::Result<()> {
+    let search = async {
+        let (client, _, _) = credentials.get_client(NO_ARGS).await?;
+        let plugins: Vec<_> = client
+            .plugin()
+            .name_startswith(&prefix)
+            .search()
+            .page_limit(PLUGIN_LIMIT as u32)
+            .max_items(PLUGIN_LIMIT)
+            .stream()
+            .try_collect()
+            .await?;
+        Ok::<_, eyre::Error>(plugins)
+    };
+    if let Ok(Ok(plugins)) = tokio::time::timeout(PLUGIN_TIMEOUT, search).await {
+        for name in plugin_names(&plugins, &prefix) {
+            println!("{}", name)
+        }
+    }
+    Ok(())
+}
+
+/// Unique names of plugins which start with `prefix`. Older versions of CUBE
+/// ignore the prefix filter, so it is applied again here.
+fn plugin_names<'a>(plugins: &'a [PluginResponse], prefix: &str) -> BTreeSet<&'a str> {
+    plugins
+        .iter()
+        .map(|p| p.name.as_str())
+        .filter(|name| name.starts_with(prefix))
+        .collect()
+}
+
+/// [clap_complete] does not skip hidden subcommands such as [COMPLETE_PLUGINS],
+/// so `command` is copied without them.
+fn without_hidden_subcommands(command: Command) -> Command {
+    let visible: Vec<_> = command
+        .get_subcommands()
+        .filter(|c| !c.is_hide_set())
+        .cloned()
+        .collect();
+    let pruned = Command::new(command.get_name().to_string())
+        .args(command.get_arguments().cloned())
+        .subcommands(visible);
+    match command.get_version() {
+        Some(version) => pruned.version(version.to_string()),
+        None => pruned,
+    }
+}
+
+/// Shell code which adds the names of plugins to the completions of `chrs run`
+/// and `chrs describe`, on top of the script generated by [clap_complete].
+fn complete_plugins_script(shell: Shell) -> String {
+    let commands = PLUGIN_COMMANDS.join("|");
+    match shell {
+        Shell::Bash => format!(
+            r#"
+_chrs_with_plugins() {{
+    _chrs "$@"
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if (( COMP_CWORD >= 2 )) && [[ "$cur" != -* ]]; then
+        case "${{COMP_WORDS[1]}}" in
+            {commands}) COMPREPLY+=($(compgen -W "$(chrs {COMPLETE_PLUGINS} "$cur" 2>/dev/null)" -- "$cur")) ;;
+        esac
+    fi
+}}
+
+complete -F _chrs_with_plugins -o bashdefault -o default chrs
+"#
+        ),
+        Shell::Zsh => format!(
+            r#"
+_chrs_with_plugins() {{
+    if (( CURRENT > 2 )) && [[ "${{words[CURRENT]}}" != -* ]]; then
+        case "${{words[2]}}" in
+            {commands})
+                local -a plugins
+                plugins=(${{(f)"$(chrs {COMPLETE_PLUGINS} "${{words[CURRENT]}}" 2>/dev/null)"}})
+                compadd -a plugins
+                ;;
+        esac
+    fi
+    _chrs "$@"
+}}
+
+compdef _chrs_with_plugins chrs
+"#
+        ),
+        Shell::Fish => PLUGIN_COMMANDS
+            .iter()
+            .map(|command| {
+                format!(
+                    "complete -c chrs -n '__fish_seen_subcommand_from {command}' \
+                     -a '(chrs {COMPLETE_PLUGINS} (commandline -ct) 2>/dev/null)'\n"
+                )
+            })
+            .collect(),
+    }
+}
+
+impl From<Shell> for clap_complete::Shell {
+    fn from(shell: Shell) -> Self {
+        match shell {
+            Shell::Bash => clap_complete::Shell::Bash,
+            Shell::Zsh => clap_complete::Shell::Zsh,
+            Shell::Fish => clap_complete::Shell::Fish,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use rstest::*;
+
+    #[rstest]
+    #[case(Shell::Bash)]
+    #[case(Shell::Zsh)]
+    #[case(Shell::Fish)]
+    fn test_scripts_of_chrs(#[case] shell: Shell) {
+        let script = script_of(crate::Cli::command(), shell);
+        assert!(script.contains("share"), "subcommands are completed");
+        assert!(script.contains("max-nodes"), "options are completed");
+        assert!(script.contains(&format!("chrs {} ", COMPLETE_PLUGINS)));
+        assert_eq!(
+            script.matches(COMPLETE_PLUGINS).count(),
+            script
+                .matches(&format!("chrs {} ", COMPLETE_PLUGINS))
+                .count(),
+            "hidden subcommand is not completed"
+        );
+    }
+
+    #[rstest]
+    fn test_plugin_names() {
+        let plugin = |name: &str| -> PluginResponse {
+            serde_json::from_value(serde_json::json!({
+                "url": "https://cube.example.org/api/v1/plugins/1/",
+                "id": 1,
+                "creation_date": "2024-02-27T20:16:10.541396-05:00",
+                "name": name,
+                "version": "1.0.0",
+                "dock_image": format!("fnndsc/{}:1.0.0", name),
+                "public_repo": "https://github.com/FNNDSC/example",
+                "icon": "",
+                "type": "ds",
+                "stars": 0,
+                "authors": "FNNDSC",
+                "title": "Example",
+                "category": "",
+                "description": "An example",
+                "documentation": "",
+                "license": "MIT",
+                "execshell": "python",
+                "selfpath": "/usr/local/bin",
+                "selfexec": "example",
+                "min_number_of_workers": 1,
+                "max_number_of_workers": 1,
+                "min_cpu_limit": 1000,
+                "max_cpu_limit": 2147483647,
+                "min_memory_limit": 200,
+                "max_memory_limit": 2147483647,
+                "min_gpu_limit": 0,
+                "max_gpu_limit": 0,
+                "meta": "https://cube.example.org/api/v1/plugins/meta/1/",
+                "parameters": "https://cube.example.org/api/v1/plugins/1/parameters/",
+                "instances": "https://cube.example.org/api/v1/plugins/1/instances/",
+                "compute_resources": "https://cube.example.org/api/v1/plugins/1/computeresources/"
+            }))
+            .unwrap()
+        };
+        let plugins = [
+            plugin("pl-dcm2niix"),
+            plugin("pl-dircopy"),
+            plugin("pl-dircopy"),
+            plugin("pl-mri10yr06mo01da_normal"),
+        ];
+        let actual: Vec<_> = plugin_names(&plugins, "pl-d").into_iter().collect();
+        assert_eq!(actual, ["pl-dcm2niix", "pl-dircopy"])
+    }
+}
@@ -0,0 +1,156 @@
+//! `chrs compute`: inspect compute resources, i.e. where plugin instances run.
+
+use clap::Subcommand;
+use color_eyre::eyre::{bail, OptionExt, Result};
+use color_eyre::owo_colors::OwoColorize;
+use futures::TryStreamExt;
+
+use chris::{ComputeResourceResponse, PluginResponse};
+
+use crate::arg::{GivenRunnable, Runnable};
+use crate::credentials::{Credentials, NO_ARGS};
+use crate::output::{write_stream, OutputFormat, Render};
+use crate::sanitize::sanitize_for_terminal;
+use crate::table::Fit;
+
+#[derive(Subcommand)]
+pub enum ComputeCommand {
+    /// List compute resources
+    Ls {
+        /// Only list the compute resources which a plugin can run on,
+        /// and show the resource limits of the plugin
+        #[clap(short, long)]
+        plugin: Option<GivenRunnable>,
+
+        /// Do not shorten descriptions to fit the terminal
+        #[clap(long)]
+        no_ellipsis: bool,
+    },
+}
+
+/// `chrs compute` command
+pub async fn compute(
+    credentials: Credentials,
+    command: ComputeCommand,
+    output: OutputFormat,
+) -> Result<()> {
+    match command {
+        ComputeCommand::Ls {
+            plugin,
+            no_ellipsis,
+        } => list_compute_resources(credentials, plugin, no_ellipsis, output).await,
+    }
+}
+
+async fn list_compute_resources(
+    credentials: Credentials,
+    plugin: Option<GivenRunnable>,
+    no_ellipsis: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let (client, _, _) = credentials.get_client(NO_ARGS).await?;
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to list compute resources.")?;
+    let (plugin, search) = if let Some(given) = plugin {
+        let plugin = match given.resolve_using(&client).await? {
+            Runnable::Plugin(plugin) => plugin,
+            Runnable::Pipeline(_) => bail!("--plugin must be a plugin, not a pipeline"),
+        };
+        let search = plugin.compute_resources();
+        (Some(plugin.object), search)
+    } else {
+        (None, client.compute_resources().search())
+    };
+    if !output.is_human() {
+        return write_stream(search.stream(), output).await;
+    }
+    let compute_resources: Vec<_> = search.stream().try_collect().await?;
+    if let Some(plugin) = plugin {
+        println!("{}", describe_limits(&plugin).dimmed());
+    }
+    let name_width = compute_resources
+        .iter()
+        .map(|c| c.name.len())
+        .max()
+        .unwrap_or_default()
+        .max("Name".len());
+    println!(
+        "{} {} {}",
+        format!("{:<name_width$}", "Name").bold().underline(),
+        format!("{:>TIME_WIDTH$}", "Time limit").bold().underline(),
+        "Description".bold().underline()
+    );
+    let fit = Fit::to_terminal(no_ellipsis, &[name_width, TIME_WIDTH]);
+    for compute_resource in compute_resources {
+        println!(
+            "{} {:>TIME_WIDTH$} {}",
+            format!(
+                "{:<name_width$}",
+                sanitize_for_terminal(&compute_resource.name)
+            )
+            .bold(),
+            time_limit(compute_resource.max_job_exec_seconds),
+            fit.fit(&sanitize_for_terminal(&compute_resource.description))
+        );
+    }
+    Ok(())
+}
+
+/// Width of the "Time limit" column
+const TIME_WIDTH: usize = 10;
+
+/// Format `max_job_exec_seconds` as hours and minutes.
+fn time_limit(seconds: i64) -> String {
+    if seconds <= 0 {
+        return "none".to_string();
+    }
+    let minutes = (seconds + 59) / 60;
+    format!("{}h{:02}m", minutes / 60, minutes % 60)
+}
+
+/// Describe the ranges of resources which can be requested for a plugin instance.
+fn describe_limits(plugin: &PluginResponse) -> String {
+    let gpus = match (plugin.min_gpu_limit, plugin.max_gpu_limit) {
+        (Some(min), Some(max)) => format!(", GPUs {}-{}", min, max),
+        _ => String::new(),
+    };
+    format!(
+        "Resource limits of {}@{}: CPU {}m-{}m, memory {}Mi-{}Mi{}, workers {}-{}",
+        plugin.name,
+        plugin.version,
+        plugin.min_cpu_limit,
+        plugin.max_cpu_limit,
+        plugin.min_memory_limit,
+        plugin.max_memory_limit,
+        gpus,
+        plugin.min_number_of_workers,
+        plugin.max_number_of_workers
+    )
+}
+
+impl Render for ComputeResourceResponse {
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.max_job_exec_seconds.to_string(),
+            self.description.clone(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(0, "none")]
+    #[case(60, "0h01m")]
+    #[case(61, "0h02m")]
+    #[case(86400, "24h00m")]
+    #[case(5400, "1h30m")]
+    fn test_time_limit(#[case] seconds: i64, #[case] expected: &str) {
+        assert_eq!(time_limit(seconds), expected)
+    }
+}
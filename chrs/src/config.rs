@@ -0,0 +1,366 @@
+//! `chrs config`: preferences of a login, which are used as the defaults of options.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::str::FromStr;
+
+use clap::{Command, CommandFactory, FromArgMatches, Subcommand, ValueEnum};
+use color_eyre::eyre::{bail, eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+
+use crate::credentials::Credentials;
+use crate::login::state::ChrsSessions;
+use crate::login::store::SavedCubeState;
+use crate::login::UiUrl;
+use crate::output::OutputFormat;
+use crate::Cli;
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Set a preference of the current login
+    Set {
+        /// Preference to set
+        key: PreferenceKey,
+        /// Value of the preference
+        value: String,
+    },
+    /// Print the preferences of the current login, or the value of one preference
+    Get {
+        /// Preference to print
+        key: Option<PreferenceKey>,
+    },
+    /// Remove a preference of the current login
+    Unset {
+        /// Preference to remove
+        key: PreferenceKey,
+    },
+}
+
+/// A preference which can be set by `chrs config set`.
+///
+/// Preferences are used as the defaults of options when their login is the
+/// current one (see `chrs switch`). Options given on the command line always win.
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq)]
+pub enum PreferenceKey {
+    /// Default of --threads for download, upload, rm, cancel, and run
+    Threads,
+    /// Default of --output
+    Output,
+    /// Default of --no-titles for ls and download, either true or false.
+    /// Overridden by --titles
+    #[value(name = "no_titles")]
+    NoTitles,
+    /// ChRIS_ui URL, the default of --ui
+    Ui,
+}
+
+impl PreferenceKey {
+    /// Name of this preference as it appears in the configuration file.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Threads => "threads",
+            Self::Output => "output",
+            Self::NoTitles => "no_titles",
+            Self::Ui => "ui",
+        }
+    }
+
+    /// ID of the options whose default is this preference.
+    fn arg_id(self) -> Option<&'static str> {
+        match self {
+            Self::Threads => Some("threads"),
+            Self::Output => Some("output"),
+            Self::NoTitles => Some("no_titles"),
+            // the UI URL of a login is already the default of --ui
+            Self::Ui => None,
+        }
+    }
+
+    /// Check that `value` is valid for this preference, returning it in canonical form.
+    fn parse(self, value: &str) -> Result<String> {
+        match self {
+            Self::Threads => value
+                .parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .map(|n| n.to_string())
+                .ok_or_else(|| eyre!("threads must be a positive integer, not \"{}\"", value)),
+            Self::Output => OutputFormat::from_str(value, true)
+                .ok()
+                .and_then(|o| o.to_possible_value())
+                .map(|o| o.get_name().to_string())
+                .ok_or_else(|| {
+                    eyre!(
+                        "output must be one of: {}",
+                        possible_values::<OutputFormat>()
+                    )
+                }),
+            Self::NoTitles => value
+                .parse::<bool>()
+                .map(|b| b.to_string())
+                .map_err(|_| eyre!("no_titles must be true or false, not \"{}\"", value)),
+            Self::Ui => UiUrl::from_str(value)
+                .map(|u| u.to_string())
+                .map_err(|_| eyre!("ui must be a URL starting with http:// or https://")),
+        }
+    }
+
+    fn get(self, session: &SavedCubeState) -> Option<String> {
+        match self {
+            Self::Ui => session.ui.as_ref().map(|u| u.to_string()),
+            _ => session.preferences.get(self.name()).cloned(),
+        }
+    }
+
+    fn set(self, session: &mut SavedCubeState, value: Option<String>) {
+        match (self, value) {
+            (Self::Ui, value) => session.ui = value.map(|v| UiUrl::from_str(&v).unwrap()),
+            (_, Some(value)) => {
+                session.preferences.insert(self.name().to_string(), value);
+            }
+            (_, None) => {
+                session.preferences.remove(self.name());
+            }
+        }
+    }
+}
+
+fn possible_values<T: ValueEnum>() -> String {
+    T::value_variants()
+        .iter()
+        .filter_map(|v| v.to_possible_value())
+        .map(|v| v.get_name().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `chrs config` command
+pub fn config(credentials: Credentials, command: ConfigCommand) -> Result<()> {
+    let config_path = credentials.config_path.as_deref();
+    let (key, value) = match command {
+        ConfigCommand::Set { key, value } => (key, Some(key.parse(&value)?)),
+        ConfigCommand::Unset { key } => (key, None),
+        ConfigCommand::Get { key: Some(key) } => {
+            let mut sessions = ChrsSessions::load(config_path)?;
+            return match key.get(current_session(&mut sessions, &credentials)?) {
+                Some(value) => {
+                    println!("{}", value);
+                    Ok(())
+                }
+                None => bail!("{} is not set", key.name()),
+            };
+        }
+        ConfigCommand::Get { key: None } => {
+            let mut sessions = ChrsSessions::load(config_path)?;
+            let session = current_session(&mut sessions, &credentials)?;
+            for key in PreferenceKey::value_variants() {
+                if let Some(value) = key.get(session) {
+                    println!("{} = {}", key.name(), value);
+                }
+            }
+            return Ok(());
+        }
+    };
+    ChrsSessions::modify(config_path, |sessions| {
+        key.set(current_session(sessions, &credentials)?, value);
+        Ok(())
+    })
+}
+
+/// Get the saved login of the given credentials.
+fn current_session<'a>(
+    sessions: &'a mut ChrsSessions,
+    credentials: &Credentials,
+) -> Result<&'a mut SavedCubeState> {
+    sessions
+        .get_cube_mut(credentials.cube_url.as_ref(), credentials.username.as_ref())
+        .ok_or_else(|| eyre!("Not logged in. Run `{}` first.", "chrs login".bold()))
+}
+
+/// Get the preferences of the login chosen by the `--cube` and `--username` options of
+/// the command-line arguments `argv`, the same way [ChrsSessions::get_cube] chooses it.
+///
+/// Since the preferences are the defaults of options, `argv` is parsed without them first.
+/// If `argv` cannot be parsed, the preferences of the current login are returned.
+pub fn chosen_preferences<I, T>(sessions: &ChrsSessions, argv: I) -> BTreeMap<String, String>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let (cube, username) = Cli::command()
+        .try_get_matches_from(argv)
+        .ok()
+        .and_then(|matches| Cli::from_arg_matches(&matches).ok())
+        .map(|cli| (cli.cube, cli.username))
+        .unwrap_or_default();
+    sessions
+        .get_cube(cube.as_ref(), username.as_ref())
+        .map(|session| session.preferences.clone())
+        .unwrap_or_default()
+}
+
+/// Use preferences as the defaults of the options of `cmd` and its subcommands.
+///
+/// Invalid preferences, e.g. from editing the configuration file by hand, are ignored.
+pub fn apply_preferences(mut cmd: Command, preferences: &BTreeMap<String, String>) -> Command {
+    for key in PreferenceKey::value_variants() {
+        let id = if let Some(id) = key.arg_id() {
+            id
+        } else {
+            continue;
+        };
+        if let Some(value) = preferences.get(key.name()).and_then(|v| key.parse(v).ok()) {
+            cmd = set_default(cmd, id, value);
+        }
+    }
+    cmd
+}
+
+/// Set the default value of every option with the given `id` of `cmd` and its subcommands.
+fn set_default(cmd: Command, id: &'static str, value: String) -> Command {
+    let cmd = if cmd.get_arguments().any(|a| a.get_id() == id) {
+        cmd.mut_arg(id, |a| a.default_value(value.clone()))
+    } else {
+        cmd
+    };
+    let subcommands: Vec<_> = cmd
+        .get_subcommands()
+        .map(|s| s.get_name().to_string())
+        .collect();
+    subcommands.into_iter().fold(cmd, |cmd, name| {
+        cmd.mut_subcommand(name, |s| set_default(s, id, value.clone()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::login::store::StoredToken;
+    use chris::types::{CubeUrl, Username};
+    use rstest::*;
+
+    #[rstest]
+    #[case(PreferenceKey::Threads, "16", Some("16"))]
+    #[case(PreferenceKey::Threads, "0", None)]
+    #[case(PreferenceKey::Threads, "many", None)]
+    #[case(PreferenceKey::Output, "json", Some("json"))]
+    #[case(PreferenceKey::Output, "JSON", Some("json"))]
+    #[case(PreferenceKey::Output, "yaml", None)]
+    #[case(PreferenceKey::NoTitles, "true", Some("true"))]
+    #[case(PreferenceKey::NoTitles, "yes", None)]
+    #[case(
+        PreferenceKey::Ui,
+        "https://app.chrisproject.org/feeds",
+        Some("https://app.chrisproject.org")
+    )]
+    #[case(PreferenceKey::Ui, "app.chrisproject.org", None)]
+    fn test_parse(#[case] key: PreferenceKey, #[case] value: &str, #[case] expected: Option<&str>) {
+        assert_eq!(key.parse(value).ok().as_deref(), expected)
+    }
+
+    #[rstest]
+    fn test_unknown_key_lists_supported_keys() {
+        let error = Cli::command()
+            .try_get_matches_from(["chrs", "config", "set", "colour", "blue"])
+            .unwrap_err()
+            .to_string();
+        for key in ["threads", "output", "no_titles", "ui"] {
+            assert!(error.contains(key), "{}", error);
+        }
+    }
+
+    fn preferences(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case(&["chrs", "download", "feed/1"], 16)]
+    #[case(&["chrs", "download", "-j", "2", "feed/1"], 2)]
+    #[case(&["chrs", "download", "--threads=3", "feed/1"], 3)]
+    fn test_threads_preference(#[case] argv: &[&str], #[case] expected: usize) {
+        let prefs = preferences(&[("threads", "16")]);
+        let matches = apply_preferences(Cli::command(), &prefs)
+            .try_get_matches_from(argv)
+            .unwrap();
+        let (_, download) = matches.subcommand().unwrap();
+        assert_eq!(download.get_one::<usize>("threads"), Some(&expected))
+    }
+
+    #[rstest]
+    #[case(&["chrs", "ls"], OutputFormat::Json)]
+    #[case(&["chrs", "ls", "--output", "plain"], OutputFormat::Plain)]
+    #[case(&["chrs", "--output", "human", "ls"], OutputFormat::Human)]
+    fn test_output_preference(#[case] argv: &[&str], #[case] expected: OutputFormat) {
+        let prefs = preferences(&[("output", "json")]);
+        let matches = apply_preferences(Cli::command(), &prefs)
+            .try_get_matches_from(argv)
+            .unwrap();
+        let cli = <Cli as clap::FromArgMatches>::from_arg_matches(&matches).unwrap();
+        assert_eq!(cli.output, expected)
+    }
+
+    #[rstest]
+    fn test_no_titles_preference() {
+        let prefs = preferences(&[("no_titles", "true"), ("threads", "not a number")]);
+        let matches = apply_preferences(Cli::command(), &prefs)
+            .try_get_matches_from(["chrs", "ls"])
+            .unwrap();
+        let (_, ls) = matches.subcommand().unwrap();
+        assert_eq!(ls.get_one::<bool>("no_titles"), Some(&true))
+    }
+
+    #[rstest]
+    #[case(&["chrs", "ls"], true)]
+    #[case(&["chrs", "ls", "--titles"], false)]
+    #[case(&["chrs", "ls", "--no-titles"], true)]
+    #[case(&["chrs", "ls", "--titles", "--no-titles"], true)]
+    #[case(&["chrs", "ls", "--no-titles", "--titles"], false)]
+    fn test_titles_overrides_no_titles_preference(#[case] argv: &[&str], #[case] expected: bool) {
+        let prefs = preferences(&[("no_titles", "true")]);
+        let matches = apply_preferences(Cli::command(), &prefs)
+            .try_get_matches_from(argv)
+            .unwrap();
+        let (_, ls) = matches.subcommand().unwrap();
+        let no_titles = ls.get_flag("no_titles") && !ls.get_flag("titles");
+        assert_eq!(no_titles, expected)
+    }
+
+    fn session(cube: &str, username: &str, threads: &str) -> SavedCubeState {
+        SavedCubeState {
+            cube: CubeUrl::try_from(cube.to_string()).unwrap(),
+            username: Username::from(username),
+            store: StoredToken::Text("token".to_string()),
+            current_plugin_instance_id: None,
+            ui: None,
+            last_listed: None,
+            remember_password: false,
+            preferences: preferences(&[("threads", threads)]),
+        }
+    }
+
+    #[rstest]
+    #[case(&["chrs", "ls"], Some("3"))]
+    #[case(&["chrs", "--cube", "https://a.example.com/api/v1/", "ls"], Some("1"))]
+    #[case(
+        &["chrs", "--cube", "https://b.example.com/api/v1/", "--username", "alice", "ls"],
+        Some("2")
+    )]
+    #[case(&["chrs", "ls", "--username", "bob", "--cube", "https://b.example.com/api/v1/"], Some("3"))]
+    #[case(&["chrs", "--cube", "https://c.example.com/api/v1/", "ls"], None)]
+    #[case(&["chrs", "ls", "--no-such-option"], Some("3"))]
+    fn test_chosen_preferences(#[case] argv: &[&str], #[case] expected: Option<&str>) {
+        let sessions = ChrsSessions {
+            sessions: vec![
+                session("https://a.example.com/api/v1/", "alice", "1"),
+                session("https://b.example.com/api/v1/", "alice", "2"),
+                session("https://b.example.com/api/v1/", "bob", "3"),
+            ],
+            ..Default::default()
+        };
+        let actual = chosen_preferences(&sessions, argv);
+        assert_eq!(actual.get("threads").map(|s| s.as_str()), expected)
+    }
+}
@@ -1,4 +1,8 @@
-use color_eyre::eyre::{eyre, Context};
+mod secret_file;
+
+pub use secret_file::{resolve_secret, secret_file_path, CUBE_FILE_ENV, TOKEN_FILE_ENV};
+
+use color_eyre::eyre::{bail, eyre, Context};
 use color_eyre::owo_colors::OwoColorize;
 use color_eyre::{eyre, Section};
 use reqwest_middleware::Middleware;
@@ -6,18 +10,29 @@ use reqwest_retry::{
     policies::ExponentialBackoff, RetryTransientMiddleware, Retryable, RetryableStrategy,
 };
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
-use chris::reqwest::Response;
+use chris::errors::CubeError;
+use chris::reqwest::{Response, StatusCode};
 use chris::types::{CubeUrl, PluginInstanceId, Username};
 use chris::{Account, AnonChrisClient, ChrisClient, EitherClient};
 
 use crate::login::state::ChrsSessions;
 use crate::login::store::CubeState;
 use crate::login::UiUrl;
+use crate::shlex::shlex_quote;
 
 /// A dummy value to provide to [Credentials::get_client]
 pub const NO_ARGS: [&str; 0] = [];
 
+/// Largest size of files which are buffered in memory when uploaded with `--retries`,
+/// so that the retry middleware can send them again. Larger files are retried by
+/// uploading them again from the start.
+const REPLAYABLE_UPLOAD_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// URL given by `--cube` which has no saved login, so it is being used anonymously.
+static ANONYMOUS_FALLBACK: OnceLock<CubeUrl> = OnceLock::new();
+
 /// Command-line options of `chrs` which are relevant to identifying the user session
 /// and obtaining a client object.
 #[derive(Debug, Clone)]
@@ -33,14 +48,22 @@ pub struct Credentials {
     /// - `None`: use default configuration file (for main use)
     /// - `Some(_)`: custom configuration file (for testing purposes only)
     pub config_path: Option<PathBuf>,
+    /// Whether the token was read from a secret file, in which case nothing should be
+    /// saved to the configuration file nor keyring.
+    pub ephemeral: bool,
+    /// Use read-only access without logging in, even if a login is saved.
+    pub anonymous: bool,
 }
 
 impl Credentials {
     /// If `--cube` is given, use it. Else, if a CUBE address appears
     /// in any of `args`, use it. Else, try to get address from the saved login.
     ///
-    /// If `--password` is given, use password to get client.
-    /// Else, try to get saved login information from configuration file.
+    /// The client is obtained using the first of these which is given:
+    ///
+    /// 1. `--password`, to get a new token
+    /// 2. `--token` (or `--token-file`), without looking up saved logins
+    /// 3. saved login information from the configuration file
     pub async fn get_client(
         self,
         args: impl IntoIterator<Item = impl AsRef<str>>,
@@ -53,31 +76,37 @@ impl Credentials {
             retries,
             ui,
             config_path: config_name,
+            ephemeral,
+            anonymous,
         } = self;
+        if ephemeral && cube_url.is_none() {
+            bail!(
+                "{} is required when the token is read from a file",
+                "--cube".bold()
+            )
+        }
         let retry_middleware = retries.map(retry_strategy);
-        if let (Some(url), Some(token), Some(username)) =
-            (cube_url.as_ref(), token, username.as_ref())
-        {
-            let builder = ChrisClient::build(url.clone(), username.clone(), token)?;
-            let builder = if let Some(middleware) = retry_middleware {
-                builder.with(middleware)
-            } else {
-                builder
-            };
-            return builder
-                .connect()
+        if let Some(password) = password {
+            get_client_with_password(cube_url, username, password, args, retry_middleware)
                 .await
                 .map(EitherClient::LoggedIn)
                 .map(|c| (c, None, ui))
-                .map_err(eyre::Error::new);
-        }
-        if let Some(password) = password {
-            get_client_with_password(cube_url, username, password, args, retry_middleware)
+        } else if let Some(token) = token {
+            get_client_with_token(cube_url, username, token, args, retry_middleware)
                 .await
                 .map(EitherClient::LoggedIn)
                 .map(|c| (c, None, ui))
         } else {
-            get_client_from_state(cube_url, username, ui, args, retry_middleware, config_name).await
+            get_client_from_state(
+                cube_url,
+                username,
+                ui,
+                args,
+                retries,
+                config_name,
+                anonymous,
+            )
+            .await
         }
     }
 }
@@ -115,42 +144,138 @@ async fn get_client_with_password(
     Ok(client)
 }
 
+/// Get an authenticated _ChRIS_ client using a token, without looking up saved logins.
+///
+/// If `username` is not given, it is gotten from _CUBE_.
+async fn get_client_with_token(
+    cube_url: Option<CubeUrl>,
+    username: Option<Username>,
+    token: String,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+    retry_middleware: Option<impl Middleware>,
+) -> eyre::Result<ChrisClient> {
+    let url = cube_url
+        .or_else(|| first_cube_urllike(args))
+        .ok_or_else(|| {
+            eyre!(
+                "{} is required when using {}",
+                "--cube".bold(),
+                "--token".bold()
+            )
+        })?;
+    let builder = match username {
+        Some(username) => ChrisClient::build(url, username, token)?,
+        None => ChrisClient::build_from_token(url, token)?,
+    };
+    let builder = if let Some(middleware) = retry_middleware {
+        builder
+            .with(middleware)
+            .replayable_upload_limit(REPLAYABLE_UPLOAD_LIMIT)
+    } else {
+        builder
+    };
+    builder
+        .connect()
+        .await
+        .wrap_err("Could not log in using the given token")
+}
+
 /// Get the client, using the previously saved config file if needed.
+///
+/// If `anonymous`, the client is anonymous even if a login is saved.
+///
+/// If the saved token was rejected by CUBE and the password was remembered
+/// (`chrs login --remember-password`), a new token is obtained and saved.
 async fn get_client_from_state(
     cube_url: Option<CubeUrl>,
     username: Option<Username>,
     ui: Option<UiUrl>,
     args: impl IntoIterator<Item = impl AsRef<str>>,
-    retry_middleware: Option<impl Middleware>,
+    retries: Option<u32>,
     config_path: Option<PathBuf>,
+    anonymous: bool,
 ) -> eyre::Result<(EitherClient, Option<PluginInstanceId>, Option<UiUrl>)> {
     let url = cube_url.clone().or_else(|| first_cube_urllike(args));
-    let login = ChrsSessions::load(config_path)?
-        .get_login(url.as_ref(), username.as_ref())?
-        .or_else(|| {
-            // If --cube is not given, no matching login found, but a URL is found from the
+    let sessions = ChrsSessions::load(config_path.as_deref())?;
+    let login = match sessions.get_login(url.as_ref(), username.as_ref())? {
+        Some(login) if anonymous && !login.is_anonymous() => {
+            Some(CubeState::anonymous(login.cube, ui.clone().or(login.ui)))
+        }
+        Some(login) => Some(login),
+        None => {
+            if let Some(cube) = cube_url.as_ref().filter(|_| !anonymous) {
+                eprintln!(
+                    "{} no login is saved for {}, proceeding anonymously.",
+                    "note:".cyan(),
+                    cube.as_str()
+                );
+                ANONYMOUS_FALLBACK.get_or_init(|| cube.clone());
+            }
+            // If no matching login found, but a URL is given by --cube or found from the
             // positional args, try doing an anonymous login.
-            cube_url.or(url).map(|cube| CubeState {
-                cube,
-                username: Username::from_static(""),
-                token: None,
-                current_plugin_instance_id: None,
-                ui: ui.clone(),
-            })
-        })
-        .ok_or_else(|| {
-            eyre!(
-                "Not logged in. Either use the {} option, or run `{}`",
-                "--cube".bold(),
-                "chrs login".bold()
-            )
-        })?;
-    let client = if login.username.as_str().is_empty() {
-        get_anon_client(login.cube, retry_middleware).await
+            cube_url
+                .or(url)
+                .map(|cube| CubeState::anonymous(cube, ui.clone()))
+        }
+    };
+    let login = login.ok_or_else(|| {
+        eyre!(
+            "Not logged in. Either use the {} option, or run `{}`",
+            "--cube".bold(),
+            "chrs login".bold()
+        )
+    })?;
+    let old = login.current_plugin_instance_id;
+    let ui = ui.or_else(|| login.ui.clone());
+    let client = if login.is_anonymous() {
+        get_anon_client(login.cube, retries.map(retry_strategy)).await
     } else {
-        get_authed_client(login.cube, login.username, login.token, retry_middleware).await
+        let result = get_authed_client(
+            login.cube.clone(),
+            login.username.clone(),
+            login.token.clone(),
+            retries.map(retry_strategy),
+        )
+        .await;
+        match result {
+            Err(e) if is_unauthorized(&e) => {
+                match sessions.get_password(&login.cube, &login.username)? {
+                    Some(password) => refresh_token(login, password, retries, config_path).await,
+                    None => Err(e),
+                }
+            }
+            result => result,
+        }
     }?;
-    Ok((client, login.current_plugin_instance_id, ui.or(login.ui)))
+    Ok((client, old, ui))
+}
+
+/// Get a new token using a remembered password, save it, and try connecting again.
+async fn refresh_token(
+    login: CubeState,
+    password: String,
+    retries: Option<u32>,
+    config_path: Option<PathBuf>,
+) -> eyre::Result<EitherClient> {
+    eprintln!(
+        "{} the saved token for {} was rejected, logging in again using the saved password.",
+        "note:".cyan(),
+        login.username.as_str()
+    );
+    let token = Account {
+        client: Default::default(),
+        url: &login.cube,
+        username: &login.username,
+        password: &password,
+    }
+    .get_token()
+    .await
+    .wrap_err("Could not log in using the saved password")?;
+    let CubeState { cube, username, .. } = login;
+    ChrsSessions::modify(config_path.as_deref(), |sessions| {
+        sessions.set_token(&cube, &username, &token)
+    })?;
+    get_authed_client(cube, username, Some(token), retries.map(retry_strategy)).await
 }
 
 async fn get_anon_client(
@@ -178,8 +303,9 @@ async fn get_authed_client(
         eyre!(
             "The saved token is invalid, please run `{}`",
             format!(
-                "chrs logout --cube \"{}\" --username \"{}\"",
-                &cube_url, &username
+                "chrs logout --cube {} --username {}",
+                shlex_quote(cube_url.as_str()),
+                shlex_quote(username.as_str())
             )
             .bold()
         )
@@ -187,6 +313,7 @@ async fn get_authed_client(
     let result = if let Some(middleware) = retry_middleware {
         ChrisClient::build(cube_url.clone(), username.clone(), token)?
             .with(middleware)
+            .replayable_upload_limit(REPLAYABLE_UPLOAD_LIMIT)
             .connect()
             .await
     } else {
@@ -201,23 +328,51 @@ async fn get_authed_client(
             format!(
                 "Try logging out.\n\n\t{}",
                 format!(
-                    "chrs logout --cube \"{}\" --username \"{}\"",
-                    &cube_url, &username
+                    "chrs logout --cube {} --username {}",
+                    shlex_quote(cube_url.as_str()),
+                    shlex_quote(username.as_str())
                 )
                 .bold()
             )
         })
 }
 
-fn handle_error(error: chris::reqwest::Error, url: &CubeUrl) -> eyre::Error {
-    if let Some(code) = error.status() {
-        if code == chris::reqwest::StatusCode::UNAUTHORIZED {
+/// If a command failed because a request was unauthorized after a `--cube` without a
+/// saved login was used anonymously, suggest logging in.
+pub fn suggest_login_if_unauthorized<T>(result: eyre::Result<T>) -> eyre::Result<T> {
+    match (result, ANONYMOUS_FALLBACK.get()) {
+        (Err(e), Some(cube)) if is_unauthorized(&e) => Err(e).with_suggestion(|| {
+            format!(
+                "{} has no saved login, so it was used anonymously. Try logging in.\n\n\t{}",
+                cube.as_str(),
+                format!("chrs login --cube {}", shlex_quote(cube.as_str())).bold()
+            )
+        }),
+        (result, _) => result,
+    }
+}
+
+/// Whether any cause of an error is a 401 Unauthorized response.
+pub(crate) fn is_unauthorized(error: &eyre::Report) -> bool {
+    error.chain().any(|cause| {
+        let status = match cause.downcast_ref::<CubeError>() {
+            Some(CubeError::Error { status, .. }) => Some(*status),
+            Some(CubeError::Raw(e)) => e.status(),
+            _ => cause
+                .downcast_ref::<chris::reqwest::Error>()
+                .and_then(|e| e.status()),
+        };
+        status == Some(StatusCode::UNAUTHORIZED)
+    })
+}
+
+fn handle_error(error: CubeError, url: &CubeUrl) -> eyre::Error {
+    match error {
+        CubeError::Error { status, .. } if status == StatusCode::UNAUTHORIZED => {
             eyre::Error::msg("Incorrect login")
-        } else {
-            eyre::Error::msg(format!("HTTP status code: {code}"))
         }
-    } else {
-        eyre::Error::msg(format!("Failed HTTP request to {url}"))
+        CubeError::Error { .. } => eyre::Error::new(error),
+        _ => eyre::Error::msg(format!("Failed HTTP request to {url}")),
     }
 }
 
@@ -237,7 +392,7 @@ fn retry_strategy(retries: u32) -> impl reqwest_middleware::Middleware {
     RetryTransientMiddleware::new_with_policy_and_strategy(policy, RetryStrategy)
 }
 
-/// - Client errors are fatal
+/// - Client errors (e.g. 400 Bad Request, 409 Conflict) are fatal
 /// - Everything else can be retried
 struct RetryStrategy;
 impl RetryableStrategy for RetryStrategy {
@@ -258,9 +413,11 @@ impl RetryableStrategy for RetryStrategy {
 
 #[cfg(test)]
 mod tests {
+    use chris::testing::MockCube;
     use rstest::*;
 
     use super::*;
+    use crate::login::store::{Backend, StoredToken};
 
     #[rstest]
     #[case([], None)]
@@ -278,4 +435,89 @@ mod tests {
             expected.map(|s| CubeUrl::from_static(s))
         );
     }
+
+    #[rstest]
+    #[case(Some("chris"), true)]
+    #[case(None, true)]
+    #[case(Some("chris"), false)]
+    #[case(None, false)]
+    #[tokio::test]
+    async fn test_token_does_not_fall_back_to_saved_session(
+        #[case] username: Option<&'static str>,
+        #[case] ephemeral: bool,
+    ) {
+        let credentials = Credentials {
+            cube_url: None,
+            username: username.map(Username::from_static),
+            password: None,
+            token: Some("from-a-file".to_string()),
+            retries: None,
+            ui: None,
+            config_path: Some(PathBuf::from("/dev/null/should-not-be-read")),
+            ephemeral,
+            anonymous: false,
+        };
+        let result = credentials.get_client(NO_ARGS).await;
+        assert!(matches!(result, Err(e) if e.to_string().contains("required")))
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_refresh_token_keeps_order_of_sessions() {
+        let mock = MockCube::start().await;
+        let tmp = tempfile::TempDir::new().unwrap();
+        let config_path = tmp.path().join("chrs.toml");
+        let login = |cube: &CubeUrl, username: &str, token: &str| CubeState {
+            cube: cube.clone(),
+            username: Username::from(username),
+            token: Some(token.to_string()),
+            current_plugin_instance_id: None,
+            ui: None,
+        };
+        let other_cube = CubeUrl::from_static("https://other.example.com/api/v1/");
+        ChrsSessions::modify(Some(&config_path), |sessions| {
+            sessions.add(login(mock.url(), "chris", "expired"), Backend::ClearText)?;
+            sessions.add(login(&other_cube, "other", "token"), Backend::ClearText)
+        })
+        .unwrap();
+
+        let expired = login(mock.url(), "chris", "expired");
+        refresh_token(
+            expired,
+            "password".to_string(),
+            None,
+            Some(config_path.clone()),
+        )
+        .await
+        .unwrap();
+
+        let sessions = ChrsSessions::load(Some(&config_path)).unwrap();
+        let default = sessions.get_login(None, None).unwrap().unwrap();
+        assert_eq!(
+            default.cube, other_cube,
+            "default session should not change"
+        );
+        let saved = sessions
+            .get_cube(Some(mock.url()), Some(&Username::from("chris")))
+            .unwrap();
+        assert_eq!(
+            saved.store,
+            StoredToken::Text("mock-token".to_string()),
+            "token should stay in the config file"
+        );
+    }
+
+    #[rstest]
+    #[case(200, None)]
+    #[case(201, None)]
+    #[case(400, Some(Retryable::Fatal))]
+    #[case(404, Some(Retryable::Fatal))]
+    #[case(409, Some(Retryable::Fatal))]
+    #[case(500, Some(Retryable::Transient))]
+    #[case(503, Some(Retryable::Transient))]
+    fn test_retry_strategy(#[case] status: u16, #[case] expected: Option<Retryable>) {
+        let response = http::Response::builder().status(status).body("").unwrap();
+        let actual = RetryStrategy.handle(&Ok(Response::from(response)));
+        assert!(actual == expected)
+    }
 }
@@ -0,0 +1,165 @@
+//! Reading credentials from files, e.g. a mounted Kubernetes or Docker secret.
+
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{bail, Result, WrapErr};
+
+/// Environment variable equivalent of `--token-file`.
+pub const TOKEN_FILE_ENV: &str = "CHRS_TOKEN_FILE";
+
+/// Environment variable equivalent of `--cube-file`.
+pub const CUBE_FILE_ENV: &str = "CHRS_CUBE_FILE";
+
+/// A secret file bigger than this is certainly not a token nor URL.
+const MAX_SECRET_FILE_SIZE: u64 = 64 * 1024;
+
+/// Get the path of the secret file given by option, or else by the environment variable `env_var`.
+pub fn secret_file_path(
+    option: Option<PathBuf>,
+    env_var: &str,
+    env: impl Fn(&str) -> Option<OsString>,
+) -> Option<PathBuf> {
+    option.or_else(|| env(env_var).filter(|v| !v.is_empty()).map(PathBuf::from))
+}
+
+/// Resolve a credential. A value given directly takes precedence over a value read from `file`.
+///
+/// Returns the value, and whether it was read from a file.
+pub fn resolve_secret<T>(
+    value: Option<T>,
+    file: Option<PathBuf>,
+    parse: impl FnOnce(String) -> Result<T>,
+) -> Result<(Option<T>, bool)> {
+    match (value, file) {
+        (Some(value), _) => Ok((Some(value), false)),
+        (None, Some(path)) => {
+            let contents = read_secret_file(&path)?;
+            let value =
+                parse(contents).wrap_err_with(|| format!("Invalid contents of {path:?}"))?;
+            Ok((Some(value), true))
+        }
+        (None, None) => Ok((None, false)),
+    }
+}
+
+/// Read the contents of a secret file, with surrounding whitespace removed.
+pub fn read_secret_file(path: &Path) -> Result<String> {
+    let file = fs_err::File::open(path)?;
+    let mut contents = String::new();
+    let size = file
+        .take(MAX_SECRET_FILE_SIZE + 1)
+        .read_to_string(&mut contents)
+        .wrap_err_with(|| format!("Could not read {path:?}"))?;
+    if size as u64 > MAX_SECRET_FILE_SIZE {
+        bail!(
+            "{:?} is larger than {} bytes, it does not look like a secret file",
+            path,
+            MAX_SECRET_FILE_SIZE
+        )
+    }
+    let trimmed = contents.trim();
+    if trimmed.is_empty() {
+        bail!("{:?} is empty", path)
+    }
+    Ok(trimmed.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[fixture]
+    fn tmp_dir() -> TempDir {
+        TempDir::new().unwrap()
+    }
+
+    fn write(dir: &TempDir, name: &str, contents: &[u8]) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn env_with(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+        let vars: HashMap<String, OsString> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), OsString::from(v)))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn as_string(s: String) -> Result<String> {
+        Ok(s)
+    }
+
+    #[rstest]
+    #[case(Some("flag"), &[(TOKEN_FILE_ENV, "env")], Some("flag"))]
+    #[case(None, &[(TOKEN_FILE_ENV, "env")], Some("env"))]
+    #[case(None, &[(TOKEN_FILE_ENV, "")], None)]
+    #[case(None, &[], None)]
+    fn test_secret_file_path(
+        #[case] option: Option<&str>,
+        #[case] vars: &[(&str, &str)],
+        #[case] expected: Option<&str>,
+    ) {
+        let actual = secret_file_path(option.map(PathBuf::from), TOKEN_FILE_ENV, env_with(vars));
+        assert_eq!(actual, expected.map(PathBuf::from))
+    }
+
+    #[rstest]
+    fn test_value_takes_precedence_over_file(tmp_dir: TempDir) {
+        let file = write(&tmp_dir, "token", b"from-file");
+        let actual = resolve_secret(Some("from-flag".to_string()), Some(file), as_string).unwrap();
+        assert_eq!(actual, (Some("from-flag".to_string()), false))
+    }
+
+    #[rstest]
+    fn test_file_is_read_and_trimmed(tmp_dir: TempDir) {
+        let file = write(&tmp_dir, "token", b"  secret-token\n");
+        let actual = resolve_secret(None, Some(file), as_string).unwrap();
+        assert_eq!(actual, (Some("secret-token".to_string()), true))
+    }
+
+    #[rstest]
+    fn test_nothing_given_falls_back_to_saved_session() {
+        let actual = resolve_secret(None, None, as_string).unwrap();
+        assert_eq!(actual, (None, false))
+    }
+
+    #[rstest]
+    fn test_env_file_is_used_when_option_not_given(tmp_dir: TempDir) {
+        let file = write(&tmp_dir, "token", b"env-token");
+        let env = env_with(&[(TOKEN_FILE_ENV, file.to_str().unwrap())]);
+        let path = secret_file_path(None, TOKEN_FILE_ENV, env);
+        let actual = resolve_secret(None, path, as_string).unwrap();
+        assert_eq!(actual, (Some("env-token".to_string()), true))
+    }
+
+    #[rstest]
+    #[case(b"".as_slice(), "is empty")]
+    #[case(b" \n\t\n".as_slice(), "is empty")]
+    #[case(&[b'a'; MAX_SECRET_FILE_SIZE as usize + 1], "does not look like a secret file")]
+    fn test_bad_secret_file(tmp_dir: TempDir, #[case] contents: &[u8], #[case] expected: &str) {
+        let file = write(&tmp_dir, "token", contents);
+        let error = read_secret_file(&file).unwrap_err();
+        assert!(error.to_string().contains(expected), "{error}")
+    }
+
+    #[rstest]
+    fn test_missing_secret_file(tmp_dir: TempDir) {
+        let file = tmp_dir.path().join("does-not-exist");
+        assert!(read_secret_file(&file).is_err())
+    }
+
+    #[rstest]
+    fn test_parse_error_names_file(tmp_dir: TempDir) {
+        let file = write(&tmp_dir, "cube", b"not a url");
+        let error = resolve_secret(None, Some(file), |_| -> Result<String> { bail!("bad url") })
+            .unwrap_err();
+        assert!(format!("{error:?}").contains("cube"))
+    }
+}
@@ -1,43 +1,60 @@
+mod example;
+
 use clap::Parser;
-use color_eyre::eyre;
+use color_eyre::eyre::{self, bail};
 use color_eyre::owo_colors::OwoColorize;
 use dialoguer::console::Term;
 use futures::TryStreamExt;
 use time::format_description::well_known::Rfc2822;
 
 use chris::errors::CubeError;
+use chris::pipeline::canon::ExpandedTreePiping;
+use chris::pipeline::{ExpandedTreePipeline, TitleIndexedPipeline};
 use chris::{
-    Access, EitherClient, Pipeline, PipelineRw, Plugin, PluginParameter, PluginResponse, PluginRw,
+    Access, Pipeline, PipelineRw, PipingParameterResponse, Plugin, PluginParameter, PluginResponse,
+    PluginRw,
 };
 
 use crate::arg::{GivenRunnable, Runnable};
 use crate::credentials::Credentials;
 use crate::login::{UiUrl, UiUrlRef};
 use crate::plugin_clap::clap_params;
+use crate::sanitize::sanitize_for_terminal;
+use example::usage_example;
 
 #[derive(Parser)]
 pub struct DescribeArgs {
     /// Plugin or pipeline
     plugin_or_pipeline: GivenRunnable,
+
+    /// Print a pipeline as YAML in the format of RFC #2, which can be edited
+    /// and uploaded using `chrs pipeline upload`
+    #[clap(long)]
+    yaml: bool,
 }
 
+/// Plugins and pipelines are public, so they are resolved with read-only access,
+/// which works whether or not the user is logged in. Information which is only
+/// available to logged in users is shown if possible.
 pub async fn describe_runnable(credentials: Credentials, args: DescribeArgs) -> eyre::Result<()> {
     let (client, _, ui) = credentials
         .get_client([args.plugin_or_pipeline.as_arg_str()])
         .await?;
-    match &client {
-        EitherClient::Anon(c) => match args.plugin_or_pipeline.resolve_using(c).await? {
-            Runnable::Plugin(p) => describe_plugin_ro(&p, ui).await,
-            Runnable::Pipeline(p) => describe_pipeline_ro(&p, ui).await,
+    match args.plugin_or_pipeline.resolve_using(&client).await? {
+        Runnable::Plugin(_) if args.yaml => bail!("--yaml is only supported for pipelines"),
+        Runnable::Plugin(p) => match client.logged_in_ref() {
+            Some(c) => describe_plugin_rw(&c.upgrade(p)?, ui).await,
+            None => describe_plugin_ro(&p, ui).await,
         },
-        EitherClient::LoggedIn(c) => match args.plugin_or_pipeline.resolve_using(c).await? {
-            Runnable::Plugin(p) => describe_plugin_rw(&p, ui).await,
-            Runnable::Pipeline(p) => {
-                describe_pipeline_ro(&p, ui).await?;
+        Runnable::Pipeline(p) if args.yaml => print_pipeline_yaml(&p).await,
+        Runnable::Pipeline(p) => {
+            describe_pipeline_ro(&p, ui).await?;
+            if let Some(c) = client.logged_in_ref() {
                 println!();
-                print_pipeline_workflow_counts(&p).await
+                print_pipeline_workflow_counts(&c.upgrade(p)?).await?;
             }
-        },
+            Ok(())
+        }
     }
 }
 
@@ -75,8 +92,7 @@ async fn describe_plugin_ro<A: Access>(plugin: &Plugin<A>, ui: Option<UiUrl>) ->
     }
     println!();
     let params = get_parameters(plugin).await?;
-    clap_params(&plugin.object.selfexec, &params).print_help()?;
-    Ok(())
+    print_usage(&plugin.object, &params)
 }
 
 async fn describe_plugin_rw(plugin: &PluginRw, ui: Option<UiUrl>) -> eyre::Result<()> {
@@ -90,7 +106,17 @@ async fn describe_plugin_rw(plugin: &PluginRw, ui: Option<UiUrl>) -> eyre::Resul
     }
     println!();
     let params = get_parameters(plugin).await?;
-    clap_params(&plugin.object.selfexec, &params).print_help()?;
+    print_usage(&plugin.object, &params)
+}
+
+/// Print the help of the parameters of a plugin, followed by an example of how to run it.
+fn print_usage(plugin: &PluginResponse, params: &[PluginParameter]) -> eyre::Result<()> {
+    clap_params(&plugin.selfexec, params).print_help()?;
+    println!();
+    println!("{}", "Usage example:".bold().underline());
+    for line in usage_example(plugin, params) {
+        println!("  {}", line)
+    }
     Ok(())
 }
 
@@ -129,9 +155,113 @@ async fn describe_pipeline_ro<A: Access>(
     for line in textwrap::wrap(pipeline.object.description.as_str(), term_cols) {
         println!("{}", line)
     }
+    println!();
+    let (title_indexed, params) = get_pipings(pipeline).await?;
+    let expanded = ExpandedTreePipeline::try_from(title_indexed)?;
+    for line in tree_lines(&expanded.plugin_tree) {
+        println!("{}", line)
+    }
+    println!();
+    println!("Parameters which can be overridden when creating a workflow:");
+    for piping in &expanded.plugin_tree {
+        let title = piping.title.as_deref().unwrap_or_default();
+        let names: Vec<_> = params
+            .iter()
+            .filter(|p| p.plugin_piping_title == title)
+            .map(|p| p.param_name.as_str())
+            .collect();
+        if !names.is_empty() {
+            println!(
+                "{:>20}: {}",
+                sanitize_for_terminal(title).bold(),
+                names.join(", ")
+            )
+        }
+    }
     Ok(())
 }
 
+async fn print_pipeline_yaml<A: Access>(pipeline: &Pipeline<A>) -> eyre::Result<()> {
+    let (title_indexed, _) = get_pipings(pipeline).await?;
+    print!("{}", serde_yaml::to_string(&title_indexed)?);
+    Ok(())
+}
+
+/// Get the pipings of a pipeline, and the default values of all their parameters.
+async fn get_pipings<A: Access>(
+    pipeline: &Pipeline<A>,
+) -> Result<(TitleIndexedPipeline, Vec<PipingParameterResponse>), CubeError> {
+    let pipings = pipeline.pipings();
+    let default_parameters = pipeline.default_parameters();
+    let (pipings, params) = futures::try_join!(
+        pipings.stream().try_collect::<Vec<_>>(),
+        default_parameters.stream().try_collect::<Vec<_>>()
+    )?;
+    let title_indexed = TitleIndexedPipeline::from_cube(&pipeline.object, pipings, params.clone());
+    Ok((title_indexed, params))
+}
+
+/// Lines of an indented tree of pipings, each followed by its default parameter values.
+fn tree_lines(pipings: &[ExpandedTreePiping]) -> Vec<String> {
+    let mut children = vec![Vec::new(); pipings.len()];
+    let mut roots = Vec::new();
+    for (i, piping) in pipings.iter().enumerate() {
+        match piping.previous_index {
+            Some(previous) => children[previous].push(i),
+            None => roots.push(i),
+        }
+    }
+    let mut lines = Vec::with_capacity(pipings.len());
+    for root in roots {
+        push_subtree(&mut lines, pipings, &children, root, "", "");
+    }
+    lines
+}
+
+fn push_subtree(
+    lines: &mut Vec<String>,
+    pipings: &[ExpandedTreePiping],
+    children: &[Vec<usize>],
+    i: usize,
+    branch: &str,
+    indent: &str,
+) {
+    let piping = &pipings[i];
+    lines.push(format!(
+        "{}{} {}",
+        branch,
+        sanitize_for_terminal(piping.title.as_deref().unwrap_or_default()).bold(),
+        format!("{}@{}", piping.plugin_name, piping.plugin_version).dimmed()
+    ));
+    let bar = if children[i].is_empty() {
+        "    "
+    } else {
+        "│   "
+    };
+    for param in piping.plugin_parameter_defaults.iter().flatten() {
+        lines.push(format!(
+            "{}{}{}={}",
+            indent,
+            bar,
+            param.name.cyan(),
+            sanitize_for_terminal(&param.default.to_string())
+        ));
+    }
+    for (n, &child) in children[i].iter().enumerate() {
+        let is_last = n + 1 == children[i].len();
+        let branch = if is_last { "└── " } else { "├── " };
+        let child_indent = if is_last { "    " } else { "│   " };
+        push_subtree(
+            lines,
+            pipings,
+            children,
+            child,
+            &format!("{}{}", indent, branch),
+            &format!("{}{}", indent, child_indent),
+        );
+    }
+}
+
 async fn print_pipeline_workflow_counts(pipeline: &PipelineRw) -> eyre::Result<()> {
     let count = pipeline.get_workflows().get_count().await?;
     if count == 1 {
@@ -141,3 +271,54 @@ async fn print_pipeline_workflow_counts(pipeline: &PipelineRw) -> eyre::Result<(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::pipeline::canon::ExpandedTreeParameter;
+    use chris::types::PluginParameterValue;
+    use dialoguer::console::strip_ansi_codes;
+    use rstest::*;
+
+    fn piping(title: &str, previous_index: Option<usize>, defaults: &[&str]) -> ExpandedTreePiping {
+        let defaults = defaults
+            .iter()
+            .map(|name| ExpandedTreeParameter {
+                name: name.to_string(),
+                default: PluginParameterValue::Integer(1),
+            })
+            .collect();
+        ExpandedTreePiping {
+            plugin_name: "pl-a".into(),
+            plugin_version: "1.0.0".into(),
+            previous_index,
+            title: Some(title.to_string()),
+            plugin_parameter_defaults: Some(defaults),
+        }
+    }
+
+    #[rstest]
+    fn test_tree_lines() {
+        let pipings = [
+            piping("root", None, &["x"]),
+            piping("b1", Some(0), &["y", "z"]),
+            piping("b2", Some(0), &[]),
+            piping("leaf", Some(1), &["w"]),
+        ];
+        let actual: Vec<_> = tree_lines(&pipings)
+            .iter()
+            .map(|line| strip_ansi_codes(line).to_string())
+            .collect();
+        let expected = [
+            "root pl-a@1.0.0",
+            "│   x=1",
+            "├── b1 pl-a@1.0.0",
+            "│   │   y=1",
+            "│   │   z=1",
+            "│   └── leaf pl-a@1.0.0",
+            "│           w=1",
+            "└── b2 pl-a@1.0.0",
+        ];
+        assert_eq!(actual, expected)
+    }
+}
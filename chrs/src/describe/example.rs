@@ -0,0 +1,217 @@
+//! Example `chrs run` command of a plugin, for `chrs describe`.
+
+use chris::types::{PluginParameterAction, PluginType};
+use chris::{PluginParameter, PluginResponse};
+
+use crate::plugin_clap::long_flag_of;
+use crate::shlex::shlex_quote;
+
+/// Lines of an example `chrs run` command of a plugin.
+///
+/// The command gives every required parameter a placeholder value, which is either the
+/// first of its choices or the name of its type. It is followed by comments listing the
+/// optional parameters with their defaults.
+pub(super) fn usage_example(plugin: &PluginResponse, params: &[PluginParameter]) -> Vec<String> {
+    let name = format!("{}@{}", plugin.name, plugin.version);
+    let mut words = vec!["chrs".to_string(), "run".to_string(), shlex_quote(&name)];
+    if plugin.plugin_type == PluginType::Fs {
+        words.extend(["--title".to_string(), "TITLE".to_string()]);
+    }
+    let mut plugin_args: Vec<_> = params
+        .iter()
+        .filter(|p| !p.optional)
+        .flat_map(|p| example_arg(p, placeholder(p)))
+        .collect();
+    match plugin.plugin_type {
+        PluginType::Fs => (),
+        PluginType::Ds => plugin_args.push("INCOMING".to_string()),
+        PluginType::Ts => plugin_args.push("INCOMING...".to_string()),
+    }
+    if !plugin_args.is_empty() {
+        words.push("--".to_string());
+        words.extend(plugin_args);
+    }
+
+    let mut lines = vec![words.join(" ")];
+    let optional: Vec<_> = params
+        .iter()
+        .filter(|p| p.optional)
+        .flat_map(|p| example_arg(p, default_of(p)))
+        .collect();
+    if !optional.is_empty() {
+        lines.push(format!("# optional: {}", optional.join(" ")));
+    }
+    if plugin.plugin_type == PluginType::Fs {
+        lines.push("# --title will become the name of the new feed".to_string());
+    }
+    lines
+}
+
+/// Shell words of a parameter. A flag which stores `true` or `false` has no value.
+fn example_arg(param: &PluginParameter, value: String) -> Vec<String> {
+    let flag = shlex_quote(&format!("--{}", long_flag_of(param)));
+    match param.action {
+        PluginParameterAction::Store => vec![flag, value],
+        PluginParameterAction::StoreTrue | PluginParameterAction::StoreFalse => vec![flag],
+    }
+}
+
+/// Placeholder value of a parameter.
+fn placeholder(param: &PluginParameter) -> String {
+    param
+        .choices
+        .as_ref()
+        .and_then(|choices| choices.first())
+        .map(|choice| shlex_quote(&choice.to_string()))
+        .unwrap_or_else(|| param.parameter_type.as_str().to_uppercase())
+}
+
+/// Default value of a parameter, or its placeholder if it has no default.
+fn default_of(param: &PluginParameter) -> String {
+    param
+        .default
+        .as_ref()
+        .map(|default| shlex_quote(&default.to_string()))
+        .unwrap_or_else(|| placeholder(param))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use chris::types::PluginId;
+    use chris::BaseChrisClient;
+    use futures::TryStreamExt;
+    use rstest::*;
+
+    /// Add a plugin with the given parameters to `mock`, then get it and its
+    /// parameters back from the mock.
+    async fn mock_plugin(
+        mock: &MockCube,
+        plugin_type: PluginType,
+        params: &[(&str, &str, &str, bool, serde_json::Value, serde_json::Value)],
+    ) -> (PluginResponse, Vec<PluginParameter>) {
+        let plugin = PluginResponse {
+            plugin_type,
+            ..mock.plugin(2, "pl-example", "1.2.3")
+        };
+        let params: Vec<_> = params
+            .iter()
+            .enumerate()
+            .map(
+                |(i, (name, param_type, action, optional, default, choices))| PluginParameter {
+                    action: serde_json::from_value(serde_json::json!(action)).unwrap(),
+                    optional: *optional,
+                    default: serde_json::from_value(default.clone()).unwrap(),
+                    choices: serde_json::from_value(choices.clone()).unwrap(),
+                    ..mock.plugin_parameter(
+                        i as u32,
+                        &plugin,
+                        name,
+                        serde_json::from_value(serde_json::json!(param_type)).unwrap(),
+                    )
+                },
+            )
+            .collect();
+        mock.add_items("plugins/2/parameters/", params);
+        mock.add_plugin(plugin);
+        let client = mock.anon_client().await;
+        let plugin = client.get_plugin(PluginId(2)).await.unwrap();
+        let params = plugin.parameters().stream().try_collect().await.unwrap();
+        (plugin.object, params)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_usage_example() {
+        let mock = MockCube::start().await;
+        let null = serde_json::Value::Null;
+        let (plugin, params) = mock_plugin(
+            &mock,
+            PluginType::Ds,
+            &[
+                (
+                    "size",
+                    "integer",
+                    "store",
+                    false,
+                    null.clone(),
+                    null.clone(),
+                ),
+                (
+                    "method",
+                    "string",
+                    "store",
+                    false,
+                    null.clone(),
+                    serde_json::json!(["fast marching", "euclidean"]),
+                ),
+                ("mask", "path", "store", false, null.clone(), null.clone()),
+                (
+                    "verbose",
+                    "boolean",
+                    "store_true",
+                    false,
+                    null.clone(),
+                    null.clone(),
+                ),
+                (
+                    "sigma",
+                    "float",
+                    "store",
+                    true,
+                    serde_json::json!(1.5),
+                    null.clone(),
+                ),
+                (
+                    "pattern",
+                    "string",
+                    "store",
+                    true,
+                    serde_json::json!("*.nii; *.mgz"),
+                    null.clone(),
+                ),
+                (
+                    "suffix",
+                    "string",
+                    "store",
+                    true,
+                    serde_json::json!(""),
+                    null.clone(),
+                ),
+                (
+                    "no_cleanup",
+                    "boolean",
+                    "store_false",
+                    true,
+                    serde_json::json!(true),
+                    null.clone(),
+                ),
+                ("seed", "integer", "store", true, null.clone(), null.clone()),
+            ],
+        )
+        .await;
+        let expected = [
+            "chrs run pl-example@1.2.3 -- --size INT --method 'fast marching' --mask PATH --verbose INCOMING",
+            "# optional: --sigma 1.5 --pattern '*.nii; *.mgz' --suffix '' --no-cleanup --seed INT",
+        ];
+        assert_eq!(usage_example(&plugin, &params), expected)
+    }
+
+    #[rstest]
+    #[case(PluginType::Fs, &[
+        "chrs run pl-example@1.2.3 --title TITLE",
+        "# --title will become the name of the new feed"
+    ])]
+    #[case(PluginType::Ds, &["chrs run pl-example@1.2.3 -- INCOMING"])]
+    #[case(PluginType::Ts, &["chrs run pl-example@1.2.3 -- INCOMING..."])]
+    #[tokio::test]
+    async fn test_usage_example_of_plugin_type(
+        #[case] plugin_type: PluginType,
+        #[case] expected: &[&str],
+    ) {
+        let mock = MockCube::start().await;
+        let (plugin, params) = mock_plugin(&mock, plugin_type, &[]).await;
+        assert_eq!(usage_example(&plugin, &params), expected)
+    }
+}
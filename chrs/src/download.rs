@@ -1,49 +1,60 @@
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::sync::Arc;
 
+use bytes::Bytes;
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::Parser;
 use color_eyre::eyre::eyre;
+use color_eyre::owo_colors::OwoColorize;
 use color_eyre::{
     eyre,
-    eyre::{bail, Context},
+    eyre::{bail, Context, OptionExt},
 };
 use fs_err::tokio::{File, OpenOptions};
-use futures::{StreamExt, TryStreamExt};
-use indicatif::HumanBytes;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt, TryStreamExt};
+use indicatif::{HumanBytes, ProgressBar};
 use tokio::join;
-use tokio::sync::{
-    mpsc::{unbounded_channel, UnboundedSender},
-    Mutex,
-};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 use tokio_util::io::StreamReader;
 
+use chris::errors::CubeError;
 use chris::search::Search;
-use chris::types::{FileResourceFname, PluginInstanceId};
+use chris::types::PluginInstanceId;
 use chris::{
-    BasicFileResponse, Downloadable, EitherClient, FeedResponse, LinkedModel,
+    BaseChrisClient, BasicFileResponse, Downloadable, EitherClient, FeedResponse, LinkedModel,
     PluginInstanceResponse, RoAccess, RoClient,
 };
 
-use crate::arg::{FeedOrPluginInstance, GivenDataNode};
+use crate::arg::{is_file_path, parse_output_root, FeedOrPluginInstance, GivenDataNode};
 use crate::credentials::Credentials;
 use crate::file_transfer::{
-    progress_bar_bytes, FileTransferError, FileTransferEvent, MultiFileTransferProgress,
+    progress_bar_bytes, restore_times_under, sha256_file, AdaptiveLimiter, Cancellation, Checksum,
+    Checksums, FileTransferError, FileTransferEvent, Hasher, ManifestFeed, ManifestFile,
+    ManifestPluginInstance, ManifestSource, ManifestStatus, ManifestWriter,
+    MultiFileTransferProgress, Outcome, Partial, ProgressFormat, CHECKSUMS_NAME, MANIFEST_NAME,
 };
-use crate::files::CoderChannel;
-use crate::files::MaybeChrisPathHumanCoder;
+use crate::files::{FileFilter, FilterArgs, MaybeChrisPathHumanCoder};
 
 #[derive(Parser)]
 pub struct DownloadArgs {
     /// Save as canonical folder names instead of renaming them to feed names
     /// or plugin instance titles
-    #[clap(short, long)]
+    #[clap(short, long, overrides_with = "titles")]
     pub no_titles: bool,
 
-    /// Join contents of all "data" folders to the same output directory.
+    /// Rename folders to feed names and plugin instance titles even if the
+    /// no_titles preference is set
+    #[clap(long, overrides_with = "no_titles")]
+    pub titles: bool,
+
+    /// Save all files directly in the download directory by their file names,
+    /// instead of in subdirectories.
     ///
-    /// Useful when trying to download sibling plugin instance outputs.
-    #[clap(short, long, hide = true)]
+    /// When several files have the same name, `_1`, `_2`, ... is added before
+    /// the extension of all but the first, and the renamed files are listed
+    /// after the download.
+    #[clap(short, long)]
     flatten: bool,
 
     /// Skip downloading of files which already exist on the filesystem,
@@ -55,10 +66,52 @@ pub struct DownloadArgs {
     #[clap(long, conflicts_with = "skip_existing")]
     clobber: bool,
 
+    /// Continue downloading partially downloaded files instead of starting over.
+    /// Partially downloaded files are also kept when interrupted by Ctrl-C.
+    #[clap(long, conflicts_with = "clobber")]
+    resume: bool,
+
+    /// Keep files which were not downloaded completely, renamed to FILE.partial
+    #[clap(long, conflicts_with = "resume")]
+    keep_partial: bool,
+
     /// Maximum number of concurrent downloads
     #[clap(short = 'j', long, default_value_t = 4)]
     threads: usize,
 
+    /// Reduce the number of concurrent downloads when server errors or timeouts happen
+    #[clap(long, overrides_with = "no_adaptive")]
+    adaptive: bool,
+
+    /// Always use the maximum number of concurrent downloads (default)
+    #[clap(long, overrides_with = "adaptive")]
+    no_adaptive: bool,
+
+    /// Restore modification times of files uploaded by `chrs upload --preserve-times`
+    #[clap(long)]
+    restore_times: bool,
+
+    /// Compute the SHA-256 of files as they are downloaded, and write them to
+    /// a SHA256SUMS file in the download directory (or FILE.sha256 when downloading one file).
+    /// Check the files later using `chrs verify`
+    #[clap(long)]
+    checksum: bool,
+
+    /// When downloading several files, write where they were downloaded from and
+    /// their sizes to chrs-manifest.json in the download directory, as they are downloaded.
+    /// Check the sizes of the files later using `chrs verify DIR`
+    #[clap(long)]
+    manifest: bool,
+
+    /// Download each file which is the same in several plugin instances only once,
+    /// and hard link (or copy) it to its other paths. Files are the same if they have
+    /// the same size and the same path under the output folders of their plugin instances
+    #[clap(long)]
+    dedupe: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
+
     /// What to download.
     src: Option<GivenDataNode>,
 
@@ -67,7 +120,11 @@ pub struct DownloadArgs {
 }
 
 /// `chrs download` command
-pub async fn download(credentials: Credentials, args: DownloadArgs) -> eyre::Result<()> {
+pub async fn download(
+    credentials: Credentials,
+    args: DownloadArgs,
+    progress: ProgressFormat,
+) -> eyre::Result<()> {
     let (client, old, _) = credentials
         .get_client(args.src.as_ref().map(|g| g.as_arg_str()).as_slice())
         .await?;
@@ -76,68 +133,217 @@ pub async fn download(credentials: Credentials, args: DownloadArgs) -> eyre::Res
         .clone()
         .or_else(|| old.map(|id| id.into()))
         .ok_or_else(|| eyre!("Missing operand"))?;
-    let (files, dst, rel) = get_files_search(&client, src, old, args.dst.clone()).await?;
-    let size = download_files(client, files, args, dst, rel).await?;
-    eprintln!("Downloaded: {}", HumanBytes(size));
+    let (files, dst, rel, origin) = get_files_search(&client, src, old, args.dst.clone()).await?;
+    let restore_times = args.restore_times.then(|| dst.clone());
+    let manifest = args
+        .manifest
+        .then(|| origin.into_manifest_source(client.url().to_string(), rel.clone()));
+    let cancellation = Cancellation::on_ctrl_c();
+    let size = download_files(
+        client,
+        files,
+        args,
+        dst,
+        rel,
+        manifest,
+        progress,
+        &cancellation,
+    )
+    .await?;
+    if progress != ProgressFormat::Json {
+        eprintln!("Downloaded: {}", HumanBytes(size));
+    }
+    if let Some(dst) = restore_times {
+        restore_times_of(&dst).await?;
+    }
     Ok(())
 }
 
-type Files = Search<BasicFileResponse, RoAccess>;
+/// Apply the modification times recorded by sidecar files found in `dst`.
+async fn restore_times_of(dst: &Utf8Path) -> eyre::Result<()> {
+    if !fs_err::tokio::metadata(dst).await?.is_dir() {
+        return Ok(());
+    }
+    let summary = restore_times_under(dst).await?;
+    eprintln!("Restored modification times of {} files", summary.restored);
+    if summary.size_mismatch > 0 {
+        eprintln!(
+            "Skipped {} files because their sizes are different from when they were uploaded",
+            summary.size_mismatch
+        );
+    }
+    Ok(())
+}
+
+pub(crate) type Files = Search<BasicFileResponse, RoAccess>;
+
+/// The feed or plugin instance which files are downloaded from.
+#[derive(Default)]
+pub(crate) struct Origin {
+    feed: Option<ManifestFeed>,
+    plugin_instance: Option<ManifestPluginInstance>,
+}
+
+impl Origin {
+    fn into_manifest_source(self, cube: String, path: String) -> ManifestSource {
+        ManifestSource {
+            chrs_version: env!("CARGO_PKG_VERSION").to_string(),
+            cube,
+            path,
+            feed: self.feed,
+            plugin_instance: self.plugin_instance,
+        }
+    }
+}
 
 /// Main implementation
+#[allow(clippy::too_many_arguments)]
 async fn download_files(
     client: EitherClient,
     files: Files,
     args: DownloadArgs,
     dst: Utf8PathBuf,
     rel: String,
+    manifest: Option<ManifestSource>,
+    progress: ProgressFormat,
+    cancellation: &Cancellation,
 ) -> eyre::Result<u64> {
-    let count = files.get_count().await?;
+    let filter = args.filter.build()?;
+    let (count, stream) = files.stream_connected_with_count().await?;
     if count == 0 {
         bail!("No files found")
     };
     if count == 1 {
-        download_single_file(files, args, dst).await
+        let only_file = std::pin::pin!(stream)
+            .try_next()
+            .await?
+            .ok_or_eyre("No files found")?;
+        download_single_file(only_file, args, dst, &rel, &filter, progress, cancellation).await
     } else {
+        // the number of files is only known after filtering them
+        let files = select_files(stream, &rel, &filter).await?;
+        if files.is_empty() {
+            bail!("None of the {} files match the given filters", count)
+        }
         let ro_client = client.into_ro();
-        download_many_files(&ro_client, files, args, dst, rel, count as u64).await
+        download_many_files(
+            &ro_client,
+            files,
+            args,
+            dst,
+            rel,
+            manifest,
+            progress,
+            cancellation,
+        )
+        .await
     }
 }
 
+/// Get the files of `files` which are selected by `filter`, where `rel` is the folder
+/// their paths are relative to.
+async fn select_files(
+    files: impl Stream<Item = Result<LinkedModel<BasicFileResponse, RoAccess>, CubeError>>,
+    rel: &str,
+    filter: &FileFilter,
+) -> Result<Vec<LinkedModel<BasicFileResponse, RoAccess>>, CubeError> {
+    files
+        .try_filter(|f| {
+            futures::future::ready(filter.is_match_under(f.object.fname().as_str(), rel))
+        })
+        .try_collect()
+        .await
+}
+
 /// Returns:
 ///
 /// 0. Files to download
 /// 1. download destination
 /// 2. _CUBE_ relative path
-async fn get_files_search(
+/// 3. feed or plugin instance of the files
+pub(crate) async fn get_files_search(
     client: &EitherClient,
     given: GivenDataNode,
     old: Option<PluginInstanceId>,
     dst: Option<Utf8PathBuf>,
-) -> eyre::Result<(Files, Utf8PathBuf, String)> {
-    match client {
-        EitherClient::LoggedIn(logged_in) => {
-            if given.is_path() {
-                let path = given.into_path(client, old).await?;
-                let dst = dst.unwrap_or_else(|| basename(&path));
-                let rel = path.to_string();
-                Ok((
-                    logged_in.files().fname(path).search().basic().into_ro(),
-                    dst,
-                    rel,
-                ))
-            } else {
-                given
-                    .into_or(client, old)
-                    .await
-                    .map(|fopi| choose_output_path(fopi, dst))
-            }
+) -> eyre::Result<(Files, Utf8PathBuf, String, Origin)> {
+    // PACS files are searched for using the pacsfiles API, which anonymous users
+    // can use too, if the CUBE allows them to see PACS files.
+    let by_path = match client {
+        EitherClient::LoggedIn(_) => given.is_path(),
+        EitherClient::Anon(_) => given.is_path() && is_pacs_path(given.as_arg_str()),
+    };
+    if by_path {
+        if given.is_relative_path() && !is_file_path(given.as_arg_str()) {
+            let plinst = given.into_plinst_either(client, old).await?;
+            let fopi = FeedOrPluginInstance::PluginInstance(plinst);
+            return Ok(choose_output_path(fopi, dst));
         }
-        EitherClient::Anon(_) => {
-            let feed_or_plinst = given.into_or(client, old).await
-                .wrap_err_with(|| "Cannot download arbitrary paths unless logged in due to a backend limitation. See https://github.com/FNNDSC/chrs/issues/32")?;
-            Ok(choose_output_path(feed_or_plinst, dst))
+        let is_relative = given.is_relative_path();
+        let path = given.into_path(client, old).await?;
+        let path = path.trim_end_matches('/').to_string();
+        if let Some(id) = parse_output_root(&path).filter(|_| is_relative) {
+            let plinst = client.get_plugin_instance(id).await?;
+            let fopi = FeedOrPluginInstance::PluginInstance(plinst);
+            return Ok(choose_output_path(fopi, dst));
         }
+        let dst = dst.unwrap_or_else(|| basename(&path));
+        let files = search_path(client, &path).await?;
+        Ok((files, dst, path, Origin::default()))
+    } else if client.logged_in_ref().is_some() {
+        given
+            .into_or(client, old)
+            .await
+            .map(|fopi| choose_output_path(fopi, dst))
+    } else {
+        let feed_or_plinst = given.into_or(client, old).await
+            .wrap_err_with(|| "Cannot download arbitrary paths unless logged in due to a backend limitation. See https://github.com/FNNDSC/chrs/issues/32")?;
+        Ok(choose_output_path(feed_or_plinst, dst))
+    }
+}
+
+/// Whether `path` is a folder or file under `SERVICES/PACS`.
+pub(crate) fn is_pacs_path(path: &str) -> bool {
+    path.trim_end_matches('/') == "SERVICES/PACS" || path.starts_with("SERVICES/PACS/")
+}
+
+/// Whether `path` is a folder or file under the uploads folder of a user, `<username>/uploads`.
+pub(crate) fn is_uploads_path(path: &str) -> bool {
+    path.trim_start_matches('/').split('/').nth(1) == Some("uploads")
+}
+
+/// Search for the files under the folder `path`, or the file `path` itself.
+///
+/// A trailing `/` is added to the search prefix so that e.g. `data/mask` does not
+/// also match the files of a sibling folder `data/masks`.
+async fn search_path(client: &EitherClient, path: &str) -> eyre::Result<Files> {
+    let (folder, file) = if is_pacs_path(path) {
+        let pacsfiles = || match client {
+            EitherClient::Anon(c) => c.pacsfiles(),
+            EitherClient::LoggedIn(c) => c.pacsfiles().into_ro(),
+        };
+        (
+            pacsfiles().fname(format!("{path}/")).search().basic(),
+            pacsfiles().fname_exact(path).search().basic(),
+        )
+    } else {
+        let client = client
+            .logged_in_ref()
+            .ok_or_else(|| eyre!("Cannot download \"{}\" unless logged in.", path))?;
+        (
+            client
+                .files()
+                .fname(format!("{path}/"))
+                .search()
+                .basic()
+                .into_ro(),
+            client.files().fname_exact(path).search().basic().into_ro(),
+        )
+    };
+    if !folder.is_empty().await? {
+        Ok(folder)
+    } else {
+        Ok(file)
     }
 }
 
@@ -146,7 +352,7 @@ async fn get_files_search(
 fn choose_output_path(
     feed_or_plinst: FeedOrPluginInstance<RoAccess>,
     dst: Option<Utf8PathBuf>,
-) -> (Files, Utf8PathBuf, String) {
+) -> (Files, Utf8PathBuf, String, Origin) {
     match feed_or_plinst {
         FeedOrPluginInstance::Feed(f) => {
             let files = f.files();
@@ -156,13 +362,31 @@ fn choose_output_path(
                 f.object.creator_username.as_str(),
                 f.object.id.0
             );
-            (files, dst, rel)
+            let origin = Origin {
+                feed: Some(ManifestFeed {
+                    id: f.object.id,
+                    name: Some(f.object.name),
+                }),
+                plugin_instance: None,
+            };
+            (files, dst, rel, origin)
         }
         FeedOrPluginInstance::PluginInstance(p) => {
             let files = p.files();
             let dst = dst.unwrap_or_else(|| plinst_title(&p.object));
-            let rel = p.object.output_path;
-            (files, dst, rel)
+            let origin = Origin {
+                feed: Some(ManifestFeed {
+                    id: p.object.feed_id,
+                    name: None,
+                }),
+                plugin_instance: Some(ManifestPluginInstance {
+                    id: p.object.id,
+                    title: p.object.title.clone(),
+                }),
+            };
+            // if CUBE is too old to report the output path, files are saved by their full paths
+            let rel = p.object.output_path.unwrap_or_default();
+            (files, dst, rel, origin)
         }
     }
 }
@@ -189,31 +413,267 @@ fn plinst_title(plinst: &PluginInstanceResponse) -> Utf8PathBuf {
 
 /// Download one file, showing a file_transfer bar.
 async fn download_single_file(
-    files: Files,
+    only_file: LinkedModel<BasicFileResponse, RoAccess>,
     args: DownloadArgs,
     dst: Utf8PathBuf,
+    rel: &str,
+    filter: &FileFilter,
+    progress: ProgressFormat,
+    cancellation: &Cancellation,
 ) -> eyre::Result<u64> {
-    let only_file = files.get_only().await?;
+    if !filter.is_match_under(only_file.object.fname().as_str(), rel) {
+        bail!(
+            "{} does not match the given filters",
+            only_file.object.fname()
+        )
+    }
     let existing_metadata = fs_err::tokio::metadata(&dst).await;
     if let Ok(metadata) = existing_metadata {
         if args.skip_existing && metadata.len() == only_file.object.fsize() {
+            if args.checksum {
+                let sha256 = sha256_file(&dst).await?;
+                write_single_checksum(&dst, sha256).await?;
+            }
             return Ok(0);
         }
     }
-    let file = open(dst, &args).await?;
-    let stream = only_file
-        .stream()
-        .await?
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::ConnectionAborted, e));
-    let mut reader = StreamReader::new(stream);
-    let pb = progress_bar_bytes(only_file.object.fsize());
-    tokio::io::copy(&mut reader, &mut pb.wrap_async_write(file)).await?;
-    Ok(only_file.object.fsize())
+    let fsize = only_file.object.fsize();
+    let partial = Partial::default();
+    let started = start_download(&only_file, &dst, args.resume, args.clobber, &partial).await?;
+    let Some(Started {
+        file,
+        stream,
+        offset,
+    }) = started
+    else {
+        if args.checksum {
+            let sha256 = sha256_file(&dst).await?;
+            write_single_checksum(&dst, sha256).await?;
+        }
+        return Ok(0);
+    };
+    // without progress bars, the file is reported like one of many files
+    let (pb, mut events) = if progress == ProgressFormat::Bars {
+        (progress_bar_bytes(fsize), None)
+    } else {
+        let mut events = MultiFileTransferProgress::new(Some(1), fsize, progress);
+        let name = only_file.object.basename().to_string();
+        events.update(FileTransferEvent::Start {
+            id: 0,
+            name,
+            size: fsize,
+        });
+        if offset > 0 {
+            events.update(FileTransferEvent::Chunk {
+                id: 0,
+                delta: offset,
+            });
+        }
+        (ProgressBar::hidden(), Some(events))
+    };
+    pb.set_position(offset);
+    let (chunk_tx, mut chunk_rx) = unbounded_channel();
+    let stream = stream.map_ok(move |chunk| {
+        // the receiver is only gone if the download was cancelled
+        let _ = chunk_tx.send(chunk.len() as u64);
+        chunk
+    });
+    let hasher = Hasher::default();
+    let mut writer = hasher.wrap_async_write(pb.wrap_async_write(file));
+    // reader and writer are moved in, so that the file is closed when the copy ends
+    let copy = async move {
+        let mut reader = StreamReader::new(stream);
+        tokio::io::copy(&mut reader, &mut writer).await
+    };
+    let report_chunks = async {
+        while let Some(delta) = chunk_rx.recv().await {
+            if let Some(events) = events.as_mut() {
+                events.update(FileTransferEvent::Chunk { id: 0, delta });
+            }
+        }
+    };
+    let partial = (!args.resume).then_some(&partial);
+    let (copied, ()) = join!(cancellation.run(partial, copy), report_chunks);
+    let copied = match copied {
+        Ok(copied) => copied,
+        Err(e) => {
+            pb.abandon();
+            if let Some(events) = events.as_mut() {
+                events.finish();
+            }
+            discard_incomplete(&dst, Incomplete::of(&args)).await?;
+            bail!("{}: {}", dst, e)
+        }
+    };
+    if let (Some(events), Some(_)) = (events.as_mut(), copied) {
+        events.update(FileTransferEvent::Done(0));
+    }
+    if let Some(events) = events.as_mut() {
+        events.finish();
+    }
+    match copied {
+        Some(written) => check_size(&dst, fsize, offset + written, Incomplete::of(&args))
+            .await
+            .map_err(|e| eyre!("{}: {}", dst, e))?,
+        None => {
+            pb.abandon();
+            cancellation.check(1)?;
+        }
+    }
+    if args.checksum {
+        // the hasher did not see the bytes which were downloaded before resuming
+        let sha256 = if offset > 0 {
+            sha256_file(&dst).await?
+        } else {
+            hasher.hex()
+        };
+        write_single_checksum(&dst, sha256).await?;
+    }
+    Ok(fsize - offset)
+}
+
+/// What to do with a file which was not downloaded completely.
+#[derive(Debug, Copy, Clone)]
+enum Incomplete {
+    /// Delete the file.
+    Delete,
+    /// Rename the file to FILE.partial
+    KeepPartial,
+    /// Leave the file as it is, so that `--resume` can continue it.
+    Resume,
+}
+
+impl Incomplete {
+    fn of(args: &DownloadArgs) -> Self {
+        if args.resume {
+            Self::Resume
+        } else if args.keep_partial {
+            Self::KeepPartial
+        } else {
+            Self::Delete
+        }
+    }
+}
+
+/// Check that the size of the file downloaded to `dst`, which is `written` bytes,
+/// is `fsize` as reported by _CUBE_. If not, the file is dealt with according to `incomplete`.
+async fn check_size(
+    dst: &Utf8Path,
+    fsize: u64,
+    written: u64,
+    incomplete: Incomplete,
+) -> Result<(), FileTransferError> {
+    if written == fsize {
+        return Ok(());
+    }
+    discard_incomplete(dst, incomplete).await?;
+    Err(FileTransferError::SizeMismatch {
+        expected: fsize,
+        actual: written,
+    })
+}
+
+/// Deal with the file at `dst` which was not downloaded completely according to `incomplete`.
+async fn discard_incomplete(dst: &Utf8Path, incomplete: Incomplete) -> std::io::Result<()> {
+    match incomplete {
+        Incomplete::Delete => fs_err::tokio::remove_file(dst).await,
+        Incomplete::KeepPartial => fs_err::tokio::rename(dst, format!("{}.partial", dst)).await,
+        Incomplete::Resume => Ok(()),
+    }
+}
+
+/// Where to continue downloading a file with `--resume`.
+#[derive(Debug, PartialEq)]
+enum Resume {
+    /// The file was already downloaded completely.
+    Complete,
+    /// Continue after this many bytes.
+    From(u64),
+    /// Download the whole file.
+    Restart,
+}
+
+impl Resume {
+    /// Decide how to resume downloading a file of size `fsize`, given the size
+    /// of what was already downloaded.
+    fn of(existing_len: Option<u64>, fsize: u64) -> Self {
+        match existing_len {
+            Some(len) if len == fsize => Self::Complete,
+            Some(len) if len > 0 && len < fsize => Self::From(len),
+            _ => Self::Restart,
+        }
+    }
+}
+
+/// A download which was started by [start_download].
+struct Started<'a> {
+    file: File,
+    stream: BoxStream<'a, std::io::Result<Bytes>>,
+    /// Number of bytes which were downloaded before.
+    offset: u64,
+}
+
+/// Open `dst` and request the bytes of `chris_file` which it does not have yet.
+///
+/// If `resume` is true, a partially downloaded file at `dst` is continued from where
+/// it left off using a range request. If the server does not support range requests,
+/// the whole file is downloaded again. Returns `None` if `dst` is already complete.
+/// `partial` is marked before `dst` is created or truncated.
+async fn start_download<'a>(
+    chris_file: &'a LinkedModel<BasicFileResponse, RoAccess>,
+    dst: &Utf8Path,
+    resume: bool,
+    clobber: bool,
+    partial: &Partial,
+) -> Result<Option<Started<'a>>, FileTransferError> {
+    let existing_len = if resume {
+        fs_err::tokio::metadata(dst).await.ok().map(|m| m.len())
+    } else {
+        None
+    };
+    let (stream, offset) = match Resume::of(existing_len, chris_file.object.fsize()) {
+        Resume::Complete => return Ok(None),
+        Resume::From(len) => {
+            let (stream, is_partial) = chris_file.stream_range(len).await?;
+            (stream.boxed(), if is_partial { len } else { 0 })
+        }
+        Resume::Restart => (chris_file.stream().await?.boxed(), 0),
+    };
+    let file = if offset > 0 {
+        OpenOptions::new().append(true).open(dst).await?
+    } else {
+        partial.writing(dst);
+        open(dst, resume || clobber).await?
+    };
+    let stream = stream
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::ConnectionAborted, e))
+        .boxed();
+    Ok(Some(Started {
+        file,
+        stream,
+        offset,
+    }))
+}
+
+/// Write the checksum of a downloaded file `dst` next to it, as `dst.sha256`.
+async fn write_single_checksum(dst: &Utf8Path, sha256: String) -> eyre::Result<()> {
+    let checksum = Checksum {
+        sha256,
+        path: dst.file_name().unwrap_or(dst.as_str()).to_string(),
+    };
+    let checksums: Checksums = [checksum].into_iter().collect();
+    write_checksums(&Utf8PathBuf::from(format!("{dst}.sha256")), &checksums).await
+}
+
+async fn write_checksums(path: &Utf8Path, checksums: &Checksums) -> eyre::Result<()> {
+    fs_err::tokio::write(path, checksums.to_text()).await?;
+    eprintln!("Wrote checksums to {}", path);
+    Ok(())
 }
 
 /// Opens a file with consideration of `--clobber`
-async fn open(path: impl AsRef<Path>, args: &DownloadArgs) -> std::io::Result<File> {
-    if args.clobber {
+async fn open(path: impl AsRef<Path>, clobber: bool) -> std::io::Result<File> {
+    if clobber {
         File::create(path.as_ref()).await
     } else {
         OpenOptions::new()
@@ -224,66 +684,409 @@ async fn open(path: impl AsRef<Path>, args: &DownloadArgs) -> std::io::Result<Fi
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn download_many_files(
     ro_client: &RoClient,
-    files: Files,
+    files: Vec<LinkedModel<BasicFileResponse, RoAccess>>,
     args: DownloadArgs,
     dst: Utf8PathBuf,
     rel: String,
-    count: u64,
+    manifest: Option<ManifestSource>,
+    progress: ProgressFormat,
+    cancellation: &Cancellation,
 ) -> eyre::Result<u64> {
-    let mut coder = MaybeChrisPathHumanCoder::new(ro_client, !args.no_titles);
-    let renamed_rel = coder.decode(rel).await;
-    let (coder_channel, coder_loop) = CoderChannel::create(coder);
-    let mutex = Mutex::new(coder_channel);
-    let coder_arc = Arc::new(mutex);
+    let mut coder = MaybeChrisPathHumanCoder::new(ro_client, args.titles || !args.no_titles);
+    let planned = plan_output_names(&mut coder, files, &dst, &rel).await;
+    let (planned, renamed) = if args.flatten {
+        flatten_planned(planned, &dst)
+    } else {
+        (planned, Vec::new())
+    };
+    let (planned, duplicates) = if args.dedupe {
+        dedupe_planned(planned)
+    } else {
+        (planned, Vec::new())
+    };
+    let count = planned.len();
 
     let (progress_tx, mut progress_rx) = unbounded_channel();
+    let limiter = AdaptiveLimiter::new(args.threads, args.adaptive, Some(progress_tx.clone()));
     let transfer_progress_loop = async {
-        let mut transfer_progress =
-            MultiFileTransferProgress::new(count, crate::file_transfer::SIZE_128_MIB);
+        let mut transfer_progress = MultiFileTransferProgress::new(
+            Some(count as u64),
+            crate::file_transfer::SIZE_128_MIB,
+            progress,
+        );
         while let Some(event) = progress_rx.recv().await {
             transfer_progress.update(event)
         }
+        transfer_progress.finish();
         transfer_progress.total_size()
     };
-    let download_loop = async move {
-        // I am wrapped in an async move to drop progress_tx after all transfers are complete
-        files
-            .stream_connected()
+    let mut manifest = match manifest {
+        Some(source) => {
+            fs_err::tokio::create_dir_all(&dst).await?;
+            Some(ManifestWriter::create(&dst.join(MANIFEST_NAME), &source).await?)
+        }
+        None => None,
+    };
+    let checksum = args.checksum;
+    let resume = args.resume;
+    let skip_existing = args.skip_existing;
+    let incomplete = Incomplete::of(&args);
+    let download_loop = async {
+        // progress_tx is moved in here to be dropped after all transfers are complete
+        let progress_tx = progress_tx;
+        let total_size: u64 = planned.iter().map(|(f, _)| f.object.fsize()).sum();
+        progress_tx
+            .send(FileTransferEvent::TotalAdjusted(total_size as i64))
+            .unwrap();
+        let limiter = &limiter;
+        let dst = &dst;
+        let with_manifest = manifest.is_some();
+        let mut downloads = futures::stream::iter(planned)
             .enumerate()
-            .map(|(i, r)| {
-                r.map(|f| {
-                    (
-                        i,
-                        f,
-                        progress_tx.clone(),
-                        ManyCoder::new(Arc::clone(&coder_arc), &dst, &renamed_rel),
-                    )
-                })
+            .map(|(i, (f, dst_path))| {
+                let rel = checksum.then(|| relative_to(&dst_path, dst));
+                let progress_tx = progress_tx.clone();
+                let fname = f.object.fname().to_string();
+                let record = with_manifest.then(|| ManifestFile {
+                    fname: fname.clone(),
+                    path: relative_to(&dst_path, dst),
+                    fsize: f.object.fsize(),
+                    creation_date: f.object.creation_date,
+                });
+                async move {
+                    let permit = limiter.acquire().await;
+                    let task = (i, f, progress_tx, dst_path);
+                    let partial = Partial::default();
+                    let download =
+                        download_with_events(task, rel, skip_existing, incomplete, &partial);
+                    let result = cancellation
+                        .run((!resume).then_some(&partial), download)
+                        .await;
+                    permit.report(Outcome::of_download(&result));
+                    let record = record.filter(|_| matches!(result, Ok(Some(_))));
+                    (fname, record, result.map(Option::flatten))
+                }
             })
-            .map_err(FileTransferError::Cube)
-            .try_for_each_concurrent(args.threads, download_with_events)
-            .await
+            .buffer_unordered(args.threads);
+        let mut results = Vec::with_capacity(count);
+        while let Some((fname, record, result)) = downloads.next().await {
+            // files are added to the manifest as they complete, so that it is
+            // truthful even if chrs is killed before all files are downloaded
+            if let (Some(writer), Some(record)) = (manifest.as_mut(), record) {
+                writer.append(&record).await?;
+            }
+            results.push((fname, result));
+        }
+        eyre::Ok(results)
     };
-    let (total_size, result, _) = join!(transfer_progress_loop, download_loop, coder_loop);
-    result.map(|_| total_size).map_err(eyre::Error::new)
+    let (total_size, results) = join!(transfer_progress_loop, download_loop);
+    let results = results?;
+    if let Err(interrupted) = cancellation.check(count) {
+        if let Some(writer) = manifest {
+            writer.finish(ManifestStatus::Interrupted).await?;
+        }
+        return Err(interrupted.into());
+    }
+    if !renamed.is_empty() {
+        eprintln!(
+            "{} {} files have the same name as another file, so they were renamed:",
+            "warning:".yellow(),
+            renamed.len()
+        );
+        for (fname, dst_path) in &renamed {
+            eprintln!("    {} -> {}", fname, relative_to(dst_path, &dst));
+        }
+    }
+    let mut checksums = Checksums::default();
+    let mut failures = Vec::new();
+    for (fname, result) in results {
+        match result {
+            Ok(Some(checksum)) => checksums.extend([checksum]),
+            Ok(None) => (),
+            Err(e) => failures.push((fname, e)),
+        }
+    }
+    let duplicate_count = duplicates.len();
+    let saved = link_duplicates(
+        duplicates,
+        &dst,
+        skip_existing,
+        &mut checksums,
+        &mut failures,
+        manifest.as_mut(),
+    )
+    .await?;
+    if saved > 0 && progress != ProgressFormat::Json {
+        eprintln!(
+            "Linked {} duplicate files instead of downloading {}",
+            duplicate_count,
+            HumanBytes(saved)
+        );
+    }
+    if let Some(writer) = manifest.as_mut() {
+        writer.set_deduplicated_bytes(saved);
+    }
+    if !failures.is_empty() {
+        eprintln!(
+            "{} {} of {} files were not downloaded:",
+            "error:".red(),
+            failures.len(),
+            count + duplicate_count
+        );
+        for (fname, e) in &failures {
+            eprintln!("    {}: {}", fname, e);
+        }
+        if let Some(writer) = manifest {
+            writer.finish(ManifestStatus::Failed).await?;
+        }
+        bail!("Failed to download {} files", failures.len())
+    }
+    if let Some(writer) = manifest {
+        writer.finish(ManifestStatus::Complete).await?;
+        eprintln!("Wrote manifest to {}", dst.join(MANIFEST_NAME));
+    }
+    if checksum {
+        write_checksums(&dst.join(CHECKSUMS_NAME), &checksums).await?;
+    }
+    Ok(total_size)
 }
 
-struct ManyCoder<'a> {
-    dst: &'a Utf8Path,
-    rel: &'a str,
-    coder: Arc<Mutex<CoderChannel>>,
+/// Path of a downloaded file relative to the download directory.
+fn relative_to(path: &Utf8Path, dst: &Utf8Path) -> String {
+    path.strip_prefix(dst).unwrap_or(path).to_string()
 }
 
-impl<'a> ManyCoder<'a> {
-    fn new(coder: Arc<Mutex<CoderChannel>>, dst: &'a Utf8Path, rel: &'a str) -> Self {
-        Self { dst, rel, coder }
+type PlannedDownload = (LinkedModel<BasicFileResponse, RoAccess>, Utf8PathBuf);
+
+/// A file which is not downloaded by `--dedupe`, because it is the same as
+/// the file `source_fname` which is downloaded to `source`.
+struct Duplicate {
+    source_fname: String,
+    source: Utf8PathBuf,
+    file: LinkedModel<BasicFileResponse, RoAccess>,
+    dst_path: Utf8PathBuf,
+}
+
+/// Separate the files which are the same as another file, for `--dedupe`.
+fn dedupe_planned(planned: Vec<PlannedDownload>) -> (Vec<PlannedDownload>, Vec<Duplicate>) {
+    let (planned, duplicates) = split_duplicates(planned, |(f, _)| {
+        dedupe_key(f.object.fname().as_str(), f.object.fsize())
+    });
+    let duplicates = duplicates
+        .into_iter()
+        .map(|(i, (file, dst_path))| {
+            let (source_file, source) = &planned[i];
+            Duplicate {
+                source_fname: source_file.object.fname().to_string(),
+                source: source.clone(),
+                file,
+                dst_path,
+            }
+        })
+        .collect();
+    (planned, duplicates)
+}
+
+/// Separate the items which have the same key as a previous item. Each of the
+/// returned duplicates is paired with the index of the first item with its key.
+/// Items without a key are never duplicates.
+fn split_duplicates<T, K: Eq + std::hash::Hash>(
+    items: Vec<T>,
+    key: impl Fn(&T) -> Option<K>,
+) -> (Vec<T>, Vec<(usize, T)>) {
+    let mut firsts: HashMap<K, usize> = HashMap::new();
+    let mut unique = Vec::with_capacity(items.len());
+    let mut duplicates = Vec::new();
+    for item in items {
+        match key(&item) {
+            Some(k) => {
+                if let Some(&i) = firsts.get(&k) {
+                    duplicates.push((i, item));
+                } else {
+                    firsts.insert(k, unique.len());
+                    unique.push(item);
+                }
+            }
+            None => unique.push(item),
+        }
     }
-    async fn name_output(self, fname: &FileResourceFname) -> Utf8PathBuf {
-        let renamed = self.coder.lock().await.decode(fname.to_string()).await;
-        join_output_name(&renamed, self.rel, self.dst)
+    (unique, duplicates)
+}
+
+/// Files of different plugin instances are considered the same if they have the same
+/// size and the same path relative to the output folders of their plugin instances.
+///
+/// The output folder of a plugin instance is the first folder called `data` of its path
+/// under a feed folder, because the folders of plugin instances are never under the
+/// output folder of their previous plugin instance. Files which are not under an output
+/// folder, e.g. uploaded files, are never the same.
+fn dedupe_key(fname: &str, fsize: u64) -> Option<(u64, String)> {
+    let components: Vec<_> = fname.split('/').collect();
+    let feed = components.iter().position(|c| is_numbered(c, "feed"))?;
+    let data = feed + 1 + components[feed + 1..].iter().position(|c| *c == "data")?;
+    if !is_numbered(components[data - 1], "") || data + 1 == components.len() {
+        return None;
+    }
+    Some((fsize, components[data + 1..].join("/")))
+}
+
+/// Whether `folder` is named like `{prefix}_{number}`, for any prefix if `prefix` is empty.
+fn is_numbered(folder: &str, prefix: &str) -> bool {
+    folder.rsplit_once('_').is_some_and(|(name, number)| {
+        (prefix.is_empty() || name == prefix)
+            && !name.is_empty()
+            && !number.is_empty()
+            && number.bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+/// Link the duplicates of files which were downloaded, see `--dedupe`.
+///
+/// Duplicates of files which could not be downloaded are added to `failures`.
+/// Returns the number of bytes which did not need to be downloaded.
+async fn link_duplicates(
+    duplicates: Vec<Duplicate>,
+    dst: &Utf8Path,
+    skip_existing: bool,
+    checksums: &mut Checksums,
+    failures: &mut Vec<(String, FileTransferError)>,
+    mut manifest: Option<&mut ManifestWriter>,
+) -> eyre::Result<u64> {
+    let failed: HashSet<_> = failures.iter().map(|(fname, _)| fname.clone()).collect();
+    let mut saved = 0;
+    let mut linked_checksums = Vec::new();
+    for duplicate in duplicates {
+        let fname = duplicate.file.object.fname().to_string();
+        let fsize = duplicate.file.object.fsize();
+        if failed.contains(&duplicate.source_fname) {
+            let message = format!("{} was not downloaded", duplicate.source_fname);
+            failures.push((fname, std::io::Error::other(message).into()));
+            continue;
+        }
+        if let Err(e) = link_or_copy(&duplicate.source, &duplicate.dst_path, skip_existing).await {
+            failures.push((fname, e.into()));
+            continue;
+        }
+        saved += fsize;
+        let path = relative_to(&duplicate.dst_path, dst);
+        let source_path = relative_to(&duplicate.source, dst);
+        if let Some(checksum) = checksums.files.iter().find(|c| c.path == source_path) {
+            linked_checksums.push(Checksum {
+                sha256: checksum.sha256.clone(),
+                path: path.clone(),
+            });
+        }
+        if let Some(writer) = manifest.as_mut() {
+            let record = ManifestFile {
+                fname,
+                path,
+                fsize,
+                creation_date: duplicate.file.object.creation_date,
+            };
+            writer.append(&record).await?;
+        }
+    }
+    checksums.extend(linked_checksums);
+    Ok(saved)
+}
+
+/// Hard link `dst` to `src`, or copy it if the filesystem does not support hard links.
+/// An existing `dst` is replaced, unless `skip_existing` and it has the same size as `src`.
+async fn link_or_copy(src: &Utf8Path, dst: &Utf8Path, skip_existing: bool) -> std::io::Result<()> {
+    if let Some(parent_dirs) = dst.parent() {
+        fs_err::tokio::create_dir_all(parent_dirs).await?;
+    }
+    if let Ok(existing) = fs_err::tokio::metadata(dst).await {
+        let src_len = fs_err::tokio::metadata(src).await?.len();
+        if skip_existing && existing.len() == src_len {
+            return Ok(());
+        }
+        fs_err::tokio::remove_file(dst).await?;
+    }
+    if fs_err::tokio::hard_link(src, dst).await.is_err() {
+        fs_err::tokio::copy(src, dst).await?;
+    }
+    Ok(())
+}
+
+/// Decide the output paths of all files before any are downloaded, so that every file
+/// of a folder gets the same renamed path even if a feed or plugin instance is deleted
+/// while downloading.
+async fn plan_output_names(
+    coder: &mut MaybeChrisPathHumanCoder<'_>,
+    files: Vec<LinkedModel<BasicFileResponse, RoAccess>>,
+    dst: &Utf8Path,
+    rel: &str,
+) -> Vec<PlannedDownload> {
+    let renamed_rel = coder.decode(rel).await;
+    let fnames: Vec<_> = files.iter().map(|f| f.object.fname().as_str()).collect();
+    let renamed = coder.decode_all(&fnames).await;
+    files
+        .into_iter()
+        .zip(renamed)
+        .map(|(file, renamed)| {
+            let dst_path = join_output_name(&renamed, &renamed_rel, dst);
+            (file, dst_path)
+        })
+        .collect()
+}
+
+/// Change the output paths of files to their file names directly under `dst`, renaming
+/// files which have the same name as another file using [flatten_names].
+/// Also returns the CUBE paths of renamed files with their new output paths.
+fn flatten_planned(
+    planned: Vec<PlannedDownload>,
+    dst: &Utf8Path,
+) -> (Vec<PlannedDownload>, Vec<(String, Utf8PathBuf)>) {
+    let names: Vec<_> = planned
+        .iter()
+        .map(|(file, _)| file.object.basename().to_string())
+        .collect();
+    let flattened = flatten_names(&names);
+    let mut renamed = Vec::new();
+    let planned = planned
+        .into_iter()
+        .zip(names.into_iter().zip(flattened))
+        .map(|((file, _), (name, flat_name))| {
+            let dst_path = dst.join(&flat_name);
+            if name != flat_name {
+                renamed.push((file.object.fname().to_string(), dst_path.clone()));
+            }
+            (file, dst_path)
+        })
+        .collect();
+    (planned, renamed)
+}
+
+/// Make file names unique. The first file of a name keeps it, the others have `_1`, `_2`, ...
+/// added before their extensions, skipping names which are already taken.
+fn flatten_names(names: &[String]) -> Vec<String> {
+    let mut taken: HashSet<String> = names.iter().cloned().collect();
+    let mut seen: HashMap<&str, usize> = HashMap::with_capacity(names.len());
+    let mut renamed = Vec::with_capacity(names.len());
+    for name in names {
+        let count = seen.entry(name).or_default();
+        *count += 1;
+        if *count == 1 {
+            renamed.push(name.to_string());
+            continue;
+        }
+        let path = Utf8Path::new(name);
+        let stem = path.file_stem().unwrap_or(name);
+        let new_name = (1..)
+            .map(|n| match path.extension() {
+                Some(ext) => format!("{}_{}.{}", stem, n, ext),
+                None => format!("{}_{}", stem, n),
+            })
+            .find(|candidate| !taken.contains(candidate))
+            .unwrap();
+        taken.insert(new_name.clone());
+        renamed.push(new_name);
     }
+    renamed
 }
 
 fn join_output_name(chris_fname: &str, chris_root: &str, dst: &Utf8Path) -> Utf8PathBuf {
@@ -295,49 +1098,254 @@ fn join_output_name(chris_fname: &str, chris_root: &str, dst: &Utf8Path) -> Utf8
 }
 
 /// Download a single file while pushing events through a channel.
+///
+/// If `rel` is given, the checksum of the file is computed and returned with `rel` as its path.
+/// If `skip_existing`, the file is not downloaded if `dst_path` already has its size.
+/// With [Incomplete::Resume], a partially downloaded file is continued.
 async fn download_with_events(
-    (id, chris_file, ptx, coder): (
+    (id, chris_file, ptx, dst_path): (
         usize,
         LinkedModel<BasicFileResponse, RoAccess>,
         UnboundedSender<FileTransferEvent>,
-        ManyCoder<'_>,
+        Utf8PathBuf,
     ),
-) -> Result<(), FileTransferError> {
-    let dst_path = coder.name_output(chris_file.object.fname()).await;
+    rel: Option<String>,
+    skip_existing: bool,
+    incomplete: Incomplete,
+    partial: &Partial,
+) -> Result<Option<Checksum>, FileTransferError> {
+    let resume = matches!(incomplete, Incomplete::Resume);
     if let Some(parent_dirs) = dst_path.parent() {
         fs_err::tokio::create_dir_all(parent_dirs).await?;
     }
-    let mut file = File::create(dst_path).await?;
-
-    let stream = chris_file
-        .stream()
-        .await?
-        .map_ok(|chunk| {
-            ptx.send(FileTransferEvent::Chunk {
-                id,
-                delta: chunk.len() as u64,
-            })
+    let name = chris_file.object.basename().to_string();
+    let fsize = chris_file.object.fsize();
+    let exists = skip_existing
+        && fs_err::tokio::metadata(&dst_path)
+            .await
+            .is_ok_and(|m| m.len() == fsize);
+    let started = if exists {
+        None
+    } else {
+        start_download(&chris_file, &dst_path, resume, true, partial).await?
+    };
+    let Some(started) = started else {
+        // the file is not transferred, so it is taken out of the total size
+        ptx.send(FileTransferEvent::TotalAdjusted(-(fsize as i64)))
             .unwrap();
-            chunk
+        ptx.send(FileTransferEvent::Start { id, name, size: 0 })
+            .unwrap();
+        ptx.send(FileTransferEvent::Done(id)).unwrap();
+        return checksum_of(&dst_path, rel).await;
+    };
+    let hasher = Hasher::default();
+    let mut file = hasher.wrap_async_write(started.file);
+
+    let stream = started.stream.map_ok(|chunk| {
+        ptx.send(FileTransferEvent::Chunk {
+            id,
+            delta: chunk.len() as u64,
         })
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::ConnectionAborted, e));
+        .unwrap();
+        chunk
+    });
     let mut reader = StreamReader::new(stream);
     ptx.send(FileTransferEvent::Start {
         id,
-        name: chris_file.object.basename().to_string(),
+        name,
         size: chris_file.object.fsize(),
     })
     .unwrap();
-    tokio::io::copy(&mut reader, &mut file)
-        .await
-        .map(|_| ptx.send(FileTransferEvent::Done(id)).unwrap())
-        .map_err(FileTransferError::IO)
+    if started.offset > 0 {
+        ptx.send(FileTransferEvent::Chunk {
+            id,
+            delta: started.offset,
+        })
+        .unwrap();
+    }
+    let copied = tokio::io::copy(&mut reader, &mut file).await;
+    drop(file);
+    let written = match copied {
+        Ok(written) => written,
+        Err(e) => {
+            discard_incomplete(&dst_path, incomplete).await?;
+            return Err(FileTransferError::IO(e));
+        }
+    };
+    check_size(
+        &dst_path,
+        chris_file.object.fsize(),
+        started.offset + written,
+        incomplete,
+    )
+    .await?;
+    ptx.send(FileTransferEvent::Done(id)).unwrap();
+    if started.offset > 0 {
+        // the hasher did not see the bytes which were downloaded before resuming
+        return checksum_of(&dst_path, rel).await;
+    }
+    Ok(rel.map(|path| Checksum {
+        sha256: hasher.hex(),
+        path,
+    }))
+}
+
+/// Compute the checksum of the file `dst_path`, if `rel` is given.
+async fn checksum_of(
+    dst_path: &Utf8Path,
+    rel: Option<String>,
+) -> Result<Option<Checksum>, FileTransferError> {
+    match rel {
+        Some(path) => Ok(Some(Checksum {
+            sha256: sha256_file(dst_path).await?,
+            path,
+        })),
+        None => Ok(None),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chris::testing::MockCube;
     use rstest::*;
+    use tempfile::TempDir;
+
+    /// A file of 1000 bytes whose download is dropped after 10 bytes.
+    async fn truncated_file(mock: &MockCube) -> LinkedModel<BasicFileResponse, RoAccess> {
+        mock.add_file("chris/uploads/big.dat", vec![7u8; 1000]);
+        mock.set_download_cutoff(10);
+        let client = mock.client("chris").await;
+        client
+            .userfiles()
+            .fname_exact("chris/uploads/big.dat")
+            .search()
+            .basic()
+            .get_first()
+            .await
+            .unwrap()
+            .unwrap()
+            .into()
+    }
+
+    #[rstest]
+    #[case(&["download"], None)]
+    #[case(&["download", "--keep-partial"], Some("big.dat.partial"))]
+    #[case(&["download", "--resume"], Some("big.dat"))]
+    #[tokio::test]
+    async fn test_single_file_connection_dropped(
+        #[case] argv: &[&str],
+        #[case] expected: Option<&str>,
+    ) {
+        let mock = MockCube::start().await;
+        let file = truncated_file(&mock).await;
+        let tmp = TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        let args = DownloadArgs::parse_from(argv);
+        let filter = args.filter.build().unwrap();
+        let result = download_single_file(
+            file,
+            args,
+            dir.join("big.dat"),
+            "chris/uploads",
+            &filter,
+            ProgressFormat::Quiet,
+            &Cancellation::default(),
+        )
+        .await;
+        assert!(result.is_err());
+        let left: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(left, expected.into_iter().collect::<Vec<_>>());
+    }
+
+    #[rstest]
+    #[case(Incomplete::Delete, None)]
+    #[case(Incomplete::KeepPartial, Some("big.dat.partial"))]
+    #[tokio::test]
+    async fn test_download_with_events_connection_dropped(
+        #[case] incomplete: Incomplete,
+        #[case] expected: Option<&str>,
+    ) {
+        let mock = MockCube::start().await;
+        let file = truncated_file(&mock).await;
+        let tmp = TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        let (tx, _rx) = unbounded_channel();
+        let task = (0, file, tx, dir.join("big.dat"));
+        let partial = Partial::default();
+        let result = download_with_events(task, None, false, incomplete, &partial).await;
+        assert!(matches!(result, Err(FileTransferError::IO(_))));
+        let left: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(left, expected.into_iter().collect::<Vec<_>>());
+    }
+
+    #[rstest]
+    #[case(false, true)]
+    #[case(true, false)]
+    #[tokio::test]
+    async fn test_download_with_events_marks_partial_when_writing(
+        #[case] skip_existing: bool,
+        #[case] expected: bool,
+    ) {
+        let mock = MockCube::start().await;
+        mock.add_file("chris/uploads/a.txt", "hello");
+        let file = mock
+            .client("chris")
+            .await
+            .userfiles()
+            .fname_exact("chris/uploads/a.txt")
+            .search()
+            .basic()
+            .get_first()
+            .await
+            .unwrap()
+            .unwrap()
+            .into();
+        let tmp = TempDir::new().unwrap();
+        let dst = Utf8Path::from_path(tmp.path()).unwrap().join("a.txt");
+        std::fs::write(&dst, "hello").unwrap();
+        let (tx, _rx) = unbounded_channel();
+        let partial = Partial::default();
+        let task = (0, file, tx, dst.clone());
+        download_with_events(task, None, skip_existing, Incomplete::Delete, &partial)
+            .await
+            .unwrap();
+        // an existing file which is skipped must not be deleted if cancelled
+        assert_eq!(partial.path() == Some(dst.as_path()), expected);
+    }
+
+    #[rstest]
+    #[case("alice/feed_1/pl-dircopy_2/data/a/b.txt", Some("a/b.txt"))]
+    #[case("alice/feed_1/pl-dircopy_2/pl-med2img_3/data/b.txt", Some("b.txt"))]
+    #[case("alice/feed_1/pl-dircopy_2/data/data/b.txt", Some("data/b.txt"))]
+    #[case("alice/feed_1/pl-dircopy_2/data", None)]
+    #[case("alice/feed_1/pl-dircopy_2/pl-med2img_3/x.txt", None)]
+    #[case("alice/uploads/a_1/data/b.txt", None)]
+    #[case("alice/feed_1/notaplugin/data/b.txt", None)]
+    fn test_dedupe_key(#[case] fname: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            dedupe_key(fname, 5),
+            expected.map(|output| (5, output.to_string()))
+        )
+    }
+
+    #[rstest]
+    fn test_split_duplicates() {
+        let items = vec![("a", 1), ("b", 2), ("c", 1), ("d", 3), ("e", 1), ("f", 2)];
+        let (unique, duplicates) =
+            split_duplicates(items, |(name, key)| (*name != "d").then_some(*key));
+        assert_eq!(unique, vec![("a", 1), ("b", 2), ("d", 3)]);
+        assert_eq!(
+            duplicates,
+            vec![(0, ("c", 1)), (0, ("e", 1)), (1, ("f", 2))]
+        );
+    }
 
     #[rstest]
     #[case(
@@ -369,4 +1377,78 @@ mod tests {
         let expected_path = Utf8PathBuf::from(expected);
         assert_eq!(actual, expected_path);
     }
+
+    #[rstest]
+    #[case(&["a.png", "b.png"], &["a.png", "b.png"])]
+    #[case(&["a.png", "a.png", "a.png"], &["a.png", "a_1.png", "a_2.png"])]
+    #[case(&["a.png", "a.png", "a_1.png"], &["a.png", "a_2.png", "a_1.png"])]
+    #[case(&["data.json", "README", "README"], &["data.json", "README", "README_1"])]
+    #[case(&["scan.nii.gz", "scan.nii.gz"], &["scan.nii.gz", "scan.nii_1.gz"])]
+    fn test_flatten_names(#[case] names: &[&str], #[case] expected: &[&str]) {
+        let names: Vec<_> = names.iter().map(|n| n.to_string()).collect();
+        assert_eq!(flatten_names(&names), expected)
+    }
+
+    #[rstest]
+    #[case(Incomplete::Delete, &[])]
+    #[case(Incomplete::KeepPartial, &["file.dat.partial"])]
+    #[case(Incomplete::Resume, &["file.dat"])]
+    #[tokio::test]
+    async fn test_check_size(#[case] incomplete: Incomplete, #[case] expected: &[&str]) {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        let dst = dir.join("file.dat");
+        fs_err::write(&dst, b"hello").unwrap();
+        check_size(&dst, 5, 5, incomplete).await.unwrap();
+        assert!(dst.exists());
+
+        let error = check_size(&dst, 10, 5, incomplete).await.unwrap_err();
+        assert!(matches!(
+            error,
+            FileTransferError::SizeMismatch {
+                expected: 10,
+                actual: 5
+            }
+        ));
+        let remaining: Vec<_> = fs_err::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(remaining, expected)
+    }
+
+    #[rstest]
+    #[case(false, "hello")]
+    #[case(true, "other")]
+    #[tokio::test]
+    async fn test_link_or_copy_existing(#[case] skip_existing: bool, #[case] expected: &str) {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        let src = dir.join("a/file.txt");
+        let dst = dir.join("b/c/file.txt");
+        fs_err::create_dir_all(src.parent().unwrap()).unwrap();
+        fs_err::write(&src, "hello").unwrap();
+        link_or_copy(&src, &dst, skip_existing).await.unwrap();
+        assert_eq!(fs_err::read_to_string(&dst).unwrap(), "hello");
+
+        fs_err::remove_file(&dst).unwrap();
+        fs_err::write(&dst, "other").unwrap();
+        link_or_copy(&src, &dst, skip_existing).await.unwrap();
+        assert_eq!(fs_err::read_to_string(&dst).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(None, 10, Resume::Restart)]
+    #[case(Some(0), 10, Resume::Restart)]
+    #[case(Some(4), 10, Resume::From(4))]
+    #[case(Some(10), 10, Resume::Complete)]
+    #[case(Some(12), 10, Resume::Restart)]
+    #[case(Some(0), 0, Resume::Complete)]
+    fn test_resume(
+        #[case] existing_len: Option<u64>,
+        #[case] fsize: u64,
+        #[case] expected: Resume,
+    ) {
+        assert_eq!(Resume::of(existing_len, fsize), expected)
+    }
 }
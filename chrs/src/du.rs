@@ -0,0 +1,241 @@
+//! `chrs du`: report storage usage.
+
+use std::collections::HashMap;
+
+use clap::Parser;
+use color_eyre::eyre::{eyre, Result};
+use futures::TryStreamExt;
+use indicatif::HumanBytes;
+
+use chris::{Downloadable, EitherClient};
+
+use crate::arg::GivenDataNode;
+use crate::credentials::Credentials;
+use crate::download::{get_files_search, Files};
+use crate::sanitize::sanitize_for_terminal;
+
+#[derive(Parser)]
+pub struct DuArgs {
+    /// Also print the usage of subfolders up to this many levels under the target
+    #[clap(short, long, default_value_t = 0)]
+    depth: usize,
+
+    /// Feed, plugin instance, or path. `~` is everything owned by the user:
+    /// uploads and feeds. Defaults to the current plugin instance if there is one,
+    /// otherwise `~`.
+    target: Option<GivenDataNode>,
+}
+
+/// `chrs du` command
+pub async fn du(credentials: Credentials, args: DuArgs) -> Result<()> {
+    let (client, old, _) = credentials
+        .get_client(args.target.as_ref().map(|g| g.as_arg_str()).as_slice())
+        .await?;
+    let target = args
+        .target
+        .filter(|g| g.as_arg_str() != "~")
+        .or_else(|| old.map(|id| id.into()));
+    let (searches, root) = if let Some(target) = target {
+        let (files, _, root, _) = get_files_search(&client, target, old, None).await?;
+        (vec![files], root)
+    } else {
+        home_files(&client)?
+    };
+    let mut usage = Usage::new(&root, args.depth);
+    for files in &searches {
+        usage.add_all(files).await?;
+    }
+    for (path, size) in usage.sorted_subtotals() {
+        print_usage(size, &path);
+    }
+    print_usage(usage.total, if root.is_empty() { "total" } else { &root });
+    Ok(())
+}
+
+/// Search for all the files of the user, which are under the folder of their username:
+/// the files of their feeds, and their uploads, which are in a different API.
+fn home_files(client: &EitherClient) -> Result<(Vec<Files>, String)> {
+    let client = client
+        .logged_in_ref()
+        .ok_or_else(|| eyre!("You must be logged in to get the usage of your files."))?;
+    let home = client.username().as_str().to_string();
+    let files = client
+        .files()
+        .fname(format!("{home}/"))
+        .search()
+        .basic()
+        .into_ro();
+    let uploads = client
+        .userfiles()
+        .fname(format!("{home}/uploads/"))
+        .search()
+        .basic()
+        .into_ro();
+    Ok((vec![files, uploads], home))
+}
+
+fn print_usage(size: u64, path: &str) {
+    println!(
+        "{:>10}  {}",
+        HumanBytes(size).to_string(),
+        sanitize_for_terminal(path)
+    );
+}
+
+/// Sums of file sizes, in total and per subfolder.
+struct Usage<'a> {
+    root: &'a str,
+    depth: usize,
+    total: u64,
+    subtotals: HashMap<String, u64>,
+}
+
+impl<'a> Usage<'a> {
+    fn new(root: &'a str, depth: usize) -> Self {
+        Self {
+            root,
+            depth,
+            total: 0,
+            subtotals: HashMap::new(),
+        }
+    }
+
+    /// Count a file. Its size is added to the subtotal of every folder it is in,
+    /// up to `depth` levels under the root.
+    fn add(&mut self, fname: &str, fsize: u64) {
+        self.total += fsize;
+        if self.depth == 0 {
+            return;
+        }
+        let rel = fname
+            .strip_prefix(self.root)
+            .unwrap_or(fname)
+            .trim_start_matches('/');
+        let folders = if let Some((folders, _)) = rel.rsplit_once('/') {
+            folders
+        } else {
+            return;
+        };
+        let mut end = 0;
+        for (level, component) in folders.split('/').take(self.depth).enumerate() {
+            end += component.len() + usize::from(level > 0);
+            *self
+                .subtotals
+                .entry(folders[..end].to_string())
+                .or_default() += fsize;
+        }
+    }
+
+    /// Count all the files found by `files`.
+    async fn add_all(&mut self, files: &Files) -> Result<()> {
+        files
+            .stream()
+            .try_for_each(|file| {
+                self.add(file.fname().as_str(), file.fsize());
+                futures::future::ready(Ok(()))
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Subtotals of subfolders as full paths, largest first.
+    fn sorted_subtotals(&self) -> Vec<(String, u64)> {
+        let mut subtotals: Vec<_> = self
+            .subtotals
+            .iter()
+            .map(|(folder, size)| {
+                let path = if self.root.is_empty() {
+                    folder.to_string()
+                } else {
+                    format!("{}/{}", self.root, folder)
+                };
+                (path, *size)
+            })
+            .collect();
+        subtotals.sort_by(|(a_path, a_size), (b_path, b_size)| {
+            b_size.cmp(a_size).then_with(|| a_path.cmp(b_path))
+        });
+        subtotals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use rstest::*;
+
+    const FILES: [(&str, u64); 5] = [
+        ("alice/feed_1/pl-dircopy_1/data/a.txt", 1),
+        ("alice/feed_1/pl-dircopy_1/data/sub/b.txt", 20),
+        ("alice/feed_1/pl-dircopy_1/pl-mri-preview_2/data/c.png", 300),
+        ("alice/feed_2/pl-dircopy_3/data/d.nii", 4000),
+        ("alice/uploads/e.txt", 50000),
+    ];
+
+    fn usage_of(root: &str, depth: usize) -> (u64, Vec<(String, u64)>) {
+        let mut usage = Usage::new(root, depth);
+        for (fname, fsize) in FILES.iter().filter(|(f, _)| f.starts_with(root)) {
+            usage.add(fname, *fsize);
+        }
+        (usage.total, usage.sorted_subtotals())
+    }
+
+    fn subtotals(expected: &[(&str, u64)]) -> Vec<(String, u64)> {
+        expected.iter().map(|(p, s)| (p.to_string(), *s)).collect()
+    }
+
+    #[rstest]
+    fn test_no_depth() {
+        assert_eq!(usage_of("alice", 0), (54321, vec![]))
+    }
+
+    #[rstest]
+    fn test_depth_1() {
+        let expected = subtotals(&[
+            ("alice/uploads", 50000),
+            ("alice/feed_2", 4000),
+            ("alice/feed_1", 321),
+        ]);
+        assert_eq!(usage_of("alice", 1), (54321, expected))
+    }
+
+    #[rstest]
+    fn test_depth_2_under_feed() {
+        let expected = subtotals(&[
+            ("alice/feed_1/pl-dircopy_1", 321),
+            ("alice/feed_1/pl-dircopy_1/pl-mri-preview_2", 300),
+            ("alice/feed_1/pl-dircopy_1/data", 21),
+        ]);
+        assert_eq!(usage_of("alice/feed_1", 2), (321, expected))
+    }
+
+    #[rstest]
+    fn test_files_directly_under_root_are_only_in_total() {
+        assert_eq!(usage_of("alice/uploads", 1), (50000, vec![]))
+    }
+
+    #[rstest]
+    fn test_empty_root() {
+        let mut usage = Usage::new("", 1);
+        usage.add("chris/feed_1/pl-dircopy_1/data/a.txt", 7);
+        assert_eq!(usage.sorted_subtotals(), subtotals(&[("chris", 7)]))
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_home_files_include_uploads() {
+        let mock = MockCube::start().await;
+        mock.add_feed_with_files(mock.feed(1, "Study"), [("pl-dircopy_1/data/a.txt", "1")]);
+        mock.add_file("chris/uploads/e.txt", "54321");
+        let client = EitherClient::LoggedIn(mock.client("chris").await);
+        let (searches, root) = home_files(&client).unwrap();
+        let mut usage = Usage::new(&root, 1);
+        for files in &searches {
+            usage.add_all(files).await.unwrap();
+        }
+        assert_eq!(usage.total, 6);
+        let expected = subtotals(&[("chris/uploads", 5), ("chris/feed_1", 1)]);
+        assert_eq!(usage.sorted_subtotals(), expected)
+    }
+}
@@ -1 +1 @@
-pub const CANNOT_ANONYMOUSLY_SEARCH: &str = "Cannot search without a user account. Please tell Jorge to fix https://github.com/FNNDSC/ChRIS_ultron_backEnd/issues/530";
+pub const CANNOT_ANONYMOUSLY_SEARCH: &str = "Cannot search without a user account. Instead, specify the plugin instance by ID (e.g. pi/543) or by path (e.g. rudolph/feed_130/pl-dircopy_543), or `chrs cd` into its feed to find it by title. Please tell Jorge to fix https://github.com/FNNDSC/ChRIS_ultron_backEnd/issues/530";
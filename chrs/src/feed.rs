@@ -0,0 +1,536 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+
+use clap::{ArgGroup, Args, Subcommand};
+use color_eyre::eyre::{bail, eyre, Error, OptionExt, Result, WrapErr};
+use color_eyre::owo_colors::OwoColorize;
+use futures::{StreamExt, TryStreamExt};
+use time::format_description::well_known::Rfc2822;
+
+use chris::errors::{CubeError, UnshareError};
+use chris::pipeline::feed::{plugin_tree_of_feed, steps_of_feed};
+use chris::pipeline::TitleIndexedPipeline;
+use chris::reqwest::StatusCode;
+use chris::types::{PluginInstanceId, Username};
+use chris::{
+    BaseChrisClient, ChrisClient, EitherClient, FeedResponse, FeedRo, FeedRw,
+    PluginInstanceParameterResponse, PluginInstanceResponse,
+};
+
+use crate::arg::{FeedOrPluginInstance, GivenDataNode};
+use crate::credentials::Credentials;
+use crate::prompt::confirm_delete;
+use crate::sanitize::sanitize_for_terminal;
+use script::{script_step, ExportedFeed, ScriptFormat};
+
+mod script;
+
+/// Number of plugin instances to get the parameters of at the same time.
+const PARAMETER_FETCH_CONCURRENCY: usize = 8;
+
+#[derive(Subcommand)]
+pub enum FeedCommand {
+    /// Delete feeds
+    Rm {
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        force: bool,
+
+        /// Only print which feeds would be deleted
+        #[clap(short, long)]
+        dry_run: bool,
+
+        /// Also accept plugin instances and paths, deleting the whole feed of each
+        #[clap(long)]
+        parent: bool,
+
+        /// Feeds to delete, as feed IDs, feed names, or URLs
+        #[clap(required = true)]
+        feeds: Vec<GivenDataNode>,
+    },
+
+    /// Print the plugin instances of a feed as a pipeline in YAML,
+    /// which can be edited and uploaded using `chrs pipeline upload`
+    ExportPipeline {
+        /// Include the root plugin instance of the feed in the pipeline
+        #[clap(long)]
+        include_root: bool,
+
+        /// Name of the pipeline [default: name of the feed]
+        #[clap(long)]
+        name: Option<String>,
+
+        /// Feed, or a plugin instance of the feed
+        feed: Option<GivenDataNode>,
+    },
+
+    /// Print a script which reproduces the plugin instances of a feed
+    ExportScript {
+        /// Language of the script
+        #[clap(long, value_enum, default_value = "shell")]
+        format: ScriptFormat,
+
+        /// Leave out parameters whose values are their defaults
+        #[clap(long)]
+        minimal: bool,
+
+        /// Feed, or a plugin instance of the feed
+        feed: Option<GivenDataNode>,
+    },
+
+    /// Share a feed with users, or make it public
+    Share(ShareArgs),
+
+    /// Stop sharing a feed with users, or make it private
+    Unshare(ShareArgs),
+}
+
+#[derive(Args)]
+#[clap(group(ArgGroup::new("who").required(true).multiple(true).args(["users", "public"])))]
+pub struct ShareArgs {
+    /// Username of a user. Can be given multiple times.
+    #[clap(short, long = "user", value_name = "USERNAME")]
+    users: Vec<Username>,
+
+    /// Make the feed public, or private for `unshare`
+    #[clap(long)]
+    public: bool,
+
+    /// Feed, or a plugin instance of the feed
+    feed: Option<GivenDataNode>,
+}
+
+/// `chrs feed` command
+pub async fn feed(credentials: Credentials, command: FeedCommand) -> Result<()> {
+    match command {
+        FeedCommand::Rm {
+            force,
+            dry_run,
+            parent,
+            feeds,
+        } => rm_feeds(credentials, feeds, force, dry_run, parent).await,
+        FeedCommand::ExportPipeline {
+            include_root,
+            name,
+            feed,
+        } => export_pipeline(credentials, feed, include_root, name).await,
+        FeedCommand::ExportScript {
+            format,
+            minimal,
+            feed,
+        } => export_script(credentials, feed, format, minimal).await,
+        FeedCommand::Share(args) => share(credentials, args, true).await,
+        FeedCommand::Unshare(args) => share(credentials, args, false).await,
+    }
+}
+
+/// Share a feed, or stop sharing it if `share` is false, then print who can access it.
+async fn share(credentials: Credentials, args: ShareArgs, share: bool) -> Result<()> {
+    let (client, old, _) = credentials
+        .get_client(args.feed.as_ref().map(|g| g.as_arg_str()).as_slice())
+        .await?;
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to share feeds.")?;
+    let given = args
+        .feed
+        .or_else(|| old.map(|id| id.into()))
+        .ok_or_eyre("missing operand")?;
+    let mut feed = given.into_feed_rw(&client, old).await?;
+    let id = feed.object.id.0;
+    if args.public {
+        feed = feed
+            .set_public(share)
+            .await
+            .map_err(|e| explain_forbidden(e, id))?;
+    }
+    for user in &args.users {
+        if share {
+            feed.share_with(user)
+                .await
+                .map_err(|e| explain_forbidden(e, id))?;
+        } else {
+            match feed.unshare(user).await {
+                Ok(()) => (),
+                Err(UnshareError::CUBEError(e)) => return Err(explain_forbidden(e, id)),
+                Err(e) => return Err(e).wrap_err_with(|| format!("Cannot unshare feed/{}", id)),
+            }
+        }
+    }
+    let feed = feed.refresh().await?;
+    let users: Option<Vec<_>> = match feed.user_permissions() {
+        Some(permissions) => Some(
+            permissions
+                .stream()
+                .map_ok(|p| p.user_username)
+                .try_collect()
+                .await?,
+        ),
+        None => None,
+    };
+    print!("{}", describe_access(&feed.object, users.as_deref()));
+    Ok(())
+}
+
+/// Explain a 403 Forbidden response, which _CUBE_ gives when trying to share a feed
+/// which is not yours.
+fn explain_forbidden(error: CubeError, feed_id: u32) -> Error {
+    let is_forbidden = matches!(
+        &error,
+        CubeError::Error { status, .. } if *status == StatusCode::FORBIDDEN
+    );
+    if is_forbidden {
+        Error::new(error).wrap_err(format!(
+            "You are not allowed to share feed/{}. Only the owner of a feed can share it.",
+            feed_id
+        ))
+    } else {
+        Error::new(error)
+    }
+}
+
+/// Describe whether a feed is public, and who it is shared with.
+///
+/// `users` are the users who the feed is shared with, which is `None` for old
+/// versions of _CUBE_ where feeds are shared by adding owners.
+fn describe_access(feed: &FeedResponse, users: Option<&[Username]>) -> String {
+    let mut lines = format!(
+        "{} {} is {}\n",
+        format!("feed/{}", feed.id.0).bold(),
+        sanitize_for_terminal(&feed.name),
+        if feed.is_public() {
+            "public".green().to_string()
+        } else {
+            "private".to_string()
+        }
+    );
+    match users {
+        Some([]) => lines.push_str("not shared with any users\n"),
+        Some(users) => {
+            lines.push_str("shared with:\n");
+            for user in users {
+                lines.push_str(&format!("  {}\n", user.as_str()));
+            }
+        }
+        None => {
+            let count = feed.owner.len();
+            lines.push_str(&format!(
+                "{} owner{}\n",
+                count,
+                if count == 1 { "" } else { "s" }
+            ));
+        }
+    }
+    lines
+}
+
+/// Get the feed of `given`, or the feed of the current plugin instance.
+async fn get_given_feed(
+    credentials: Credentials,
+    given: Option<GivenDataNode>,
+) -> Result<(EitherClient, FeedRo)> {
+    let (client, old, _) = credentials
+        .get_client(given.as_ref().map(|g| g.as_arg_str()).as_slice())
+        .await?;
+    let given = given
+        .or_else(|| old.map(|id| id.into()))
+        .ok_or_eyre("missing operand")?;
+    let feed: FeedRo = match given.into_or(&client, old).await? {
+        FeedOrPluginInstance::Feed(feed) => feed,
+        FeedOrPluginInstance::PluginInstance(p) => p.feed().get().await?,
+    };
+    Ok((client, feed))
+}
+
+/// Get the plugin instances of a feed with their parameters.
+async fn plugin_instances_with_parameters(
+    feed: &FeedRo,
+) -> Result<Vec<(PluginInstanceResponse, Vec<PluginInstanceParameterResponse>)>, CubeError> {
+    feed.get_plugin_instances()
+        .stream_connected()
+        .map_ok(|p| async move {
+            let params = p.parameters().stream().try_collect().await?;
+            Ok((p.object, params))
+        })
+        .try_buffered(PARAMETER_FETCH_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+async fn export_pipeline(
+    credentials: Credentials,
+    given: Option<GivenDataNode>,
+    include_root: bool,
+    name: Option<String>,
+) -> Result<()> {
+    let (_, feed) = get_given_feed(credentials, given).await?;
+    let plugin_instances = plugin_instances_with_parameters(&feed).await?;
+    let plugin_tree = plugin_tree_of_feed(plugin_instances, include_root)?;
+    let pipeline = TitleIndexedPipeline {
+        authors: feed.object.creator_username.to_string(),
+        name: name.unwrap_or_else(|| feed.object.name.clone()),
+        description: format!("Exported from feed/{}", feed.object.id.0),
+        category: String::new(),
+        locked: false,
+        plugin_tree,
+    };
+    print!("{}", serde_yaml::to_string(&pipeline)?);
+    Ok(())
+}
+
+async fn export_script(
+    credentials: Credentials,
+    given: Option<GivenDataNode>,
+    format: ScriptFormat,
+    minimal: bool,
+) -> Result<()> {
+    let (client, feed) = get_given_feed(credentials, given).await?;
+    let plugin_instances = plugin_instances_with_parameters(&feed).await?;
+    let steps = steps_of_feed(plugin_instances, true)?;
+    let plugin_ids: HashSet<_> = steps.iter().map(|s| s.plugin_instance.plugin_id).collect();
+    let parameter_info: HashMap<_, Vec<_>> = futures::stream::iter(plugin_ids)
+        .map(|id| {
+            let client = &client;
+            async move {
+                let plugin = client.get_plugin(id).await?;
+                let parameters = plugin.parameters().stream().try_collect().await?;
+                Ok::<_, Error>((id, parameters))
+            }
+        })
+        .buffer_unordered(PARAMETER_FETCH_CONCURRENCY)
+        .try_collect()
+        .await?;
+    let steps = steps
+        .into_iter()
+        .map(|step| {
+            let info = &parameter_info[&step.plugin_instance.plugin_id];
+            script_step(step, info, minimal)
+        })
+        .collect();
+    let exported = ExportedFeed {
+        id: feed.object.id,
+        name: &feed.object.name,
+        url: client.url().as_str(),
+        steps,
+    };
+    print!("{}", exported.render(format));
+    Ok(())
+}
+
+async fn rm_feeds(
+    credentials: Credentials,
+    given: Vec<GivenDataNode>,
+    force: bool,
+    dry_run: bool,
+    parent: bool,
+) -> Result<()> {
+    let args: Vec<_> = given.iter().map(|g| g.as_arg_str()).collect();
+    let (client, old, _) = credentials.get_client(&args).await?;
+    let client = client
+        .logged_in()
+        .ok_or_else(|| eyre!("You must be logged in to delete feeds."))?;
+    let feeds = feeds_to_delete(&client, given, old, parent).await?;
+    for feed in &feeds {
+        println!("{}", describe_feed(&feed.object)?);
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !force && !confirm_delete(feeds.len(), "feed", None)? {
+        bail!("Cancelled, no feeds were deleted.")
+    }
+    let total = feeds.len();
+    let failed = delete_each(feeds, "feed", |feed| async move {
+        let id = feed.object.id.0;
+        (id, feed.delete().await.map_err(Error::new))
+    })
+    .await;
+    if failed > 0 {
+        bail!("{} of {} feeds were not deleted", failed, total)
+    }
+    Ok(())
+}
+
+/// Get the feeds to delete.
+///
+/// Plugin instances and paths are rejected unless `parent` is true, in which case
+/// they are resolved to the feeds which contain them.
+async fn feeds_to_delete(
+    client: &ChrisClient,
+    given: Vec<GivenDataNode>,
+    old: Option<PluginInstanceId>,
+    parent: bool,
+) -> Result<Vec<FeedRw>> {
+    if !parent {
+        if let Some(not_feed) = given.iter().find(|g| !g.is_feed()) {
+            bail!(
+                "{} is not a feed. Use {} to delete the whole feed which contains it.",
+                not_feed.as_arg_str(),
+                "--parent".bold()
+            )
+        }
+    }
+    futures::stream::iter(given)
+        .then(|g| g.into_feed_rw(client, old))
+        .try_collect()
+        .await
+}
+
+/// A line describing a feed which is about to be deleted.
+fn describe_feed(feed: &FeedResponse) -> Result<String> {
+    let count = feed.num_plugin_instances();
+    Ok(format!(
+        "{} {} ({} plugin instance{}, created {})",
+        format!("feed/{}", feed.id.0).bold(),
+        sanitize_for_terminal(&feed.name),
+        count,
+        if count == 1 { "" } else { "s" },
+        feed.creation_date.format(&Rfc2822)?.italic()
+    ))
+}
+
+/// Delete every item, continuing past failures. Returns the number of failures.
+///
+/// Items are reported by `label` and their ID, e.g. `feed/42`.
+async fn delete_each<T, F, Fut>(items: Vec<T>, label: &str, delete: F) -> usize
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = (u32, Result<()>)>,
+{
+    let mut failed = 0;
+    for item in items {
+        match delete(item).await {
+            (id, Ok(())) => eprintln!("deleted {}/{}", label, id),
+            (id, Err(e)) => {
+                eprintln!(
+                    "{}: could not delete {}/{}: {}",
+                    "error".red(),
+                    label,
+                    id,
+                    e
+                );
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use chris::types::{FeedId, ItemUrl};
+    use rstest::*;
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_delete_each_continues_past_failures() {
+        let deleted = std::sync::Mutex::new(Vec::new());
+        let failed = delete_each(vec![1, 2, 3], "feed", |id| {
+            let deleted = &deleted;
+            async move {
+                if id == 2 {
+                    (id, Err(eyre!("forbidden")))
+                } else {
+                    deleted.lock().unwrap().push(id);
+                    (id, Ok(()))
+                }
+            }
+        })
+        .await;
+        assert_eq!(failed, 1);
+        assert_eq!(deleted.into_inner().unwrap(), vec![1, 3]);
+    }
+
+    /// A mock with feed/1 which has plugin instance pi/1.
+    async fn feed_mock() -> MockCube {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(1, "pl-dircopy", "2.1.1");
+        let feed = mock.feed(1, "My Study");
+        let plinst = mock.plugin_instance(1, &plugin, &feed, None);
+        mock.add_plugin(plugin);
+        mock.add_feed(feed);
+        mock.add_plugin_instance(plinst);
+        mock
+    }
+
+    #[rstest]
+    #[case("pi/1")]
+    #[case("chris/feed_1/pl-dircopy_1")]
+    #[tokio::test]
+    async fn test_rm_rejects_what_is_not_a_feed(#[case] given: &str) {
+        let mock = feed_mock().await;
+        let client = mock.client("chris").await;
+        let given = vec![GivenDataNode::from(given.to_string())];
+        let error = match feeds_to_delete(&client, given, None, false).await {
+            Ok(feeds) => panic!("resolved to {} feeds", feeds.len()),
+            Err(e) => e,
+        };
+        assert!(error.to_string().contains("is not a feed"), "{error}");
+        assert!(mock.requests_of("DELETE").is_empty());
+    }
+
+    #[rstest]
+    #[case("feed/1", false)]
+    #[case("pi/1", true)]
+    #[tokio::test]
+    async fn test_rm_deletes_feed(#[case] given: &str, #[case] parent: bool) {
+        let mock = feed_mock().await;
+        let client = mock.client("chris").await;
+        let given = vec![GivenDataNode::from(given.to_string())];
+        let feeds = feeds_to_delete(&client, given, None, parent).await.unwrap();
+        let failed = delete_each(feeds, "feed", |feed| async move {
+            (feed.object.id.0, feed.delete().await.map_err(Error::new))
+        })
+        .await;
+        assert_eq!(failed, 0);
+        assert_eq!(mock.requests_of("DELETE"), vec!["/api/v1/1/"]);
+        assert!(client.get_feed(FeedId(1)).await.is_err());
+    }
+
+    async fn feed_response(public: Option<bool>, owners: usize) -> FeedResponse {
+        let mock = MockCube::start().await;
+        let feed = mock.feed(45, "Visual dataset example");
+        let owner = (1..=owners)
+            .map(|i| ItemUrl::from(format!("{}users/{}/", mock.url(), i)))
+            .collect();
+        FeedResponse {
+            public,
+            owner,
+            ..feed
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_describe_access_shared() {
+        let feed = feed_response(Some(true), 1).await;
+        let users = [Username::from("bob"), Username::from("carol")];
+        let actual = describe_access(&feed, Some(&users));
+        let lines: Vec<_> = actual.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains("public"));
+        assert_eq!(&lines[1..], ["shared with:", "  bob", "  carol"]);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_describe_access_not_shared() {
+        let feed = feed_response(Some(false), 1).await;
+        let actual = describe_access(&feed, Some(&[]));
+        let lines: Vec<_> = actual.lines().collect();
+        assert!(lines[0].ends_with(" is private"));
+        assert_eq!(lines[1], "not shared with any users");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_describe_access_old_cube() {
+        let feed = feed_response(None, 2).await;
+        let actual = describe_access(&feed, None);
+        let lines: Vec<_> = actual.lines().collect();
+        assert!(lines[0].ends_with(" is private"));
+        assert_eq!(lines[1], "2 owners");
+    }
+}
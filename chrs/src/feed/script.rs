@@ -0,0 +1,489 @@
+//! Scripts which reproduce the plugin instances of a feed, for `chrs feed export-script`.
+
+use std::fmt::Write;
+
+use chris::pipeline::feed::FeedStep;
+use chris::types::{
+    FeedId, PluginInstanceId, PluginParameterAction, PluginParameterType, PluginParameterValue,
+};
+use chris::{PluginInstanceParameterResponse, PluginParameter};
+
+use crate::plugin_clap::long_flag_of;
+use crate::shlex::shlex_quote;
+
+/// Language of `chrs feed export-script`.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq)]
+pub enum ScriptFormat {
+    /// Bash script of `chrs run` commands
+    Shell,
+    /// Python script using python-chrisclient
+    Python,
+}
+
+/// A plugin instance to create.
+#[derive(Debug, PartialEq)]
+pub(super) struct ScriptStep {
+    pub id: PluginInstanceId,
+    pub title: String,
+    /// ID and title of the previous plugin instance
+    pub previous: Option<(PluginInstanceId, String)>,
+    pub plugin_name: String,
+    pub plugin_version: String,
+    pub args: Vec<ScriptArg>,
+}
+
+/// A parameter value of a plugin instance.
+#[derive(Debug, PartialEq)]
+pub(super) struct ScriptArg {
+    pub name: String,
+    /// Long flag of the parameter, without the leading `--`
+    pub flag: String,
+    pub value: PluginParameterValue,
+    /// Whether the flag is given without a value, i.e. it is a `store_true`
+    /// or `store_false` parameter
+    pub is_switch: bool,
+}
+
+/// A feed to reproduce.
+pub(super) struct ExportedFeed<'a> {
+    pub id: FeedId,
+    pub name: &'a str,
+    pub url: &'a str,
+    pub steps: Vec<ScriptStep>,
+}
+
+/// Recover the command-line arguments which a plugin instance was created with.
+///
+/// Arguments are in the order of the plugin's parameters. If `minimal` is true,
+/// parameters whose values are their defaults are left out. `store_true` and
+/// `store_false` parameters are left out regardless when their flag was not given,
+/// because there is no way to give them.
+pub(super) fn script_step(step: FeedStep, info: &[PluginParameter], minimal: bool) -> ScriptStep {
+    let p = step.plugin_instance;
+    ScriptStep {
+        id: p.id,
+        title: step.title,
+        previous: p.previous_id.zip(step.previous),
+        plugin_name: p.plugin_name.to_string(),
+        plugin_version: p.plugin_version.to_string(),
+        args: script_args(step.parameters, info, minimal),
+    }
+}
+
+fn script_args(
+    mut params: Vec<PluginInstanceParameterResponse>,
+    info: &[PluginParameter],
+    minimal: bool,
+) -> Vec<ScriptArg> {
+    let mut args = Vec::with_capacity(params.len());
+    for param in info {
+        let Some(i) = params.iter().position(|p| p.param_name == param.name) else {
+            continue;
+        };
+        let value = params.swap_remove(i).value;
+        if minimal && param.default.as_ref().is_some_and(|d| is_same(d, &value)) {
+            continue;
+        }
+        let is_switch = param.parameter_type == PluginParameterType::Boolean
+            && param.action != PluginParameterAction::Store;
+        let switch_given = match param.action {
+            PluginParameterAction::StoreTrue => value == PluginParameterValue::Boolean(true),
+            PluginParameterAction::StoreFalse => value == PluginParameterValue::Boolean(false),
+            PluginParameterAction::Store => true,
+        };
+        if is_switch && !switch_given {
+            continue;
+        }
+        args.push(ScriptArg {
+            name: param.name.clone(),
+            flag: long_flag_of(param),
+            value,
+            is_switch,
+        });
+    }
+    // parameters which the plugin does not describe, which should not happen
+    params.sort_by(|a, b| a.param_name.cmp(&b.param_name));
+    args.extend(params.into_iter().map(|p| ScriptArg {
+        flag: p.param_name.clone(),
+        name: p.param_name,
+        value: p.value,
+        is_switch: false,
+    }));
+    args
+}
+
+/// Whether a parameter value is the same as a default value, where integers and
+/// floats of the same number are the same.
+fn is_same(default: &PluginParameterValue, value: &PluginParameterValue) -> bool {
+    match (default, value) {
+        (PluginParameterValue::Integer(i), PluginParameterValue::Float(f))
+        | (PluginParameterValue::Float(f), PluginParameterValue::Integer(i)) => *i as f64 == *f,
+        (default, value) => default == value,
+    }
+}
+
+impl ExportedFeed<'_> {
+    pub fn render(&self, format: ScriptFormat) -> String {
+        match format {
+            ScriptFormat::Shell => self.to_shell(),
+            ScriptFormat::Python => self.to_python(),
+        }
+    }
+
+    /// Render as a bash script of `chrs run` commands. The plugin instance created by
+    /// each command, which `chrs run` prints as `plugininstance/ID`, is saved in a shell
+    /// variable, which is given as the input of the commands after it. Titles are not
+    /// used to find the inputs because _CUBE_ searches for titles which contain the title.
+    fn to_shell(&self) -> String {
+        let mut out = format!(
+            "#!/usr/bin/env bash\n# Reproduces feed/{} of {}\n\nset -euo pipefail\n\n",
+            self.id.0, self.url
+        );
+        for (i, step) in self.steps.iter().enumerate() {
+            let mut words = vec![
+                "chrs".to_string(),
+                "run".to_string(),
+                shlex_quote(&format!("{}@{}", step.plugin_name, step.plugin_version)),
+                "--title".to_string(),
+                shlex_quote(&step.title),
+                "--".to_string(),
+            ];
+            for arg in &step.args {
+                words.push(format!("--{}", arg.flag));
+                if !arg.is_switch {
+                    words.push(shlex_quote(&arg.value.to_string()));
+                }
+            }
+            if let Some((id, _)) = &step.previous {
+                words.push(format!("\"$plinst_{}\"", id.0));
+            }
+            writeln!(out, "plinst_{}=$({})", step.id.0, words.join(" ")).unwrap();
+            if i == 0 {
+                writeln!(
+                    out,
+                    "chrs set feed-name {} \"$plinst_{}\"",
+                    shlex_quote(self.name),
+                    step.id.0
+                )
+                .unwrap();
+            }
+        }
+        out
+    }
+
+    /// Render as a Python script using python-chrisclient.
+    fn to_python(&self) -> String {
+        let mut out = format!(
+            r#"#!/usr/bin/env python
+# Reproduces feed/{} of {}
+# Requires python-chrisclient: pip install python-chrisclient
+
+import getpass
+import os
+
+from chrisclient import client
+
+cl = client.Client(
+    os.environ.get("CHRIS_URL", {}),
+    os.environ.get("CHRIS_USERNAME") or input("Username: "),
+    os.environ.get("CHRIS_PASSWORD") or getpass.getpass(),
+)
+
+
+def plugin_id(name, version):
+    plugins = cl.get_plugins({{"name_exact": name, "version": version}})
+    return plugins["data"][0]["id"]
+
+"#,
+            self.id.0,
+            self.url,
+            python_str(self.url)
+        );
+        for step in &self.steps {
+            writeln!(
+                out,
+                "\nplinst_{} = cl.create_plugin_instance(\n    plugin_id({}, {}),\n    {{",
+                step.id.0,
+                python_str(&step.plugin_name),
+                python_str(&step.plugin_version)
+            )
+            .unwrap();
+            writeln!(out, "        \"title\": {},", python_str(&step.title)).unwrap();
+            if let Some((id, _)) = &step.previous {
+                writeln!(out, "        \"previous_id\": plinst_{}[\"id\"],", id.0).unwrap();
+            }
+            for arg in &step.args {
+                writeln!(
+                    out,
+                    "        {}: {},",
+                    python_str(&arg.name),
+                    python_value(&arg.value)
+                )
+                .unwrap();
+            }
+            out.push_str("    },\n)\n");
+        }
+        out
+    }
+}
+
+/// A Python string literal.
+fn python_str(s: &str) -> String {
+    // JSON string escapes are also valid in Python
+    serde_json::to_string(s).unwrap()
+}
+
+/// A Python literal of a parameter value.
+fn python_value(value: &PluginParameterValue) -> String {
+    match value {
+        PluginParameterValue::Boolean(true) => "True".to_string(),
+        PluginParameterValue::Boolean(false) => "False".to_string(),
+        PluginParameterValue::Integer(i) => i.to_string(),
+        PluginParameterValue::Float(f) if f.is_finite() => format!("{:?}", f),
+        PluginParameterValue::Float(f) => format!("float(\"{}\")", f),
+        PluginParameterValue::Stringish(s) => python_str(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use chris::types::{PluginParameterAction, PluginParameterType};
+    use rstest::*;
+
+    fn given(name: &str, value: serde_json::Value) -> PluginInstanceParameterResponse {
+        serde_json::from_value(serde_json::json!({
+            "url": "https://cube.example.org/api/v1/plugins/string-parameter/1/",
+            "id": 1,
+            "param_name": name,
+            "value": value,
+            "type": "string",
+            "plugin_inst": "https://cube.example.org/api/v1/plugins/instances/2/",
+            "plugin_param": "https://cube.example.org/api/v1/plugins/parameters/1/",
+        }))
+        .unwrap()
+    }
+
+    #[fixture]
+    async fn info() -> Vec<PluginParameter> {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(2, "pl-simpledsapp", "2.1.0");
+        let parameter = |id, name, parameter_type, action, default| PluginParameter {
+            action,
+            default: Some(default),
+            ..mock.plugin_parameter(id, &plugin, name, parameter_type)
+        };
+        vec![
+            parameter(
+                1,
+                "prefix",
+                PluginParameterType::String,
+                PluginParameterAction::Store,
+                PluginParameterValue::Stringish(String::new()),
+            ),
+            parameter(
+                2,
+                "sleep_length",
+                PluginParameterType::Float,
+                PluginParameterAction::Store,
+                PluginParameterValue::Float(0.5),
+            ),
+            parameter(
+                3,
+                "ignore_inputs",
+                PluginParameterType::Boolean,
+                PluginParameterAction::StoreTrue,
+                PluginParameterValue::Boolean(false),
+            ),
+            parameter(
+                4,
+                "no_jitter",
+                PluginParameterType::Boolean,
+                PluginParameterAction::StoreFalse,
+                PluginParameterValue::Boolean(true),
+            ),
+        ]
+    }
+
+    #[fixture]
+    fn given_params() -> Vec<PluginInstanceParameterResponse> {
+        vec![
+            given("no_jitter", serde_json::json!(true)),
+            given("ignore_inputs", serde_json::json!(true)),
+            given("sleep_length", serde_json::json!(0.5)),
+            given("prefix", serde_json::json!("it's")),
+        ]
+    }
+
+    fn summary(args: &[ScriptArg]) -> Vec<(&str, String, bool)> {
+        args.iter()
+            .map(|a| (a.flag.as_str(), a.value.to_string(), a.is_switch))
+            .collect()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_script_args(
+        #[future] info: Vec<PluginParameter>,
+        given_params: Vec<PluginInstanceParameterResponse>,
+    ) {
+        let info = info.await;
+        let args = script_args(given_params, &info, false);
+        assert_eq!(
+            summary(&args),
+            vec![
+                ("prefix", "it's".to_string(), false),
+                ("sleep-length", "0.5".to_string(), false),
+                ("ignore-inputs", "true".to_string(), true),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_script_args_minimal(
+        #[future] info: Vec<PluginParameter>,
+        given_params: Vec<PluginInstanceParameterResponse>,
+    ) {
+        let info = info.await;
+        let args = script_args(given_params, &info, true);
+        assert_eq!(
+            summary(&args),
+            vec![
+                ("prefix", "it's".to_string(), false),
+                ("ignore-inputs", "true".to_string(), true),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case(serde_json::json!(1), serde_json::json!(1.0), true)]
+    #[case(serde_json::json!(1.5), serde_json::json!(1), false)]
+    #[case(serde_json::json!("1"), serde_json::json!(1), false)]
+    fn test_is_same(
+        #[case] default: serde_json::Value,
+        #[case] value: serde_json::Value,
+        #[case] expected: bool,
+    ) {
+        let default = serde_json::from_value(default).unwrap();
+        let value = serde_json::from_value(value).unwrap();
+        assert_eq!(is_same(&default, &value), expected)
+    }
+
+    #[fixture]
+    fn feed() -> ExportedFeed<'static> {
+        let arg = |flag: &str, value, is_switch| ScriptArg {
+            name: flag.replace('-', "_"),
+            flag: flag.to_string(),
+            value,
+            is_switch,
+        };
+        ExportedFeed {
+            id: FeedId(45),
+            name: "My Study",
+            url: "https://cube.example.org/api/v1/",
+            steps: vec![
+                ScriptStep {
+                    id: PluginInstanceId(1),
+                    title: "upload".to_string(),
+                    previous: None,
+                    plugin_name: "pl-dircopy".to_string(),
+                    plugin_version: "2.1.1".to_string(),
+                    args: vec![arg(
+                        "dir",
+                        PluginParameterValue::Stringish("chris/uploads/brain".to_string()),
+                        false,
+                    )],
+                },
+                ScriptStep {
+                    id: PluginInstanceId(2),
+                    title: "it's a test".to_string(),
+                    previous: Some((PluginInstanceId(1), "upload".to_string())),
+                    plugin_name: "pl-simpledsapp".to_string(),
+                    plugin_version: "2.1.0".to_string(),
+                    args: vec![
+                        arg("sleep-length", PluginParameterValue::Float(1.0), false),
+                        arg("ignore-inputs", PluginParameterValue::Boolean(true), true),
+                    ],
+                },
+            ],
+        }
+    }
+
+    #[rstest]
+    fn test_shell(feed: ExportedFeed<'static>) {
+        let expected = r#"#!/usr/bin/env bash
+# Reproduces feed/45 of https://cube.example.org/api/v1/
+
+set -euo pipefail
+
+plinst_1=$(chrs run pl-dircopy@2.1.1 --title upload -- --dir chris/uploads/brain)
+chrs set feed-name 'My Study' "$plinst_1"
+plinst_2=$(chrs run pl-simpledsapp@2.1.0 --title 'it'\''s a test' -- --sleep-length 1 --ignore-inputs "$plinst_1")
+"#;
+        assert_eq!(feed.render(ScriptFormat::Shell), expected)
+    }
+
+    #[rstest]
+    fn test_shell_same_plugin_twice(mut feed: ExportedFeed<'static>) {
+        // titles made unique by chris::pipeline::feed::unique_title, where the
+        // title of one is a substring of the title of the other
+        let step = |id, title: &str, previous: (u32, &str)| ScriptStep {
+            id: PluginInstanceId(id),
+            title: title.to_string(),
+            previous: Some((PluginInstanceId(previous.0), previous.1.to_string())),
+            plugin_name: "pl-simpledsapp".to_string(),
+            plugin_version: "2.1.0".to_string(),
+            args: Vec::new(),
+        };
+        feed.steps.truncate(1);
+        feed.steps.extend([
+            step(2, "pl-simpledsapp", (1, "upload")),
+            step(3, "pl-simpledsapp (3)", (1, "upload")),
+            step(4, "pl-simpledsapp (4)", (2, "pl-simpledsapp")),
+        ]);
+        let actual = feed.render(ScriptFormat::Shell);
+        let commands: Vec<_> = actual
+            .lines()
+            .filter(|line| line.starts_with("plinst_"))
+            .collect();
+        assert_eq!(
+            &commands[1..],
+            [
+                "plinst_2=$(chrs run pl-simpledsapp@2.1.0 --title pl-simpledsapp -- \"$plinst_1\")",
+                "plinst_3=$(chrs run pl-simpledsapp@2.1.0 --title 'pl-simpledsapp (3)' -- \"$plinst_1\")",
+                "plinst_4=$(chrs run pl-simpledsapp@2.1.0 --title 'pl-simpledsapp (4)' -- \"$plinst_2\")",
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_python(feed: ExportedFeed<'static>) {
+        let actual = feed.render(ScriptFormat::Python);
+        let expected_end = r#"
+plinst_1 = cl.create_plugin_instance(
+    plugin_id("pl-dircopy", "2.1.1"),
+    {
+        "title": "upload",
+        "dir": "chris/uploads/brain",
+    },
+)
+
+plinst_2 = cl.create_plugin_instance(
+    plugin_id("pl-simpledsapp", "2.1.0"),
+    {
+        "title": "it's a test",
+        "previous_id": plinst_1["id"],
+        "sleep_length": 1.0,
+        "ignore_inputs": True,
+    },
+)
+"#;
+        assert!(actual.starts_with("#!/usr/bin/env python\n# Reproduces feed/45 of"));
+        assert!(
+            actual.contains(r#"os.environ.get("CHRIS_URL", "https://cube.example.org/api/v1/"),"#)
+        );
+        assert!(actual.ends_with(expected_end), "{}", actual)
+    }
+}
@@ -1,11 +1,26 @@
 //! Shared helper functions for upload and download.
 
+mod adaptive;
 mod bytes_bar;
+mod checksum;
 mod error;
+mod interrupt;
+mod json_progress;
+mod manifest;
 mod multi_progress;
+mod times;
+mod totals;
 
+pub use adaptive::{AdaptiveLimiter, Outcome};
 pub use bytes_bar::*;
+pub use checksum::{sha256_file, Checksum, Checksums, Hasher, Verification, CHECKSUMS_NAME};
 pub use error::FileTransferError;
+pub use interrupt::{Cancellation, Interrupted, Partial, EXIT_INTERRUPTED};
+pub use manifest::{
+    Manifest, ManifestFeed, ManifestFile, ManifestPluginInstance, ManifestSource, ManifestStatus,
+    ManifestWriter, MANIFEST_NAME,
+};
 pub use multi_progress::*;
+pub use times::{restore_times_under, TimesSidecar, TIMES_SIDECAR_NAME};
 
 pub const SIZE_128_MIB: u64 = 134217728;
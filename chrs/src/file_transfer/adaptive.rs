@@ -0,0 +1,328 @@
+use std::fmt::Display;
+use std::sync::Mutex;
+
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use chris::errors::{CubeError, FileIOError};
+
+use super::{FileTransferError, FileTransferEvent};
+
+/// Number of consecutive successful transfers needed before concurrency is increased by one.
+const RECOVERY_STREAK: usize = 8;
+
+/// Outcome of a file transfer, as far as [ConcurrencyController] is concerned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Transfer completed.
+    Success,
+    /// Transfer failed because of a server error or timeout, i.e. the server might be overloaded.
+    Overloaded,
+    /// Transfer failed for a reason which has nothing to do with concurrency.
+    Other,
+}
+
+impl Outcome {
+    /// Classify the result of an upload.
+    pub fn of_upload<T>(result: &Result<T, FileIOError>) -> Self {
+        match result {
+            Ok(_) => Self::Success,
+            Err(FileIOError::Cube(e)) => Self::of_cube_error(e),
+            Err(FileIOError::IO(e)) => Self::of_io_error(e),
+            Err(FileIOError::PathError(_)) => Self::Other,
+        }
+    }
+
+    /// Classify the result of a download.
+    pub fn of_download<T>(result: &Result<T, FileTransferError>) -> Self {
+        match result {
+            Ok(_) => Self::Success,
+            Err(FileTransferError::Cube(e)) => Self::of_cube_error(e),
+            Err(FileTransferError::IO(e)) => Self::of_io_error(e),
+            // usually the connection was dropped before the download finished
+            Err(FileTransferError::SizeMismatch { .. }) => Self::Overloaded,
+        }
+    }
+
+    fn of_cube_error(error: &CubeError) -> Self {
+        let overloaded = match error {
+            CubeError::Error { status, .. } => status.is_server_error(),
+            CubeError::Raw(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status().map(|s| s.is_server_error()).unwrap_or(false)
+            }
+            CubeError::Middleware(_) => true,
+            CubeError::InvalidNextUrl { .. }
+            | CubeError::UnknownUser
+            | CubeError::IncorrectPassword => false,
+        };
+        if overloaded {
+            Self::Overloaded
+        } else {
+            Self::Other
+        }
+    }
+
+    fn of_io_error(error: &std::io::Error) -> Self {
+        // HTTP response bodies are adapted to readers with errors of kind ConnectionAborted
+        match error.kind() {
+            std::io::ErrorKind::ConnectionAborted | std::io::ErrorKind::TimedOut => {
+                Self::Overloaded
+            }
+            _ => Self::Other,
+        }
+    }
+}
+
+/// A change to the effective number of concurrent transfers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Adjustment {
+    pub from: usize,
+    pub to: usize,
+}
+
+impl Display for Adjustment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = if self.to < self.from {
+            "server errors or timeouts observed"
+        } else {
+            "recovering after successful transfers"
+        };
+        write!(
+            f,
+            "Concurrency adjusted from {} to {} ({})",
+            self.from, self.to, reason
+        )
+    }
+}
+
+/// State machine which decides the number of concurrent transfers.
+///
+/// - An [Outcome::Overloaded] transfer halves the concurrency (to a minimum of 1).
+/// - Every [RECOVERY_STREAK] consecutive successful transfers increases the concurrency by one,
+///   up to the initial maximum.
+#[derive(Debug)]
+pub struct ConcurrencyController {
+    max: usize,
+    current: usize,
+    streak: usize,
+}
+
+impl ConcurrencyController {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            current: max,
+            streak: 0,
+        }
+    }
+
+    /// The current number of allowed concurrent transfers.
+    pub fn limit(&self) -> usize {
+        self.current
+    }
+
+    /// Update the state with the outcome of a transfer, returning what changed (if anything).
+    pub fn update(&mut self, outcome: Outcome) -> Option<Adjustment> {
+        let from = self.current;
+        match outcome {
+            Outcome::Success => {
+                self.streak += 1;
+                if self.streak >= RECOVERY_STREAK && self.current < self.max {
+                    self.streak = 0;
+                    self.current += 1;
+                }
+            }
+            Outcome::Overloaded => {
+                self.streak = 0;
+                self.current = (self.current / 2).max(1);
+            }
+            Outcome::Other => {}
+        }
+        if from == self.current {
+            None
+        } else {
+            Some(Adjustment {
+                from,
+                to: self.current,
+            })
+        }
+    }
+}
+
+/// An async semaphore whose number of permits is decided by a [ConcurrencyController].
+///
+/// Permits are never revoked from running transfers. Instead, when the concurrency is
+/// reduced, permits are "forgotten" as they are returned.
+pub struct AdaptiveLimiter {
+    semaphore: Semaphore,
+    state: Mutex<LimiterState>,
+    enabled: bool,
+    log: Option<UnboundedSender<FileTransferEvent>>,
+}
+
+struct LimiterState {
+    controller: ConcurrencyController,
+    /// Number of permits which should be forgotten when they are returned.
+    debt: usize,
+}
+
+impl AdaptiveLimiter {
+    /// Create a limiter starting at `threads` concurrent transfers. If `enabled` is false,
+    /// the limit will stay constant. If `log` is given, adjustments are printed to it.
+    pub fn new(
+        threads: usize,
+        enabled: bool,
+        log: Option<UnboundedSender<FileTransferEvent>>,
+    ) -> Self {
+        let controller = ConcurrencyController::new(threads);
+        Self {
+            semaphore: Semaphore::new(controller.limit()),
+            state: Mutex::new(LimiterState {
+                controller,
+                debt: 0,
+            }),
+            enabled,
+            log,
+        }
+    }
+
+    /// Wait until it is OK to start another transfer.
+    pub async fn acquire(&self) -> AdaptivePermit<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("AdaptiveLimiter semaphore is never closed");
+        AdaptivePermit {
+            limiter: self,
+            permit: Some(permit),
+        }
+    }
+
+    fn report(&self, outcome: Outcome) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if let Some(adjustment) = state.controller.update(outcome) {
+            if adjustment.to < adjustment.from {
+                state.debt += adjustment.from - adjustment.to;
+            } else if state.debt > 0 {
+                state.debt -= 1;
+            } else {
+                self.semaphore.add_permits(adjustment.to - adjustment.from);
+            }
+            if let Some(tx) = &self.log {
+                tx.send(FileTransferEvent::Println(adjustment.to_string()))
+                    .unwrap();
+            }
+        }
+    }
+
+    fn release(&self, permit: SemaphorePermit) {
+        let mut state = self.state.lock().unwrap();
+        if state.debt > 0 {
+            state.debt -= 1;
+            permit.forget();
+        }
+    }
+}
+
+/// Permission to run one transfer, obtained from [AdaptiveLimiter::acquire].
+pub struct AdaptivePermit<'a> {
+    limiter: &'a AdaptiveLimiter,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl AdaptivePermit<'_> {
+    /// Report how the transfer went, then give back the permit.
+    pub fn report(self, outcome: Outcome) {
+        self.limiter.report(outcome)
+    }
+}
+
+impl Drop for AdaptivePermit<'_> {
+    fn drop(&mut self) {
+        if let Some(permit) = self.permit.take() {
+            self.limiter.release(permit)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(8, &[Outcome::Overloaded], 4)]
+    #[case(8, &[Outcome::Overloaded, Outcome::Overloaded], 2)]
+    #[case(8, &[Outcome::Overloaded, Outcome::Overloaded, Outcome::Overloaded, Outcome::Overloaded], 1)]
+    #[case(1, &[Outcome::Overloaded], 1)]
+    #[case(4, &[Outcome::Other, Outcome::Other], 4)]
+    #[case(4, &[Outcome::Success; 20], 4)]
+    fn test_controller_limit(
+        #[case] max: usize,
+        #[case] outcomes: &[Outcome],
+        #[case] expected: usize,
+    ) {
+        let mut controller = ConcurrencyController::new(max);
+        for outcome in outcomes {
+            controller.update(*outcome);
+        }
+        assert_eq!(controller.limit(), expected);
+    }
+
+    #[rstest]
+    fn test_controller_recovers_slowly() {
+        let mut controller = ConcurrencyController::new(4);
+        assert_eq!(
+            controller.update(Outcome::Overloaded),
+            Some(Adjustment { from: 4, to: 2 })
+        );
+        for _ in 1..RECOVERY_STREAK {
+            assert_eq!(controller.update(Outcome::Success), None);
+        }
+        assert_eq!(
+            controller.update(Outcome::Success),
+            Some(Adjustment { from: 2, to: 3 })
+        );
+        controller.update(Outcome::Success);
+        assert_eq!(
+            controller.update(Outcome::Overloaded),
+            Some(Adjustment { from: 3, to: 1 }),
+            "success streak should be reset by a failure"
+        );
+        for _ in 1..RECOVERY_STREAK {
+            controller.update(Outcome::Success);
+        }
+        assert_eq!(controller.limit(), 1);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_limiter_forgets_permits() {
+        let limiter = AdaptiveLimiter::new(4, true, None);
+        let a = limiter.acquire().await;
+        let b = limiter.acquire().await;
+        a.report(Outcome::Overloaded);
+        assert_eq!(limiter.semaphore.available_permits(), 2);
+        b.report(Outcome::Success);
+        assert_eq!(limiter.semaphore.available_permits(), 2);
+        for _ in 0..RECOVERY_STREAK {
+            limiter.acquire().await.report(Outcome::Success);
+        }
+        assert_eq!(limiter.semaphore.available_permits(), 3);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_limiter_disabled() {
+        let limiter = AdaptiveLimiter::new(4, false, None);
+        limiter.acquire().await.report(Outcome::Overloaded);
+        assert_eq!(limiter.semaphore.available_permits(), 4);
+    }
+}
@@ -0,0 +1,311 @@
+//! SHA-256 checksums of transferred files, in the format of `sha256sum`.
+//!
+//! Checksums are computed while files are uploaded (`chrs upload --checksum`) or
+//! downloaded (`chrs download --checksum`), and checked offline by `chrs verify`.
+
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use camino::Utf8Path;
+use futures::{Stream, TryStreamExt};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWrite;
+
+/// File name of the checksums written by `chrs download --checksum` for a directory.
+pub const CHECKSUMS_NAME: &str = "SHA256SUMS";
+
+/// Computes the SHA-256 of data as it passes through a stream or writer.
+#[derive(Clone, Default)]
+pub struct Hasher(Arc<Mutex<Sha256>>);
+
+impl Hasher {
+    pub fn update(&self, data: &[u8]) {
+        self.0.lock().unwrap().update(data)
+    }
+
+    /// Hex-encoded SHA-256 of the data seen so far.
+    pub fn hex(&self) -> String {
+        format!("{:x}", self.0.lock().unwrap().clone().finalize())
+    }
+
+    /// Hash the chunks of a stream as they are consumed.
+    pub fn inspect_stream<S, B, E>(&self, stream: S) -> impl Stream<Item = Result<B, E>>
+    where
+        S: Stream<Item = Result<B, E>>,
+        B: AsRef<[u8]>,
+    {
+        let hasher = self.clone();
+        stream.inspect_ok(move |chunk| hasher.update(chunk.as_ref()))
+    }
+
+    /// Hash the data which is written to `writer`.
+    pub fn wrap_async_write<W: AsyncWrite + Unpin>(&self, writer: W) -> HashingWriter<W> {
+        HashingWriter {
+            inner: writer,
+            hasher: self.clone(),
+        }
+    }
+}
+
+/// See [Hasher::wrap_async_write].
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Hasher,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for HashingWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.hasher.update(&buf[..n]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Compute the SHA-256 of a local file.
+pub async fn sha256_file(path: impl AsRef<std::path::Path>) -> std::io::Result<String> {
+    let mut file = fs_err::tokio::File::open(path.as_ref()).await?;
+    let hasher = Hasher::default();
+    tokio::io::copy(&mut file, &mut hasher.wrap_async_write(tokio::io::sink())).await?;
+    Ok(hasher.hex())
+}
+
+/// The SHA-256 of a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checksum {
+    /// Hex-encoded SHA-256
+    pub sha256: String,
+    /// Path relative to the directory the checksums are of
+    pub path: String,
+}
+
+/// A list of [Checksum], which is read and written in the format of `sha256sum`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Checksums {
+    pub files: Vec<Checksum>,
+}
+
+/// Result of [Checksums::verify].
+#[derive(Debug, Default, PartialEq)]
+pub struct Verification {
+    /// Number of files with the expected checksum
+    pub ok: usize,
+    /// Files which exist with a different checksum
+    pub mismatched: Vec<String>,
+    /// Files which do not exist
+    pub missing: Vec<String>,
+}
+
+impl Verification {
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty()
+    }
+}
+
+impl Extend<Checksum> for Checksums {
+    fn extend<T: IntoIterator<Item = Checksum>>(&mut self, iter: T) {
+        self.files.extend(iter)
+    }
+}
+
+impl FromIterator<Checksum> for Checksums {
+    fn from_iter<T: IntoIterator<Item = Checksum>>(iter: T) -> Self {
+        Self {
+            files: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Checksums {
+    /// Serialize as lines of `<sha256>  <path>`, sorted by path.
+    pub fn to_text(&self) -> String {
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+            .into_iter()
+            .map(|c| format!("{}  {}\n", c.sha256, c.path))
+            .collect()
+    }
+
+    /// Parse the output of `chrs download --checksum`, `chrs upload --checksum`, or `sha256sum`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                parse_line(line).ok_or_else(|| format!("line {}: invalid checksum line", i + 1))
+            })
+            .collect()
+    }
+
+    /// Check the files in `dir` against the checksums.
+    pub async fn verify(&self, dir: &Utf8Path) -> std::io::Result<Verification> {
+        let mut verification = Verification::default();
+        for expected in &self.files {
+            match sha256_file(dir.join(&expected.path)).await {
+                Ok(actual) if actual == expected.sha256 => verification.ok += 1,
+                Ok(_) => verification.mismatched.push(expected.path.clone()),
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    verification.missing.push(expected.path.clone())
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(verification)
+    }
+}
+
+/// Parse a line of `sha256sum` output. In binary mode, the path is prefixed by `*`.
+fn parse_line(line: &str) -> Option<Checksum> {
+    let (sha256, path) = line.split_once(' ')?;
+    let path = path.strip_prefix([' ', '*'])?;
+    let is_hex = sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit());
+    if !is_hex || path.is_empty() {
+        return None;
+    }
+    Some(Checksum {
+        sha256: sha256.to_ascii_lowercase(),
+        path: path.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camino::Utf8PathBuf;
+    use rstest::*;
+    use tempfile::TempDir;
+
+    /// `printf hello | sha256sum`
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[fixture]
+    fn tree() -> (TempDir, Checksums) {
+        let tmp = TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        let files = [
+            ("a.txt", "hello"),
+            ("sub/b.txt", "world"),
+            ("sub/deeper/c.dat", "ChRIS"),
+        ];
+        for (path, content) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let checksums = files
+            .iter()
+            .map(|(path, content)| Checksum {
+                sha256: format!("{:x}", Sha256::digest(content)),
+                path: path.to_string(),
+            })
+            .collect();
+        (tmp, checksums)
+    }
+
+    fn dir_of(tmp: &TempDir) -> Utf8PathBuf {
+        Utf8PathBuf::from_path_buf(tmp.path().to_path_buf()).unwrap()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_hashing_writer() {
+        let hasher = Hasher::default();
+        let mut out = Vec::new();
+        let mut writer = hasher.wrap_async_write(&mut out);
+        tokio::io::copy(&mut "hello".as_bytes(), &mut writer)
+            .await
+            .unwrap();
+        assert_eq!(hasher.hex(), HELLO_SHA256);
+        assert_eq!(out, b"hello");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_inspect_stream() {
+        let hasher = Hasher::default();
+        let chunks = futures::stream::iter(["he", "ll", "o"].map(Ok::<_, std::io::Error>));
+        let collected: Vec<_> = hasher.inspect_stream(chunks).try_collect().await.unwrap();
+        assert_eq!(collected, vec!["he", "ll", "o"]);
+        assert_eq!(hasher.hex(), HELLO_SHA256);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_verify_intact(tree: (TempDir, Checksums)) {
+        let (tmp, checksums) = tree;
+        let verification = checksums.verify(&dir_of(&tmp)).await.unwrap();
+        assert!(verification.is_ok());
+        assert_eq!(verification.ok, 3);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_verify_tampered_truncated_and_missing(tree: (TempDir, Checksums)) {
+        let (tmp, checksums) = tree;
+        let dir = dir_of(&tmp);
+        std::fs::write(dir.join("a.txt"), "jello").unwrap();
+        std::fs::write(dir.join("sub/b.txt"), "wor").unwrap();
+        std::fs::remove_file(dir.join("sub/deeper/c.dat")).unwrap();
+        let verification = checksums.verify(&dir).await.unwrap();
+        let expected = Verification {
+            ok: 0,
+            mismatched: vec!["a.txt".to_string(), "sub/b.txt".to_string()],
+            missing: vec!["sub/deeper/c.dat".to_string()],
+        };
+        assert_eq!(verification, expected);
+        assert!(!verification.is_ok());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_sha256_file(tree: (TempDir, Checksums)) {
+        let (tmp, _) = tree;
+        let actual = sha256_file(dir_of(&tmp).join("a.txt")).await.unwrap();
+        assert_eq!(actual, HELLO_SHA256);
+    }
+
+    #[rstest]
+    fn test_round_trip(tree: (TempDir, Checksums)) {
+        let (_tmp, checksums) = tree;
+        let text = checksums.to_text();
+        assert!(text.starts_with(&format!("{}  a.txt\n", HELLO_SHA256)));
+        assert_eq!(Checksums::parse(&text).unwrap(), checksums);
+    }
+
+    #[rstest]
+    fn test_parse_sha256sum_binary_mode() {
+        let text = format!("{} *a file.txt\n\n", HELLO_SHA256.to_uppercase());
+        let expected = Checksums {
+            files: vec![Checksum {
+                sha256: HELLO_SHA256.to_string(),
+                path: "a file.txt".to_string(),
+            }],
+        };
+        assert_eq!(Checksums::parse(&text).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("abc  a.txt", "line 1")]
+    #[case(HELLO_SHA256, "line 1")]
+    #[case(&format!("{HELLO_SHA256}  a.txt\n{HELLO_SHA256}\tb.txt"), "line 2")]
+    fn test_parse_invalid(#[case] text: &str, #[case] expected: &str) {
+        let error = Checksums::parse(text).unwrap_err();
+        assert!(error.contains(expected), "{error}")
+    }
+}
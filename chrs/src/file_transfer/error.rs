@@ -6,4 +6,7 @@ pub enum FileTransferError {
     Cube(#[from] CubeError),
     #[error(transparent)]
     IO(#[from] std::io::Error),
+    /// The number of bytes received is different from the file size reported by _CUBE_.
+    #[error("received {actual} bytes, but the file size is {expected} bytes")]
+    SizeMismatch { expected: u64, actual: u64 },
 }
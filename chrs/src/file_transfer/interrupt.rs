@@ -0,0 +1,325 @@
+//! Stopping uploads and downloads cleanly when the user presses Ctrl-C.
+//!
+//! Transfers are run by [Cancellation::run]. On Ctrl-C, transfers which have not started
+//! yet are skipped, and transfers in progress are dropped and the files which they
+//! started writing are deleted. The command then fails with [Interrupted].
+
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Exit code of `chrs` after it was interrupted by Ctrl-C.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// Cancels transfers when the user presses Ctrl-C, and counts how many of them completed.
+///
+/// The Ctrl-C listener stops when the last clone of the [Cancellation] is dropped.
+#[derive(Clone, Default)]
+pub struct Cancellation {
+    token: CancellationToken,
+    completed: Arc<AtomicUsize>,
+    aborted: Arc<AtomicUsize>,
+    _listener: Option<Arc<Listener>>,
+}
+
+/// Task which listens for Ctrl-C, aborted when dropped.
+struct Listener(JoinHandle<()>);
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.0.abort()
+    }
+}
+
+/// The file of a transfer, which is deleted if the transfer is cancelled after it
+/// started writing to the file. A file which the transfer did not create or truncate,
+/// e.g. an existing file which is skipped, is kept.
+#[derive(Debug, Default)]
+pub struct Partial(OnceLock<Utf8PathBuf>);
+
+impl Partial {
+    /// Mark `path` as the file of the transfer, before it is created or truncated.
+    pub fn writing(&self, path: &Utf8Path) {
+        let _ = self.0.set(path.to_path_buf());
+    }
+
+    /// The file of the transfer, if it started writing to it.
+    pub fn path(&self) -> Option<&Utf8Path> {
+        self.0.get().map(|path| path.as_path())
+    }
+}
+
+/// Error of a command which was interrupted by Ctrl-C.
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("Interrupted: {completed} of {total} files transferred, {aborted} aborted")]
+pub struct Interrupted {
+    pub completed: usize,
+    pub aborted: usize,
+    pub total: usize,
+}
+
+impl Cancellation {
+    /// Cancel on Ctrl-C. A second Ctrl-C exits immediately.
+    pub fn on_ctrl_c() -> Self {
+        Self::on_interrupt(
+            || async { tokio::signal::ctrl_c().await.is_ok() },
+            || std::process::exit(EXIT_INTERRUPTED),
+        )
+    }
+
+    /// Cancel when `interrupt` resolves to `true`, and call `on_second` if it does again.
+    fn on_interrupt<F, Fut>(interrupt: F, on_second: impl FnOnce() + Send + 'static) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = bool> + Send,
+    {
+        let token = CancellationToken::new();
+        let on_first = token.clone();
+        let handle = tokio::spawn(async move {
+            if !interrupt().await {
+                return;
+            }
+            eprintln!("Stopping transfers... (press Ctrl-C again to exit immediately)");
+            on_first.cancel();
+            if interrupt().await {
+                on_second();
+            }
+        });
+        Self {
+            token,
+            _listener: Some(Arc::new(Listener(handle))),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(test)]
+    pub fn cancel(&self) {
+        self.token.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Run `transfer`, returning `None` if it was cancelled. If it was cancelled
+    /// while in progress, the file of `partial` is deleted, if there is one yet.
+    pub async fn run<T, E>(
+        &self,
+        partial: Option<&Partial>,
+        transfer: impl Future<Output = Result<T, E>>,
+    ) -> Result<Option<T>, E> {
+        if self.token.is_cancelled() {
+            return Ok(None);
+        }
+        tokio::select! {
+            biased;
+            _ = self.token.cancelled() => {
+                self.aborted.fetch_add(1, Ordering::Relaxed);
+                if let Some(path) = partial.and_then(Partial::path) {
+                    // the file may not have been created yet
+                    let _ = fs_err::tokio::remove_file(path).await;
+                }
+                Ok(None)
+            }
+            result = transfer => {
+                if result.is_ok() {
+                    self.completed.fetch_add(1, Ordering::Relaxed);
+                }
+                result.map(Some)
+            }
+        }
+    }
+
+    /// Fails with [Interrupted] if cancelled. `total` is the number of transfers
+    /// which were supposed to happen.
+    pub fn check(&self, total: usize) -> Result<(), Interrupted> {
+        if self.is_cancelled() {
+            Err(Interrupted {
+                completed: self.completed.load(Ordering::Relaxed),
+                aborted: self.aborted.load(Ordering::Relaxed),
+                total,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camino::Utf8PathBuf;
+    use rstest::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use tokio::io::AsyncWriteExt;
+
+    /// Writes `data` to `path` one byte at a time. Files named "slow*" are written slowly.
+    async fn fake_transfer(
+        path: Utf8PathBuf,
+        data: &[u8],
+        partial: &Partial,
+    ) -> std::io::Result<()> {
+        let delay = if path.file_name().unwrap().starts_with("slow") {
+            Duration::from_secs(60)
+        } else {
+            Duration::ZERO
+        };
+        partial.writing(&path);
+        let mut file = fs_err::tokio::File::create(&path).await?;
+        for byte in data {
+            file.write_all(&[*byte]).await?;
+            file.flush().await?;
+            tokio::time::sleep(delay).await;
+        }
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_cancel_mid_transfer_deletes_partial_file() {
+        let tmp = TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        let cancellation = Cancellation::default();
+        let transfer = |name: &'static str| {
+            let cancellation = &cancellation;
+            async move {
+                let path = dir.join(name);
+                let partial = Partial::default();
+                cancellation
+                    .run(Some(&partial), fake_transfer(path, b"hello", &partial))
+                    .await
+                    .unwrap()
+            }
+        };
+        assert_eq!(transfer("fast1").await, Some(()));
+        let fast2_then_cancel = async {
+            let result = transfer("fast2").await;
+            cancellation.cancel();
+            result
+        };
+        let in_flight =
+            async { tokio::join!(transfer("slow1"), transfer("slow2"), fast2_then_cancel) };
+        let results = tokio::time::timeout(Duration::from_secs(10), in_flight)
+            .await
+            .expect("cancellation should not wait for slow transfers");
+        assert_eq!(results, (None, None, Some(())));
+        assert_eq!(transfer("fast3").await, None);
+
+        let error = cancellation.check(5).unwrap_err();
+        let expected = Interrupted {
+            completed: 2,
+            aborted: 2,
+            total: 5,
+        };
+        assert_eq!(error, expected);
+        assert_eq!(std::fs::read(dir.join("fast1")).unwrap(), b"hello");
+        assert_eq!(std::fs::read(dir.join("fast2")).unwrap(), b"hello");
+        // the slow transfers had started, their partial files are deleted
+        assert!(!dir.join("slow1").exists());
+        assert!(!dir.join("slow2").exists());
+        // fast3 was not started
+        assert!(!dir.join("fast3").exists());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_not_cancelled() {
+        let tmp = TempDir::new().unwrap();
+        let path = Utf8PathBuf::from_path_buf(tmp.path().join("fast")).unwrap();
+        let cancellation = Cancellation::default();
+        let partial = Partial::default();
+        let result = cancellation
+            .run(Some(&partial), fake_transfer(path.clone(), b"hi", &partial))
+            .await
+            .unwrap();
+        assert_eq!(result, Some(()));
+        assert!(cancellation.check(1).is_ok());
+        assert_eq!(std::fs::read(path).unwrap(), b"hi");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_cancel_before_writing_keeps_file() {
+        let tmp = TempDir::new().unwrap();
+        let path = Utf8PathBuf::from_path_buf(tmp.path().join("existing")).unwrap();
+        std::fs::write(&path, b"complete").unwrap();
+        let cancellation = Cancellation::default();
+        let partial = Partial::default();
+        // e.g. checking the size of an existing file, which is never written to
+        let inspect = async {
+            cancellation.cancel();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok::<_, ()>(())
+        };
+        let result = cancellation.run(Some(&partial), inspect).await;
+        assert_eq!(result, Ok(None));
+        assert_eq!(std::fs::read(&path).unwrap(), b"complete");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_interrupt_twice() {
+        let (tx, _) = tokio::sync::broadcast::channel::<()>(4);
+        let exits = Arc::new(AtomicUsize::new(0));
+        let listen = || {
+            let tx = tx.clone();
+            let exits = Arc::clone(&exits);
+            Cancellation::on_interrupt(
+                move || {
+                    let mut rx = tx.subscribe();
+                    async move { rx.recv().await.is_ok() }
+                },
+                move || {
+                    exits.fetch_add(1, Ordering::SeqCst);
+                },
+            )
+        };
+        let interrupt = |cancellation: &Cancellation| {
+            let cancellation = cancellation.clone();
+            let tx = tx.clone();
+            async move {
+                let transfer = cancellation.run(None, async {
+                    tx.send(()).unwrap();
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok::<_, ()>(())
+                });
+                tokio::time::timeout(Duration::from_secs(10), transfer)
+                    .await
+                    .expect("transfer should be cancelled")
+            }
+        };
+
+        let first = listen();
+        let clone = first.clone();
+        tokio::task::yield_now().await;
+        assert_eq!(interrupt(&first).await, Ok(None));
+        assert!(clone.is_cancelled());
+        drop(first);
+        drop(clone);
+
+        // the listener of the first transfer is stopped, so this interrupt
+        // cancels the second transfer instead of exiting
+        let second = listen();
+        tokio::task::yield_now().await;
+        assert_eq!(interrupt(&second).await, Ok(None));
+        assert_eq!(second.check(1).unwrap_err().aborted, 1);
+        assert_eq!(exits.load(Ordering::SeqCst), 0);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_error_is_not_counted_as_completed() {
+        let cancellation = Cancellation::default();
+        let result = cancellation
+            .run(None, async { Err::<(), _>("failed") })
+            .await;
+        assert_eq!(result, Err("failed"));
+        cancellation.cancel();
+        assert_eq!(cancellation.check(1).unwrap_err().completed, 0);
+    }
+}
@@ -0,0 +1,273 @@
+//! Progress of file transfers as newline-delimited JSON, for `--progress json`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use super::FileTransferEvent;
+
+/// Shortest time between two chunk events of the same file.
+const MIN_CHUNK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A progress event, written as one line of JSON.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonEvent<'a> {
+    Start(FileEvent<'a>),
+    Chunk(FileEvent<'a>),
+    Done(FileEvent<'a>),
+    Retry(FileEvent<'a>),
+    Message {
+        message: &'a str,
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+    },
+    Summary {
+        files: u64,
+        bytes: u64,
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+    },
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct FileEvent<'a> {
+    id: usize,
+    name: &'a str,
+    /// Bytes transferred so far
+    bytes: u64,
+    /// Size of the file
+    total: u64,
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+}
+
+/// A file being transferred.
+struct File {
+    name: String,
+    bytes: u64,
+    total: u64,
+    /// When a chunk event was last written.
+    last_chunk: Option<Instant>,
+}
+
+impl File {
+    fn event(&self, id: usize) -> FileEvent<'_> {
+        FileEvent {
+            id,
+            name: &self.name,
+            bytes: self.bytes,
+            total: self.total,
+            timestamp: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+/// Writes [FileTransferEvent] as JSON, writing at most one chunk event per file
+/// every [MIN_CHUNK_INTERVAL].
+pub(super) struct JsonProgress<W: Write> {
+    out: W,
+    files: HashMap<usize, File>,
+}
+
+impl<W: Write> JsonProgress<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            files: Default::default(),
+        }
+    }
+
+    pub fn update(&mut self, event: FileTransferEvent) {
+        self.update_at(event, Instant::now())
+    }
+
+    fn update_at(&mut self, event: FileTransferEvent, now: Instant) {
+        match event {
+            FileTransferEvent::Start { id, name, size } => {
+                let file = File {
+                    name,
+                    bytes: 0,
+                    total: size,
+                    last_chunk: None,
+                };
+                write_event(&mut self.out, &JsonEvent::Start(file.event(id)));
+                self.files.insert(id, file);
+            }
+            FileTransferEvent::Chunk { id, delta } => {
+                if let Some(file) = self.files.get_mut(&id) {
+                    file.bytes += delta;
+                    let is_due = file
+                        .last_chunk
+                        .map(|t| now.duration_since(t) >= MIN_CHUNK_INTERVAL)
+                        .unwrap_or(true);
+                    if is_due {
+                        file.last_chunk = Some(now);
+                        write_event(&mut self.out, &JsonEvent::Chunk(file.event(id)));
+                    }
+                }
+            }
+            FileTransferEvent::Done(id) => {
+                if let Some(file) = self.files.remove(&id) {
+                    write_event(&mut self.out, &JsonEvent::Done(file.event(id)));
+                }
+            }
+            FileTransferEvent::Retry { id, .. } => {
+                if let Some(file) = self.files.get_mut(&id) {
+                    file.bytes = 0;
+                    file.last_chunk = None;
+                    write_event(&mut self.out, &JsonEvent::Retry(file.event(id)));
+                }
+            }
+            FileTransferEvent::Println(message) => {
+                let event = JsonEvent::Message {
+                    message: &message,
+                    timestamp: OffsetDateTime::now_utc(),
+                };
+                write_event(&mut self.out, &event)
+            }
+            // consumers count files from the start events and the summary
+            FileTransferEvent::Total(_) | FileTransferEvent::TotalAdjusted(_) => (),
+        }
+    }
+
+    /// Write the final event, with the number of files transferred and the total
+    /// number of bytes.
+    pub fn summary(&mut self, files: u64, bytes: u64) {
+        let event = JsonEvent::Summary {
+            files,
+            bytes,
+            timestamp: OffsetDateTime::now_utc(),
+        };
+        write_event(&mut self.out, &event)
+    }
+}
+
+/// Write an event as a line. Progress is not important enough to fail the transfer,
+/// so errors are ignored.
+fn write_event(out: &mut impl Write, event: &JsonEvent) {
+    if let Ok(line) = serde_json::to_string(event) {
+        let _ = writeln!(out, "{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn events_of(output: &[u8]) -> Vec<serde_json::Value> {
+        std::str::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let mut value: serde_json::Value = serde_json::from_str(line).unwrap();
+                let timestamp = value.as_object_mut().unwrap().remove("timestamp");
+                assert!(timestamp.is_some_and(|t| t.is_string()));
+                value
+            })
+            .collect()
+    }
+
+    #[rstest]
+    fn test_json_progress() {
+        let mut output = Vec::new();
+        let mut progress = JsonProgress::new(&mut output);
+        let t0 = Instant::now();
+        let events = [
+            (
+                FileTransferEvent::Start {
+                    id: 3,
+                    name: "a.nii".to_string(),
+                    size: 30,
+                },
+                0,
+            ),
+            (FileTransferEvent::Chunk { id: 3, delta: 10 }, 0),
+            (FileTransferEvent::Chunk { id: 3, delta: 10 }, 50),
+            (FileTransferEvent::Chunk { id: 3, delta: 5 }, 100),
+            (FileTransferEvent::Chunk { id: 3, delta: 5 }, 150),
+            (FileTransferEvent::Done(3), 150),
+        ];
+        for (event, millis) in events {
+            progress.update_at(event, t0 + Duration::from_millis(millis));
+        }
+        progress.summary(1, 30);
+        let file = |t: &str, bytes: u64| serde_json::json!({"type": t, "id": 3, "name": "a.nii", "bytes": bytes, "total": 30});
+        let expected = vec![
+            file("start", 0),
+            file("chunk", 10),
+            file("chunk", 25),
+            file("done", 30),
+            serde_json::json!({"type": "summary", "files": 1, "bytes": 30}),
+        ];
+        assert_eq!(events_of(&output), expected)
+    }
+
+    #[rstest]
+    fn test_chunks_are_coalesced_per_file() {
+        let mut output = Vec::new();
+        let mut progress = JsonProgress::new(&mut output);
+        let t0 = Instant::now();
+        for id in [1, 2] {
+            let start = FileTransferEvent::Start {
+                id,
+                name: format!("{id}.txt"),
+                size: 100,
+            };
+            progress.update_at(start, t0);
+        }
+        for i in 0..10 {
+            for id in [1, 2] {
+                let chunk = FileTransferEvent::Chunk { id, delta: 10 };
+                progress.update_at(chunk, t0 + Duration::from_millis(i * 20));
+            }
+        }
+        let chunks: Vec<_> = events_of(&output)
+            .into_iter()
+            .filter(|e| e["type"] == "chunk")
+            .map(|e| (e["id"].as_u64().unwrap(), e["bytes"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(chunks, vec![(1, 10), (2, 10), (1, 60), (2, 60)])
+    }
+
+    #[rstest]
+    fn test_retry_starts_over() {
+        let mut output = Vec::new();
+        let mut progress = JsonProgress::new(&mut output);
+        let t0 = Instant::now();
+        let events = [
+            FileTransferEvent::Start {
+                id: 1,
+                name: "a.nii".to_string(),
+                size: 30,
+            },
+            FileTransferEvent::Chunk { id: 1, delta: 20 },
+            FileTransferEvent::Retry {
+                id: 1,
+                discarded: 20,
+            },
+            FileTransferEvent::Chunk { id: 1, delta: 30 },
+            FileTransferEvent::Done(1),
+        ];
+        for event in events {
+            progress.update_at(event, t0);
+        }
+        let actual: Vec<_> = events_of(&output)
+            .into_iter()
+            .map(|e| (e["type"].as_str().unwrap().to_string(), e["bytes"].as_u64()))
+            .collect();
+        let expected = [
+            ("start", 0),
+            ("chunk", 20),
+            ("retry", 0),
+            ("chunk", 30),
+            ("done", 30),
+        ]
+        .map(|(t, bytes)| (t.to_string(), Some(bytes)));
+        assert_eq!(actual, expected)
+    }
+}
@@ -0,0 +1,240 @@
+//! Manifest of the files downloaded by `chrs download --manifest`, for provenance.
+//!
+//! The manifest is written incrementally: a record is appended as each file is
+//! downloaded, and a status is written when the download ends. If chrs is killed
+//! before then, the manifest is still readable, and its status is unknown.
+
+use camino::Utf8Path;
+use fs_err::tokio::File;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
+
+use chris::types::{FeedId, PluginInstanceId};
+
+/// File name of the manifest written by `chrs download --manifest`.
+pub const MANIFEST_NAME: &str = "chrs-manifest.json";
+
+/// Closes the list of files and the object of a manifest which was not finalized.
+const UNFINISHED_END: &str = "\n  ]\n}\n";
+
+/// Where the files of a manifest were downloaded from.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestSource {
+    /// Version of chrs which downloaded the files
+    pub chrs_version: String,
+    /// _CUBE_ API URL
+    pub cube: String,
+    /// _CUBE_ path of the downloaded folder
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feed: Option<ManifestFeed>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_instance: Option<ManifestPluginInstance>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFeed {
+    pub id: FeedId,
+    /// Not known when a plugin instance was downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestPluginInstance {
+    pub id: PluginInstanceId,
+    pub title: String,
+}
+
+/// A downloaded file.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// _CUBE_ path of the file
+    pub fname: String,
+    /// Path of the downloaded file, relative to the directory containing the manifest
+    pub path: String,
+    /// File size in bytes
+    pub fsize: u64,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub creation_date: Option<OffsetDateTime>,
+}
+
+/// How a download ended.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestStatus {
+    /// All files were downloaded
+    Complete,
+    /// Some files could not be downloaded
+    Failed,
+    /// The download was interrupted by Ctrl-C
+    Interrupted,
+}
+
+/// Contents of a manifest file.
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Manifest {
+    #[serde(flatten)]
+    pub source: ManifestSource,
+    pub files: Vec<ManifestFile>,
+    /// `None` if chrs stopped before finishing the manifest
+    #[serde(default)]
+    pub status: Option<ManifestStatus>,
+    /// Size of the files which were linked instead of downloaded by `--dedupe`
+    #[serde(default)]
+    pub deduplicated_bytes: u64,
+}
+
+impl Manifest {
+    /// Parse a manifest, including one which was not finished by [ManifestWriter::finish].
+    pub fn parse(text: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(text).or_else(|e| {
+            if e.is_eof() {
+                serde_json::from_str(&format!("{}{}", text.trim_end(), UNFINISHED_END))
+            } else {
+                Err(e)
+            }
+        })
+    }
+}
+
+/// Writes a manifest one file at a time.
+pub struct ManifestWriter {
+    file: File,
+    is_empty: bool,
+    deduplicated_bytes: u64,
+}
+
+impl ManifestWriter {
+    /// Create the manifest file `path` and write `source` to it.
+    pub async fn create(path: &Utf8Path, source: &ManifestSource) -> std::io::Result<Self> {
+        let mut file = File::create(path).await?;
+        let header = serde_json::to_string_pretty(source)?;
+        // the object is left open so that files can be appended to it
+        let header = header.strip_suffix("\n}").unwrap_or(&header);
+        file.write_all(format!("{},\n  \"files\": [", header).as_bytes())
+            .await?;
+        file.flush().await?;
+        Ok(Self {
+            file,
+            is_empty: true,
+            deduplicated_bytes: 0,
+        })
+    }
+
+    /// Append a downloaded file to the manifest.
+    pub async fn append(&mut self, record: &ManifestFile) -> std::io::Result<()> {
+        let sep = if self.is_empty { "" } else { "," };
+        let line = format!("{}\n    {}", sep, serde_json::to_string(record)?);
+        self.file.write_all(line.as_bytes()).await?;
+        self.file.flush().await?;
+        self.is_empty = false;
+        Ok(())
+    }
+
+    /// Record the size of files which were linked instead of downloaded by `--dedupe`.
+    pub fn set_deduplicated_bytes(&mut self, bytes: u64) {
+        self.deduplicated_bytes = bytes;
+    }
+
+    /// Finish the manifest with the status of the download.
+    pub async fn finish(mut self, status: ManifestStatus) -> std::io::Result<()> {
+        let deduplicated = if self.deduplicated_bytes > 0 {
+            format!(",\n  \"deduplicated_bytes\": {}", self.deduplicated_bytes)
+        } else {
+            String::new()
+        };
+        let end = format!(
+            "\n  ],\n  \"status\": {}{}\n}}\n",
+            serde_json::to_string(&status)?,
+            deduplicated
+        );
+        self.file.write_all(end.as_bytes()).await?;
+        self.file.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use tempfile::TempDir;
+    use time::macros::datetime;
+
+    #[fixture]
+    fn source() -> ManifestSource {
+        ManifestSource {
+            chrs_version: "1.2.3".to_string(),
+            cube: "https://cube.example.com/api/v1/".to_string(),
+            path: "chris/feed_7".to_string(),
+            feed: Some(ManifestFeed {
+                id: FeedId(7),
+                name: Some("My Study".to_string()),
+            }),
+            plugin_instance: None,
+        }
+    }
+
+    fn record(n: u64) -> ManifestFile {
+        ManifestFile {
+            fname: format!("chris/feed_7/pl-dircopy_8/data/{n}.txt"),
+            path: format!("pl-dircopy/{n}.txt"),
+            fsize: n,
+            creation_date: Some(datetime!(2024-05-01 12:00 UTC)),
+        }
+    }
+
+    #[rstest]
+    #[case(0, Some(ManifestStatus::Complete))]
+    #[case(2, Some(ManifestStatus::Failed))]
+    #[case(0, None)]
+    #[case(3, None)]
+    #[tokio::test]
+    async fn test_write_and_parse(
+        source: ManifestSource,
+        #[case] count: u64,
+        #[case] status: Option<ManifestStatus>,
+    ) {
+        let tmp = TempDir::new().unwrap();
+        let path = Utf8Path::from_path(tmp.path()).unwrap().join(MANIFEST_NAME);
+        let mut writer = ManifestWriter::create(&path, &source).await.unwrap();
+        for n in 1..=count {
+            writer.append(&record(n)).await.unwrap();
+        }
+        if let Some(status) = status {
+            writer.finish(status).await.unwrap();
+        } else {
+            drop(writer);
+        }
+        let text = fs_err::read_to_string(&path).unwrap();
+        if status.is_some() {
+            serde_json::from_str::<serde_json::Value>(&text).unwrap();
+        }
+        let expected = Manifest {
+            source,
+            files: (1..=count).map(record).collect(),
+            status,
+            deduplicated_bytes: 0,
+        };
+        assert_eq!(Manifest::parse(&text).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_deduplicated_bytes(source: ManifestSource) {
+        let tmp = TempDir::new().unwrap();
+        let path = Utf8Path::from_path(tmp.path()).unwrap().join(MANIFEST_NAME);
+        let mut writer = ManifestWriter::create(&path, &source).await.unwrap();
+        writer.append(&record(1)).await.unwrap();
+        writer.set_deduplicated_bytes(42);
+        writer.finish(ManifestStatus::Complete).await.unwrap();
+        let text = fs_err::read_to_string(&path).unwrap();
+        assert_eq!(Manifest::parse(&text).unwrap().deduplicated_bytes, 42);
+    }
+
+    #[rstest]
+    fn test_parse_invalid() {
+        assert!(Manifest::parse("{\"files\": 5}").is_err())
+    }
+}
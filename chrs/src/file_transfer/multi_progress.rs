@@ -1,5 +1,13 @@
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
 use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::time::Instant;
+
+use super::json_progress::JsonProgress;
+use super::totals::TransferTotals;
+
+/// Maximum number of bars of files when the height of the terminal is unknown.
+const DEFAULT_MAX_BARS: usize = 10;
 
 /// File transfer event.
 #[derive(Debug)]
@@ -10,74 +18,296 @@ pub enum FileTransferEvent {
     Chunk { id: usize, delta: u64 },
     /// File transfer done
     Done(usize),
+    /// The transfer failed and starts over, discarding the *N* bytes already transferred
+    Retry { id: usize, discarded: u64 },
+    /// Print a message above the progress bars
+    Println(String),
+    /// The number of files to transfer, if it was not known at the start
+    Total(u64),
+    /// The expected total size of all files changed by *N* bytes. Once given, the total size
+    /// is the sum of these adjustments instead of the sizes of started transfers, so that it
+    /// is known before every transfer starts.
+    TotalAdjusted(i64),
 }
 
-/// A [MultiProgress] wrapper for showing the upload or download progress of multiple files.
+/// How the progress of file transfers is shown, selected by `--progress`.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq)]
+pub enum ProgressFormat {
+    /// Progress bars
+    Bars,
+    /// Newline-delimited JSON events
+    Json,
+    /// Only a summary after all transfers are done
+    Quiet,
+}
+
+impl ProgressFormat {
+    /// The format given by `--progress`, otherwise progress bars if stderr is a terminal,
+    /// or else quiet.
+    pub fn or_default(given: Option<Self>) -> Self {
+        given.unwrap_or_else(|| {
+            if std::io::stderr().is_terminal() {
+                Self::Bars
+            } else {
+                Self::Quiet
+            }
+        })
+    }
+}
+
+/// Shows the upload or download progress of multiple files, in a [ProgressFormat].
 pub struct MultiFileTransferProgress {
+    display: Display,
+    totals: TransferTotals,
+}
+
+enum Display {
+    Bars(Bars),
+    Json(JsonProgress<std::io::Stderr>),
+    Quiet,
+}
+
+/// A [MultiProgress] with an overall bar of bytes, and a bar for each file which is large,
+/// up to `max_bars` of them. Files without a bar because of `max_bars` are counted on a line
+/// below the bars, and get a bar when another bar is done.
+struct Bars {
     multi_progress: MultiProgress,
     overall_bar: ProgressBar,
     bars: HashMap<usize, ProgressBar>,
+    /// Files which are large but do not have a bar, in order of when they started
+    waiting: Vec<(usize, WaitingFile)>,
+    /// The "and N more" line, while any file is waiting
+    more_line: Option<ProgressBar>,
     size_threshold: u64,
-    total_size: u64,
+    max_bars: usize,
+}
+
+struct WaitingFile {
+    name: String,
+    size: u64,
+    position: u64,
 }
 
 impl MultiFileTransferProgress {
-    /// Create a new multi-progress bar.
-    pub fn new(total_files: u64, size_threshold: u64) -> Self {
-        let multi_progress = MultiProgress::new();
-        let overall_bar =
-            multi_progress.add(ProgressBar::new(total_files).with_style(overall_style()));
+    /// Create a new multi-progress bar. If `total_files` is not known yet, files are
+    /// counted until it is given by [FileTransferEvent::Total].
+    ///
+    /// Files of at least `size_threshold` bytes are shown with their own bar, as many as fit
+    /// in the terminal.
+    pub fn new(total_files: Option<u64>, size_threshold: u64, format: ProgressFormat) -> Self {
+        let display = match format {
+            ProgressFormat::Bars => {
+                let multi_progress = MultiProgress::new();
+                let max_bars = console::Term::stderr()
+                    .size_checked()
+                    // a line each for the overall bar, the "and N more" line, and the cursor
+                    .map(|(rows, _)| (rows as usize).saturating_sub(3).max(1))
+                    .unwrap_or(DEFAULT_MAX_BARS);
+                Display::Bars(Bars::new(multi_progress, size_threshold, max_bars))
+            }
+            ProgressFormat::Json => Display::Json(JsonProgress::new(std::io::stderr())),
+            ProgressFormat::Quiet => Display::Quiet,
+        };
+        Self {
+            display,
+            totals: TransferTotals::new(total_files),
+        }
+    }
+
+    /// Update this with an event.
+    pub fn update(&mut self, event: FileTransferEvent) {
+        self.totals.update(&event);
+        match &mut self.display {
+            Display::Bars(bars) => {
+                bars.update(event);
+                bars.show_totals(&self.totals, Instant::now());
+            }
+            Display::Json(json) => json.update(event),
+            Display::Quiet => {
+                if let FileTransferEvent::Println(msg) = event {
+                    eprintln!("{}", msg)
+                }
+            }
+        }
+    }
+
+    /// Remove the bars of transfers which did not finish, e.g. because they were
+    /// interrupted, and stop drawing. Without bars, a summary is printed.
+    pub fn finish(&mut self) {
+        let done_files = self.totals.done_files();
+        let transferred = self.totals.transferred();
+        match &mut self.display {
+            Display::Bars(bars) => bars.finish(),
+            Display::Json(json) => json.summary(done_files, transferred),
+            Display::Quiet => match self.totals.total_files() {
+                Some(total) => eprintln!(
+                    "Transferred {} of {} files ({})",
+                    done_files,
+                    total,
+                    HumanBytes(transferred)
+                ),
+                None => eprintln!(
+                    "Transferred {} files ({})",
+                    done_files,
+                    HumanBytes(transferred)
+                ),
+            },
+        }
+    }
+
+    /// Get the total size of all (attempted) transfers.
+    pub fn total_size(&self) -> u64 {
+        self.totals.started_size()
+    }
+}
+
+impl Bars {
+    fn new(multi_progress: MultiProgress, size_threshold: u64, max_bars: usize) -> Self {
+        let overall_bar = multi_progress.add(ProgressBar::new(0).with_style(overall_style()));
         Self {
             multi_progress,
             overall_bar,
             bars: Default::default(),
+            waiting: Default::default(),
+            more_line: None,
             size_threshold,
-            total_size: 0,
+            max_bars,
         }
     }
 
-    /// Update this with an event.
-    pub fn update(&mut self, event: FileTransferEvent) {
+    fn update(&mut self, event: FileTransferEvent) {
         match event {
             FileTransferEvent::Start { id, name, size } => self.add_file(id, name, size),
             FileTransferEvent::Chunk { id, delta } => self.on_chunk(id, delta),
             FileTransferEvent::Done(id) => self.finish_one(id),
-            // FileTransferEvent::Println(msg) => self.println(msg)
+            FileTransferEvent::Retry { id, .. } => self.restart(id),
+            FileTransferEvent::Println(msg) => self.println(msg),
+            FileTransferEvent::Total(_) | FileTransferEvent::TotalAdjusted(_) => (),
         }
     }
 
+    /// Show the bytes and files of `totals` on the overall bar.
+    fn show_totals(&self, totals: &TransferTotals, now: Instant) {
+        self.overall_bar.set_length(totals.total_size());
+        self.overall_bar.set_position(totals.transferred());
+        let files = match totals.total_files() {
+            Some(total) => format!("{}/{} Files", totals.done_files(), total),
+            None => format!("{} Files, looking for more...", totals.done_files()),
+        };
+        let throughput = totals
+            .throughput(now)
+            .map(|t| format!(" @ {}/s", HumanBytes(t as u64)))
+            .unwrap_or_default();
+        let eta = totals
+            .eta(now)
+            .map(|eta| format!(", ETA {}", HumanDuration(eta)))
+            .unwrap_or_default();
+        self.overall_bar
+            .set_message(format!("{}{}{}", files, throughput, eta));
+    }
+
     fn add_file(&mut self, id: usize, name: String, size: u64) {
-        self.total_size += size;
-        if size >= self.size_threshold {
-            let bar = ProgressBar::new(size)
-                .with_style(file_style())
-                .with_prefix(name);
-            self.bars.insert(id, self.multi_progress.add(bar));
+        if size < self.size_threshold {
+            return;
+        }
+        let file = WaitingFile {
+            name,
+            size,
+            position: 0,
+        };
+        if self.bars.len() < self.max_bars {
+            self.show_file(id, file);
+        } else {
+            self.waiting.push((id, file));
+            self.update_more_line();
+        }
+    }
+
+    /// Add the bar of a file, above the "and N more" line.
+    fn show_file(&mut self, id: usize, file: WaitingFile) {
+        let bar = ProgressBar::new(file.size)
+            .with_style(file_style())
+            .with_prefix(file.name)
+            .with_position(file.position);
+        let bar = match &self.more_line {
+            Some(more_line) => self.multi_progress.insert_before(more_line, bar),
+            None => self.multi_progress.add(bar),
+        };
+        self.bars.insert(id, bar);
+    }
+
+    fn update_more_line(&mut self) {
+        if self.waiting.is_empty() {
+            if let Some(more_line) = self.more_line.take() {
+                self.multi_progress.remove(&more_line);
+            }
+            return;
+        }
+        let message = format!("... and {} more", self.waiting.len());
+        match &self.more_line {
+            Some(more_line) => more_line.set_message(message),
+            None => {
+                let more_line = ProgressBar::new_spinner()
+                    .with_style(more_style())
+                    .with_message(message);
+                self.more_line = Some(self.multi_progress.add(more_line));
+            }
         }
     }
 
-    fn on_chunk(&self, id: usize, delta: u64) {
+    fn waiting_file(&mut self, id: usize) -> Option<&mut WaitingFile> {
+        self.waiting
+            .iter_mut()
+            .find(|(waiting_id, _)| *waiting_id == id)
+            .map(|(_, file)| file)
+    }
+
+    fn on_chunk(&mut self, id: usize, delta: u64) {
         if let Some(bar) = self.bars.get(&id) {
             bar.inc(delta)
+        } else if let Some(file) = self.waiting_file(id) {
+            file.position += delta
+        }
+    }
+
+    fn restart(&mut self, id: usize) {
+        if let Some(bar) = self.bars.get(&id) {
+            bar.reset()
+        } else if let Some(file) = self.waiting_file(id) {
+            file.position = 0
         }
     }
 
     fn finish_one(&mut self, id: usize) {
         if let Some(bar) = self.bars.remove(&id) {
             self.multi_progress.remove(&bar);
+            if !self.waiting.is_empty() {
+                let (next_id, next) = self.waiting.remove(0);
+                self.show_file(next_id, next);
+            }
+        } else {
+            self.waiting.retain(|(waiting_id, _)| *waiting_id != id);
         }
-        self.overall_bar.inc(1);
+        self.update_more_line();
     }
 
-    /// Get the total size of all (attempted) transfers.
-    pub fn total_size(&self) -> u64 {
-        self.total_size
+    fn println(&self, msg: String) {
+        self.multi_progress.println(msg).unwrap()
+    }
+
+    fn finish(&mut self) {
+        for (_, bar) in self.bars.drain() {
+            self.multi_progress.remove(&bar);
+        }
+        self.waiting.clear();
+        self.update_more_line();
+        self.overall_bar.abandon();
     }
 }
 
 fn overall_style() -> ProgressStyle {
     ProgressStyle::default_bar()
-        .template("[{elapsed_precise}] {wide_bar} {human_pos}/{human_len} Files, ETA {eta}")
+        .template("[{elapsed_precise}] {wide_bar} {bytes}/{total_bytes}, {msg}")
         .unwrap()
 }
 
@@ -86,3 +316,54 @@ fn file_style() -> ProgressStyle {
         .template("{prefix} {wide_bar} {bytes}/{total_bytes} @ {bytes_per_sec}")
         .unwrap()
 }
+
+fn more_style() -> ProgressStyle {
+    ProgressStyle::default_spinner().template("{msg}").unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicatif::ProgressDrawTarget;
+    use rstest::*;
+
+    fn start(id: usize, size: u64) -> FileTransferEvent {
+        FileTransferEvent::Start {
+            id,
+            name: format!("{id}.nii"),
+            size,
+        }
+    }
+
+    #[rstest]
+    fn test_bars_are_capped() {
+        let multi_progress = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+        let mut bars = Bars::new(multi_progress, 100, 2);
+        for id in 0..4 {
+            bars.update(start(id, 100));
+        }
+        bars.update(start(4, 10));
+        assert_eq!(bars.bars.len(), 2);
+        assert_eq!(
+            bars.waiting.len(),
+            2,
+            "small files should not wait for a bar"
+        );
+        let more_line = bars.more_line.as_ref().unwrap();
+        assert_eq!(more_line.message(), "... and 2 more");
+
+        bars.update(FileTransferEvent::Chunk { id: 2, delta: 30 });
+        bars.update(FileTransferEvent::Done(0));
+        assert_eq!(
+            bars.bars[&2].position(),
+            30,
+            "waiting file should keep its progress"
+        );
+        assert_eq!(bars.waiting.len(), 1);
+
+        bars.update(FileTransferEvent::Done(3));
+        assert!(bars.waiting.is_empty());
+        assert!(bars.more_line.is_none());
+        assert_eq!(bars.bars.len(), 2);
+    }
+}
@@ -0,0 +1,251 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use filetime::FileTime;
+use futures::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+/// File name of the sidecar written by `chrs upload --preserve-times`.
+pub const TIMES_SIDECAR_NAME: &str = ".chrs_times.json";
+
+/// Modification times of uploaded files, saved as a sidecar file next to the uploaded data
+/// so that they can be restored by `chrs download --restore-times`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimesSidecar {
+    pub files: Vec<RecordedTime>,
+}
+
+/// Modification time and size of a file.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedTime {
+    /// Path relative to the directory containing the sidecar
+    pub path: String,
+    /// Seconds since the UNIX epoch
+    pub mtime: i64,
+    /// Nanoseconds part of `mtime`
+    #[serde(default)]
+    pub mtime_nanos: u32,
+    /// File size in bytes
+    pub size: u64,
+}
+
+/// Summary of [TimesSidecar::restore].
+#[derive(Debug, Default, PartialEq)]
+pub struct RestoredTimes {
+    /// Number of files which had their modification times restored
+    pub restored: usize,
+    /// Number of files skipped because their size is different from what was recorded
+    pub size_mismatch: usize,
+    /// Number of recorded files which do not exist locally
+    pub missing: usize,
+}
+
+impl RestoredTimes {
+    fn add(mut self, other: Self) -> Self {
+        self.restored += other.restored;
+        self.size_mismatch += other.size_mismatch;
+        self.missing += other.missing;
+        self
+    }
+}
+
+impl TimesSidecar {
+    /// Record the modification time and size of a file from its metadata.
+    /// `path` is relative to the directory of the sidecar.
+    pub fn push(&mut self, path: String, metadata: &std::fs::Metadata) {
+        let mtime = FileTime::from_last_modification_time(metadata);
+        self.files.push(RecordedTime {
+            path,
+            mtime: mtime.unix_seconds(),
+            mtime_nanos: mtime.nanoseconds(),
+            size: metadata.len(),
+        });
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self)
+    }
+
+    pub fn from_json(data: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(data)
+    }
+
+    /// Apply the recorded modification times to the files in `dir`, which is the directory
+    /// containing the sidecar file. Files whose size is different from what was recorded
+    /// are skipped.
+    pub async fn restore(&self, dir: &Utf8Path) -> std::io::Result<RestoredTimes> {
+        let mut summary = RestoredTimes::default();
+        for recorded in &self.files {
+            let path = dir.join(&recorded.path);
+            let metadata = match fs_err::tokio::metadata(&path).await {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    summary.missing += 1;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if metadata.len() != recorded.size {
+                summary.size_mismatch += 1;
+                continue;
+            }
+            let mtime = FileTime::from_unix_time(recorded.mtime, recorded.mtime_nanos);
+            let path = path.into_std_path_buf();
+            tokio::task::spawn_blocking(move || filetime::set_file_mtime(path, mtime))
+                .await
+                .expect("set_file_mtime should not panic")?;
+            summary.restored += 1;
+        }
+        Ok(summary)
+    }
+}
+
+/// Find every [TimesSidecar] under `root` and restore the modification times they recorded.
+pub async fn restore_times_under(root: &Utf8Path) -> std::io::Result<RestoredTimes> {
+    let sidecars: Vec<Utf8PathBuf> = async_walkdir::WalkDir::new(root)
+        .try_filter_map(|entry| async move {
+            let is_sidecar = entry.file_name() == TIMES_SIDECAR_NAME;
+            let path = Utf8PathBuf::from_path_buf(entry.path()).ok();
+            Ok(path.filter(|_| is_sidecar))
+        })
+        .try_collect()
+        .await?;
+    futures::stream::iter(sidecars)
+        .then(|sidecar| async move {
+            let data = fs_err::tokio::read(&sidecar).await?;
+            let times = TimesSidecar::from_json(&data)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            times.restore(sidecar.parent().unwrap_or(root)).await
+        })
+        .try_fold(RestoredTimes::default(), |acc, summary| async move {
+            Ok(acc.add(summary))
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    fn system_time_of(recorded: &RecordedTime) -> SystemTime {
+        UNIX_EPOCH + Duration::new(recorded.mtime as u64, recorded.mtime_nanos)
+    }
+
+    #[fixture]
+    fn tmp_dir() -> TempDir {
+        TempDir::new().unwrap()
+    }
+
+    fn utf8(dir: &TempDir) -> &Utf8Path {
+        Utf8Path::from_path(dir.path()).unwrap()
+    }
+
+    async fn write_with_mtime(path: &Utf8Path, content: &str, secs: i64) {
+        fs_err::tokio::create_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+        fs_err::tokio::write(path, content).await.unwrap();
+        filetime::set_file_mtime(path, FileTime::from_unix_time(secs, 0)).unwrap();
+    }
+
+    async fn mtime_of(path: &Utf8Path) -> SystemTime {
+        fs_err::tokio::metadata(path)
+            .await
+            .unwrap()
+            .modified()
+            .unwrap()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_record_and_restore(tmp_dir: TempDir) {
+        let src = utf8(&tmp_dir).join("src");
+        write_with_mtime(&src.join("a.dcm"), "first", 1_000_000_000).await;
+        write_with_mtime(&src.join("sub/b.dcm"), "second", 1_100_000_000).await;
+        let a = src.join("a.dcm");
+        let b = src.join("sub/b.dcm");
+        let mut sidecar = TimesSidecar::default();
+        for (path, local) in [("a.dcm", a), ("sub/b.dcm", b)] {
+            let metadata = fs_err::tokio::metadata(local).await.unwrap();
+            sidecar.push(path.to_string(), &metadata);
+        }
+        assert_eq!(sidecar.files[1].size, 6);
+
+        // simulate download: same content, new mtimes, sidecar alongside the data
+        let dst = utf8(&tmp_dir).join("dst");
+        write_with_mtime(&dst.join("a.dcm"), "first", 1_700_000_000).await;
+        write_with_mtime(&dst.join("sub/b.dcm"), "second", 1_700_000_000).await;
+        fs_err::tokio::write(dst.join(TIMES_SIDECAR_NAME), sidecar.to_json().unwrap())
+            .await
+            .unwrap();
+
+        let summary = restore_times_under(&dst).await.unwrap();
+        assert_eq!(
+            summary,
+            RestoredTimes {
+                restored: 2,
+                size_mismatch: 0,
+                missing: 0
+            }
+        );
+        assert_eq!(
+            mtime_of(&dst.join("a.dcm")).await,
+            system_time_of(&sidecar.files[0])
+        );
+        assert_eq!(
+            mtime_of(&dst.join("sub/b.dcm")).await,
+            system_time_of(&sidecar.files[1])
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_restore_skips_size_mismatch(tmp_dir: TempDir) {
+        let dir = utf8(&tmp_dir);
+        write_with_mtime(&dir.join("changed.txt"), "changed content", 1_700_000_000).await;
+        let sidecar = TimesSidecar {
+            files: vec![
+                RecordedTime {
+                    path: "changed.txt".to_string(),
+                    mtime: 1_000_000_000,
+                    mtime_nanos: 0,
+                    size: 3,
+                },
+                RecordedTime {
+                    path: "gone.txt".to_string(),
+                    mtime: 1_000_000_000,
+                    mtime_nanos: 0,
+                    size: 3,
+                },
+            ],
+        };
+        let summary = sidecar.restore(dir).await.unwrap();
+        assert_eq!(
+            summary,
+            RestoredTimes {
+                restored: 0,
+                size_mismatch: 1,
+                missing: 1
+            }
+        );
+        assert_eq!(
+            mtime_of(&dir.join("changed.txt")).await,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        );
+    }
+
+    #[rstest]
+    fn test_sidecar_json_round_trip() {
+        let sidecar = TimesSidecar {
+            files: vec![RecordedTime {
+                path: "a/b.nii".to_string(),
+                mtime: 1_234_567_890,
+                mtime_nanos: 42,
+                size: 1024,
+            }],
+        };
+        let json = sidecar.to_json().unwrap();
+        assert_eq!(TimesSidecar::from_json(&json).unwrap(), sidecar);
+    }
+}
@@ -0,0 +1,253 @@
+//! Overall progress of file transfers, in bytes and files.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::FileTransferEvent;
+
+/// Duration of the sliding window over which throughput is measured.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Shortest time between two samples of the throughput window.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Counts the files and bytes of [FileTransferEvent]s, and measures throughput.
+///
+/// The total size is the sum of the sizes of started transfers, unless it is given ahead
+/// of time by [FileTransferEvent::TotalAdjusted].
+#[derive(Default)]
+pub(super) struct TransferTotals {
+    total_files: Option<u64>,
+    started_files: u64,
+    done_files: u64,
+    /// Sum of the sizes of started transfers
+    started_size: u64,
+    /// Sum of [FileTransferEvent::TotalAdjusted], if any
+    expected_size: Option<i64>,
+    /// Bytes transferred, less the bytes discarded by retries
+    transferred: u64,
+    /// Bytes transferred including the bytes discarded by retries, which never decreases
+    moved: u64,
+    /// Samples of `(time, moved)`, oldest first
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl TransferTotals {
+    pub fn new(total_files: Option<u64>) -> Self {
+        Self {
+            total_files,
+            ..Default::default()
+        }
+    }
+
+    pub fn update(&mut self, event: &FileTransferEvent) {
+        self.update_at(event, Instant::now())
+    }
+
+    pub fn update_at(&mut self, event: &FileTransferEvent, now: Instant) {
+        match event {
+            FileTransferEvent::Start { size, .. } => {
+                self.started_files += 1;
+                self.started_size += size;
+            }
+            FileTransferEvent::Chunk { delta, .. } => {
+                self.transferred += delta;
+                self.moved += delta;
+                self.sample(now);
+            }
+            FileTransferEvent::Done(_) => self.done_files += 1,
+            FileTransferEvent::Retry { discarded, .. } => {
+                self.transferred = self.transferred.saturating_sub(*discarded)
+            }
+            FileTransferEvent::Println(_) => (),
+            FileTransferEvent::Total(total) => self.total_files = Some(*total),
+            FileTransferEvent::TotalAdjusted(delta) => {
+                *self.expected_size.get_or_insert(0) += delta
+            }
+        }
+    }
+
+    /// Record the bytes moved at `now`, and forget samples which are out of the window.
+    fn sample(&mut self, now: Instant) {
+        let is_due = self
+            .samples
+            .back()
+            .map(|(t, _)| now.saturating_duration_since(*t) >= SAMPLE_INTERVAL)
+            .unwrap_or(true);
+        if is_due {
+            self.samples.push_back((now, self.moved));
+        }
+        // the oldest sample in the window is kept to measure from
+        while self.samples.len() > 1
+            && now.saturating_duration_since(self.samples[1].0) >= THROUGHPUT_WINDOW
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn total_files(&self) -> Option<u64> {
+        self.total_files
+    }
+
+    pub fn done_files(&self) -> u64 {
+        self.done_files
+    }
+
+    pub fn transferred(&self) -> u64 {
+        self.transferred
+    }
+
+    /// Get the total size of all (attempted) transfers.
+    pub fn started_size(&self) -> u64 {
+        self.started_size
+    }
+
+    /// Total size of the files to transfer, as far as it is known.
+    pub fn total_size(&self) -> u64 {
+        let total = match self.expected_size {
+            Some(expected) => expected.max(0) as u64,
+            None => self.started_size,
+        };
+        total.max(self.transferred)
+    }
+
+    /// Whether [Self::total_size] includes every file which will be transferred.
+    pub fn is_total_size_known(&self) -> bool {
+        self.total_files
+            .is_some_and(|total| self.expected_size.is_some() || self.started_files >= total)
+    }
+
+    /// Bytes per second transferred over the sliding window ending at `now`.
+    pub fn throughput(&self, now: Instant) -> Option<f64> {
+        let (since, moved) = self.samples.front()?;
+        let elapsed = now.saturating_duration_since(*since).as_secs_f64();
+        (elapsed > 0.0).then(|| (self.moved - moved) as f64 / elapsed)
+    }
+
+    /// Estimated time until all bytes are transferred, if the total size is known
+    /// and bytes are being transferred.
+    pub fn eta(&self, now: Instant) -> Option<Duration> {
+        if !self.is_total_size_known() {
+            return None;
+        }
+        let throughput = self.throughput(now).filter(|t| *t > 0.0)?;
+        let remaining = self.total_size() - self.transferred;
+        Some(Duration::from_secs_f64(remaining as f64 / throughput))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn start(id: usize, size: u64) -> FileTransferEvent {
+        FileTransferEvent::Start {
+            id,
+            name: format!("{id}.nii"),
+            size,
+        }
+    }
+
+    fn chunk(id: usize, delta: u64) -> FileTransferEvent {
+        FileTransferEvent::Chunk { id, delta }
+    }
+
+    #[rstest]
+    fn test_large_file_dominates() {
+        let mut totals = TransferTotals::new(Some(3));
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+        let gib = 1 << 30;
+        let script = [
+            (start(0, 5 * gib), 0),
+            (start(1, 10), 0),
+            (chunk(1, 10), 0),
+            (FileTransferEvent::Done(1), 0),
+            (chunk(0, gib), 1),
+            (start(2, 10), 1),
+            (chunk(2, 10), 1),
+            (FileTransferEvent::Done(2), 1),
+            (chunk(0, gib), 2),
+        ];
+        for (event, secs) in script {
+            totals.update_at(&event, at(secs));
+        }
+        assert_eq!(totals.done_files(), 2);
+        assert_eq!(totals.total_size(), 5 * gib + 20);
+        assert_eq!(totals.transferred(), 2 * gib + 20);
+        assert!(totals.is_total_size_known());
+        // about 1 GiB per second since the first chunk
+        let throughput = totals.throughput(at(2)).unwrap();
+        assert_eq!(throughput, (2 * gib + 10) as f64 / 2.0);
+        let eta = totals.eta(at(2)).unwrap();
+        assert_eq!(
+            eta.as_secs_f64().round(),
+            3.0,
+            "3 GiB remain at about 1 GiB/s"
+        );
+    }
+
+    #[rstest]
+    fn test_total_adjusted_ahead_of_starts() {
+        let mut totals = TransferTotals::new(None);
+        let t0 = Instant::now();
+        for (size, id) in [(100, 0), (300, 1)] {
+            totals.update_at(&FileTransferEvent::TotalAdjusted(size), t0);
+            totals.update_at(&start(id, size as u64), t0);
+        }
+        totals.update_at(&FileTransferEvent::TotalAdjusted(600), t0);
+        assert_eq!(totals.total_size(), 1000);
+        assert!(!totals.is_total_size_known(), "more files may be found");
+        totals.update_at(&chunk(0, 100), t0 + Duration::from_secs(1));
+        assert_eq!(totals.eta(t0 + Duration::from_secs(1)), None);
+        totals.update_at(&FileTransferEvent::Total(3), t0);
+        assert!(totals.is_total_size_known());
+
+        // a file is skipped, so its size is taken back
+        totals.update_at(&FileTransferEvent::TotalAdjusted(-600), t0);
+        assert_eq!(totals.total_size(), 400);
+    }
+
+    #[rstest]
+    fn test_retry_is_not_progress_but_is_throughput() {
+        let mut totals = TransferTotals::new(Some(1));
+        let t0 = Instant::now();
+        let script = [
+            (start(0, 100), 0),
+            (chunk(0, 40), 0),
+            (
+                FileTransferEvent::Retry {
+                    id: 0,
+                    discarded: 40,
+                },
+                1,
+            ),
+            (chunk(0, 40), 2),
+        ];
+        for (event, secs) in script {
+            totals.update_at(&event, t0 + Duration::from_secs(secs));
+        }
+        assert_eq!(totals.transferred(), 40);
+        assert_eq!(totals.throughput(t0 + Duration::from_secs(2)), Some(20.0));
+        assert_eq!(
+            totals.eta(t0 + Duration::from_secs(2)),
+            Some(Duration::from_secs(3))
+        );
+    }
+
+    #[rstest]
+    fn test_throughput_window_slides() {
+        let mut totals = TransferTotals::new(Some(1));
+        let t0 = Instant::now();
+        totals.update_at(&start(0, 1000), t0);
+        // fast at first, then slow
+        totals.update_at(&chunk(0, 500), t0);
+        for secs in 1..=20 {
+            totals.update_at(&chunk(0, 10), t0 + Duration::from_secs(secs));
+        }
+        let now = t0 + Duration::from_secs(20);
+        assert_eq!(totals.throughput(now), Some(10.0));
+        assert_eq!(totals.samples.len(), 11);
+    }
+}
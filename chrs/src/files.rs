@@ -1,5 +1,7 @@
 mod channel;
 mod decoder;
+mod filter;
 
 pub use channel::CoderChannel;
-pub use decoder::MaybeChrisPathHumanCoder;
+pub(crate) use decoder::{ChrisPathHumanCoder, MaybeChrisPathHumanCoder};
+pub use filter::{FileFilter, FilterArgs};
@@ -4,25 +4,31 @@ use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 /// A channel for communicating with [MaybeChrisPathHumanCoder] in async contexts.
 pub struct CoderChannel {
-    tx_fname: UnboundedSender<String>,
-    rx_decoded: UnboundedReceiver<String>,
+    tx_fnames: UnboundedSender<Vec<String>>,
+    rx_decoded: UnboundedReceiver<Vec<String>>,
 }
 
 impl CoderChannel {
     pub fn create(coder: MaybeChrisPathHumanCoder) -> (Self, impl Future<Output = ()> + '_) {
-        let (tx_fname, rx_fname) = unbounded_channel();
+        let (tx_fnames, rx_fnames) = unbounded_channel();
         let (tx_decoded, rx_decoded) = unbounded_channel();
         let decoder_channel = Self {
-            tx_fname,
+            tx_fnames,
             rx_decoded,
         };
-        let decoder_loop = loop_decoder(coder, rx_fname, tx_decoded);
+        let decoder_loop = loop_decoder(coder, rx_fnames, tx_decoded);
         (decoder_channel, decoder_loop)
     }
 
     /// Calls [MaybeChrisPathHumanCoder::decode]
     pub async fn decode(&mut self, fname: String) -> String {
-        self.tx_fname.send(fname).unwrap();
+        self.decode_all(vec![fname]).await.pop().unwrap()
+    }
+
+    /// Calls [MaybeChrisPathHumanCoder::decode_all], which looks up the names
+    /// of all the folders of `fnames` together.
+    pub async fn decode_all(&mut self, fnames: Vec<String>) -> Vec<String> {
+        self.tx_fnames.send(fnames).unwrap();
         self.rx_decoded.recv().await.unwrap()
     }
 }
@@ -30,10 +36,11 @@ impl CoderChannel {
 #[allow(clippy::needless_lifetimes)]
 async fn loop_decoder<'a>(
     mut coder: MaybeChrisPathHumanCoder<'a>,
-    mut rx: UnboundedReceiver<String>,
-    tx: UnboundedSender<String>,
+    mut rx: UnboundedReceiver<Vec<String>>,
+    tx: UnboundedSender<Vec<String>>,
 ) {
-    while let Some(fname) = rx.recv().await {
-        tx.send(coder.decode(&fname).await).unwrap()
+    while let Some(fnames) = rx.recv().await {
+        let fnames: Vec<_> = fnames.iter().map(|s| s.as_str()).collect();
+        tx.send(coder.decode_all(&fnames).await).unwrap()
     }
 }
@@ -21,14 +21,21 @@
 use async_stream::stream;
 use chris::errors::CubeError;
 use chris::types::{CollectionUrl, CubeUrl, FeedId, PluginInstanceId};
-use chris::{reqwest, RoClient};
+use chris::{reqwest, BaseChrisClient, ChrisClient, RoClient};
 use futures::{StreamExt, TryStreamExt};
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use url::Url;
 
 const FOLDER_SUBSTR_SUBSTITUTIONS: [(&str, &str); 1] = [("/", "!SLASH!")];
 
+/// Maximum number of plugin instance titles to get from _CUBE_ at the same time.
+const MAX_CONCURRENT_LOOKUPS: usize = 8;
+
+/// Top-level folders of _ChRIS_ storage which do not contain feed output files,
+/// so their subfolders are never renamed.
+const WELL_KNOWN_ROOTS: [&str; 2] = ["SERVICES", "PIPELINES"];
+
 /// Wrapper around [`Option<ChrisPathHumanCoder>`].
 #[derive(Default)]
 pub struct MaybeChrisPathHumanCoder<'a> {
@@ -57,6 +64,16 @@ impl MaybeChrisPathHumanCoder<'_> {
         }
     }
 
+    /// Calls the wrapped [ChrisPathHumanCoder::decode_all] if Some,
+    /// otherwise returns `fnames` as strings.
+    pub async fn decode_all(&mut self, fnames: &[&str]) -> Vec<String> {
+        if let Some(ref mut n) = self.namer {
+            n.decode_all(fnames).await
+        } else {
+            fnames.iter().map(|s| s.to_string()).collect()
+        }
+    }
+
     // BLOCKED by https://github.com/FNNDSC/ChRIS_ultron_backEnd/issues/530
     // Here we want to use the same code for logged in users vs anonymous users,
     // however since anonymous users can't use the same plugins/instances/search/
@@ -111,11 +128,120 @@ impl MaybeChrisPathHumanCoder<'_> {
     }
 }
 
+/// Where [ChrisPathHumanCoder] gets feed names and plugin instance titles from.
+///
+/// `Ok(None)` means the feed or plugin instance does not exist (anymore).
+pub(crate) trait NameSource {
+    async fn feed_name(&self, id: FeedId) -> Result<Option<String>, CubeError>;
+
+    async fn plinst_title(&self, id: PluginInstanceId) -> Result<Option<String>, CubeError>;
+
+    /// Search for the IDs of feeds named exactly `name`, for [ChrisPathHumanCoder::encode].
+    /// Sources which cannot search for feeds find none.
+    async fn feeds_named(&self, name: &str) -> Result<Vec<FeedId>, CubeError> {
+        Ok(Vec::new())
+    }
+
+    /// Search for the plugin instances of a feed titled exactly `title`, for
+    /// [ChrisPathHumanCoder::encode]. Sources which cannot search for plugin instances
+    /// find none.
+    async fn plinsts_titled(
+        &self,
+        feed_id: FeedId,
+        title: &str,
+    ) -> Result<Vec<TitledPluginInstance>, CubeError> {
+        Ok(Vec::new())
+    }
+}
+
+/// A plugin instance found by [NameSource::plinsts_titled].
+pub(crate) struct TitledPluginInstance {
+    pub id: PluginInstanceId,
+    pub previous_id: Option<PluginInstanceId>,
+    /// Name of the folder of the plugin instance's files, e.g. `pl-dircopy_5`
+    pub folder: String,
+}
+
+impl NameSource for RoClient {
+    async fn feed_name(&self, id: FeedId) -> Result<Option<String>, CubeError> {
+        not_found_as_none(self.get_feed(id).await.map(|feed| feed.object.name))
+    }
+
+    async fn plinst_title(&self, id: PluginInstanceId) -> Result<Option<String>, CubeError> {
+        not_found_as_none(
+            self.get_plugin_instance(id)
+                .await
+                .map(|plinst| plinst.object.title),
+        )
+    }
+}
+
+/// Logged in users can search for feeds and plugin instances, so they can also
+/// [ChrisPathHumanCoder::encode] paths.
+impl NameSource for ChrisClient {
+    async fn feed_name(&self, id: FeedId) -> Result<Option<String>, CubeError> {
+        not_found_as_none(self.get_feed(id).await.map(|feed| feed.object.name))
+    }
+
+    async fn plinst_title(&self, id: PluginInstanceId) -> Result<Option<String>, CubeError> {
+        not_found_as_none(
+            self.get_plugin_instance(id)
+                .await
+                .map(|plinst| plinst.object.title),
+        )
+    }
+
+    async fn feeds_named(&self, name: &str) -> Result<Vec<FeedId>, CubeError> {
+        self.feeds()
+            .name_exact(name)
+            .search()
+            .stream()
+            .map_ok(|feed| feed.id)
+            .try_collect()
+            .await
+    }
+
+    async fn plinsts_titled(
+        &self,
+        feed_id: FeedId,
+        title: &str,
+    ) -> Result<Vec<TitledPluginInstance>, CubeError> {
+        // the title filter of CUBE matches titles which contain it
+        self.plugin_instances()
+            .feed_id(feed_id)
+            .title(title)
+            .search()
+            .stream()
+            .try_filter(|plinst| futures::future::ready(plinst.title == title))
+            .map_ok(|plinst| TitledPluginInstance {
+                folder: format!("{}_{}", plinst.plugin_name, plinst.id.0),
+                id: plinst.id,
+                previous_id: plinst.previous_id,
+            })
+            .try_collect()
+            .await
+    }
+}
+
+fn not_found_as_none<T>(result: Result<T, CubeError>) -> Result<Option<T>, CubeError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(CubeError::Error { status, .. }) if status == reqwest::StatusCode::NOT_FOUND => {
+            Ok(None)
+        }
+        Err(CubeError::Raw(e)) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 /// [ChrisPathHumanCoder] provides methods for renaming CUBE file paths to more-easily
 /// human-readable file paths by replacing feed and plugin instance folder names with
 /// feed names and plugin instance titles respectively.
-pub(crate) struct ChrisPathHumanCoder<'a> {
-    chris: &'a RoClient,
+///
+/// Every folder name is looked up at most once, so a folder is always renamed the same
+/// way even if its feed or plugin instance is deleted partway through.
+pub(crate) struct ChrisPathHumanCoder<'a, S: NameSource = RoClient> {
+    chris: &'a S,
 
     /// cache of plugin instance titles
     plinst_memo: HashMap<String, String>,
@@ -126,30 +252,42 @@ pub(crate) struct ChrisPathHumanCoder<'a> {
     /// When this [ChrisPathHumanCoder] encounters an error from CUBE (except from 404 errors)
     /// `cube_error` is set to `true` so that it won't try to contact CUBE again.
     cube_error: bool,
+
+    /// Number of warnings printed
+    warnings: usize,
 }
 
-impl<'a> ChrisPathHumanCoder<'a> {
-    fn new(chris: &'a RoClient) -> Self {
+impl<'a, S: NameSource> ChrisPathHumanCoder<'a, S> {
+    pub fn new(chris: &'a S) -> Self {
         Self {
             chris,
             plinst_memo: Default::default(),
             feed_memo: Default::default(),
             cube_error: false,
+            warnings: 0,
         }
     }
 }
 
-impl ChrisPathHumanCoder<'_> {
+impl<S: NameSource> ChrisPathHumanCoder<'_, S> {
+    fn warn(&mut self, message: impl std::fmt::Display) {
+        eprintln!("WARNING: {}", message);
+        self.warnings += 1;
+    }
+
     /// Tries to rename a path components of a feed file output's `fname` so that the folder names
     /// are changed to use the folder's corresponding feed name or plugin instance title.
     ///
     /// The renamed paths are more human-friendly for the purposes of downloading output folders.
+    /// The titles of the plugin instances of `fname` are looked up concurrently.
     pub async fn decode(&mut self, fname: impl AsRef<str>) -> String {
         if let Some((username, feed_folder, feed_id, split)) =
             consume_feed_fname(fname.as_ref().split('/'))
         {
             let feed_name = self.get_feed_name(feed_id, feed_folder).await;
-            let folders = self.rename_plugin_instances(split).await;
+            let split: Vec<_> = split.collect();
+            self.prefetch_titles(plinst_folders_of(&split)).await;
+            let folders = self.rename_plugin_instances(split.into_iter()).await;
             if folders.is_empty() {
                 format!("{}/{}", username, feed_name)
             } else {
@@ -160,6 +298,52 @@ impl ChrisPathHumanCoder<'_> {
         }
     }
 
+    /// Calls [ChrisPathHumanCoder::decode] on every fname. The titles of all the plugin instances
+    /// of all the fnames are looked up together, so every plugin instance is requested once
+    /// and the requests are made concurrently.
+    pub async fn decode_all(&mut self, fnames: &[&str]) -> Vec<String> {
+        let folders: Vec<_> = fnames
+            .iter()
+            .filter_map(|fname| consume_feed_fname(fname.split('/')))
+            .flat_map(|(_, _, _, split)| plinst_folders_of(&split.collect::<Vec<_>>()))
+            .collect();
+        self.prefetch_titles(folders).await;
+        let mut decoded = Vec::with_capacity(fnames.len());
+        for fname in fnames {
+            decoded.push(self.decode(fname).await);
+        }
+        decoded
+    }
+
+    /// Get the titles of plugin instance folders which are not cached yet from _CUBE_,
+    /// making at most [MAX_CONCURRENT_LOOKUPS] requests at a time, and cache them.
+    ///
+    /// Malformed folder names are skipped, so that [ChrisPathHumanCoder::get_title_for]
+    /// handles them like before.
+    async fn prefetch_titles<'b>(&mut self, folders: impl IntoIterator<Item = &'b str>) {
+        if self.cube_error {
+            return;
+        }
+        let mut seen = HashSet::new();
+        let lookups: Vec<_> = folders
+            .into_iter()
+            .filter(|folder| !self.plinst_memo.contains_key(*folder) && seen.insert(*folder))
+            .filter_map(|folder| parse_plinst_id(folder).ok().map(|id| (folder, id)))
+            .collect();
+        let chris = self.chris;
+        let mut results: Vec<_> = futures::stream::iter(lookups.iter().enumerate())
+            .map(|(i, (_, id))| async move { (i, chris.plinst_title(*id).await) })
+            .buffer_unordered(MAX_CONCURRENT_LOOKUPS)
+            .collect()
+            .await;
+        // handle results in a consistent order, so that warnings are printed in order
+        results.sort_unstable_by_key(|(i, _)| *i);
+        for ((folder, _), (_, result)) in lookups.into_iter().zip(results) {
+            let title = self.title_of(folder, result.map_err(PluginInstanceTitleError::Cube));
+            self.plinst_memo.insert(folder.to_string(), title);
+        }
+    }
+
     /// If a feed ID can be parsed from the given folder name, try and
     /// get its name from CUBE. In any case that is not possible, the folder
     /// name is simply returned as a string.
@@ -172,26 +356,36 @@ impl ChrisPathHumanCoder<'_> {
     }
 
     /// Gets (and caches) the feed name for the specified feed ID. If unable to, then
-    /// a given default value is returned, and [ChrisPathHumanCoder::cube_error] is set to `true`.
+    /// the folder name is cached and returned. If the error was something besides a 404,
+    /// [ChrisPathHumanCoder::cube_error] is set to `true`.
     async fn get_feed_name(&mut self, id: FeedId, feed_folder: &str) -> String {
         if let Some(name) = self.feed_memo.get(feed_folder) {
             return name.to_string();
         }
-        self.chris
-            .get_feed(id)
-            .await
-            .map(|feed| feed.object.name)
-            .map(substitute_unallowed)
-            .map(|name| this_or_that(name, feed_folder))
-            .map(|f| self.cache_feed_name(feed_folder, f))
-            .unwrap_or_else(|e| {
-                eprintln!(
-                    "WARNING: could not get feed name for \"{}\". {:?}",
+        if self.cube_error {
+            return self.cache_feed_name(feed_folder, feed_folder.to_string());
+        }
+        match self.chris.feed_name(id).await {
+            Ok(Some(name)) => {
+                let name = this_or_that(substitute_unallowed(name), feed_folder);
+                self.cache_feed_name(feed_folder, name)
+            }
+            Ok(None) => {
+                self.warn(format_args!(
+                    "feed for \"{}\" not found, it might have been deleted.",
+                    feed_folder
+                ));
+                self.cache_feed_name(feed_folder, feed_folder.to_string())
+            }
+            Err(e) => {
+                self.warn(format_args!(
+                    "could not get feed name for \"{}\". {:?}",
                     feed_folder, e
-                );
+                ));
                 self.cube_error = true;
-                feed_folder.to_string()
-            })
+                self.cache_feed_name(feed_folder, feed_folder.to_string())
+            }
+        }
     }
 
     fn cache_feed_name(&mut self, folder: &str, feed_name: String) -> String {
@@ -250,40 +444,54 @@ impl ChrisPathHumanCoder<'_> {
         }
 
         // else, try to parse and get from CUBE
-        match self.get_from_cube(folder).await {
-            Ok(title) => {
-                let title = substitute_unallowed(title);
-                let title = this_or_that(title, folder);
-                self.plinst_memo.insert(folder.to_string(), title.clone());
-                title
+        let result = self.get_from_cube(folder).await;
+        let title = self.title_of(folder, result);
+        self.plinst_memo.insert(folder.to_string(), title.clone());
+        title
+    }
+
+    /// Use the title of a plugin instance from CUBE as the name of its folder. If it could not be
+    /// gotten, a warning is printed and the folder name is used as-is.
+    fn title_of(
+        &mut self,
+        folder: &str,
+        result: Result<Option<String>, PluginInstanceTitleError>,
+    ) -> String {
+        match result {
+            Ok(Some(title)) => this_or_that(substitute_unallowed(title), folder),
+            Ok(None) => {
+                // plugin instance was deleted, which is not a reason to stop trying others
+                self.warn(format_args!(
+                    "plugin instance for \"{}\" not found, it might have been deleted.",
+                    folder
+                ));
+                folder.to_string()
             }
             Err(e) => {
-                eprintln!("WARNING: {:?}", e);
+                // concurrent requests might have failed for the same reason, only warn once
+                if !self.cube_error {
+                    self.warn(format!("{:?}", e));
+                }
                 self.cube_error = true; // don't try to speak to CUBE again
-                                        // default to using the folder name as-is
-                self.plinst_memo
-                    .insert(folder.to_string(), folder.to_string());
-                folder.to_string()
+                folder.to_string() // default to using the folder name as-is
             }
         }
     }
 
     /// Get from CUBE the title of the plugin instance which corresponds to the given folder name.
-    async fn get_from_cube<'a>(
-        &'a self,
-        folder: &'a str,
-    ) -> Result<String, PluginInstanceTitleError> {
+    async fn get_from_cube<'f>(
+        &self,
+        folder: &'f str,
+    ) -> Result<Option<String>, PluginInstanceTitleError<'f>> {
         let id = parse_plinst_id(folder)?;
-        let plinst = self
-            .chris
-            .get_plugin_instance(id)
+        self.chris
+            .plinst_title(id)
             .await
-            .map_err(PluginInstanceTitleError::Cube)?;
-        Ok(plinst.object.title)
+            .map_err(PluginInstanceTitleError::Cube)
     }
 
-    /// Attempts to reverse operation of [Self::decode]. Untranslatable
-    /// path components are left as-is.
+    /// Attempts to reverse operation of [Self::decode]. Folders which are already named
+    /// like the folders of feeds and plugin instances are left as-is.
     pub async fn encode(&mut self, path: &str) -> Result<String, TranslationError> {
         if let Some((username_folder, feed_name, joined_plinst_titles, data_folder, output_path)) =
             split_renamed_path(path)
@@ -292,10 +500,8 @@ impl ChrisPathHumanCoder<'_> {
                 .split('/')
                 .filter(|s| !s.is_empty())
                 .collect();
-            let (feed_folder, plinst_folders) = tokio::try_join!(
-                self.feed_name2folder(feed_name),
-                self.plinst_titles2folders(&plinst_titles)
-            )?;
+            let (feed_folder, feed_id) = self.feed_name2folder(feed_name).await?;
+            let plinst_folders = self.plinst_titles2folders(feed_id, &plinst_titles).await?;
 
             // cache the stuff
             self.feed_memo
@@ -320,75 +526,65 @@ impl ChrisPathHumanCoder<'_> {
         }
     }
 
-    /// Convert plugin instance titles to plugin instance folder names.
+    /// Convert plugin instance titles to plugin instance folder names. Each plugin
+    /// instance is searched for in the feed, after the plugin instance before it.
     async fn plinst_titles2folders(
         &self,
+        feed_id: FeedId,
         titles: &[&str],
     ) -> Result<Vec<String>, TranslationError> {
-        // Future work:
-        // The searches can be made more strict using information about `previous_id`
-        // and `feed_id`, which we know from the full given path.
-        futures::stream::iter(titles)
-            .map(|title| self.plinst_title2folder(title))
-            .buffered(10)
-            .try_collect()
-            .await
+        let mut folders = Vec::with_capacity(titles.len());
+        let mut previous_id = None;
+        for title in titles {
+            let (folder, id) = self
+                .plinst_title2folder(feed_id, previous_id, title)
+                .await?;
+            folders.push(folder);
+            previous_id = Some(id);
+        }
+        Ok(folders)
     }
 
-    async fn plinst_title2folder(&self, title: &str) -> Result<String, TranslationError> {
-        Err(TranslationError::PluginInstanceNotFound(
-            "Not implemented".to_string(),
-        ))
-        // let query = &[("title", title), ("limit", "1")];
-        // let search = self.chris.search_plugin_instances(query);
-        // pin_mut!(search);
-        // let folder = search
-        //     .try_next()
-        //     .await
-        //     .map_err(TranslationError::RequestError)?
-        //     .map(|plinst| format!("{}_{}", plinst.plugin_name.as_str(), plinst.id.0))
-        //     .ok_or_else(|| TranslationError::PluginInstanceNotFound(title.to_string()))
-        //     .or_else(|e| {
-        //         // given value is not a plugin instance ID, but looks like a valid folder already
-        //         parse_plinst_id(title)
-        //             .map_err(|_| e)
-        //             .map(|_| title.to_string())
-        //     });
-        // if let Some(second) = search.try_next().await? {
-        //     Err(TranslationError::AmbiguousPluginInstanceTitleError(
-        //         second.title,
-        //     ))
-        // } else {
-        //     folder
-        // }
+    async fn plinst_title2folder(
+        &self,
+        feed_id: FeedId,
+        previous_id: Option<PluginInstanceId>,
+        title: &str,
+    ) -> Result<(String, PluginInstanceId), TranslationError> {
+        if let Ok(id) = parse_plinst_id(title) {
+            // given value looks like a valid folder already
+            return Ok((title.to_string(), id));
+        }
+        let title = restore_unallowed(title);
+        let mut found = self
+            .chris
+            .plinsts_titled(feed_id, &title)
+            .await?
+            .into_iter()
+            .filter(|p| p.previous_id == previous_id);
+        match (found.next(), found.next()) {
+            (Some(plinst), None) => Ok((plinst.folder, plinst.id)),
+            (None, _) => Err(TranslationError::PluginInstanceNotFound(title)),
+            (Some(_), Some(_)) => Err(TranslationError::AmbiguousPluginInstanceTitleError(title)),
+        }
     }
 
     /// Given the name of a feed, search CUBE for its ID *N* and return its folder name in the
-    /// format "feed_*N*". If the feed is found, its name will be cached.
-    async fn feed_name2folder(&self, feed_name: &str) -> Result<String, TranslationError> {
-        Err(TranslationError::FeedNotFound(
-            "not implemented".to_string(),
-        ))
-        // let query = &[("name", feed_name), ("limit", "1")];
-        // let search = self.chris.search_feeds(query);
-        // pin_mut!(search);
-        // let folder = search
-        //     .try_next()
-        //     .await
-        //     .map_err(TranslationError::RequestError)?
-        //     .map(|feed| format!("feed_{}", feed.id.0))
-        //     .ok_or_else(|| TranslationError::FeedNotFound(feed_name.to_string()))
-        //     .or_else(|e| {
-        //         // given value is not a feed name, but looks like a valid feed folder already
-        //         parse_feed_folder(feed_name)
-        //             .map(|_| feed_name.to_string())
-        //             .ok_or(e)
-        //     });
-        // if let Some(second) = search.try_next().await? {
-        //     Err(TranslationError::AmbiguousFeedNameError(second.name))
-        // } else {
-        //     folder
-        // }
+    /// format "feed_*N*", and its ID.
+    async fn feed_name2folder(
+        &self,
+        feed_name: &str,
+    ) -> Result<(String, FeedId), TranslationError> {
+        if let Some(id) = parse_feed_folder(feed_name) {
+            // given value looks like a valid feed folder already
+            return Ok((feed_name.to_string(), id));
+        }
+        let feed_name = restore_unallowed(feed_name);
+        match self.chris.feeds_named(&feed_name).await?.as_slice() {
+            [id] => Ok((format!("feed_{}", id.0), *id)),
+            [] => Err(TranslationError::FeedNotFound(feed_name)),
+            _ => Err(TranslationError::AmbiguousFeedNameError(feed_name)),
+        }
     }
 }
 
@@ -397,6 +593,9 @@ pub enum TranslationError {
     #[error(transparent)]
     RequestError(#[from] reqwest::Error),
 
+    #[error(transparent)]
+    Cube(#[from] CubeError),
+
     #[error("Ambiguous name: \"{0}\" (must give canonical fname with numerical ID)")]
     // TODO show matching feed IDs
     AmbiguousFeedNameError(String),
@@ -460,8 +659,8 @@ fn to_search(address: &CubeUrl, endpoint: &str, fname: &str) -> CollectionUrl {
 /// uploaded file fname, then `None` is returned.
 fn split_renamed_path(path: &str) -> Option<(&str, &str, &str, &str, &str)> {
     path.split_once('/')
-        // all top-level folders besides "SERVICES" contain user files
-        .filter(|(root_folder, _rest)| *root_folder != "SERVICES")
+        // all top-level folders besides the well-known roots contain user files
+        .filter(|(root_folder, _rest)| !WELL_KNOWN_ROOTS.contains(root_folder))
         .map(|(root_folder, rest)| {
             rest.split_once('/')
                 .map(|(feed_folder, rest)| (root_folder, feed_folder, rest))
@@ -491,6 +690,15 @@ fn substitute_unallowed(mut folder_name: String) -> String {
     folder_name
 }
 
+/// Reverse [substitute_unallowed].
+fn restore_unallowed(folder_name: &str) -> String {
+    let mut name = folder_name.to_string();
+    for (from, to) in FOLDER_SUBSTR_SUBSTITUTIONS {
+        name = name.replace(to, from)
+    }
+    name
+}
+
 fn this_or_that(a: String, b: &str) -> String {
     if a.is_empty() {
         b.to_string()
@@ -509,14 +717,27 @@ fn parse_plinst_id(folder: &str) -> Result<PluginInstanceId, PluginInstanceTitle
         .ok_or(PluginInstanceTitleError::Malformed(folder))
 }
 
+/// The plugin instance folders of the components of a fname after its feed folder,
+/// i.e. the folders which come before "data".
+fn plinst_folders_of<'a>(split: &[&'a str]) -> Vec<&'a str> {
+    split
+        .iter()
+        .copied()
+        .take_while(|folder| *folder != "data" && !folder.is_empty())
+        .collect()
+}
+
 /// Consumes the first two items from the given iterator. If the second item is
 /// recognized as a feed output folder, the consumed items, feed ID, and the
 /// rest of the iterator is returned. Otherwise, the given iterator gets dropped.
+///
+/// Paths under [WELL_KNOWN_ROOTS] are never feed output paths, even if e.g.
+/// a user whose pipelines are under `PIPELINES/` is named like a feed folder.
 fn consume_feed_fname<'a, I>(mut iter: I) -> Option<(&'a str, &'a str, FeedId, I)>
 where
     I: Iterator<Item = &'a str>,
 {
-    if let Some(first) = iter.next() {
+    if let Some(first) = iter.next().filter(|f| !WELL_KNOWN_ROOTS.contains(f)) {
         iter.next()
             .and_then(|f| parse_feed_folder(f).map(|n| (f, n)))
             .map(|(feed_folder, feed_id)| (first, feed_folder, feed_id, iter))
@@ -552,8 +773,9 @@ enum PluginInstanceTitleError<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chris::testing::MockCube;
     use chris::types::FileResourceFname;
-    use chris::{AnonChrisClient, EitherClient};
+    use chris::{AnonChrisClient, EitherClient, PluginInstanceResponse};
     use rstest::*;
 
     #[rstest]
@@ -597,6 +819,15 @@ mod tests {
         );
     }
 
+    #[rstest]
+    #[case("SERVICES/PACS/Orthanc/00000_PatientName_000000")]
+    #[case("SERVICES/feed_12/file.dcm")]
+    #[case("PIPELINES/feed_3/pipeline.yml")]
+    #[case("chrisuser/uploads/hello.json")]
+    fn test_consume_feed_fname_not_feed(#[case] fname: &str) {
+        assert!(consume_feed_fname(fname.split('/')).is_none())
+    }
+
     #[rstest]
     #[case("pl-dircopy_3", Some(3))]
     #[case("pl-simpledsapp_45", Some(45))]
@@ -629,6 +860,8 @@ mod tests {
     #[rstest]
     #[case("", None)]
     #[case("SERVICES/PACS/something...", None)]
+    #[case("SERVICES/feed_12", None)]
+    #[case("PIPELINES/feed_3/pipeline.yml", None)]
     #[case("chris/uploads", None)]
     #[case("chris/uploads/something", None)]
     #[case("christopher/feed_12", Some(("christopher", "feed_12", "", "", "")))]
@@ -652,6 +885,223 @@ mod tests {
         CubeUrl::try_from("https://example.com/api/v1/").unwrap()
     }
 
+    /// A [NameSource] where plugin instances are deleted after their titles are first looked up.
+    #[derive(Default)]
+    struct DeletingNameSource {
+        requests: std::cell::RefCell<Vec<PluginInstanceId>>,
+    }
+
+    impl NameSource for DeletingNameSource {
+        async fn feed_name(&self, _id: FeedId) -> Result<Option<String>, CubeError> {
+            Ok(Some("My Feed".to_string()))
+        }
+
+        async fn plinst_title(&self, id: PluginInstanceId) -> Result<Option<String>, CubeError> {
+            let mut requests = self.requests.borrow_mut();
+            let seen = requests.contains(&id);
+            requests.push(id);
+            if seen || id == PluginInstanceId(6) {
+                Ok(None)
+            } else {
+                Ok(Some(format!("Title {}", id.0)))
+            }
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_decode_deleted_plugin_instance() {
+        let source = DeletingNameSource::default();
+        let mut coder = ChrisPathHumanCoder::new(&source);
+        let fnames = [
+            "chris/feed_1/pl-dircopy_5/data/a.txt",
+            "chris/feed_1/pl-dircopy_5/pl-deleted_6/data/b.txt",
+            "chris/feed_1/pl-dircopy_5/data/c.txt",
+            "chris/feed_1/pl-dircopy_5/pl-deleted_6/data/d.txt",
+            "chris/feed_1/pl-dircopy_5/pl-deleted_6/pl-child_7/data/e.txt",
+        ];
+        let mut actual = Vec::with_capacity(fnames.len());
+        for fname in fnames {
+            actual.push(coder.decode(fname).await);
+        }
+        let expected = [
+            "chris/My Feed/Title 5/data/a.txt",
+            "chris/My Feed/Title 5/pl-deleted_6/data/b.txt",
+            "chris/My Feed/Title 5/data/c.txt",
+            "chris/My Feed/Title 5/pl-deleted_6/data/d.txt",
+            "chris/My Feed/Title 5/pl-deleted_6/Title 7/data/e.txt",
+        ];
+        assert_eq!(actual, expected);
+        assert_eq!(coder.warnings, 1);
+        assert!(!coder.cube_error, "404 should not stop further lookups");
+        assert_eq!(
+            source.requests.into_inner(),
+            vec![
+                PluginInstanceId(5),
+                PluginInstanceId(6),
+                PluginInstanceId(7)
+            ]
+        );
+    }
+
+    /// Add a feed "My Feed" to `mock` with a chain of `n` plugin instances titled "Title 1"
+    /// to "Title n", each with 5 output files. Returns the fnames of the files.
+    fn titled_chain(mock: &MockCube, n: u32) -> Vec<String> {
+        let plugin = mock.plugin(1, "pl-app", "1.0.0");
+        let feed = mock.feed(1, "My Feed");
+        let mut plinsts: Vec<PluginInstanceResponse> = Vec::new();
+        for id in 1..=n {
+            let plinst = PluginInstanceResponse {
+                title: format!("Title {}", id),
+                ..mock.plugin_instance(id, &plugin, &feed, plinsts.last())
+            };
+            plinsts.push(plinst);
+        }
+        let fnames: Vec<_> = plinsts
+            .iter()
+            .flat_map(|p| {
+                let output_path = p.output_path.clone().unwrap();
+                (0..5).map(move |i| format!("{}/{}.txt", output_path, i))
+            })
+            .collect();
+        let files: Vec<_> = fnames
+            .iter()
+            .map(|fname| (fname.strip_prefix("chris/feed_1/").unwrap(), ""))
+            .collect();
+        mock.add_plugin(plugin);
+        mock.add_feed_with_files(feed, files);
+        plinsts
+            .into_iter()
+            .for_each(|p| mock.add_plugin_instance(p));
+        fnames
+    }
+
+    fn plinst_requests(mock: &MockCube) -> Vec<String> {
+        mock.requests()
+            .into_iter()
+            .filter(|target| target.contains("plugins/instances/"))
+            .collect()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_decode_all_requests_each_folder_once() {
+        let mock = MockCube::start().await;
+        let fnames = titled_chain(&mock, 10);
+        let fnames: Vec<_> = fnames.iter().map(|s| s.as_str()).collect();
+        assert_eq!(fnames.len(), 50);
+        let client = EitherClient::Anon(mock.anon_client().await).into_ro();
+        let mut coder = ChrisPathHumanCoder::new(&client);
+        let actual = coder.decode_all(&fnames).await;
+
+        assert_eq!(actual[0], "chris/My Feed/Title 1/data/0.txt");
+        assert_eq!(
+            actual[49],
+            format!(
+                "chris/My Feed/{}/data/4.txt",
+                (1..=10).map(|i| format!("Title {}", i)).join("/")
+            )
+        );
+        assert_eq!(coder.warnings, 0);
+        let mut requests = plinst_requests(&mock);
+        assert_eq!(requests.len(), 10, "every folder should be requested once");
+        requests.sort_unstable();
+        requests.dedup();
+        assert_eq!(requests.len(), 10);
+    }
+
+    /// A [NameSource] of a feed "My Feed" with the plugin instances
+    ///
+    /// ```text
+    /// pl-dircopy_1 "root" -> pl-app_2 "a/b" -> pl-app_3 "same"
+    ///                     -> pl-app_4 "same"
+    /// ```
+    struct SearchingNameSource;
+
+    impl NameSource for SearchingNameSource {
+        async fn feed_name(&self, _id: FeedId) -> Result<Option<String>, CubeError> {
+            Ok(Some("My Feed".to_string()))
+        }
+
+        async fn plinst_title(&self, _id: PluginInstanceId) -> Result<Option<String>, CubeError> {
+            Ok(None)
+        }
+
+        async fn feeds_named(&self, name: &str) -> Result<Vec<FeedId>, CubeError> {
+            let ids = match name {
+                "My Feed" => vec![FeedId(7)],
+                "Twins" => vec![FeedId(8), FeedId(9)],
+                _ => vec![],
+            };
+            Ok(ids)
+        }
+
+        async fn plinsts_titled(
+            &self,
+            feed_id: FeedId,
+            title: &str,
+        ) -> Result<Vec<TitledPluginInstance>, CubeError> {
+            assert_eq!(feed_id, FeedId(7));
+            let plinsts = [
+                (1, None, "pl-dircopy", "root"),
+                (2, Some(1), "pl-app", "a/b"),
+                (3, Some(2), "pl-app", "same"),
+                (4, Some(1), "pl-app", "same"),
+            ];
+            let found = plinsts
+                .into_iter()
+                .filter(|(_, _, _, t)| *t == title)
+                .map(|(id, previous_id, plugin_name, _)| TitledPluginInstance {
+                    id: PluginInstanceId(id),
+                    previous_id: previous_id.map(PluginInstanceId),
+                    folder: format!("{}_{}", plugin_name, id),
+                })
+                .collect();
+            Ok(found)
+        }
+    }
+
+    #[rstest]
+    #[case("chris/My Feed", "chris/feed_7")]
+    #[case("chris/My Feed/root", "chris/feed_7/pl-dircopy_1")]
+    #[case(
+        "chris/My Feed/root/same/data",
+        "chris/feed_7/pl-dircopy_1/pl-app_4/data"
+    )]
+    #[case(
+        "chris/My Feed/root/a!SLASH!b/same/data/out.txt",
+        "chris/feed_7/pl-dircopy_1/pl-app_2/pl-app_3/data/out.txt"
+    )]
+    #[case(
+        "chris/feed_7/pl-dircopy_1/a!SLASH!b/data",
+        "chris/feed_7/pl-dircopy_1/pl-app_2/data"
+    )]
+    #[case("chris/uploads/My Feed", "chris/uploads/My Feed")]
+    #[case("SERVICES/PACS/My Feed", "SERVICES/PACS/My Feed")]
+    #[tokio::test]
+    async fn test_encode(#[case] path: &str, #[case] expected: &str) {
+        let mut coder = ChrisPathHumanCoder::new(&SearchingNameSource);
+        assert_eq!(coder.encode(path).await.unwrap(), expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_encode_errors() {
+        let mut coder = ChrisPathHumanCoder::new(&SearchingNameSource);
+        assert!(matches!(
+            coder.encode("chris/Nope/root").await,
+            Err(TranslationError::FeedNotFound(name)) if name == "Nope"
+        ));
+        assert!(matches!(
+            coder.encode("chris/Twins/root").await,
+            Err(TranslationError::AmbiguousFeedNameError(name)) if name == "Twins"
+        ));
+        assert!(matches!(
+            coder.encode("chris/My Feed/same").await,
+            Err(TranslationError::PluginInstanceNotFound(title)) if title == "same"
+        ));
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_try() {
@@ -0,0 +1,157 @@
+use clap::Args;
+use color_eyre::eyre::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Options for selecting files by glob patterns.
+#[derive(Args, Debug, Default)]
+pub struct FilterArgs {
+    /// Only include files matching a glob, e.g. "*.nii.gz". Globs are matched against
+    /// paths relative to the given folder, where `*` also matches `/`.
+    /// Can be given multiple times to include files matching any of the globs.
+    #[clap(long, value_name = "GLOB")]
+    pub filter: Vec<String>,
+
+    /// Leave out files matching a glob. Can be given multiple times.
+    #[clap(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+}
+
+impl FilterArgs {
+    /// Compile the globs.
+    pub fn build(&self) -> Result<FileFilter> {
+        let include = if self.filter.is_empty() {
+            None
+        } else {
+            Some(glob_set(&self.filter)?)
+        };
+        let exclude = if self.exclude.is_empty() {
+            None
+        } else {
+            Some(glob_set(&self.exclude)?)
+        };
+        Ok(FileFilter { include, exclude })
+    }
+}
+
+fn glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).wrap_err_with(|| format!("Invalid glob: {:?}", glob))?);
+    }
+    builder.build().map_err(Into::into)
+}
+
+/// A compiled [FilterArgs].
+#[derive(Debug, Default)]
+pub struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl FileFilter {
+    /// Whether no globs were given, so every file is selected.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+
+    /// Whether a file should be selected, given its path relative to the folder being listed.
+    pub fn is_match(&self, rel: &str) -> bool {
+        self.include
+            .as_ref()
+            .map(|g| g.is_match(rel))
+            .unwrap_or(true)
+            && !self
+                .exclude
+                .as_ref()
+                .map(|g| g.is_match(rel))
+                .unwrap_or(false)
+    }
+
+    /// Whether the file `fname` under the folder `root` should be selected.
+    ///
+    /// If `fname` is `root` itself, i.e. the given path is a file, its basename is matched.
+    pub fn is_match_under(&self, fname: &str, root: &str) -> bool {
+        self.is_match(relative_fname(fname, root))
+    }
+}
+
+/// Path of `fname` relative to `root`, or the basename of `fname` if it is not under `root`.
+/// An empty `root` means files are identified by their full paths.
+fn relative_fname<'a>(fname: &'a str, root: &str) -> &'a str {
+    let root = root.trim_end_matches('/');
+    if root.is_empty() {
+        return fname;
+    }
+    fname
+        .strip_prefix(root)
+        .and_then(|s| s.strip_prefix('/'))
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| {
+            fname
+                .rsplit_once('/')
+                .map(|(_, name)| name)
+                .unwrap_or(fname)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> FileFilter {
+        FilterArgs {
+            filter: include.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case(&[], &[], "brain.nii.gz", true)]
+    #[case(&["*.nii.gz"], &[], "brain.nii.gz", true)]
+    #[case(&["*.nii.gz"], &[], "sub-01/brain.nii.gz", true)]
+    #[case(&["*.nii.gz"], &[], "brain.json", false)]
+    #[case(&["*.nii.gz", "*.json"], &[], "brain.json", true)]
+    #[case(&[], &["*.json"], "brain.json", false)]
+    #[case(&[], &["*.json"], "brain.nii.gz", true)]
+    #[case(&["sub-01/**"], &["*.json"], "sub-01/brain.nii.gz", true)]
+    #[case(&["sub-01/**"], &["*.json"], "sub-01/brain.json", false)]
+    #[case(&["sub-01/**"], &["*.json"], "sub-02/brain.nii.gz", false)]
+    fn test_is_match(
+        #[case] include: &[&str],
+        #[case] exclude: &[&str],
+        #[case] rel: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(filter(include, exclude).is_match(rel), expected)
+    }
+
+    #[rstest]
+    #[case(
+        "alice/feed_1/pl-dircopy_2/data/a/b.txt",
+        "alice/feed_1/pl-dircopy_2/data",
+        "a/b.txt"
+    )]
+    #[case(
+        "alice/feed_1/pl-dircopy_2/data/a/b.txt",
+        "alice/feed_1/pl-dircopy_2/data/",
+        "a/b.txt"
+    )]
+    #[case("alice/uploads/b.txt", "alice/uploads/b.txt", "b.txt")]
+    #[case("alice/uploads/b.txt", "bob/uploads", "b.txt")]
+    #[case("alice/uploads/b.txt", "", "alice/uploads/b.txt")]
+    fn test_relative_fname(#[case] fname: &str, #[case] root: &str, #[case] expected: &str) {
+        assert_eq!(relative_fname(fname, root), expected)
+    }
+
+    #[rstest]
+    fn test_invalid_glob() {
+        let args = FilterArgs {
+            filter: vec!["a[".to_string()],
+            exclude: vec![],
+        };
+        assert!(args.build().is_err())
+    }
+}
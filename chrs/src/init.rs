@@ -0,0 +1,216 @@
+//! `chrs init`: guided setup for first-time users.
+mod steps;
+
+use std::io::IsTerminal;
+
+use clap::Parser;
+use color_eyre::eyre::{bail, Result};
+use color_eyre::owo_colors::OwoColorize;
+use dialoguer::console::Term;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Input, Password, Select};
+
+use chris::types::{CubeUrl, Username};
+use chris::{AnonChrisClient, BaseChrisClient};
+
+use crate::credentials::Credentials;
+use crate::login::cmd::{login_with_password, login_with_token, save_login};
+use crate::login::public::public_cubes;
+use crate::login::state::ChrsSessions;
+use crate::login::store::{Backend, CubeState};
+use crate::login::UiUrl;
+use steps::*;
+
+#[derive(Parser)]
+pub struct InitArgs {
+    /// Use ChRIS without logging in
+    #[clap(long, conflicts_with = "password_stdin")]
+    anonymous: bool,
+
+    /// Do not prompt, use defaults instead
+    #[clap(short, long)]
+    yes: bool,
+
+    /// Save token in plaintext instead of using keyring
+    #[clap(long)]
+    no_keyring: bool,
+
+    /// Take the password from stdin
+    #[clap(long)]
+    password_stdin: bool,
+
+    /// Do not look for ChRIS_ui
+    #[clap(long)]
+    no_ui_discovery: bool,
+}
+
+/// `chrs init` command
+pub async fn init(credentials: Credentials, args: InitArgs) -> Result<()> {
+    if credentials.ephemeral {
+        bail!("A token read from a file is never saved, so chrs init cannot use it.")
+    }
+    let password = if args.password_stdin {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        Some(line.trim_end_matches(['\r', '\n']).to_string())
+    } else {
+        credentials.password
+    };
+    let options = InitOptions {
+        cube: credentials.cube_url,
+        ui: credentials.ui,
+        username: credentials.username,
+        password,
+        token: credentials.token,
+        anonymous: args.anonymous,
+        no_ui: args.no_ui_discovery,
+    };
+    let public = public_cubes(&ChrsSessions::load(credentials.config_path.as_deref())?);
+    let setup = if args.yes {
+        guided_setup(&options, &mut NonInteractive, &public).await
+    } else if !std::io::stdin().is_terminal() && !args.password_stdin {
+        guided_setup(&options, &mut Lines::from_stdin()?, &public).await
+    } else {
+        guided_setup(&options, &mut Dialog, &public).await
+    }?;
+
+    let backend = if args.no_keyring {
+        Backend::ClearText
+    } else {
+        Backend::Keyring
+    };
+    save_login(
+        credentials.config_path.as_deref(),
+        session_of(&setup),
+        backend,
+        None,
+    )?;
+
+    println!("{} ChRIS is set up. Try:", "done:".green());
+    for command in example_commands(&setup) {
+        println!("    {}", command.bold());
+    }
+    Ok(())
+}
+
+fn session_of(setup: &Setup) -> CubeState {
+    let cube = setup.cube.clone().unwrap();
+    if let Some(username) = setup.username.clone() {
+        CubeState {
+            cube,
+            token: setup.token.clone(),
+            username,
+            current_plugin_instance_id: None,
+            ui: setup.ui.clone(),
+        }
+    } else {
+        CubeState::anonymous(cube, setup.ui.clone())
+    }
+}
+
+async fn guided_setup(
+    options: &InitOptions,
+    prompter: &mut impl Prompter,
+    public: &[crate::login::public::PublicCube],
+) -> Result<Setup> {
+    let cube = RealCube;
+    let mut setup = Setup::default();
+    choose_cube(&mut setup, options, prompter, public)?;
+    probe_cube(&mut setup, &cube).await?;
+    eprintln!(
+        "Connected to ChRIS {} (version {})",
+        setup.cube.as_ref().unwrap(),
+        setup.version.as_deref().unwrap_or("unknown")
+    );
+    choose_account(&mut setup, options, prompter)?;
+    log_in(&mut setup, options, prompter, &cube).await?;
+    discover_ui(&mut setup, options, prompter, &cube).await?;
+    for check in sanity_checks(&mut setup, &cube).await? {
+        match check {
+            Check::Pass(msg) => eprintln!("{} {}", "ok:".green(), msg),
+            Check::Warn(msg) => eprintln!("{} {}", "warning:".yellow(), msg),
+            Check::Fail(msg) => eprintln!("{} {}", "failed:".red(), msg),
+        }
+    }
+    Ok(setup)
+}
+
+/// A [Prompter] which asks the user using `dialoguer`.
+struct Dialog;
+
+impl Prompter for Dialog {
+    fn select(&mut self, prompt: &str, items: &[String], default: usize, _: &str) -> Result<usize> {
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(items)
+            .default(default)
+            .interact_on(&Term::stderr())?;
+        Ok(selection)
+    }
+
+    fn input(&mut self, prompt: &str, default: Option<&str>, _: &str) -> Result<String> {
+        let theme = ColorfulTheme::default();
+        let mut input = Input::<String>::with_theme(&theme).with_prompt(prompt);
+        if let Some(default) = default {
+            input = input.default(default.to_string()).allow_empty(true);
+        }
+        Ok(input.interact_text_on(&Term::stderr())?)
+    }
+
+    fn password(&mut self, prompt: &str, _: &str) -> Result<String> {
+        let password = Password::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .interact_on(&Term::stderr())?;
+        Ok(password)
+    }
+
+    fn confirm(&mut self, prompt: &str, default: bool, _: &str) -> Result<bool> {
+        let answer = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(default)
+            .interact_on(&Term::stderr())?;
+        Ok(answer)
+    }
+}
+
+/// [InitCube] for a real _CUBE_.
+struct RealCube;
+
+impl InitCube for RealCube {
+    async fn probe(&self, cube: &CubeUrl) -> Result<Option<String>> {
+        let client = AnonChrisClient::build(cube.clone())?.connect().await?;
+        Ok(client.server_version().map(|v| v.to_string()))
+    }
+
+    async fn get_token(
+        &self,
+        cube: &CubeUrl,
+        username: &Username,
+        password: &str,
+    ) -> Result<String> {
+        let token = login_with_password(cube, username, password).await?;
+        Ok(token.unwrap_or_default())
+    }
+
+    async fn check_token(
+        &self,
+        cube: &CubeUrl,
+        username: Option<&Username>,
+        token: &str,
+    ) -> Result<Username> {
+        login_with_token(cube, username.cloned(), token).await
+    }
+
+    async fn is_ui(&self, ui: &UiUrl) -> bool {
+        chris::reqwest::get(ui.as_str())
+            .await
+            .map(|res| res.status().is_success())
+            .unwrap_or(false)
+    }
+
+    async fn any_plugin(&self, cube: &CubeUrl) -> Result<Option<String>> {
+        let client = AnonChrisClient::build(cube.clone())?.connect().await?;
+        let plugin = client.plugin().search().get_first().await?;
+        Ok(plugin.map(|p| p.object.name.to_string()))
+    }
+}
@@ -0,0 +1,680 @@
+//! Steps of `chrs init`, which share a [Setup]. Input from the user is
+//! abstracted behind [Prompter], and requests to _CUBE_ behind [InitCube].
+
+use std::collections::VecDeque;
+
+use chris::types::{CubeUrl, Username};
+use color_eyre::eyre::{bail, eyre, Result};
+
+use crate::login::public::PublicCube;
+use crate::login::UiUrl;
+use crate::version::{degraded_features, CubeVersion};
+
+/// Asks the user for input.
+///
+/// Every prompt is given the flag of `chrs init` which can be used instead,
+/// so that [NonInteractive] can tell the user what is missing.
+pub(crate) trait Prompter {
+    /// Pick one of `items`.
+    fn select(
+        &mut self,
+        prompt: &str,
+        items: &[String],
+        default: usize,
+        flag: &str,
+    ) -> Result<usize>;
+
+    /// Ask for a line of text. Empty input is replaced by `default`.
+    fn input(&mut self, prompt: &str, default: Option<&str>, flag: &str) -> Result<String>;
+
+    /// Ask for a secret.
+    fn password(&mut self, prompt: &str, flag: &str) -> Result<String>;
+
+    /// Ask a yes or no question.
+    fn confirm(&mut self, prompt: &str, default: bool, flag: &str) -> Result<bool>;
+}
+
+/// A [Prompter] which takes the default of every prompt, for `chrs init --yes`.
+/// Prompts without a default are errors.
+pub(crate) struct NonInteractive;
+
+impl Prompter for NonInteractive {
+    fn select(&mut self, _: &str, _: &[String], default: usize, _: &str) -> Result<usize> {
+        Ok(default)
+    }
+
+    fn input(&mut self, prompt: &str, default: Option<&str>, flag: &str) -> Result<String> {
+        default
+            .map(|s| s.to_string())
+            .ok_or_else(|| missing(prompt, flag))
+    }
+
+    fn password(&mut self, prompt: &str, flag: &str) -> Result<String> {
+        Err(missing(prompt, flag))
+    }
+
+    fn confirm(&mut self, _: &str, default: bool, _: &str) -> Result<bool> {
+        Ok(default)
+    }
+}
+
+fn missing(prompt: &str, flag: &str) -> color_eyre::eyre::Error {
+    eyre!("Missing {}. Please specify {}", prompt, flag)
+}
+
+/// A [Prompter] which answers prompts from lines of text, e.g. read from a pipe.
+///
+/// For [Prompter::select], a line is either the number of the item (starting from 1)
+/// or its text. For [Prompter::confirm], a line is "y" or "n". An empty line
+/// takes the default. When there are no more lines, it behaves like [NonInteractive].
+pub(crate) struct Lines(VecDeque<String>);
+
+impl Lines {
+    pub fn new<S: Into<String>>(lines: impl IntoIterator<Item = S>) -> Self {
+        Self(lines.into_iter().map(|s| s.into()).collect())
+    }
+
+    /// Read answers from stdin.
+    pub fn from_stdin() -> std::io::Result<Self> {
+        std::io::stdin()
+            .lines()
+            .collect::<std::io::Result<Vec<_>>>()
+            .map(Self::new)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        self.0.pop_front().map(|line| line.trim().to_string())
+    }
+}
+
+impl Prompter for Lines {
+    fn select(
+        &mut self,
+        prompt: &str,
+        items: &[String],
+        default: usize,
+        flag: &str,
+    ) -> Result<usize> {
+        let Some(line) = self.next() else {
+            return NonInteractive.select(prompt, items, default, flag);
+        };
+        if line.is_empty() {
+            return Ok(default);
+        }
+        line.parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .filter(|&i| i < items.len())
+            .or_else(|| items.iter().position(|item| item == &line))
+            .ok_or_else(|| eyre!("Invalid choice for {}: {}", prompt, line))
+    }
+
+    fn input(&mut self, prompt: &str, default: Option<&str>, flag: &str) -> Result<String> {
+        let Some(line) = self.next() else {
+            return NonInteractive.input(prompt, default, flag);
+        };
+        match (line.is_empty(), default) {
+            (true, Some(default)) => Ok(default.to_string()),
+            _ => Ok(line),
+        }
+    }
+
+    fn password(&mut self, prompt: &str, flag: &str) -> Result<String> {
+        self.next()
+            .map_or_else(|| NonInteractive.password(prompt, flag), Ok)
+    }
+
+    fn confirm(&mut self, prompt: &str, default: bool, flag: &str) -> Result<bool> {
+        let Some(line) = self.next() else {
+            return NonInteractive.confirm(prompt, default, flag);
+        };
+        match line.to_lowercase().as_str() {
+            "" => Ok(default),
+            "y" | "yes" => Ok(true),
+            "n" | "no" => Ok(false),
+            other => bail!("Invalid answer for {}: {}", prompt, other),
+        }
+    }
+}
+
+/// What `chrs init` needs from _CUBE_.
+pub(crate) trait InitCube {
+    /// Connect to _CUBE_ anonymously, returning its advertised version.
+    async fn probe(&self, cube: &CubeUrl) -> Result<Option<String>>;
+
+    /// Get an authorization token.
+    async fn get_token(
+        &self,
+        cube: &CubeUrl,
+        username: &Username,
+        password: &str,
+    ) -> Result<String>;
+
+    /// Check that a token is valid, returning the username of its user.
+    /// If `username` is given, the token must belong to them.
+    async fn check_token(
+        &self,
+        cube: &CubeUrl,
+        username: Option<&Username>,
+        token: &str,
+    ) -> Result<Username>;
+
+    /// Whether _ChRIS_ui is served at `ui`.
+    async fn is_ui(&self, ui: &UiUrl) -> bool;
+
+    /// Name of any plugin of _CUBE_, for the example commands.
+    async fn any_plugin(&self, cube: &CubeUrl) -> Result<Option<String>>;
+}
+
+/// Values of the flags of `chrs init`.
+#[derive(Debug, Default)]
+pub(crate) struct InitOptions {
+    pub cube: Option<CubeUrl>,
+    pub ui: Option<UiUrl>,
+    pub username: Option<Username>,
+    pub password: Option<String>,
+    pub token: Option<String>,
+    pub anonymous: bool,
+    pub no_ui: bool,
+}
+
+/// What was set up so far.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Setup {
+    pub cube: Option<CubeUrl>,
+    pub ui: Option<UiUrl>,
+    pub version: Option<String>,
+    /// `None` for anonymous access.
+    pub username: Option<Username>,
+    pub token: Option<String>,
+    pub example_plugin: Option<String>,
+}
+
+impl Setup {
+    fn cube(&self) -> Result<&CubeUrl> {
+        self.cube
+            .as_ref()
+            .ok_or_else(|| eyre!("CUBE was not chosen"))
+    }
+}
+
+const OTHER_CUBE: &str = "Other (enter the address of a ChRIS API)";
+const WITH_ACCOUNT: &str = "Log in with a user account";
+const ANONYMOUS: &str = "Continue anonymously (read-only, public data)";
+
+/// Choose a _CUBE_, either given by `--cube` or from the well-known public CUBEs.
+pub(crate) fn choose_cube(
+    setup: &mut Setup,
+    options: &InitOptions,
+    prompter: &mut impl Prompter,
+    public_cubes: &[PublicCube],
+) -> Result<()> {
+    setup.ui = options.ui.clone();
+    if let Some(cube) = &options.cube {
+        setup.cube = Some(cube.clone());
+        return Ok(());
+    }
+    let items: Vec<_> = public_cubes
+        .iter()
+        .map(|c| format!("{} ({})", c.name, c.url))
+        .chain(std::iter::once(OTHER_CUBE.to_string()))
+        .collect();
+    let i = prompter.select("ChRIS instance", &items, 0, "--cube")?;
+    if let Some(public) = public_cubes.get(i) {
+        setup.cube = Some(public.url.clone());
+        setup.ui = setup.ui.take().or_else(|| public.ui.clone());
+    } else {
+        let url = prompter.input("ChRIS API address", None, "--cube")?;
+        setup.cube = Some(CubeUrl::try_from(url)?);
+    }
+    Ok(())
+}
+
+/// Make sure _CUBE_ is reachable.
+pub(crate) async fn probe_cube(setup: &mut Setup, cube: &impl InitCube) -> Result<()> {
+    setup.version = cube.probe(setup.cube()?).await?;
+    Ok(())
+}
+
+/// Choose between logging in and anonymous access.
+pub(crate) fn choose_account(
+    setup: &mut Setup,
+    options: &InitOptions,
+    prompter: &mut impl Prompter,
+) -> Result<()> {
+    if options.anonymous {
+        setup.username = None;
+        return Ok(());
+    }
+    // a token is of a user account, and tells which one
+    if options.username.is_some() || options.token.is_some() {
+        setup.username = options.username.clone();
+        return Ok(());
+    }
+    let items = [WITH_ACCOUNT.to_string(), ANONYMOUS.to_string()];
+    let flags = "--username or --anonymous";
+    if prompter.select("How to use ChRIS", &items, 0, flags)? == 0 {
+        let username = prompter.input("username", None, "--username")?;
+        setup.username = Some(Username::new(username));
+    } else {
+        setup.username = None;
+    }
+    Ok(())
+}
+
+/// Get a token for the chosen user account. Like `chrs login`, a password takes
+/// precedence over a token given by `--token`.
+pub(crate) async fn log_in(
+    setup: &mut Setup,
+    options: &InitOptions,
+    prompter: &mut impl Prompter,
+    cube: &impl InitCube,
+) -> Result<()> {
+    if options.anonymous {
+        return Ok(());
+    }
+    if let Some(token) = options
+        .token
+        .as_ref()
+        .filter(|_| options.password.is_none())
+    {
+        let username = cube
+            .check_token(setup.cube()?, setup.username.as_ref(), token)
+            .await?;
+        setup.username = Some(username);
+        setup.token = Some(token.clone());
+    } else if let Some(username) = &setup.username {
+        let password = if let Some(password) = &options.password {
+            password.to_string()
+        } else {
+            prompter.password("password", "--password or --password-stdin")?
+        };
+        let token = cube.get_token(setup.cube()?, username, &password).await?;
+        setup.token = Some(token);
+    }
+    Ok(())
+}
+
+/// Find _ChRIS_ui for the chosen _CUBE_, if not known already.
+pub(crate) async fn discover_ui(
+    setup: &mut Setup,
+    options: &InitOptions,
+    prompter: &mut impl Prompter,
+    cube: &impl InitCube,
+) -> Result<()> {
+    if setup.ui.is_some() || options.no_ui {
+        return Ok(());
+    }
+    for guess in guess_ui_urls(setup.cube()?) {
+        if cube.is_ui(&guess).await {
+            let prompt = format!("Found ChRIS_ui at {}. Use it", guess);
+            if prompter.confirm(&prompt, true, "--ui")? {
+                setup.ui = Some(guess);
+                return Ok(());
+            }
+            break;
+        }
+    }
+    let ui = prompter.input("ChRIS_ui address (leave empty to skip)", Some(""), "--ui")?;
+    if !ui.is_empty() {
+        setup.ui = Some(UiUrl::try_from(ui)?);
+    }
+    Ok(())
+}
+
+/// Guess where _ChRIS_ui is from the address of _CUBE_, e.g. for `https://cube.example.org/api/v1/`
+/// it might be `https://app.example.org`.
+pub(crate) fn guess_ui_urls(cube: &CubeUrl) -> Vec<UiUrl> {
+    let Ok(url) = url::Url::parse(cube.as_str()) else {
+        return Vec::new();
+    };
+    let scheme = url.scheme();
+    let mut guesses = Vec::new();
+    if let Some(host) = url.host_str() {
+        if let Some((first, rest)) = host.split_once('.') {
+            if ["cube", "api", "chris-api"].contains(&first) {
+                guesses.push(format!("{}://app.{}", scheme, rest));
+            }
+        }
+        // miniChRIS serves CUBE on port 8000 and ChRIS_ui on port 8020
+        if url.port() == Some(8000) {
+            guesses.push(format!("{}://{}:8020", scheme, host));
+        }
+    }
+    guesses
+        .into_iter()
+        .filter_map(|s| UiUrl::try_from(s).ok())
+        .collect()
+}
+
+/// Outcome of a sanity check.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Check {
+    Pass(String),
+    Warn(String),
+    Fail(String),
+}
+
+/// Check that the setup works.
+pub(crate) async fn sanity_checks(setup: &mut Setup, cube: &impl InitCube) -> Result<Vec<Check>> {
+    let mut checks = Vec::with_capacity(2);
+    match setup.version.as_deref().map(|v| (v, CubeVersion::parse(v))) {
+        Some((v, Some(version))) => {
+            let degraded = degraded_features(version);
+            if degraded.is_empty() {
+                checks.push(Check::Pass(format!(
+                    "CUBE version {} is fully supported",
+                    v
+                )));
+            } else {
+                checks.push(Check::Warn(format!(
+                    "CUBE version {} is missing {} features, see `chrs version --check`",
+                    v,
+                    degraded.len()
+                )));
+            }
+        }
+        _ => checks.push(Check::Warn(
+            "CUBE did not report its version, so compatibility is unknown".to_string(),
+        )),
+    }
+    match cube.any_plugin(setup.cube()?).await {
+        Ok(Some(plugin)) => {
+            checks.push(Check::Pass("Plugins can be listed".to_string()));
+            setup.example_plugin = Some(plugin);
+        }
+        Ok(None) => checks.push(Check::Warn("CUBE has no plugins".to_string())),
+        Err(e) => checks.push(Check::Fail(format!("Could not list plugins: {}", e))),
+    }
+    Ok(checks)
+}
+
+/// Three commands to try next.
+pub(crate) fn example_commands(setup: &Setup) -> [String; 3] {
+    let plugin = setup.example_plugin.as_deref().unwrap_or("pl-dircopy");
+    if setup.username.is_some() {
+        [
+            "chrs list".to_string(),
+            format!("chrs describe {}", plugin),
+            "chrs upload --feed \"My first feed\" path/to/data".to_string(),
+        ]
+    } else {
+        [
+            "chrs list --public".to_string(),
+            format!("chrs describe {}", plugin),
+            "chrs search pl-".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::cell::RefCell;
+
+    /// A CUBE at `https://cube.example.org/api/v1/` with ChRIS_ui at `https://app.example.org`.
+    #[derive(Default)]
+    struct FakeCube {
+        requests: RefCell<Vec<String>>,
+    }
+
+    impl InitCube for FakeCube {
+        async fn probe(&self, cube: &CubeUrl) -> Result<Option<String>> {
+            self.requests.borrow_mut().push(format!("probe {}", cube));
+            if cube.as_str().contains("example.org") {
+                Ok(Some("6.1.0".to_string()))
+            } else {
+                bail!("Could not connect to {}", cube)
+            }
+        }
+
+        async fn get_token(
+            &self,
+            _: &CubeUrl,
+            username: &Username,
+            password: &str,
+        ) -> Result<String> {
+            self.requests
+                .borrow_mut()
+                .push(format!("token {}", username));
+            if password == "chris1234" {
+                Ok("secret-token".to_string())
+            } else {
+                bail!("Could not log in")
+            }
+        }
+
+        async fn check_token(
+            &self,
+            _: &CubeUrl,
+            username: Option<&Username>,
+            token: &str,
+        ) -> Result<Username> {
+            self.requests.borrow_mut().push(format!("check {}", token));
+            if token != "secret-token" {
+                bail!("Invalid token")
+            }
+            match username {
+                Some(username) if username.as_str() != "chris" => {
+                    bail!("The token belongs to chris, not {}.", username)
+                }
+                _ => Ok(Username::from_static("chris")),
+            }
+        }
+
+        async fn is_ui(&self, ui: &UiUrl) -> bool {
+            ui.as_str() == "https://app.example.org"
+        }
+
+        async fn any_plugin(&self, _: &CubeUrl) -> Result<Option<String>> {
+            Ok(Some("pl-example".to_string()))
+        }
+    }
+
+    fn public_cubes() -> Vec<PublicCube> {
+        vec![PublicCube {
+            name: "Demo".to_string(),
+            url: CubeUrl::from_static("https://cube.demo.org/api/v1/"),
+            ui: Some(UiUrl::from_static("https://app.demo.org")),
+            description: "A demo".to_string(),
+        }]
+    }
+
+    async fn run(
+        options: &InitOptions,
+        prompter: &mut impl Prompter,
+        cube: &FakeCube,
+    ) -> Result<Setup> {
+        let mut setup = Setup::default();
+        choose_cube(&mut setup, options, prompter, &public_cubes())?;
+        choose_account(&mut setup, options, prompter)?;
+        log_in(&mut setup, options, prompter, cube).await?;
+        discover_ui(&mut setup, options, prompter, cube).await?;
+        Ok(setup)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_scripted_anonymous() {
+        let options = InitOptions {
+            cube: Some(CubeUrl::from_static("https://cube.example.org/api/v1/")),
+            anonymous: true,
+            ..Default::default()
+        };
+        let cube = FakeCube::default();
+        let mut setup = run(&options, &mut NonInteractive, &cube).await.unwrap();
+        probe_cube(&mut setup, &cube).await.unwrap();
+        let expected = Setup {
+            cube: options.cube.clone(),
+            ui: Some(UiUrl::from_static("https://app.example.org")),
+            version: Some("6.1.0".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(setup, expected);
+        assert_eq!(
+            *cube.requests.borrow(),
+            vec!["probe https://cube.example.org/api/v1/"]
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_scripted_login_without_password_fails() {
+        let options = InitOptions {
+            cube: Some(CubeUrl::from_static("https://cube.example.org/api/v1/")),
+            username: Some(Username::from_static("chris")),
+            ..Default::default()
+        };
+        let error = run(&options, &mut NonInteractive, &FakeCube::default())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("--password"), "{error}")
+    }
+
+    #[rstest]
+    #[case(None, Ok("chris"))]
+    #[case(Some("chris"), Ok("chris"))]
+    #[case(Some("alice"), Err("The token belongs to chris, not alice."))]
+    #[tokio::test]
+    async fn test_scripted_login_with_token(
+        #[case] username: Option<&str>,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let options = InitOptions {
+            cube: Some(CubeUrl::from_static("https://cube.example.org/api/v1/")),
+            username: username.map(Username::from),
+            token: Some("secret-token".to_string()),
+            ..Default::default()
+        };
+        let cube = FakeCube::default();
+        let actual = run(&options, &mut NonInteractive, &cube)
+            .await
+            .map(|setup| {
+                assert_eq!(setup.token.as_deref(), Some("secret-token"));
+                setup.username.unwrap().as_str().to_string()
+            })
+            .map_err(|e| e.to_string());
+        let expected = expected.map(String::from).map_err(String::from);
+        assert_eq!(actual, expected);
+        assert_eq!(*cube.requests.borrow(), vec!["check secret-token"]);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_scripted_picks_first_public_cube() {
+        let options = InitOptions {
+            anonymous: true,
+            ..Default::default()
+        };
+        let setup = run(&options, &mut NonInteractive, &FakeCube::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            setup.cube.unwrap().as_str(),
+            "https://cube.demo.org/api/v1/"
+        );
+        assert_eq!(setup.ui.unwrap().as_str(), "https://app.demo.org");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_interactive_login() {
+        let mut lines = Lines::new([
+            "2",                                // Other
+            "https://cube.example.org/api/v1/", // ChRIS API address
+            "",                                 // default: log in with a user account
+            "chris",                            // username
+            "chris1234",                        // password
+            "n",                                // do not use found ChRIS_ui
+            "",                                 // skip ChRIS_ui
+        ]);
+        let cube = FakeCube::default();
+        let setup = run(&InitOptions::default(), &mut lines, &cube)
+            .await
+            .unwrap();
+        assert_eq!(setup.username, Some(Username::from_static("chris")));
+        assert_eq!(setup.token.as_deref(), Some("secret-token"));
+        assert_eq!(setup.ui, None);
+        assert!(lines.0.is_empty());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_interactive_anonymous_by_name() {
+        let mut lines = Lines::new(["", ANONYMOUS]);
+        let setup = run(&InitOptions::default(), &mut lines, &FakeCube::default())
+            .await
+            .unwrap();
+        assert_eq!(setup.username, None);
+        assert_eq!(setup.token, None);
+        // UI of the public CUBE
+        assert_eq!(setup.ui.unwrap().as_str(), "https://app.demo.org");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_lines_exhausted_takes_defaults() {
+        let options = InitOptions {
+            anonymous: true,
+            ..Default::default()
+        };
+        let mut lines = Lines::new(Vec::<String>::new());
+        let setup = run(&options, &mut lines, &FakeCube::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            setup.cube.unwrap().as_str(),
+            "https://cube.demo.org/api/v1/"
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_wrong_password() {
+        let mut lines = Lines::new(["", "1", "chris", "wrong"]);
+        let error = run(&InitOptions::default(), &mut lines, &FakeCube::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Could not log in")
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_unreachable_cube() {
+        let mut setup = Setup {
+            cube: Some(CubeUrl::from_static("https://cube.nowhere.org/api/v1/")),
+            ..Default::default()
+        };
+        assert!(probe_cube(&mut setup, &FakeCube::default()).await.is_err())
+    }
+
+    #[rstest]
+    #[case("https://cube.example.org/api/v1/", vec!["https://app.example.org"])]
+    #[case("http://localhost:8000/api/v1/", vec!["http://localhost:8020"])]
+    #[case("https://example.org/api/v1/", vec![])]
+    fn test_guess_ui_urls(#[case] cube: &str, #[case] expected: Vec<&str>) {
+        let actual = guess_ui_urls(&CubeUrl::try_from(cube.to_string()).unwrap());
+        let actual: Vec<_> = actual.iter().map(|u| u.as_str()).collect();
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_sanity_checks_and_examples() {
+        let mut setup = Setup {
+            cube: Some(CubeUrl::from_static("https://cube.example.org/api/v1/")),
+            version: Some("3.0.0".to_string()),
+            username: Some(Username::from_static("chris")),
+            ..Default::default()
+        };
+        let checks = sanity_checks(&mut setup, &FakeCube::default())
+            .await
+            .unwrap();
+        assert!(matches!(checks[0], Check::Warn(_)));
+        assert_eq!(checks[1], Check::Pass("Plugins can be listed".to_string()));
+        let examples = example_commands(&setup);
+        assert_eq!(examples[1], "chrs describe pl-example");
+        assert_eq!(examples[0], "chrs list");
+    }
+}
@@ -1,126 +1,689 @@
 // There is a lot of code duplication in here, but it works for now.
 
+use std::collections::HashSet;
+use std::future::Future;
+
 use clap::Parser;
-use color_eyre::eyre;
 use color_eyre::eyre::{bail, Result};
 use color_eyre::owo_colors::OwoColorize;
-use futures::{future, future::Ready, TryStreamExt};
+use futures::{future, Stream, TryStreamExt};
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime};
 
 use chris::errors::CubeError;
+use chris::search::{FeedSearchBuilder, Search};
+use chris::types::Username;
 use chris::{Access, BaseChrisClient, ChrisClient, EitherClient, FeedResponse};
 
 use crate::credentials::{Credentials, NO_ARGS};
+use crate::login::state::ChrsSessions;
+use crate::output::{write_stream, OutputFormat};
+use crate::sanitize::sanitize_for_terminal;
+use crate::table::Fit;
+use crate::tag::resolve_tag;
 use crate::unicode;
+use selection::{FeedSort, NotShown, Selection};
+
+mod selection;
 
 #[derive(Parser)]
 pub struct ListFeedArgs {
-    /// Show only public feeds
+    /// Show only public feeds (default when not logged in)
     #[clap(long)]
     public: bool,
 
-    /// Show only private feeds
+    /// Show only private feeds (default when logged in)
     #[clap(long, conflicts_with = "public")]
     private: bool,
 
+    /// Show both public and private feeds, with a column indicating which are public
+    #[clap(long, conflicts_with_all = ["public", "private"])]
+    all: bool,
+
+    /// Show at most this many feeds (0 shows all)
+    #[clap(long, default_value_t = 20)]
+    limit: usize,
+
+    /// Show only feeds created by you, leaving out feeds which are shared with you
+    #[clap(long)]
+    mine: bool,
+
+    /// Show only feeds with errored or cancelled plugin instances
+    #[clap(long)]
+    errored: bool,
+
+    /// Show only feeds with plugin instances which are waiting or running
+    #[clap(long)]
+    running: bool,
+
+    /// Show only feeds where every plugin instance finished successfully
+    #[clap(long)]
+    finished: bool,
+
+    /// Sort feeds by creation or modification time (newest first), or by name,
+    /// instead of in the order given by ChRIS
+    #[clap(long, value_enum)]
+    sort: Option<FeedSort>,
+
+    /// Show only feeds with names containing this text, same as giving NAME
+    #[clap(long, value_name = "TEXT", conflicts_with = "name")]
+    search: Option<String>,
+
+    /// Show only feeds which have this tag, given by name or as tag/ID
+    #[clap(long)]
+    tag: Option<String>,
+
     /// Do not print header
     #[clap(short, long)]
     no_header: bool,
 
+    /// Do not shorten names to fit the width of the terminal
+    #[clap(long)]
+    no_ellipsis: bool,
+
+    /// Show only feeds created since the last time feeds were listed.
+    /// Feeds which are not shown, because of --limit or other filters, stay new.
+    #[clap(long)]
+    new: bool,
+
+    /// Show only feeds created at or after this time, e.g. 2024-01-31 or 2024-01-31T12:00:00Z.
+    /// Does not change when feeds were last listed.
+    #[clap(long, value_parser = parse_time)]
+    since: Option<OffsetDateTime>,
+
+    /// Show only feeds created at or before this time.
+    /// Does not change when feeds were last listed.
+    #[clap(long, value_parser = parse_time)]
+    until: Option<OffsetDateTime>,
+
     /// Feed name to filter by
     #[clap(default_value = "")]
     name: String,
 }
 
-pub async fn list_feeds(credentials: Credentials, args: ListFeedArgs) -> Result<()> {
+pub async fn list_feeds(
+    credentials: Credentials,
+    args: ListFeedArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let config_path = credentials.config_path.clone();
+    let ephemeral = credentials.ephemeral;
     let (client, _, _) = credentials.get_client(NO_ARGS).await?;
+    let started = OffsetDateTime::now_utc();
     match client {
-        EitherClient::Anon(c) => list_feeds_anon(c, args).await,
-        EitherClient::LoggedIn(c) => list_feeds_authed(c, args).await,
+        EitherClient::Anon(c) => {
+            if args.new {
+                bail!("Cannot list new feeds, not logged in.")
+            }
+            if args.tag.is_some() {
+                bail!("Cannot list tagged feeds, not logged in.")
+            }
+            let window = CreationWindow::new(&args, None);
+            list_feeds_anon(c, args, &window, output).await.map(|_| ())
+        }
+        EitherClient::LoggedIn(c) => {
+            let sessions = ChrsSessions::load(config_path.as_deref())?;
+            let last_listed = sessions.last_listed(c.url(), c.username());
+            if args.new && last_listed.is_none() && args.since.is_none() {
+                eprintln!("Feeds were not listed before, so all feeds are new.")
+            }
+            let window = CreationWindow::new(&args, last_listed);
+            let (url, username) = (c.url().clone(), c.username().clone());
+            let listing = list_feeds_authed(c, args, &window, output);
+            list_then_bookmark(&window, listing, || {
+                if !ephemeral {
+                    ChrsSessions::modify(config_path.as_deref(), |sessions| {
+                        sessions.set_last_listed(&url, &username, started);
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })
+            .await
+        }
+    }
+}
+
+/// Run a listing, and only if it succeeds and showed every feed, remember when the
+/// listing happened. Otherwise, the feeds which were left out because of filters or
+/// `--limit` would not be new the next time.
+async fn list_then_bookmark(
+    window: &CreationWindow,
+    listing: impl Future<Output = Result<NotShown>>,
+    bookmark: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    if listing.await?.is_none() && window.bookmark {
+        bookmark()?;
+    }
+    Ok(())
+}
+
+/// Range of creation times of the feeds to list.
+#[derive(Debug, PartialEq)]
+struct CreationWindow {
+    since: Option<OffsetDateTime>,
+    until: Option<OffsetDateTime>,
+    /// Whether to mark the listed feeds as new
+    mark_new: bool,
+    /// Whether to remember this listing as when feeds were last listed
+    bookmark: bool,
+}
+
+impl CreationWindow {
+    /// An explicit `--since` or `--until` overrides when feeds were last listed,
+    /// and does not count as a listing of new feeds. Neither does a listing of only
+    /// some of the feeds.
+    fn new(args: &ListFeedArgs, last_listed: Option<OffsetDateTime>) -> Self {
+        let explicit = args.since.is_some() || args.until.is_some();
+        Self {
+            since: args.since.or(last_listed.filter(|_| args.new)),
+            until: args.until,
+            mark_new: args.new,
+            bookmark: !explicit && args.is_unfiltered(),
+        }
+    }
+
+    /// Whether a feed created at `creation_date` is in this window.
+    fn contains(&self, creation_date: OffsetDateTime) -> bool {
+        self.since.is_none_or(|since| creation_date >= since)
+            && self.until.is_none_or(|until| creation_date <= until)
+    }
+
+    fn apply<A: Access>(&self, query: FeedSearchBuilder<A>) -> FeedSearchBuilder<A> {
+        let query = match self.since {
+            Some(since) => query.min_creation_date(since),
+            None => query,
+        };
+        match self.until {
+            Some(until) => query.max_creation_date(until),
+            None => query,
+        }
     }
 }
 
+/// Parse an RFC 3339 time, or a date which is taken to mean midnight UTC.
+pub(crate) fn parse_time(value: &str) -> std::result::Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(value, &Rfc3339)
+        .or_else(|_| {
+            Date::parse(value, format_description!("[year]-[month]-[day]"))
+                .map(|date| date.midnight().assume_utc())
+        })
+        .map_err(|_| format!("\"{value}\" is not a date (YYYY-MM-DD) or RFC 3339 time"))
+}
+
 async fn list_feeds_anon<A: Access>(
     client: impl BaseChrisClient<A>,
     args: ListFeedArgs,
-) -> Result<()> {
-    if args.private {
+    window: &CreationWindow,
+    output: OutputFormat,
+) -> Result<NotShown> {
+    if args.private || args.all {
         bail!("Cannot list private feeds, not logged in.")
     }
-    if !args.no_header {
-        println!(
-            "{:<13} {:<60}",
-            "ID".bold().underline(),
-            "Name".bold().underline()
-        );
+    if args.mine {
+        bail!("Cannot list your feeds, not logged in.")
     }
-    let search_builder = client.public_feeds().name(&args.name);
-    search_builder
-        .search()
-        .stream()
-        .try_for_each(print_feed_id_and_name)
-        .await
-        .map_err(eyre::Error::new)
+    let selection = selection_of(&args, None);
+    let search = window
+        .apply(client.public_feeds().name(args.name_filter()))
+        .search();
+    let (feeds, not_shown) = selection.select_from_stream(search.stream()).await?;
+    let not_shown = count_not_shown(&search, &selection, feeds.len(), not_shown).await?;
+    print_listing(&args, window, output, feeds, not_shown, false).await
 }
 
-fn print_feed_id_and_name(feed: FeedResponse) -> Ready<std::result::Result<(), CubeError>> {
-    println!("feed/{:<8} {}", feed.id.0.bold(), feed.name);
-    future::ok(())
+impl ListFeedArgs {
+    /// Feed name to filter by, given by `--search` or NAME.
+    fn name_filter(&self) -> &str {
+        self.search.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Whether every feed is listed, except for those left out by `--limit`
+    /// or the creation window.
+    fn is_unfiltered(&self) -> bool {
+        self.name_filter().is_empty()
+            && self.tag.is_none()
+            && !self.public
+            && !self.mine
+            && !self.errored
+            && !self.running
+            && !self.finished
+    }
+}
+
+/// Which feeds to show of those found by searching. If `username` is given, `--mine`
+/// shows only feeds created by them.
+fn selection_of(args: &ListFeedArgs, username: Option<&Username>) -> Selection {
+    Selection {
+        creator: username.filter(|_| args.mine).cloned(),
+        errored: args.errored,
+        running: args.running,
+        finished: args.finished,
+        sort: args.sort,
+        limit: Some(args.limit).filter(|limit| *limit > 0),
+    }
+}
+
+/// Count the feeds which were not shown using the count of a search, which is
+/// possible if the feeds were not filtered by the client.
+async fn count_not_shown<A: Access>(
+    search: &Search<FeedResponse, A>,
+    selection: &Selection,
+    shown: usize,
+    not_shown: NotShown,
+) -> Result<NotShown, CubeError> {
+    if not_shown != NotShown::Unknown || selection.is_filtering() {
+        return Ok(not_shown);
+    }
+    let count = search.get_count().await?;
+    Ok(NotShown::Exactly(count.saturating_sub(shown)))
 }
 
-async fn list_feeds_authed(client: ChrisClient, args: ListFeedArgs) -> Result<()> {
-    if args.public {
-        list_feeds_anon(client, args).await
-    } else if args.private {
-        list_feeds_private(client, args).await
+/// Print feeds, then print how many feeds were not shown to stderr.
+/// Returns how many feeds were not shown.
+async fn print_listing(
+    args: &ListFeedArgs,
+    window: &CreationWindow,
+    output: OutputFormat,
+    feeds: Vec<FeedResponse>,
+    not_shown: NotShown,
+    show_public: bool,
+) -> Result<NotShown> {
+    if output.is_human() {
+        print_feeds(args, window, &feeds, show_public);
     } else {
-        list_feeds_public_and_private(client, args).await
+        let stream = futures::stream::iter(feeds.into_iter().map(Ok::<_, CubeError>));
+        write_stream(stream, output).await?;
     }
+    if let Some(footer) = not_shown.footer() {
+        eprintln!("{}", footer.dimmed());
+    }
+    Ok(not_shown)
 }
 
-async fn list_feeds_private(client: ChrisClient, args: ListFeedArgs) -> Result<()> {
-    if !args.no_header {
-        println!(
-            "{:<13} {:<60}",
-            "ID".bold().underline(),
-            "Name".bold().underline(),
-        );
+fn print_feeds(
+    args: &ListFeedArgs,
+    window: &CreationWindow,
+    feeds: &[FeedResponse],
+    show_public: bool,
+) {
+    if show_public {
+        if !args.no_header {
+            println!(
+                "{:<13} {:<60} {}",
+                "ID".bold().underline(),
+                "Name".bold().underline(),
+                "Public?".bold().underline()
+            );
+        }
+        let fit = fit_names(args, window, &[ID_WIDTH, PUBLIC_WIDTH]);
+        for feed in feeds {
+            print_public_or_private(feed, window.mark_new, fit)
+        }
+    } else {
+        if !args.no_header {
+            println!(
+                "{:<13} {:<60}",
+                "ID".bold().underline(),
+                "Name".bold().underline()
+            );
+        }
+        let fit = fit_names(args, window, &[ID_WIDTH]);
+        for feed in feeds {
+            print_feed_id_and_name(feed, window.mark_new, fit)
+        }
     }
-    let private_feeds = client.feeds().name(&args.name);
-    private_feeds
-        .search()
-        .stream()
-        .try_for_each(print_feed_id_and_name)
-        .await
-        .map_err(eyre::Error::new)
-}
-
-async fn list_feeds_public_and_private(client: ChrisClient, args: ListFeedArgs) -> Result<()> {
-    let public_feeds_builder = client.public_feeds().name(&args.name);
-    let public_feeds = public_feeds_builder.search();
-    let private_feeds_builder = client.feeds().name(&args.name);
-    let private_feeds = private_feeds_builder.search();
-    let stream = tokio_stream::StreamExt::merge(public_feeds.stream(), private_feeds.stream());
-    if !args.no_header {
-        println!(
-            "{:<13} {:<60} {}",
-            "ID".bold().underline(),
-            "Name".bold().underline(),
-            "Public?".bold().underline()
-        );
+}
+
+/// Width of "feed/{id}" column
+const ID_WIDTH: usize = 13;
+/// Width of "Public?" column
+const PUBLIC_WIDTH: usize = 7;
+
+/// Fit feed names to the terminal, next to columns of the given `widths`.
+fn fit_names(args: &ListFeedArgs, window: &CreationWindow, widths: &[usize]) -> Fit {
+    let fit = Fit::to_terminal(args.no_ellipsis, widths);
+    if window.mark_new {
+        fit.reserve(" NEW".len())
+    } else {
+        fit
     }
-    stream
-        .try_for_each(print_public_or_private)
-        .await
-        .map_err(eyre::Error::new)
 }
 
-fn print_public_or_private(feed: FeedResponse) -> Ready<std::result::Result<(), CubeError>> {
-    let is_public = if feed.public { unicode::CHECK_MARK } else { "" };
+fn print_feed_id_and_name(feed: &FeedResponse, mark_new: bool, fit: Fit) {
     println!(
-        "feed/{:<8} {:<60} {}",
+        "feed/{:<8} {}{}",
         feed.id.0.bold(),
-        feed.name,
-        is_public.bold().green()
+        fit.fit(&sanitize_for_terminal(&feed.name)),
+        new_marker(mark_new)
     );
-    future::ok(())
+}
+
+fn new_marker(mark_new: bool) -> String {
+    if mark_new {
+        format!(" {}", "NEW".bold().yellow())
+    } else {
+        String::new()
+    }
+}
+
+async fn list_feeds_authed(
+    client: ChrisClient,
+    args: ListFeedArgs,
+    window: &CreationWindow,
+    output: OutputFormat,
+) -> Result<NotShown> {
+    if let Some(tag) = &args.tag {
+        list_tagged_feeds(&client, tag, &args, window, output).await
+    } else if args.public {
+        list_feeds_anon(client, args, window, output).await
+    } else if args.all {
+        list_feeds_public_and_private(client, args, window, output).await
+    } else {
+        list_feeds_private(client, args, window, output).await
+    }
+}
+
+/// List the feeds which have the tag `given`. The feeds of a tag cannot be searched,
+/// so `--public`, the name filter, and the creation window are applied here.
+async fn list_tagged_feeds(
+    client: &ChrisClient,
+    given: &str,
+    args: &ListFeedArgs,
+    window: &CreationWindow,
+    output: OutputFormat,
+) -> Result<NotShown> {
+    let tag = resolve_tag(client, given).await?;
+    let selection = selection_of(args, Some(client.username()));
+    let name = args.name_filter().to_lowercase();
+    let search = tag.feeds();
+    let feeds = search.stream().try_filter(|feed| {
+        future::ready(
+            (!args.public || feed.is_public())
+                && feed.name.to_lowercase().contains(&name)
+                && window.contains(feed.creation_date),
+        )
+    });
+    let (feeds, not_shown) = selection.select_from_stream(feeds).await?;
+    print_listing(args, window, output, feeds, not_shown, args.all).await
+}
+
+async fn list_feeds_private(
+    client: ChrisClient,
+    args: ListFeedArgs,
+    window: &CreationWindow,
+    output: OutputFormat,
+) -> Result<NotShown> {
+    let selection = selection_of(&args, Some(client.username()));
+    let search = window
+        .apply(client.feeds().name(args.name_filter()))
+        .search();
+    let (feeds, not_shown) = selection.select_from_stream(search.stream()).await?;
+    let not_shown = count_not_shown(&search, &selection, feeds.len(), not_shown).await?;
+    print_listing(&args, window, output, feeds, not_shown, false).await
+}
+
+async fn list_feeds_public_and_private(
+    client: ChrisClient,
+    args: ListFeedArgs,
+    window: &CreationWindow,
+    output: OutputFormat,
+) -> Result<NotShown> {
+    let selection = selection_of(&args, Some(client.username()));
+    let public_feeds = window
+        .apply(client.public_feeds().name(args.name_filter()))
+        .search();
+    let private_feeds = window
+        .apply(client.feeds().name(args.name_filter()))
+        .search();
+    let stream = merge_feeds(public_feeds.stream(), private_feeds.stream());
+    let (feeds, not_shown) = selection.select_from_stream(stream).await?;
+    print_listing(&args, window, output, feeds, not_shown, true).await
+}
+
+/// Merge streams of public and private feeds, leaving out the second occurrence of
+/// feeds which are in both, i.e. public feeds of the user.
+fn merge_feeds<E>(
+    public_feeds: impl Stream<Item = std::result::Result<FeedResponse, E>>,
+    private_feeds: impl Stream<Item = std::result::Result<FeedResponse, E>>,
+) -> impl Stream<Item = std::result::Result<FeedResponse, E>> {
+    let mut seen = HashSet::new();
+    tokio_stream::StreamExt::merge(public_feeds, private_feeds)
+        .try_filter(move |feed| future::ready(seen.insert(feed.id.0)))
+}
+
+fn print_public_or_private(feed: &FeedResponse, mark_new: bool, fit: Fit) {
+    let is_public = if feed.is_public() {
+        unicode::CHECK_MARK
+    } else {
+        ""
+    };
+    println!(
+        "feed/{:<8} {} {:<7}{}",
+        feed.id.0.bold(),
+        fit.fit_and_pad(&sanitize_for_terminal(&feed.name), 60),
+        is_public.bold().green(),
+        new_marker(mark_new)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use color_eyre::eyre;
+    use rstest::*;
+    use std::cell::Cell;
+    use time::macros::datetime;
+
+    fn args(
+        new: bool,
+        since: Option<OffsetDateTime>,
+        until: Option<OffsetDateTime>,
+    ) -> ListFeedArgs {
+        ListFeedArgs {
+            public: false,
+            private: false,
+            all: false,
+            limit: 20,
+            mine: false,
+            errored: false,
+            running: false,
+            finished: false,
+            sort: None,
+            search: None,
+            tag: None,
+            no_header: false,
+            no_ellipsis: false,
+            new,
+            since,
+            until,
+            name: "".to_string(),
+        }
+    }
+
+    const LAST: OffsetDateTime = datetime!(2024-01-02 03:04:05 UTC);
+    const GIVEN: OffsetDateTime = datetime!(2023-06-01 00:00 UTC);
+
+    #[rstest]
+    #[case(args(false, None, None), Some(LAST), None, None, false, true)]
+    #[case(args(true, None, None), Some(LAST), Some(LAST), None, true, true)]
+    #[case(args(true, None, None), None, None, None, true, true)]
+    #[case(
+        args(true, Some(GIVEN), None),
+        Some(LAST),
+        Some(GIVEN),
+        None,
+        true,
+        false
+    )]
+    #[case(
+        args(false, None, Some(GIVEN)),
+        Some(LAST),
+        None,
+        Some(GIVEN),
+        false,
+        false
+    )]
+    fn test_creation_window(
+        #[case] args: ListFeedArgs,
+        #[case] last_listed: Option<OffsetDateTime>,
+        #[case] since: Option<OffsetDateTime>,
+        #[case] until: Option<OffsetDateTime>,
+        #[case] mark_new: bool,
+        #[case] bookmark: bool,
+    ) {
+        let expected = CreationWindow {
+            since,
+            until,
+            mark_new,
+            bookmark,
+        };
+        assert_eq!(CreationWindow::new(&args, last_listed), expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_bookmark_after_successful_listing() {
+        let bookmarked = Cell::new(false);
+        let window = CreationWindow::new(&args(false, None, None), None);
+        list_then_bookmark(&window, async { Ok(NotShown::None) }, || {
+            bookmarked.set(true);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert!(bookmarked.get())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_no_bookmark_after_failed_listing() {
+        let bookmarked = Cell::new(false);
+        let window = CreationWindow::new(&args(false, None, None), None);
+        let listing = async { Err(eyre::eyre!("connection reset")) };
+        let result = list_then_bookmark(&window, listing, || {
+            bookmarked.set(true);
+            Ok(())
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(!bookmarked.get())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_no_bookmark_after_truncated_listing() {
+        let mock = MockCube::start().await;
+        for id in 1..=30 {
+            mock.add_feed(mock.feed(id, &format!("feed {id}")));
+        }
+        let client = mock.client("chris").await;
+        let args = args(true, None, None);
+        let window = CreationWindow::new(&args, None);
+        let bookmarked = Cell::new(false);
+        let listing = list_feeds_authed(client, args, &window, OutputFormat::Plain);
+        list_then_bookmark(&window, listing, || {
+            bookmarked.set(true);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert!(!bookmarked.get())
+    }
+
+    fn filtered(change: impl FnOnce(&mut ListFeedArgs)) -> ListFeedArgs {
+        let mut args = args(true, None, None);
+        change(&mut args);
+        args
+    }
+
+    #[rstest]
+    #[case(filtered(|_| ()), true)]
+    #[case(filtered(|a| a.name = "brain".to_string()), false)]
+    #[case(filtered(|a| a.search = Some("brain".to_string())), false)]
+    #[case(filtered(|a| a.public = true), false)]
+    #[case(filtered(|a| a.tag = Some("brain".to_string())), false)]
+    #[case(filtered(|a| a.mine = true), false)]
+    #[case(filtered(|a| a.errored = true), false)]
+    #[case(filtered(|a| a.running = true), false)]
+    #[case(filtered(|a| a.finished = true), false)]
+    #[tokio::test]
+    async fn test_bookmark_only_when_every_feed_is_listed(
+        #[case] args: ListFeedArgs,
+        #[case] expected: bool,
+    ) {
+        let mock = MockCube::start().await;
+        mock.add_tagged_feeds();
+        let client = mock.client("chris").await;
+        let window = CreationWindow::new(&args, None);
+        let bookmarked = Cell::new(false);
+        let listing = list_feeds_authed(client, args, &window, OutputFormat::Plain);
+        list_then_bookmark(&window, listing, || {
+            bookmarked.set(true);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(bookmarked.get(), expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_merge_feeds() {
+        let mock = MockCube::start().await;
+        let feed = |id, public| FeedResponse {
+            public: Some(public),
+            ..mock.feed(id, &format!("feed {}", id))
+        };
+        let public_feeds = [feed(1, true), feed(3, true), feed(4, true)];
+        let private_feeds = [feed(2, false), feed(3, true)];
+        let merged: Vec<_> = merge_feeds(
+            futures::stream::iter(public_feeds.map(Ok::<_, CubeError>)),
+            futures::stream::iter(private_feeds.map(Ok)),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+        let ids: HashSet<_> = merged.iter().map(|f| f.id.0).collect();
+        assert_eq!(merged.len(), 4);
+        assert_eq!(ids, HashSet::from([1, 2, 3, 4]), "feeds are not duplicated");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_list_tagged_feeds() {
+        let mock = MockCube::start().await;
+        mock.add_tagged_feeds();
+        let client = mock.client("chris").await;
+        let args = ListFeedArgs {
+            tag: Some("brain".to_string()),
+            ..args(false, None, None)
+        };
+        let window = CreationWindow::new(&args, None);
+        list_feeds_authed(client, args, &window, OutputFormat::Plain)
+            .await
+            .unwrap();
+        let requests = mock.requests();
+        assert!(
+            requests
+                .iter()
+                .any(|r| r.starts_with("/api/v1/tags/1/feeds/")),
+            "{requests:?}"
+        );
+        assert!(
+            !requests.iter().any(|r| r.contains("taggings")),
+            "{requests:?}"
+        );
+    }
+
+    #[rstest]
+    #[case("2024-01-31", Some(datetime!(2024-01-31 00:00 UTC)))]
+    #[case("2024-01-31T12:30:00Z", Some(datetime!(2024-01-31 12:30 UTC)))]
+    #[case("2024-01-31T12:30:00-05:00", Some(datetime!(2024-01-31 12:30 -5)))]
+    #[case("yesterday", None)]
+    fn test_parse_time(#[case] value: &str, #[case] expected: Option<OffsetDateTime>) {
+        assert_eq!(parse_time(value).ok(), expected)
+    }
 }
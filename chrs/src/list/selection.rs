@@ -0,0 +1,276 @@
+//! Filtering, sorting, and limiting the feeds of `chrs list`.
+
+use std::cmp::Reverse;
+
+use futures::{future, Stream, StreamExt, TryStreamExt};
+
+use chris::types::Username;
+use chris::FeedResponse;
+
+/// Order of the feeds of `chrs list`.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq)]
+pub enum FeedSort {
+    /// Newest first
+    Created,
+    /// Most recently modified first
+    Modified,
+    /// Alphabetically by name
+    Name,
+}
+
+/// Which feeds to show, in what order, and how many of them.
+#[derive(Debug, Default)]
+pub(super) struct Selection {
+    /// Show only feeds created by this user
+    pub creator: Option<Username>,
+    pub errored: bool,
+    pub running: bool,
+    pub finished: bool,
+    /// Sort feeds instead of keeping the order of _CUBE_
+    pub sort: Option<FeedSort>,
+    /// Maximum number of feeds to show, or `None` to show all
+    pub limit: Option<usize>,
+}
+
+/// Number of feeds which were not shown because of the limit.
+#[derive(Debug, PartialEq)]
+pub(super) enum NotShown {
+    None,
+    Exactly(usize),
+    /// At least one
+    Unknown,
+}
+
+impl Selection {
+    /// Whether feeds are filtered here instead of by _CUBE_.
+    pub fn is_filtering(&self) -> bool {
+        self.creator.is_some() || self.errored || self.running || self.finished
+    }
+
+    /// Whether a feed should be shown. A feed is shown if it has any of the
+    /// statuses asked for.
+    pub fn matches(&self, feed: &FeedResponse) -> bool {
+        if self
+            .creator
+            .as_ref()
+            .is_some_and(|creator| &feed.creator_username != creator)
+        {
+            return false;
+        }
+        if !(self.errored || self.running || self.finished) {
+            return true;
+        }
+        (self.errored && feed.has_errored_job())
+            || (self.running && feed.has_unfinished_jobs())
+            || (self.finished && !feed.has_errored_job() && !feed.has_unfinished_jobs())
+    }
+
+    /// Select from all feeds.
+    pub fn select_from(&self, feeds: Vec<FeedResponse>) -> (Vec<FeedResponse>, NotShown) {
+        let mut feeds: Vec<_> = feeds.into_iter().filter(|f| self.matches(f)).collect();
+        if let Some(sort) = self.sort {
+            sort_feeds(&mut feeds, sort);
+        }
+        let not_shown = match self.limit {
+            Some(limit) if feeds.len() > limit => {
+                let count = feeds.len() - limit;
+                feeds.truncate(limit);
+                NotShown::Exactly(count)
+            }
+            _ => NotShown::None,
+        };
+        (feeds, not_shown)
+    }
+
+    /// Select from a stream of feeds. Unless the feeds are sorted, the stream is
+    /// only consumed until the limit is exceeded.
+    pub async fn select_from_stream<E>(
+        &self,
+        feeds: impl Stream<Item = Result<FeedResponse, E>>,
+    ) -> Result<(Vec<FeedResponse>, NotShown), E> {
+        if self.sort.is_some() {
+            let feeds = feeds.try_collect().await?;
+            return Ok(self.select_from(feeds));
+        }
+        let take = self.limit.map(|limit| limit + 1).unwrap_or(usize::MAX);
+        let mut feeds: Vec<_> = feeds
+            .try_filter(|f| future::ready(self.matches(f)))
+            .take(take)
+            .try_collect()
+            .await?;
+        let not_shown = match self.limit {
+            Some(limit) if feeds.len() > limit => {
+                feeds.truncate(limit);
+                NotShown::Unknown
+            }
+            _ => NotShown::None,
+        };
+        Ok((feeds, not_shown))
+    }
+}
+
+fn sort_feeds(feeds: &mut [FeedResponse], sort: FeedSort) {
+    match sort {
+        FeedSort::Created => feeds.sort_by_key(|f| Reverse((f.creation_date, f.id.0))),
+        FeedSort::Modified => feeds.sort_by_key(|f| Reverse((f.modification_date, f.id.0))),
+        FeedSort::Name => feeds.sort_by_cached_key(|f| (f.name.to_lowercase(), f.id.0)),
+    }
+}
+
+impl NotShown {
+    /// Whether every feed was shown.
+    pub fn is_none(&self) -> bool {
+        matches!(self, NotShown::None | NotShown::Exactly(0))
+    }
+
+    /// A note saying how many feeds were not shown.
+    pub fn footer(&self) -> Option<String> {
+        match self {
+            NotShown::None => None,
+            NotShown::Exactly(count) => Some(format!(
+                "{} more feed{} not shown. Use --limit to show more.",
+                count,
+                if *count == 1 { " was" } else { "s were" }
+            )),
+            NotShown::Unknown => {
+                Some("More feeds were not shown. Use --limit to show more.".to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use rstest::*;
+    use time::macros::datetime;
+    use time::Duration;
+
+    #[fixture]
+    async fn feeds() -> Vec<FeedResponse> {
+        let mock = MockCube::start().await;
+        let feed = |id, name, creator: &str, day: i64, jobs: [u32; 3]| {
+            let [running, finished, errored] = jobs;
+            FeedResponse {
+                creator_username: Username::from(creator),
+                creation_date: datetime!(2024-02-01 0:00 UTC) + Duration::days(day),
+                modification_date: datetime!(2024-03-01 0:00 UTC) + Duration::days(30 - day),
+                started_jobs: running,
+                finished_jobs: finished,
+                errored_jobs: errored,
+                ..mock.feed(id, name)
+            }
+        };
+        vec![
+            feed(1, "brain", "bob", 3, [0, 2, 0]),
+            feed(2, "Lungs", "alice", 1, [1, 1, 0]),
+            feed(3, "abdomen", "bob", 4, [0, 1, 1]),
+            feed(4, "heart", "alice", 2, [0, 3, 0]),
+        ]
+    }
+
+    fn ids(feeds: &[FeedResponse]) -> Vec<u32> {
+        feeds.iter().map(|f| f.id.0).collect()
+    }
+
+    #[rstest]
+    #[case(Selection::default(), vec![1, 2, 3, 4])]
+    #[case(Selection { errored: true, ..Default::default() }, vec![3])]
+    #[case(Selection { running: true, ..Default::default() }, vec![2])]
+    #[case(Selection { finished: true, ..Default::default() }, vec![1, 4])]
+    #[case(Selection { errored: true, running: true, ..Default::default() }, vec![2, 3])]
+    #[case(Selection { creator: Some(Username::from("alice")), ..Default::default() }, vec![2, 4])]
+    #[case(
+        Selection { creator: Some(Username::from("alice")), finished: true, ..Default::default() },
+        vec![4]
+    )]
+    #[tokio::test]
+    async fn test_filter(
+        #[future] feeds: Vec<FeedResponse>,
+        #[case] selection: Selection,
+        #[case] expected: Vec<u32>,
+    ) {
+        let (actual, not_shown) = selection.select_from(feeds.await);
+        assert_eq!(ids(&actual), expected);
+        assert_eq!(not_shown, NotShown::None);
+    }
+
+    #[rstest]
+    #[case(FeedSort::Created, vec![3, 1, 4, 2])]
+    #[case(FeedSort::Modified, vec![2, 4, 1, 3])]
+    #[case(FeedSort::Name, vec![3, 1, 4, 2])]
+    #[tokio::test]
+    async fn test_sort(
+        #[future] feeds: Vec<FeedResponse>,
+        #[case] sort: FeedSort,
+        #[case] expected: Vec<u32>,
+    ) {
+        let selection = Selection {
+            sort: Some(sort),
+            ..Default::default()
+        };
+        let (actual, _) = selection.select_from(feeds.await);
+        assert_eq!(ids(&actual), expected);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_sort_then_limit(#[future] feeds: Vec<FeedResponse>) {
+        let selection = Selection {
+            sort: Some(FeedSort::Name),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let (actual, not_shown) = selection.select_from(feeds.await);
+        assert_eq!(ids(&actual), vec![3]);
+        assert_eq!(not_shown, NotShown::Exactly(3));
+    }
+
+    #[rstest]
+    #[case(false, Some(1), vec![1], NotShown::Unknown, 2)]
+    #[case(true, Some(1), vec![1], NotShown::Unknown, 4)]
+    #[case(true, Some(2), vec![1, 4], NotShown::None, 4)]
+    #[case(true, None, vec![1, 4], NotShown::None, 4)]
+    #[tokio::test]
+    async fn test_select_from_stream_stops_early(
+        #[future] feeds: Vec<FeedResponse>,
+        #[case] finished: bool,
+        #[case] limit: Option<usize>,
+        #[case] expected: Vec<u32>,
+        #[case] expected_not_shown: NotShown,
+        #[case] expected_pulled: usize,
+    ) {
+        let selection = Selection {
+            finished,
+            limit,
+            ..Default::default()
+        };
+        let pulled = std::cell::Cell::new(0);
+        let stream = futures::stream::iter(feeds.await)
+            .inspect(|_| pulled.set(pulled.get() + 1))
+            .map(Ok::<_, std::convert::Infallible>);
+        let (actual, not_shown) = selection.select_from_stream(stream).await.unwrap();
+        assert_eq!(ids(&actual), expected);
+        assert_eq!(not_shown, expected_not_shown);
+        assert_eq!(pulled.get(), expected_pulled);
+    }
+
+    #[rstest]
+    #[case(NotShown::None, None)]
+    #[case(
+        NotShown::Exactly(1),
+        Some("1 more feed was not shown. Use --limit to show more.")
+    )]
+    #[case(
+        NotShown::Exactly(5),
+        Some("5 more feeds were not shown. Use --limit to show more.")
+    )]
+    #[case(
+        NotShown::Unknown,
+        Some("More feeds were not shown. Use --limit to show more.")
+    )]
+    fn test_footer(#[case] not_shown: NotShown, #[case] expected: Option<&str>) {
+        assert_eq!(not_shown.footer().as_deref(), expected)
+    }
+}
@@ -1,10 +1,11 @@
 mod cd;
 pub mod cmd;
 mod prompt;
+pub mod public;
 pub mod state;
 pub mod store;
 pub mod switch;
 mod ui;
 
-pub use cd::set_cd;
+pub use cd::{clear_cd, set_cd};
 pub use ui::*;
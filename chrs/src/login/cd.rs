@@ -1,17 +1,51 @@
 use super::state::ChrsSessions;
+use crate::credentials::Credentials;
 use chris::types::{CubeUrl, PluginInstanceId, Username};
 use color_eyre::eyre;
+use color_eyre::owo_colors::OwoColorize;
 use std::path::PathBuf;
 
+/// Clear the current plugin instance of a saved session.
+pub fn clear_cd(
+    Credentials {
+        cube_url,
+        username,
+        config_path,
+        ephemeral,
+        ..
+    }: Credentials,
+) -> eyre::Result<()> {
+    if ephemeral {
+        return Ok(());
+    }
+    let cleared = ChrsSessions::modify(config_path.as_deref(), |sessions| {
+        Ok(sessions.clear_plugin_instance(cube_url.as_ref(), username.as_ref()))
+    })?;
+    if cleared.is_none() {
+        eprintln!("{}", "There was no current plugin instance.".dimmed());
+    }
+    Ok(())
+}
+
+/// Set the current plugin instance of a saved session.
+///
+/// Does nothing if `ephemeral`, i.e. the session should not be saved.
 pub fn set_cd(
     cube_url: &CubeUrl,
     username: &Username,
     id: PluginInstanceId,
     config_path: Option<PathBuf>,
+    ephemeral: bool,
 ) -> eyre::Result<()> {
-    let mut sessions = ChrsSessions::load(config_path.as_deref())?;
-    if sessions.set_plugin_instance(cube_url, username, id) {
-        sessions.save(config_path.as_deref())?;
+    if ephemeral {
+        eprintln!(
+            "{}",
+            "Current plugin instance is not saved because the token was read from a file.".dimmed()
+        );
+        return Ok(());
     }
-    Ok(())
+    ChrsSessions::modify(config_path.as_deref(), |sessions| {
+        sessions.set_plugin_instance(cube_url, username, id);
+        Ok(())
+    })
 }
@@ -8,6 +8,7 @@ use chris::{
 };
 use color_eyre::eyre::{bail, Context, Result};
 use color_eyre::owo_colors::OwoColorize;
+use std::path::Path;
 
 pub async fn login(
     Credentials {
@@ -17,11 +18,21 @@ pub async fn login(
         token,
         ui,
         config_path,
+        ephemeral,
         ..
     }: Credentials,
     backend: store::Backend,
     password_from_stdin: bool,
+    token_from_stdin: bool,
+    remember_password: bool,
 ) -> Result<()> {
+    if ephemeral {
+        bail!(
+            "A token read from a file is never saved. Use {} instead of {} to log in.",
+            "--token".bold(),
+            "--token-file".bold()
+        );
+    }
     if password.is_some() && password_from_stdin {
         bail!(
             "Options {} and {} may not be used together.",
@@ -30,18 +41,46 @@ pub async fn login(
         );
     }
 
-    let mut config = ChrsSessions::load(config_path.as_deref())?;
     let cube = prompt_if_missing(cube_url, "ChRIS API address")?;
-    let username = prompt_if_missing(username, "username")?;
+    // a password takes precedence over a token
+    let token = if password.is_some() || password_from_stdin {
+        None
+    } else if token_from_stdin {
+        let token: String = prompt_if_missing_password(None, "token", true)?;
+        Some(token.trim().to_string())
+    } else {
+        token
+    };
 
-    let token = if username.as_str().is_empty() {
-        login_anonymous(&cube).await
-    } else if let Some(token) = token {
-        login_with_token(&cube, &username, &token).await
+    let mut password_to_remember = None;
+    let (username, token) = if let Some(token) = token {
+        if remember_password {
+            bail!(
+                "{} can only be used when logging in with a password.",
+                "--remember-password".bold()
+            );
+        }
+        let username = login_with_token(&cube, username, &token).await?;
+        (username, Some(token))
     } else {
-        let password = prompt_if_missing_password(password, "password", password_from_stdin)?;
-        login_with_password(&cube, &username, &password).await
-    }?;
+        let username = prompt_if_missing(username, "username")?;
+        if username.as_str().is_empty() {
+            if remember_password {
+                bail!(
+                    "{} can only be used when logging in with a password.",
+                    "--remember-password".bold()
+                );
+            }
+            (username, login_anonymous(&cube).await?)
+        } else {
+            let password = prompt_if_missing_password(password, "password", password_from_stdin)?;
+            let token = login_with_password(&cube, &username, &password).await?;
+            if remember_password {
+                password_to_remember = Some(password);
+            }
+            (username, token)
+        }
+    };
 
     let login = store::CubeState {
         cube,
@@ -50,19 +89,34 @@ pub async fn login(
         current_plugin_instance_id: None,
         ui,
     };
+    save_login(config_path.as_deref(), login, backend, password_to_remember)
+}
 
-    config.add(login, backend)?;
-    config.save(config_path.as_deref())
+/// Save a login to the configuration file, and remember its password if given.
+pub(crate) fn save_login(
+    config_path: Option<&Path>,
+    login: store::CubeState,
+    backend: store::Backend,
+    password_to_remember: Option<String>,
+) -> Result<()> {
+    let (cube, username) = (login.cube.clone(), login.username.clone());
+    ChrsSessions::modify(config_path, |config| {
+        config.add(login, backend)?;
+        if let Some(password) = password_to_remember {
+            config.remember_password(&cube, &username, &password)?;
+        }
+        Ok(())
+    })
 }
 
 /// Contact CUBE just to make sure CUBE is reachable.
-async fn login_anonymous(cube_url: &CubeUrl) -> Result<Option<String>> {
+pub(super) async fn login_anonymous(cube_url: &CubeUrl) -> Result<Option<String>> {
     AnonChrisClient::build(cube_url.clone())?.connect().await?;
     Ok(None)
 }
 
 /// Login to CUBE by getting a token using a password.
-async fn login_with_password(
+pub(crate) async fn login_with_password(
     cube_url: &CubeUrl,
     username: &Username,
     password: &str,
@@ -78,17 +132,36 @@ async fn login_with_password(
     Ok(Some(token))
 }
 
-/// Verify token works for the CUBE.
-async fn login_with_token(
+/// Verify token works for the CUBE, and get the username of its user.
+///
+/// If `username` is given, check that the token belongs to that user.
+pub(crate) async fn login_with_token(
     cube_url: &CubeUrl,
-    username: &Username,
+    username: Option<Username>,
     token: &str,
-) -> Result<Option<String>> {
-    ChrisClient::build(cube_url.clone(), username.clone(), token)?
-        .connect()
-        .await
-        .wrap_err_with(|| format!("Invalid token for {cube_url}"))?;
-    Ok(Some(token.to_string()))
+) -> Result<Username> {
+    let invalid = || format!("Invalid token for {cube_url}");
+    let client = if let Some(username) = username {
+        let client = ChrisClient::build(cube_url.clone(), username, token)?
+            .connect()
+            .await
+            .wrap_err_with(invalid)?;
+        let user = client.user().await.wrap_err_with(invalid)?;
+        if let Some(user) = user.filter(|u| &u.object.username != client.username()) {
+            bail!(
+                "The token belongs to {}, not {}.",
+                user.object.username.as_str().bold(),
+                client.username().as_str().bold()
+            );
+        }
+        client
+    } else {
+        ChrisClient::build_from_token(cube_url.clone(), token)?
+            .connect()
+            .await
+            .wrap_err_with(invalid)?
+    };
+    Ok(client.username().clone())
 }
 
 pub fn logout(
@@ -99,17 +172,18 @@ pub fn logout(
         ..
     }: Credentials,
 ) -> Result<()> {
-    let mut config = ChrsSessions::load(config_path.as_deref())?;
-    if let Some(url) = cube_url {
-        let removed = match username {
-            Some(u) => config.remove(&url, Some(&u)),
-            None => config.remove(&url, None),
-        };
-        if !removed {
+    ChrsSessions::modify(config_path.as_deref(), |config| {
+        if let Some(url) = cube_url {
+            let removed = match username {
+                Some(u) => config.remove(&url, Some(&u)),
+                None => config.remove(&url, None),
+            };
+            if !removed {
+                bail!("Not logged in.");
+            }
+        } else if !config.clear() {
             bail!("Not logged in.");
         }
-    } else if !config.clear() {
-        bail!("Not logged in.");
-    }
-    config.save(config_path.as_deref())
+        Ok(())
+    })
 }
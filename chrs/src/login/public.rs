@@ -0,0 +1,116 @@
+//! Well-known public instances of _CUBE_, see `chrs login --public`.
+
+use chris::types::CubeUrl;
+use color_eyre::eyre::{bail, Result};
+use dialoguer::console::Term;
+use dialoguer::{theme::ColorfulTheme, Select};
+use serde::{Deserialize, Serialize};
+
+use super::state::ChrsSessions;
+use super::store::{Backend, CubeState};
+use super::UiUrl;
+use crate::credentials::Credentials;
+
+/// Built-in list of public CUBEs: name, API URL, UI URL, and description.
+const WELL_KNOWN_CUBES: &[(&str, &str, &str, &str)] = &[(
+    "ChRIS demo",
+    "https://cube.chrisproject.org/api/v1/",
+    "https://app.chrisproject.org",
+    "Public demo of ChRIS, hosted by the ChRIS project",
+)];
+
+/// A _CUBE_ which can be used without an account.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
+pub struct PublicCube {
+    pub name: String,
+    pub url: CubeUrl,
+    pub ui: Option<UiUrl>,
+    pub description: String,
+}
+
+/// Get the public CUBEs listed in the configuration file, or the built-in list if none are.
+pub fn public_cubes(config: &ChrsSessions) -> Vec<PublicCube> {
+    if !config.public_cubes.is_empty() {
+        return config.public_cubes.clone();
+    }
+    WELL_KNOWN_CUBES
+        .iter()
+        .map(|(name, url, ui, description)| PublicCube {
+            name: name.to_string(),
+            url: CubeUrl::from_static(url),
+            ui: Some(UiUrl::from_static(ui)),
+            description: description.to_string(),
+        })
+        .collect()
+}
+
+/// `chrs login --public`: create an anonymous session for a public CUBE.
+///
+/// If `--cube` is given, it is used. Otherwise, the user picks from [public_cubes].
+pub async fn login_public(
+    Credentials {
+        cube_url,
+        ui,
+        config_path,
+        ..
+    }: Credentials,
+) -> Result<()> {
+    let config = ChrsSessions::load(config_path.as_deref())?;
+    let (cube, ui) = if let Some(cube) = cube_url {
+        (cube, ui)
+    } else {
+        let cubes = public_cubes(&config);
+        if let Some(i) = pick(&cubes)? {
+            let picked = cubes.into_iter().nth(i).unwrap();
+            (picked.url, ui.or(picked.ui))
+        } else {
+            bail!("No CUBE was selected.")
+        }
+    };
+    super::cmd::login_anonymous(&cube).await?;
+    eprintln!("Logged into ChRIS {} anonymously.", &cube);
+    ChrsSessions::modify(config_path.as_deref(), |config| {
+        config.add(CubeState::anonymous(cube, ui), Backend::ClearText)
+    })
+}
+
+fn pick(cubes: &[PublicCube]) -> Result<Option<usize>> {
+    let width = cubes.iter().map(|c| c.name.len()).max().unwrap_or(0) + 2;
+    let items: Vec<String> = cubes
+        .iter()
+        .map(|c| format!("{:<w$}{}  {}", c.name, c.url, c.description, w = width))
+        .collect();
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Public ChRIS instances")
+        .items(&items)
+        .default(0)
+        .interact_on_opt(&Term::stderr())?;
+    Ok(selection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn test_builtin_public_cubes() {
+        let cubes = public_cubes(&ChrsSessions::default());
+        assert!(!cubes.is_empty());
+    }
+
+    #[rstest]
+    fn test_public_cubes_from_config() {
+        let custom = PublicCube {
+            name: "Hospital research".to_string(),
+            url: CubeUrl::from_static("https://cube.example.org/api/v1/"),
+            ui: None,
+            description: "CUBE of a hospital".to_string(),
+        };
+        let config = ChrsSessions {
+            public_cubes: vec![custom.clone()],
+            ..Default::default()
+        };
+        assert_eq!(public_cubes(&config), vec![custom]);
+    }
+}
@@ -1,18 +1,40 @@
-use crate::login::store::{Backend, CubeState, SavedCubeState};
+use crate::login::public::PublicCube;
+use crate::login::store::{Backend, CubeState, SavedCubeState, StoredToken};
 use chris::types::{CubeUrl, PluginInstanceId, Username};
 use color_eyre::eyre::{Result, WrapErr};
 use color_eyre::owo_colors::OwoColorize;
 use color_eyre::Section;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
 
 const SERVICE: &str = "org.chrisproject.chrs";
 const APP_NAME: &str = "chrs";
 
+/// Path of the config file, which is `config_path` if given.
+fn config_file<P: AsRef<Path>>(config_path: Option<P>) -> Result<PathBuf> {
+    match config_path {
+        Some(path) => Ok(path.as_ref().to_path_buf()),
+        None => confy::get_configuration_file_path(APP_NAME, None)
+            .wrap_err("Could not find configuration directory"),
+    }
+}
+
+/// Path of the file which is locked while modifying a config file. The config file
+/// itself is not locked because it is replaced when written.
+fn lock_file_of(config_file: &Path) -> PathBuf {
+    let mut name = config_file.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    config_file.with_file_name(name)
+}
+
 /// The application state is a list of user sessions represented by [SavedCubeState].
-#[derive(Serialize, Deserialize, Default, Clone)]
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct ChrsSessions {
     pub sessions: Vec<SavedCubeState>,
+    /// Public CUBEs offered by `chrs login --public`, instead of the built-in list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub public_cubes: Vec<PublicCube>,
 }
 
 impl ChrsSessions {
@@ -46,6 +68,21 @@ impl ChrsSessions {
         }
     }
 
+    /// Like [ChrsSessions::get_cube], but mutable.
+    pub fn get_cube_mut(
+        &mut self,
+        cube: Option<&CubeUrl>,
+        username: Option<&Username>,
+    ) -> Option<&mut SavedCubeState> {
+        let i = match cube {
+            None => self.sessions.len().checked_sub(1),
+            Some(cube_url) => self.sessions.iter().position(|session| {
+                &session.cube == cube_url && username.is_none_or(|u| u == &session.username)
+            }),
+        };
+        i.map(|i| &mut self.sessions[i])
+    }
+
     fn find_cube(
         &self,
         cube_url: &CubeUrl,
@@ -66,15 +103,23 @@ impl ChrsSessions {
     }
 
     /// Append the given [CubeState]. If there already exists in this [ChrsSessions]
-    /// a token for the [CubeState]'s address and username, it is overwritten.
+    /// a token for the [CubeState]'s address and username, it is overwritten,
+    /// keeping its preferences and when feeds were last listed.
     pub fn add(&mut self, session: CubeState, backend: Backend) -> Result<()> {
+        let (preferences, last_listed) = self
+            .find_cube(&session.cube, Some(&session.username))
+            .map(|old| (old.preferences.clone(), old.last_listed))
+            .unwrap_or_default();
         self.remove(&session.cube, Some(&session.username));
-        self.sessions.push(session.into_saved(backend, SERVICE)?);
+        let mut saved = session.into_saved(backend, SERVICE)?;
+        saved.preferences = preferences;
+        saved.last_listed = last_listed;
+        self.sessions.push(saved);
         Ok(())
     }
 
-    /// Remove saved login(s), Returns `true` if login was removed,
-    /// or `false` if nothing was removed.
+    /// Remove saved login(s) and their remembered passwords. Returns `true` if login
+    /// was removed, or `false` if nothing was removed.
     pub fn remove(&mut self, cube_url: &CubeUrl, username: Option<&Username>) -> bool {
         fn keep(a: &SavedCubeState, cube_url: &CubeUrl, username: Option<&Username>) -> bool {
             if &a.cube != cube_url {
@@ -90,6 +135,11 @@ impl ChrsSessions {
         }
 
         let original_len = self.sessions.len();
+        for session in &mut self.sessions {
+            if !keep(session, cube_url, username) {
+                forget_password(session)
+            }
+        }
         self.sessions.retain(|l| keep(l, cube_url, username));
         self.sessions.len() != original_len
     }
@@ -100,13 +150,44 @@ impl ChrsSessions {
         self.sessions.swap(a, b)
     }
 
+    /// Like [ChrsSessions::set_last], but if the login is for a different CUBE than
+    /// the previously preferred login, its current plugin instance is cleared, since
+    /// it was set before switching CUBEs. Returns the cleared plugin instance.
+    pub fn switch_to(&mut self, b: usize) -> Option<PluginInstanceId> {
+        let previous_cube = self.sessions.last().map(|s| s.cube.clone());
+        self.set_last(b);
+        let session = self.sessions.last_mut()?;
+        if previous_cube.as_ref() == Some(&session.cube) {
+            None
+        } else {
+            session.current_plugin_instance_id.take()
+        }
+    }
+
     /// Remove all saved logins. Returns `true` if any logins were removed.
     pub fn clear(&mut self) -> bool {
+        self.sessions.iter_mut().for_each(forget_password);
         let original_len = self.sessions.len();
         self.sessions.clear();
         original_len != 0
     }
 
+    /// Directory where the default configuration file is saved.
+    pub fn config_dir() -> Result<PathBuf> {
+        let path = confy::get_configuration_file_path(APP_NAME, None)
+            .wrap_err("Could not find configuration directory")?;
+        Ok(path.parent().map(Path::to_path_buf).unwrap_or(path))
+    }
+
+    /// Load the default config file if it exists. Unlike [ChrsSessions::load],
+    /// the file is not created, and errors are ignored.
+    pub fn load_existing() -> Option<Self> {
+        confy::get_configuration_file_path(APP_NAME, None)
+            .ok()
+            .filter(|path| path.is_file())
+            .and_then(|path| confy::load_path(path).ok())
+    }
+
     /// Load config from file.
     pub fn load<P: AsRef<Path>>(config_path: Option<P>) -> Result<Self> {
         let result = if let Some(path) = config_path {
@@ -125,13 +206,50 @@ impl ChrsSessions {
     }
 
     /// Write config to file.
+    ///
+    /// The config is written to a temporary file which then replaces the config file,
+    /// so that other processes never read a partially written config file.
     pub fn save<P: AsRef<Path>>(&self, config_path: Option<P>) -> Result<()> {
-        let result = if let Some(path) = config_path {
-            confy::store_path(path, self)
-        } else {
-            confy::store(APP_NAME, None, self)
-        };
-        result.wrap_err("Couldn't write config file")
+        let path = config_file(config_path)?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        fs_err::create_dir_all(dir)?;
+        let tmp = tempfile::NamedTempFile::new_in(dir)?;
+        confy::store_path(tmp.path(), self).wrap_err("Couldn't write config file")?;
+        tmp.persist(&path)
+            .wrap_err_with(|| format!("Couldn't replace config file {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Load config from file, change it using `f`, then write it if it was changed.
+    ///
+    /// Other processes of chrs are prevented from modifying the config until it is
+    /// written, so that their changes are not lost.
+    pub fn modify<P: AsRef<Path>, T>(
+        config_path: Option<P>,
+        f: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let path = config_file(config_path)?;
+        if let Some(dir) = path.parent() {
+            fs_err::create_dir_all(dir)?;
+        }
+        let lock_path = lock_file_of(&path);
+        let lock_file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .wrap_err_with(|| format!("Couldn't open lock file {}", lock_path.display()))?;
+        let mut lock = fd_lock::RwLock::new(lock_file);
+        let _guard = lock
+            .write()
+            .wrap_err_with(|| format!("Couldn't lock {}", lock_path.display()))?;
+        let mut sessions = Self::load(Some(&path))?;
+        let original = sessions.clone();
+        let value = f(&mut sessions)?;
+        if sessions != original {
+            sessions.save(Some(&path))?;
+        }
+        Ok(value)
     }
 
     /// Set the plugin instance of a session.
@@ -150,12 +268,134 @@ impl ChrsSessions {
         }
         false
     }
+
+    /// Clear the current plugin instance of a session.
+    /// Returns the cleared plugin instance.
+    pub fn clear_plugin_instance(
+        &mut self,
+        cube_url: Option<&CubeUrl>,
+        username: Option<&Username>,
+    ) -> Option<PluginInstanceId> {
+        self.get_cube_mut(cube_url, username)?
+            .current_plugin_instance_id
+            .take()
+    }
+
+    /// Remember the password of a session using the keyring.
+    /// Returns true if state was modified.
+    pub fn remember_password(
+        &mut self,
+        cube_url: &CubeUrl,
+        username: &Username,
+        password: &str,
+    ) -> Result<bool> {
+        for session in &mut self.sessions {
+            if &session.cube == cube_url && &session.username == username {
+                session.set_password(SERVICE, password)?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Replace the token of a session, keeping it where the old token was stored.
+    /// If the password of the session is remembered, it is replaced by `password`.
+    /// Returns true if state was modified.
+    pub fn replace_token(
+        &mut self,
+        cube_url: &CubeUrl,
+        username: &Username,
+        token: &str,
+        password: &str,
+    ) -> Result<bool> {
+        if !self.set_token(cube_url, username, token)? {
+            return Ok(false);
+        }
+        let session = self
+            .sessions
+            .iter_mut()
+            .find(|s| &s.cube == cube_url && &s.username == username)
+            .unwrap();
+        if session.remember_password {
+            session.set_password(SERVICE, password)?;
+        }
+        Ok(true)
+    }
+
+    /// Replace the token of a session, keeping it where the old token was stored.
+    /// Unlike [ChrsSessions::add], the order of sessions, and hence which session
+    /// is the default, does not change. Returns true if state was modified.
+    pub fn set_token(
+        &mut self,
+        cube_url: &CubeUrl,
+        username: &Username,
+        token: &str,
+    ) -> Result<bool> {
+        let Some(session) = self
+            .sessions
+            .iter_mut()
+            .find(|s| &s.cube == cube_url && &s.username == username)
+        else {
+            return Ok(false);
+        };
+        let backend = match session.store {
+            StoredToken::Keyring => Backend::Keyring,
+            StoredToken::Text(_) | StoredToken::None => Backend::ClearText,
+        };
+        let login = CubeState {
+            cube: cube_url.clone(),
+            username: username.clone(),
+            token: Some(token.to_string()),
+            current_plugin_instance_id: None,
+            ui: None,
+        };
+        session.store = login.into_saved(backend, SERVICE)?.store;
+        Ok(true)
+    }
+
+    /// Get the remembered password of a session.
+    pub fn get_password(&self, cube_url: &CubeUrl, username: &Username) -> Result<Option<String>> {
+        match self.find_cube(cube_url, Some(username)) {
+            Some(session) => session.get_password(SERVICE),
+            None => Ok(None),
+        }
+    }
+
+    /// Get when feeds were last listed by a session.
+    pub fn last_listed(&self, cube_url: &CubeUrl, username: &Username) -> Option<OffsetDateTime> {
+        self.find_cube(cube_url, Some(username))
+            .and_then(|session| session.last_listed)
+    }
+
+    /// Set when feeds were last listed by a session.
+    /// Returns true if state was modified.
+    pub fn set_last_listed(
+        &mut self,
+        cube_url: &CubeUrl,
+        username: &Username,
+        time: OffsetDateTime,
+    ) -> bool {
+        for session in &mut self.sessions {
+            if &session.cube == cube_url && &session.username == username {
+                session.last_listed = Some(time);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Remove the password of a session from the keyring. Failure is not fatal,
+/// since the session is being removed anyways.
+fn forget_password(session: &mut SavedCubeState) {
+    if let Err(e) = session.forget_password(SERVICE) {
+        eprintln!("{} {}", "warning:".yellow(), e)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::login::store::StoredToken;
     use chris::types::PluginInstanceId;
     use rstest::*;
     use std::str::FromStr;
@@ -169,6 +409,9 @@ mod tests {
                 store: StoredToken::Text("token-a".to_string()),
                 current_plugin_instance_id: None,
                 ui: None,
+                last_listed: None,
+                remember_password: false,
+                preferences: Default::default(),
             },
             SavedCubeState {
                 cube: CubeUrl::from_static("https://b.example.com/api/v1/"),
@@ -176,6 +419,9 @@ mod tests {
                 store: StoredToken::Text("token-b1".to_string()),
                 current_plugin_instance_id: None,
                 ui: None,
+                last_listed: None,
+                remember_password: false,
+                preferences: Default::default(),
             },
             SavedCubeState {
                 cube: CubeUrl::from_static("https://c.example.com/api/v1/"),
@@ -183,6 +429,9 @@ mod tests {
                 store: StoredToken::Keyring,
                 current_plugin_instance_id: None,
                 ui: None,
+                last_listed: None,
+                remember_password: false,
+                preferences: Default::default(),
             },
             SavedCubeState {
                 cube: CubeUrl::from_static("https://b.example.com/api/v1/"),
@@ -190,13 +439,19 @@ mod tests {
                 store: StoredToken::Text("token-b2".to_string()),
                 current_plugin_instance_id: Some(PluginInstanceId(43)),
                 ui: None,
+                last_listed: None,
+                remember_password: false,
+                preferences: Default::default(),
             },
         ]
     }
 
     #[fixture]
     fn chrs_sessions(sessions: Vec<SavedCubeState>) -> ChrsSessions {
-        ChrsSessions { sessions }
+        ChrsSessions {
+            sessions,
+            ..Default::default()
+        }
     }
 
     #[fixture]
@@ -454,4 +709,323 @@ mod tests {
         assert_eq!(actual.current_plugin_instance_id, Some(plinst));
         Ok(())
     }
+
+    #[rstest]
+    fn test_last_listed_is_saved(mut chrs_sessions: ChrsSessions) -> Result<()> {
+        let cube_url = CubeUrl::from_static("https://b.example.com/api/v1/");
+        let username = Username::from_static("b-first");
+        let other = Username::from_static("b-second");
+        let time = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        assert!(chrs_sessions.set_last_listed(&cube_url, &username, time));
+        assert!(!chrs_sessions.set_last_listed(&cube_url, &Username::from_static("nobody"), time));
+
+        let tmp = tempfile::TempDir::new()?;
+        let config_path = tmp.path().join("chrs.toml");
+        chrs_sessions.save(Some(&config_path))?;
+        let loaded = ChrsSessions::load(Some(&config_path))?;
+        assert_eq!(loaded.last_listed(&cube_url, &username), Some(time));
+        assert_eq!(loaded.last_listed(&cube_url, &other), None);
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_last_listed_is_kept_when_logging_in_again(
+        mut chrs_sessions: ChrsSessions,
+    ) -> Result<()> {
+        let cube_url = CubeUrl::from_static("https://b.example.com/api/v1/");
+        let username = Username::from_static("b-first");
+        let time = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        assert!(chrs_sessions.set_last_listed(&cube_url, &username, time));
+        chrs_sessions.add(
+            CubeState {
+                cube: cube_url.clone(),
+                username: username.clone(),
+                token: Some("new-token-b".to_string()),
+                current_plugin_instance_id: None,
+                ui: None,
+            },
+            Backend::ClearText,
+        )?;
+        assert_eq!(chrs_sessions.last_listed(&cube_url, &username), Some(time));
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_anonymous_session_is_saved(mut chrs_sessions: ChrsSessions) -> Result<()> {
+        let cube_url = CubeUrl::from_static("https://public.example.com/api/v1/");
+        chrs_sessions.add(
+            CubeState::anonymous(cube_url.clone(), None),
+            Backend::Keyring,
+        )?;
+        assert_eq!(
+            chrs_sessions.sessions.last().unwrap().store,
+            StoredToken::None
+        );
+
+        let tmp = tempfile::TempDir::new()?;
+        let config_path = tmp.path().join("chrs.toml");
+        chrs_sessions.save(Some(&config_path))?;
+        let loaded = ChrsSessions::load(Some(&config_path))?;
+        let login = loaded.get_login(None, None)?.unwrap();
+        assert_eq!(login, CubeState::anonymous(cube_url.clone(), None));
+        assert!(login.is_anonymous());
+        assert_eq!(
+            loaded.get_login(Some(&cube_url), None)?,
+            Some(CubeState::anonymous(cube_url, None))
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_anonymous_session_is_not_duplicated() -> Result<()> {
+        let cube_url = CubeUrl::from_static("https://public.example.com/api/v1/");
+        let mut config = ChrsSessions::default();
+        config.add(
+            CubeState::anonymous(cube_url.clone(), None),
+            Backend::ClearText,
+        )?;
+        config.add(
+            CubeState::anonymous(cube_url.clone(), None),
+            Backend::ClearText,
+        )?;
+        assert_eq!(config.sessions.len(), 1);
+
+        // a logged-in session for the same CUBE is kept separately
+        config.add(
+            CubeState {
+                cube: cube_url.clone(),
+                username: Username::from_static("apple"),
+                token: Some("red-delicious".to_string()),
+                current_plugin_instance_id: None,
+                ui: None,
+            },
+            Backend::ClearText,
+        )?;
+        assert_eq!(config.sessions.len(), 2);
+        assert!(config.remove(&cube_url, Some(&Username::from_static(""))));
+        assert_eq!(config.sessions[0].username.as_str(), "apple");
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_switch_to_anonymous_session(mut chrs_sessions: ChrsSessions) -> Result<()> {
+        let cube_url = CubeUrl::from_static("https://public.example.com/api/v1/");
+        chrs_sessions.add(
+            CubeState::anonymous(cube_url.clone(), None),
+            Backend::ClearText,
+        )?;
+        chrs_sessions.set_last(0);
+        assert!(!chrs_sessions.get_cube(None, None).unwrap().is_anonymous());
+        let anon = chrs_sessions
+            .sessions
+            .iter()
+            .position(|s| s.is_anonymous())
+            .unwrap();
+        chrs_sessions.set_last(anon);
+        assert!(chrs_sessions.get_cube(None, None).unwrap().is_anonymous());
+        assert_eq!(chrs_sessions.get_login(None, None)?.unwrap().token, None);
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_switch_to_same_cube_keeps_plugin_instance(mut chrs_sessions: ChrsSessions) {
+        let cube_url = CubeUrl::from_static("https://b.example.com/api/v1/");
+        let username = Username::from_static("b-first");
+        chrs_sessions.set_plugin_instance(&cube_url, &username, PluginInstanceId(7));
+        assert_eq!(chrs_sessions.switch_to(1), None);
+        let session = chrs_sessions.get_cube(None, None).unwrap();
+        assert_eq!(session.username, username);
+        assert_eq!(
+            session.current_plugin_instance_id,
+            Some(PluginInstanceId(7))
+        );
+    }
+
+    #[rstest]
+    fn test_switch_to_other_cube_clears_plugin_instance(mut chrs_sessions: ChrsSessions) {
+        let cube_url = CubeUrl::from_static("https://a.example.com/api/v1/");
+        let username = Username::from_static("aaaaa");
+        chrs_sessions.set_plugin_instance(&cube_url, &username, PluginInstanceId(5));
+        assert_eq!(chrs_sessions.switch_to(0), Some(PluginInstanceId(5)));
+        let session = chrs_sessions.get_cube(None, None).unwrap();
+        assert_eq!(session.username, username);
+        assert_eq!(session.current_plugin_instance_id, None);
+
+        // the plugin instance of the previous login is kept for when it is switched back to
+        let previous = chrs_sessions
+            .get_cube(
+                Some(&CubeUrl::from_static("https://b.example.com/api/v1/")),
+                Some(&Username::from_static("b-second")),
+            )
+            .unwrap();
+        assert_eq!(
+            previous.current_plugin_instance_id,
+            Some(PluginInstanceId(43))
+        );
+    }
+
+    #[rstest]
+    fn test_clear_plugin_instance(mut chrs_sessions: ChrsSessions) {
+        assert_eq!(
+            chrs_sessions.clear_plugin_instance(None, None),
+            Some(PluginInstanceId(43))
+        );
+        assert_eq!(chrs_sessions.clear_plugin_instance(None, None), None);
+        assert!(chrs_sessions
+            .sessions
+            .iter()
+            .all(|s| s.current_plugin_instance_id.is_none()));
+    }
+
+    #[rstest]
+    fn test_replace_token(mut chrs_sessions: ChrsSessions) -> Result<()> {
+        let cube_url = CubeUrl::from_static("https://b.example.com/api/v1/");
+        let username = Username::from_static("b-first");
+        let before = chrs_sessions.clone();
+        assert!(chrs_sessions.replace_token(&cube_url, &username, "token-b1-new", "password")?);
+        let session = chrs_sessions
+            .get_cube(Some(&cube_url), Some(&username))
+            .unwrap();
+        assert_eq!(session.store, StoredToken::Text("token-b1-new".to_string()));
+        assert!(!session.remember_password);
+        assert_eq!(
+            chrs_sessions
+                .sessions
+                .iter()
+                .map(|s| &s.username)
+                .collect::<Vec<_>>(),
+            before
+                .sessions
+                .iter()
+                .map(|s| &s.username)
+                .collect::<Vec<_>>(),
+            "order of sessions should not change"
+        );
+
+        let nobody = Username::from_static("nobody");
+        assert!(!chrs_sessions.replace_token(&cube_url, &nobody, "token", "password")?);
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_preferences_are_saved(mut chrs_sessions: ChrsSessions) -> Result<()> {
+        let cube_url = CubeUrl::from_static("https://a.example.com/api/v1/");
+        let username = Username::from_static("aaaaa");
+        chrs_sessions
+            .get_cube_mut(Some(&cube_url), Some(&username))
+            .unwrap()
+            .preferences
+            .insert("threads".to_string(), "16".to_string());
+
+        let tmp = tempfile::TempDir::new()?;
+        let config_path = tmp.path().join("chrs.toml");
+        chrs_sessions.save(Some(&config_path))?;
+        let mut loaded = ChrsSessions::load(Some(&config_path))?;
+        let saved = loaded.get_cube(Some(&cube_url), Some(&username)).unwrap();
+        assert_eq!(saved.preferences.get("threads").unwrap(), "16");
+        assert!(loaded.get_cube(None, None).unwrap().preferences.is_empty());
+
+        // logging in again keeps the preferences
+        loaded.add(
+            CubeState {
+                cube: cube_url.clone(),
+                username: username.clone(),
+                token: Some("new-token-a".to_string()),
+                current_plugin_instance_id: None,
+                ui: None,
+            },
+            Backend::ClearText,
+        )?;
+        let last = loaded.get_cube(None, None).unwrap();
+        assert_eq!(last.username, username);
+        assert_eq!(last.preferences.get("threads").unwrap(), "16");
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_load_config_without_preferences() -> Result<()> {
+        let tmp = tempfile::TempDir::new()?;
+        let config_path = tmp.path().join("chrs.toml");
+        fs_err::write(&config_path, OLD_CONFIG)?;
+        let loaded = ChrsSessions::load(Some(&config_path))?;
+        let session = loaded.get_cube(None, None).unwrap();
+        assert_eq!(session.username.as_str(), "aaaaa");
+        assert!(session.preferences.is_empty());
+
+        // empty preferences are not written, so older versions of chrs can read the file
+        loaded.save(Some(&config_path))?;
+        assert!(!fs_err::read_to_string(&config_path)?.contains("preferences"));
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_concurrent_modify_keeps_all_changes() -> Result<()> {
+        const USERS: u32 = 8;
+        const CHANGES: u32 = 20;
+        let tmp = tempfile::TempDir::new()?;
+        let config_path = tmp.path().join("chrs.toml");
+        let cube_url = CubeUrl::from_static("https://cube.example.com/api/v1/");
+        let username = |i: u32| Username::new(format!("user-{i}"));
+        for i in 0..USERS {
+            ChrsSessions::modify(Some(&config_path), |config| {
+                config.add(
+                    CubeState {
+                        cube: cube_url.clone(),
+                        username: username(i),
+                        token: Some(format!("token-{i}")),
+                        current_plugin_instance_id: None,
+                        ui: None,
+                    },
+                    Backend::ClearText,
+                )
+            })?;
+        }
+
+        std::thread::scope(|scope| {
+            for i in 0..USERS {
+                let (config_path, cube_url, username) = (&config_path, &cube_url, username(i));
+                scope.spawn(move || {
+                    for j in 1..=CHANGES {
+                        ChrsSessions::modify(Some(config_path), |config| {
+                            Ok(config.set_plugin_instance(
+                                cube_url,
+                                &username,
+                                PluginInstanceId(i * 100 + j),
+                            ))
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+
+        let config = ChrsSessions::load(Some(&config_path))?;
+        assert_eq!(config.sessions.len(), USERS as usize);
+        for i in 0..USERS {
+            let session = config
+                .get_cube(Some(&cube_url), Some(&username(i)))
+                .unwrap();
+            assert_eq!(
+                session.current_plugin_instance_id,
+                Some(PluginInstanceId(i * 100 + CHANGES))
+            );
+        }
+        Ok(())
+    }
+
+    /// Configuration file written by a version of chrs without preferences.
+    const OLD_CONFIG: &str = r#"(
+    sessions: [
+        (
+            cube: "https://a.example.com/api/v1/",
+            username: "aaaaa",
+            store: (
+                store: Text,
+                value: "token-a",
+            ),
+            current_plugin_instance_id: None,
+            ui: None,
+        ),
+    ],
+)"#;
 }
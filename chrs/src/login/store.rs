@@ -3,11 +3,14 @@
 //! "{username}@{CUBEAddress}"
 
 use crate::login::ui::UiUrl;
+use crate::shlex::shlex_quote;
 use chris::types::{CubeUrl, PluginInstanceId, Username};
 use color_eyre::eyre::{Result, WrapErr};
 use color_eyre::owo_colors::OwoColorize;
 use color_eyre::Section;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
 
 /// Supported mechanisms for storing secrets.
 pub enum Backend {
@@ -34,6 +37,16 @@ pub struct SavedCubeState {
     pub store: StoredToken,
     pub current_plugin_instance_id: Option<PluginInstanceId>,
     pub ui: Option<UiUrl>,
+    /// When feeds were last listed by `chrs list`
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub last_listed: Option<OffsetDateTime>,
+    /// Whether the password is stored by a keyring, so that a new token can be
+    /// obtained when the saved token expires.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub remember_password: bool,
+    /// Preferences set by `chrs config`, which are the defaults of command-line options.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub preferences: BTreeMap<String, String>,
 }
 
 impl SavedCubeState {
@@ -58,6 +71,48 @@ impl SavedCubeState {
         })
     }
 
+    /// Whether this is a session without a user account.
+    pub fn is_anonymous(&self) -> bool {
+        self.username.as_str().is_empty()
+    }
+
+    /// Get the password from the keyring, if it was remembered.
+    pub fn get_password(&self, service: &str) -> Result<Option<String>> {
+        if !self.remember_password {
+            return Ok(None);
+        }
+        let password = self
+            .password_entry(service)?
+            .get_password()
+            .wrap_err("Could not get password from keyring.")?;
+        Ok(Some(password))
+    }
+
+    /// Store the password in the keyring. Passwords are never saved as plaintext.
+    pub fn set_password(&mut self, service: &str, password: &str) -> Result<()> {
+        self.password_entry(service)?
+            .set_password(password)
+            .wrap_err("Could not save password to keyring.")?;
+        self.remember_password = true;
+        Ok(())
+    }
+
+    /// Remove the password from the keyring, if it was remembered.
+    pub fn forget_password(&mut self, service: &str) -> Result<()> {
+        if self.remember_password {
+            self.remember_password = false;
+            match self.password_entry(service)?.delete_password() {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(e) => return Err(e).wrap_err("Could not remove password from keyring."),
+            }
+        }
+        Ok(())
+    }
+
+    fn password_entry(&self, service: &str) -> keyring::Result<keyring::Entry> {
+        keyring::Entry::new(&format!("{service}.password"), &self.to_keyring_username())
+    }
+
     fn to_keyring_username(&self) -> String {
         format!("{}@{}", self.username.as_str(), self.cube.as_str())
     }
@@ -75,6 +130,22 @@ pub struct CubeState {
 }
 
 impl CubeState {
+    /// A session for reading public data from a CUBE, without a user account.
+    pub fn anonymous(cube: CubeUrl, ui: Option<UiUrl>) -> Self {
+        Self {
+            cube,
+            username: Username::from_static(""),
+            token: None,
+            current_plugin_instance_id: None,
+            ui,
+        }
+    }
+
+    /// Whether this is a session without a user account.
+    pub fn is_anonymous(&self) -> bool {
+        self.username.as_str().is_empty()
+    }
+
     /// Convert to [SavedCubeState]. If specified to use keyring backend,
     /// token is saved to the keyring.
     pub fn into_saved(self, backend: Backend, service: &str) -> Result<SavedCubeState> {
@@ -92,7 +163,9 @@ impl CubeState {
                                 "--no-keyring".bold(),
                                 format!(
                                     "chrs login --cube={} --username={} --token={} --no-keyring",
-                                    &self.cube, &self.username, token
+                                    shlex_quote(self.cube.as_str()),
+                                    shlex_quote(self.username.as_str()),
+                                    shlex_quote(token)
                                 )
                                 .bold()
                             )
@@ -109,6 +182,9 @@ impl CubeState {
             store: token,
             current_plugin_instance_id: self.current_plugin_instance_id,
             ui: self.ui,
+            last_listed: None,
+            remember_password: false,
+            preferences: Default::default(),
         };
         Ok(saved)
     }
@@ -161,6 +237,9 @@ mod tests {
             store: stored_token,
             current_plugin_instance_id: None,
             ui: None,
+            last_listed: None,
+            remember_password: false,
+            preferences: Default::default(),
         };
         let login = CubeState {
             cube: cube_url.clone(),
@@ -172,6 +251,19 @@ mod tests {
         Ok((login, cube.into_login(TEST_SERVICE)?))
     }
 
+    #[rstest]
+    fn test_remember_password_not_serialized_by_default(cube_url: CubeUrl, username: Username) {
+        let mut saved = CubeState::anonymous(cube_url, None)
+            .into_saved(Backend::ClearText, TEST_SERVICE)
+            .unwrap();
+        saved.username = username;
+        let value = serde_json::to_value(&saved).unwrap();
+        assert!(value.get("remember_password").is_none());
+        let deserialized: SavedCubeState = serde_json::from_value(value).unwrap();
+        assert!(!deserialized.remember_password);
+        assert_eq!(deserialized.get_password(TEST_SERVICE).unwrap(), None);
+    }
+
     #[rstest]
     fn test_into_login_from_keyring(username: Username, cube_url: CubeUrl) -> Result<()> {
         let token = "my-secret-secure-token";
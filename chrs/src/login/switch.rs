@@ -1,16 +1,19 @@
 use super::state::ChrsSessions;
+use super::store::SavedCubeState;
 use crate::credentials::Credentials;
 use chris::types::{CubeUrl, Username};
-use color_eyre::eyre::{Error, Result};
+use color_eyre::eyre::{bail, Error, Result};
 use color_eyre::owo_colors::OwoColorize;
 use dialoguer::console::Term;
 use dialoguer::{theme::ColorfulTheme, Select};
 
-/// Switch the preferred login. If any of `username`, `password`
-/// are specified, then the process is non-interactive, and
+/// Switch the preferred login. If a `target` is given, or any of `username`,
+/// `password` are specified, then the process is non-interactive, and
 /// selects any saved login which fits the criteria.
 /// Otherwise, an interactive menu is shown where the user
 /// presses arrow keys to make a selection.
+///
+/// If `list` is true, saved logins are printed instead.
 pub(crate) fn switch_login(
     Credentials {
         cube_url,
@@ -18,15 +21,33 @@ pub(crate) fn switch_login(
         config_path,
         ..
     }: Credentials,
+    target: Option<String>,
+    list: bool,
 ) -> Result<()> {
-    let mut logins = ChrsSessions::load(config_path.as_deref())?;
+    let logins = ChrsSessions::load(config_path.as_deref())?;
+
+    if list {
+        print!("{}", list_sessions(&logins.sessions));
+        return Ok(());
+    }
+    if let Some(target) = target {
+        let selected = find_target(&logins.sessions, &Target::parse(&target))?;
+        return set_last(config_path, &logins.sessions[selected]);
+    }
 
     if logins.sessions.len() == 1 {
         let login = &logins.sessions[0];
-        println!(
-            "Only one login found. Logged into ChRIS {} as user \"{}\"",
-            login.cube, login.username
-        );
+        if login.is_anonymous() {
+            println!(
+                "Only one login found. Using ChRIS {} anonymously",
+                login.cube
+            );
+        } else {
+            println!(
+                "Only one login found. Logged into ChRIS {} as user \"{}\"",
+                login.cube, login.username
+            );
+        }
         return Ok(());
     }
 
@@ -42,8 +63,29 @@ pub(crate) fn switch_login(
         interactive(&logins, max_username_len)?
     };
     if let Some(selected) = selection {
-        logins.set_last(selected);
-        logins.save(config_path)?;
+        set_last(config_path, &logins.sessions[selected])?;
+    }
+    Ok(())
+}
+
+/// Make the given login the preferred login. The login is found again in case
+/// the saved logins were changed since they were loaded.
+fn set_last(config_path: Option<std::path::PathBuf>, login: &SavedCubeState) -> Result<()> {
+    let cleared = ChrsSessions::modify(config_path, |logins| {
+        let i = logins
+            .sessions
+            .iter()
+            .position(|s| s.cube == login.cube && s.username == login.username)
+            .ok_or_else(|| Error::msg("The selected login was removed."))?;
+        Ok(logins.switch_to(i))
+    })?;
+    if let Some(id) = cleared {
+        eprintln!(
+            "{} cleared the current plugin instance plugininstance/{}, which was set before switching to {}.",
+            "note:".cyan(),
+            id.0,
+            login.cube
+        );
     }
     Ok(())
 }
@@ -73,6 +115,100 @@ fn noninteractive(
     Ok(Some(index))
 }
 
+/// A saved login given as a command-line argument.
+#[derive(Debug, PartialEq)]
+enum Target {
+    /// `username@https://cube.example.com/api/v1/`, where username is empty if anonymous.
+    Session(Username, CubeUrl),
+    Username(Username),
+    Cube(CubeUrl),
+}
+
+impl Target {
+    fn parse(value: &str) -> Self {
+        if let Ok(cube) = CubeUrl::try_from(value) {
+            return Self::Cube(cube);
+        }
+        value
+            .split_once('@')
+            .and_then(|(username, cube)| {
+                CubeUrl::try_from(cube)
+                    .ok()
+                    .map(|cube| Self::Session(Username::from(username), cube))
+            })
+            .unwrap_or_else(|| Self::Username(Username::from(value)))
+    }
+
+    fn matches(&self, session: &SavedCubeState) -> bool {
+        match self {
+            Self::Session(username, cube) => &session.username == username && &session.cube == cube,
+            Self::Username(username) => &session.username == username,
+            Self::Cube(cube) => &session.cube == cube,
+        }
+    }
+}
+
+/// Find the index of the only saved login which matches the target.
+fn find_target(sessions: &[SavedCubeState], target: &Target) -> Result<usize> {
+    let matches: Vec<_> = sessions
+        .iter()
+        .enumerate()
+        .filter(|(_, session)| target.matches(session))
+        .collect();
+    match matches.as_slice() {
+        [(index, _)] => Ok(*index),
+        [] => bail!(
+            "No saved login matches the given target. Saved logins are:\n{}",
+            session_names(sessions.iter())
+        ),
+        _ => bail!(
+            "More than one saved login matches the given target, please specify \
+            one of:\n{}",
+            session_names(matches.into_iter().map(|(_, session)| session))
+        ),
+    }
+}
+
+/// Names of sessions in the form accepted by `chrs switch`, one per line.
+fn session_names<'a>(sessions: impl Iterator<Item = &'a SavedCubeState>) -> String {
+    sessions
+        .map(|s| format!("\t{}@{}", s.username.as_str(), s.cube.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Show all saved logins, marking the active one with `*`.
+fn list_sessions(sessions: &[SavedCubeState]) -> String {
+    let width = sessions
+        .iter()
+        .map(|s| s.username.as_str().len())
+        .chain(std::iter::once("(anonymous)".len()))
+        .max()
+        .unwrap_or_default();
+    sessions
+        .iter()
+        .enumerate()
+        .map(|(i, session)| {
+            let username = if session.is_anonymous() {
+                "(anonymous)"
+            } else {
+                session.username.as_str()
+            };
+            let context = session
+                .current_plugin_instance_id
+                .map(|id| format!("plugininstance/{}", id.0))
+                .unwrap_or_else(|| "-".to_string());
+            format!(
+                "{} {:<width$}  {}  {}\n",
+                if i + 1 == sessions.len() { "*" } else { " " },
+                username,
+                session.cube.as_str(),
+                context,
+            )
+        })
+        .collect()
+}
+
 fn get_index_of(
     logins: &ChrsSessions,
     address: &Option<CubeUrl>,
@@ -91,7 +227,7 @@ fn interactive(logins: &ChrsSessions, max_username_len: usize) -> Result<Option<
         .map(|login| {
             format!(
                 "{:<p$}{}",
-                if login.username.as_str().is_empty() {
+                if login.is_anonymous() {
                     "(anonymous)"
                 } else {
                     &login.username.as_str()
@@ -107,3 +243,94 @@ fn interactive(logins: &ChrsSessions, max_username_len: usize) -> Result<Option<
         .interact_on_opt(&Term::stderr())?;
     Ok(selection)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::login::store::StoredToken;
+    use chris::types::PluginInstanceId;
+    use rstest::*;
+
+    fn session(username: &str, cube: &str, plinst: Option<u32>) -> SavedCubeState {
+        SavedCubeState {
+            cube: CubeUrl::try_from(cube).unwrap(),
+            username: Username::from(username),
+            store: StoredToken::None,
+            current_plugin_instance_id: plinst.map(PluginInstanceId),
+            ui: None,
+            last_listed: None,
+            remember_password: false,
+            preferences: Default::default(),
+        }
+    }
+
+    #[fixture]
+    fn sessions() -> Vec<SavedCubeState> {
+        vec![
+            session("alice", "https://a.example.com/api/v1/", None),
+            session("bob", "https://a.example.com/api/v1/", Some(5)),
+            session("alice", "https://b.example.com/api/v1/", None),
+            session("", "https://c.example.com/api/v1/", None),
+        ]
+    }
+
+    #[rstest]
+    #[case(
+        "alice@https://a.example.com/api/v1/",
+        Target::Session(
+            Username::from("alice"),
+            CubeUrl::from_static("https://a.example.com/api/v1/")
+        )
+    )]
+    #[case("alice", Target::Username(Username::from("alice")))]
+    #[case(
+        "https://a.example.com/api/v1/",
+        Target::Cube(CubeUrl::from_static("https://a.example.com/api/v1/"))
+    )]
+    #[case(
+        "@https://c.example.com/api/v1/",
+        Target::Session(
+            Username::from(""),
+            CubeUrl::from_static("https://c.example.com/api/v1/")
+        )
+    )]
+    fn test_parse_target(#[case] value: &str, #[case] expected: Target) {
+        assert_eq!(Target::parse(value), expected)
+    }
+
+    #[rstest]
+    #[case("bob", 1)]
+    #[case("alice@https://b.example.com/api/v1/", 2)]
+    #[case("https://b.example.com/api/v1/", 2)]
+    #[case("@https://c.example.com/api/v1/", 3)]
+    fn test_find_target(
+        sessions: Vec<SavedCubeState>,
+        #[case] value: &str,
+        #[case] expected: usize,
+    ) {
+        assert_eq!(
+            find_target(&sessions, &Target::parse(value)).unwrap(),
+            expected
+        )
+    }
+
+    #[rstest]
+    #[case("alice")]
+    #[case("https://a.example.com/api/v1/")]
+    #[case("carol")]
+    #[case("bob@https://b.example.com/api/v1/")]
+    fn test_find_target_ambiguous_or_missing(sessions: Vec<SavedCubeState>, #[case] value: &str) {
+        assert!(find_target(&sessions, &Target::parse(value)).is_err())
+    }
+
+    #[rstest]
+    fn test_list_sessions(sessions: Vec<SavedCubeState>) {
+        let expected = concat!(
+            "  alice        https://a.example.com/api/v1/  -\n",
+            "  bob          https://a.example.com/api/v1/  plugininstance/5\n",
+            "  alice        https://b.example.com/api/v1/  -\n",
+            "* (anonymous)  https://c.example.com/api/v1/  -\n",
+        );
+        assert_eq!(list_sessions(&sessions), expected)
+    }
+}
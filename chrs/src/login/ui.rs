@@ -16,7 +16,11 @@ impl UiUrl {
     pub fn feed_url_of(&self, feed: &FeedResponse) -> String {
         // required to specify feed as public or private.
         // https://github.com/FNNDSC/ChRIS_ui/issues/1072
-        let t = if feed.public { "public" } else { "private" };
+        let t = if feed.is_public() {
+            "public"
+        } else {
+            "private"
+        };
         format!("{}/feeds/{}?type={}", &self.as_str(), feed.id.0, t)
     }
 }
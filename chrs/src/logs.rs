@@ -1,9 +1,27 @@
+use std::io::Write;
+use std::time::Duration;
+
 use color_eyre::eyre::{OptionExt, Result};
+use color_eyre::owo_colors::OwoColorize;
+
+use chris::{Access, LinkedModel, PluginInstanceResponse, PluginInstanceRo};
 
 use crate::arg::GivenDataNode;
 use crate::credentials::Credentials;
+use crate::unicode;
+
+/// Time between checks for new logs with `--follow`.
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(3);
 
-pub async fn logs(credentials: Credentials, given: Option<GivenDataNode>) -> Result<()> {
+/// Shortest overlap between logs which were already printed and the current logs
+/// for them to be considered a continuation after CUBE truncated their beginning.
+const MIN_OVERLAP: usize = 64;
+
+pub async fn logs(
+    credentials: Credentials,
+    given: Option<GivenDataNode>,
+    follow: bool,
+) -> Result<()> {
     let (client, old, _) = credentials
         .get_client(given.as_ref().map(|g| g.as_arg_str()).as_slice())
         .await?;
@@ -11,6 +29,128 @@ pub async fn logs(credentials: Credentials, given: Option<GivenDataNode>) -> Res
         .or_else(|| old.map(|id| id.into()))
         .ok_or_eyre("missing operand")?;
     let plinst = given.into_plinst_either(&client, old).await?;
-    print!("{}", plinst.logs());
+    if follow {
+        follow_logs(plinst).await
+    } else {
+        print!("{}", plinst.logs());
+        Ok(())
+    }
+}
+
+/// Print the logs of a plugin instance as they are written, until it is finished.
+async fn follow_logs(mut plinst: PluginInstanceRo) -> Result<()> {
+    let mut printed = String::new();
+    loop {
+        let current = plinst.logs();
+        match new_output(&printed, &current) {
+            NewOutput::Append(suffix) => print!("{}", suffix),
+            NewOutput::Rewritten(logs) => {
+                println!(
+                    "\n{}",
+                    format!(
+                        "{} logs were rewritten by CUBE, printing them again {}",
+                        unicode::HORIZONTAL_BAR.repeat(4),
+                        unicode::HORIZONTAL_BAR.repeat(4)
+                    )
+                    .dimmed()
+                );
+                print!("{}", logs)
+            }
+        }
+        std::io::stdout().flush()?;
+        printed = current;
+        if plinst.object.status.is_terminal() {
+            break;
+        }
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
+        match plinst.refresh().await {
+            Ok(refreshed) => plinst = refreshed,
+            Err(e) => eprintln!("{} could not get logs: {}", "warning:".yellow(), e),
+        }
+    }
+    eprintln!(
+        "plugininstance/{} {}",
+        plinst.object.id.0,
+        plinst.object.status.as_str()
+    );
     Ok(())
 }
+
+/// Print the logs of a plugin instance to stderr, e.g. after it failed.
+pub(crate) fn eprint_logs<A: Access>(plinst: &LinkedModel<PluginInstanceResponse, A>) {
+    eprintln!(
+        "{}",
+        format!(
+            "{} logs of plugininstance/{} {}",
+            unicode::HORIZONTAL_BAR.repeat(4),
+            plinst.object.id.0,
+            unicode::HORIZONTAL_BAR.repeat(4)
+        )
+        .dimmed()
+    );
+    eprint!("{}", plinst.logs());
+}
+
+/// What to print after `printed` to show `current` logs.
+#[derive(Debug, PartialEq)]
+enum NewOutput<'a> {
+    /// Logs were added to the end.
+    Append(&'a str),
+    /// Logs were changed, so all of them must be printed again.
+    Rewritten(&'a str),
+}
+
+/// Figure out what is new in `current` compared to what was already `printed`.
+///
+/// CUBE only keeps the end of long logs, so the beginning of `current` may be
+/// cut off. In that case, the new logs start after where the end of `printed`
+/// overlaps with the beginning of `current`.
+fn new_output<'a>(printed: &str, current: &'a str) -> NewOutput<'a> {
+    if let Some(suffix) = current.strip_prefix(printed) {
+        return NewOutput::Append(suffix);
+    }
+    let overlap = printed
+        .char_indices()
+        .map(|(i, _)| &printed[i..])
+        .take_while(|end| end.len() >= MIN_OVERLAP)
+        .find(|end| current.starts_with(end));
+    match overlap {
+        Some(end) => NewOutput::Append(&current[end.len()..]),
+        None => NewOutput::Rewritten(current),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("", "", NewOutput::Append(""))]
+    #[case("", "hello\n", NewOutput::Append("hello\n"))]
+    #[case("hello\n", "hello\n", NewOutput::Append(""))]
+    #[case("hello\n", "hello\nworld\n", NewOutput::Append("world\n"))]
+    #[case("hello\n", "goodbye\n", NewOutput::Rewritten("goodbye\n"))]
+    #[case("hello\nworld\n", "hello\n", NewOutput::Rewritten("hello\n"))]
+    fn test_new_output(#[case] printed: &str, #[case] current: &str, #[case] expected: NewOutput) {
+        assert_eq!(new_output(printed, current), expected)
+    }
+
+    #[rstest]
+    fn test_new_output_truncated_beginning() {
+        let lines: Vec<_> = (0..40).map(|i| format!("line {i} ✓\n")).collect();
+        let printed = lines[..30].concat();
+        // CUBE dropped the first 10 lines, and 10 new lines were written
+        let current = lines[10..].concat();
+        let expected = lines[30..].concat();
+        assert_eq!(new_output(&printed, &current), NewOutput::Append(&expected))
+    }
+
+    #[rstest]
+    fn test_new_output_short_overlap_is_rewritten() {
+        assert_eq!(
+            new_output("abc\ndone\n", "done\nmore\n"),
+            NewOutput::Rewritten("done\nmore\n")
+        )
+    }
+}
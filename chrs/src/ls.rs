@@ -1,4 +1,5 @@
 mod cmd;
+mod long;
 pub mod options;
 mod plain;
 mod tree;
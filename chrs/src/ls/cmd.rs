@@ -1,13 +1,16 @@
 use clap::Parser;
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{bail, eyre, OptionExt, Result};
 use tokio::join;
 
-use crate::arg::GivenPluginInstanceOrPath;
+use crate::arg::{output_path_of, GivenPluginInstanceOrPath};
 use crate::credentials::Credentials;
-use crate::files::{CoderChannel, MaybeChrisPathHumanCoder};
-use crate::ls::options::WhatToPrint;
+use crate::files::{CoderChannel, FilterArgs, MaybeChrisPathHumanCoder};
+use crate::ls::options::{SortBy, WhatToPrint};
+use crate::output::OutputFormat;
 
-use super::plain::ls_plain;
+use super::long::LongOptions;
+use super::plain::{ls_plain, ls_search};
+use super::tree::ls_tree;
 
 #[derive(Parser)]
 pub struct LsArgs {
@@ -15,8 +18,8 @@ pub struct LsArgs {
     #[clap(short, long)]
     pub tree: bool,
 
-    /// Maximum subdirectory depth
-    #[clap(short = 'L', long)]
+    /// Maximum subdirectory depth (default: 1, or 3 for --tree)
+    #[clap(short = 'L', long, visible_alias = "depth")]
     pub level: Option<u16>,
 
     /// Show full paths, which may be convenient for copy-paste
@@ -24,13 +27,46 @@ pub struct LsArgs {
     pub full: bool,
 
     /// Show canonical folder names instead of renaming them to feed names or plugin instance titles
-    #[clap(short, long)]
+    #[clap(short, long, overrides_with = "titles")]
     pub no_titles: bool,
 
+    /// Rename folders to feed names and plugin instance titles even if the no_titles preference is set
+    #[clap(long, overrides_with = "no_titles")]
+    pub titles: bool,
+
     /// What to print
     #[clap(short, long, default_value_t, value_enum)]
     pub show: WhatToPrint,
 
+    /// List from the root of the feed of the plugin instance, marking the plugin instance's folder
+    #[clap(long)]
+    pub feed: bool,
+
+    /// Search for files under the path whose names contain the given text,
+    /// ignoring case, at any depth
+    #[clap(long, conflicts_with_all = ["tree", "level", "show"])]
+    pub contains: Option<String>,
+
+    /// Long listing with size, owner, and creation date. Instead of a size,
+    /// the number of subfolders and files is shown for folders.
+    #[clap(short = 'l', long, conflicts_with = "tree")]
+    pub long: bool,
+
+    /// Sort entries
+    #[clap(long, value_enum, conflicts_with = "tree")]
+    pub sort: Option<SortBy>,
+
+    /// Reverse the order of entries
+    #[clap(short, long, conflicts_with = "tree")]
+    pub reverse: bool,
+
+    /// List at most this many files of each folder, or in total with --contains
+    #[clap(long, conflicts_with = "tree")]
+    pub limit: Option<usize>,
+
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+
     /// directory path or plugin instance
     #[clap(default_value_t)]
     pub path: GivenPluginInstanceOrPath,
@@ -43,29 +79,144 @@ pub async fn ls(
         level,
         full,
         no_titles,
+        titles,
         show,
+        feed,
+        contains,
+        long,
+        sort,
+        reverse,
+        limit,
+        filter,
         path,
     }: LsArgs,
+    output: OutputFormat,
 ) -> Result<()> {
     let (client, old_id, _) = credentials.get_client([path.as_arg_str()]).await?;
-    let level = level.unwrap_or(if tree { 4 } else { 1 });
-    let path = path.into_path(&client, old_id).await?;
+    if tree && !output.is_human() {
+        bail!("--tree can only be used with --output human")
+    }
+    let level = level.unwrap_or(if tree { 3 } else { 1 });
+    let filter = filter.build()?;
+    let long = LongOptions {
+        long,
+        sort,
+        reverse,
+    };
+    let (path, current) = if feed {
+        let plinst = path.get_using_either(&client, old_id).await?;
+        let current = plinst_folder(output_path_of(&plinst.object)?).to_string();
+        let root = feed_root(&current)
+            .ok_or_else(|| eyre!("Not a feed output path: {}", current))?
+            .to_string();
+        (root, Some(current))
+    } else {
+        (path.into_path(&client, old_id).await?, None)
+    };
+
+    let search = if let Some(text) = contains {
+        let query = client
+            .logged_in_ref()
+            .ok_or_eyre("--contains is only available for logged in users")?
+            .files()
+            .fname_icontains(text);
+        let query = match path.trim_end_matches('/') {
+            "" => query,
+            folder => query.fname(format!("{}/", folder)),
+        };
+        Some(query.search().basic().into_ro())
+    } else {
+        None
+    };
 
     let ro_client = client.into_ro();
-    let coder = MaybeChrisPathHumanCoder::new(&ro_client, !no_titles);
+    let coder = MaybeChrisPathHumanCoder::new(&ro_client, titles || !no_titles);
     let (decode_channel, decoder_loop) = CoderChannel::create(coder);
 
-    let (result, _) = if tree {
-        todo!()
-        // join!(
-        //     ls_tree(&ro_client, &path, level, full, decode_channel),
-        //     decoder_loop
-        // )
+    let (result, _) = if let Some(files) = search {
+        join!(
+            ls_search(
+                files,
+                &path,
+                full,
+                &filter,
+                decode_channel,
+                long,
+                limit,
+                output
+            ),
+            decoder_loop
+        )
+    } else if tree {
+        join!(
+            ls_tree(&ro_client, &path, level, full, &filter, decode_channel),
+            decoder_loop
+        )
     } else {
         join!(
-            ls_plain(&ro_client, &path, level, full, decode_channel, show),
+            ls_plain(
+                &ro_client,
+                &path,
+                level,
+                full,
+                decode_channel,
+                show,
+                &filter,
+                current.as_deref(),
+                long,
+                limit,
+                output
+            ),
             decoder_loop
         )
     };
     result
 }
+
+/// Get the folder of a plugin instance from its output path, i.e. remove the trailing "/data".
+fn plinst_folder(output_path: &str) -> &str {
+    output_path.strip_suffix("/data").unwrap_or(output_path)
+}
+
+/// Get the root folder of a feed, `<owner>/feed_N`, from a path under it.
+///
+/// The owner is taken from the path, since it can be a feed shared by another user.
+fn feed_root(path: &str) -> Option<&str> {
+    let (owner, rest) = path.split_once('/')?;
+    let feed_folder = rest.split('/').next()?;
+    feed_folder
+        .strip_prefix("feed_")
+        .and_then(|n| n.parse::<u32>().ok())
+        .map(|_| &path[..owner.len() + 1 + feed_folder.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("jennings/feed_5/pl-dircopy_7/data", Some("jennings/feed_5"))]
+    #[case(
+        "jennings/feed_5/pl-dircopy_7/pl-simpledsapp_9",
+        Some("jennings/feed_5")
+    )]
+    // feed shared with the logged-in user by its owner, alice
+    #[case(
+        "alice/feed_12/pl-dircopy_40/pl-med2img_41/data",
+        Some("alice/feed_12")
+    )]
+    #[case("alice/feed_12", Some("alice/feed_12"))]
+    #[case("alice/uploads/feed_12", None)]
+    #[case("SERVICES/PACS/Orthanc", None)]
+    fn test_feed_root(#[case] path: &str, #[case] expected: Option<&str>) {
+        assert_eq!(feed_root(path), expected)
+    }
+
+    #[rstest]
+    #[case("alice/feed_12/pl-dircopy_40/data", "alice/feed_12/pl-dircopy_40")]
+    #[case("alice/feed_12/pl-dircopy_40", "alice/feed_12/pl-dircopy_40")]
+    fn test_plinst_folder(#[case] output_path: &str, #[case] expected: &str) {
+        assert_eq!(plinst_folder(output_path), expected)
+    }
+}
@@ -0,0 +1,216 @@
+//! `chrs ls --long`, and sorting of listed files and folders.
+
+use indicatif::HumanBytes;
+use serde::Serialize;
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+use chris::{BasicFileResponse, Downloadable};
+
+use super::options::SortBy;
+use super::plain::PathKind;
+use crate::table::{fixed_widths, pad_left, pad_right};
+
+/// Options for `--long`, `--sort`, and `--reverse`.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct LongOptions {
+    pub long: bool,
+    pub sort: Option<SortBy>,
+    pub reverse: bool,
+}
+
+impl LongOptions {
+    /// Whether entries must be collected before printing them, instead of printing
+    /// them as they come.
+    pub fn collects(&self) -> bool {
+        self.long || self.sort.is_some() || self.reverse
+    }
+
+    /// Whether [Details] of folders are needed, which take extra requests to get.
+    pub fn needs_details(&self) -> bool {
+        self.long || self.sort == Some(SortBy::Date)
+    }
+}
+
+/// Metadata of a file or folder, shown by `--long`.
+#[derive(Serialize, Default, Debug, Clone, PartialEq)]
+pub(super) struct Details {
+    /// Size of a file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Number of subfolders and files of a folder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<usize>,
+    pub owner: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub creation_date: Option<OffsetDateTime>,
+}
+
+impl Details {
+    pub fn of_file(file: &BasicFileResponse) -> Self {
+        Self {
+            size: Some(file.fsize()),
+            children: None,
+            owner: file.owner_username.as_ref().map(|u| u.to_string()),
+            creation_date: file.creation_date,
+        }
+    }
+}
+
+/// A listed file or folder, collected to be sorted or aligned.
+pub(super) struct Row {
+    pub path: String,
+    pub kind: PathKind,
+    pub details: Details,
+}
+
+/// Sort rows client-side. Without `sort`, the order from _CUBE_ is kept.
+pub(super) fn sort_rows(rows: &mut [Row], sort: Option<SortBy>, reverse: bool) {
+    match sort {
+        None => (),
+        Some(SortBy::Name) => rows.sort_by(|a, b| a.path.cmp(&b.path)),
+        Some(SortBy::Size) => {
+            rows.sort_by(|a, b| (a.details.size, &a.path).cmp(&(b.details.size, &b.path)))
+        }
+        Some(SortBy::Date) => rows.sort_by(|a, b| {
+            (a.details.creation_date, &a.path).cmp(&(b.details.creation_date, &b.path))
+        }),
+    }
+    if reverse {
+        rows.reverse()
+    }
+}
+
+/// Format rows as lines of aligned columns: size or number of children, owner,
+/// creation date, and the path, which is formatted by `format_path`.
+pub(super) fn long_lines(
+    rows: &[Row],
+    format_path: impl Fn(&str, PathKind) -> String,
+) -> Vec<String> {
+    let columns: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            let details = &row.details;
+            let size = match (details.size, details.children) {
+                (Some(size), _) => HumanBytes(size).to_string(),
+                (None, Some(1)) => "1 item".to_string(),
+                (None, Some(n)) => format!("{} items", n),
+                (None, None) => "-".to_string(),
+            };
+            let owner = details.owner.as_deref().unwrap_or("-").to_string();
+            let date = details
+                .creation_date
+                .and_then(|d| {
+                    d.format(format_description!("[year]-[month]-[day] [hour]:[minute]"))
+                        .ok()
+                })
+                .unwrap_or_else(|| "-".to_string());
+            vec![size, owner, date, row.path.clone()]
+        })
+        .collect();
+    let widths = fixed_widths(&columns, 3);
+    rows.iter()
+        .zip(columns)
+        .map(|(row, values)| {
+            format!(
+                "{}  {}  {}  {}",
+                pad_left(&values[0], widths[0]),
+                pad_right(&values[1], widths[1]),
+                pad_right(&values[2], widths[2]),
+                format_path(&row.path, row.kind)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use time::macros::datetime;
+
+    fn file(path: &str, size: u64, date: OffsetDateTime) -> Row {
+        Row {
+            path: path.to_string(),
+            kind: PathKind::File,
+            details: Details {
+                size: Some(size),
+                children: None,
+                owner: Some("alice".to_string()),
+                creation_date: Some(date),
+            },
+        }
+    }
+
+    fn folder(path: &str, children: usize) -> Row {
+        Row {
+            path: path.to_string(),
+            kind: PathKind::Dir,
+            details: Details {
+                children: Some(children),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[fixture]
+    fn rows() -> Vec<Row> {
+        vec![
+            file("b.txt", 2048, datetime!(2024-03-01 14:06 UTC)),
+            folder("data", 3),
+            file("a.txt", 10, datetime!(2024-03-02 09:30 UTC)),
+            file("c.txt", 10, datetime!(2024-02-28 05:41 UTC)),
+        ]
+    }
+
+    fn paths(rows: &[Row]) -> Vec<&str> {
+        rows.iter().map(|r| r.path.as_str()).collect()
+    }
+
+    #[rstest]
+    #[case(None, false, vec!["b.txt", "data", "a.txt", "c.txt"])]
+    #[case(None, true, vec!["c.txt", "a.txt", "data", "b.txt"])]
+    #[case(Some(SortBy::Name), false, vec!["a.txt", "b.txt", "c.txt", "data"])]
+    #[case(Some(SortBy::Name), true, vec!["data", "c.txt", "b.txt", "a.txt"])]
+    #[case(Some(SortBy::Size), false, vec!["data", "a.txt", "c.txt", "b.txt"])]
+    #[case(Some(SortBy::Date), false, vec!["data", "c.txt", "b.txt", "a.txt"])]
+    #[case(Some(SortBy::Date), true, vec!["a.txt", "b.txt", "c.txt", "data"])]
+    fn test_sort_rows(
+        mut rows: Vec<Row>,
+        #[case] sort: Option<SortBy>,
+        #[case] reverse: bool,
+        #[case] expected: Vec<&str>,
+    ) {
+        sort_rows(&mut rows, sort, reverse);
+        assert_eq!(paths(&rows), expected)
+    }
+
+    #[rstest]
+    fn test_long_lines(rows: Vec<Row>) {
+        let actual = long_lines(&rows, |path, kind| match kind {
+            PathKind::File => path.to_string(),
+            _ => format!("{}/", path),
+        });
+        let expected = vec![
+            "2.00 KiB  alice  2024-03-01 14:06  b.txt",
+            " 3 items  -      -                 data/",
+            "    10 B  alice  2024-03-02 09:30  a.txt",
+            "    10 B  alice  2024-02-28 05:41  c.txt",
+        ];
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    fn test_long_lines_align_wide_owners() {
+        let date = datetime!(2024-03-01 14:06 UTC);
+        let mut rows = vec![file("a.txt", 10, date), file("b.txt", 10, date)];
+        rows[0].details.owner = Some("zoë".to_string());
+        rows[1].details.owner = Some("山田".to_string());
+        let actual = long_lines(&rows, |path, _| path.to_string());
+        let expected = vec![
+            "10 B  zoë   2024-03-01 14:06  a.txt",
+            "10 B  山田  2024-03-01 14:06  b.txt",
+        ];
+        assert_eq!(actual, expected)
+    }
+}
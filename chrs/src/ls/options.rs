@@ -18,3 +18,13 @@ impl WhatToPrint {
         !matches!(&self, Self::Files)
     }
 }
+
+/// Order of listed files and folders, for `--sort`.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq)]
+pub enum SortBy {
+    Name,
+    /// File size. Folders come first.
+    Size,
+    /// Creation date
+    Date,
+}
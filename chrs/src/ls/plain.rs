@@ -2,13 +2,21 @@ use async_recursion::async_recursion;
 use color_eyre::eyre::{eyre, Result};
 use color_eyre::owo_colors::OwoColorize;
 use futures::{pin_mut, StreamExt};
+use serde::Serialize;
+use std::io::Stdout;
+use std::sync::Mutex;
 
-use crate::files::CoderChannel;
+use crate::files::{CoderChannel, FileFilter};
+use chris::search::Search;
 use chris::types::{FileBrowserPath, FileResourceFname};
-use chris::{FileBrowser, RoClient};
+use chris::{BasicFileResponse, FileBrowser, RoAccess, RoClient};
 
+use super::long::{long_lines, sort_rows, Details, LongOptions, Row};
 use crate::ls::options::WhatToPrint;
+use crate::output::{rfc3339, OutputFormat, RecordWriter, Render};
+use crate::unicode;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn ls_plain(
     client: &RoClient,
     path: &str,
@@ -16,24 +24,44 @@ pub async fn ls_plain(
     full: bool,
     mut coder: CoderChannel,
     what_to_print: WhatToPrint,
+    filter: &FileFilter,
+    current: Option<&str>,
+    long: LongOptions,
+    limit: Option<usize>,
+    output: OutputFormat,
 ) -> Result<()> {
     let relative_parent = if full {
         None
     } else {
         Some(coder.decode(path.to_string()).await)
     };
+    let records = (!output.is_human()).then(|| RecordWriter::stdout(output));
+    let listing = Listing {
+        relative_parent,
+        what_to_print,
+        root: path,
+        filter,
+        current,
+        records: records.as_ref(),
+        long,
+        limit,
+        collected: long.collects().then(Default::default),
+    };
     let was = ls_recursive(
         client.filebrowser(),
         path.into(),
         level,
-        &relative_parent,
+        &listing,
         &mut coder,
-        what_to_print,
         Default::default(),
     )
     .await?;
+    listing.print_collected()?;
+    if let Some(records) = records {
+        records.finish()?;
+    }
 
-    if !was.printed && was.had_subdirs {
+    if !was.printed && was.had_subdirs && filter.is_empty() {
         // future work: add the rest of chrs' arguments here too.
         let mut cmd: Vec<String> = std::env::args().collect();
         cmd.insert(cmd.len() - 1, "--show=folders".to_string());
@@ -46,16 +74,152 @@ pub async fn ls_plain(
     Ok(())
 }
 
+/// Print the files found by a search for files under `path`, at most `limit` of them.
+#[allow(clippy::too_many_arguments)]
+pub async fn ls_search(
+    files: Search<BasicFileResponse, RoAccess>,
+    path: &str,
+    full: bool,
+    filter: &FileFilter,
+    mut coder: CoderChannel,
+    long: LongOptions,
+    limit: Option<usize>,
+    output: OutputFormat,
+) -> Result<()> {
+    let relative_parent = if full {
+        None
+    } else {
+        Some(coder.decode(path.to_string()).await)
+    };
+    let records = (!output.is_human()).then(|| RecordWriter::stdout(output));
+    let listing = Listing {
+        relative_parent,
+        what_to_print: WhatToPrint::Files,
+        root: path,
+        filter,
+        current: None,
+        records: records.as_ref(),
+        long,
+        limit,
+        collected: long.collects().then(Default::default),
+    };
+    let pages = files.stream_pages();
+    pin_mut!(pages);
+    let mut printed = 0;
+    while !listing.is_full(printed) {
+        let Some(page) = pages.next().await else {
+            break;
+        };
+        let batch = listing.files_of(page?, printed);
+        printed += batch.len();
+        print_paths(&mut coder, batch, &listing).await?;
+    }
+    listing.print_collected()?;
+    if let Some(records) = records {
+        records.finish()?;
+    }
+    if printed == 0 {
+        eprintln!("No files found.");
+    }
+    Ok(())
+}
+
+/// Options for [ls_recursive] which stay the same throughout the recursion.
+struct Listing<'a> {
+    relative_parent: Option<String>,
+    what_to_print: WhatToPrint,
+    /// Folder being listed, which `filter` is applied relative to
+    root: &'a str,
+    filter: &'a FileFilter,
+    /// Folder to mark as the current plugin instance
+    current: Option<&'a str>,
+    /// Where to write entries for `--output plain` or `--output json`
+    records: Option<&'a RecordWriter<Stdout>>,
+    long: LongOptions,
+    /// Maximum number of files to print of each folder
+    limit: Option<usize>,
+    /// Entries to print after all are listed, if [LongOptions::collects]
+    collected: Option<Mutex<Vec<Row>>>,
+}
+
+impl Listing<'_> {
+    /// Whether no more files should be printed after `printed` files.
+    fn is_full(&self, printed: usize) -> bool {
+        self.limit.is_some_and(|limit| printed >= limit)
+    }
+
+    /// Get the paths and details of the files of `page` which are selected by the filter,
+    /// without going over the limit after `printed` files.
+    fn files_of(&self, page: Vec<BasicFileResponse>, printed: usize) -> Vec<Unprinted> {
+        let remaining = self
+            .limit
+            .map_or(usize::MAX, |limit| limit.saturating_sub(printed));
+        page.into_iter()
+            .map(|file| {
+                let details = Details::of_file(&file);
+                let file_path: FileResourceFname = file.into();
+                (file_path.take(), PathKind::File, details)
+            })
+            .filter(|(fname, _, _)| self.filter.is_match_under(fname, self.root))
+            .take(remaining)
+            .collect()
+    }
+
+    /// Print an entry, or collect it to be printed by [Listing::print_collected].
+    fn emit(&self, row: Row) -> Result<()> {
+        if let Some(collected) = &self.collected {
+            collected.lock().unwrap().push(row);
+            Ok(())
+        } else {
+            self.write(&row)
+        }
+    }
+
+    fn write(&self, row: &Row) -> Result<()> {
+        if let Some(records) = self.records {
+            records.write(&Entry {
+                path: &row.path,
+                kind: row.kind,
+                details: self.long.long.then_some(&row.details),
+            })?;
+        } else {
+            println!("{}", format_path(&row.path, row.kind))
+        }
+        Ok(())
+    }
+
+    /// Sort and print collected entries.
+    fn print_collected(&self) -> Result<()> {
+        let mut rows = if let Some(collected) = &self.collected {
+            std::mem::take(&mut *collected.lock().unwrap())
+        } else {
+            return Ok(());
+        };
+        sort_rows(&mut rows, self.long.sort, self.long.reverse);
+        if self.long.long && self.records.is_none() {
+            for line in long_lines(&rows, format_path) {
+                println!("{}", line)
+            }
+            return Ok(());
+        }
+        rows.iter().try_for_each(|row| self.write(row))
+    }
+}
+
+/// List `path` and the folders under it, up to `level` folders deep.
+///
+/// Paths are decoded in batches: the subfolders of a folder together, and its files
+/// a page at a time. Batches of different folders are decoded one after another.
 #[async_recursion]
 async fn ls_recursive(
     fb: FileBrowser,
     path: FileBrowserPath,
     level: u16,
-    relative_parent: &Option<String>,
+    listing: &Listing<'_>,
     coder: &mut CoderChannel,
-    what_to_print: WhatToPrint,
     mut was: WasPrinted,
 ) -> Result<WasPrinted> {
+    let what_to_print = listing.what_to_print;
     if level == 0 {
         return Ok(was);
     }
@@ -67,73 +231,172 @@ async fn ls_recursive(
     was.had_subdirs = was.had_subdirs || !entry.subfolders().is_empty();
 
     if what_to_print.should_print_folders() {
+        let mut batch = Vec::with_capacity(entry.subfolder_count());
         for subfolder in entry.absolute_subfolders() {
-            print_path(coder, subfolder.take(), relative_parent, true).await?;
-            was.printed = true;
+            let is_current = listing.current == Some(subfolder.as_str());
+            let kind = if is_current {
+                PathKind::CurrentDir
+            } else {
+                PathKind::Dir
+            };
+            let details = if listing.long.needs_details() {
+                folder_details(&fb, &subfolder).await?
+            } else {
+                Default::default()
+            };
+            batch.push((subfolder.take(), kind, details));
         }
+        was.printed = was.printed || !batch.is_empty();
+        print_paths(coder, batch, listing).await?;
     }
 
     if what_to_print.should_print_files() {
+        // files are requested page by page, so that every file of huge folders is listed
         let iter_files = entry.iter_files();
-        let files_stream = iter_files.stream();
-        pin_mut!(files_stream);
-        while let Some(file_result) = files_stream.next().await {
-            let file_path: FileResourceFname = file_result?.into();
-            print_path(coder, file_path.take(), relative_parent, false).await?;
-            was.printed = true;
+        let pages = iter_files.stream_pages();
+        pin_mut!(pages);
+        let mut printed = 0;
+        while !listing.is_full(printed) {
+            let Some(page) = pages.next().await else {
+                break;
+            };
+            let batch = listing.files_of(page?, printed);
+            printed += batch.len();
+            was.printed = was.printed || !batch.is_empty();
+            print_paths(coder, batch, listing).await?;
         }
     }
 
     // Recurse into subdirectories
     for subfolder in entry.absolute_subfolders() {
-        let sub_was = ls_recursive(
-            fb.clone(),
-            subfolder,
-            level - 1,
-            relative_parent,
-            coder,
-            what_to_print,
-            was,
-        )
-        .await?;
+        let sub_was = ls_recursive(fb.clone(), subfolder, level - 1, listing, coder, was).await?;
         was = was.reduce(sub_was);
     }
     Ok(was)
 }
 
-async fn print_path(
+/// Get the number of children, owner, and creation date of a folder.
+async fn folder_details(fb: &FileBrowser, path: &FileBrowserPath) -> Result<Details> {
+    let entry = if let Some(entry) = fb.readdir(path).await? {
+        entry
+    } else {
+        return Ok(Default::default());
+    };
+    let children = entry.subfolder_count() + entry.file_count().await?;
+    Ok(Details {
+        size: None,
+        children: Some(children),
+        owner: entry.owner_username().map(|u| u.to_string()),
+        creation_date: entry.creation_date(),
+    })
+}
+
+/// A fname-like path which is yet to be decoded and printed.
+type Unprinted = (String, PathKind, Details);
+
+/// Decode and print a batch of paths. The names of the folders of the whole batch
+/// are looked up together, so a batch takes as many round trips to _CUBE_ as one path.
+async fn print_paths(
     coder: &mut CoderChannel,
-    fnamelike: String,
-    relative_parent: &Option<String>,
-    is_dir: bool,
+    batch: Vec<Unprinted>,
+    listing: &Listing<'_>,
 ) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let relative_parent = &listing.relative_parent;
     let relative_parent_len = relative_parent.as_ref().map(|s| s.len() + 1).unwrap_or(0);
-    let ez_path = coder.decode(fnamelike).await;
-    let rel_path = ez_path.get(relative_parent_len..).ok_or_else(|| {
-        eyre!(
-            "CUBE returned a file path \"{}\" which is not a subpath of parent {:?}",
-            &ez_path,
-            &relative_parent.as_slice()
-        )
-    })?;
-    if is_dir {
-        print_dir(rel_path)
-    } else {
-        print_file(rel_path)
+    let (fnamelikes, rest): (Vec<_>, Vec<_>) = batch
+        .into_iter()
+        .map(|(fnamelike, kind, details)| (fnamelike, (kind, details)))
+        .unzip();
+    let ez_paths = coder.decode_all(fnamelikes).await;
+    for (ez_path, (kind, details)) in ez_paths.into_iter().zip(rest) {
+        let rel_path = ez_path.get(relative_parent_len..).ok_or_else(|| {
+            eyre!(
+                "CUBE returned a file path \"{}\" which is not a subpath of parent {:?}",
+                &ez_path,
+                &relative_parent.as_slice()
+            )
+        })?;
+        listing.emit(Row {
+            path: rel_path.to_string(),
+            kind,
+            details,
+        })?;
     }
     Ok(())
 }
 
-fn print_dir(path: &str) {
-    println!("{}/", path.blue())
+/// A listed file or folder.
+#[derive(Serialize)]
+struct Entry<'a> {
+    path: &'a str,
+    #[serde(rename = "type")]
+    kind: PathKind,
+    /// Given for `--long`
+    #[serde(flatten)]
+    details: Option<&'a Details>,
+}
+
+impl Render for Entry<'_> {
+    fn columns(&self) -> Vec<String> {
+        let kind = match self.kind {
+            PathKind::File => "file",
+            PathKind::Dir => "folder",
+            PathKind::CurrentDir => "current",
+        };
+        let mut columns = vec![kind.to_string(), self.path.to_string()];
+        if let Some(details) = self.details {
+            columns.push(
+                details
+                    .size
+                    .or(details.children.map(|n| n as u64))
+                    .map(|n| n.to_string())
+                    .unwrap_or_default(),
+            );
+            columns.push(details.owner.clone().unwrap_or_default());
+            columns.push(details.creation_date.map(rfc3339).unwrap_or_default());
+        }
+        columns
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize)]
+pub(super) enum PathKind {
+    #[serde(rename = "file")]
+    File,
+    #[serde(rename = "folder")]
+    Dir,
+    /// Folder of the current plugin instance
+    #[serde(rename = "current")]
+    CurrentDir,
 }
 
-fn print_file(path: &str) {
-    let colored = path
-        .rsplit_once('/')
+fn format_path(path: &str, kind: PathKind) -> String {
+    match kind {
+        PathKind::File => format_file(path),
+        PathKind::Dir => format_dir(path),
+        PathKind::CurrentDir => format_current_dir(path),
+    }
+}
+
+fn format_dir(path: &str) -> String {
+    format!("{}/", path.blue())
+}
+
+fn format_current_dir(path: &str) -> String {
+    format!(
+        "{}/ {}",
+        path.blue().bold(),
+        format!("{} current plugin instance", unicode::LEFTWARDS_ARROW).green()
+    )
+}
+
+fn format_file(path: &str) -> String {
+    path.rsplit_once('/')
         .map(|(dir, file)| format!("{}/{}", dir.blue(), file))
-        .unwrap_or_else(|| path.to_string());
-    println!("{}", colored)
+        .unwrap_or_else(|| path.to_string())
 }
 
 #[derive(Default, Clone, Copy)]
@@ -150,3 +413,96 @@ impl WasPrinted {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::MaybeChrisPathHumanCoder;
+    use chris::testing::MockCube;
+    use chris::{EitherClient, PluginInstanceResponse};
+    use rstest::*;
+
+    /// Add a feed "My Feed" to `mock` with a plugin instance titled "root" followed by
+    /// 10 plugin instances titled "Title 2" to "Title 11", each with 5 output files.
+    fn feed_of_siblings(mock: &MockCube) {
+        let plugin = mock.plugin(1, "pl-app", "1.0.0");
+        let feed = mock.feed(1, "My Feed");
+        let root = PluginInstanceResponse {
+            title: "root".to_string(),
+            ..mock.plugin_instance(1, &plugin, &feed, None)
+        };
+        let children: Vec<_> = (2..=11)
+            .map(|id| PluginInstanceResponse {
+                title: format!("Title {}", id),
+                ..mock.plugin_instance(id, &plugin, &feed, Some(&root))
+            })
+            .collect();
+        let files: Vec<_> = (2..=11)
+            .flat_map(|id| (0..5).map(move |i| format!("pl-app_1/pl-app_{}/data/{}.txt", id, i)))
+            .collect();
+        mock.add_plugin(plugin);
+        mock.add_feed_with_files(feed, files.iter().map(|f| (f, "")));
+        mock.add_plugin_instance(root);
+        children
+            .into_iter()
+            .for_each(|c| mock.add_plugin_instance(c));
+    }
+
+    /// Call [ls_recursive] on `path` with titles, and get the paths of the listed rows.
+    async fn list(client: &RoClient, path: &str, level: u16, limit: Option<usize>) -> Vec<String> {
+        let coder = MaybeChrisPathHumanCoder::new(client, true);
+        let (mut coder, decoder_loop) = CoderChannel::create(coder);
+        let filter = FileFilter::default();
+        let listed = async move {
+            let listing = Listing {
+                relative_parent: Some(coder.decode(path.to_string()).await),
+                what_to_print: WhatToPrint::All,
+                root: path,
+                filter: &filter,
+                current: None,
+                records: None,
+                long: Default::default(),
+                limit,
+                collected: Some(Default::default()),
+            };
+            let fb = client.filebrowser();
+            ls_recursive(
+                fb,
+                path.into(),
+                level,
+                &listing,
+                &mut coder,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+            listing.collected.unwrap().into_inner().unwrap()
+        };
+        let (rows, _) = tokio::join!(listed, decoder_loop);
+        rows.into_iter().map(|row| row.path).collect()
+    }
+
+    #[rstest]
+    #[case(None, 71)]
+    #[case(Some(2), 41)]
+    #[tokio::test]
+    async fn test_ls_recursive_requests_each_title_once(
+        #[case] limit: Option<usize>,
+        #[case] expected_count: usize,
+    ) {
+        let mock = MockCube::start().await;
+        feed_of_siblings(&mock);
+        let client = EitherClient::Anon(mock.anon_client().await).into_ro();
+        let paths = list(&client, "chris/feed_1/pl-app_1", 3, limit).await;
+
+        assert_eq!(paths.len(), expected_count);
+        assert_eq!(&paths[..3], ["data", "Title 10", "Title 11"]);
+        assert!(paths.contains(&"Title 11/data/1.txt".to_string()));
+        let title_requests = mock
+            .requests()
+            .into_iter()
+            .filter(|target| target.contains("plugins/instances/"))
+            .count();
+        assert_eq!(title_requests, 11, "every folder should be requested once");
+    }
+}
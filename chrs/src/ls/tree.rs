@@ -1,7 +1,311 @@
-// use crate::get_client::RoClient;
-// use color_eyre::eyre::Result;
-// use crate::ls::coder_channel::DecodeChannel;
-//
-// pub async fn ls_tree(client: &RoClient, path: &str, level: u16, full: bool, coder: DecodeChannel) -> Result<()> {
-//     todo!()
-// }
+use std::collections::HashMap;
+
+use color_eyre::eyre::{eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+use futures::{StreamExt, TryStreamExt};
+use indicatif::HumanBytes;
+
+use chris::types::FileBrowserPath;
+use chris::{Downloadable, FileBrowser, RoClient};
+
+use crate::files::{CoderChannel, FileFilter};
+
+/// Maximum number of subfolders and files to show per folder.
+const MAX_CHILDREN: usize = 100;
+
+/// Number of folders to list at the same time.
+const CONCURRENCY: usize = 4;
+
+/// Print the folders and files under `path` as a tree, like the UNIX `tree` command.
+pub async fn ls_tree(
+    client: &RoClient,
+    path: &str,
+    level: u16,
+    full: bool,
+    filter: &FileFilter,
+    mut coder: CoderChannel,
+) -> Result<()> {
+    let folders = walk(&client.filebrowser(), path, level, filter).await?;
+    let subfolders: Vec<_> = folders
+        .values()
+        .flat_map(|f| f.subfolders.iter().cloned())
+        .collect();
+    let decoded = coder.decode_all(subfolders.clone()).await;
+    let names: HashMap<_, _> = subfolders
+        .into_iter()
+        .zip(decoded)
+        .map(|(subfolder, decoded)| (subfolder, basename(&decoded).to_string()))
+        .collect();
+    let root = if full {
+        path.to_string()
+    } else {
+        coder.decode(path.to_string()).await
+    };
+    println!("{}", root.blue());
+    for (prefix, node) in render(path, &folders, &names) {
+        let line = match node {
+            Node::Folder(name) => name.blue().to_string(),
+            Node::File { name, size } => format!("{} {}", name, HumanBytes(size).dimmed()),
+            Node::More(count) => format!("… and {} more", count).dimmed().to_string(),
+        };
+        println!("{}{}", prefix.dimmed(), line)
+    }
+    Ok(())
+}
+
+/// Contents of a folder, limited to [MAX_CHILDREN] items.
+#[derive(Debug, Default)]
+struct Folder {
+    /// Absolute paths of subfolders
+    subfolders: Vec<String>,
+    /// Basenames and sizes of files
+    files: Vec<(String, u64)>,
+    /// Number of subfolders and files which were not listed
+    more: usize,
+}
+
+/// List the folders under `root` breadth-first, up to `level` folders deep.
+async fn walk(
+    fb: &FileBrowser,
+    root: &str,
+    level: u16,
+    filter: &FileFilter,
+) -> Result<HashMap<String, Folder>> {
+    let mut folders = HashMap::new();
+    let mut frontier = vec![root.to_string()];
+    for _ in 0..level {
+        if frontier.is_empty() {
+            break;
+        }
+        let listed: Vec<_> = futures::stream::iter(frontier)
+            .map(|path| async move {
+                read_folder(fb, &path, root, filter)
+                    .await
+                    .map(|f| (path, f))
+            })
+            .buffered(CONCURRENCY)
+            .try_collect()
+            .await?;
+        frontier = listed
+            .iter()
+            .flat_map(|(_, folder)| folder.subfolders.iter().cloned())
+            .collect();
+        folders.extend(listed);
+    }
+    Ok(folders)
+}
+
+/// List a folder, keeping only the files selected by `filter` relative to `root`.
+async fn read_folder(
+    fb: &FileBrowser,
+    path: &str,
+    root: &str,
+    filter: &FileFilter,
+) -> Result<Folder> {
+    let entry = fb
+        .readdir(path)
+        .await?
+        .ok_or_else(|| eyre!("Path not found: {}", path))?;
+    let total_subfolders = entry.subfolder_count();
+    let subfolders: Vec<_> = entry
+        .absolute_subfolders()
+        .take(MAX_CHILDREN)
+        .map(FileBrowserPath::take)
+        .collect();
+    let max_files = MAX_CHILDREN - subfolders.len();
+    let (files, more_files) = if filter.is_empty() {
+        let search = entry.iter_files().max_items(max_files);
+        let files: Vec<_> = if max_files == 0 {
+            Vec::new()
+        } else {
+            search
+                .stream()
+                .map_ok(|f| (f.basename().to_string(), f.fsize()))
+                .try_collect()
+                .await?
+        };
+        let more_files = if files.len() < max_files {
+            0
+        } else {
+            search.get_count().await? - files.len()
+        };
+        (files, more_files)
+    } else {
+        // the number of matching files can only be known by going through all of them
+        let mut files: Vec<_> = entry
+            .iter_files()
+            .stream()
+            .try_filter(|f| futures::future::ready(filter.is_match_under(f.fname().as_str(), root)))
+            .map_ok(|f| (f.basename().to_string(), f.fsize()))
+            .try_collect()
+            .await?;
+        let more_files = files.len().saturating_sub(max_files);
+        files.truncate(max_files);
+        (files, more_files)
+    };
+    Ok(Folder {
+        more: total_subfolders - subfolders.len() + more_files,
+        subfolders,
+        files,
+    })
+}
+
+/// An item of the tree.
+#[derive(Debug, PartialEq)]
+enum Node {
+    Folder(String),
+    File { name: String, size: u64 },
+    More(usize),
+}
+
+/// Produce the lines of the tree under `root`, which are prefixes made of box-drawing characters
+/// followed by an item. Subfolders are named using `names`, falling back to their basename.
+fn render(
+    root: &str,
+    folders: &HashMap<String, Folder>,
+    names: &HashMap<String, String>,
+) -> Vec<(String, Node)> {
+    let mut lines = Vec::new();
+    render_folder(root, folders, names, "", &mut lines);
+    lines
+}
+
+fn render_folder(
+    path: &str,
+    folders: &HashMap<String, Folder>,
+    names: &HashMap<String, String>,
+    indent: &str,
+    lines: &mut Vec<(String, Node)>,
+) {
+    let folder = if let Some(folder) = folders.get(path) {
+        folder
+    } else {
+        return;
+    };
+    let count = folder.subfolders.len() + folder.files.len() + (folder.more > 0) as usize;
+    let mut i = 0;
+    let mut next_prefix = || {
+        i += 1;
+        let is_last = i == count;
+        let branch = if is_last { "└── " } else { "├── " };
+        let child_indent = if is_last { "    " } else { "│   " };
+        (
+            format!("{}{}", indent, branch),
+            format!("{}{}", indent, child_indent),
+        )
+    };
+    for subfolder in &folder.subfolders {
+        let (prefix, child_indent) = next_prefix();
+        let name = names
+            .get(subfolder)
+            .cloned()
+            .unwrap_or_else(|| basename(subfolder).to_string());
+        lines.push((prefix, Node::Folder(name)));
+        render_folder(subfolder, folders, names, &child_indent, lines);
+    }
+    for (name, size) in &folder.files {
+        let (prefix, _) = next_prefix();
+        lines.push((
+            prefix,
+            Node::File {
+                name: name.clone(),
+                size: *size,
+            },
+        ));
+    }
+    if folder.more > 0 {
+        let (prefix, _) = next_prefix();
+        lines.push((prefix, Node::More(folder.more)));
+    }
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit_once('/').map(|(_, name)| name).unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use chris::BaseChrisClient;
+    use rstest::*;
+
+    #[rstest]
+    fn test_render() {
+        let folders = HashMap::from([
+            (
+                "a".to_string(),
+                Folder {
+                    subfolders: vec!["a/b".to_string(), "a/c".to_string()],
+                    files: vec![("x.txt".to_string(), 5)],
+                    more: 0,
+                },
+            ),
+            (
+                "a/b".to_string(),
+                Folder {
+                    subfolders: vec![],
+                    files: vec![("y.txt".to_string(), 10)],
+                    more: 3,
+                },
+            ),
+            // "a/c" is deeper than the level, so it is not listed
+        ]);
+        let names = HashMap::from([("a/b".to_string(), "Title B".to_string())]);
+        let actual: Vec<_> = render("a", &folders, &names)
+            .into_iter()
+            .map(|(prefix, node)| {
+                let item = match node {
+                    Node::Folder(name) => format!("{}/", name),
+                    Node::File { name, size } => format!("{} {}", name, size),
+                    Node::More(n) => format!("+{}", n),
+                };
+                format!("{}{}", prefix, item)
+            })
+            .collect();
+        let expected = [
+            "├── Title B/",
+            "│   ├── y.txt 10",
+            "│   └── +3",
+            "├── c/",
+            "└── x.txt 5",
+        ];
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_walk() {
+        let mock = MockCube::start().await;
+        mock.add_feed_with_files(
+            mock.feed(1, "My Study"),
+            [
+                ("pl-dircopy_1/data/a.txt", "a"),
+                ("pl-dircopy_1/data/sub/b.txt", "bb"),
+                ("pl-dircopy_1/data/sub/deeper/c.txt", "ccc"),
+            ],
+        );
+        let client = mock.anon_client().await;
+        let folders = walk(
+            &client.filebrowser(),
+            "chris/feed_1/pl-dircopy_1",
+            2,
+            &FileFilter::default(),
+        )
+        .await
+        .unwrap();
+        let mut paths: Vec<_> = folders.keys().map(|p| p.as_str()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "chris/feed_1/pl-dircopy_1",
+                "chris/feed_1/pl-dircopy_1/data"
+            ]
+        );
+        let data = &folders["chris/feed_1/pl-dircopy_1/data"];
+        assert_eq!(data.subfolders, ["chris/feed_1/pl-dircopy_1/data/sub"]);
+        assert_eq!(data.files, [("a.txt".to_string(), 1)]);
+        assert_eq!(data.more, 0);
+    }
+}
@@ -1,45 +1,101 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use camino::Utf8PathBuf;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 
 use chris::types::{CubeUrl, Username};
 
+use crate::account::{account, AccountCommand};
 use crate::arg::GivenDataNode;
+use crate::cancel::{cancel, CancelArgs};
+use crate::cat::{cat, CatArgs};
 use crate::cd::cd;
-use crate::credentials::Credentials;
+use crate::completions::{complete_plugins, completions, Shell};
+use crate::compute::{compute, ComputeCommand};
+use crate::config::{apply_preferences, chosen_preferences, config, ConfigCommand};
+use crate::credentials::{
+    resolve_secret, secret_file_path, Credentials, CUBE_FILE_ENV, TOKEN_FILE_ENV,
+};
 use crate::describe::{describe_runnable, DescribeArgs};
 use crate::download::{download, DownloadArgs};
+use crate::du::{du, DuArgs};
+use crate::feed::{feed, FeedCommand};
+use crate::file_transfer::{Interrupted, ProgressFormat, EXIT_INTERRUPTED};
+use crate::init::{init, InitArgs};
 use crate::list::{list_feeds, ListFeedArgs};
 use crate::login::cmd::{login, logout};
+use crate::login::public::login_public;
+use crate::login::state::ChrsSessions;
 use crate::login::store::Backend;
 use crate::login::switch::switch_login;
-use crate::login::UiUrl;
+use crate::login::{clear_cd, UiUrl};
 use crate::logs::logs;
 use crate::ls::{ls, LsArgs};
+use crate::merge::{merge, MergeArgs};
+use crate::note::{note, NoteArgs};
+use crate::output::OutputFormat;
+use crate::pacs::{pacs, PacsCommand};
+use crate::pipeline::{pipeline, PipelineCommand};
+use crate::pwd::pwd;
+use crate::rerun::{rerun, RerunArgs};
+use crate::rm::{rm, RmArgs};
 use crate::run::{run_command, RunArgs};
 use crate::search::{search_runnable, SearchArgs};
+use crate::set::{set, SetCommand};
 use crate::status::cmd::status;
+use crate::status::{GraphFormat, TimedOut, EXIT_TIMED_OUT};
+use crate::tag::{tag, TagCommand};
 use crate::upload::{upload, UploadArgs};
+use crate::verify::verify;
+use crate::version::version;
 use crate::whoami::whoami;
+use crate::workflow::{workflow, WorkflowCommand};
 
+mod account;
 mod arg;
+mod cancel;
+mod cat;
 mod cd;
+mod completions;
+mod compute;
+mod config;
 mod credentials;
 mod describe;
 mod download;
+mod du;
 mod error_messages;
+mod feed;
 mod file_transfer;
 mod files;
+mod init;
 mod list;
 mod login;
 mod logs;
 mod ls;
+mod merge;
+mod note;
+mod output;
+mod pacs;
+mod pipeline;
 mod plugin_clap;
+mod prompt;
+mod pwd;
+mod rerun;
+mod rm;
 mod run;
+mod sanitize;
 mod search;
+mod set;
 mod shlex;
 mod status;
+mod table;
+mod tag;
 pub mod unicode;
 mod upload;
+mod verify;
+mod version;
 mod whoami;
+mod workflow;
 
 #[derive(Parser)]
 #[clap(
@@ -61,27 +117,61 @@ struct Cli {
     #[clap(long, global = true)]
     username: Option<Username>,
 
-    /// account password
+    /// account password. Takes precedence over --token and the saved login
     #[clap(long, global = true)]
     password: Option<String>,
 
-    /// authorization token
+    /// authorization token, used instead of the saved login.
+    /// --cube is required, and --username is optional
     #[clap(long, global = true)]
     token: Option<String>,
 
-    /// Number of times to retry HTTP requests
+    /// Read the ChRIS backend API URL from a file, e.g. a mounted secret
+    /// [env: CHRS_CUBE_FILE]
+    #[clap(long, global = true)]
+    cube_file: Option<PathBuf>,
+
+    /// Read the authorization token from a file, e.g. a mounted secret.
+    /// Nothing is saved to the configuration file or keyring.
+    /// [env: CHRS_TOKEN_FILE]
+    #[clap(long, global = true)]
+    token_file: Option<PathBuf>,
+
+    /// Number of times to retry HTTP requests, and uploads of files which failed
+    /// because of server errors or timeouts. Files up to 64 MiB are held in memory
+    /// while they are uploaded so that they can be sent again
     #[clap(long)]
     retries: Option<u32>,
 
+    /// Access ChRIS without logging in, even if a login is saved
+    #[clap(long, global = true, conflicts_with_all = ["password", "token", "token_file"])]
+    anonymous: bool,
+
+    /// Output format of list, search, ls, status, and pacs ls
+    #[clap(long, global = true, value_enum, default_value_t)]
+    output: OutputFormat,
+
+    /// How to show the progress of uploads and downloads
+    /// [default: bars if stderr is a terminal, else quiet]
+    #[clap(long, global = true, value_enum)]
+    progress: Option<ProgressFormat>,
+
     #[clap(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Set up chrs for the first time, guided by prompts
+    ///
+    /// Answers can also be given by options, or piped to stdin one per line.
+    Init(InitArgs),
+
     /// Remember login account
     ///
     /// Stores a username and authorization token for a given ChRIS API URL.
+    /// The token is obtained using a password, or given by --token or --token-stdin,
+    /// in which case the username is gotten from ChRIS.
     Login {
         /// Save token in plaintext instead of using keyring
         #[clap(long)]
@@ -90,14 +180,57 @@ enum Commands {
         /// Take the password from stdin
         #[clap(long)]
         password_stdin: bool,
+
+        /// Take the authorization token from stdin
+        #[clap(long, conflicts_with_all = ["password_stdin", "remember_password"])]
+        token_stdin: bool,
+
+        /// Save the password in the keyring, so that a new token can be obtained
+        /// automatically when the saved token expires
+        #[clap(long, conflicts_with = "no_keyring")]
+        remember_password: bool,
+
+        /// Pick from a list of public ChRIS instances, and use it without logging in
+        #[clap(long, conflicts_with_all = ["no_keyring", "password_stdin", "token_stdin", "remember_password"])]
+        public: bool,
     },
 
     /// Remove a user session
     Logout {},
     /// Switch user
-    Switch {},
+    Switch {
+        /// Print saved logins, marking the active one with `*`
+        #[clap(long, conflicts_with = "target")]
+        list: bool,
+
+        /// Login to switch to, given as `username@url`, a username, or a CUBE URL
+        target: Option<String>,
+    },
     /// Show login information
-    Whoami {},
+    Whoami {
+        /// Contact ChRIS to check that the saved login is still valid,
+        /// and exit with an error if it is not
+        #[clap(long)]
+        check: bool,
+    },
+
+    /// Change the password or email address of the current user
+    #[clap(subcommand)]
+    Account(AccountCommand),
+
+    /// Set default options for the current login
+    ///
+    /// Options given on the command line take precedence. Preferences are
+    /// saved per login, see `chrs switch`.
+    #[clap(subcommand)]
+    Config(ConfigCommand),
+
+    /// Show version information
+    Version {
+        /// Check compatibility with the version of CUBE
+        #[clap(long)]
+        check: bool,
+    },
 
     /// List files
     Ls(LsArgs),
@@ -115,7 +248,23 @@ enum Commands {
         /// The value can be a plugin instance ID or title. For a title,
         /// the title must be unique within the search space. The current
         /// feed will be searched before searching across all feeds.
-        plugin_instance: GivenDataNode,
+        #[clap(required_unless_present = "clear")]
+        plugin_instance: Option<GivenDataNode>,
+
+        /// Change into the given plugin instance even if it failed and has no output files
+        #[clap(long)]
+        exact: bool,
+
+        /// Clear the current plugin instance
+        #[clap(long, conflicts_with_all = ["plugin_instance", "exact"])]
+        clear: bool,
+    },
+
+    /// Print the path of the current plugin instance
+    Pwd {
+        /// Show feed names and plugin instance titles instead of folder names
+        #[clap(short, long)]
+        titles: bool,
     },
 
     /// Show status of a feed branch
@@ -124,27 +273,130 @@ enum Commands {
         #[clap(short, long)]
         execshell: bool,
 
+        /// Maximum number of the last plugin instances of the branch to show after its root,
+        /// summarizing the ones in between (0 shows all)
+        #[clap(long, default_value_t = 20)]
+        max_nodes: usize,
+
+        /// Keep refreshing the status until no plugin instances of the feed are waiting
+        /// or running. Exits with an error if any of them failed
+        #[clap(short = 'w', long, visible_alias = "watch")]
+        follow: bool,
+
+        /// Seconds between refreshes of --follow
+        #[clap(long, default_value_t = 10, requires = "follow")]
+        interval: u64,
+
+        /// Get all plugin instances from ChRIS instead of using the ones
+        /// which were saved previously
+        #[clap(long)]
+        no_cache: bool,
+
+        /// Print the graph of all the plugin instances of the feed in a graph description
+        /// language, where failed plugin instances are red and running ones are dashed
+        #[clap(long, value_enum, conflicts_with_all = ["follow", "execshell"])]
+        format: Option<GraphFormat>,
+
         /// Feed or plugin instance
         feed_or_plugin_instance: Option<GivenDataNode>,
     },
 
     /// Show the logs of a plugin instance
     Logs {
+        /// Keep printing new logs until the plugin instance is finished
+        #[clap(short, long)]
+        follow: bool,
+
         /// Plugin instance
         plugin_instance: Option<GivenDataNode>,
     },
 
+    /// Cancel a plugin instance, or the unfinished plugin instances of a feed
+    Cancel(CancelArgs),
+
+    /// Change the title of a plugin instance or the name of a feed
+    #[clap(subcommand)]
+    Set(SetCommand),
+
     /// Describe and get usage of a plugin or pipeline
     Describe(DescribeArgs),
 
     /// Run a plugin or pipeline
     Run(RunArgs),
 
+    /// Re-run the errored plugin instances of a feed, or a plugin instance
+    Rerun(RerunArgs),
+
+    /// Merge the outputs of plugin instances into one plugin instance using pl-topologicalcopy
+    Merge(MergeArgs),
+
     /// Upload files to ChRIS
     Upload(UploadArgs),
 
+    /// Delete uploaded files
+    Rm(RmArgs),
+
+    /// Manage pipelines
+    #[clap(subcommand)]
+    Pipeline(PipelineCommand),
+
+    /// Manage feeds
+    #[clap(subcommand)]
+    Feed(FeedCommand),
+
+    /// Inspect compute resources, where plugin instances run
+    #[clap(subcommand)]
+    Compute(ComputeCommand),
+
+    /// Inspect workflows, i.e. runs of pipelines
+    #[clap(subcommand)]
+    Workflow(WorkflowCommand),
+
+    /// Show or edit the note of a feed
+    Note(NoteArgs),
+
+    /// Create tags, and put them on feeds
+    #[clap(subcommand)]
+    Tag(TagCommand),
+
+    /// Browse files retrieved from PACS
+    #[clap(subcommand)]
+    Pacs(PacsCommand),
+
     /// Download files from ChRIS
     Download(DownloadArgs),
+
+    /// Print files from ChRIS to stdout
+    Cat(CatArgs),
+
+    /// Report the storage used by a feed, plugin instance, path, or all your files
+    Du(DuArgs),
+
+    /// Check local files against checksums written by `upload --checksum` or `download --checksum`,
+    /// or their sizes against the manifest written by `download --manifest`
+    Verify {
+        /// File of checksums, or a directory downloaded with `download --manifest`
+        manifest: Utf8PathBuf,
+
+        /// Directory containing the files (default: directory of MANIFEST)
+        dir: Option<Utf8PathBuf>,
+    },
+
+    /// Print a shell completion script
+    ///
+    /// For example, add `source <(chrs completions bash)` to ~/.bashrc
+    Completions {
+        /// Shell to complete commands of
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print the names of plugins starting with a prefix, for shell completion
+    #[clap(name = completions::COMPLETE_PLUGINS, hide = true)]
+    CompletePlugins {
+        #[clap(default_value = "")]
+        prefix: String,
+    },
     // /// Get detailed information about a ChRIS object
     // ///
     // /// An object may be a plugin, plugin instance, pipeline, feed, or file.
@@ -191,45 +443,144 @@ async fn main() -> color_eyre::eyre::Result<()> {
         .display_location_section(false)
         .install()?;
 
-    let args: Cli = Cli::parse();
+    chris::search::set_default_warning_sink(std::sync::Arc::new(|warning| {
+        eprintln!("WARNING: {}", warning)
+    }));
+
+    let preferences = ChrsSessions::load_existing()
+        .map(|sessions| chosen_preferences(&sessions, std::env::args_os()))
+        .unwrap_or_default();
+    let matches = apply_preferences(Cli::command(), &preferences).get_matches();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let env = |name: &str| std::env::var_os(name);
+    let (cube_url, _) = resolve_secret(
+        args.cube,
+        secret_file_path(args.cube_file, CUBE_FILE_ENV, env),
+        |s| CubeUrl::try_from(s).map_err(color_eyre::eyre::Error::new),
+    )?;
+    let (token, ephemeral) = resolve_secret(
+        args.token,
+        secret_file_path(args.token_file, TOKEN_FILE_ENV, env),
+        Ok,
+    )?;
     let credentials = Credentials {
-        cube_url: args.cube,
+        cube_url,
         username: args.username,
         password: args.password,
-        token: args.token,
+        token,
         retries: args.retries,
         ui: args.ui,
         config_path: None,
+        ephemeral,
+        anonymous: args.anonymous,
     };
 
-    match args.command {
+    let output = args.output;
+    let progress = ProgressFormat::or_default(args.progress);
+    let result = match args.command {
+        Commands::Init(args) => init(credentials, args).await,
+        Commands::Login { public: true, .. } => login_public(credentials).await,
         Commands::Login {
             no_keyring,
             password_stdin,
+            token_stdin,
+            remember_password,
+            ..
         } => {
             let backend = if no_keyring {
                 Backend::ClearText
             } else {
                 Backend::Keyring
             };
-            login(credentials, backend, password_stdin).await
+            login(
+                credentials,
+                backend,
+                password_stdin,
+                token_stdin,
+                remember_password,
+            )
+            .await
         }
-        Commands::Switch {} => switch_login(credentials),
-        Commands::Whoami {} => whoami(credentials),
+        Commands::Switch { list, target } => switch_login(credentials, target, list),
+        Commands::Whoami { check } => whoami(credentials, check).await,
+        Commands::Account(command) => account(credentials, command).await,
         Commands::Logout {} => logout(credentials),
+        Commands::Config(command) => config(credentials, command),
 
-        Commands::Ls(args) => ls(credentials, args).await,
-        Commands::Cd { plugin_instance } => cd(credentials, plugin_instance).await,
+        Commands::Version { check } => version(credentials, check).await,
+        Commands::Ls(args) => ls(credentials, args, output).await,
+        Commands::Cd {
+            plugin_instance: Some(plugin_instance),
+            exact,
+            ..
+        } => cd(credentials, plugin_instance, exact).await,
+        Commands::Cd { .. } => clear_cd(credentials),
+        Commands::Pwd { titles } => pwd(credentials, titles).await,
         Commands::Status {
             feed_or_plugin_instance,
             execshell,
-        } => status(credentials, feed_or_plugin_instance, execshell).await,
-        Commands::Logs { plugin_instance } => logs(credentials, plugin_instance).await,
-        Commands::List(args) => list_feeds(credentials, args).await,
-        Commands::Search(args) => search_runnable(credentials, args).await,
+            max_nodes,
+            follow,
+            interval,
+            no_cache,
+            format,
+        } => {
+            status(
+                credentials,
+                feed_or_plugin_instance,
+                execshell,
+                max_nodes,
+                follow.then(|| std::time::Duration::from_secs(interval)),
+                no_cache,
+                format,
+                output,
+            )
+            .await
+        }
+        Commands::Logs {
+            plugin_instance,
+            follow,
+        } => logs(credentials, plugin_instance, follow).await,
+        Commands::List(args) => list_feeds(credentials, args, output).await,
+        Commands::Search(args) => search_runnable(credentials, args, output).await,
         Commands::Describe(args) => describe_runnable(credentials, args).await,
         Commands::Run(args) => run_command(credentials, args).await,
-        Commands::Download(args) => download(credentials, args).await,
-        Commands::Upload(args) => upload(credentials, args).await,
+        Commands::Rerun(args) => rerun(credentials, args).await,
+        Commands::Merge(args) => merge(credentials, args).await,
+        Commands::Cancel(args) => cancel(credentials, args).await,
+        Commands::Set(command) => set(credentials, command).await,
+        Commands::Download(args) => download(credentials, args, progress).await,
+        Commands::Cat(args) => cat(credentials, args).await,
+        Commands::Du(args) => du(credentials, args).await,
+        Commands::Upload(args) => upload(credentials, args, progress).await,
+        Commands::Rm(args) => rm(credentials, args).await,
+        Commands::Pipeline(command) => pipeline(credentials, command).await,
+        Commands::Feed(command) => feed(credentials, command).await,
+        Commands::Workflow(command) => workflow(credentials, command).await,
+        Commands::Compute(command) => compute(credentials, command, output).await,
+        Commands::Note(args) => note(credentials, args).await,
+        Commands::Tag(command) => tag(credentials, command, output).await,
+        Commands::Pacs(command) => pacs(credentials, command, output).await,
+        Commands::Verify { manifest, dir } => verify(manifest, dir).await,
+        Commands::Completions { shell } => completions(Cli::command(), shell),
+        Commands::CompletePlugins { prefix } => complete_plugins(credentials, prefix).await,
+    };
+    let result = credentials::suggest_login_if_unauthorized(result);
+    let interrupted = result
+        .as_ref()
+        .err()
+        .and_then(|e| e.downcast_ref::<Interrupted>());
+    if let Some(interrupted) = interrupted {
+        eprintln!("{}", interrupted);
+        std::process::exit(EXIT_INTERRUPTED)
+    }
+    if let Some(timed_out) = result
+        .as_ref()
+        .err()
+        .and_then(|e| e.downcast_ref::<TimedOut>())
+    {
+        eprintln!("{}", timed_out);
+        std::process::exit(EXIT_TIMED_OUT)
     }
+    result
 }
@@ -0,0 +1,223 @@
+//! `chrs merge`: merge the outputs of plugin instances using `pl-topologicalcopy`.
+
+use clap::Parser;
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use color_eyre::owo_colors::OwoColorize;
+use futures::{StreamExt, TryStreamExt};
+use itertools::Itertools;
+
+use chris::types::PluginInstanceId;
+use chris::{BaseChrisClient, ChrisClient, PluginInstanceResponse, PluginInstanceRw};
+
+use crate::arg::GivenDataNode;
+use crate::credentials::Credentials;
+use crate::sanitize::sanitize_for_terminal;
+
+/// Maximum number of inputs to resolve at the same time.
+const CONCURRENCY: usize = 4;
+
+/// _CUBE_ does not allow plugin instance titles to be longer than 100 characters.
+const MAX_TITLE_LEN: usize = 100;
+
+#[derive(Parser)]
+pub struct MergeArgs {
+    /// Title of the created plugin instance [default: "Merge of: " followed by the
+    /// titles of the inputs]
+    #[clap(short, long)]
+    title: Option<String>,
+
+    /// Only copy files matching this glob. Given to pl-topologicalcopy's --filter
+    #[clap(long)]
+    filter: Option<String>,
+
+    /// Merge plugin instances even if they are from different feeds
+    #[clap(short, long)]
+    force: bool,
+
+    /// Plugin instances to merge
+    #[clap(required = true, num_args = 2..)]
+    inputs: Vec<GivenDataNode>,
+}
+
+/// `chrs merge` command
+pub async fn merge(credentials: Credentials, args: MergeArgs) -> Result<()> {
+    let given_args: Vec<_> = args.inputs.iter().map(|g| g.as_arg_str()).collect();
+    let (client, old, _) = credentials.clone().get_client(&given_args).await?;
+    let client = client.logged_in().ok_or_else(|| {
+        eyre!(
+            "This command is only available for authenticated users. Try running `{}` with a username first.",
+            "chrs login".bold()
+        )
+    })?;
+    let inputs: Vec<_> = futures::stream::iter(args.inputs)
+        .map(|given| given.into_plinst_rw(&client, old))
+        .buffered(CONCURRENCY)
+        .try_collect()
+        .await?;
+    if !args.force {
+        check_same_feed(inputs.iter().map(|p| &p.object))?;
+    }
+    let merged = topologicalcopy(&client, inputs, args.title, args.filter).await?;
+    let id = merged.object.id;
+    crate::login::set_cd(
+        client.url(),
+        client.username(),
+        id,
+        credentials.config_path,
+        credentials.ephemeral,
+    )?;
+    println!("plugininstance/{}", id.0);
+    Ok(())
+}
+
+/// Fail if the plugin instances are not all from the same feed, since merging plugin
+/// instances of different feeds is usually a mistake.
+fn check_same_feed<'a>(
+    plugin_instances: impl IntoIterator<Item = &'a PluginInstanceResponse>,
+) -> Result<()> {
+    let mut plugin_instances = plugin_instances.into_iter();
+    let first = if let Some(first) = plugin_instances.next() {
+        first
+    } else {
+        return Ok(());
+    };
+    if let Some(other) = plugin_instances.find(|p| p.feed_id != first.feed_id) {
+        bail!(
+            "plugininstance/{} is in feed/{} but plugininstance/{} is in feed/{}. Use --force to merge them anyway.",
+            first.id.0,
+            first.feed_id.0,
+            other.id.0,
+            other.feed_id.0
+        )
+    }
+    Ok(())
+}
+
+/// Run `pl-topologicalcopy` to merge the outputs of `inputs`.
+///
+/// The created plugin instance comes after the first of `inputs`.
+pub(crate) async fn topologicalcopy(
+    client: &ChrisClient,
+    inputs: Vec<PluginInstanceRw>,
+    title: Option<String>,
+    filter: Option<String>,
+) -> Result<PluginInstanceRw> {
+    let previous: Vec<_> = inputs.into_iter().map(|p| p.object).collect();
+    let topologicalcopy = client
+        .plugin()
+        .name_exact("pl-topologicalcopy")
+        .version("1.0.2")
+        .search()
+        .get_only()
+        .await
+        .wrap_err("pl-topologicalcopy@1.0.2 not found")?;
+    let params = TopologicalCopyParameters::new(&previous, title, filter);
+    let created = topologicalcopy.create_instance(&params).await?;
+    Ok(created)
+}
+
+#[derive(serde::Serialize)]
+struct TopologicalCopyParameters {
+    previous_id: PluginInstanceId,
+    plugininstances: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filter: Option<String>,
+}
+
+impl TopologicalCopyParameters {
+    fn new(
+        previous: &[PluginInstanceResponse],
+        title: Option<String>,
+        filter: Option<String>,
+    ) -> Self {
+        let title = title.unwrap_or_else(|| {
+            format!(
+                "Merge of: {}",
+                previous
+                    .iter()
+                    .map(quoted_title_of_plinst_response)
+                    .join(" ")
+            )
+        });
+        Self {
+            previous_id: previous.first().unwrap().id,
+            plugininstances: previous.iter().map(|p| p.id.0.to_string()).join(","),
+            title: truncate_title(title),
+            filter,
+        }
+    }
+}
+
+/// Shorten a title to [MAX_TITLE_LEN] characters, ending it with "..." if it was too long.
+fn truncate_title(title: String) -> String {
+    if title.chars().count() > MAX_TITLE_LEN {
+        let shortened: String = title.chars().take(MAX_TITLE_LEN - 3).collect();
+        format!("{}...", shortened)
+    } else {
+        title
+    }
+}
+
+fn quoted_title_of_plinst_response(p: &PluginInstanceResponse) -> String {
+    if p.title.is_empty() {
+        format!(
+            "{}#{}",
+            sanitize_for_terminal(p.plugin_name.as_str()),
+            p.id.0
+        )
+    } else {
+        format!("\"{}\"", sanitize_for_terminal(&p.title))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::types::FeedId;
+    use rstest::*;
+    use std::path::PathBuf;
+
+    #[fixture]
+    #[once]
+    fn plugin_instances() -> Vec<PluginInstanceResponse> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("cube_chrisproject_org_feed_45_plugininstances_results.json");
+        serde_json::from_str(&fs_err::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[rstest]
+    #[case("short title", "short title")]
+    #[case(&"a".repeat(100), &"a".repeat(100))]
+    #[case(&"a".repeat(101), &format!("{}...", "a".repeat(97)))]
+    // must not panic by slicing in the middle of a multibyte character
+    #[case(&"é".repeat(101), &format!("{}...", "é".repeat(97)))]
+    fn test_truncate_title(#[case] title: &str, #[case] expected: &str) {
+        let actual = truncate_title(title.to_string());
+        assert_eq!(actual, expected);
+        assert!(actual.chars().count() <= MAX_TITLE_LEN)
+    }
+
+    #[rstest]
+    fn test_default_title_is_truncated(plugin_instances: &[PluginInstanceResponse]) {
+        let mut many = plugin_instances.to_vec();
+        for p in many.iter_mut() {
+            p.title = "a rather long title for a plugin instance".to_string();
+        }
+        let params = TopologicalCopyParameters::new(&many, None, None);
+        assert!(params.title.starts_with("Merge of: \"a rather long title"));
+        assert!(params.title.ends_with("..."));
+        assert_eq!(params.title.chars().count(), MAX_TITLE_LEN);
+    }
+
+    #[rstest]
+    fn test_check_same_feed(plugin_instances: &[PluginInstanceResponse]) {
+        assert!(check_same_feed(plugin_instances).is_ok());
+        let mut other_feed = plugin_instances[1].clone();
+        other_feed.feed_id = FeedId(plugin_instances[0].feed_id.0 + 1);
+        let mixed = [plugin_instances[0].clone(), other_feed];
+        let error = check_same_feed(&mixed).unwrap_err().to_string();
+        assert!(error.contains("--force"), "{}", error);
+    }
+}
@@ -0,0 +1,123 @@
+//! `chrs note`: read or write the note of a feed.
+
+use clap::Parser;
+use color_eyre::eyre::{eyre, Error, OptionExt, Result};
+use color_eyre::owo_colors::OwoColorize;
+use dialoguer::Editor;
+use itertools::Itertools;
+
+use chris::types::PluginInstanceId;
+use chris::{EitherClient, FeedRo};
+
+use crate::arg::{FeedOrPluginInstance, GivenDataNode};
+use crate::credentials::Credentials;
+use crate::sanitize::sanitize_for_terminal;
+
+/// Title of notes created by chrs.
+const DEFAULT_TITLE: &str = "Description";
+
+#[derive(Parser)]
+pub struct NoteArgs {
+    /// Replace the note with the given text
+    #[clap(short, long, conflicts_with = "edit")]
+    set: Option<String>,
+
+    /// Edit the note using $EDITOR
+    #[clap(short, long)]
+    edit: bool,
+
+    /// Feed, or a plugin instance of the feed (default: feed of current plugin instance)
+    feed: Option<GivenDataNode>,
+}
+
+pub async fn note(credentials: Credentials, args: NoteArgs) -> Result<()> {
+    let (client, old, _) = credentials
+        .get_client(args.feed.as_ref().map(|f| f.as_arg_str()).as_slice())
+        .await?;
+    let given = args.feed.or(old.map(GivenDataNode::from)).ok_or_else(|| {
+        eyre!(
+            "No feed given. Specify a feed, or run `{}` first.",
+            "chrs cd".bold()
+        )
+    })?;
+    if args.set.is_none() && !args.edit {
+        let feed = get_feed_ro(&client, given, old).await?;
+        let note = feed.note().get().await?;
+        if !note.is_empty() {
+            println!("{}", printable(&note.object.content));
+        }
+        return Ok(());
+    }
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to edit feed notes.")?;
+    let feed = given.into_feed_rw(&client, old).await?;
+    let note = feed.note().get().await?;
+    let content = if let Some(content) = args.set {
+        content
+    } else if let Some(content) = edit(Editor::new(), &note.object.content)? {
+        content
+    } else {
+        eprintln!("Note was not saved, nothing changed.");
+        return Ok(());
+    };
+    let title = if note.object.title.is_empty() {
+        DEFAULT_TITLE.to_string()
+    } else {
+        note.object.title.clone()
+    };
+    note.set(title, content).await?;
+    Ok(())
+}
+
+async fn get_feed_ro(
+    client: &EitherClient,
+    given: GivenDataNode,
+    old: Option<PluginInstanceId>,
+) -> Result<FeedRo> {
+    match given.into_or(client, old).await? {
+        FeedOrPluginInstance::Feed(feed) => Ok(feed),
+        FeedOrPluginInstance::PluginInstance(p) => p.feed().get().await.map_err(Error::new),
+    }
+}
+
+/// Edit `content` using `editor`. Returns `None` if the file was not saved.
+fn edit(mut editor: Editor, content: &str) -> Result<Option<String>> {
+    editor
+        .extension(".md")
+        .edit(content)
+        .map_err(|e| eyre!("Could not open editor: {}", e))
+}
+
+/// Make note content safe to print to the terminal, keeping its line breaks.
+fn printable(content: &str) -> String {
+    content.lines().map(sanitize_for_terminal).join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const CONTENT: &str = "# Résumé\n\nline two — 日本語\n\tindented 🧠\n";
+
+    #[rstest]
+    #[cfg(unix)]
+    fn test_edit_round_trip() {
+        let mut editor = Editor::new();
+        editor
+            .executable("true")
+            .require_save(false)
+            .trim_newlines(false);
+        let actual = edit(editor, CONTENT).unwrap();
+        assert_eq!(actual.as_deref(), Some(CONTENT));
+    }
+
+    #[rstest]
+    #[case(CONTENT, "# Résumé\n\nline two — 日本語\n\\tindented 🧠")]
+    #[case("a\r\nb", "a\nb")]
+    #[case("\x1b[31mred\x1b[0m", "red")]
+    fn test_printable(#[case] content: &str, #[case] expected: &str) {
+        assert_eq!(printable(content), expected)
+    }
+}
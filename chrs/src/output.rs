@@ -0,0 +1,214 @@
+//! Machine-readable output of list-style subcommands, selected by `--output`.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use color_eyre::eyre;
+use futures::{future, Stream, TryStreamExt};
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use chris::{FeedResponse, PipelineResponse, PluginInstanceResponse, PluginResponse};
+
+/// How list-style subcommands print their results.
+#[derive(clap::ValueEnum, Copy, Clone, Default, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Colored output for humans
+    #[default]
+    Human,
+    /// One record per line with tab-separated columns
+    Plain,
+    /// JSON array of the objects returned by CUBE
+    Json,
+}
+
+impl OutputFormat {
+    pub fn is_human(&self) -> bool {
+        matches!(self, Self::Human)
+    }
+}
+
+/// A record which can be printed by [RecordWriter].
+pub trait Render: Serialize {
+    /// Columns of this record for `--output plain`.
+    fn columns(&self) -> Vec<String>;
+}
+
+/// Prints records in the format of `--output plain` or `--output json`.
+///
+/// [RecordWriter::finish] must be called after all records are written,
+/// so that the JSON array is closed.
+pub struct RecordWriter<W: Write> {
+    format: OutputFormat,
+    out: Mutex<W>,
+    count: AtomicUsize,
+}
+
+impl RecordWriter<std::io::Stdout> {
+    pub fn stdout(format: OutputFormat) -> Self {
+        Self::new(format, std::io::stdout())
+    }
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(format: OutputFormat, out: W) -> Self {
+        Self {
+            format,
+            out: Mutex::new(out),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn write(&self, record: &impl Render) -> std::io::Result<()> {
+        let mut out = self.out.lock().unwrap();
+        match self.format {
+            OutputFormat::Json => {
+                let separator = if self.count.load(Ordering::Relaxed) == 0 {
+                    "["
+                } else {
+                    ","
+                };
+                write!(out, "{}", separator)?;
+                serde_json::to_writer(&mut *out, record)?;
+            }
+            _ => {
+                let columns: Vec<_> = record.columns().iter().map(|c| plain(c)).collect();
+                writeln!(out, "{}", columns.join("\t"))?;
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn finish(self) -> std::io::Result<W> {
+        let mut out = self.out.into_inner().unwrap();
+        if self.format == OutputFormat::Json {
+            let end = if self.count.into_inner() == 0 {
+                "[]"
+            } else {
+                "]"
+            };
+            writeln!(out, "{}", end)?;
+        }
+        out.flush()?;
+        Ok(out)
+    }
+}
+
+/// Write all records of `stream`.
+pub async fn write_stream<T, E>(
+    stream: impl Stream<Item = Result<T, E>>,
+    format: OutputFormat,
+) -> eyre::Result<()>
+where
+    T: Render,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let writer = RecordWriter::stdout(format);
+    stream
+        .map_err(eyre::Error::new)
+        .try_for_each(|record| future::ready(writer.write(&record).map_err(eyre::Error::new)))
+        .await?;
+    writer.finish()?;
+    Ok(())
+}
+
+impl Render for FeedResponse {
+    fn columns(&self) -> Vec<String> {
+        vec![
+            format!("feed/{}", self.id.0),
+            self.name.clone(),
+            self.creator_username.to_string(),
+            self.is_public().to_string(),
+            rfc3339(self.creation_date),
+        ]
+    }
+}
+
+impl Render for PluginResponse {
+    fn columns(&self) -> Vec<String> {
+        vec![
+            format!("plugin/{}", self.id.0),
+            self.name.to_string(),
+            self.version.to_string(),
+        ]
+    }
+}
+
+impl Render for PipelineResponse {
+    fn columns(&self) -> Vec<String> {
+        vec![format!("pipeline/{}", self.id.0), self.name.clone()]
+    }
+}
+
+impl Render for PluginInstanceResponse {
+    fn columns(&self) -> Vec<String> {
+        vec![
+            format!("plugininstance/{}", self.id.0),
+            self.title.clone(),
+            format!("{}@{}", self.plugin_name, self.plugin_version),
+            self.status.as_str().to_string(),
+            self.previous_id
+                .map(|id| format!("plugininstance/{}", id.0))
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+pub(crate) fn rfc3339(time: OffsetDateTime) -> String {
+    time.format(&Rfc3339).unwrap_or_default()
+}
+
+/// Replace tabs and line breaks, which would break up the columns of `--output plain`.
+fn plain(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[derive(Serialize)]
+    struct Fake {
+        id: u32,
+        name: &'static str,
+    }
+
+    impl Render for Fake {
+        fn columns(&self) -> Vec<String> {
+            vec![self.id.to_string(), self.name.to_string()]
+        }
+    }
+
+    const RECORDS: [Fake; 2] = [
+        Fake {
+            id: 1,
+            name: "a\tb",
+        },
+        Fake {
+            id: 2,
+            name: "hello",
+        },
+    ];
+
+    #[rstest]
+    #[case(OutputFormat::Plain, 2, "1\ta b\n2\thello\n")]
+    #[case(OutputFormat::Plain, 0, "")]
+    #[case(
+        OutputFormat::Json,
+        2,
+        "[{\"id\":1,\"name\":\"a\\tb\"},{\"id\":2,\"name\":\"hello\"}]\n"
+    )]
+    #[case(OutputFormat::Json, 0, "[]\n")]
+    fn test_record_writer(#[case] format: OutputFormat, #[case] n: usize, #[case] expected: &str) {
+        let writer = RecordWriter::new(format, Vec::new());
+        for record in &RECORDS[..n] {
+            writer.write(record).unwrap();
+        }
+        let actual = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(actual, expected)
+    }
+}
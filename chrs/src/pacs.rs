@@ -0,0 +1,280 @@
+//! `chrs pacs`: files retrieved from PACS.
+
+use std::collections::BTreeMap;
+
+use clap::{Parser, Subcommand};
+use color_eyre::eyre::{eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+use futures::TryStreamExt;
+use indicatif::HumanBytes;
+use serde::Serialize;
+
+use chris::PacsFileResponse;
+
+use crate::credentials::{Credentials, NO_ARGS};
+use crate::output::{OutputFormat, RecordWriter, Render};
+use crate::sanitize::sanitize_for_terminal;
+
+#[derive(Subcommand)]
+pub enum PacsCommand {
+    /// List PACS files by patient, study, and series
+    Ls(PacsLsArgs),
+}
+
+#[derive(Parser)]
+pub struct PacsLsArgs {
+    /// Patient ID to filter by
+    #[clap(long)]
+    patient_id: Option<String>,
+
+    /// Study instance UID to filter by
+    #[clap(long)]
+    study_instance_uid: Option<String>,
+
+    /// Series instance UID to filter by
+    #[clap(long)]
+    series_instance_uid: Option<String>,
+
+    /// Modality to filter by, e.g. MR
+    #[clap(long)]
+    modality: Option<String>,
+
+    /// Name of the PACS the files were retrieved from
+    #[clap(long)]
+    pacs: Option<String>,
+}
+
+/// `chrs pacs` command
+pub async fn pacs(
+    credentials: Credentials,
+    command: PacsCommand,
+    output: OutputFormat,
+) -> Result<()> {
+    match command {
+        PacsCommand::Ls(args) => ls_pacs(credentials, args, output).await,
+    }
+}
+
+async fn ls_pacs(credentials: Credentials, args: PacsLsArgs, output: OutputFormat) -> Result<()> {
+    let (client, _, _) = credentials.get_client(NO_ARGS).await?;
+    let client = client
+        .logged_in()
+        .ok_or_else(|| eyre!("You must be logged in to list PACS files."))?;
+    let mut query = client.pacsfiles();
+    if let Some(patient_id) = args.patient_id {
+        query = query.patient_id(patient_id);
+    }
+    if let Some(uid) = args.study_instance_uid {
+        query = query.study_instance_uid(uid);
+    }
+    if let Some(uid) = args.series_instance_uid {
+        query = query.series_instance_uid(uid);
+    }
+    if let Some(modality) = args.modality {
+        query = query.modality(modality);
+    }
+    if let Some(pacs) = args.pacs {
+        query = query.pacs_identifier(pacs);
+    }
+    let files: Vec<_> = query.search().stream().try_collect().await?;
+    let series = summarize(files);
+    if output.is_human() {
+        if series.is_empty() {
+            eprintln!("No PACS files found.");
+        }
+        for line in render(&series) {
+            println!("{}", line);
+        }
+    } else {
+        let writer = RecordWriter::stdout(output);
+        for s in &series {
+            writer.write(s)?;
+        }
+        writer.finish()?;
+    }
+    Ok(())
+}
+
+/// Summary of the files of a DICOM series.
+#[derive(Debug, Serialize, PartialEq)]
+struct Series {
+    patient_id: String,
+    patient_name: Option<String>,
+    patient_birth_date: Option<String>,
+    study_instance_uid: String,
+    study_date: String,
+    study_description: Option<String>,
+    series_instance_uid: String,
+    series_description: Option<String>,
+    modality: Option<String>,
+    files: usize,
+    size: u64,
+}
+
+impl Render for Series {
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.patient_id.clone(),
+            self.study_instance_uid.clone(),
+            self.series_instance_uid.clone(),
+            self.modality.clone().unwrap_or_default(),
+            self.series_description.clone().unwrap_or_default(),
+            self.files.to_string(),
+            self.size.to_string(),
+        ]
+    }
+}
+
+/// Group files into series, sorted by patient, study date, and study.
+fn summarize(files: impl IntoIterator<Item = PacsFileResponse>) -> Vec<Series> {
+    let mut series: BTreeMap<(String, String, String, String), Series> = BTreeMap::new();
+    for file in files {
+        let key = (
+            file.patient_id.clone(),
+            file.study_date.clone(),
+            file.study_instance_uid.clone(),
+            file.series_instance_uid.clone(),
+        );
+        let summary = series.entry(key).or_insert_with(|| Series {
+            patient_id: file.patient_id,
+            patient_name: file.patient_name,
+            patient_birth_date: file.patient_birth_date,
+            study_instance_uid: file.study_instance_uid,
+            study_date: file.study_date,
+            study_description: file.study_description,
+            series_instance_uid: file.series_instance_uid,
+            series_description: file.series_description,
+            modality: file.modality,
+            files: 0,
+            size: 0,
+        });
+        summary.files += 1;
+        summary.size += file.fsize;
+    }
+    series.into_values().collect()
+}
+
+/// Lines of a table of series, indented under their patient and study.
+fn render(series: &[Series]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut patient = None;
+    let mut study = None;
+    for s in series {
+        if patient != Some(&s.patient_id) {
+            patient = Some(&s.patient_id);
+            study = None;
+            let name = s
+                .patient_name
+                .as_deref()
+                .map(|n| format!(" {}", sanitize_for_terminal(&n.replace('^', " "))))
+                .unwrap_or_default();
+            let birth_date = s
+                .patient_birth_date
+                .as_deref()
+                .map(|d| format!(" (born {})", d))
+                .unwrap_or_default();
+            lines.push(format!(
+                "{}{}{}",
+                sanitize_for_terminal(&s.patient_id).bold(),
+                name,
+                birth_date.dimmed()
+            ));
+        }
+        if study != Some(&s.study_instance_uid) {
+            study = Some(&s.study_instance_uid);
+            lines.push(format!(
+                "  {} {} {}",
+                s.study_date,
+                sanitize_for_terminal(s.study_description.as_deref().unwrap_or("")).blue(),
+                s.study_instance_uid.dimmed()
+            ));
+        }
+        lines.push(format!(
+            "    {:<3} {} {}",
+            s.modality.as_deref().unwrap_or("?"),
+            sanitize_for_terminal(s.series_description.as_deref().unwrap_or("")),
+            format!(
+                "({} file{}, {})",
+                s.files,
+                if s.files == 1 { "" } else { "s" },
+                HumanBytes(s.size)
+            )
+            .dimmed()
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn pacs_file(patient: &str, study: &str, series: &str, fsize: u64) -> PacsFileResponse {
+        serde_json::from_value(serde_json::json!({
+            "url": "https://cube.example.org/api/v1/pacsfiles/1/",
+            "id": 1,
+            "creation_date": "2024-03-01T14:06:10.372393-05:00",
+            "fname": format!("SERVICES/PACS/ORTHANC/{patient}/{study}/{series}/1.dcm"),
+            "fsize": fsize,
+            "file_resource": "https://cube.example.org/api/v1/pacsfiles/1/1.dcm",
+            "pacs_identifier": "ORTHANC",
+            "PatientID": patient,
+            "PatientName": "DOE^JANE",
+            "PatientBirthDate": "2009-07-01",
+            "StudyDate": format!("2013-03-0{}", &study[study.len() - 1..]),
+            "StudyInstanceUID": study,
+            "StudyDescription": "Brain",
+            "SeriesInstanceUID": series,
+            "SeriesDescription": "SAG MPRAGE",
+            "Modality": "MR",
+        }))
+        .unwrap()
+    }
+
+    #[rstest]
+    fn test_summarize() {
+        let files = [
+            pacs_file("p2", "s3", "a", 1),
+            pacs_file("p1", "s2", "b", 10),
+            pacs_file("p1", "s1", "c", 100),
+            pacs_file("p1", "s2", "b", 1000),
+        ];
+        let actual: Vec<_> = summarize(files)
+            .into_iter()
+            .map(|s| {
+                (
+                    s.patient_id,
+                    s.study_instance_uid,
+                    s.series_instance_uid,
+                    s.files,
+                    s.size,
+                )
+            })
+            .collect();
+        let expected = [
+            ("p1", "s1", "c", 1, 100),
+            ("p1", "s2", "b", 2, 1010),
+            ("p2", "s3", "a", 1, 1),
+        ]
+        .map(|(p, st, se, n, size)| (p.to_string(), st.to_string(), se.to_string(), n, size));
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    fn test_render_groups_by_patient_and_study() {
+        let files = [
+            pacs_file("p1", "s1", "a", 1),
+            pacs_file("p1", "s1", "b", 1),
+            pacs_file("p1", "s2", "c", 1),
+            pacs_file("p2", "s3", "d", 1),
+        ];
+        let lines = render(&summarize(files));
+        let indents: Vec<_> = lines
+            .iter()
+            .map(|line| line.len() - line.trim_start().len())
+            .collect();
+        assert_eq!(indents, [0, 2, 4, 4, 2, 4, 0, 2, 4]);
+        assert!(lines[0].contains("DOE JANE"));
+    }
+}
@@ -0,0 +1,240 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::Subcommand;
+use color_eyre::eyre::{bail, eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+use itertools::Itertools;
+
+use chris::pipeline::{CanonPipeline, PossiblyExpandedTreePipeline, TitleIndexedPipeline};
+use chris::types::{PluginName, PluginVersion};
+use chris::{BaseChrisClient, ChrisClient};
+
+use crate::credentials::{Credentials, NO_ARGS};
+
+#[derive(Subcommand)]
+pub enum PipelineCommand {
+    /// Upload a pipeline
+    Upload {
+        /// Pipeline file, either YAML in the format of RFC #2 (.yml or .yaml)
+        /// or JSON in the canonical format (.json)
+        file: Utf8PathBuf,
+    },
+}
+
+/// `chrs pipeline` command
+pub async fn pipeline(credentials: Credentials, command: PipelineCommand) -> Result<()> {
+    match command {
+        PipelineCommand::Upload { file } => upload_pipeline(credentials, file).await,
+    }
+}
+
+async fn upload_pipeline(credentials: Credentials, file: Utf8PathBuf) -> Result<()> {
+    let text = fs_err::tokio::read_to_string(&file).await?;
+    let pipeline = parse_pipeline(&file, &text)?;
+    let (client, _, _) = credentials.get_client(NO_ARGS).await?;
+    let client = client
+        .logged_in()
+        .ok_or_else(|| eyre!("You must be logged in to upload pipelines."))?;
+    let missing = missing_plugins(&client, &pipeline).await?;
+    if !missing.is_empty() {
+        bail!(
+            "Pipeline \"{}\" needs plugins which are not registered in {}: {}",
+            pipeline.name,
+            client.url(),
+            missing
+                .iter()
+                .map(|(name, version)| format!("{} v{}", name, version))
+                .join(", ")
+        )
+    }
+    let uploaded = client.create_pipeline(&pipeline).await?;
+    println!("{} {}", uploaded.object.url, uploaded.object.name.bold());
+    Ok(())
+}
+
+/// Parse and validate a pipeline file. The format is chosen by file extension.
+fn parse_pipeline(file: &Utf8Path, text: &str) -> Result<CanonPipeline> {
+    let pipeline = match file.extension() {
+        Some("yml" | "yaml") => {
+            let title_indexed: TitleIndexedPipeline =
+                serde_yaml::from_str(text).map_err(|e| eyre!("{}: {}", file, e))?;
+            CanonPipeline::try_from(title_indexed).map_err(|e| {
+                let line = e.title().and_then(|title| line_of_title(text, title));
+                match line {
+                    Some(line) => eyre!("{}:{}: {}", file, line, e),
+                    None => eyre!("{}: {}", file, e),
+                }
+            })?
+        }
+        Some("json") => {
+            let possibly_expanded: PossiblyExpandedTreePipeline =
+                serde_json::from_str(text).map_err(|e| eyre!("{}: {}", file, e))?;
+            CanonPipeline::from(possibly_expanded)
+        }
+        _ => bail!(
+            "Unknown file type of {}, expected a file name ending with .yml, .yaml, or .json",
+            file
+        ),
+    };
+    check_previous_indices(&pipeline).map_err(|e| eyre!("{}: {}", file, e))?;
+    Ok(pipeline)
+}
+
+/// Line number of the piping with the given title.
+fn line_of_title(text: &str, title: &str) -> Option<usize> {
+    text.lines()
+        .map(|line| line.trim_start().trim_start_matches("- ").trim())
+        .position(|line| {
+            line.strip_prefix("title:")
+                .map(|value| value.trim().trim_matches(['"', '\'']) == title)
+                .unwrap_or(false)
+        })
+        .map(|i| i + 1)
+}
+
+/// Check that every `previous_index` refers to a piping, and that there is exactly one root.
+fn check_previous_indices(pipeline: &CanonPipeline) -> Result<()> {
+    let len = pipeline.plugin_tree.len();
+    let mut roots = 0;
+    for (i, piping) in pipeline.plugin_tree.iter().enumerate() {
+        match piping.previous_index {
+            None => roots += 1,
+            Some(previous) if previous >= len || previous == i => bail!(
+                "previous_index {} of piping {} ({}) does not refer to another piping",
+                previous,
+                i,
+                piping.plugin_name
+            ),
+            Some(_) => (),
+        }
+    }
+    if roots != 1 {
+        bail!("Pipeline must have exactly one root, found {}", roots)
+    }
+    Ok(())
+}
+
+/// Plugins of _CUBE_.
+pub(crate) trait PluginRegistry {
+    async fn has_plugin(&self, name: &PluginName, version: &PluginVersion) -> Result<bool>;
+}
+
+impl PluginRegistry for ChrisClient {
+    async fn has_plugin(&self, name: &PluginName, version: &PluginVersion) -> Result<bool> {
+        let count = self
+            .plugin()
+            .name_exact(name.as_str())
+            .version(version.as_str())
+            .search()
+            .get_count()
+            .await?;
+        Ok(count > 0)
+    }
+}
+
+/// Plugins of `pipeline` which are not in `registry`.
+async fn missing_plugins<'a>(
+    registry: &impl PluginRegistry,
+    pipeline: &'a CanonPipeline,
+) -> Result<Vec<(&'a PluginName, &'a PluginVersion)>> {
+    let mut missing = Vec::new();
+    for (name, version) in pipeline.plugins() {
+        if !registry.has_plugin(name, version).await? {
+            missing.push((name, version));
+        }
+    }
+    Ok(missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const YAML: &str = r#"
+name: Example pipeline
+authors: Jennings Zhang
+category: MRI
+description: An example
+locked: false
+plugin_tree:
+  - title: copy
+    plugin: pl-dircopy v2.1.1
+    previous: null
+  - title: "simple"
+    plugin: pl-simpledsapp v2.1.0
+    previous: copy
+    plugin_parameter_defaults:
+      prefix: hello
+  - title: other
+    plugin: pl-simpledsapp v2.1.0
+    previous: nothing
+"#;
+
+    struct FakeRegistry;
+
+    impl PluginRegistry for FakeRegistry {
+        async fn has_plugin(&self, name: &PluginName, version: &PluginVersion) -> Result<bool> {
+            Ok(name.as_str() == "pl-dircopy" && version.as_str() == "2.1.1")
+        }
+    }
+
+    fn valid_yaml() -> String {
+        YAML.replace("previous: nothing", "previous: simple")
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_missing_plugins() {
+        let pipeline = parse_pipeline(Utf8Path::new("p.yml"), &valid_yaml()).unwrap();
+        assert_eq!(pipeline.plugin_tree.len(), 3);
+        let missing = missing_plugins(&FakeRegistry, &pipeline).await.unwrap();
+        let missing: Vec<_> = missing
+            .into_iter()
+            .map(|(n, v)| format!("{}@{}", n, v))
+            .collect();
+        assert_eq!(missing, vec!["pl-simpledsapp@2.1.0"])
+    }
+
+    #[rstest]
+    fn test_invalid_yaml_reports_line() {
+        let error = parse_pipeline(Utf8Path::new("p.yml"), YAML).unwrap_err();
+        assert!(error.to_string().starts_with("p.yml:16: "), "{error}");
+        assert!(error.to_string().contains("\"nothing\""), "{error}");
+    }
+
+    #[rstest]
+    fn test_json_round_trip() {
+        let pipeline = parse_pipeline(Utf8Path::new("p.yaml"), &valid_yaml()).unwrap();
+        let json = serde_json::to_string(&pipeline).unwrap();
+        let parsed = parse_pipeline(Utf8Path::new("p.json"), &json).unwrap();
+        assert_eq!(parsed, pipeline);
+    }
+
+    #[rstest]
+    #[case("p.txt", "Unknown file type")]
+    #[case("p.json", "p.json: ")]
+    fn test_parse_fails(#[case] file: &str, #[case] expected: &str) {
+        let error = parse_pipeline(Utf8Path::new(file), "{}").unwrap_err();
+        assert!(error.to_string().contains(expected), "{error}");
+    }
+
+    #[rstest]
+    #[case(vec![None, Some(0), Some(1)], true)]
+    #[case(vec![None, Some(5)], false)]
+    #[case(vec![None, Some(1)], false)]
+    #[case(vec![None, None], false)]
+    fn test_check_previous_indices(#[case] previous: Vec<Option<usize>>, #[case] ok: bool) {
+        let mut pipeline = parse_pipeline(Utf8Path::new("p.yml"), &valid_yaml()).unwrap();
+        let template = pipeline.plugin_tree[0].clone();
+        pipeline.plugin_tree = previous
+            .into_iter()
+            .map(
+                |previous_index| chris::pipeline::canon::ExpandedTreePiping {
+                    previous_index,
+                    ..template.clone()
+                },
+            )
+            .collect();
+        assert_eq!(check_previous_indices(&pipeline).is_ok(), ok);
+    }
+}
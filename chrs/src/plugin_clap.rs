@@ -5,24 +5,140 @@ use std::collections::HashMap;
 use clap::builder::NonEmptyStringValueParser;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use color_eyre::eyre;
-use futures::TryStreamExt;
+use color_eyre::eyre::{bail, WrapErr};
 
-use chris::types::{PluginParameterAction, PluginParameterType, PluginParameterValue};
-use chris::{Access, Plugin, PluginParameter};
+use chris::types::{
+    PluginInstanceId, PluginParameterAction, PluginParameterType, PluginParameterValue,
+};
+use chris::{ChrisClient, PluginParameter};
 
 use crate::arg::GivenDataNode;
+use crate::download::{is_pacs_path, is_uploads_path};
+use crate::files::ChrisPathHumanCoder;
 
 /// clap arg ID for plugin input
 pub const CHRS_INCOMING: &str = "chrs-incoming-cfb8a325-fbfc-4467-b7d1-4975d1a249cf";
 
-/// Use clap to serialize user-specified `args` for a `plugin`.
-pub async fn clap_serialize_params<A: Access>(
-    plugin: &Plugin<A>,
+/// Use clap to serialize user-specified `args` for a plugin with the given parameters.
+///
+/// `preset` are parameter values which were given some other way, e.g. by `--params-file`.
+/// They are not required in `args`, and are overridden by values in `args`. Every
+/// name in `preset` must be the name of a parameter in `parameter_info`.
+///
+/// Required parameters are not enforced here, so that `chrs run` can list every missing
+/// parameter at once, or not check them at all with `--no-validate`.
+///
+/// Values of `path` and `unextpath` parameters are comma-separated paths, which are
+/// resolved to _ChRIS_ file paths by `paths`.
+pub async fn clap_serialize_params(
+    selfexec: &str,
+    parameter_info: &[PluginParameter],
     args: &[String],
+    preset: &HashMap<String, PluginParameterValue>,
+    paths: &mut impl PathResolver,
 ) -> eyre::Result<(HashMap<String, PluginParameterValue>, Vec<GivenDataNode>)> {
-    let parameter_info: Vec<_> = plugin.parameters().stream().try_collect().await?;
-    let command = clap_params(&plugin.object.selfexec, &parameter_info);
-    parse_args_using(command, &parameter_info, args)
+    let command = clap_params(selfexec, parameter_info).mut_args(|arg| arg.required(false));
+    let (parsed, incoming) = parse_args_using(command, parameter_info, args)?;
+    let mut params = preset.clone();
+    params.extend(parsed);
+    resolve_paths(parameter_info, &mut params, paths).await?;
+    Ok((params, incoming))
+}
+
+/// Resolves user-given values of `path` and `unextpath` plugin parameters.
+pub(crate) trait PathResolver {
+    /// Get the _ChRIS_ file path of `given`.
+    async fn resolve(&mut self, given: &str) -> eyre::Result<String>;
+}
+
+/// Resolves paths given in the same syntax as the paths of [GivenDataNode], including paths
+/// where folders are renamed to feed names and plugin instance titles. Resolved paths must
+/// exist in _CUBE_.
+pub(crate) struct ChrisPathResolver<'a> {
+    client: &'a ChrisClient,
+    old: Option<PluginInstanceId>,
+    coder: ChrisPathHumanCoder<'a, ChrisClient>,
+}
+
+impl<'a> ChrisPathResolver<'a> {
+    pub fn new(client: &'a ChrisClient, old: Option<PluginInstanceId>) -> Self {
+        Self {
+            client,
+            old,
+            coder: ChrisPathHumanCoder::new(client),
+        }
+    }
+
+    /// Whether `fname` is a file or a folder of files. Uploaded files and PACS files
+    /// are searched for in their own APIs.
+    async fn exists(&self, fname: &str) -> eyre::Result<bool> {
+        let folder = format!("{fname}/");
+        let (file, under) = if is_pacs_path(fname) {
+            let pacsfiles = || self.client.pacsfiles();
+            (
+                pacsfiles().fname_exact(fname).search().basic(),
+                pacsfiles().fname(folder).search().basic(),
+            )
+        } else if is_uploads_path(fname) {
+            let userfiles = || self.client.userfiles();
+            (
+                userfiles().fname_exact(fname).search().basic(),
+                userfiles().fname(folder).search().basic(),
+            )
+        } else {
+            let files = || self.client.files();
+            (
+                files().fname_exact(fname).search().basic(),
+                files().fname(folder).search().basic(),
+            )
+        };
+        Ok(!file.is_empty().await? || !under.is_empty().await?)
+    }
+}
+
+impl PathResolver for ChrisPathResolver<'_> {
+    async fn resolve(&mut self, given: &str) -> eyre::Result<String> {
+        let fname = match GivenDataNode::from(given.to_string()) {
+            // e.g. "chris/My Feed/pl-dircopy_1/data", which is not a plugin instance title
+            GivenDataNode::Ambiguous(path) if path.contains('/') => {
+                self.coder.encode(&path).await?
+            }
+            node => node.into_path_rw(self.client, self.old).await?,
+        };
+        if !self.exists(&fname).await? {
+            bail!("path \"{given}\", translated as \"{fname}\", does not exist")
+        }
+        Ok(fname)
+    }
+}
+
+/// Replace the values of `path` and `unextpath` parameters of `params` with the
+/// paths resolved by `paths`.
+async fn resolve_paths(
+    parameter_info: &[PluginParameter],
+    params: &mut HashMap<String, PluginParameterValue>,
+    paths: &mut impl PathResolver,
+) -> eyre::Result<()> {
+    let path_params = parameter_info.iter().filter(|p| {
+        matches!(
+            p.parameter_type,
+            PluginParameterType::Path | PluginParameterType::Unextpath
+        )
+    });
+    for param in path_params {
+        if let Some(PluginParameterValue::Stringish(value)) = params.get_mut(&param.name) {
+            let mut resolved = Vec::new();
+            for given in value.split(',') {
+                let fname = paths
+                    .resolve(given)
+                    .await
+                    .wrap_err_with(|| format!("Invalid value for parameter \"{}\"", param.name))?;
+                resolved.push(fname);
+            }
+            *value = resolved.join(",");
+        }
+    }
+    Ok(())
 }
 
 pub fn clap_params(selfexec: &str, parameter_info: &[PluginParameter]) -> Command {
@@ -96,15 +212,12 @@ fn pluginparameter2claparg(param: &PluginParameter) -> Arg {
         PluginParameterAction::StoreFalse => ArgAction::SetFalse,
     };
 
-    let long_flag = get_long_flag_name(param.flag.as_str())
-        .get_or_insert(param.name.as_str())
-        .to_string();
     let arg = Arg::new(&param.name)
         .value_name(param.parameter_type.as_str())
         .value_parser(clap_parser_for(param.parameter_type))
         .required(!param.optional)
         .help(&param.help)
-        .long(long_flag)
+        .long(long_flag_of(param))
         .action(action);
 
     if let Some(short_flag) = get_short_flag_char(param.short_flag.as_str()) {
@@ -114,6 +227,13 @@ fn pluginparameter2claparg(param: &PluginParameter) -> Arg {
     }
 }
 
+/// Name of the long flag of a plugin parameter, without the leading `--`.
+pub(crate) fn long_flag_of(param: &PluginParameter) -> String {
+    get_long_flag_name(param.flag.as_str())
+        .unwrap_or(param.name.as_str())
+        .to_string()
+}
+
 fn clap_parser_for(t: PluginParameterType) -> clap::builder::ValueParser {
     match t {
         PluginParameterType::Boolean => clap::builder::ValueParser::bool(),
@@ -156,6 +276,7 @@ fn get_long_flag_name(long_flag: &str) -> Option<&str> {
 mod tests {
     use rstest::*;
 
+    use chris::testing::MockCube;
     use chris::types::PluginParameterId;
 
     use super::*;
@@ -212,6 +333,12 @@ mod tests {
             PluginParameterAction::Store,
             true,
         ),
+        (
+            "mask",
+            PluginParameterType::Path,
+            PluginParameterAction::Store,
+            true,
+        ),
     ];
 
     #[fixture]
@@ -235,6 +362,7 @@ mod tests {
                     help: format!("help message for \"{name}\""),
                     ui_exposed: true,
                     plugin: "https://example.com/api/v1/plugins/2/".into(),
+                    choices: None,
                 }
             })
             .collect()
@@ -245,6 +373,153 @@ mod tests {
         clap_params("unit test for plugin_clap", params)
     }
 
+    /// A [PathResolver] where every path except "missing" is an uploaded file.
+    struct UploadsResolver;
+
+    impl PathResolver for UploadsResolver {
+        async fn resolve(&mut self, given: &str) -> eyre::Result<String> {
+            if given == "missing" {
+                bail!("path \"{given}\" does not exist")
+            }
+            Ok(format!("chris/uploads/{given}"))
+        }
+    }
+
+    async fn serialize(
+        params: &[PluginParameter],
+        args: &[&str],
+        preset: &HashMap<String, PluginParameterValue>,
+    ) -> eyre::Result<(HashMap<String, PluginParameterValue>, Vec<GivenDataNode>)> {
+        let args: Vec<_> = args.iter().map(|s| s.to_string()).collect();
+        clap_serialize_params(
+            "unit test for plugin_clap",
+            params,
+            &args,
+            preset,
+            &mut UploadsResolver,
+        )
+        .await
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_serialize_params_with_preset(params: &[PluginParameter]) {
+        let preset = HashMap::from([
+            ("score".to_string(), PluginParameterValue::Float(1.5)),
+            (
+                "comment".to_string(),
+                PluginParameterValue::Stringish("from file".to_string()),
+            ),
+        ]);
+        let args = ["--comment", "from cli", "feed/5"];
+        let (actual, incoming) = serialize(params, &args, &preset).await.unwrap();
+        let expected = HashMap::from([
+            ("score".to_string(), PluginParameterValue::Float(1.5)),
+            (
+                "comment".to_string(),
+                PluginParameterValue::Stringish("from cli".to_string()),
+            ),
+        ]);
+        assert_eq!(actual, expected);
+        assert_eq!(incoming.len(), 1);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_serialize_params_does_not_require(params: &[PluginParameter]) {
+        let (actual, _) = serialize(params, &["--fun"], &HashMap::new())
+            .await
+            .unwrap();
+        assert!(!actual.contains_key("score"))
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_serialize_params_resolves_paths(params: &[PluginParameter]) {
+        let preset = HashMap::from([(
+            "mask".to_string(),
+            PluginParameterValue::Stringish("from_file.nii".to_string()),
+        )]);
+        let (actual, _) = serialize(params, &[], &preset).await.unwrap();
+        assert_eq!(
+            actual.get("mask"),
+            Some(&PluginParameterValue::Stringish(
+                "chris/uploads/from_file.nii".to_string()
+            ))
+        );
+        let args = ["--mask", "a.nii,b.nii", "--comment", "a.nii"];
+        let (actual, _) = serialize(params, &args, &preset).await.unwrap();
+        assert_eq!(
+            actual.get("mask"),
+            Some(&PluginParameterValue::Stringish(
+                "chris/uploads/a.nii,chris/uploads/b.nii".to_string()
+            ))
+        );
+        assert_eq!(
+            actual.get("comment"),
+            Some(&PluginParameterValue::Stringish("a.nii".to_string())),
+            "string parameters should not be resolved"
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_serialize_params_path_does_not_exist(params: &[PluginParameter]) {
+        let args = ["--mask", "a.nii,missing"];
+        let e = serialize(params, &args, &HashMap::new())
+            .await
+            .expect_err("path \"missing\" should not be resolved");
+        assert_eq!(e.to_string(), "Invalid value for parameter \"mask\"");
+        assert_eq!(
+            e.root_cause().to_string(),
+            "path \"missing\" does not exist"
+        );
+    }
+
+    /// A mock with an uploaded file, a PACS file, and a file of a feed.
+    async fn files_mock() -> MockCube {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(1, "pl-dircopy", "2.1.1");
+        let feed = mock.feed(1, "My Study");
+        let plinst = mock.plugin_instance(1, &plugin, &feed, None);
+        mock.add_plugin(plugin);
+        mock.add_feed_with_files(feed, [("pl-dircopy_1/data/brain.nii", "feed")]);
+        mock.add_plugin_instance(plinst);
+        mock.add_file("chris/uploads/masks/mask.nii", "upload");
+        mock.add_file(
+            "SERVICES/PACS/Orthanc/123-anon/MR-Brain/00001-SAG/1.dcm",
+            "dicom",
+        );
+        mock
+    }
+
+    #[rstest]
+    #[case("chris/uploads/masks/mask.nii")]
+    #[case("chris/uploads/masks")]
+    #[case("SERVICES/PACS/Orthanc/123-anon/MR-Brain/00001-SAG/1.dcm")]
+    #[case("SERVICES/PACS/Orthanc/123-anon")]
+    #[case("chris/feed_1/pl-dircopy_1/data/brain.nii")]
+    #[tokio::test]
+    async fn test_chris_path_resolver(#[case] given: &str) {
+        let mock = files_mock().await;
+        let client = mock.client("chris").await;
+        let mut resolver = ChrisPathResolver::new(&client, None);
+        assert_eq!(resolver.resolve(given).await.unwrap(), given);
+    }
+
+    #[rstest]
+    #[case("chris/uploads/masks/brain.nii")]
+    #[case("chris/uploads/mask")]
+    #[case("SERVICES/PACS/Orthanc/456-anon")]
+    #[tokio::test]
+    async fn test_chris_path_resolver_does_not_exist(#[case] given: &str) {
+        let mock = files_mock().await;
+        let client = mock.client("chris").await;
+        let mut resolver = ChrisPathResolver::new(&client, None);
+        let e = resolver.resolve(given).await.unwrap_err();
+        assert!(e.to_string().ends_with("does not exist"), "{e}");
+    }
+
     #[rstest]
     fn test_parse_args_not_optional_param(command: Command, params: &[PluginParameter]) {
         let e = parse_args_using(command, params, &["--fun".to_string()])
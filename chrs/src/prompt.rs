@@ -0,0 +1,24 @@
+//! Asking before doing something which cannot be undone.
+
+use color_eyre::eyre::{eyre, Result};
+use dialoguer::console::Term;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Confirm;
+
+/// Ask whether to delete `count` items of the kind `noun`, e.g. "feed".
+/// `detail` is shown after the count, e.g. the total size of files.
+pub(crate) fn confirm_delete(count: usize, noun: &str, detail: Option<&str>) -> Result<bool> {
+    let detail = detail.map(|d| format!(" ({d})")).unwrap_or_default();
+    let prompt = format!(
+        "Delete {} {}{}{}? This cannot be undone",
+        count,
+        noun,
+        if count == 1 { "" } else { "s" },
+        detail
+    );
+    Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(false)
+        .interact_on(&Term::stderr())
+        .map_err(|e| eyre!("{}. Use --force to delete without confirmation.", e))
+}
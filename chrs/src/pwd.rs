@@ -0,0 +1,53 @@
+use color_eyre::eyre::{eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+
+use chris::BaseChrisClient;
+
+use crate::arg::output_path_of;
+use crate::credentials::{Credentials, NO_ARGS};
+use crate::files::MaybeChrisPathHumanCoder;
+
+/// `chrs pwd` command: print the path of the current plugin instance.
+pub async fn pwd(credentials: Credentials, titles: bool) -> Result<()> {
+    let (client, old, _) = credentials.get_client(NO_ARGS).await?;
+    let id = old.ok_or_else(|| {
+        eyre!(
+            "There is no current plugin instance. Set one using `{}`",
+            "chrs cd".bold()
+        )
+    })?;
+    let plinst = client.get_plugin_instance(id).await?;
+    let path = working_dir_of(output_path_of(&plinst.object)?).to_string();
+    let ro_client = client.into_ro();
+    let path = MaybeChrisPathHumanCoder::new(&ro_client, titles)
+        .decode(path)
+        .await;
+    println!("{}", path);
+    Ok(())
+}
+
+/// The output path of a plugin instance without its trailing `/data`.
+fn working_dir_of(output_path: &str) -> &str {
+    let output_path = output_path.trim_end_matches('/');
+    output_path.strip_suffix("/data").unwrap_or(output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(
+        "rudolphpienaar/feed_452/pl-dircopy_3975/pl-spleendatads_4034/data",
+        "rudolphpienaar/feed_452/pl-dircopy_3975/pl-spleendatads_4034"
+    )]
+    #[case(
+        "rudolphpienaar/feed_452/pl-dircopy_3975/data/",
+        "rudolphpienaar/feed_452/pl-dircopy_3975"
+    )]
+    #[case("chris/feed_1/pl-dircopy_1", "chris/feed_1/pl-dircopy_1")]
+    fn test_working_dir_of(#[case] output_path: &str, #[case] expected: &str) {
+        assert_eq!(working_dir_of(output_path), expected)
+    }
+}
@@ -0,0 +1,216 @@
+//! `chrs rerun`: re-run errored plugin instances.
+
+mod plan;
+
+use std::collections::HashMap;
+
+use clap::Parser;
+use color_eyre::eyre::{eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+use futures::TryStreamExt;
+use tokio::try_join;
+
+use chris::errors::CubeError;
+use chris::types::{CpuLimit, FeedId, MemoryLimit, PluginInstanceId, PluginParameterValue, Status};
+use chris::{BaseChrisClient, ChrisClient, PluginInstanceResponse};
+
+use crate::arg::{FeedOrPluginInstance, GivenDataNode};
+use crate::credentials::Credentials;
+use crate::unicode;
+
+use plan::{execute_rerun, plan_rerun, replace_plugininstances, Node, RerunPlan, Rerunner};
+
+#[derive(Parser)]
+pub struct RerunArgs {
+    /// Also re-run everything after the re-run plugin instances
+    #[clap(long)]
+    cascade: bool,
+
+    /// Cancel the original plugin instances after they are re-run, unless they are
+    /// already finished
+    #[clap(long)]
+    cancel: bool,
+
+    /// Print what would be re-run without running anything
+    #[clap(short, long)]
+    dry_run: bool,
+
+    /// Feed, to re-run its errored plugin instances, or a plugin instance to re-run
+    feed_or_plugin_instance: GivenDataNode,
+}
+
+pub async fn rerun(credentials: Credentials, args: RerunArgs) -> Result<()> {
+    let (client, old, _) = credentials
+        .get_client([args.feed_or_plugin_instance.as_arg_str()])
+        .await?;
+    let chris = client.logged_in_ref().ok_or_else(|| {
+        eyre!(
+            "This command is only available for authenticated users. Try running `{}` with a username first.",
+            "chrs login".bold()
+        )
+    })?;
+    let targets = match args.feed_or_plugin_instance.into_or(&client, old).await? {
+        FeedOrPluginInstance::Feed(feed) => {
+            let errored = chris.errored_of_feed(feed.object.id).await?;
+            if errored.is_empty() {
+                eprintln!("feed/{} has no errored plugin instances.", feed.object.id.0);
+                return Ok(());
+            }
+            errored
+        }
+        FeedOrPluginInstance::PluginInstance(plinst) => vec![Node::from(&plinst.object)],
+    };
+    let plan = plan_rerun(chris, targets, args.cascade).await?;
+    if args.dry_run {
+        print_plan(&plan, args.cancel);
+    } else {
+        execute_rerun(chris, &plan, args.cancel, |step, created, cancelled| {
+            let note = if cancelled { " (cancelled)" } else { "" };
+            println!(
+                "plugininstance/{}{} {} plugininstance/{}",
+                step.original.0,
+                note.dimmed(),
+                unicode::RIGHTWARDS_ARROW,
+                created.0
+            )
+        })
+        .await?;
+    }
+    if !plan.not_rewired.is_empty() {
+        let after = plan
+            .not_rewired
+            .iter()
+            .map(|id| format!("plugininstance/{}", id.0))
+            .collect::<Vec<_>>()
+            .join(" ");
+        eprintln!(
+            "{}",
+            format!(
+                "Note: {} still come after the original plugin instances. Use --cascade to re-run them too.",
+                after
+            )
+            .dimmed()
+        );
+    }
+    Ok(())
+}
+
+fn print_plan(plan: &RerunPlan, cancel: bool) {
+    let rerun: Vec<_> = plan.steps.iter().map(|s| s.original).collect();
+    for step in &plan.steps {
+        let after = match step.previous {
+            Some(p) if rerun.contains(&p) => format!("after the re-run of plugininstance/{}", p.0),
+            Some(p) => format!("after plugininstance/{}", p.0),
+            None => "as a new feed".to_string(),
+        };
+        let and_cancel = if cancel { " and cancel" } else { "" };
+        println!(
+            "would re-run{} plugininstance/{} {}",
+            and_cancel,
+            step.original.0,
+            after.dimmed()
+        );
+    }
+}
+
+impl From<&PluginInstanceResponse> for Node {
+    fn from(value: &PluginInstanceResponse) -> Self {
+        Self {
+            id: value.id,
+            previous: value.previous_id,
+        }
+    }
+}
+
+impl Rerunner for ChrisClient {
+    async fn errored_of_feed(&self, feed: FeedId) -> Result<Vec<Node>, CubeError> {
+        let query = self
+            .plugin_instances()
+            .feed_id(feed)
+            .status(Status::FinishedWithError);
+        let search = query.search();
+        search
+            .stream()
+            .map_ok(|p| Node::from(&p))
+            .try_collect()
+            .await
+    }
+
+    async fn children_of(&self, id: PluginInstanceId) -> Result<Vec<Node>, CubeError> {
+        let query = self.plugin_instances().previous_id(id);
+        let search = query.search();
+        search
+            .stream()
+            .map_ok(|p| Node::from(&p))
+            .try_collect()
+            .await
+    }
+
+    async fn recreate(
+        &self,
+        original: PluginInstanceId,
+        previous: Option<PluginInstanceId>,
+        replaced: &HashMap<PluginInstanceId, PluginInstanceId>,
+    ) -> Result<PluginInstanceId, CubeError> {
+        let original = self.get_plugin_instance(original).await?;
+        let parameters = original.parameters();
+        let (plugin, parameters): (_, Vec<_>) =
+            try_join!(original.plugin().get(), parameters.stream().try_collect())?;
+        let mut body: HashMap<String, PluginParameterValue> = parameters
+            .into_iter()
+            .map(|p| {
+                let value = match (p.param_name.as_str(), p.value) {
+                    ("plugininstances", PluginParameterValue::Stringish(ids)) => {
+                        PluginParameterValue::Stringish(replace_plugininstances(&ids, replaced))
+                    }
+                    (_, value) => value,
+                };
+                (p.param_name, value)
+            })
+            .collect();
+        body.extend(resources_of(&original.object, previous));
+        plugin.create_instance(&body).await.map(|p| p.object.id)
+    }
+
+    async fn cancel(&self, id: PluginInstanceId) -> Result<bool, CubeError> {
+        let plinst = self.get_plugin_instance(id).await?;
+        if plinst.object.status.is_terminal() {
+            return Ok(false);
+        }
+        plinst.cancel().await.map(|_| true)
+    }
+}
+
+/// Title, previous, and compute resource options to create a plugin instance like `original`.
+fn resources_of(
+    original: &PluginInstanceResponse,
+    previous: Option<PluginInstanceId>,
+) -> impl Iterator<Item = (String, PluginParameterValue)> {
+    let values = [
+        Some((
+            "title",
+            PluginParameterValue::Stringish(original.title.clone()),
+        )),
+        Some(("cpu_limit", CpuLimit(original.cpu_limit).into())),
+        Some(("memory_limit", MemoryLimit(original.memory_limit).into())),
+        Some((
+            "gpu_limit",
+            PluginParameterValue::Integer(original.gpu_limit as i64),
+        )),
+        Some((
+            "number_of_workers",
+            PluginParameterValue::Integer(original.number_of_workers as i64),
+        )),
+        original.compute_resource_name.as_ref().map(|name| {
+            (
+                "compute_resource_name",
+                PluginParameterValue::Stringish(name.to_string()),
+            )
+        }),
+        previous.map(|p| ("previous_id", PluginParameterValue::Integer(p.0 as i64))),
+    ];
+    values
+        .into_iter()
+        .flatten()
+        .map(|(k, v)| (k.to_string(), v))
+}
@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chris::errors::CubeError;
+use chris::types::{FeedId, PluginInstanceId};
+
+/// A plugin instance and its previous.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Node {
+    pub id: PluginInstanceId,
+    pub previous: Option<PluginInstanceId>,
+}
+
+/// What needs to be done in _CUBE_ to re-run plugin instances.
+pub(crate) trait Rerunner {
+    /// Get the errored plugin instances of a feed.
+    async fn errored_of_feed(&self, feed: FeedId) -> Result<Vec<Node>, CubeError>;
+
+    /// Get the plugin instances which come after a plugin instance.
+    async fn children_of(&self, id: PluginInstanceId) -> Result<Vec<Node>, CubeError>;
+
+    /// Create a plugin instance of the same plugin, with the same parameters and resources,
+    /// as `original`. In the `plugininstances` parameter of ts-type plugins, the IDs which
+    /// are keys of `replaced` are substituted by their values.
+    async fn recreate(
+        &self,
+        original: PluginInstanceId,
+        previous: Option<PluginInstanceId>,
+        replaced: &HashMap<PluginInstanceId, PluginInstanceId>,
+    ) -> Result<PluginInstanceId, CubeError>;
+
+    /// Cancel a plugin instance unless it is already finished. Returns whether it
+    /// was cancelled.
+    async fn cancel(&self, id: PluginInstanceId) -> Result<bool, CubeError>;
+}
+
+/// A plugin instance to re-run.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Step {
+    pub original: PluginInstanceId,
+    /// Previous of the original. If it is also re-run, the new plugin instance
+    /// is created after the re-run of the previous instead.
+    pub previous: Option<PluginInstanceId>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct RerunPlan {
+    /// Plugin instances to re-run, in topological order.
+    pub steps: Vec<Step>,
+    /// Plugin instances after a re-run plugin instance which are not re-run themselves,
+    /// so they stay attached to the original.
+    pub not_rewired: Vec<PluginInstanceId>,
+}
+
+/// Plan to re-run `targets`. If `cascade`, all of their descendants are re-run too.
+pub(crate) async fn plan_rerun(
+    rerunner: &impl Rerunner,
+    targets: Vec<Node>,
+    cascade: bool,
+) -> Result<RerunPlan, CubeError> {
+    // plugin instance IDs are increasing, so sorting by ID is a topological sort.
+    let mut nodes: BTreeMap<u32, Node> = targets.into_iter().map(|n| (n.id.0, n)).collect();
+    let mut queue: VecDeque<_> = nodes.values().map(|n| n.id).collect();
+    let mut not_rewired = Vec::new();
+    while let Some(id) = queue.pop_front() {
+        for child in rerunner.children_of(id).await? {
+            if nodes.contains_key(&child.id.0) || not_rewired.contains(&child.id) {
+                continue;
+            }
+            if cascade {
+                nodes.insert(child.id.0, child);
+                queue.push_back(child.id);
+            } else {
+                not_rewired.push(child.id);
+            }
+        }
+    }
+    not_rewired.retain(|id| !nodes.contains_key(&id.0));
+    let steps = nodes
+        .into_values()
+        .map(|n| Step {
+            original: n.id,
+            previous: n.previous,
+        })
+        .collect();
+    Ok(RerunPlan { steps, not_rewired })
+}
+
+/// Create the plugin instances of the plan, one at a time. If `cancel_originals`, each
+/// original plugin instance is cancelled after it is re-run. `on_created` is called with
+/// each step, the ID of the plugin instance created for it, and whether the original
+/// was cancelled.
+///
+/// Returns the mapping of original plugin instances to their re-runs.
+pub(crate) async fn execute_rerun(
+    rerunner: &impl Rerunner,
+    plan: &RerunPlan,
+    cancel_originals: bool,
+    mut on_created: impl FnMut(&Step, PluginInstanceId, bool),
+) -> Result<HashMap<PluginInstanceId, PluginInstanceId>, CubeError> {
+    let mut replaced = HashMap::with_capacity(plan.steps.len());
+    for step in &plan.steps {
+        let previous = step
+            .previous
+            .map(|p| replaced.get(&p).copied().unwrap_or(p));
+        let created = rerunner
+            .recreate(step.original, previous, &replaced)
+            .await?;
+        let cancelled = cancel_originals && rerunner.cancel(step.original).await?;
+        on_created(step, created, cancelled);
+        replaced.insert(step.original, created);
+    }
+    Ok(replaced)
+}
+
+/// Substitute plugin instance IDs in the value of the `plugininstances` parameter,
+/// which is a comma-separated list of IDs.
+pub(crate) fn replace_plugininstances(
+    value: &str,
+    replaced: &HashMap<PluginInstanceId, PluginInstanceId>,
+) -> String {
+    value
+        .split(',')
+        .map(|s| {
+            s.trim()
+                .parse()
+                .ok()
+                .and_then(|id| replaced.get(&PluginInstanceId(id)))
+                .map(|id| id.0.to_string())
+                .unwrap_or_else(|| s.trim().to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::cell::RefCell;
+
+    /// A feed like this, where the starred plugin instances are errored:
+    ///
+    /// ```text
+    ///          1
+    ///        /   \
+    ///       2*    3
+    ///      / \     \
+    ///     4   5     6*
+    ///     |
+    ///     7
+    /// ```
+    struct FakeFeed {
+        nodes: Vec<(u32, Option<u32>, bool)>,
+        created: RefCell<Vec<(PluginInstanceId, Option<PluginInstanceId>)>>,
+        cancelled: RefCell<Vec<PluginInstanceId>>,
+        requests: RefCell<usize>,
+    }
+
+    impl FakeFeed {
+        fn node(&self, id: u32) -> Node {
+            let (id, previous, _) = *self.nodes.iter().find(|(n, _, _)| *n == id).unwrap();
+            Node {
+                id: PluginInstanceId(id),
+                previous: previous.map(PluginInstanceId),
+            }
+        }
+    }
+
+    impl Rerunner for FakeFeed {
+        async fn errored_of_feed(&self, _feed: FeedId) -> Result<Vec<Node>, CubeError> {
+            *self.requests.borrow_mut() += 1;
+            Ok(self
+                .nodes
+                .iter()
+                .filter(|(_, _, errored)| *errored)
+                .map(|(id, _, _)| self.node(*id))
+                .collect())
+        }
+
+        async fn children_of(&self, id: PluginInstanceId) -> Result<Vec<Node>, CubeError> {
+            *self.requests.borrow_mut() += 1;
+            Ok(self
+                .nodes
+                .iter()
+                .filter(|(_, previous, _)| *previous == Some(id.0))
+                .map(|(id, _, _)| self.node(*id))
+                .collect())
+        }
+
+        async fn recreate(
+            &self,
+            original: PluginInstanceId,
+            previous: Option<PluginInstanceId>,
+            _replaced: &HashMap<PluginInstanceId, PluginInstanceId>,
+        ) -> Result<PluginInstanceId, CubeError> {
+            *self.requests.borrow_mut() += 1;
+            let mut created = self.created.borrow_mut();
+            created.push((original, previous));
+            Ok(PluginInstanceId(100 + created.len() as u32))
+        }
+
+        async fn cancel(&self, id: PluginInstanceId) -> Result<bool, CubeError> {
+            *self.requests.borrow_mut() += 1;
+            // errored plugin instances are finished, the others are still running
+            let (_, _, errored) = *self.nodes.iter().find(|(n, _, _)| *n == id.0).unwrap();
+            if !errored {
+                self.cancelled.borrow_mut().push(id);
+            }
+            Ok(!errored)
+        }
+    }
+
+    #[fixture]
+    fn feed() -> FakeFeed {
+        FakeFeed {
+            nodes: vec![
+                (1, None, false),
+                (2, Some(1), true),
+                (3, Some(1), false),
+                (4, Some(2), false),
+                (5, Some(2), false),
+                (6, Some(3), true),
+                (7, Some(4), false),
+            ],
+            created: Default::default(),
+            cancelled: Default::default(),
+            requests: Default::default(),
+        }
+    }
+
+    fn ids(ids: &[u32]) -> Vec<PluginInstanceId> {
+        ids.iter().copied().map(PluginInstanceId).collect()
+    }
+
+    fn created(feed: &FakeFeed) -> Vec<(u32, Option<u32>)> {
+        feed.created
+            .borrow()
+            .iter()
+            .map(|(o, p)| (o.0, p.map(|p| p.0)))
+            .collect()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_rerun_single(feed: FakeFeed) {
+        let plan = plan_rerun(&feed, vec![feed.node(6)], false).await.unwrap();
+        assert!(plan.not_rewired.is_empty());
+        let replaced = execute_rerun(&feed, &plan, false, |_, _, _| ())
+            .await
+            .unwrap();
+        assert_eq!(created(&feed), vec![(6, Some(3))]);
+        assert_eq!(replaced[&PluginInstanceId(6)], PluginInstanceId(101));
+        // one request to check for children, one to create
+        assert_eq!(*feed.requests.borrow(), 2);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_rerun_multiple(feed: FakeFeed) {
+        let errored = feed.errored_of_feed(FeedId(1)).await.unwrap();
+        let plan = plan_rerun(&feed, errored, false).await.unwrap();
+        assert_eq!(plan.not_rewired, ids(&[4, 5]));
+        let mut mapping = Vec::new();
+        execute_rerun(&feed, &plan, false, |step, new, _| {
+            mapping.push((step.original.0, new.0))
+        })
+        .await
+        .unwrap();
+        assert_eq!(created(&feed), vec![(2, Some(1)), (6, Some(3))]);
+        assert_eq!(mapping, vec![(2, 101), (6, 102)]);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_rerun_cascade(feed: FakeFeed) {
+        let plan = plan_rerun(&feed, vec![feed.node(2)], true).await.unwrap();
+        assert!(plan.not_rewired.is_empty());
+        let originals: Vec<_> = plan.steps.iter().map(|s| s.original).collect();
+        assert_eq!(originals, ids(&[2, 4, 5, 7]));
+        execute_rerun(&feed, &plan, false, |_, _, _| ())
+            .await
+            .unwrap();
+        assert_eq!(
+            created(&feed),
+            vec![(2, Some(1)), (4, Some(101)), (5, Some(101)), (7, Some(102))]
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_errored_after_errored_is_rewired(mut feed: FakeFeed) {
+        feed.nodes[3].2 = true; // 4 is errored too
+        let errored = feed.errored_of_feed(FeedId(1)).await.unwrap();
+        let plan = plan_rerun(&feed, errored, false).await.unwrap();
+        assert_eq!(plan.not_rewired, ids(&[5, 7]));
+        execute_rerun(&feed, &plan, false, |_, _, _| ())
+            .await
+            .unwrap();
+        assert_eq!(
+            created(&feed),
+            vec![(2, Some(1)), (4, Some(101)), (6, Some(3))]
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_rerun_cancel_originals(feed: FakeFeed) {
+        let plan = plan_rerun(&feed, vec![feed.node(2)], true).await.unwrap();
+        let mut cancelled = Vec::new();
+        execute_rerun(&feed, &plan, true, |step, _, was_cancelled| {
+            if was_cancelled {
+                cancelled.push(step.original)
+            }
+        })
+        .await
+        .unwrap();
+        // the errored plugin instance is already finished
+        assert_eq!(cancelled, ids(&[4, 5, 7]));
+        assert_eq!(*feed.cancelled.borrow(), ids(&[4, 5, 7]));
+    }
+
+    #[rstest]
+    #[case("2,3", "102,3")]
+    #[case("3", "3")]
+    #[case("2, 9", "102,9")]
+    #[case("", "")]
+    fn test_replace_plugininstances(#[case] value: &str, #[case] expected: &str) {
+        let replaced = HashMap::from([(PluginInstanceId(2), PluginInstanceId(102))]);
+        assert_eq!(replace_plugininstances(value, &replaced), expected)
+    }
+}
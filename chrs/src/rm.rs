@@ -0,0 +1,179 @@
+//! `chrs rm`: delete uploaded files.
+
+use std::collections::BTreeMap;
+
+use clap::Parser;
+use color_eyre::eyre::{bail, eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+use futures::{StreamExt, TryStreamExt};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+
+use chris::types::Username;
+use chris::{ChrisClient, Downloadable, RwAccess, UserFile};
+
+use crate::credentials::{Credentials, NO_ARGS};
+use crate::prompt::confirm_delete;
+use crate::sanitize::sanitize_for_terminal;
+
+#[derive(Parser)]
+pub struct RmArgs {
+    /// Do not ask for confirmation
+    #[clap(short, long)]
+    force: bool,
+
+    /// Only print which files would be deleted
+    #[clap(short, long)]
+    dry_run: bool,
+
+    /// Maximum number of concurrent deletions
+    #[clap(short = 'j', long, default_value_t = 4)]
+    threads: usize,
+
+    /// Paths of uploaded files, e.g. `alice/uploads/brain.nii`.
+    /// Folders, or paths ending with `/`, delete everything under them.
+    #[clap(required = true)]
+    paths: Vec<String>,
+}
+
+/// `chrs rm` command
+pub async fn rm(credentials: Credentials, args: RmArgs) -> Result<()> {
+    let (client, _, _) = credentials.get_client(NO_ARGS).await?;
+    let client = client
+        .logged_in()
+        .ok_or_else(|| eyre!("You must be logged in to delete files."))?;
+    let mut files = BTreeMap::new();
+    for path in &args.paths {
+        let target = Target::parse(path, client.username())?;
+        let found = find_files(&client, &target).await?;
+        if found.is_empty() {
+            bail!("No uploaded files found at {}", path)
+        }
+        files.extend(found.into_iter().map(|f| (f.object.id, f)));
+    }
+    let files: Vec<_> = files.into_values().collect();
+    if args.dry_run {
+        for file in &files {
+            println!("{}", sanitize_for_terminal(file.object.fname().as_str()));
+        }
+        return Ok(());
+    }
+    let size = files.iter().map(|f| f.object.fsize()).sum();
+    if !args.force && !confirm_delete(files.len(), "file", Some(&HumanBytes(size).to_string()))? {
+        bail!("Cancelled, no files were deleted.")
+    }
+    delete_all(files, args.threads).await
+}
+
+/// What to delete, given as a path.
+#[derive(Debug, PartialEq)]
+enum Target<'a> {
+    /// A file, or if there is no such file, everything under a folder.
+    FileOrFolder(&'a str),
+    /// Everything under a folder, given as a path ending with `/`.
+    Folder(&'a str),
+}
+
+impl<'a> Target<'a> {
+    /// Parse a path, which must be under `<username>/uploads/`.
+    fn parse(path: &'a str, username: &Username) -> Result<Self> {
+        let path = path.trim_start_matches('/');
+        let uploads = format!("{}/uploads", username.as_str());
+        let is_under_uploads = path
+            .strip_prefix(&uploads)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+        if !is_under_uploads {
+            bail!(
+                "Cannot delete {}: only files under {}/ can be deleted",
+                path,
+                uploads
+            )
+        }
+        if path.ends_with('/') || path == uploads {
+            Ok(Self::Folder(path.trim_end_matches('/')))
+        } else {
+            Ok(Self::FileOrFolder(path))
+        }
+    }
+}
+
+async fn find_files(client: &ChrisClient, target: &Target<'_>) -> Result<Vec<UserFile<RwAccess>>> {
+    let folder = match target {
+        Target::Folder(folder) => *folder,
+        Target::FileOrFolder(path) => {
+            let query = client.userfiles().fname_exact(*path);
+            if let Some(file) = query.search().get_first().await? {
+                return Ok(vec![file]);
+            }
+            path
+        }
+    };
+    let query = client.userfiles().fname(format!("{}/", folder));
+    let files = query.search().stream_connected().try_collect().await?;
+    Ok(files)
+}
+
+/// Delete files concurrently, continuing past failures.
+async fn delete_all(files: Vec<UserFile<RwAccess>>, threads: usize) -> Result<()> {
+    let total = files.len();
+    let pb = ProgressBar::new(total as u64).with_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {wide_bar} {human_pos}/{human_len} Files deleted")
+            .unwrap(),
+    );
+    let failures: Vec<_> = futures::stream::iter(files)
+        .map(|file| async move {
+            let fname = file.object.fname().to_string();
+            file.delete().await.err().map(|e| (fname, e))
+        })
+        .buffer_unordered(threads.max(1))
+        .inspect(|_| pb.inc(1))
+        .filter_map(|failure| async move { failure })
+        .collect()
+        .await;
+    pb.finish_and_clear();
+    for (fname, e) in &failures {
+        eprintln!(
+            "{}: could not delete {}: {}",
+            "error".red(),
+            sanitize_for_terminal(fname),
+            e
+        );
+    }
+    if !failures.is_empty() {
+        bail!("{} of {} files were not deleted", failures.len(), total)
+    }
+    eprintln!(
+        "Deleted {} file{}",
+        total,
+        if total == 1 { "" } else { "s" }
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("alice/uploads/a.txt", Target::FileOrFolder("alice/uploads/a.txt"))]
+    #[case("/alice/uploads/data", Target::FileOrFolder("alice/uploads/data"))]
+    #[case("alice/uploads/data/", Target::Folder("alice/uploads/data"))]
+    #[case("alice/uploads/", Target::Folder("alice/uploads"))]
+    #[case("alice/uploads", Target::Folder("alice/uploads"))]
+    fn test_parse_target(#[case] path: &str, #[case] expected: Target) {
+        let username = Username::from("alice");
+        assert_eq!(Target::parse(path, &username).unwrap(), expected)
+    }
+
+    #[rstest]
+    #[case("alice/feed_1/pl-dircopy_1/data/a.txt")]
+    #[case("bob/uploads/a.txt")]
+    #[case("alice/uploadsfoo/a.txt")]
+    #[case("SERVICES/PACS/ORTHANC/1.dcm")]
+    #[case("alice")]
+    fn test_parse_target_outside_uploads(#[case] path: &str) {
+        let username = Username::from("alice");
+        assert!(Target::parse(path, &username).is_err())
+    }
+}
@@ -1,9 +1,11 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
+use std::time::Duration;
 
+use camino::Utf8PathBuf;
 use clap::Parser;
-use color_eyre::eyre::{eyre, OptionExt, WrapErr};
+use color_eyre::eyre::{eyre, OptionExt};
 use color_eyre::owo_colors::OwoColorize;
 use color_eyre::{eyre, eyre::bail};
 use futures::{StreamExt, TryStreamExt};
@@ -11,16 +13,29 @@ use itertools::Itertools;
 use tokio::try_join;
 
 use chris::errors::CubeError;
-use chris::types::{ComputeResourceName, PluginInstanceId, PluginParameterValue};
+use chris::types::{
+    ComputeResourceName, CpuLimit, MemoryLimit, PluginInstanceId, PluginParameterValue,
+    SimplifiedStatus,
+};
 use chris::{
-    BaseChrisClient, ChrisClient, EitherClient, PipelineRw, PluginInstanceResponse,
-    PluginInstanceRw, PluginRw,
+    BaseChrisClient, ChrisClient, EitherClient, PipelineRw, PluginInstanceRw, PluginRw,
+    WorkflowBuilder,
 };
 
 use crate::arg::{GivenDataNode, GivenRunnable, Runnable};
 use crate::credentials::Credentials;
 use crate::login::UiUrl;
-use crate::plugin_clap::clap_serialize_params;
+use crate::logs::eprint_logs;
+use crate::merge::topologicalcopy;
+use crate::plugin_clap::{clap_serialize_params, ChrisPathResolver};
+use crate::status::wait_for_plugin_instance;
+use params_file::load_params_file;
+use plan::{Resources, RunPlan};
+use validate::{validate_compute_resource, validate_params};
+
+mod params_file;
+mod plan;
+mod validate;
 
 #[derive(Parser)]
 pub struct RunArgs {
@@ -28,15 +43,13 @@ pub struct RunArgs {
     #[clap(short = 'J', long, value_name = "N")]
     cpu: Option<u32>,
 
-    /// CPU resource request.
-    /// Format is xm where x is an integer in millicores.
+    /// CPU resource request, in millicores, e.g. `1500m`, or cores, e.g. `1.5`
     #[clap(long, conflicts_with = "cpu")]
-    cpu_limit: Option<String>,
+    cpu_limit: Option<CpuLimit>,
 
-    /// Memory resource request.
-    /// Format is xMi or xGi where x is an integer.
+    /// Memory resource request, e.g. `2Gi`, `2GB`, or `1500Mi`
     #[clap(short, long)]
-    memory_limit: Option<String>,
+    memory_limit: Option<MemoryLimit>,
 
     /// GPU resource request.
     /// Number of GPUs to use for plugin instance.
@@ -64,14 +77,52 @@ pub struct RunArgs {
     #[clap(short, long)]
     dry_run: bool,
 
-    /// Plugin or pipeline to run
-    #[clap(required = true)]
-    plugin_or_pipeline: GivenRunnable,
+    /// Do not check that required plugin parameters are given before running,
+    /// in case the plugin's description of its parameters is wrong
+    #[clap(long)]
+    no_validate: bool,
+
+    /// Write the plan of this run to a JSON file
+    #[clap(long, value_name = "FILE")]
+    save_plan: Option<Utf8PathBuf>,
+
+    /// Run the plan from a JSON file created by --save-plan
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["plugin_or_pipeline", "parameters"])]
+    plan: Option<Utf8PathBuf>,
+
+    /// Run the nearest available version if the plugin version of --plan is not found
+    #[clap(long, requires = "plan")]
+    allow_version_drift: bool,
+
+    /// Read plugin parameters from a JSON or YAML file of parameter names to values.
+    /// Parameters given on the command line take precedence
+    #[clap(long, value_name = "FILE", conflicts_with = "plan")]
+    params_file: Option<Utf8PathBuf>,
+
+    /// Plugin or pipeline to run. A plugin can be given as NAME@VERSION, where VERSION
+    /// is an exact version, `latest` (the default), or a range such as `^1.2` or `~1.2.0`
+    #[clap(required_unless_present = "plan")]
+    plugin_or_pipeline: Option<GivenRunnable>,
 
     /// Maximum number of concurrent HTTP requests
     #[clap(short = 'j', long, default_value_t = 4)]
     threads: usize,
 
+    /// Wait for the plugin instance (or the last plugin instance created by a pipeline)
+    /// to finish. If it fails, its logs are printed and chrs exits with an error
+    #[clap(long, conflicts_with = "dry_run")]
+    follow: bool,
+
+    /// Stop waiting for --follow after a duration, e.g. `90s`, `30m`, or `2h`,
+    /// and exit with code 124. The plugin instance keeps running
+    #[clap(long, requires = "follow", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Set a parameter of a piping when running a pipeline, where TITLE is the
+    /// title of the piping. Can be given multiple times
+    #[clap(long = "set", value_name = "TITLE.PARAM=VALUE", value_parser = parse_piping_param)]
+    set: Vec<PipingParam>,
+
     /// Plugin parameters and/or plugin/pipeline inputs
     parameters: Vec<String>,
 }
@@ -79,7 +130,12 @@ pub struct RunArgs {
 pub async fn run_command(credentials: Credentials, args: RunArgs) -> eyre::Result<()> {
     let (client, old, ui) = credentials
         .clone()
-        .get_client([args.plugin_or_pipeline.as_arg_str()])
+        .get_client(
+            args.plugin_or_pipeline
+                .as_ref()
+                .map(|g| g.as_arg_str())
+                .as_slice(),
+        )
         .await?;
     let client = if let EitherClient::LoggedIn(logged_in_client) = client {
         Ok(logged_in_client)
@@ -89,29 +145,105 @@ pub async fn run_command(credentials: Credentials, args: RunArgs) -> eyre::Resul
             "chrs login".bold()
         ))
     }?;
+    let follow = args.follow.then_some(args.timeout);
     if let Some(id) = run(&client, old, ui, args).await? {
-        crate::login::set_cd(client.url(), client.username(), id, credentials.config_path)?;
+        crate::login::set_cd(
+            client.url(),
+            client.username(),
+            id,
+            credentials.config_path,
+            credentials.ephemeral,
+        )?;
         println!("plugininstance/{}", id.0);
+        if let Some(timeout) = follow {
+            let plinst = client.get_plugin_instance(id).await?;
+            let plinst = wait_for_plugin_instance(plinst, timeout).await?;
+            let status = &plinst.object.status;
+            if status.simplify() != SimplifiedStatus::Success {
+                eprint_logs(&plinst);
+                bail!("plugininstance/{} {}", id.0, status.as_str())
+            }
+        }
     }
     Ok(())
 }
 
+/// Parse a duration given as a number of seconds, optionally followed by a unit
+/// `s`, `m`, or `h`.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, seconds_per_unit) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 3600),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .map(|n| Duration::from_secs(n.saturating_mul(seconds_per_unit)))
+        .map_err(|_| format!("\"{value}\" is not a duration, e.g. 90s, 30m, or 2h"))
+}
+
+/// A parameter of a piping given by `--set`.
+#[derive(Clone, Debug, PartialEq)]
+struct PipingParam {
+    piping: String,
+    name: String,
+    value: String,
+}
+
+/// Parse `TITLE.PARAM=VALUE`. The title of a piping may contain `.`, so the
+/// parameter name is what comes after the last `.` before `=`.
+fn parse_piping_param(value: &str) -> Result<PipingParam, String> {
+    value
+        .split_once('=')
+        .and_then(|(key, value)| {
+            key.rsplit_once('.')
+                .filter(|(piping, name)| !piping.is_empty() && !name.is_empty())
+                .map(|(piping, name)| PipingParam {
+                    piping: piping.to_string(),
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+        })
+        .ok_or_else(|| format!("\"{value}\" is not of the form TITLE.PARAM=VALUE"))
+}
+
 async fn run(
     client: &ChrisClient,
     old: Option<PluginInstanceId>,
     ui: Option<UiUrl>,
-    args: RunArgs,
+    mut args: RunArgs,
 ) -> eyre::Result<Option<PluginInstanceId>> {
+    let plan = if let Some(path) = args.plan.as_deref() {
+        let plan = RunPlan::load(path)?;
+        apply_plan_defaults(&mut args, &plan);
+        Some(plan)
+    } else {
+        None
+    };
+    let resolve_runnable = async {
+        if let Some(plan) = plan.as_ref() {
+            plan.runnable
+                .resolve_using(client, args.allow_version_drift)
+                .await
+        } else {
+            args.plugin_or_pipeline
+                .clone()
+                .ok_or_eyre("Missing plugin or pipeline")?
+                .resolve_using(client)
+                .await
+        }
+    };
     let (title_is_unique, runnable) = try_join!(
         check_title(client, old, args.title.as_deref(), args.force),
-        args.plugin_or_pipeline.clone().resolve_using(client)
+        resolve_runnable
     )?;
     if let Some(error) = title_is_unique {
         bail!("{}", error);
     }
     let plinst = match runnable {
-        Runnable::Plugin(p) => run_plugin(client, p, old, args).await,
-        Runnable::Pipeline(p) => run_pipeline(client, p, old, args).await,
+        Runnable::Plugin(p) => run_plugin(client, p, old, plan, args).await,
+        Runnable::Pipeline(p) => run_pipeline(client, p, old, plan, args).await,
     }?;
     if let (Some(ui), Some(plinst)) = (ui, plinst.as_ref()) {
         let feed = plinst.feed().get().await?;
@@ -121,37 +253,152 @@ async fn run(
     Ok(plinst.map(|p| p.object.id))
 }
 
+/// Fill in options which were not given on the command line from the plan.
+fn apply_plan_defaults(args: &mut RunArgs, plan: &RunPlan) {
+    let resources = plan.resources.clone();
+    if args.cpu.is_none() && args.cpu_limit.is_none() {
+        args.cpu_limit = resources.cpu_limit;
+    }
+    args.memory_limit = args.memory_limit.take().or(resources.memory_limit);
+    args.gpu_limit = args.gpu_limit.or(resources.gpu_limit);
+    args.number_of_workers = args.number_of_workers.or(resources.number_of_workers);
+    args.compute_resource_name = args
+        .compute_resource_name
+        .take()
+        .or(resources.compute_resource_name);
+    args.title = args.title.take().or_else(|| plan.title.clone());
+}
+
 async fn run_plugin(
     client: &ChrisClient,
     plugin: PluginRw,
     old: Option<PluginInstanceId>,
+    plan: Option<RunPlan>,
     args: RunArgs,
 ) -> eyre::Result<Option<PluginInstanceRw>> {
-    let (params, incoming) = clap_serialize_params(&plugin, &args.parameters).await?;
-    let previous = get_input(client, old, incoming, args.threads).await?;
-    let previous_id = previous.as_ref().map(|previous| previous.object.id.0);
-    if args.dry_run {
-        eprintln!("Input: plugininstance/{:?}", previous_id);
-        Ok(None)
+    if !args.set.is_empty() {
+        bail!("--set can only be used to run a pipeline, not a plugin")
+    }
+    let parameter_info: Vec<_> = plugin.parameters().stream().try_collect().await?;
+    let (params, incoming) = if let Some(plan) = plan {
+        let params = plan.checked_parameters(&parameter_info)?;
+        let incoming = plan.previous.into_iter().map(GivenDataNode::from).collect();
+        (params, incoming)
     } else {
-        create_plugin_instance(&plugin, params, previous_id, args)
-            .await
-            .map(Some)
+        let preset = match args.params_file.as_deref() {
+            Some(path) => load_params_file(path, &parameter_info)?,
+            None => HashMap::new(),
+        };
+        clap_serialize_params(
+            &plugin.object.selfexec,
+            &parameter_info,
+            &args.parameters,
+            &preset,
+            &mut ChrisPathResolver::new(client, old),
+        )
+        .await?
+    };
+    if !args.no_validate {
+        validate_params(&plugin.object.selfexec, &params, &parameter_info)?;
+    }
+    if let Some(name) = args.compute_resource_name.as_ref() {
+        let available: Vec<_> = plugin
+            .compute_resources()
+            .stream()
+            .map_ok(|c| c.name)
+            .try_collect()
+            .await?;
+        validate_compute_resource(&plugin.object.selfexec, name, &available)?;
     }
+    let inputs = resolve_inputs(client, old, incoming, args.threads).await?;
+    if let Some(path) = args.save_plan.as_deref() {
+        RunPlan::for_plugin(
+            &plugin.object,
+            inputs.iter().map(|p| p.object.id).collect(),
+            &params,
+            &parameter_info,
+            Resources::from(&args),
+            args.title.clone(),
+        )
+        .save(path)?;
+    }
+    if args.dry_run {
+        print_dry_run_inputs(&inputs);
+        let previous_id = dry_run_previous_id(&inputs);
+        let body = instance_body(params, previous_id, &args);
+        let dry_run = serde_json::json!({
+            "plugin": {
+                "url": plugin.object.url,
+                "name": plugin.object.name,
+                "version": plugin.object.version,
+            },
+            "body": BTreeMap::from_iter(body),
+        });
+        println!("{}", serde_json::to_string_pretty(&dry_run)?);
+        return Ok(None);
+    }
+    let previous = get_input(client, inputs).await?;
+    let previous_id = previous.as_ref().map(|previous| previous.object.id.0);
+    create_plugin_instance(&plugin, params, previous_id, args)
+        .await
+        .map(Some)
 }
 
 async fn run_pipeline(
     client: &ChrisClient,
     pipeline: PipelineRw,
     old: Option<PluginInstanceId>,
+    plan: Option<RunPlan>,
     args: RunArgs,
 ) -> eyre::Result<Option<PluginInstanceRw>> {
-    let inputs: Vec<GivenDataNode> = args.parameters.into_iter().map(|p| p.into()).collect();
-    let prev = get_input(client, old, inputs, args.threads)
+    if args.params_file.is_some() {
+        bail!("--params-file can only be used to run a plugin, not a pipeline")
+    }
+    let incoming: Vec<GivenDataNode> = if let Some(plan) = plan {
+        plan.previous.into_iter().map(GivenDataNode::from).collect()
+    } else {
+        args.parameters
+            .iter()
+            .cloned()
+            .map(GivenDataNode::from)
+            .collect()
+    };
+    let inputs = resolve_inputs(client, old, incoming, args.threads).await?;
+    if let Some(path) = args.save_plan.as_deref() {
+        RunPlan::for_pipeline(
+            pipeline.object.name.clone(),
+            inputs.iter().map(|p| p.object.id).collect(),
+            args.title.clone(),
+        )
+        .save(path)?;
+    }
+    if args.dry_run {
+        print_dry_run_inputs(&inputs);
+        let body = if let Some(previous_id) = dry_run_previous_id(&inputs) {
+            let builder = workflow_builder(&pipeline, PluginInstanceId(previous_id), &args);
+            serde_json::to_value(builder.body().await?)?
+        } else {
+            serde_json::json!({
+                "previous_plugin_inst_id": null,
+                "title": args.title,
+            })
+        };
+        let dry_run = serde_json::json!({
+            "pipeline": {
+                "url": pipeline.object.url,
+                "id": pipeline.object.id,
+                "name": pipeline.object.name,
+            },
+            "body": body,
+        });
+        println!("{}", serde_json::to_string_pretty(&dry_run)?);
+        return Ok(None);
+    }
+    let prev = get_input(client, inputs)
         .await?
         .ok_or_eyre("Missing operand")?;
-    let workflow = pipeline
-        .create_workflow(prev.object.id, args.title.as_deref())
+    let workflow = workflow_builder(&pipeline, prev.object.id, &args)
+        .create()
         .await?;
     // get the "last" plugin instance created by the workflow. Assumes CUBE returns the plugin instances in order.
     workflow
@@ -161,20 +408,38 @@ async fn run_pipeline(
         .map_err(eyre::Error::new)
 }
 
+/// Run `pipeline` with the title and piping parameters of `args`.
+fn workflow_builder<'a>(
+    pipeline: &'a PipelineRw,
+    previous_id: PluginInstanceId,
+    args: &RunArgs,
+) -> WorkflowBuilder<'a> {
+    let builder = pipeline.workflow_builder(previous_id);
+    let builder = match args.title.as_deref() {
+        Some(title) => builder.title(title),
+        None => builder,
+    };
+    args.set.iter().fold(builder, |builder, p| {
+        builder.set_param(
+            &p.piping,
+            &p.name,
+            PluginParameterValue::Stringish(p.value.clone()),
+        )
+    })
+}
+
 /// Create a plugin instance. If the plugin is a fs-type plugin, then the created feed name
 /// is set to the plugin instance's title.
 async fn create_plugin_instance(
     plugin: &PluginRw,
-    mut params: HashMap<String, PluginParameterValue>,
+    params: HashMap<String, PluginParameterValue>,
     previous_id: Option<u32>,
     args: RunArgs,
 ) -> eyre::Result<PluginInstanceRw> {
-    let title = args.title.clone();
-    let optional_resources = serialize_optional_resources(args, previous_id);
-    params.extend(optional_resources);
+    let params = instance_body(params, previous_id, &args);
     let created = plugin.create_instance(&params).await?;
     if previous_id.is_none() {
-        if let Some(title) = title {
+        if let Some(title) = args.title {
             let feed = created.feed();
             feed.set_name(&title).await?;
         }
@@ -182,42 +447,63 @@ async fn create_plugin_instance(
     Ok(created)
 }
 
+/// The request body for creating a plugin instance: plugin parameters plus
+/// resource requests, title, and `previous_id`.
+fn instance_body(
+    mut params: HashMap<String, PluginParameterValue>,
+    previous_id: Option<u32>,
+    args: &RunArgs,
+) -> HashMap<String, PluginParameterValue> {
+    let optional_resources =
+        serialize_optional_resources(Resources::from(args), args.title.clone(), previous_id);
+    params.extend(optional_resources);
+    params
+}
+
+impl From<&RunArgs> for Resources {
+    fn from(args: &RunArgs) -> Self {
+        let cpu_limit = args.cpu.map(|c| CpuLimit(c * 1000)).or(args.cpu_limit);
+        Self {
+            cpu_limit,
+            memory_limit: args.memory_limit,
+            gpu_limit: args.gpu_limit,
+            number_of_workers: args.number_of_workers,
+            compute_resource_name: args.compute_resource_name.clone(),
+        }
+    }
+}
+
 fn serialize_optional_resources(
-    args: RunArgs,
+    resources: Resources,
+    title: Option<String>,
     previous_id: Option<u32>,
 ) -> impl Iterator<Item = (String, PluginParameterValue)> {
-    let cpu_limit = args
-        .cpu
-        .map(|c| format!("{}m", c * 1000))
-        .or(args.cpu_limit);
     let optional_resources = [
-        cpu_limit.map(|v| ("cpu_limit".to_string(), PluginParameterValue::Stringish(v))),
-        args.memory_limit.map(|v| {
-            (
-                "memory_limit".to_string(),
-                PluginParameterValue::Stringish(v),
-            )
-        }),
-        args.gpu_limit.map(|v| {
+        resources
+            .cpu_limit
+            .map(|v| ("cpu_limit".to_string(), v.into())),
+        resources
+            .memory_limit
+            .map(|v| ("memory_limit".to_string(), v.into())),
+        resources.gpu_limit.map(|v| {
             (
                 "gpu_limit".to_string(),
                 PluginParameterValue::Integer(v as i64),
             )
         }),
-        args.number_of_workers.map(|v| {
+        resources.number_of_workers.map(|v| {
             (
                 "number_of_workers".to_string(),
                 PluginParameterValue::Integer(v as i64),
             )
         }),
-        args.compute_resource_name.map(|v| {
+        resources.compute_resource_name.map(|v| {
             (
                 "compute_resource_name".to_string(),
                 PluginParameterValue::Stringish(v.to_string()),
             )
         }),
-        args.title
-            .map(|v| ("title".to_string(), PluginParameterValue::Stringish(v))),
+        title.map(|v| ("title".to_string(), PluginParameterValue::Stringish(v))),
         previous_id.map(|v| {
             (
                 "previous_id".to_string(),
@@ -251,7 +537,7 @@ async fn check_title(
     Ok(None)
 }
 
-enum TitleUniqueness {
+pub(crate) enum TitleUniqueness {
     NotUniqueWithinFeed,
     NotUniqueFeedName,
     NoTitle,
@@ -271,7 +557,7 @@ impl Display for TitleUniqueness {
     }
 }
 
-async fn title_is_not_unique(
+pub(crate) async fn title_is_not_unique(
     client: &ChrisClient,
     plinst: PluginInstanceId,
     title: &str,
@@ -282,108 +568,88 @@ async fn title_is_not_unique(
         .feed_id(feed_id)
         .title(title.to_string());
     let search = query.search();
-    search.get_count().await.map(|count| count > 0)
+    search.is_empty().await.map(|empty| !empty)
 }
 
-async fn feed_name_is_not_unique(client: &ChrisClient, name: &str) -> Result<bool, CubeError> {
+pub(crate) async fn feed_name_is_not_unique(
+    client: &ChrisClient,
+    name: &str,
+) -> Result<bool, CubeError> {
     let query = client.feeds().name_exact(name);
     let search = query.search();
-    search.get_count().await.map(|count| count > 0)
+    search.is_empty().await.map(|empty| !empty)
 }
 
-/// Picks a plugin instance to use as the input.
-///
-/// - If `given` is of length one: get it as a plugin instance.
-/// - If `given` has length > 1: run `pl-topologicalcopy` and return that
-/// - If `given` has length = 0: get `old` and return that
-async fn get_input(
+/// Get the plugin instances of `given`. If nothing is given, get `old` instead.
+async fn resolve_inputs(
     client: &ChrisClient,
     old: Option<PluginInstanceId>,
     given: Vec<GivenDataNode>,
     threads: usize,
-) -> eyre::Result<Option<PluginInstanceRw>> {
-    if given.len() > 1 {
-        return topologicalcopy(client, old, given, threads).await.map(Some);
-    }
-    if let Some(feed_or_plinst) = given.into_iter().next() {
-        feed_or_plinst.into_plinst_rw(client, old).await.map(Some)
-    } else if let Some(id) = old {
-        client
-            .get_plugin_instance(id)
-            .await
-            .map(Some)
-            .map_err(eyre::Error::new)
-    } else {
-        Ok(None)
+) -> eyre::Result<Vec<PluginInstanceRw>> {
+    if given.is_empty() {
+        return if let Some(id) = old {
+            client
+                .get_plugin_instance(id)
+                .await
+                .map(|p| vec![p])
+                .map_err(eyre::Error::new)
+        } else {
+            Ok(Vec::with_capacity(0))
+        };
     }
-}
-
-/// Run `pl-topologicalcopy`
-async fn topologicalcopy(
-    client: &ChrisClient,
-    old: Option<PluginInstanceId>,
-    given: Vec<GivenDataNode>,
-    threads: usize,
-) -> eyre::Result<PluginInstanceRw> {
-    let previous: Vec<_> = futures::stream::iter(given)
-        .map(|p| async move { p.into_plinst_rw(client, old).await.map(|p| p.object) })
-        .map(Ok::<_, eyre::Error>)
-        .try_buffered(threads)
+    futures::stream::iter(given)
+        .map(|p| p.into_plinst_rw(client, old))
+        .buffered(threads)
         .try_collect()
-        .await?;
-    let topologicalcopy = client
-        .plugin()
-        .name_exact("pl-topologicalcopy")
-        .version("1.0.2")
-        .search()
-        .get_only()
         .await
-        .wrap_err("pl-topologicalcopy@1.0.2 not found")?;
-    let params = TopologicalCopyParameters::new(&previous);
-    let created = topologicalcopy.create_instance(&params).await?;
-    Ok(created)
 }
 
-#[derive(serde::Serialize)]
-struct TopologicalCopyParameters {
-    previous_id: PluginInstanceId,
-    plugininstances: String,
-    title: String,
+fn print_dry_run_inputs(inputs: &[PluginInstanceRw]) {
+    let inputs = inputs
+        .iter()
+        .map(|p| format!("plugininstance/{}", p.object.id.0))
+        .join(" ");
+    eprintln!("Input: {}", inputs);
 }
 
-impl TopologicalCopyParameters {
-    fn new(previous: &[PluginInstanceResponse]) -> Self {
-        let title = format!(
-            "Merge of: {}",
-            previous
-                .iter()
-                .map(quoted_title_of_plinst_response)
-                .join(" ")
-        );
-        // CUBE does not allow plugin instance titles to be longer than 100 characters.
-        let title = if title.len() > 100 {
-            format!("{}...", &title[..97])
-        } else {
-            title
-        };
-        Self {
-            previous_id: previous.first().unwrap().id,
-            plugininstances: previous.iter().map(|p| p.id.0.to_string()).join(","),
-            title,
+/// The `previous_id` which would be used for `inputs` if this were not a dry run.
+///
+/// Multiple inputs would be merged by `pl-topologicalcopy` first, which is not
+/// done in a dry run, so its ID cannot be known.
+fn dry_run_previous_id(inputs: &[PluginInstanceRw]) -> Option<u32> {
+    match inputs {
+        [] => None,
+        [input] => Some(input.object.id.0),
+        _ => {
+            eprintln!(
+                "{}",
+                "Note: the inputs would be merged by pl-topologicalcopy, whose ID is used as previous_id."
+                    .dimmed()
+            );
+            None
         }
     }
 }
 
-fn quoted_title_of_plinst_response(p: &PluginInstanceResponse) -> String {
-    if p.title.is_empty() {
-        format!("{}#{}", p.plugin_name, p.id.0)
+/// Picks a plugin instance to use as the input.
+///
+/// - If `inputs` is of length one: return it.
+/// - If `inputs` has length > 1: run `pl-topologicalcopy` and return that
+/// - If `inputs` has length = 0: return nothing
+async fn get_input(
+    client: &ChrisClient,
+    mut inputs: Vec<PluginInstanceRw>,
+) -> eyre::Result<Option<PluginInstanceRw>> {
+    if inputs.len() > 1 {
+        topologicalcopy(client, inputs, None, None).await.map(Some)
     } else {
-        format!("\"{}\"", p.title)
+        Ok(inputs.pop())
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use std::collections::HashSet;
     use std::path::PathBuf;
 
@@ -421,7 +687,7 @@ mod tests {
 
     #[fixture]
     #[once]
-    fn credentials(cube_url: CubeUrl, config_path: &Option<PathBuf>) -> Credentials {
+    pub(crate) fn credentials(cube_url: CubeUrl, config_path: &Option<PathBuf>) -> Credentials {
         let username: String = fake::faker::internet::en::Username().fake();
         let email: String = fake::faker::internet::en::SafeEmail().fake();
         let password = format!("{}1234", &username.chars().rev().collect::<String>());
@@ -438,7 +704,11 @@ mod tests {
                 store: StoredToken::Text(token),
                 current_plugin_instance_id: None,
                 ui: None,
+                last_listed: None,
+                remember_password: false,
+                preferences: Default::default(),
             }],
+            ..Default::default()
         };
         // save token to storage
         sessions.save(config_path.as_deref()).unwrap();
@@ -450,6 +720,8 @@ mod tests {
             retries: None,
             ui: None,
             config_path: config_path.clone(),
+            ephemeral: false,
+            anonymous: false,
         }
     }
 
@@ -563,7 +835,7 @@ mod tests {
                 Some(third_title.clone()),
                 "pl-mri-preview@1.2.0",
                 &[&feed_by_name],
-                Some("1234Mi".to_string()),
+                Some(MemoryLimit(1234)),
             ),
         )
         .await
@@ -715,7 +987,43 @@ mod tests {
         );
     }
 
-    fn uuid_name(name: &str) -> String {
+    #[rstest]
+    fn test_instance_body() {
+        let mut args = create_args_mem(
+            Some("my title".to_string()),
+            "pl-dircopy",
+            &[],
+            Some(MemoryLimit(2048)),
+        );
+        args.cpu = Some(2);
+        let params = HashMap::from([(
+            "dir".to_string(),
+            PluginParameterValue::Stringish("a/b".to_string()),
+        )]);
+        let actual = instance_body(params, Some(5), &args);
+        let expected = HashMap::from([
+            (
+                "dir".to_string(),
+                PluginParameterValue::Stringish("a/b".to_string()),
+            ),
+            (
+                "cpu_limit".to_string(),
+                PluginParameterValue::Stringish("2000m".to_string()),
+            ),
+            (
+                "memory_limit".to_string(),
+                PluginParameterValue::Stringish("2Gi".to_string()),
+            ),
+            (
+                "title".to_string(),
+                PluginParameterValue::Stringish("my title".to_string()),
+            ),
+            ("previous_id".to_string(), PluginParameterValue::Integer(5)),
+        ]);
+        assert_eq!(actual, expected)
+    }
+
+    pub(crate) fn uuid_name(name: &str) -> String {
         format!(
             "chrs test -- {} -- {}",
             name,
@@ -734,8 +1042,18 @@ mod tests {
             title,
             force: false,
             dry_run: false,
-            plugin_or_pipeline: GivenRunnable::try_from(plugin_or_pipeline.to_string()).unwrap(),
+            no_validate: false,
+            save_plan: None,
+            plan: None,
+            allow_version_drift: false,
+            params_file: None,
+            plugin_or_pipeline: Some(
+                GivenRunnable::try_from(plugin_or_pipeline.to_string()).unwrap(),
+            ),
             threads: 4,
+            follow: false,
+            timeout: None,
+            set: Vec::new(),
             parameters: args.into_iter().map(|s| s.to_string()).collect(),
         }
     }
@@ -744,7 +1062,7 @@ mod tests {
         title: Option<String>,
         plugin: &str,
         args: &[&str],
-        memory_limit: Option<String>,
+        memory_limit: Option<MemoryLimit>,
     ) -> RunArgs {
         RunArgs {
             cpu: None,
@@ -756,9 +1074,49 @@ mod tests {
             title,
             force: false,
             dry_run: false,
-            plugin_or_pipeline: GivenRunnable::try_from(plugin.to_string()).unwrap(),
+            no_validate: false,
+            save_plan: None,
+            plan: None,
+            allow_version_drift: false,
+            params_file: None,
+            plugin_or_pipeline: Some(GivenRunnable::try_from(plugin.to_string()).unwrap()),
             threads: 4,
+            follow: false,
+            timeout: None,
+            set: Vec::new(),
             parameters: args.into_iter().map(|s| s.to_string()).collect(),
         }
     }
+
+    #[rstest]
+    #[case("90", Some(90))]
+    #[case("90s", Some(90))]
+    #[case("30m", Some(1800))]
+    #[case("2h", Some(7200))]
+    #[case("2d", None)]
+    #[case("m", None)]
+    #[case("", None)]
+    fn test_parse_duration(#[case] value: &str, #[case] expected: Option<u64>) {
+        assert_eq!(
+            parse_duration(value).ok(),
+            expected.map(Duration::from_secs)
+        )
+    }
+
+    #[rstest]
+    #[case("child.sleepLength=5", Some(("child", "sleepLength", "5")))]
+    #[case("fetch v1.2.prefix=a=b", Some(("fetch v1.2", "prefix", "a=b")))]
+    #[case("child.prefix=", Some(("child", "prefix", "")))]
+    #[case("child=5", None)]
+    #[case("child.=5", None)]
+    #[case(".sleepLength=5", None)]
+    #[case("child.sleepLength", None)]
+    fn test_parse_piping_param(#[case] value: &str, #[case] expected: Option<(&str, &str, &str)>) {
+        let expected = expected.map(|(piping, name, value)| PipingParam {
+            piping: piping.to_string(),
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+        assert_eq!(parse_piping_param(value).ok(), expected)
+    }
 }
@@ -0,0 +1,146 @@
+//! Plugin parameter values read from a file by `chrs run --params-file`.
+
+use std::collections::HashMap;
+
+use camino::Utf8Path;
+use color_eyre::eyre;
+use color_eyre::eyre::{bail, eyre, WrapErr};
+use itertools::Itertools;
+
+use chris::types::{PluginParameterType, PluginParameterValue};
+use chris::PluginParameter;
+
+use super::plan::PlannedParameter;
+
+/// Read a map of parameter names to values from a JSON or YAML file, and check them
+/// against the parameters of the plugin. The format is chosen by file extension.
+pub fn load_params_file(
+    path: &Utf8Path,
+    parameter_info: &[PluginParameter],
+) -> eyre::Result<HashMap<String, PluginParameterValue>> {
+    let text = fs_err::read_to_string(path)?;
+    let values = parse_params(path, &text)?;
+    check_params(values, parameter_info).wrap_err_with(|| format!("Invalid parameters in {}", path))
+}
+
+fn parse_params(
+    path: &Utf8Path,
+    text: &str,
+) -> eyre::Result<HashMap<String, PluginParameterValue>> {
+    match path.extension() {
+        Some("json") => serde_json::from_str(text).map_err(|e| eyre!("{}: {}", path, e)),
+        Some("yml" | "yaml") => serde_yaml::from_str(text).map_err(|e| eyre!("{}: {}", path, e)),
+        _ => bail!(
+            "Unknown file type of {}, expected a file name ending with .json, .yml, or .yaml",
+            path
+        ),
+    }
+}
+
+/// Check that every value is for a parameter of the plugin, coercing it to the parameter's type.
+fn check_params(
+    values: HashMap<String, PluginParameterValue>,
+    parameter_info: &[PluginParameter],
+) -> eyre::Result<HashMap<String, PluginParameterValue>> {
+    values
+        .into_iter()
+        .map(|(name, value)| {
+            let info = parameter_info
+                .iter()
+                .find(|info| info.name == name)
+                .ok_or_else(|| {
+                    eyre!(
+                        "Plugin does not have a parameter \"{}\". Its parameters are: {}",
+                        name,
+                        parameter_info.iter().map(|info| &info.name).join(", ")
+                    )
+                })?;
+            let value = match (info.parameter_type, value) {
+                // numbers in YAML files are fine as values of string parameters
+                (
+                    PluginParameterType::String
+                    | PluginParameterType::Path
+                    | PluginParameterType::Unextpath,
+                    v @ (PluginParameterValue::Integer(_) | PluginParameterValue::Float(_)),
+                ) => PluginParameterValue::Stringish(v.to_string()),
+                (_, v) => v,
+            };
+            let planned = PlannedParameter {
+                name,
+                parameter_type: info.parameter_type,
+                value,
+            };
+            planned.checked_value().map(|value| (planned.name, value))
+        })
+        .try_collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use rstest::*;
+
+    #[fixture]
+    async fn parameter_info() -> Vec<PluginParameter> {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(2, "pl-simpledsapp", "2.1.0");
+        vec![
+            mock.plugin_parameter(1, &plugin, "prefix", PluginParameterType::String),
+            mock.plugin_parameter(2, &plugin, "dummyFloat", PluginParameterType::Float),
+            mock.plugin_parameter(3, &plugin, "sleepLength", PluginParameterType::Integer),
+            mock.plugin_parameter(4, &plugin, "ignoreInputDir", PluginParameterType::Boolean),
+        ]
+    }
+
+    #[rstest]
+    #[case(
+        "p.yml",
+        "prefix: 2024\ndummyFloat: 3\nsleepLength: 5\nignoreInputDir: true\n"
+    )]
+    #[case(
+        "p.json",
+        r#"{"prefix": "2024", "dummyFloat": 3.0, "sleepLength": 5, "ignoreInputDir": true}"#
+    )]
+    #[tokio::test]
+    async fn test_parse_and_check(
+        #[future] parameter_info: Vec<PluginParameter>,
+        #[case] file: &str,
+        #[case] text: &str,
+    ) {
+        let parameter_info = parameter_info.await;
+        let values = parse_params(Utf8Path::new(file), text).unwrap();
+        let actual = check_params(values, &parameter_info).unwrap();
+        let expected = HashMap::from([
+            (
+                "prefix".to_string(),
+                PluginParameterValue::Stringish("2024".to_string()),
+            ),
+            ("dummyFloat".to_string(), PluginParameterValue::Float(3.0)),
+            ("sleepLength".to_string(), PluginParameterValue::Integer(5)),
+            (
+                "ignoreInputDir".to_string(),
+                PluginParameterValue::Boolean(true),
+            ),
+        ]);
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    #[case("p.yml", "prefx: hello\n", "\"prefx\"")]
+    #[case("p.yml", "sleepLength: soon\n", "\"sleepLength\" is not a int")]
+    #[case("p.toml", "", "Unknown file type")]
+    #[tokio::test]
+    async fn test_invalid(
+        #[future] parameter_info: Vec<PluginParameter>,
+        #[case] file: &str,
+        #[case] text: &str,
+        #[case] expected: &str,
+    ) {
+        let parameter_info = parameter_info.await;
+        let error = parse_params(Utf8Path::new(file), text)
+            .and_then(|values| check_params(values, &parameter_info))
+            .unwrap_err();
+        assert!(error.to_string().contains(expected), "{error}")
+    }
+}
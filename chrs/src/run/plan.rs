@@ -0,0 +1,433 @@
+//! Machine-readable plans of `chrs run`, for reproducible re-runs.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use camino::Utf8Path;
+use color_eyre::eyre;
+use color_eyre::eyre::{bail, eyre, WrapErr};
+use color_eyre::owo_colors::OwoColorize;
+use futures::TryStreamExt;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use chris::types::{
+    ComputeResourceName, CpuLimit, MemoryLimit, PluginInstanceId, PluginParameterType,
+    PluginParameterValue,
+};
+use chris::{BaseChrisClient, ChrisClient, PluginParameter, PluginResponse, PluginRw, RwAccess};
+
+use crate::arg::{GivenRunnable, Runnable};
+
+/// Version of the plan file format written by this version of `chrs`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A plan of what `chrs run` should do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunPlan {
+    pub schema_version: u32,
+    pub runnable: PlannedRunnable,
+    /// Plugin instances to use as input.
+    #[serde(default)]
+    pub previous: Vec<PluginInstanceId>,
+    #[serde(default)]
+    pub parameters: Vec<PlannedParameter>,
+    #[serde(default)]
+    pub resources: Resources,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// A plugin or pipeline, identified in a way which can be resolved against any CUBE.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PlannedRunnable {
+    Plugin { name: String, version: String },
+    Pipeline { name: String },
+}
+
+/// A plugin parameter value, along with the type of the parameter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub parameter_type: PluginParameterType,
+    pub value: PluginParameterValue,
+}
+
+/// Optional resource requests of a plugin instance.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Resources {
+    pub cpu_limit: Option<CpuLimit>,
+    pub memory_limit: Option<MemoryLimit>,
+    pub gpu_limit: Option<u32>,
+    pub number_of_workers: Option<u32>,
+    pub compute_resource_name: Option<ComputeResourceName>,
+}
+
+impl RunPlan {
+    /// Create a plan for running a plugin.
+    pub fn for_plugin(
+        plugin: &PluginResponse,
+        previous: Vec<PluginInstanceId>,
+        params: &HashMap<String, PluginParameterValue>,
+        parameter_info: &[PluginParameter],
+        resources: Resources,
+        title: Option<String>,
+    ) -> Self {
+        let parameters = parameter_info
+            .iter()
+            .filter_map(|info| {
+                params.get(&info.name).map(|value| PlannedParameter {
+                    name: info.name.clone(),
+                    parameter_type: info.parameter_type,
+                    value: value.clone(),
+                })
+            })
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .collect();
+        Self {
+            schema_version: SCHEMA_VERSION,
+            runnable: PlannedRunnable::Plugin {
+                name: plugin.name.to_string(),
+                version: plugin.version.to_string(),
+            },
+            previous,
+            parameters,
+            resources,
+            title,
+        }
+    }
+
+    /// Create a plan for running a pipeline.
+    pub fn for_pipeline(
+        name: String,
+        previous: Vec<PluginInstanceId>,
+        title: Option<String>,
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            runnable: PlannedRunnable::Pipeline { name },
+            previous,
+            parameters: Vec::with_capacity(0),
+            resources: Default::default(),
+            title,
+        }
+    }
+
+    /// Read a plan from a JSON file.
+    pub fn load(path: &Utf8Path) -> eyre::Result<Self> {
+        let content = fs_err::read_to_string(path)?;
+        Self::from_json(&content).wrap_err_with(|| format!("Invalid plan file: {}", path))
+    }
+
+    /// Write this plan to a JSON file.
+    pub fn save(&self, path: &Utf8Path) -> eyre::Result<()> {
+        fs_err::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    fn from_json(content: &str) -> eyre::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(content)?;
+        let schema_version = value
+            .get("schema_version")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| eyre!("Missing \"schema_version\", is this a chrs run plan?"))?;
+        if schema_version > SCHEMA_VERSION as u64 {
+            bail!(
+                "Plan has schema version {}, but this version of chrs only supports up to {}. Please upgrade chrs.",
+                schema_version,
+                SCHEMA_VERSION
+            )
+        }
+        serde_json::from_value(value).map_err(eyre::Error::new)
+    }
+
+    /// Get the planned parameter values, checking them against the parameters of the plugin
+    /// which the plan was resolved to.
+    pub fn checked_parameters(
+        &self,
+        parameter_info: &[PluginParameter],
+    ) -> eyre::Result<HashMap<String, PluginParameterValue>> {
+        let params: HashMap<_, _> = self
+            .parameters
+            .iter()
+            .map(|p| {
+                let info = parameter_info
+                    .iter()
+                    .find(|info| info.name == p.name)
+                    .ok_or_else(|| eyre!("Plugin does not have a parameter \"{}\"", p.name))?;
+                if info.parameter_type != p.parameter_type {
+                    bail!(
+                        "Parameter \"{}\" has type {}, but plan says it is {}",
+                        p.name,
+                        info.parameter_type.as_str(),
+                        p.parameter_type.as_str()
+                    )
+                }
+                p.checked_value().map(|value| (p.name.clone(), value))
+            })
+            .try_collect()?;
+        let missing = parameter_info
+            .iter()
+            .filter(|info| !info.optional && !params.contains_key(&info.name))
+            .map(|info| info.name.as_str())
+            .join(", ");
+        if !missing.is_empty() {
+            bail!("Plan is missing required parameters: {}", missing)
+        }
+        Ok(params)
+    }
+}
+
+impl PlannedParameter {
+    /// Get the value, coerced to the parameter's type.
+    pub(super) fn checked_value(&self) -> eyre::Result<PluginParameterValue> {
+        let value = match (self.parameter_type, &self.value) {
+            (PluginParameterType::Boolean, PluginParameterValue::Boolean(b)) => {
+                PluginParameterValue::Boolean(*b)
+            }
+            (PluginParameterType::Integer, PluginParameterValue::Integer(n)) => {
+                PluginParameterValue::Integer(*n)
+            }
+            (PluginParameterType::Float, PluginParameterValue::Float(f)) => {
+                PluginParameterValue::Float(*f)
+            }
+            (PluginParameterType::Float, PluginParameterValue::Integer(n)) => {
+                PluginParameterValue::Float(*n as f64)
+            }
+            (
+                PluginParameterType::String
+                | PluginParameterType::Path
+                | PluginParameterType::Unextpath,
+                PluginParameterValue::Stringish(s),
+            ) => PluginParameterValue::Stringish(s.clone()),
+            (t, v) => bail!(
+                "Value of parameter \"{}\" is not a {}: {}",
+                self.name,
+                t.as_str(),
+                v
+            ),
+        };
+        Ok(value)
+    }
+}
+
+impl PlannedRunnable {
+    /// Find the planned plugin or pipeline in CUBE.
+    ///
+    /// If the planned plugin version is not found and `allow_version_drift` is true,
+    /// the nearest available version of the plugin is used instead.
+    pub async fn resolve_using(
+        &self,
+        client: &ChrisClient,
+        allow_version_drift: bool,
+    ) -> eyre::Result<Runnable<RwAccess>> {
+        match self {
+            PlannedRunnable::Plugin { name, version } => {
+                resolve_plugin(client, name, version, allow_version_drift)
+                    .await
+                    .map(Runnable::Plugin)
+            }
+            PlannedRunnable::Pipeline { name } => {
+                GivenRunnable::PipelineName(name.to_string())
+                    .resolve_using(client)
+                    .await
+            }
+        }
+    }
+}
+
+async fn resolve_plugin(
+    client: &ChrisClient,
+    name: &str,
+    version: &str,
+    allow_version_drift: bool,
+) -> eyre::Result<PluginRw> {
+    let plugins: Vec<_> = client
+        .plugin()
+        .name_exact(name)
+        .search()
+        .stream_connected()
+        .try_collect()
+        .await?;
+    if plugins.is_empty() {
+        bail!("Plugin not found: {}", name)
+    }
+    let chosen_version = if plugins.iter().any(|p| p.object.version.as_str() == version) {
+        version.to_string()
+    } else if allow_version_drift {
+        let nearest = nearest_version(version, plugins.iter().map(|p| p.object.version.as_str()))
+            .unwrap()
+            .to_string();
+        eprintln!(
+            "{}: {}@{} not found, using version {} instead.",
+            "warning".bold().yellow(),
+            name,
+            version,
+            nearest
+        );
+        nearest
+    } else {
+        bail!(
+            "Plugin {}@{} not found. Available versions are: {}. Hint: use {} to run the nearest version instead.",
+            name,
+            version,
+            plugins.iter().map(|p| p.object.version.as_str()).join(", "),
+            "--allow-version-drift".bold()
+        )
+    };
+    Ok(plugins
+        .into_iter()
+        .find(|p| p.object.version.as_str() == chosen_version)
+        .unwrap())
+}
+
+/// Pick the version from `available` which is closest to `wanted`. Differences in
+/// more significant version components outweigh differences in less significant ones,
+/// and ties are broken in favor of the newer version.
+fn nearest_version<'a>(
+    wanted: &str,
+    available: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let wanted = version_components(wanted);
+    available.into_iter().min_by_key(|v| {
+        let components = version_components(v);
+        let len = wanted.len().max(components.len());
+        let distance: Vec<_> = (0..len)
+            .map(|i| {
+                let a = wanted.get(i).copied().unwrap_or(0);
+                let b = components.get(i).copied().unwrap_or(0);
+                a.abs_diff(b)
+            })
+            .collect();
+        (distance, Reverse(components))
+    })
+}
+
+fn version_components(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map(|part| {
+            part.chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect::<String>()
+                .parse()
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[fixture]
+    fn plan() -> RunPlan {
+        RunPlan {
+            schema_version: SCHEMA_VERSION,
+            runnable: PlannedRunnable::Plugin {
+                name: "pl-simpledsapp".to_string(),
+                version: "2.0.2".to_string(),
+            },
+            previous: vec![PluginInstanceId(5), PluginInstanceId(8)],
+            parameters: vec![
+                PlannedParameter {
+                    name: "dummyFloat".to_string(),
+                    parameter_type: PluginParameterType::Float,
+                    value: PluginParameterValue::Float(35.0),
+                },
+                PlannedParameter {
+                    name: "prefix".to_string(),
+                    parameter_type: PluginParameterType::String,
+                    value: PluginParameterValue::Stringish("hello".to_string()),
+                },
+            ],
+            resources: Resources {
+                cpu_limit: Some(CpuLimit(2000)),
+                memory_limit: Some(MemoryLimit(1234)),
+                gpu_limit: None,
+                number_of_workers: Some(1),
+                compute_resource_name: Some(ComputeResourceName::from_static("host")),
+            },
+            title: Some("a reproducible run".to_string()),
+        }
+    }
+
+    #[rstest]
+    fn test_round_trip(plan: RunPlan) {
+        let json = plan.to_json().unwrap();
+        let actual = RunPlan::from_json(&json).unwrap();
+        assert_eq!(actual, plan);
+    }
+
+    #[rstest]
+    fn test_round_trip_pipeline() {
+        let plan = RunPlan::for_pipeline("A pipeline".to_string(), vec![PluginInstanceId(1)], None);
+        let json = plan.to_json().unwrap();
+        assert_eq!(RunPlan::from_json(&json).unwrap(), plan);
+    }
+
+    #[rstest]
+    fn test_minimal_plan() {
+        let json =
+            r#"{"schema_version": 1, "runnable": {"type": "pipeline", "name": "A pipeline"}}"#;
+        let expected = RunPlan::for_pipeline("A pipeline".to_string(), vec![], None);
+        assert_eq!(RunPlan::from_json(json).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(r#"{"runnable": {"type": "pipeline", "name": "A pipeline"}}"#)]
+    #[case(r#"{"schema_version": 999, "runnable": {"type": "pipeline", "name": "A pipeline"}}"#)]
+    #[case(r#"{"schema_version": 1, "runnable": {"type": "workflow", "name": "A pipeline"}}"#)]
+    fn test_invalid_plan(#[case] json: &str) {
+        assert!(RunPlan::from_json(json).is_err())
+    }
+
+    #[rstest]
+    #[case(
+        PluginParameterType::Float,
+        PluginParameterValue::Integer(3),
+        Some(PluginParameterValue::Float(3.0))
+    )]
+    #[case(
+        PluginParameterType::Integer,
+        PluginParameterValue::Integer(3),
+        Some(PluginParameterValue::Integer(3))
+    )]
+    #[case(PluginParameterType::Integer, PluginParameterValue::Float(3.5), None)]
+    #[case(PluginParameterType::Boolean, PluginParameterValue::Stringish("true".to_string()), None)]
+    #[case(PluginParameterType::Path, PluginParameterValue::Stringish("a/b".to_string()), Some(PluginParameterValue::Stringish("a/b".to_string())))]
+    fn test_checked_value(
+        #[case] parameter_type: PluginParameterType,
+        #[case] value: PluginParameterValue,
+        #[case] expected: Option<PluginParameterValue>,
+    ) {
+        let param = PlannedParameter {
+            name: "unit".to_string(),
+            parameter_type,
+            value,
+        };
+        assert_eq!(param.checked_value().ok(), expected);
+    }
+
+    #[rstest]
+    #[case("1.2.3", &["1.2.2", "1.2.4"], Some("1.2.4"))]
+    #[case("1.2.3", &["1.3.0", "1.2.0"], Some("1.2.0"))]
+    #[case("1.2.3", &["2.2.3", "1.9.9"], Some("1.9.9"))]
+    #[case("1.2.3", &["0.0.1"], Some("0.0.1"))]
+    #[case("1.2", &["1.2.1", "1.3"], Some("1.2.1"))]
+    #[case("1.2.3", &[], None)]
+    fn test_nearest_version(
+        #[case] wanted: &str,
+        #[case] available: &[&'static str],
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(nearest_version(wanted, available.iter().copied()), expected);
+    }
+}
@@ -0,0 +1,227 @@
+//! Checking plugin parameters before creating a plugin instance, so that mistakes
+//! are explained better than by the error response of _CUBE_.
+
+use std::collections::HashMap;
+
+use color_eyre::eyre;
+use color_eyre::owo_colors::OwoColorize;
+use color_eyre::Section;
+use itertools::Itertools;
+
+use chris::types::{ComputeResourceName, PluginParameterValue};
+use chris::PluginParameter;
+
+/// A problem with the parameters given for a plugin.
+#[derive(Debug, PartialEq)]
+enum Problem<'a> {
+    /// A required parameter without a default was not given.
+    Missing(&'a PluginParameter),
+    /// The given value is not one of the parameter's `choices`.
+    NotAChoice(&'a PluginParameter, &'a PluginParameterValue),
+}
+
+/// Check that every required parameter without a default is given, and that given
+/// values are allowed by the `choices` of their parameters.
+pub fn validate_params(
+    selfexec: &str,
+    params: &HashMap<String, PluginParameterValue>,
+    parameter_info: &[PluginParameter],
+) -> eyre::Result<()> {
+    let problems = find_problems(params, parameter_info);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(eyre::eyre!(
+        "Invalid parameters for {}:\n{}",
+        selfexec,
+        problems.iter().map(explain).join("\n")
+    ))
+    .with_suggestion(|| {
+        format!(
+            "If the parameters of the plugin are described wrong, use {} to run it anyway.",
+            "--no-validate".bold()
+        )
+    })
+}
+
+fn find_problems<'a>(
+    params: &'a HashMap<String, PluginParameterValue>,
+    parameter_info: &'a [PluginParameter],
+) -> Vec<Problem<'a>> {
+    parameter_info
+        .iter()
+        .filter_map(|info| match params.get(&info.name) {
+            None if !info.optional && info.default.is_none() => Some(Problem::Missing(info)),
+            Some(value) if !is_a_choice(info, value) => Some(Problem::NotAChoice(info, value)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether the parameter allows the value. Parameters without `choices` allow anything.
+fn is_a_choice(info: &PluginParameter, value: &PluginParameterValue) -> bool {
+    match info.choices.as_deref() {
+        None | Some([]) => true,
+        Some(choices) => choices.contains(value),
+    }
+}
+
+fn explain(problem: &Problem) -> String {
+    match problem {
+        Problem::Missing(info) => {
+            format!(
+                "  missing {} <{}>  {}",
+                flags_of(info),
+                info.parameter_type.as_str(),
+                info.help.dimmed()
+            )
+        }
+        Problem::NotAChoice(info, value) => format!(
+            "  {} must be one of: {} (given: {})",
+            flags_of(info),
+            info.choices.iter().flatten().join(", "),
+            value
+        ),
+    }
+}
+
+/// Smallest similarity between a given compute resource name and the name of
+/// an available compute resource for it to be suggested.
+const MIN_SIMILARITY: f64 = 0.8;
+
+/// Check that `name` is one of the compute resources which a plugin can run on.
+pub fn validate_compute_resource(
+    selfexec: &str,
+    name: &ComputeResourceName,
+    available: &[String],
+) -> eyre::Result<()> {
+    if available.iter().any(|a| a == name.as_str()) {
+        return Ok(());
+    }
+    let error = Err(eyre::eyre!(
+        "{} cannot run on compute resource \"{}\". Available compute resources: {}",
+        selfexec,
+        name,
+        available.join(", ")
+    ));
+    match close_matches(name.as_str(), available).as_slice() {
+        [] => error,
+        matches => error.with_suggestion(|| format!("Did you mean: {}", matches.join(", "))),
+    }
+}
+
+/// Names of `available` which are similar to `name`, most similar first.
+fn close_matches<'a>(name: &str, available: &'a [String]) -> Vec<&'a str> {
+    available
+        .iter()
+        .map(|a| (strsim::jaro_winkler(name, a), a.as_str()))
+        .filter(|(similarity, _)| *similarity >= MIN_SIMILARITY)
+        .sorted_by(|(a, _), (b, _)| b.total_cmp(a))
+        .map(|(_, a)| a)
+        .collect()
+}
+
+fn flags_of(info: &PluginParameter) -> String {
+    if info.short_flag.is_empty() || info.short_flag == info.flag {
+        info.flag.clone()
+    } else {
+        format!("{}, {}", info.short_flag, info.flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use chris::types::PluginParameterType;
+    use rstest::*;
+
+    fn s(value: &str) -> PluginParameterValue {
+        PluginParameterValue::Stringish(value.to_string())
+    }
+
+    #[fixture]
+    async fn parameter_info() -> Vec<PluginParameter> {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(2, "pl-test", "1.0.0");
+        let parameter = |id, name: &str, optional, default, choices| PluginParameter {
+            optional,
+            default,
+            choices,
+            help: format!("help for {}", name),
+            ..mock.plugin_parameter(id, &plugin, name, PluginParameterType::String)
+        };
+        vec![
+            parameter(1, "required", false, None, None),
+            parameter(2, "with-default", false, Some(s("x")), None),
+            parameter(3, "optional", true, None, None),
+            parameter(4, "mode", true, None, Some(vec![s("fast"), s("slow")])),
+        ]
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_valid(#[future] parameter_info: Vec<PluginParameter>) {
+        let parameter_info = parameter_info.await;
+        let params = HashMap::from([
+            ("required".to_string(), s("a")),
+            ("mode".to_string(), s("fast")),
+        ]);
+        assert_eq!(find_problems(&params, &parameter_info), vec![]);
+        assert!(validate_params("pl-test", &params, &parameter_info).is_ok())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_missing(#[future] parameter_info: Vec<PluginParameter>) {
+        let parameter_info = parameter_info.await;
+        let params = HashMap::new();
+        assert_eq!(
+            find_problems(&params, &parameter_info),
+            vec![Problem::Missing(&parameter_info[0])]
+        );
+        let message = validate_params("pl-test", &params, &parameter_info)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("missing --required <string>"));
+        assert!(!message.contains("--with-default"));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_not_a_choice(#[future] parameter_info: Vec<PluginParameter>) {
+        let parameter_info = parameter_info.await;
+        let medium = s("medium");
+        let params = HashMap::from([
+            ("required".to_string(), s("a")),
+            ("mode".to_string(), medium.clone()),
+        ]);
+        assert_eq!(
+            find_problems(&params, &parameter_info),
+            vec![Problem::NotAChoice(&parameter_info[3], &medium)]
+        );
+        let message = validate_params("pl-test", &params, &parameter_info)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("--mode must be one of: fast, slow (given: medium)"));
+    }
+
+    #[rstest]
+    #[case("galena", vec!["galena"])]
+    #[case("galean", vec!["galena"])]
+    #[case("moc-gpu", vec!["moc-gpu", "moc"])]
+    #[case("aws", vec![])]
+    fn test_close_matches(#[case] name: &str, #[case] expected: Vec<&str>) {
+        let available = ["host", "galena", "moc", "moc-gpu"].map(String::from);
+        assert_eq!(close_matches(name, &available), expected)
+    }
+
+    #[rstest]
+    fn test_validate_compute_resource() {
+        let available = ["host", "galena"].map(String::from);
+        let name = ComputeResourceName::from_static("galena");
+        assert!(validate_compute_resource("pl-x", &name, &available).is_ok());
+        let name = ComputeResourceName::from_static("galean");
+        assert!(validate_compute_resource("pl-x", &name, &available).is_err());
+    }
+}
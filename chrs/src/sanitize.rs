@@ -0,0 +1,73 @@
+use std::borrow::Cow;
+use std::iter::Peekable;
+
+/// Make a string from _CUBE_ (e.g. a feed name) safe to print to a terminal.
+///
+/// ANSI CSI sequences (e.g. colors) and OSC sequences (e.g. setting the window title)
+/// are removed. Other control characters, such as `\r`, are replaced with a visible escape.
+pub fn sanitize_for_terminal(s: &str) -> Cow<'_, str> {
+    if !s.chars().any(char::is_control) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' if chars.next_if_eq(&'[').is_some() => skip_csi(&mut chars),
+            '\x1b' if chars.next_if_eq(&']').is_some() => skip_osc(&mut chars),
+            '\u{9b}' => skip_csi(&mut chars),
+            '\u{9d}' => skip_osc(&mut chars),
+            c if c.is_control() => out.extend(c.escape_default()),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Skip the parameter and intermediate bytes, then the final byte of a CSI sequence.
+fn skip_csi(chars: &mut Peekable<impl Iterator<Item = char>>) {
+    while chars.next_if(|c| ('\x20'..='\x3f').contains(c)).is_some() {}
+    chars.next_if(|c| ('\x40'..='\x7e').contains(c));
+}
+
+/// Skip until the end of an OSC sequence, which is terminated by BEL or ST.
+fn skip_osc(chars: &mut Peekable<impl Iterator<Item = char>>) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\x07' | '\u{9c}' => return,
+            '\x1b' if chars.next_if_eq(&'\\').is_some() => return,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("plain name", "plain name")]
+    #[case("émoji 😀 名前", "émoji 😀 名前")]
+    #[case("\x1b[31mred\x1b[0m", "red")]
+    #[case("\x1b[1;38;5;208mbold orange", "bold orange")]
+    #[case("\x1b]0;pwned\x07title", "title")]
+    #[case("\x1b]0;pwned\x1b\\title", "title")]
+    #[case("\x1b]8;;https://example.org\x1b\\link\x1b]8;;\x1b\\", "link")]
+    #[case("\u{9b}31mC1 CSI", "C1 CSI")]
+    #[case("\u{9d}0;C1 OSC\u{9c}after", "after")]
+    #[case("over\rwrite", r"over\rwrite")]
+    #[case("two\nlines", r"two\nlines")]
+    #[case("lone \x1b escape", r"lone \u{1b} escape")]
+    #[case("bell\x07", r"bell\u{7}")]
+    #[case("ñ\x1b[2Jü", "ñü")]
+    #[case("\x1b]0;unterminated", "")]
+    fn test_sanitize_for_terminal(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(sanitize_for_terminal(value), expected)
+    }
+
+    #[rstest]
+    fn test_clean_string_is_borrowed() {
+        assert!(matches!(sanitize_for_terminal("feed"), Cow::Borrowed(_)))
+    }
+}
@@ -1,32 +1,116 @@
 use crate::credentials::{Credentials, NO_ARGS};
+use crate::list::parse_time;
+use crate::output::{write_stream, OutputFormat, Render};
+use crate::sanitize::sanitize_for_terminal;
+use crate::table::Fit;
+use crate::unicode::display_width;
 use chris::errors::CubeError;
-use chris::{PipelineResponse, PluginResponse};
+use chris::search::{PipelineSearchBuilder, PluginSearchBuilder, Search};
+use chris::types::PluginType;
+use chris::{PipelineResponse, PluginResponse, RoAccess};
 use clap::Parser;
 use color_eyre::eyre;
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{bail, Result};
 use color_eyre::owo_colors::OwoColorize;
 use futures::future::Ready;
 use futures::{future, TryStreamExt};
+use serde::Serialize;
+use time::OffsetDateTime;
 
 #[derive(Parser)]
 pub struct SearchArgs {
+    /// Do not shorten names to fit the width of the terminal
+    #[clap(long)]
+    no_ellipsis: bool,
+
+    /// Search only plugins
+    #[clap(long, conflicts_with = "pipelines")]
+    plugins: bool,
+
+    /// Search only pipelines
+    #[clap(long)]
+    pipelines: bool,
+
+    /// Category to filter by
+    #[clap(long)]
+    category: Option<String>,
+
+    /// Author to filter by
+    #[clap(long)]
+    author: Option<String>,
+
+    /// Show only what was created at or after this time, e.g. 2024-01-31 or 2024-01-31T12:00:00Z
+    #[clap(long, value_parser = parse_time)]
+    since: Option<OffsetDateTime>,
+
+    /// Plugin type to filter by. Only applies to plugins, so it must be used with --plugins
+    #[clap(long = "type", value_enum, conflicts_with = "pipelines")]
+    plugin_type: Option<TypeArg>,
+
     /// Name to filter by
     #[clap(default_value = "")]
     name: String,
 }
 
-pub async fn search_runnable(credentials: Credentials, args: SearchArgs) -> Result<()> {
+/// Plugin type, see [PluginType].
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq)]
+enum TypeArg {
+    Fs,
+    Ds,
+    Ts,
+}
+
+impl From<TypeArg> for PluginType {
+    fn from(value: TypeArg) -> Self {
+        match value {
+            TypeArg::Fs => PluginType::Fs,
+            TypeArg::Ds => PluginType::Ds,
+            TypeArg::Ts => PluginType::Ts,
+        }
+    }
+}
+
+/// Width of "plugin/{id}" and "pipeline/{id}" column
+const ID_WIDTH: usize = 22;
+
+/// Width of the plugin type column
+const TYPE_WIDTH: usize = 3;
+
+pub async fn search_runnable(
+    credentials: Credentials,
+    args: SearchArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let (search_plugins, search_pipelines) = what_to_search(&args)?;
     let (client, _, _) = credentials.get_client(NO_ARGS).await?;
     let client_ro = client.into_ro();
+    // there is no space between columns
+    let fit = Fit::to_terminal(args.no_ellipsis, &[ID_WIDTH - 1, TYPE_WIDTH]);
 
-    let plugin_query = client_ro.plugin().name_title_category(&args.name);
-    let plugin_search = plugin_query.search();
-    let plugins = plugin_search.stream().map_ok(format_plugin);
+    let plugin_search = if search_plugins {
+        plugin_query(client_ro.plugin(), &args).search()
+    } else {
+        Search::empty()
+    };
+    let pipeline_search = if search_pipelines {
+        pipeline_query(client_ro.pipeline(), &args).search()
+    } else {
+        Search::empty()
+    };
 
-    let pipeline_query = client_ro.pipeline().name(&args.name);
-    let pipeline_search = pipeline_query.search();
-    let pipelines = pipeline_search.stream().map_ok(format_pipeline);
+    if !output.is_human() {
+        let plugins = plugin_search
+            .stream()
+            .map_ok(|p| Runnable::Plugin(Box::new(p)));
+        let pipelines = pipeline_search
+            .stream()
+            .map_ok(|p| Runnable::Pipeline(Box::new(p)));
+        let stream = tokio_stream::StreamExt::merge(plugins, pipelines);
+        return write_stream(stream, output).await;
+    }
 
+    let plugins = plugin_search.stream().map_ok(|p| format_plugin(p, fit));
+    let pipelines = pipeline_search.stream().map_ok(|p| format_pipeline(p, fit));
     let stream = tokio_stream::StreamExt::merge(plugins, pipelines);
     stream
         .try_for_each(print_string)
@@ -34,23 +118,120 @@ pub async fn search_runnable(credentials: Credentials, args: SearchArgs) -> Resu
         .map_err(eyre::Error::new)
 }
 
-fn format_plugin(p: PluginResponse) -> String {
+/// Whether to search for plugins and whether to search for pipelines.
+fn what_to_search(args: &SearchArgs) -> Result<(bool, bool)> {
+    if args.plugins {
+        Ok((true, false))
+    } else if args.pipelines {
+        Ok((false, true))
+    } else if args.plugin_type.is_some() {
+        bail!("--type only applies to plugins, use it with --plugins to search only plugins")
+    } else {
+        Ok((true, true))
+    }
+}
+
+fn plugin_query(
+    query: PluginSearchBuilder<RoAccess>,
+    args: &SearchArgs,
+) -> PluginSearchBuilder<RoAccess> {
+    let mut query = query.name_title_category(&args.name);
+    if let Some(category) = &args.category {
+        query = query.category(category);
+    }
+    if let Some(author) = &args.author {
+        query = query.authors(author);
+    }
+    if let Some(since) = args.since {
+        query = query.min_creation_date(since);
+    }
+    if let Some(plugin_type) = args.plugin_type {
+        query = query.plugin_type(plugin_type.into());
+    }
+    query
+}
+
+fn pipeline_query(
+    query: PipelineSearchBuilder<RoAccess>,
+    args: &SearchArgs,
+) -> PipelineSearchBuilder<RoAccess> {
+    let mut query = query.name(&args.name);
+    if let Some(category) = &args.category {
+        query = query.category(category);
+    }
+    if let Some(author) = &args.author {
+        query = query.authors(author);
+    }
+    if let Some(since) = args.since {
+        query = query.min_creation_date(since);
+    }
+    query
+}
+
+/// A plugin or pipeline.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Runnable {
+    Plugin(Box<PluginResponse>),
+    Pipeline(Box<PipelineResponse>),
+}
+
+impl Render for Runnable {
+    fn columns(&self) -> Vec<String> {
+        match self {
+            Self::Plugin(p) => p.columns(),
+            Self::Pipeline(p) => p.columns(),
+        }
+    }
+}
+
+fn format_plugin(p: PluginResponse, fit: Fit) -> String {
     let id = format!("{}/{}", "plugin".dimmed(), p.id.0);
+    let fit = fit.reserve(1 + display_width(p.version.as_str()));
     format!(
-        "{:<22}{}{}{}",
+        "{:<22}{:<TYPE_WIDTH$}{}{}{}",
         id.magenta(),
-        p.name,
+        p.plugin_type.as_str(),
+        fit.fit(&sanitize_for_terminal(p.name.as_str())),
         "@".dimmed(),
         p.version.dimmed()
     )
 }
 
-fn format_pipeline(p: PipelineResponse) -> String {
+fn format_pipeline(p: PipelineResponse, fit: Fit) -> String {
     let id = format!("{}/{}", "pipeline".dimmed(), p.id.0);
-    format!("{:<22}{}", id.bright_magenta(), p.name)
+    format!(
+        "{:<22}{:<TYPE_WIDTH$}{}",
+        id.bright_magenta(),
+        "",
+        fit.fit(&sanitize_for_terminal(&p.name))
+    )
 }
 
 fn print_string(s: String) -> Ready<std::result::Result<(), CubeError>> {
     println!("{}", s);
     future::ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(&[], Some((true, true)))]
+    #[case(&["--plugins"], Some((true, false)))]
+    #[case(&["--pipelines"], Some((false, true)))]
+    #[case(&["--category", "MRI", "--author", "FNNDSC"], Some((true, true)))]
+    #[case(&["--plugins", "--type", "ds"], Some((true, false)))]
+    #[case(&["--type", "ds"], None)]
+    fn test_what_to_search(#[case] flags: &[&str], #[case] expected: Option<(bool, bool)>) {
+        let args = SearchArgs::try_parse_from(["search"].iter().chain(flags)).unwrap();
+        assert_eq!(what_to_search(&args).ok(), expected)
+    }
+
+    #[rstest]
+    fn test_type_conflicts_with_pipelines() {
+        assert!(SearchArgs::try_parse_from(["search", "--pipelines", "--type", "fs"]).is_err())
+    }
+}
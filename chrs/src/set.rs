@@ -0,0 +1,113 @@
+//! `chrs set`: change the title of a plugin instance or the name of a feed.
+
+use clap::Subcommand;
+use color_eyre::eyre::{bail, OptionExt, Result};
+use color_eyre::owo_colors::OwoColorize;
+
+use crate::arg::{GivenDataNode, GivenPluginInstanceOrPath};
+use crate::credentials::Credentials;
+use crate::run::{feed_name_is_not_unique, title_is_not_unique, TitleUniqueness};
+use crate::sanitize::sanitize_for_terminal;
+
+#[derive(Subcommand)]
+pub enum SetCommand {
+    /// Set the title of a plugin instance. An empty title clears it.
+    Title {
+        /// Set the title even if another plugin instance of the feed has it
+        #[clap(short, long)]
+        force: bool,
+
+        /// New title
+        title: String,
+
+        /// Plugin instance [default: current plugin instance]
+        plugin_instance: Option<GivenPluginInstanceOrPath>,
+    },
+
+    /// Set the name of a feed
+    FeedName {
+        /// Set the name even if another feed has it
+        #[clap(short, long)]
+        force: bool,
+
+        /// New name
+        name: String,
+
+        /// Feed, or a plugin instance of the feed [default: feed of the current plugin instance]
+        feed: Option<GivenDataNode>,
+    },
+}
+
+/// `chrs set` command
+pub async fn set(credentials: Credentials, command: SetCommand) -> Result<()> {
+    match command {
+        SetCommand::Title {
+            force,
+            title,
+            plugin_instance,
+        } => set_title(credentials, plugin_instance, &title, force).await,
+        SetCommand::FeedName { force, name, feed } => {
+            set_feed_name(credentials, feed, &name, force).await
+        }
+    }
+}
+
+async fn set_title(
+    credentials: Credentials,
+    given: Option<GivenPluginInstanceOrPath>,
+    title: &str,
+    force: bool,
+) -> Result<()> {
+    let (client, old, _) = credentials
+        .get_client(given.as_ref().map(|g| g.as_arg_str()).as_slice())
+        .await?;
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to set the titles of plugin instances.")?;
+    let plinst = given.unwrap_or_default().get_using_rw(&client, old).await?;
+    let is_changed = plinst.object.title != title;
+    if !force
+        && is_changed
+        && !title.is_empty()
+        && title_is_not_unique(&client, plinst.object.id, title).await?
+    {
+        bail!("{}", TitleUniqueness::NotUniqueWithinFeed)
+    }
+    let changed = plinst.set_title(title).await?;
+    print_change(&plinst.object.title, &changed.object.title);
+    Ok(())
+}
+
+async fn set_feed_name(
+    credentials: Credentials,
+    given: Option<GivenDataNode>,
+    name: &str,
+    force: bool,
+) -> Result<()> {
+    let (client, old, _) = credentials
+        .get_client(given.as_ref().map(|g| g.as_arg_str()).as_slice())
+        .await?;
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to set the names of feeds.")?;
+    let given = given
+        .or_else(|| old.map(|id| id.into()))
+        .ok_or_eyre("missing operand")?;
+    let feed = given.into_feed_rw(&client, old).await?;
+    let is_changed = feed.object.name != name;
+    if !force && is_changed && feed_name_is_not_unique(&client, name).await? {
+        bail!("{}", TitleUniqueness::NotUniqueFeedName)
+    }
+    let changed = feed.set_name(name).await?;
+    print_change(&feed.object.name, &changed.object.name);
+    Ok(())
+}
+
+/// Print the old and new values of a title or name.
+fn print_change(old: &str, new: &str) {
+    println!("{} -> {}", quoted(old).dimmed(), quoted(new).bold());
+}
+
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", sanitize_for_terminal(value))
+}
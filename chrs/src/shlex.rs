@@ -1,6 +1,112 @@
-/// Wrapper for [shlex::try_quote] which never fails. NUL characters are replaced.
+/// Quote a string so that it can be copy-pasted into a POSIX shell as a single word.
+///
+/// Strings made up only of [is_safe] characters are returned as-is. Otherwise, the string
+/// is wrapped in single quotes, inside of which the shell does no expansion. Control
+/// characters such as newlines are replaced with a visible escape sequence (e.g. `\n`),
+/// because they would break the command when copy-pasted.
 pub(crate) fn shlex_quote(in_str: &str) -> String {
-    shlex::try_quote(&in_str.replace('\0', "¡NUL!"))
-        .unwrap()
-        .to_string()
+    let visible = escape_control(in_str);
+    if !visible.is_empty() && visible.chars().all(is_safe) {
+        return visible;
+    }
+    format!("'{}'", visible.replace('\'', r"'\''"))
+}
+
+/// Characters which have no special meaning to a shell when unquoted.
+fn is_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(c, '-' | '_' | '.' | '/' | '=' | ':' | ',' | '+' | '@' | '%')
+}
+
+fn escape_control(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    /// Parse a word the way `sh` would, for the subset of syntax produced by [shlex_quote].
+    /// Panics if an unquoted character would be interpreted by the shell.
+    fn sh_unquote(word: &str) -> String {
+        let mut out = String::new();
+        let mut chars = word.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(quoted) => out.push(quoted),
+                        None => panic!("unterminated single quote in {word:?}"),
+                    }
+                },
+                '\\' => out.push(chars.next().expect("trailing backslash")),
+                c if is_safe(c) => out.push(c),
+                c => panic!("unquoted special character {c:?} in {word:?}"),
+            }
+        }
+        out
+    }
+
+    /// Deterministic pseudo-random strings made from characters which are tricky for shells.
+    fn tricky_strings() -> impl Iterator<Item = String> {
+        const ALPHABET: &[char] = &[
+            'a', 'Z', '0', ' ', '\'', '"', '`', '$', '(', ')', '{', '}', '\\', '!', '*', '?', '~',
+            '#', '&', ';', '|', '<', '>', '[', ']', '-', '=', '/', '.', 'é', '😀',
+        ];
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        (0..2000).map(move |_| {
+            let len = (next() % 12) as usize;
+            (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect()
+        })
+    }
+
+    #[rstest]
+    #[case("", "''")]
+    #[case("simple", "simple")]
+    #[case("my-feed_1.0", "my-feed_1.0")]
+    #[case("two words", "'two words'")]
+    #[case("it's", r"'it'\''s'")]
+    #[case("$(rm -rf ~)", "'$(rm -rf ~)'")]
+    #[case("`whoami`", "'`whoami`'")]
+    #[case("line\nbreak", r"'line\nbreak'")]
+    #[case("nul\0byte", r"'nul\u{0}byte'")]
+    fn test_shlex_quote(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(shlex_quote(value), expected)
+    }
+
+    #[rstest]
+    fn test_round_trip() {
+        for value in tricky_strings() {
+            let quoted = shlex_quote(&value);
+            assert_eq!(sh_unquote(&quoted), value, "quoted as {quoted}");
+        }
+    }
+
+    #[rstest]
+    #[case("a\nb; touch pwned")]
+    #[case("\r\t\x1b[31m")]
+    #[case("\u{85}next line")]
+    fn test_control_characters_are_visible(#[case] value: &str) {
+        let quoted = shlex_quote(value);
+        assert!(!quoted.chars().any(char::is_control), "{quoted:?}");
+        assert_eq!(sh_unquote(&quoted), escape_control(value));
+    }
 }
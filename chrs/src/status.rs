@@ -1,4 +1,11 @@
+mod cache;
 pub mod cmd;
 mod feed;
 mod find_branch;
+mod follow;
+mod graph;
 mod print_branch;
+
+pub(crate) use follow::{wait_for_plugin_instance, TimedOut, EXIT_TIMED_OUT};
+pub(crate) use graph::GraphFormat;
+pub(crate) use print_branch::symbol_for;
@@ -0,0 +1,272 @@
+//! On-disk cache of the plugin instances of a feed, so that `chrs status` does not
+//! need to get plugin instances which are finished again.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use chris::errors::CubeError;
+use chris::types::{CubeUrl, PluginType};
+use chris::{FeedResponse, PluginInstanceResponse, PluginInstanceRo};
+
+use crate::login::state::ChrsSessions;
+
+use super::find_branch::{walk_branch, Branch, FetchPrevious, PluginInstanceLike};
+
+/// Plugin instances of a feed which were previously retrieved.
+pub(crate) struct PluginInstanceCache {
+    /// Where the cache is saved. If `None`, caching is disabled.
+    path: Option<PathBuf>,
+    feed: Mutex<CachedFeed>,
+}
+
+/// The content of a cache file.
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct CachedFeed {
+    /// Modification date of the feed when its plugin instances were cached.
+    #[serde(with = "time::serde::rfc3339::option")]
+    modification_date: Option<OffsetDateTime>,
+    /// Plugin instances which are finished, by ID.
+    plugin_instances: BTreeMap<u32, PluginInstanceResponse>,
+}
+
+impl CachedFeed {
+    /// Forget all plugin instances if the feed was modified since they were cached.
+    fn invalidate_if_stale(&mut self, modification_date: OffsetDateTime) {
+        if self.modification_date != Some(modification_date) {
+            self.plugin_instances.clear();
+            self.modification_date = Some(modification_date);
+        }
+    }
+
+    /// Get a plugin instance, only if its cached status is finished.
+    fn get(&self, id: u32) -> Option<&PluginInstanceResponse> {
+        self.plugin_instances
+            .get(&id)
+            .filter(|p| p.status.is_terminal())
+    }
+
+    /// Cache a plugin instance if it is finished.
+    fn insert(&mut self, plinst: &PluginInstanceResponse) {
+        if plinst.status.is_terminal() {
+            self.plugin_instances.insert(plinst.id.0, plinst.clone());
+        } else {
+            self.plugin_instances.remove(&plinst.id.0);
+        }
+    }
+}
+
+impl PluginInstanceCache {
+    /// A cache which never has anything, for `--no-cache`.
+    pub fn disabled() -> Self {
+        Self {
+            path: None,
+            feed: Default::default(),
+        }
+    }
+
+    /// Load the cache of a feed. If the cache cannot be read, it is treated as empty.
+    pub fn load(cube_url: &CubeUrl, feed: &FeedResponse) -> Self {
+        let path = ChrsSessions::config_dir()
+            .ok()
+            .map(|dir| dir.join("cache").join(file_name_of(cube_url, feed.id.0)));
+        let mut cached: CachedFeed = path
+            .as_ref()
+            .and_then(|p| fs_err::read(p).ok())
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        cached.invalidate_if_stale(feed.modification_date);
+        Self {
+            path,
+            feed: Mutex::new(cached),
+        }
+    }
+
+    /// Forget cached plugin instances if the feed was modified, e.g. after it was
+    /// refreshed by `chrs status --follow`.
+    pub fn revalidate(&self, feed: &FeedResponse) {
+        self.feed
+            .lock()
+            .unwrap()
+            .invalidate_if_stale(feed.modification_date)
+    }
+
+    /// Get a finished plugin instance from the cache, using the client of `linked`.
+    fn get(&self, linked: &PluginInstanceRo, id: u32) -> Option<PluginInstanceRo> {
+        self.path.as_ref()?;
+        let feed = self.feed.lock().unwrap();
+        feed.get(id).map(|p| linked.with_object(p.clone()))
+    }
+
+    /// Save the cache, remembering finished plugin instances of `plugin_instances`.
+    pub fn save<'a>(
+        &self,
+        plugin_instances: impl IntoIterator<Item = &'a PluginInstanceResponse>,
+    ) -> Result<()> {
+        let path = if let Some(path) = &self.path {
+            path
+        } else {
+            return Ok(());
+        };
+        let mut feed = self.feed.lock().unwrap();
+        plugin_instances.into_iter().for_each(|p| feed.insert(p));
+        if let Some(parent) = path.parent() {
+            fs_err::create_dir_all(parent)?;
+        }
+        fs_err::write(path, serde_json::to_vec(&*feed)?)?;
+        Ok(())
+    }
+}
+
+/// Name of the cache file of a feed of a CUBE.
+fn file_name_of(cube_url: &CubeUrl, feed_id: u32) -> String {
+    let cube: String = cube_url
+        .as_str()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("status_{}feed_{}.json", cube, feed_id)
+}
+
+/// Walk the branch of `selected` like [walk_branch], getting finished plugin instances
+/// from the cache instead of from CUBE, then save the plugin instances of the branch
+/// to the cache.
+pub(crate) async fn walk_branch_cached(
+    selected: PluginInstanceRo,
+    max_nodes: usize,
+    cache: &PluginInstanceCache,
+) -> Result<Branch<PluginInstanceRo>, CubeError> {
+    let selected = CachedPlinst {
+        plinst: selected,
+        cache,
+    };
+    let branch = walk_branch(selected, max_nodes).await?;
+    let nodes: Vec<_> = branch.nodes.into_iter().map(|n| n.plinst).collect();
+    // the cache is only for speed, so failing to save it is not an error
+    let _ = cache.save(nodes.iter().map(|n| &n.object));
+    Ok(Branch {
+        nodes,
+        complete: branch.complete,
+    })
+}
+
+/// A plugin instance which gets its previous from a [PluginInstanceCache] if possible.
+struct CachedPlinst<'a> {
+    plinst: PluginInstanceRo,
+    cache: &'a PluginInstanceCache,
+}
+
+impl PluginInstanceLike for CachedPlinst<'_> {
+    fn previous(&self) -> Option<u32> {
+        PluginInstanceLike::previous(&self.plinst)
+    }
+
+    fn plugin_type(&self) -> PluginType {
+        PluginInstanceLike::plugin_type(&self.plinst)
+    }
+}
+
+impl FetchPrevious for CachedPlinst<'_> {
+    async fn fetch_previous(&self) -> Result<Option<Self>, CubeError> {
+        let cached = self
+            .previous()
+            .and_then(|id| self.cache.get(&self.plinst, id));
+        let previous = match cached {
+            Some(previous) => Some(previous),
+            None => self.plinst.fetch_previous().await?,
+        };
+        Ok(previous.map(|plinst| Self {
+            plinst,
+            cache: self.cache,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::types::Status;
+    use rstest::*;
+    use time::macros::datetime;
+
+    #[fixture]
+    fn plugin_instances() -> Vec<PluginInstanceResponse> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("cube_chrisproject_org_feed_45_plugininstances_results.json");
+        serde_json::from_str(&fs_err::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn with_status(mut plinst: PluginInstanceResponse, status: Status) -> PluginInstanceResponse {
+        plinst.status = status;
+        plinst
+    }
+
+    #[rstest]
+    fn test_only_finished_are_cached(plugin_instances: Vec<PluginInstanceResponse>) {
+        let mut cached = CachedFeed::default();
+        let finished = with_status(plugin_instances[0].clone(), Status::FinishedWithError);
+        let running = with_status(plugin_instances[1].clone(), Status::Started);
+        cached.insert(&finished);
+        cached.insert(&running);
+        assert!(cached.get(finished.id.0).is_some());
+        assert!(cached.get(running.id.0).is_none());
+    }
+
+    #[rstest]
+    fn test_unfinished_replaces_cached(plugin_instances: Vec<PluginInstanceResponse>) {
+        let mut cached = CachedFeed::default();
+        let plinst = &plugin_instances[0];
+        cached.insert(&with_status(plinst.clone(), Status::FinishedSuccessfully));
+        cached.insert(&with_status(plinst.clone(), Status::Waiting));
+        assert!(cached.get(plinst.id.0).is_none());
+    }
+
+    #[rstest]
+    fn test_cache_is_stale_when_feed_modified(plugin_instances: Vec<PluginInstanceResponse>) {
+        let mut cached = CachedFeed::default();
+        let date = datetime!(2024-03-01 14:06:10 -5);
+        cached.invalidate_if_stale(date);
+        let finished = with_status(plugin_instances[0].clone(), Status::FinishedSuccessfully);
+        cached.insert(&finished);
+
+        cached.invalidate_if_stale(date);
+        assert!(cached.get(finished.id.0).is_some(), "feed was not modified");
+
+        cached.invalidate_if_stale(datetime!(2024-03-01 15:00:00 -5));
+        assert!(cached.get(finished.id.0).is_none(), "feed was modified");
+    }
+
+    #[rstest]
+    fn test_serialization_roundtrip(plugin_instances: Vec<PluginInstanceResponse>) {
+        let mut cached = CachedFeed::default();
+        let date = datetime!(2024-03-01 14:06:10 -5);
+        cached.invalidate_if_stale(date);
+        let finished = with_status(plugin_instances[0].clone(), Status::FinishedSuccessfully);
+        cached.insert(&finished);
+        let json = serde_json::to_vec(&cached).unwrap();
+        let mut loaded: CachedFeed = serde_json::from_slice(&json).unwrap();
+        loaded.invalidate_if_stale(date);
+        assert_eq!(loaded.get(finished.id.0).unwrap().url, finished.url);
+    }
+
+    #[rstest]
+    #[case(
+        "https://cube.example.org/api/v1/",
+        45,
+        "status_https___cube_example_org_api_v1_feed_45.json"
+    )]
+    #[case(
+        "http://localhost:8000/api/v1/",
+        1,
+        "status_http___localhost_8000_api_v1_feed_1.json"
+    )]
+    fn test_file_name_of(#[case] cube_url: &str, #[case] feed_id: u32, #[case] expected: &str) {
+        let cube_url = CubeUrl::try_from(cube_url).unwrap();
+        assert_eq!(file_name_of(&cube_url, feed_id), expected)
+    }
+}
@@ -1,19 +1,38 @@
-use color_eyre::eyre::{OptionExt, Result};
+use std::time::Duration;
 
-use chris::{FeedRo, PluginInstanceRo};
+use color_eyre::eyre::{bail, OptionExt, Result};
+use futures::TryStreamExt;
+
+use chris::{BaseChrisClient, EitherClient, FeedRo, PluginInstanceRo};
 
 use crate::arg::{FeedOrPluginInstance, GivenDataNode};
 use crate::credentials::Credentials;
 use crate::login::UiUrl;
+use crate::output::{OutputFormat, RecordWriter};
 
-use super::feed::only_print_feed_status;
-use super::print_branch::print_branch_status;
+use super::cache::{walk_branch_cached, PluginInstanceCache};
+use super::feed::write_feed_status;
+use super::follow::follow_status;
+use super::graph::{feed_graph, GraphFormat};
+use super::print_branch::write_branch_status;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn status(
     credentials: Credentials,
     given: Option<GivenDataNode>,
     show_execshell: bool,
+    max_nodes: usize,
+    follow: Option<Duration>,
+    no_cache: bool,
+    format: Option<GraphFormat>,
+    output: OutputFormat,
 ) -> Result<()> {
+    if follow.is_some() && !output.is_human() {
+        bail!("--follow can only be used with --output human")
+    }
+    if format.is_some() && !output.is_human() {
+        bail!("--format cannot be used with --output")
+    }
     let (client, old, ui) = credentials
         .get_client(given.as_ref().map(|g| g.as_arg_str()).as_slice())
         .await?;
@@ -27,25 +46,87 @@ pub async fn status(
             (Some(feed), Some(p))
         }
     };
-    print_status(feed, plinst, ui, show_execshell).await
+    if let (Some(format), Some(feed)) = (format, feed.as_ref()) {
+        let plugin_instances = feed.get_plugin_instances().stream().try_collect().await?;
+        print!("{}", feed_graph(plugin_instances).render(format));
+        return Ok(());
+    }
+    let cache = match feed.as_ref() {
+        Some(feed) if !no_cache => PluginInstanceCache::load(client.url(), &feed.object),
+        _ => PluginInstanceCache::disabled(),
+    };
+    if !output.is_human() {
+        return write_status(feed, plinst, max_nodes, &cache, output).await;
+    }
+    if let (Some(interval), Some(feed)) = (follow, feed.as_ref()) {
+        return follow_status(
+            &client,
+            feed,
+            plinst.as_ref(),
+            ui,
+            show_execshell,
+            max_nodes,
+            &cache,
+            interval,
+        )
+        .await;
+    }
+    let status =
+        render_status(feed, plinst, ui, show_execshell, max_nodes, &cache, &client).await?;
+    print!("{}", status);
+    Ok(())
 }
 
-async fn print_status(
+/// Write the branch of `plinst`, or else `feed`, as records.
+async fn write_status(
     feed: Option<FeedRo>,
     plinst: Option<PluginInstanceRo>,
-    ui_url: Option<UiUrl>,
-    show_execshell: bool,
+    max_nodes: usize,
+    cache: &PluginInstanceCache,
+    output: OutputFormat,
 ) -> Result<()> {
+    let records = RecordWriter::stdout(output);
     if let Some(plugin_instance) = plinst {
-        if let Some(feed) = feed {
-            print_branch_status(feed, plugin_instance, ui_url, show_execshell).await
-        } else {
-            let feed = plugin_instance.feed().get().await?;
-            print_branch_status(feed, plugin_instance, ui_url, show_execshell).await
+        let branch = walk_branch_cached(plugin_instance, max_nodes, cache).await?;
+        for node in branch.nodes {
+            records.write(&node.object)?;
         }
     } else if let Some(feed) = feed {
-        only_print_feed_status(&feed, ui_url).await
-    } else {
-        Ok(())
+        records.write(&feed.object)?;
+    }
+    records.finish()?;
+    Ok(())
+}
+
+/// Produce what `chrs status` prints for the branch of `plinst`, or else `feed`.
+pub(super) async fn render_status(
+    feed: Option<FeedRo>,
+    plinst: Option<PluginInstanceRo>,
+    ui_url: Option<UiUrl>,
+    show_execshell: bool,
+    max_nodes: usize,
+    cache: &PluginInstanceCache,
+    client: &EitherClient,
+) -> Result<String> {
+    let mut out = String::new();
+    if let Some(plugin_instance) = plinst {
+        let feed = match feed {
+            Some(feed) => feed,
+            None => plugin_instance.feed().get().await?,
+        };
+        write_branch_status(
+            &mut out,
+            feed,
+            plugin_instance,
+            ui_url,
+            show_execshell,
+            max_nodes,
+            cache,
+            client,
+        )
+        .await?;
+    } else if let Some(feed) = feed {
+        write_feed_status(&mut out, &feed, ui_url).await?;
     }
+    Ok(out)
 }
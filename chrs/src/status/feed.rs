@@ -1,20 +1,23 @@
 use crate::login::UiUrl;
+use crate::sanitize::sanitize_for_terminal;
 use crate::unicode;
 use chris::{FeedResponse, FeedRo};
 use color_eyre::owo_colors::OwoColorize;
 use dialoguer::console::Term;
-use std::fmt::Display;
+use std::fmt::{Display, Write};
 use time::format_description::well_known::Rfc2822;
 
-pub async fn only_print_feed_status(
+/// Write the status of a feed to `out`.
+pub async fn write_feed_status(
+    out: &mut String,
     feed: &FeedRo,
     ui_url: Option<UiUrl>,
 ) -> color_eyre::Result<()> {
     let symbol = feed_symbol_for(&feed.object);
     let name = if feed.object.name.is_empty() {
-        "(no name)"
+        "(no name)".into()
     } else {
-        feed.object.name.as_str()
+        sanitize_for_terminal(&feed.object.name)
     };
 
     let (styled_name, styled_id) = if feed.object.has_errored_job() {
@@ -30,9 +33,9 @@ pub async fn only_print_feed_status(
     };
 
     let id_part = format!("(feed/{})", styled_id);
-    println!("{} {}  {}", symbol, styled_name, id_part.dimmed());
+    writeln!(out, "{} {}  {}", symbol, styled_name, id_part.dimmed())?;
     if let Some(ui) = ui_url {
-        println!("  {}", ui.feed_url_of(&feed.object))
+        writeln!(out, "  {}", ui.feed_url_of(&feed.object))?;
     }
     let dim_lines = [
         "".to_string(),
@@ -57,15 +60,15 @@ pub async fn only_print_feed_status(
     let bar = "  |".dimmed();
 
     for dim_line in dim_lines {
-        println!("{} {}", &bar, dim_line.dimmed())
+        writeln!(out, "{} {}", &bar, dim_line.dimmed())?;
     }
 
     let note = feed.note().get().await?;
     if !note.is_empty() {
         let term_cols = std::cmp::min(Term::stdout().size().1, 120) as usize;
-        println!("{}", &bar);
+        writeln!(out, "{}", &bar)?;
         for line in textwrap::wrap(note.object.content.as_str(), term_cols) {
-            println!("{} {}", &bar, line)
+            writeln!(out, "{} {}", &bar, sanitize_for_terminal(&line))?;
         }
     }
     Ok(())
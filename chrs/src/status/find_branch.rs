@@ -1,6 +1,15 @@
+use chris::errors::CubeError;
 use chris::types::PluginType;
 use chris::{PluginInstanceResponse, PluginInstanceRo};
 
+/// A branch of a feed, from its root (or a ts-type plugin instance) to a selected leaf.
+pub(crate) struct Branch<T> {
+    /// Plugin instances of the branch, root-most first.
+    pub nodes: Vec<T>,
+    /// Whether `nodes` is the whole branch, i.e. whether the walk stopped at the root.
+    pub complete: bool,
+}
+
 /// For a feed like this:
 /// ```text
 ///                 1
@@ -10,36 +19,36 @@ use chris::{PluginInstanceResponse, PluginInstanceRo};
 ///                 4   5
 /// ```
 ///
-/// Result: `find_branch_to(4, [1, 2, 3, 4, 5]) -> [1, 3, 4]`
-pub(crate) fn find_branch_to<T: PluginInstanceLike>(id: u32, all: &[T]) -> Option<Vec<&T>> {
-    all.iter()
-        .find(|p| p.id() == id)
-        .map(|leaf| loop_up_to_root(leaf, all))
-}
-
-fn loop_up_to_root<'a, T: PluginInstanceLike>(leaf: &'a T, all: &'a [T]) -> Vec<&'a T> {
-    // I would've preferred recursion, but Rust isn't great for that...
-    let mut branch = Vec::with_capacity(all.len());
-    let mut head = leaf;
-    branch.push(leaf);
-    while let Some(previous_id) = head.previous() {
-        if let Some(previous) = all.iter().find(|p| p.id() == previous_id) {
-            head = previous;
-            branch.push(previous);
-            if previous.is_ts() {
-                break;
-            }
+/// Result: `walk_branch(4, 0) -> [1, 3, 4]`
+///
+/// At most `max_nodes` plugin instances are retrieved, unless `max_nodes` is 0.
+pub(crate) async fn walk_branch<T: FetchPrevious>(
+    selected: T,
+    max_nodes: usize,
+) -> Result<Branch<T>, CubeError> {
+    let mut nodes = vec![selected];
+    let complete = loop {
+        let head = nodes.last().unwrap();
+        if nodes.len() > 1 && head.is_ts() {
+            break true;
+        }
+        if head.previous().is_none() {
+            break true;
+        }
+        if max_nodes > 0 && nodes.len() >= max_nodes {
+            break false;
+        }
+        if let Some(previous) = head.fetch_previous().await? {
+            nodes.push(previous);
         } else {
-            break;
+            break true;
         }
-    }
-    branch.reverse();
-    branch
+    };
+    nodes.reverse();
+    Ok(Branch { nodes, complete })
 }
 
 pub(crate) trait PluginInstanceLike {
-    fn id(&self) -> u32;
-
     fn previous(&self) -> Option<u32>;
 
     fn plugin_type(&self) -> PluginType;
@@ -49,11 +58,22 @@ pub(crate) trait PluginInstanceLike {
     }
 }
 
-impl PluginInstanceLike for PluginInstanceRo {
-    fn id(&self) -> u32 {
-        *self.object.id
+/// A [PluginInstanceLike] which can retrieve its previous plugin instance.
+pub(crate) trait FetchPrevious: PluginInstanceLike + Sized {
+    async fn fetch_previous(&self) -> Result<Option<Self>, CubeError>;
+}
+
+impl FetchPrevious for PluginInstanceRo {
+    async fn fetch_previous(&self) -> Result<Option<Self>, CubeError> {
+        if let Some(previous) = self.previous() {
+            previous.get().await.map(Some)
+        } else {
+            Ok(None)
+        }
     }
+}
 
+impl PluginInstanceLike for PluginInstanceRo {
     fn previous(&self) -> Option<u32> {
         self.object.previous_id.map(|p| p.0)
     }
@@ -64,10 +84,6 @@ impl PluginInstanceLike for PluginInstanceRo {
 }
 
 impl PluginInstanceLike for PluginInstanceResponse {
-    fn id(&self) -> u32 {
-        *self.id
-    }
-
     fn previous(&self) -> Option<u32> {
         self.previous_id.map(|p| p.0)
     }
@@ -79,37 +95,103 @@ impl PluginInstanceLike for PluginInstanceResponse {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
-
     use rstest::*;
 
-    use chris::PluginInstanceResponse;
+    use chris::testing::MockCube;
+    use chris::types::PluginInstanceId;
+    use chris::{BaseChrisClient, PluginResponse};
+
+    use super::*;
+
+    /// A feed of plugin instances, given as pairs of ID and previous ID.
+    /// Plugin instances must come after their previous.
+    async fn feed_of(plugin_instances: &[(u32, Option<u32>)]) -> MockCube {
+        let mock = MockCube::start().await;
+        let fs = PluginResponse {
+            plugin_type: PluginType::Fs,
+            ..mock.plugin(1, "pl-dircopy", "2.1.1")
+        };
+        let ds = PluginResponse {
+            plugin_type: PluginType::Ds,
+            ..mock.plugin(2, "pl-simpledsapp", "2.1.0")
+        };
+        let feed = mock.feed(1, "Long Study");
+        let mut added: Vec<PluginInstanceResponse> = Vec::new();
+        for &(id, previous) in plugin_instances {
+            let previous = previous.map(|p| added.iter().find(|a| a.id.0 == p).unwrap());
+            let plugin = if previous.is_some() { &ds } else { &fs };
+            let plinst = mock.plugin_instance(id, plugin, &feed, previous);
+            mock.add_plugin_instance(plinst.clone());
+            added.push(plinst);
+        }
+        mock.add_plugin(fs);
+        mock.add_plugin(ds);
+        mock.add_feed(feed);
+        mock
+    }
 
-    use crate::status::find_branch::find_branch_to;
+    /// A feed which is a chain of plugin instances `1 <- 2 <- ... <- n`.
+    async fn chain(n: u32) -> MockCube {
+        let plugin_instances: Vec<_> = (1..=n).map(|i| (i, (i > 1).then(|| i - 1))).collect();
+        feed_of(&plugin_instances).await
+    }
 
-    #[fixture]
-    fn test_data_dir() -> PathBuf {
-        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        d.push("test_data");
-        d
+    /// Walk the branch of the plugin instance `id`. Returns the IDs of the branch,
+    /// whether it is complete, and the number of requests made by the walk.
+    async fn walk_from(mock: &MockCube, id: u32, max_nodes: usize) -> (Vec<u32>, bool, usize) {
+        let client = mock.anon_client().await;
+        let selected = client
+            .get_plugin_instance(PluginInstanceId(id))
+            .await
+            .unwrap();
+        let before = mock.requests().len();
+        let branch = walk_branch(selected, max_nodes).await.unwrap();
+        let ids = branch.nodes.iter().map(|p| p.object.id.0).collect();
+        (ids, branch.complete, mock.requests().len() - before)
     }
 
-    #[fixture]
-    fn json_string(test_data_dir: PathBuf) -> String {
-        let path = test_data_dir.join("cube_chrisproject_org_feed_45_plugininstances_results.json");
-        fs_err::read_to_string(path).unwrap()
+    #[rstest]
+    #[tokio::test]
+    async fn test_walk_branch() {
+        let mock = feed_of(&[
+            (1, None),
+            (2, Some(1)),
+            (3, Some(1)),
+            (4, Some(3)),
+            (5, Some(3)),
+        ])
+        .await;
+        let (actual, complete, requests) = walk_from(&mock, 4, 0).await;
+        assert_eq!(actual, vec![1, 3, 4]);
+        assert!(complete);
+        assert_eq!(requests, 2);
     }
 
-    #[fixture]
-    fn plugin_instances(json_string: String) -> Vec<PluginInstanceResponse> {
-        serde_json::from_str(&json_string).unwrap()
+    #[rstest]
+    #[case(0, (1..=50).collect(), true, 49)]
+    #[case(20, (31..=50).collect(), false, 19)]
+    #[case(1, vec![50], false, 0)]
+    #[tokio::test]
+    async fn test_walk_branch_of_large_feed(
+        #[case] max_nodes: usize,
+        #[case] expected: Vec<u32>,
+        #[case] expected_complete: bool,
+        #[case] expected_requests: usize,
+    ) {
+        let mock = chain(50).await;
+        let (actual, complete, requests) = walk_from(&mock, 50, max_nodes).await;
+        assert_eq!(actual, expected);
+        assert_eq!(complete, expected_complete);
+        assert_eq!(requests, expected_requests);
     }
 
     #[rstest]
-    fn test_find_branch_to(plugin_instances: Vec<PluginInstanceResponse>) {
-        let expected = vec![214, 215, 219, 220];
-        let actual_branch = find_branch_to(220, &plugin_instances).unwrap();
-        let actual: Vec<_> = actual_branch.into_iter().map(|p| p.id.0).collect();
-        assert_eq!(actual, expected)
+    #[tokio::test]
+    async fn test_walk_branch_stops_at_root_within_cap() {
+        let mock = chain(5).await;
+        let (actual, complete, requests) = walk_from(&mock, 5, 5).await;
+        assert_eq!(actual, vec![1, 2, 3, 4, 5]);
+        assert!(complete, "branch is complete because the root was reached");
+        assert_eq!(requests, 4);
     }
 }
@@ -0,0 +1,252 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{bail, Result};
+use color_eyre::owo_colors::OwoColorize;
+use dialoguer::console::{measure_text_width, Term};
+use indicatif::{HumanDuration, ProgressBar, ProgressStyle};
+
+use chris::types::PluginInstanceId;
+use chris::{
+    Access, EitherClient, FeedResponse, FeedRo, LinkedModel, PluginInstanceResponse,
+    PluginInstanceRo,
+};
+
+use crate::login::UiUrl;
+
+use super::cache::PluginInstanceCache;
+use super::cmd::render_status;
+
+/// Longest time to wait before trying again after CUBE could not be reached.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Time between the first checks of a plugin instance's status in [wait_for_plugin_instance].
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest time between checks of a plugin instance's status in [wait_for_plugin_instance].
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Exit code of `chrs` after giving up on waiting for a plugin instance, same as `timeout(1)`.
+pub const EXIT_TIMED_OUT: i32 = 124;
+
+/// Error of waiting for a plugin instance which did not finish in time.
+#[derive(thiserror::Error, Debug)]
+#[error("Timed out after {} waiting for plugininstance/{} to finish", HumanDuration(*.elapsed), .id.0)]
+pub struct TimedOut {
+    pub id: PluginInstanceId,
+    pub elapsed: Duration,
+}
+
+/// Print the status of a feed every `interval`, redrawing it in place, until
+/// none of its plugin instances are waiting or running.
+///
+/// Returns an error if any plugin instance of the feed finished with an error.
+#[allow(clippy::too_many_arguments)]
+pub async fn follow_status(
+    client: &EitherClient,
+    feed: &FeedRo,
+    plinst: Option<&PluginInstanceRo>,
+    ui_url: Option<UiUrl>,
+    show_execshell: bool,
+    max_nodes: usize,
+    cache: &PluginInstanceCache,
+    interval: Duration,
+) -> Result<()> {
+    let started = Instant::now();
+    let term = Term::stdout();
+    let mut drawn = 0;
+    let mut last_status = String::new();
+    let mut failures = 0;
+    let outcome = loop {
+        let refreshed = async {
+            let feed = feed.refresh().await?;
+            let plinst = match plinst {
+                Some(p) => Some(p.refresh().await?),
+                None => None,
+            };
+            let outcome = Outcome::of(&feed.object);
+            cache.revalidate(&feed.object);
+            let status = render_status(
+                Some(feed),
+                plinst,
+                ui_url.clone(),
+                show_execshell,
+                max_nodes,
+                cache,
+                client,
+            )
+            .await?;
+            Ok::<_, color_eyre::eyre::Error>((status, outcome))
+        };
+        let (frame, outcome, delay) = match refreshed.await {
+            Ok((status, outcome)) => {
+                failures = 0;
+                last_status = status;
+                (last_status.clone(), outcome, interval)
+            }
+            Err(e) => {
+                failures += 1;
+                let delay = backoff(interval, failures, MAX_BACKOFF);
+                let warning = format!(
+                    "{}{} could not get status: {} (retrying in {})\n",
+                    last_status,
+                    "warning:".yellow(),
+                    e.to_string().lines().next().unwrap_or_default(),
+                    HumanDuration(delay)
+                );
+                (warning, Outcome::Running, delay)
+            }
+        };
+        if term.is_term() {
+            term.clear_last_lines(drawn)?;
+        }
+        print!("{}", frame);
+        drawn = visual_lines(&frame, term.size().1 as usize);
+        if outcome != Outcome::Running {
+            break outcome;
+        }
+        tokio::time::sleep(delay).await;
+    };
+    eprintln!("Elapsed time: {}", HumanDuration(started.elapsed()));
+    if let Outcome::Failed(errors) = outcome {
+        bail!(
+            "{} plugin instance{} of feed/{} finished with an error",
+            errors,
+            if errors == 1 { "" } else { "s" },
+            feed.object.id.0
+        )
+    }
+    Ok(())
+}
+
+/// Whether a feed is done.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Outcome {
+    /// Some plugin instances are waiting or running.
+    Running,
+    /// All plugin instances finished without error.
+    Success,
+    /// All plugin instances are done, and this many of them finished with an error.
+    Failed(u32),
+}
+
+impl Outcome {
+    fn of(feed: &FeedResponse) -> Self {
+        Self::from_counts(feed.unfinished_jobs(), feed.errored_jobs)
+    }
+
+    fn from_counts(unfinished: u32, errored: u32) -> Self {
+        if unfinished > 0 {
+            Self::Running
+        } else if errored > 0 {
+            Self::Failed(errored)
+        } else {
+            Self::Success
+        }
+    }
+}
+
+/// Check the status of a plugin instance until it is finished, showing a spinner
+/// with the elapsed time and its current status. Checks are done with exponential
+/// backoff, from [POLL_INTERVAL] up to [MAX_POLL_INTERVAL] apart.
+///
+/// Fails with [TimedOut] if the plugin instance is not finished after `timeout`.
+/// Errors getting the status are printed as warnings and retried.
+pub async fn wait_for_plugin_instance<A: Access>(
+    mut plinst: LinkedModel<PluginInstanceResponse, A>,
+    timeout: Option<Duration>,
+) -> Result<LinkedModel<PluginInstanceResponse, A>> {
+    let started = Instant::now();
+    let spinner = ProgressBar::new_spinner()
+        .with_style(ProgressStyle::with_template("{spinner} [{elapsed_precise}] {msg}").unwrap());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    let mut polls = 0;
+    while !plinst.object.status.is_terminal() {
+        spinner.set_message(format!(
+            "plugininstance/{} {}",
+            plinst.object.id.0,
+            plinst.object.status.as_str()
+        ));
+        let delay = backoff(POLL_INTERVAL, polls, MAX_POLL_INTERVAL);
+        if let Some(timeout) = timeout {
+            let remaining = timeout.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                spinner.finish_and_clear();
+                return Err(TimedOut {
+                    id: plinst.object.id,
+                    elapsed: started.elapsed(),
+                }
+                .into());
+            }
+            tokio::time::sleep(delay.min(remaining)).await;
+        } else {
+            tokio::time::sleep(delay).await;
+        }
+        polls += 1;
+        match plinst.refresh().await {
+            Ok(refreshed) => plinst = refreshed,
+            Err(e) => {
+                spinner.suspend(|| eprintln!("{} could not get status: {}", "warning:".yellow(), e))
+            }
+        }
+    }
+    spinner.finish_and_clear();
+    Ok(plinst)
+}
+
+/// How long to wait after `failures` consecutive attempts: `interval`
+/// doubled for each attempt, up to `max` (or `interval` if it is longer).
+fn backoff(interval: Duration, failures: u32, max: Duration) -> Duration {
+    let factor = 2u32.saturating_pow(failures.min(16));
+    interval.saturating_mul(factor).min(max.max(interval))
+}
+
+/// Number of rows `text` takes up in a terminal which is `columns` wide.
+fn visual_lines(text: &str, columns: usize) -> usize {
+    text.lines()
+        .map(|line| measure_text_width(line).max(1).div_ceil(columns.max(1)))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(3, 0, Outcome::Running)]
+    #[case(1, 2, Outcome::Running)]
+    #[case(0, 0, Outcome::Success)]
+    #[case(0, 2, Outcome::Failed(2))]
+    fn test_outcome(#[case] unfinished: u32, #[case] errored: u32, #[case] expected: Outcome) {
+        assert_eq!(Outcome::from_counts(unfinished, errored), expected)
+    }
+
+    #[rstest]
+    #[case(10, 1, 20)]
+    #[case(10, 3, 80)]
+    #[case(10, 5, 300)]
+    #[case(10, 1000, 300)]
+    #[case(600, 1, 600)]
+    fn test_backoff(#[case] interval: u64, #[case] failures: u32, #[case] expected: u64) {
+        let actual = backoff(Duration::from_secs(interval), failures, MAX_BACKOFF);
+        assert_eq!(actual, Duration::from_secs(expected))
+    }
+
+    #[rstest]
+    fn test_poll_intervals() {
+        let actual: Vec<_> = (0..8)
+            .map(|polls| backoff(POLL_INTERVAL, polls, MAX_POLL_INTERVAL).as_secs())
+            .collect();
+        assert_eq!(actual, [1, 2, 4, 8, 16, 30, 30, 30])
+    }
+
+    #[rstest]
+    #[case("", 80, 0)]
+    #[case("a\nb\n", 80, 2)]
+    #[case("\n\n", 80, 2)]
+    #[case("abcdef\n", 4, 2)]
+    #[case("\x1b[1mabcd\x1b[0m\n", 4, 1)]
+    fn test_visual_lines(#[case] text: &str, #[case] columns: usize, #[case] expected: usize) {
+        assert_eq!(visual_lines(text, columns), expected)
+    }
+}
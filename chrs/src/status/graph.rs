@@ -0,0 +1,306 @@
+//! The plugin instances of a feed as a graph, for `chrs status --format`.
+
+use std::fmt::Write;
+
+use chris::types::{SimplifiedStatus, Status};
+use chris::PluginInstanceResponse;
+
+/// Graph description language of `chrs status --format`.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+}
+
+/// A plugin instance of a feed.
+#[derive(Debug, PartialEq)]
+pub(super) struct GraphNode {
+    pub id: u32,
+    /// Title of the plugin instance, or its plugin name if it has no title
+    pub name: String,
+    pub status: Status,
+}
+
+impl GraphNode {
+    fn is_error(&self) -> bool {
+        self.status.is_error()
+    }
+
+    fn is_running(&self) -> bool {
+        self.status.simplify() == SimplifiedStatus::Running
+    }
+}
+
+/// The plugin instances of a feed, where each plugin instance is connected
+/// to the plugin instance before it.
+#[derive(Debug, PartialEq)]
+pub(super) struct FeedGraph {
+    /// Nodes in order of ID
+    pub nodes: Vec<GraphNode>,
+    /// Pairs of IDs of a previous plugin instance and a plugin instance after it
+    pub edges: Vec<(u32, u32)>,
+}
+
+/// Create the graph of the plugin instances of a feed. Plugin instances whose
+/// previous plugin instance is not given are roots.
+pub(super) fn feed_graph(mut plugin_instances: Vec<PluginInstanceResponse>) -> FeedGraph {
+    plugin_instances.sort_by_key(|p| p.id.0);
+    let is_given = |id: u32| {
+        plugin_instances
+            .binary_search_by_key(&id, |p| p.id.0)
+            .is_ok()
+    };
+    let edges = plugin_instances
+        .iter()
+        .filter_map(|p| p.previous_id.map(|prev| (prev.0, p.id.0)))
+        .filter(|(prev, _)| is_given(*prev))
+        .collect();
+    let nodes = plugin_instances
+        .into_iter()
+        .map(|p| GraphNode {
+            id: p.id.0,
+            name: if p.title.is_empty() {
+                p.plugin_name.to_string()
+            } else {
+                p.title
+            },
+            status: p.status,
+        })
+        .collect();
+    FeedGraph { nodes, edges }
+}
+
+impl FeedGraph {
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    /// Render as Graphviz DOT.
+    fn to_dot(&self) -> String {
+        let mut out = String::from("digraph feed {\n  node [shape=box];\n");
+        for node in &self.nodes {
+            let label = format!(
+                "{} ({})\\n{}",
+                escape_dot(&node.name),
+                node.id,
+                node.status.as_str()
+            );
+            let style = if node.is_error() {
+                " color=red fontcolor=red"
+            } else if node.is_running() {
+                " style=dashed"
+            } else {
+                ""
+            };
+            writeln!(out, "  n{} [label=\"{}\"{}];", node.id, label, style).unwrap();
+        }
+        for (previous, next) in &self.edges {
+            writeln!(out, "  n{} -> n{};", previous, next).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render as a Mermaid flowchart.
+    fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart TD\n");
+        for node in &self.nodes {
+            writeln!(
+                out,
+                "  n{}[\"{} ({})<br>{}\"]",
+                node.id,
+                escape_mermaid(&node.name),
+                node.id,
+                node.status.as_str()
+            )
+            .unwrap();
+        }
+        for (previous, next) in &self.edges {
+            writeln!(out, "  n{} --> n{}", previous, next).unwrap();
+        }
+        let classes = [
+            (
+                "error",
+                "stroke:red,color:red",
+                GraphNode::is_error as fn(&_) -> _,
+            ),
+            ("running", "stroke-dasharray:5 5", GraphNode::is_running),
+        ];
+        for (class, style, predicate) in classes {
+            let ids: Vec<_> = self
+                .nodes
+                .iter()
+                .filter(|n| predicate(n))
+                .map(|n| format!("n{}", n.id))
+                .collect();
+            if !ids.is_empty() {
+                writeln!(out, "  classDef {} {}", class, style).unwrap();
+                writeln!(out, "  class {} {}", ids.join(","), class).unwrap();
+            }
+        }
+        out
+    }
+}
+
+/// Escape text for a quoted DOT string. Control characters are replaced by spaces.
+fn escape_dot(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            c if c.is_control() => " ".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Escape text for a quoted Mermaid label using entity codes. Control characters
+/// are replaced by spaces.
+fn escape_mermaid(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '"' => "#quot;".to_string(),
+            '#' => "#35;".to_string(),
+            '<' => "#lt;".to_string(),
+            '>' => "#gt;".to_string(),
+            c if c.is_control() => " ".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use chris::types::PluginInstanceId;
+    use rstest::*;
+
+    /// Plugin instances of pl-simpledsapp given as (id, previous_id, title, status).
+    async fn plinsts(specs: &[(u32, Option<u32>, &str, Status)]) -> Vec<PluginInstanceResponse> {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(1, "pl-simpledsapp", "2.1.0");
+        let feed = mock.feed(1, "Graph");
+        specs
+            .iter()
+            .map(|(id, previous_id, title, status)| PluginInstanceResponse {
+                previous_id: previous_id.map(PluginInstanceId),
+                title: title.to_string(),
+                status: status.clone(),
+                ..mock.plugin_instance(*id, &plugin, &feed, None)
+            })
+            .collect()
+    }
+
+    fn node(id: u32, name: &str, status: Status) -> GraphNode {
+        GraphNode {
+            id,
+            name: name.to_string(),
+            status,
+        }
+    }
+
+    #[fixture]
+    async fn branched() -> FeedGraph {
+        feed_graph(
+            plinsts(&[
+                (4, Some(2), "left", Status::FinishedWithError),
+                (2, Some(1), "", Status::FinishedSuccessfully),
+                (1, None, "root", Status::FinishedSuccessfully),
+                (3, Some(2), "right", Status::Started),
+            ])
+            .await,
+        )
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_branched_graph(#[future] branched: FeedGraph) {
+        let expected = FeedGraph {
+            nodes: vec![
+                node(1, "root", Status::FinishedSuccessfully),
+                node(2, "pl-simpledsapp", Status::FinishedSuccessfully),
+                node(3, "right", Status::Started),
+                node(4, "left", Status::FinishedWithError),
+            ],
+            edges: vec![(1, 2), (2, 3), (2, 4)],
+        };
+        assert_eq!(branched.await, expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_chain_graph() {
+        let actual = feed_graph(
+            plinsts(&[
+                (7, None, "a", Status::FinishedSuccessfully),
+                (8, Some(7), "b", Status::FinishedSuccessfully),
+                (9, Some(8), "c", Status::Waiting),
+            ])
+            .await,
+        );
+        assert_eq!(actual.edges, vec![(7, 8), (8, 9)]);
+        assert_eq!(
+            actual.nodes.iter().map(|n| n.id).collect::<Vec<_>>(),
+            vec![7, 8, 9]
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_previous_not_given_is_root() {
+        let actual = feed_graph(plinsts(&[(5, Some(4), "", Status::FinishedSuccessfully)]).await);
+        assert!(actual.edges.is_empty())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_dot(#[future] branched: FeedGraph) {
+        let branched = branched.await;
+        let expected = r#"digraph feed {
+  node [shape=box];
+  n1 [label="root (1)\nfinishedSuccessfully"];
+  n2 [label="pl-simpledsapp (2)\nfinishedSuccessfully"];
+  n3 [label="right (3)\nstarted" style=dashed];
+  n4 [label="left (4)\nfinishedWithError" color=red fontcolor=red];
+  n1 -> n2;
+  n2 -> n3;
+  n2 -> n4;
+}
+"#;
+        assert_eq!(branched.render(GraphFormat::Dot), expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_mermaid(#[future] branched: FeedGraph) {
+        let branched = branched.await;
+        let expected = r#"flowchart TD
+  n1["root (1)<br>finishedSuccessfully"]
+  n2["pl-simpledsapp (2)<br>finishedSuccessfully"]
+  n3["right (3)<br>started"]
+  n4["left (4)<br>finishedWithError"]
+  n1 --> n2
+  n2 --> n3
+  n2 --> n4
+  classDef error stroke:red,color:red
+  class n4 error
+  classDef running stroke-dasharray:5 5
+  class n3 running
+"#;
+        assert_eq!(branched.render(GraphFormat::Mermaid), expected)
+    }
+
+    #[rstest]
+    #[case("say \"hi\"\n", r#"say \"hi\" "#, "say #quot;hi#quot; ")]
+    #[case(r"a\b <c> #1", r"a\\b <c> #1", r"a\b #lt;c#gt; #35;1")]
+    fn test_escape(#[case] text: &str, #[case] dot: &str, #[case] mermaid: &str) {
+        assert_eq!(escape_dot(text), dot);
+        assert_eq!(escape_mermaid(text), mermaid);
+    }
+}
@@ -1,71 +1,322 @@
-use std::fmt::Display;
+use std::collections::HashSet;
+use std::fmt::{Display, Write};
 
-use color_eyre::eyre;
-use color_eyre::eyre::{bail, eyre, Result};
+use color_eyre::eyre::Result;
 use color_eyre::owo_colors::OwoColorize;
 use dialoguer::console::Term;
 use futures::TryStreamExt;
-use itertools::Itertools;
 use tokio::try_join;
 
 use chris::errors::CubeError;
-use chris::types::{PluginParameterAction, PluginParameterValue, SimplifiedStatus};
-use chris::{FeedRo, PluginInstanceRo, PluginParameter, PluginRo};
+use chris::types::{
+    PluginInstanceId, PluginParameterAction, PluginParameterValue, SimplifiedStatus, Status,
+};
+use chris::{
+    BaseChrisClient, ChrisClient, EitherClient, FeedResponse, FeedRo, PluginInstanceResponse,
+    PluginInstanceRo, PluginParameter, PluginRo,
+};
 
 use crate::login::UiUrl;
+use crate::sanitize::sanitize_for_terminal;
 use crate::shlex::shlex_quote;
 use crate::unicode;
 
-use super::feed::only_print_feed_status;
-use super::find_branch::find_branch_to;
+use super::cache::{walk_branch_cached, PluginInstanceCache};
+use super::feed::write_feed_status;
+use super::find_branch::Branch;
 
-pub async fn print_branch_status(
+/// Write the status of a feed and the branch of `selected` to `out`.
+///
+/// At most `max_nodes` plugin instances of the branch (0 means no limit) are shown after
+/// its root. The plugin instances between them are summarized in one line. Errored plugin
+/// instances elsewhere in the feed, including those which were not shown, are listed after
+/// the branch if `client` is logged in.
+#[allow(clippy::too_many_arguments)]
+pub async fn write_branch_status(
+    out: &mut String,
     feed: FeedRo,
     selected: PluginInstanceRo,
     ui_url: Option<UiUrl>,
     show_execshell: bool,
+    max_nodes: usize,
+    cache: &PluginInstanceCache,
+    client: &EitherClient,
 ) -> Result<()> {
-    only_print_feed_status(&feed, ui_url).await?;
-    let all_plinst = get_all_plugin_instances(&feed).await?;
-    let branch = find_branch_to(*selected.object.id, &all_plinst).ok_or_else(|| {
-        eyre!(
-            "plugininstance/{} not found in feed, which contains plugin instances {}",
-            *selected.object.id,
-            all_plinst.iter().map(|p| p.object.id.0).join(",")
-        )
-    })?;
+    write_feed_status(out, &feed, ui_url).await?;
+    let selected_id = selected.object.id;
+    let (branch, errored) = try_join!(
+        walk_branch_cached(selected, max_nodes, cache),
+        find_errored(client.logged_in_ref(), &feed.object)
+    )?;
+    let root = if branch.complete {
+        None
+    } else {
+        root_of_branch(client, &branch.nodes[0], &errored).await?
+    };
 
-    println!("\n{}", unicode::HORIZONTAL_BAR.repeat(40).dimmed());
+    writeln!(out, "\n{}", unicode::HORIZONTAL_BAR.repeat(40).dimmed())?;
+    write_branch(out, root.as_ref(), &branch, selected_id, show_execshell).await?;
 
+    let mut shown: Vec<_> = branch.nodes.iter().map(|p| &p.object).collect();
+    shown.extend(root.as_ref().map(|(root, _)| &root.object));
+    let hidden = root.as_ref().map(|(_, hidden)| hidden);
+    if let Some(rest) = RestOfFeed::summarize(&feed.object, &shown, hidden) {
+        writeln!(out, "\n{}", rest.to_string().dimmed())?;
+    }
+    let shown_ids: HashSet<_> = shown.iter().map(|p| p.id).collect();
+    let mut not_shown = errored.iter().filter(|p| !shown_ids.contains(&p.id));
+    if let Some(first) = not_shown.next() {
+        writeln!(out)?;
+        for plinst in std::iter::once(first).chain(not_shown) {
+            let id_part = format!("(plugininstance/{})", plinst.id.0.cyan());
+            writeln!(
+                out,
+                "{} {}  {}",
+                symbol_for(plinst),
+                title_of(plinst, false),
+                id_part.dimmed()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the plugin instances of a `branch`, after its `root` if the branch is incomplete.
+async fn write_branch(
+    out: &mut String,
+    root: Option<&(PluginInstanceRo, Hidden)>,
+    branch: &Branch<PluginInstanceRo>,
+    selected_id: PluginInstanceId,
+    show_execshell: bool,
+) -> Result<()> {
     let term_cols = std::cmp::min(Term::stdout().size().1, 120) as usize;
-    let branch_len = branch.len();
-    for (i, plinst) in branch.into_iter().enumerate() {
-        let is_current = plinst.object.id == selected.object.id;
-        let has_next = i + 1 < branch_len;
-        let id_part = format!("(plugininstance/{})", plinst.object.id.0.cyan());
-        println!(
-            "{} {}  {}",
-            symbol_for(plinst),
-            title_of(plinst, is_current),
-            id_part.dimmed()
+    if let Some((root, hidden)) = root {
+        write_node(out, root, false, true, show_execshell, term_cols).await?;
+        if hidden.count > 0 {
+            writeln!(out, "{} {}", unicode::VERTICAL_ELLIPSIS.dimmed(), hidden)?;
+            writeln!(out, "{}", unicode::VERTICAL_BAR.dimmed())?;
+        }
+    } else if !branch.complete {
+        writeln!(
+            out,
+            "{} {}",
+            unicode::VERTICAL_ELLIPSIS.dimmed(),
+            format!(
+                "earlier plugin instances of this branch are hidden -- use {} to show all",
+                "--max-nodes 0".bold()
+            )
+            .dimmed()
+        )?;
+        writeln!(out, "{}", unicode::VERTICAL_BAR.dimmed())?;
+    }
+    let nodes = &branch.nodes;
+    for (i, plinst) in nodes.iter().enumerate() {
+        let is_current = plinst.object.id == selected_id;
+        let has_next = i + 1 < nodes.len();
+        write_node(out, plinst, is_current, has_next, show_execshell, term_cols).await?;
+    }
+    Ok(())
+}
+
+async fn write_node(
+    out: &mut String,
+    plinst: &PluginInstanceRo,
+    is_current: bool,
+    has_next: bool,
+    show_execshell: bool,
+    term_cols: usize,
+) -> Result<()> {
+    let id_part = format!("(plugininstance/{})", plinst.object.id.0.cyan());
+    writeln!(
+        out,
+        "{} {}  {}",
+        symbol_for(&plinst.object),
+        title_of(&plinst.object, is_current),
+        id_part.dimmed()
+    )?;
+    let pipe = if has_next { unicode::VERTICAL_BAR } else { " " };
+    let cmd = cmd_of(plinst, show_execshell).await?;
+    let mut is_first = true;
+    for line in textwrap::wrap(cmd.as_str(), term_cols) {
+        let space = if is_first { " " } else { "     " };
+        writeln!(out, "{}{}{}", pipe.dimmed(), space, line.dimmed())?;
+        is_first = false;
+    }
+    if has_next {
+        writeln!(out, "{}", pipe.dimmed())?;
+    }
+    Ok(())
+}
+
+/// Summary of the plugin instances of a branch between its root and the ones which
+/// are shown.
+#[derive(Debug, PartialEq)]
+struct Hidden {
+    count: u32,
+    /// Number of finished plugin instances, if known.
+    finished: Option<u32>,
+    errors: u32,
+}
+
+impl Hidden {
+    /// Summarize the plugin instances `hidden`, which come before `first`.
+    ///
+    /// A plugin instance only starts after its previous plugin instance finished
+    /// successfully, so if `first` started, every hidden plugin instance finished.
+    /// Otherwise, the hidden plugin instances which are known to be finished are
+    /// only the ones in `errored`.
+    fn summarize(
+        first: &PluginInstanceResponse,
+        hidden: &[PluginInstanceId],
+        errored: &[PluginInstanceResponse],
+    ) -> Self {
+        let errors = errored.iter().filter(|p| hidden.contains(&p.id)).count() as u32;
+        let started = !matches!(
+            first.status.simplify(),
+            SimplifiedStatus::Waiting | SimplifiedStatus::Cancelled
         );
-        let pipe = if has_next { unicode::VERTICAL_BAR } else { " " };
-        let cmd = cmd_of(plinst, show_execshell).await?;
-        let mut is_first = true;
-        for line in textwrap::wrap(cmd.as_str(), term_cols) {
-            let space = if is_first { " " } else { "     " };
-            println!("{}{}{}", pipe.dimmed(), space, line.dimmed());
-            is_first = false;
+        let count = hidden.len() as u32;
+        Self {
+            count,
+            finished: started.then(|| count - errors),
+            errors,
+        }
+    }
+}
+
+impl Display for Hidden {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hint = format!("use {} to show all", "--max-nodes 0".bold());
+        let summary = match self.finished {
+            Some(finished) => format!(
+                "{} more nodes ({} finished, {} errors -- {})",
+                self.count, finished, self.errors, hint
+            ),
+            None => format!(
+                "{} more nodes ({} errors -- {})",
+                self.count, self.errors, hint
+            ),
+        };
+        write!(f, "{}", summary.dimmed())
+    }
+}
+
+/// Get the root of the branch which `first` is in, and summarize the plugin instances
+/// between them. Their IDs are known from the output path of `first`, so only the root
+/// is retrieved.
+///
+/// Returns `None` if the output path of `first` is not reported by _CUBE_.
+async fn root_of_branch(
+    client: &EitherClient,
+    first: &PluginInstanceRo,
+    errored: &[PluginInstanceResponse],
+) -> Result<Option<(PluginInstanceRo, Hidden)>> {
+    let ancestors = match first.object.output_path.as_deref().map(ancestors_of) {
+        Some(ancestors) if !ancestors.is_empty() => ancestors,
+        _ => return Ok(None),
+    };
+    let root = client.get_plugin_instance(ancestors[0]).await?;
+    let hidden = Hidden::summarize(&first.object, &ancestors[1..], errored);
+    Ok(Some((root, hidden)))
+}
+
+/// Get the IDs of the previous plugin instances of a plugin instance from its output path,
+/// e.g. `[1, 2]` from `chris/feed_1/pl-dircopy_1/pl-a_2/pl-b_3/data`.
+fn ancestors_of(output_path: &str) -> Vec<PluginInstanceId> {
+    let path = output_path.strip_suffix("/data").unwrap_or(output_path);
+    let mut ids: Vec<_> = path
+        .split('/')
+        .skip(2)
+        .filter_map(|folder| folder.rsplit_once('_'))
+        .filter_map(|(_, id)| id.parse().ok().map(PluginInstanceId))
+        .collect();
+    ids.pop();
+    ids
+}
+
+/// Summary of the plugin instances of a feed which were not shown.
+#[derive(Debug, PartialEq)]
+struct RestOfFeed {
+    count: u32,
+    finished: u32,
+    errors: u32,
+}
+
+impl RestOfFeed {
+    /// Subtract the `shown` and `hidden` plugin instances from the job counts of a `feed`.
+    /// Returns `None` if nothing was left out.
+    fn summarize(
+        feed: &FeedResponse,
+        shown: &[&PluginInstanceResponse],
+        hidden: Option<&Hidden>,
+    ) -> Option<Self> {
+        let total = feed.pending_jobs()
+            + feed.running_jobs()
+            + feed.finished_jobs
+            + feed.errored_jobs
+            + feed.cancelled_jobs;
+        let count_shown =
+            |status: Status| shown.iter().filter(|p| p.status == status).count() as u32;
+        let (hidden_count, hidden_finished, hidden_errors) = hidden
+            .map(|h| (h.count, h.finished.unwrap_or(0), h.errors))
+            .unwrap_or_default();
+        let count = total.saturating_sub(shown.len() as u32 + hidden_count);
+        if count == 0 {
+            return None;
         }
-        if has_next {
-            println!("{}", pipe.dimmed())
+        Some(Self {
+            count,
+            finished: feed
+                .finished_jobs
+                .saturating_sub(count_shown(Status::FinishedSuccessfully) + hidden_finished),
+            errors: feed
+                .errored_jobs
+                .saturating_sub(count_shown(Status::FinishedWithError) + hidden_errors),
+        })
+    }
+}
+
+impl Display for RestOfFeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} more plugin instances in this feed ({} finished, {} errors)",
+            unicode::HORIZONTAL_ELLIPSIS,
+            self.count,
+            self.finished,
+            self.errors
+        )
+    }
+}
+
+/// Use the status-filtered search to find errored plugin instances of a feed.
+/// Anonymous users cannot search for plugin instances, so for them nothing is found.
+async fn find_errored(
+    client: Option<&ChrisClient>,
+    feed: &FeedResponse,
+) -> Result<Vec<PluginInstanceResponse>, CubeError> {
+    match client {
+        Some(client) if feed.has_errored_job() => {
+            client
+                .plugin_instances()
+                .feed_id(feed.id)
+                .status(Status::FinishedWithError)
+                .search()
+                .page_limit(MAX_ERRORED as u32)
+                .max_items(MAX_ERRORED)
+                .stream()
+                .try_collect()
+                .await
         }
+        _ => Ok(Vec::with_capacity(0)),
     }
-    Ok(())
 }
 
-fn symbol_for(plinst: &PluginInstanceRo) -> impl Display {
-    match plinst.object.status.simplify() {
+/// Maximum number of errored plugin instances to list.
+const MAX_ERRORED: usize = 100;
+
+pub(crate) fn symbol_for(plinst: &PluginInstanceResponse) -> impl Display {
+    match plinst.status.simplify() {
         SimplifiedStatus::Waiting => unicode::DOTTED_CIRCLE.bold().to_string(),
         SimplifiedStatus::Running => unicode::BLACK_CIRCLE.bold().bright_blue().to_string(),
         SimplifiedStatus::Success => unicode::BLACK_CIRCLE.bold().blue().to_string(),
@@ -74,12 +325,12 @@ fn symbol_for(plinst: &PluginInstanceRo) -> impl Display {
     }
 }
 
-fn title_of(plinst: &PluginInstanceRo, is_current: bool) -> impl Display {
-    let title = if plinst.object.title.is_empty() {
-        plinst.object.plugin_name.as_str()
+fn title_of(plinst: &PluginInstanceResponse, is_current: bool) -> impl Display {
+    let title = sanitize_for_terminal(if plinst.title.is_empty() {
+        plinst.plugin_name.as_str()
     } else {
-        plinst.object.title.as_str()
-    };
+        plinst.title.as_str()
+    });
     if is_current {
         title.bold().to_string()
     } else {
@@ -165,16 +416,130 @@ fn format_param(param: PluginParameter, value: PluginParameterValue) -> Option<S
     }
 }
 
-async fn get_all_plugin_instances(feed: &FeedRo) -> Result<Vec<PluginInstanceRo>> {
-    let collection = feed.get_plugin_instances().page_limit(20).max_items(100);
-    let count = collection.get_count().await?;
-    if count > 100 {
-        bail!("Feed contains over 100 plugin instances.")
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use rstest::*;
+
+    #[rstest]
+    #[case("chris/feed_1/pl-dircopy_1/pl-a_2/pl-b_3/data", &[1, 2])]
+    #[case("chris/feed_1/pl-dircopy_1/pl-a_2/pl-b_3", &[1, 2])]
+    #[case("chris/feed_1/pl-dircopy_1/data", &[])]
+    fn test_ancestors_of(#[case] output_path: &str, #[case] expected: &[u32]) {
+        let expected: Vec<_> = expected.iter().copied().map(PluginInstanceId).collect();
+        assert_eq!(ancestors_of(output_path), expected)
+    }
+
+    /// A feed which is a chain of plugin instances `1 <- 2 <- ... <- n`, where the
+    /// status of plugin instance `i` is `statuses[i - 1]`.
+    async fn chain(statuses: &[Status]) -> MockCube {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(1, "pl-dircopy", "2.1.1");
+        let feed = mock.feed(1, "Long Study");
+        let mut previous: Option<PluginInstanceResponse> = None;
+        for (i, status) in statuses.iter().enumerate() {
+            let id = i as u32 + 1;
+            let plinst = PluginInstanceResponse {
+                title: format!("step-{id}"),
+                status: status.clone(),
+                ..mock.plugin_instance(id, &plugin, &feed, previous.as_ref())
+            };
+            mock.add_plugin_instance(plinst.clone());
+            previous = Some(plinst);
+        }
+        mock.add_plugin(plugin);
+        mock.add_feed(feed);
+        mock
+    }
+
+    async fn render_branch(
+        mock: &MockCube,
+        selected: u32,
+        max_nodes: usize,
+        errored: &[PluginInstanceResponse],
+    ) -> String {
+        let client = EitherClient::Anon(mock.anon_client().await);
+        let selected = client
+            .get_plugin_instance(PluginInstanceId(selected))
+            .await
+            .unwrap();
+        let selected_id = selected.object.id;
+        let branch = walk_branch_cached(selected, max_nodes, &PluginInstanceCache::disabled())
+            .await
+            .unwrap();
+        let root = if branch.complete {
+            None
+        } else {
+            root_of_branch(&client, &branch.nodes[0], errored)
+                .await
+                .unwrap()
+        };
+        let mut out = String::new();
+        write_branch(&mut out, root.as_ref(), &branch, selected_id, false)
+            .await
+            .unwrap();
+        console::strip_ansi_codes(&out).to_string()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_root_chain_and_last_nodes() {
+        let mut statuses = vec![Status::FinishedSuccessfully; 5];
+        statuses.push(Status::Started);
+        let mock = chain(&statuses).await;
+        let before = mock.requests().len();
+        let actual = render_branch(&mock, 6, 2, &[]).await;
+        let expected = "\
+● step-1  (plugininstance/1)
+| ghcr.io/fnndsc/pl-dircopy:2.1.1 pl-dircopy
+|
+⋮ 3 more nodes (3 finished, 0 errors -- use --max-nodes 0 to show all)
+|
+● step-5  (plugininstance/5)
+| ghcr.io/fnndsc/pl-dircopy:2.1.1 pl-dircopy
+|
+● step-6  (plugininstance/6)
+  ghcr.io/fnndsc/pl-dircopy:2.1.1 pl-dircopy
+";
+        assert_eq!(actual, expected);
+        let plugin_instances = mock.requests()[before..]
+            .iter()
+            .filter(|r| r.contains("/plugins/instances/") && !r.contains("parameters"))
+            .count();
+        // selected, its previous, and the root
+        assert_eq!(plugin_instances, 3);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_hidden_errors_when_waiting() {
+        let mut statuses = vec![Status::FinishedSuccessfully; 3];
+        statuses.push(Status::FinishedWithError);
+        statuses.extend([Status::Cancelled, Status::Cancelled]);
+        let mock = chain(&statuses).await;
+        let client = mock.anon_client().await;
+        let errored = client
+            .get_plugin_instance(PluginInstanceId(4))
+            .await
+            .unwrap()
+            .object;
+        let actual = render_branch(&mock, 6, 2, &[errored]).await;
+        assert!(
+            actual.contains("⋮ 3 more nodes (1 errors -- use --max-nodes 0 to show all)"),
+            "{actual}"
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_complete_branch_has_no_summary() {
+        let mock = chain(&[Status::FinishedSuccessfully, Status::Started]).await;
+        let actual = render_branch(&mock, 2, 2, &[]).await;
+        assert!(
+            actual.starts_with("● step-1  (plugininstance/1)\n"),
+            "{actual}"
+        );
+        assert!(!actual.contains('⋮'), "{actual}");
     }
-    collection
-        .stream_connected()
-        .try_collect()
-        .await
-        .map_err(eyre::Error::new)
-    // maybe a file_transfer bar would be nice if count > 20
 }
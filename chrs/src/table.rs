@@ -0,0 +1,163 @@
+//! Fitting tables to the width of the terminal.
+//!
+//! Columns of numbers and dates get the width they need, and the remaining width
+//! goes to one "flexible" column (e.g. a name or path). Values of the flexible column
+//! which are too wide are shortened using [ellipsize_middle].
+
+use std::borrow::Cow;
+
+use crate::unicode::{display_width, ellipsize_middle};
+
+/// Narrowest the flexible column gets, even if the terminal is narrower.
+const MIN_FLEXIBLE_WIDTH: usize = 16;
+
+/// Number of spaces between columns.
+const GAP: usize = 1;
+
+/// Shortens values of the flexible column of a table.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fit(Option<usize>);
+
+impl Fit {
+    /// Fit the flexible column to the width of stdout, if it is a terminal and
+    /// `no_ellipsis` is false. `fixed` are the widths of the other columns.
+    pub fn to_terminal(no_ellipsis: bool, fixed: &[usize]) -> Self {
+        if no_ellipsis {
+            Self(None)
+        } else {
+            Self::new(terminal_width(), fixed)
+        }
+    }
+
+    /// Give the flexible column whatever is left of `terminal_width` by the `fixed` columns.
+    /// Values are not shortened if `terminal_width` is `None`.
+    pub fn new(terminal_width: Option<usize>, fixed: &[usize]) -> Self {
+        let used: usize = fixed.iter().map(|w| w + GAP).sum();
+        let width = terminal_width.map(|w| w.saturating_sub(used).max(MIN_FLEXIBLE_WIDTH));
+        Self(width)
+    }
+
+    /// The same, but with `n` columns less.
+    pub fn reserve(self, n: usize) -> Self {
+        Self(self.0.map(|w| w.saturating_sub(n)))
+    }
+
+    /// Shorten `value` if it is too wide.
+    pub fn fit<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        match self.0 {
+            Some(width) => ellipsize_middle(value, width),
+            None => Cow::Borrowed(value),
+        }
+    }
+
+    /// Shorten `value` if it is too wide, then pad it with spaces to `at_most` columns
+    /// or the width of the flexible column, whichever is less.
+    pub fn fit_and_pad(&self, value: &str, at_most: usize) -> String {
+        let fitted = self.fit(value);
+        let width = self.0.map(|w| w.min(at_most)).unwrap_or(at_most);
+        let padding = width.saturating_sub(display_width(&fitted));
+        format!("{}{}", fitted, " ".repeat(padding))
+    }
+}
+
+/// Widths of the columns of `rows`, except for the `flexible` column, which is
+/// the width of the widest value in each column. For tables which are printed
+/// after all of their rows are known.
+pub fn fixed_widths<S: AsRef<str>>(rows: &[Vec<S>], flexible: usize) -> Vec<usize> {
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    (0..columns)
+        .filter(|&i| i != flexible)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|value| display_width(value.as_ref()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+/// Pad `value` with spaces on the left to `width` columns, to align it to the right.
+pub fn pad_left(value: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(value));
+    format!("{}{}", " ".repeat(padding), value)
+}
+
+/// Pad `value` with spaces on the right to `width` columns.
+pub fn pad_right(value: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(value));
+    format!("{}{}", value, " ".repeat(padding))
+}
+
+/// Width of stdout, if it is a terminal.
+fn terminal_width() -> Option<usize> {
+    console::Term::stdout()
+        .size_checked()
+        .map(|(_rows, columns)| columns as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[fixture]
+    fn rows() -> Vec<Vec<&'static str>> {
+        vec![
+            vec![
+                "feed/1",
+                "chris/Brain Volume Analysis/data/x.txt",
+                "2024-01-02",
+            ],
+            vec!["feed/100", "chris/脳の画像/data/y.txt", "2024-01-02"],
+            vec!["feed/12345", "short", "2024-12-31"],
+        ]
+    }
+
+    #[rstest]
+    fn test_fixed_widths(rows: Vec<Vec<&str>>) {
+        assert_eq!(fixed_widths(&rows, 1), vec![10, 10]);
+        assert_eq!(fixed_widths(&rows, 0), vec![38, 10]);
+        assert!(fixed_widths::<&str>(&[], 0).is_empty());
+    }
+
+    #[rstest]
+    #[case(Some(80), Some(58))]
+    #[case(Some(40), Some(18))]
+    #[case(Some(20), Some(MIN_FLEXIBLE_WIDTH))]
+    #[case(Some(0), Some(MIN_FLEXIBLE_WIDTH))]
+    #[case(None, None)]
+    fn test_flexible_width(
+        rows: Vec<Vec<&str>>,
+        #[case] terminal_width: Option<usize>,
+        #[case] expected: Option<usize>,
+    ) {
+        let fit = Fit::new(terminal_width, &fixed_widths(&rows, 1));
+        assert_eq!(fit.0, expected);
+    }
+
+    #[rstest]
+    fn test_rows_fit_terminal(rows: Vec<Vec<&str>>) {
+        let widths = fixed_widths(&rows, 1);
+        let fit = Fit::new(Some(40), &widths);
+        for row in &rows {
+            let line = format!("{:<10} {} {}", row[0], fit.fit_and_pad(row[1], 60), row[2]);
+            assert_eq!(display_width(&line), 40, "{line}");
+        }
+    }
+
+    #[rstest]
+    fn test_no_ellipsis() {
+        let fit = Fit::to_terminal(true, &[10]);
+        let value = "chris/Brain Volume Analysis/data/x.txt";
+        assert_eq!(fit.fit(value), value);
+        assert_eq!(fit.fit_and_pad("short", 10), "short     ");
+    }
+
+    #[rstest]
+    fn test_reserve() {
+        let fit = Fit::new(Some(40), &[10]).reserve(6);
+        assert_eq!(fit.0, Some(23));
+        assert_eq!(Fit(None).reserve(6), Fit(None));
+    }
+}
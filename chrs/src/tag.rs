@@ -0,0 +1,280 @@
+//! `chrs tag`: create tags, and put them on feeds.
+
+use clap::Subcommand;
+use color_eyre::eyre::{bail, eyre, Error, OptionExt, Result, WrapErr};
+use color_eyre::owo_colors::OwoColorize;
+use futures::TryStreamExt;
+
+use chris::errors::TaggingError;
+use chris::types::TagId;
+use chris::{ChrisClient, TagResponse, TagRw};
+
+use crate::arg::GivenDataNode;
+use crate::credentials::{Credentials, NO_ARGS};
+use crate::output::{write_stream, OutputFormat, Render};
+use crate::sanitize::sanitize_for_terminal;
+
+/// Color of tags created without `--color`, which is the blue of the ChRIS UI.
+const DEFAULT_COLOR: &str = "#2196f3";
+
+#[derive(Subcommand)]
+pub enum TagCommand {
+    /// List your tags
+    Ls,
+
+    /// Create a tag
+    Create {
+        /// Color of the tag in the ChRIS UI, e.g. "#ff9800"
+        #[clap(short, long, default_value = DEFAULT_COLOR)]
+        color: String,
+
+        /// Name of the tag
+        name: String,
+    },
+
+    /// Put a tag on a feed
+    Add {
+        /// Name of the tag, or tag/ID
+        tag: String,
+
+        /// Feed, or a plugin instance of the feed
+        feed: Option<GivenDataNode>,
+    },
+
+    /// Remove a tag from a feed
+    Rm {
+        /// Name of the tag, or tag/ID
+        tag: String,
+
+        /// Feed, or a plugin instance of the feed
+        feed: Option<GivenDataNode>,
+    },
+}
+
+/// `chrs tag` command
+pub async fn tag(
+    credentials: Credentials,
+    command: TagCommand,
+    output: OutputFormat,
+) -> Result<()> {
+    match command {
+        TagCommand::Ls => list_tags(credentials, output).await,
+        TagCommand::Create { color, name } => create_tag(credentials, name, color).await,
+        TagCommand::Add { tag, feed } => change_tag(credentials, tag, feed, true).await,
+        TagCommand::Rm { tag, feed } => change_tag(credentials, tag, feed, false).await,
+    }
+}
+
+async fn list_tags(credentials: Credentials, output: OutputFormat) -> Result<()> {
+    let (client, _, _) = credentials.get_client(NO_ARGS).await?;
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to list tags.")?;
+    let search = client.tags().search();
+    if !output.is_human() {
+        return write_stream(search.stream(), output).await;
+    }
+    let tags: Vec<_> = search.stream().try_collect().await?;
+    println!(
+        "{:<12} {:<9} {}",
+        "ID".bold().underline(),
+        "Color".bold().underline(),
+        "Name".bold().underline()
+    );
+    for tag in tags {
+        println!(
+            "tag/{:<8} {:<9} {}",
+            tag.id.0.bold(),
+            sanitize_for_terminal(&tag.color),
+            sanitize_for_terminal(&tag.name)
+        );
+    }
+    Ok(())
+}
+
+async fn create_tag(credentials: Credentials, name: String, color: String) -> Result<()> {
+    let (client, _, _) = credentials.get_client(NO_ARGS).await?;
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to create tags.")?;
+    let tag = client
+        .create_tag(&name, &color)
+        .await
+        .wrap_err_with(|| format!("Could not create tag \"{}\"", name))?;
+    eprintln!(
+        "Created {} {}",
+        format!("tag/{}", tag.object.id.0).bold(),
+        sanitize_for_terminal(&tag.object.name)
+    );
+    Ok(())
+}
+
+/// Put a tag on a feed, or remove it from the feed if `add` is false.
+async fn change_tag(
+    credentials: Credentials,
+    tag: String,
+    feed: Option<GivenDataNode>,
+    add: bool,
+) -> Result<()> {
+    let (client, old, _) = credentials
+        .get_client(feed.as_ref().map(|g| g.as_arg_str()).as_slice())
+        .await?;
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to tag feeds.")?;
+    let given = feed
+        .or_else(|| old.map(|id| id.into()))
+        .ok_or_eyre("missing operand")?;
+    let tag = resolve_tag(&client, &tag).await?;
+    let feed = given.into_feed_rw(&client, old).await?;
+    let feed_id = feed.object.id.0;
+    let tag_name = sanitize_for_terminal(&tag.object.name);
+    let feed_name = sanitize_for_terminal(&feed.object.name);
+    if add {
+        feed.tag(tag.object.id)
+            .await
+            .map_err(|e| explain_tagging_error(e, feed_id))?;
+        eprintln!(
+            "Tagged {} {} with \"{}\"",
+            format!("feed/{}", feed_id).bold(),
+            feed_name,
+            tag_name
+        );
+    } else {
+        feed.untag(tag.object.id)
+            .await
+            .map_err(|e| explain_tagging_error(e, feed_id))?;
+        eprintln!(
+            "Removed tag \"{}\" from {} {}",
+            tag_name,
+            format!("feed/{}", feed_id).bold(),
+            feed_name
+        );
+    }
+    Ok(())
+}
+
+fn explain_tagging_error(error: TaggingError, feed_id: u32) -> Error {
+    match error {
+        TaggingError::CUBEError(e) => {
+            Error::new(e).wrap_err(format!("Could not change the tags of feed/{}", feed_id))
+        }
+        e => eyre!("Cannot change the tags of feed/{}: {}", feed_id, e),
+    }
+}
+
+/// Get a tag of the user by its name, or by `tag/ID`.
+///
+/// It is an error if the user has more than one tag with the name.
+pub(crate) async fn resolve_tag(client: &ChrisClient, given: &str) -> Result<TagRw> {
+    if let Some(id) = given.strip_prefix("tag/").and_then(|id| id.parse().ok()) {
+        return client
+            .tags()
+            .id(TagId(id))
+            .search()
+            .get_first()
+            .await?
+            .ok_or_else(|| eyre!("{} not found", given));
+    }
+    let search = client.tags().name(given).search();
+    let mut matches: Vec<_> = search
+        .stream_connected()
+        .try_filter(|tag| futures::future::ready(tag.object.name == given))
+        .try_collect()
+        .await?;
+    match matches.len() {
+        0 => bail!("You have no tag named \"{}\"", given),
+        1 => Ok(matches.pop().unwrap()),
+        _ => {
+            let choices: Vec<_> = matches
+                .iter()
+                .map(|tag| describe_tag(&tag.object))
+                .collect();
+            bail!(
+                "There are {} tags named \"{}\", specify one of them by its ID:\n{}",
+                matches.len(),
+                given,
+                choices.join("\n")
+            )
+        }
+    }
+}
+
+fn describe_tag(tag: &TagResponse) -> String {
+    format!(
+        "  tag/{:<8} {} (color: {})",
+        tag.id.0,
+        sanitize_for_terminal(&tag.name),
+        sanitize_for_terminal(&tag.color)
+    )
+}
+
+impl Render for TagResponse {
+    fn columns(&self) -> Vec<String> {
+        vec![
+            format!("tag/{}", self.id.0),
+            self.name.clone(),
+            self.color.clone(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use chris::types::Username;
+    use rstest::*;
+
+    /// A mock where "brain" is on feeds 1 and 3, "lung" is on feed 2,
+    /// and there are two tags named "todo".
+    async fn tagged_mock() -> MockCube {
+        let mock = MockCube::start().await;
+        mock.add_tagged_feeds();
+        mock.add_tag(mock.tag(3, "todo"), &[]);
+        mock.add_tag(
+            TagResponse {
+                color: "red".to_string(),
+                owner_username: Username::from("alice"),
+                ..mock.tag(4, "todo")
+            },
+            &[],
+        );
+        mock
+    }
+
+    #[rstest]
+    #[case("brain", Some(1))]
+    #[case("tag/2", Some(2))]
+    #[case("tag/4", Some(4))]
+    #[case("tag/5", None)]
+    #[case("Brain", None)]
+    #[tokio::test]
+    async fn test_resolve_tag(#[case] given: &str, #[case] expected: Option<u32>) {
+        let mock = tagged_mock().await;
+        let client = mock.client("chris").await;
+        let actual = resolve_tag(&client, given)
+            .await
+            .ok()
+            .map(|t| t.object.id.0);
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_resolve_ambiguous_tag() {
+        let mock = tagged_mock().await;
+        let client = mock.client("chris").await;
+        let error = match resolve_tag(&client, "todo").await {
+            Ok(tag) => panic!("resolved to tag/{}", tag.object.id.0),
+            Err(e) => e.to_string(),
+        };
+        assert!(
+            error.starts_with("There are 2 tags named \"todo\""),
+            "{error}"
+        );
+        assert!(error.contains("tag/3"), "{error}");
+        assert!(error.contains("tag/4"), "{error}");
+        assert!(error.contains("color: red"), "{error}");
+    }
+}
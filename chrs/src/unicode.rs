@@ -14,3 +14,109 @@ pub const HORIZONTAL_BAR: &str = "\u{2015}";
 pub const VERTICAL_BAR: &str = "\u{007C}";
 
 pub const CHECK_MARK: &str = "\u{2713}";
+
+pub const LEFTWARDS_ARROW: &str = "\u{2190}";
+pub const RIGHTWARDS_ARROW: &str = "\u{2192}";
+
+pub const HORIZONTAL_ELLIPSIS: &str = "\u{2026}";
+pub const VERTICAL_ELLIPSIS: &str = "\u{22EE}";
+
+/// Width of a string in columns when printed to a terminal.
+pub fn display_width(s: &str) -> usize {
+    unicode_width::UnicodeWidthStr::width(s)
+}
+
+/// Shorten a string to fit in `width` columns, replacing its middle with an ellipsis,
+/// e.g. `chris/Brain Volume…/data/x.txt`.
+///
+/// Wide characters (e.g. CJK) count as two columns, and combining characters are
+/// never separated from the character before them.
+pub fn ellipsize_middle(s: &str, width: usize) -> std::borrow::Cow<'_, str> {
+    if display_width(s) <= width {
+        return std::borrow::Cow::Borrowed(s);
+    }
+    if width == 0 {
+        return std::borrow::Cow::Borrowed("");
+    }
+    let clusters = clusters_of(s);
+    let budget = width - display_width(HORIZONTAL_ELLIPSIS);
+    let mut head_width = 0;
+    let head_end = clusters
+        .iter()
+        .take_while(|(_, w)| {
+            let fits = head_width + w <= budget.div_ceil(2);
+            if fits {
+                head_width += w;
+            }
+            fits
+        })
+        .count();
+    let mut tail_width = 0;
+    let tail_len = clusters[head_end..]
+        .iter()
+        .rev()
+        .take_while(|(_, w)| {
+            let fits = head_width + tail_width + w <= budget;
+            if fits {
+                tail_width += w;
+            }
+            fits
+        })
+        .count();
+    let head = clusters[..head_end].iter().map(|(c, _)| *c);
+    let tail = clusters[clusters.len() - tail_len..]
+        .iter()
+        .map(|(c, _)| *c);
+    let ellipsized = head
+        .chain(std::iter::once(HORIZONTAL_ELLIPSIS))
+        .chain(tail)
+        .collect();
+    std::borrow::Cow::Owned(ellipsized)
+}
+
+/// Split a string into characters followed by any zero-width (e.g. combining) characters,
+/// and their widths.
+fn clusters_of(s: &str) -> Vec<(&str, usize)> {
+    let mut clusters: Vec<(&str, usize)> = Vec::with_capacity(s.len());
+    let mut start = 0;
+    for (i, c) in s.char_indices().skip(1) {
+        if unicode_width::UnicodeWidthChar::width(c) != Some(0) {
+            clusters.push((&s[start..i], display_width(&s[start..i])));
+            start = i;
+        }
+    }
+    if !s.is_empty() {
+        clusters.push((&s[start..], display_width(&s[start..])));
+    }
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("short", 10, "short")]
+    #[case("exactly10!", 10, "exactly10!")]
+    #[case(
+        "chris/Brain Volume Analysis/data/x.txt",
+        30,
+        "chris/Brain Vol…sis/data/x.txt"
+    )]
+    #[case("abcdefgh", 5, "ab…gh")]
+    #[case("abcdefgh", 4, "ab…h")]
+    #[case("abcdefgh", 1, "…")]
+    #[case("abcdefgh", 0, "")]
+    #[case("ñandú/niño/año.txt", 18, "ñandú/niño/año.txt")]
+    #[case("ñandú/niño/año.txt", 9, "ñand….txt")]
+    #[case("ñandú/niño/año.txt", 5, "ña…xt")]
+    #[case("脳の画像/データ.nii", 9, "脳の….nii")]
+    #[case("脳の画像/データ", 6, "脳…タ")]
+    #[case("ne\u{301}e\u{301}/cafe\u{301}", 5, "ne\u{301}…fe\u{301}")]
+    fn test_ellipsize_middle(#[case] value: &str, #[case] width: usize, #[case] expected: &str) {
+        let actual = ellipsize_middle(value, width);
+        assert_eq!(actual, expected);
+        assert!(display_width(&actual) <= width);
+    }
+}
@@ -1,4 +1,8 @@
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use async_walkdir::WalkDir;
 use camino::{Utf8Path, Utf8PathBuf};
@@ -6,19 +10,30 @@ use clap::{builder::NonEmptyStringValueParser, Parser};
 use color_eyre::eyre;
 use color_eyre::eyre::{bail, eyre, WrapErr};
 use color_eyre::owo_colors::OwoColorize;
-use futures::{StreamExt, TryFutureExt, TryStreamExt};
+use futures::{Stream, StreamExt, TryStreamExt};
 use itertools::Itertools;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::mpsc::{channel, unbounded_channel, Sender, UnboundedSender};
 use tokio::{join, try_join};
+use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::codec::{BytesCodec, FramedRead};
 
+use chris::errors::FileIOError;
 use chris::types::{PluginInstanceId, PluginType};
-use chris::{BaseChrisClient, ChrisClient, FeedRw, PluginInstanceRw, PluginRw};
+use chris::{
+    BaseChrisClient, ChrisClient, Downloadable, FeedRw, PipelineRw, PluginInstanceRw, PluginRw,
+};
 
+use crate::arg::{GivenRunnable, Runnable};
 use crate::credentials::{Credentials, NO_ARGS};
-use crate::file_transfer::{progress_bar_bytes, FileTransferEvent, MultiFileTransferProgress};
+use crate::file_transfer::{
+    progress_bar_bytes, AdaptiveLimiter, Cancellation, Checksum, Checksums, FileTransferEvent,
+    Hasher, MultiFileTransferProgress, Outcome, ProgressFormat, TimesSidecar, TIMES_SIDECAR_NAME,
+};
 use crate::login::UiUrl;
 use crate::shlex::shlex_quote;
+use manifest::{FeedIngester, Ingested, ManifestRow};
+
+mod manifest;
 
 #[derive(Parser)]
 pub struct UploadArgs {
@@ -42,16 +57,90 @@ pub struct UploadArgs {
     #[clap(short = 'j', long, default_value_t = 4)]
     threads: usize,
 
+    /// Reduce the number of concurrent uploads when server errors or timeouts happen (default)
+    #[clap(long, overrides_with = "no_adaptive")]
+    adaptive: bool,
+
+    /// Always use the maximum number of concurrent uploads
+    #[clap(long, overrides_with = "adaptive")]
+    no_adaptive: bool,
+
+    /// Record the modification times of files in a sidecar file, which can be
+    /// used by `chrs download --restore-times`
+    #[clap(long)]
+    preserve_times: bool,
+
+    /// Compute the SHA-256 of files as they are uploaded, and write them to FILE.
+    /// Paths are relative to the upload, so a download of it can be checked
+    /// using `chrs verify FILE DIR`
+    #[clap(long, value_name = "FILE", conflicts_with = "manifest")]
+    checksum: Option<Utf8PathBuf>,
+
+    /// CSV file with columns `path`, `feed_name`, and optionally `pipeline`.
+    /// A feed is created for every row, and the pipeline is run on it.
+    #[clap(
+        long,
+        visible_alias = "feed-file",
+        conflicts_with_all = ["feed", "no_feed", "paths"]
+    )]
+    manifest: Option<PathBuf>,
+
+    /// Number of feeds from the manifest to create concurrently
+    #[clap(long, default_value_t = 1, requires = "manifest")]
+    parallel_feeds: usize,
+
+    /// Where to write the results of the manifest as CSV (default: stdout)
+    #[clap(long, requires = "manifest")]
+    results: Option<PathBuf>,
+
+    /// Run a pipeline after the upload
+    #[clap(long, conflicts_with_all = ["no_feed", "manifest"])]
+    pipeline: Option<String>,
+
+    /// Create a feed from a folder which is already in ChRIS, e.g. from a previous
+    /// upload, instead of uploading local files
+    #[clap(
+        long,
+        visible_alias = "dircopy-path",
+        value_name = "CHRIS_PATH",
+        conflicts_with_all = ["no_feed", "manifest", "checksum", "preserve_times"]
+    )]
+    from: Option<String>,
+
+    /// Name of the folder under `<username>/uploads/` to upload to, instead of a
+    /// new folder with a timestamp in its name
+    #[clap(long, value_name = "NAME", conflicts_with_all = ["manifest", "from"])]
+    upload_path: Option<String>,
+
+    /// Do not upload files which were already uploaded to --upload-path with the same size,
+    /// e.g. to resume an upload which was interrupted
+    #[clap(
+        long,
+        requires = "upload_path",
+        conflicts_with_all = ["checksum", "preserve_times"]
+    )]
+    skip_existing: bool,
+
     /// Paths to upload
     paths: Vec<Utf8PathBuf>,
 }
 
 /// `chrs upload` command
-pub async fn upload(credentials: Credentials, args: UploadArgs) -> eyre::Result<()> {
+pub async fn upload(
+    credentials: Credentials,
+    args: UploadArgs,
+    progress: ProgressFormat,
+) -> eyre::Result<()> {
     let config_path = credentials.config_path.clone();
+    let ephemeral = credentials.ephemeral;
+    let concurrency = Concurrency::of(&args, progress, credentials.retries.unwrap_or(0));
     let (client, old, ui) = credentials.get_client(NO_ARGS).await?;
     if let Some(client) = client.logged_in() {
-        upload_logged_in(client, old, ui, args, config_path).await
+        if let Some(manifest) = args.manifest.clone() {
+            upload_manifest(client, args, manifest, concurrency).await
+        } else {
+            upload_logged_in(client, old, ui, args, concurrency, config_path, ephemeral).await
+        }
     } else {
         bail!("You must be logged in to upload files.")
     }
@@ -62,9 +151,16 @@ async fn upload_logged_in(
     old: Option<PluginInstanceId>,
     ui: Option<UiUrl>,
     args: UploadArgs,
+    concurrency: Concurrency,
     config_path: Option<PathBuf>,
+    ephemeral: bool,
 ) -> eyre::Result<()> {
-    let threads = args.threads;
+    if args.from.is_some() && !args.paths.is_empty() {
+        bail!(
+            "Local paths cannot be uploaded with --from, which uses files already in ChRIS. \
+            Upload them with a separate `chrs upload` command."
+        )
+    }
     let input_paths = args.paths.clone();
     let title = args.feed.clone();
     let get_cube_info = async {
@@ -73,14 +169,64 @@ async fn upload_logged_in(
         let plugins = find_plugins(&client, previous_id.is_some(), &args).await?;
         Ok::<_, eyre::Error>((current_feed, previous_id, plugins))
     };
+    let get_pipeline = async {
+        match args.pipeline.as_deref() {
+            Some(pipeline) => find_pipeline(&client, pipeline).await.map(Some),
+            None => Ok(None),
+        }
+    };
 
-    let ((current_feed, previous_id, plugins), files) = try_join!(
-        get_cube_info,
-        discover_files(input_paths).map_err(eyre::Error::new)
-    )?;
+    let get_source = async {
+        if let Some(path) = &args.from {
+            existing_folder(&client, path).await.map(Source::Existing)
+        } else {
+            discover_files(input_paths)
+                .await
+                .map(Source::Local)
+                .map_err(eyre::Error::new)
+        }
+    };
 
-    let upload_path = upload_all(&client, files, threads).await?;
-    let plinsts = run_plugins(plugins, previous_id, upload_path).await?;
+    let ((current_feed, previous_id, plugins), pipeline, source) =
+        try_join!(get_cube_info, get_pipeline, get_source)?;
+
+    let upload_path = match source {
+        Source::Existing(path) => path,
+        Source::Local(files) => {
+            let base = upload_root(&client, args.upload_path.as_deref());
+            let skipped = AtomicUsize::new(0);
+            let files = files.map_err(eyre::Error::new);
+            let files = if args.skip_existing {
+                skip_existing(&client, files, &base, args.threads, &skipped).boxed()
+            } else {
+                files.boxed()
+            };
+            let uploaded = upload_all(
+                &client,
+                files,
+                base.clone(),
+                concurrency,
+                args.preserve_times,
+                args.checksum.is_some(),
+                &Cancellation::on_ctrl_c(),
+            )
+            .await?;
+            if let Some(path) = &args.checksum {
+                fs_err::tokio::write(path, uploaded.checksums.to_text()).await?;
+                eprintln!("Wrote checksums to {}", path);
+            }
+            if args.skip_existing {
+                eprintln!(
+                    "Uploaded {} file{}, skipped {} which were already uploaded",
+                    uploaded.count,
+                    if uploaded.count == 1 { "" } else { "s" },
+                    skipped.into_inner()
+                );
+            }
+            uploaded.path
+        }
+    };
+    let plinsts = run_plugins(&plugins, previous_id, upload_path).await?;
 
     let feed = if let Some(feed) = current_feed {
         // added to an already existing feed
@@ -106,20 +252,151 @@ async fn upload_logged_in(
             eprintln!("{}", ui.feed_url_of(&feed.object))
         }
     }
-    if let Some(plinst) = plinsts.last() {
-        crate::login::set_cd(
-            client.url(),
-            client.username(),
-            plinst.object.id,
-            config_path,
-        )?;
-        println!("plugininstance/{}", plinst.object.id.0)
+    let last = match (pipeline, plinsts.last()) {
+        (Some(pipeline), Some(plinst)) => {
+            Some(run_pipeline_after(&pipeline, plinst.object.id).await?)
+        }
+        (_, plinst) => plinst.map(|p| p.object.id),
+    };
+    if let Some(id) = last {
+        crate::login::set_cd(client.url(), client.username(), id, config_path, ephemeral)?;
+        println!("plugininstance/{}", id.0)
     }
     Ok(())
 }
 
-async fn run_plugins(
+/// Create a feed for every row of a manifest CSV.
+async fn upload_manifest(
+    client: ChrisClient,
+    args: UploadArgs,
+    manifest: PathBuf,
+    concurrency: Concurrency,
+) -> eyre::Result<()> {
+    let entries = manifest::read_manifest(fs_err::File::open(&manifest)?)
+        .wrap_err_with(|| format!("Invalid manifest {:?}", manifest))?;
+    let total = entries.len();
+    let ingester = UploadIngester {
+        plugins: find_plugins(&client, false, &args).await?,
+        client: &client,
+        concurrency,
+        preserve_times: args.preserve_times,
+        note: args.note.as_deref(),
+    };
+    let mut done = 0;
+    let results = manifest::run_manifest(&ingester, entries, args.parallel_feeds, |result| {
+        done += 1;
+        if let Some(feed_id) = result.feed_id {
+            eprintln!("[{}/{}] {} -> feed/{}", done, total, result.path, feed_id);
+        } else {
+            eprintln!(
+                "[{}/{}] {} {}",
+                done,
+                total,
+                result.path,
+                format!("failed: {}", result.error.as_deref().unwrap_or_default()).red()
+            );
+        }
+    })
+    .await;
+    if let Some(path) = &args.results {
+        manifest::write_results(fs_err::File::create(path)?, &results)?;
+    } else {
+        manifest::write_results(std::io::stdout().lock(), &results)?;
+    }
+    let failed = results.iter().filter(|r| !r.is_ok()).count();
+    if failed > 0 {
+        bail!("{} of {} rows of the manifest failed", failed, total)
+    }
+    Ok(())
+}
+
+/// Creates feeds the same way as `chrs upload --feed`.
+struct UploadIngester<'a> {
+    client: &'a ChrisClient,
     plugins: Vec<PluginRw>,
+    concurrency: Concurrency,
+    preserve_times: bool,
+    note: Option<&'a str>,
+}
+
+impl FeedIngester for UploadIngester<'_> {
+    async fn ingest(&self, row: &ManifestRow) -> eyre::Result<Ingested> {
+        let mut files = discover_files(vec![Utf8PathBuf::from(&row.path)])
+            .await?
+            .peekable();
+        if Pin::new(&mut files).peek().await.is_none() {
+            bail!("No files found in {}", row.path)
+        }
+        let uploaded = upload_all(
+            self.client,
+            files.map_err(eyre::Error::new),
+            create_upload_root_for(self.client),
+            self.concurrency,
+            self.preserve_times,
+            false,
+            &Cancellation::default(),
+        )
+        .await?;
+        let plinsts = run_plugins(&self.plugins, None, uploaded.path).await?;
+        let last = plinsts
+            .last()
+            .ok_or_else(|| eyre!("No plugin instances were created"))?;
+        let feed = last.feed().get().await?.set_name(&row.feed_name).await?;
+        if let Some(note) = self.note {
+            feed.note().set("Description", note).await?;
+        }
+        let plugin_instance = if let Some(pipeline) = &row.pipeline {
+            let pipeline = find_pipeline(self.client, pipeline).await?;
+            run_pipeline_after(&pipeline, last.object.id).await?
+        } else {
+            last.object.id
+        };
+        Ok(Ingested {
+            feed: feed.object.id,
+            plugin_instance,
+        })
+    }
+}
+
+/// Where the files of a feed come from.
+enum Source {
+    /// Local files which need to be uploaded, received as they are discovered.
+    Local(DiscoveredFiles),
+    /// A folder which is already in ChRIS storage.
+    Existing(String),
+}
+
+/// Check that a folder exists in ChRIS storage, so that it can be given to `pl-dircopy`.
+async fn existing_folder(client: &ChrisClient, path: &str) -> eyre::Result<String> {
+    let path = path.trim_matches('/');
+    if client.filebrowser().readdir(path).await?.is_none() {
+        bail!("Path not found: {}", path)
+    }
+    Ok(path.to_string())
+}
+
+/// Find a pipeline by name or ID.
+async fn find_pipeline(client: &ChrisClient, pipeline: &str) -> eyre::Result<PipelineRw> {
+    let given = GivenRunnable::pipeline(pipeline.to_string())?;
+    match given.resolve_using(client).await? {
+        Runnable::Pipeline(pipeline) => Ok(pipeline),
+        Runnable::Plugin(_) => bail!("\"{}\" is a plugin, not a pipeline", pipeline),
+    }
+}
+
+/// Run a pipeline after a plugin instance, returning the ID of its last plugin instance.
+async fn run_pipeline_after(
+    pipeline: &PipelineRw,
+    previous: PluginInstanceId,
+) -> eyre::Result<PluginInstanceId> {
+    let workflow = pipeline.create_workflow(previous, None).await?;
+    // Assumes CUBE returns the plugin instances in order, like in `chrs run`.
+    let last = workflow.plugin_instances().get_first().await?;
+    Ok(last.map(|p| p.object.id).unwrap_or(previous))
+}
+
+async fn run_plugins(
+    plugins: &[PluginRw],
     mut previous_id: Option<PluginInstanceId>,
     upload_path: String,
 ) -> eyre::Result<Vec<PluginInstanceRw>> {
@@ -152,101 +429,390 @@ struct PluginParameters<'a> {
     previous_id: Option<PluginInstanceId>,
 }
 
+/// Options for concurrent uploads.
+#[derive(Copy, Clone)]
+struct Concurrency {
+    threads: usize,
+    adaptive: bool,
+    progress: ProgressFormat,
+    /// Number of times to retry the upload of a file, from `--retries`
+    retries: u32,
+}
+
+impl Concurrency {
+    fn of(args: &UploadArgs, progress: ProgressFormat, retries: u32) -> Self {
+        Self {
+            threads: args.threads,
+            adaptive: !args.no_adaptive,
+            progress,
+            retries,
+        }
+    }
+}
+
+/// Files which were uploaded by [upload_all].
+struct Uploaded {
+    /// The path the files were uploaded to
+    path: String,
+    /// Checksums of the files, if they were computed
+    checksums: Checksums,
+    /// Number of files
+    count: usize,
+}
+
+/// Upload files to `base` as they are discovered. If `checksum`, the checksums of the
+/// files are computed as they are uploaded.
 async fn upload_all(
     client: &ChrisClient,
-    files: Vec<DiscoveredFile>,
-    threads: usize,
-) -> eyre::Result<String> {
-    let base = create_upload_root_for(client);
-    if files.len() == 1 {
-        upload_single(client, files.into_iter().next().unwrap().path, &base).await?;
+    files: impl Stream<Item = eyre::Result<DiscoveredFile>>,
+    base: String,
+    concurrency: Concurrency,
+    preserve_times: bool,
+    checksum: bool,
+    cancellation: &Cancellation,
+) -> eyre::Result<Uploaded> {
+    let times = Mutex::new(TimesSidecar::default());
+    let files = files
+        .inspect_ok(|file| {
+            if preserve_times {
+                let mut times = times.lock().unwrap();
+                times.push(file.to_relative(), &file.metadata)
+            }
+        })
+        .fuse();
+    let mut files = std::pin::pin!(files);
+    // without progress bars, a single file is reported like many files,
+    // so there is no need to wait for a second file
+    let first = files.try_next().await?;
+    let second = if first.is_some() && concurrency.progress == ProgressFormat::Bars {
+        files.try_next().await?
     } else {
-        upload_multiple(client, files, &base, threads).await?;
+        None
+    };
+    let (checksums, count) = match (first, second) {
+        (Some(file), None) if concurrency.progress == ProgressFormat::Bars => {
+            let rel = file.to_relative();
+            let sha256 = upload_single(client, file, &rel, &base, cancellation).await?;
+            let checksums = checksum
+                .then_some(Checksum { sha256, path: rel })
+                .into_iter()
+                .collect();
+            (checksums, 1)
+        }
+        (first, second) => {
+            let found = futures::stream::iter(first.into_iter().chain(second).map(Ok));
+            let files = found.chain(files);
+            upload_multiple(client, files, &base, concurrency, checksum, cancellation).await?
+        }
+    };
+    if preserve_times {
+        let sidecar = std::mem::take(&mut *times.lock().unwrap());
+        upload_times_sidecar(client, &sidecar, &base).await?;
     }
-    Ok(base)
+    Ok(Uploaded {
+        path: base,
+        checksums,
+        count,
+    })
 }
 
-/// Upload a single file with a progress bar.
-async fn upload_single(client: &ChrisClient, file: Utf8PathBuf, base: &str) -> eyre::Result<()> {
-    let file_name = file.file_name().unwrap_or(file.as_str()).to_string();
-    let upload_name = format!("{}/{}", base, file_name);
-    let content_length = fs_err::tokio::metadata(&file).await?.len();
-    let open_file = fs_err::tokio::File::open(&file).await?;
-    let pb = progress_bar_bytes(content_length);
-    let stream = FramedRead::new(pb.wrap_async_read(open_file), BytesCodec::new());
+/// Upload a [TimesSidecar] with the modification times of uploaded files.
+async fn upload_times_sidecar(
+    client: &ChrisClient,
+    sidecar: &TimesSidecar,
+    base: &str,
+) -> eyre::Result<()> {
+    let data = sidecar.to_json()?;
+    let content_length = data.len() as u64;
+    let stream = futures::stream::iter([Ok::<_, std::io::Error>(data)]);
+    let upload_name = format!("{}/{}", base, TIMES_SIDECAR_NAME);
     client
-        .upload_stream(stream, file_name, upload_name, content_length)
+        .upload_stream(stream, TIMES_SIDECAR_NAME, upload_name, content_length)
         .await?;
     Ok(())
 }
 
-/// Upload multiple files with progress bars.
+/// Upload a single file to `base/rel` with a progress bar, returning its SHA-256.
+async fn upload_single(
+    client: &ChrisClient,
+    file: DiscoveredFile,
+    rel: &str,
+    base: &str,
+    cancellation: &Cancellation,
+) -> eyre::Result<String> {
+    let file_name = file
+        .path
+        .file_name()
+        .unwrap_or(file.path.as_str())
+        .to_string();
+    let upload_name = format!("{}/{}", base, rel);
+    let content_length = file.metadata.len();
+    let open_file = fs_err::tokio::File::open(&file.path).await?;
+    let pb = progress_bar_bytes(content_length);
+    let hasher = Hasher::default();
+    let stream = hasher.inspect_stream(FramedRead::new(
+        pb.wrap_async_read(open_file),
+        BytesCodec::new(),
+    ));
+    let upload = client.upload_stream(stream, file_name, upload_name, content_length);
+    if cancellation.run(None, upload).await?.is_none() {
+        pb.abandon();
+        cancellation.check(1)?;
+    }
+    Ok(hasher.hex())
+}
+
+/// Upload multiple files with progress bars, starting as soon as files are discovered.
+/// Returns the checksums of the files if `checksum`, and the number of files.
 async fn upload_multiple(
     client: &ChrisClient,
-    files: Vec<DiscoveredFile>,
+    files: impl Stream<Item = eyre::Result<DiscoveredFile>>,
     base: &str,
-    threads: usize,
-) -> eyre::Result<()> {
+    concurrency: Concurrency,
+    checksum: bool,
+    cancellation: &Cancellation,
+) -> eyre::Result<(Checksums, usize)> {
     let (tx, mut rx) = unbounded_channel();
-    let total = files.len() as u64;
+    let limiter = AdaptiveLimiter::new(concurrency.threads, concurrency.adaptive, Some(tx.clone()));
     let transfer_progress_loop = async {
-        let mut transfer_progress =
-            MultiFileTransferProgress::new(total, crate::file_transfer::SIZE_128_MIB);
+        // the total is sent when all files were discovered
+        let mut transfer_progress = MultiFileTransferProgress::new(
+            None,
+            crate::file_transfer::SIZE_128_MIB,
+            concurrency.progress,
+        );
         while let Some(event) = rx.recv().await {
             transfer_progress.update(event)
         }
+        transfer_progress.finish();
     };
+    let discovered = AtomicUsize::new(0);
+    let discovered_ref = &discovered;
     let upload_loop = async move {
         // I am wrapped in an async move to drop tx after all transfers are complete
-        futures::stream::iter(files)
+        let limiter = &limiter;
+        let total_tx = tx.clone();
+        files
+            .map(Some)
+            .chain(futures::stream::iter([None]))
             .enumerate()
-            .map(Ok::<_, chris::errors::FileIOError>)
-            .try_for_each_concurrent(threads, |(i, file)| {
-                upload_with_events(client, base, file, i, tx.clone())
+            .filter_map(move |(i, file)| {
+                // the end of the files is marked by None
+                match &file {
+                    Some(Ok(file)) => {
+                        let size = file.metadata.len() as i64;
+                        total_tx
+                            .send(FileTransferEvent::TotalAdjusted(size))
+                            .unwrap();
+                    }
+                    Some(Err(_)) => (),
+                    None => {
+                        discovered_ref.store(i, Ordering::Relaxed);
+                        total_tx.send(FileTransferEvent::Total(i as u64)).unwrap();
+                    }
+                }
+                futures::future::ready(file.map(|file| (i, file)))
             })
+            .map(|(i, file)| {
+                let tx = tx.clone();
+                async move {
+                    // errors of discovery are fatal, errors of uploads are collected
+                    let file = file?;
+                    let permit = limiter.acquire().await;
+                    let path = file.path.clone();
+                    let rel = file.to_relative();
+                    let upload = upload_with_events(client, base, file, i, tx, concurrency.retries);
+                    let result = cancellation.run(None, upload).await;
+                    permit.report(Outcome::of_upload(&result));
+                    let checksum = result.map(|sha256| {
+                        sha256
+                            .filter(|_| checksum)
+                            .map(|sha256| Checksum { sha256, path: rel })
+                    });
+                    Ok::<_, eyre::Error>((path, checksum))
+                }
+            })
+            .buffer_unordered(concurrency.threads)
+            .try_collect::<Vec<_>>()
             .await
     };
-    let (_, result) = join!(transfer_progress_loop, upload_loop);
-    result.map_err(eyre::Error::new)
+    let (_, results) = join!(transfer_progress_loop, upload_loop);
+    let results = results?;
+    let count = discovered.into_inner();
+    cancellation.check(count)?;
+    let mut checksums = Checksums::default();
+    let mut failures = Vec::new();
+    for (path, result) in results {
+        match result {
+            Ok(checksum) => checksums.extend(checksum),
+            Err(e) => failures.push((path, e)),
+        }
+    }
+    if !failures.is_empty() {
+        eprintln!(
+            "{} {} of {} files were not uploaded:",
+            "error:".red(),
+            failures.len(),
+            count
+        );
+        for (path, e) in &failures {
+            eprintln!("    {}: {}", path, e);
+        }
+        bail!("Failed to upload {} files", failures.len())
+    }
+    Ok((checksums, count))
 }
 
+/// Upload a file while pushing events through a channel, returning its SHA-256.
+///
+/// If the upload fails because of a server error or timeout, the file is uploaded
+/// again, up to `retries` times. Files up to [ChrisClient::replayable_upload_limit]
+/// are retried by the middleware of the client instead, and their bytes are counted
+/// as sent once they are read into memory.
 async fn upload_with_events(
     client: &ChrisClient,
     base: &str,
     file: DiscoveredFile,
     id: usize,
     tx: UnboundedSender<FileTransferEvent>,
-) -> Result<(), chris::errors::FileIOError> {
+    retries: u32,
+) -> Result<String, FileIOError> {
     let file_name = file
         .path
         .file_name()
         .unwrap_or(file.path.as_str())
         .to_string();
-    let rel = file.to_relative();
-    let upload_name = format!("{}/{}", base, rel);
-    let content_length = fs_err::tokio::metadata(&file.path).await?.len();
-    let open_file = fs_err::tokio::File::open(&file.path).await?;
-    let chunk_tx = tx.clone();
-    let stream = FramedRead::new(open_file, BytesCodec::new()).map_ok(move |chunk| {
-        chunk_tx
-            .send(FileTransferEvent::Chunk {
-                id,
-                delta: chunk.len() as u64,
-            })
-            .unwrap();
-        chunk
-    });
+    let upload_name = format!("{}/{}", base, file.to_relative());
     tx.send(FileTransferEvent::Start {
         id,
         name: file_name.to_string(),
-        size: content_length,
+        size: file.metadata.len(),
     })
     .unwrap();
-    client
-        .upload_stream(stream, file_name, upload_name, content_length)
-        .await?;
+    let retries = if file.metadata.len() <= client.replayable_upload_limit() {
+        0
+    } else {
+        retries
+    };
+    let mut attempt = 0;
+    let sha256 = loop {
+        let sent = Arc::new(AtomicU64::new(0));
+        let upload = UploadAttempt {
+            client,
+            file: &file,
+            file_name: &file_name,
+            upload_name: &upload_name,
+            id,
+        };
+        let result = upload.run(&tx, Arc::clone(&sent)).await;
+        if attempt < retries && Outcome::of_upload(&result) == Outcome::Overloaded {
+            attempt += 1;
+            tx.send(FileTransferEvent::Retry {
+                id,
+                discarded: sent.load(Ordering::Relaxed),
+            })
+            .unwrap();
+            tokio::time::sleep(retry_delay(attempt)).await;
+        } else {
+            break result?;
+        }
+    };
     tx.send(FileTransferEvent::Done(id)).unwrap();
-    Ok(())
+    Ok(sha256)
+}
+
+/// One try of [upload_with_events].
+struct UploadAttempt<'a> {
+    client: &'a ChrisClient,
+    file: &'a DiscoveredFile,
+    file_name: &'a str,
+    upload_name: &'a str,
+    id: usize,
+}
+
+impl UploadAttempt<'_> {
+    /// Upload the file from its start, counting the bytes sent in `sent`.
+    async fn run(
+        &self,
+        tx: &UnboundedSender<FileTransferEvent>,
+        sent: Arc<AtomicU64>,
+    ) -> Result<String, FileIOError> {
+        let id = self.id;
+        let open_file = fs_err::tokio::File::open(&self.file.path).await?;
+        let chunk_tx = tx.clone();
+        let hasher = Hasher::default();
+        let chunks = hasher.inspect_stream(FramedRead::new(open_file, BytesCodec::new()));
+        let stream = chunks.map_ok(move |chunk| {
+            let delta = chunk.len() as u64;
+            sent.fetch_add(delta, Ordering::Relaxed);
+            chunk_tx
+                .send(FileTransferEvent::Chunk { id, delta })
+                .unwrap();
+            chunk
+        });
+        self.client
+            .upload_stream(
+                stream,
+                self.file_name.to_string(),
+                self.upload_name.to_string(),
+                self.file.metadata.len(),
+            )
+            .await?;
+        Ok(hasher.hex())
+    }
+}
+
+/// How long to wait before the `attempt`-th retry of an upload.
+fn retry_delay(attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(6);
+    Duration::from_millis(500 * 2u64.pow(exponent))
+}
+
+/// Folder to upload files to: `<username>/uploads/<name>` if a name is given,
+/// otherwise a new folder from [create_upload_root_for].
+fn upload_root(client: &ChrisClient, name: Option<&str>) -> String {
+    match name.map(|n| n.trim_matches('/')) {
+        Some(name) => format!("{}/uploads/{}", client.username().as_str(), name),
+        None => create_upload_root_for(client),
+    }
+}
+
+/// Leave out files which were already uploaded to `base`, i.e. a file with the same
+/// path and size exists there. The number of files which were left out is counted
+/// in `skipped`.
+fn skip_existing<'a>(
+    client: &'a ChrisClient,
+    files: impl Stream<Item = eyre::Result<DiscoveredFile>> + Send + 'a,
+    base: &'a str,
+    threads: usize,
+    skipped: &'a AtomicUsize,
+) -> impl Stream<Item = eyre::Result<DiscoveredFile>> + Send + 'a {
+    files
+        .map_ok(move |file| async move {
+            let uploaded = is_uploaded(client, &file, base).await?;
+            if uploaded {
+                skipped.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok((!uploaded).then_some(file))
+        })
+        .try_buffered(threads.max(1))
+        .try_filter_map(futures::future::ok)
+}
+
+/// Whether a file with the same path under `base` and the same size was already uploaded.
+async fn is_uploaded(
+    client: &ChrisClient,
+    file: &DiscoveredFile,
+    base: &str,
+) -> eyre::Result<bool> {
+    let fname = format!("{}/{}", base, file.to_relative());
+    let existing = client
+        .userfiles()
+        .fname_exact(fname)
+        .search()
+        .get_first()
+        .await?;
+    Ok(existing.is_some_and(|f| f.object.fsize() == file.metadata.len()))
 }
 
 fn create_upload_root_for(client: &ChrisClient) -> String {
@@ -330,6 +896,8 @@ struct DiscoveredFile {
     path: Utf8PathBuf,
     /// Positional argument path which this file was discovered under
     src: Utf8PathBuf,
+    /// Metadata of the file, read while it was discovered
+    metadata: std::fs::Metadata,
 }
 
 impl DiscoveredFile {
@@ -347,43 +915,75 @@ impl DiscoveredFile {
     }
 }
 
-/// Collect all files in a set of paths.
-async fn discover_files(paths: Vec<Utf8PathBuf>) -> Result<Vec<DiscoveredFile>, std::io::Error> {
+/// Files found by [discover_files], received as they are found.
+type DiscoveredFiles = ReceiverStream<std::io::Result<DiscoveredFile>>;
+
+/// Maximum number of discovered files which are waiting to be uploaded.
+const DISCOVERY_BUFFER: usize = 256;
+
+/// Find all files in a set of paths. The paths must exist. Directories are walked
+/// in the background, sending their files as they are found.
+async fn discover_files(paths: Vec<Utf8PathBuf>) -> Result<DiscoveredFiles, std::io::Error> {
     let either_file_or_dir: Vec<(std::fs::Metadata, Utf8PathBuf)> = futures::stream::iter(paths)
         .map(|p| async move { fs_err::tokio::metadata(&p).await.map(|m| (m, p)) })
         .map(Ok::<_, std::io::Error>)
         .try_buffer_unordered(100)
         .try_collect()
         .await?;
-    let dirs = either_file_or_dir
-        .iter()
-        .filter_map(|(m, p)| if m.is_dir() { Some(p) } else { None });
-    let mut subdir_files = futures::stream::iter(dirs)
-        .flat_map_unordered(None, |src| {
-            WalkDir::new(src).map_ok(|entry| (src.as_path(), entry))
-        })
-        .try_fold(vec![], file_entries_reducer)
-        .await?;
-    let files = either_file_or_dir
+    let (tx, rx) = channel(DISCOVERY_BUFFER);
+    tokio::spawn(async move {
+        if let Err(e) = send_files(either_file_or_dir, &tx).await {
+            // fails if the receiver was dropped, which means nobody needs the error
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+    Ok(ReceiverStream::new(rx))
+}
+
+/// Send the files of `either_file_or_dir`, and the files under its directories.
+/// Stops early if the receiver is dropped.
+async fn send_files(
+    either_file_or_dir: Vec<(std::fs::Metadata, Utf8PathBuf)>,
+    tx: &Sender<std::io::Result<DiscoveredFile>>,
+) -> Result<(), std::io::Error> {
+    let (files, dirs): (Vec<_>, Vec<_>) = either_file_or_dir
         .into_iter()
-        .filter_map(|(metadata, path)| {
-            if metadata.is_file() {
-                Some(DiscoveredFile {
-                    src: path.clone(),
-                    path,
-                })
-            } else {
-                None
+        .partition(|(metadata, _)| metadata.is_file());
+    for (metadata, path) in files {
+        let file = DiscoveredFile {
+            src: path.clone(),
+            path,
+            metadata,
+        };
+        if tx.send(Ok(file)).await.is_err() {
+            return Ok(());
+        }
+    }
+    let dirs = dirs.into_iter().filter(|(m, _)| m.is_dir()).map(|(_, p)| p);
+    let entries = futures::stream::iter(dirs).flat_map_unordered(None, walk_dir);
+    let mut entries = std::pin::pin!(entries);
+    while let Some((src, entry)) = entries.try_next().await? {
+        if let Some(file) = discovered_file(&src, entry).await? {
+            if tx.send(Ok(file)).await.is_err() {
+                break;
             }
-        });
-    subdir_files.extend(files);
-    Ok(subdir_files)
+        }
+    }
+    Ok(())
 }
 
-async fn file_entries_reducer(
-    mut all_files: Vec<DiscoveredFile>,
-    (src, entry): (&Utf8Path, async_walkdir::DirEntry),
-) -> Result<Vec<DiscoveredFile>, std::io::Error> {
+/// Walk a directory, pairing every entry with the directory.
+fn walk_dir(
+    src: Utf8PathBuf,
+) -> impl Stream<Item = std::io::Result<(Utf8PathBuf, async_walkdir::DirEntry)>> {
+    WalkDir::new(&src).map_ok(move |entry| (src.clone(), entry))
+}
+
+/// Get the [DiscoveredFile] of an entry found by walking `src`, if it is a file.
+async fn discovered_file(
+    src: &Utf8Path,
+    entry: async_walkdir::DirEntry,
+) -> Result<Option<DiscoveredFile>, std::io::Error> {
     let file_type = entry.file_type().await?;
     let path = Utf8PathBuf::from_path_buf(entry.path()).map_err(|_| {
         std::io::Error::new(
@@ -391,13 +991,15 @@ async fn file_entries_reducer(
             eyre!("Path is invalid UTF-8: {:?}", entry.path()),
         )
     })?;
-    if file_type.is_file() {
-        all_files.push(DiscoveredFile {
-            src: src.to_path_buf(),
-            path,
-        });
+    if !file_type.is_file() {
+        return Ok(None);
     }
-    Ok(all_files)
+    let metadata = entry.metadata().await?;
+    Ok(Some(DiscoveredFile {
+        src: src.to_path_buf(),
+        path,
+        metadata,
+    }))
 }
 
 async fn find_plugins(
@@ -445,7 +1047,13 @@ async fn find_plugins(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chris::pipeline::{CanonPipeline, TitleIndexedPipeline};
+    use chris::testing::MockCube;
+    use clap::Parser;
     use rstest::*;
+    use tempfile::TempDir;
+
+    use crate::run::tests::{credentials, uuid_name};
 
     #[rstest]
     #[case("a", "a", "a")]
@@ -460,8 +1068,242 @@ mod tests {
         let discovered = DiscoveredFile {
             path: Utf8PathBuf::from(path),
             src: Utf8PathBuf::from(src),
+            metadata: fs_err::metadata(env!("CARGO_MANIFEST_DIR")).unwrap(),
         };
         let actual = discovered.to_relative();
         assert_eq!(&actual, expected);
     }
+
+    #[rstest]
+    #[case(1, 500)]
+    #[case(2, 1000)]
+    #[case(3, 2000)]
+    #[case(100, 32000)]
+    fn test_retry_delay(#[case] attempt: u32, #[case] expected_millis: u64) {
+        assert_eq!(retry_delay(attempt), Duration::from_millis(expected_millis))
+    }
+
+    /// Discover files, returning their relative paths and sizes.
+    async fn discover_all(paths: Vec<Utf8PathBuf>) -> Vec<(String, u64)> {
+        let files: Vec<_> = discover_files(paths)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        files
+            .iter()
+            .map(|f| (f.to_relative(), f.metadata.len()))
+            .sorted()
+            .collect()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_discover_files() {
+        let tmp = TempDir::new().unwrap();
+        let root = Utf8PathBuf::from_path_buf(tmp.path().to_path_buf()).unwrap();
+        let data = root.join("data");
+        fs_err::create_dir_all(data.join("sub")).unwrap();
+        fs_err::create_dir_all(data.join("empty")).unwrap();
+        fs_err::write(data.join("a.txt"), "a").unwrap();
+        fs_err::write(data.join("sub/b.txt"), "bb").unwrap();
+        fs_err::write(root.join("single.txt"), "ccc").unwrap();
+
+        let actual = discover_all(vec![data.clone(), root.join("single.txt")]).await;
+        let expected = vec![
+            ("a.txt".to_string(), 1),
+            ("single.txt".to_string(), 3),
+            ("sub/b.txt".to_string(), 2),
+        ];
+        assert_eq!(actual, expected);
+        assert!(discover_all(vec![data.join("empty")]).await.is_empty());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_discover_more_files_than_buffer() {
+        let tmp = TempDir::new().unwrap();
+        let root = Utf8PathBuf::from_path_buf(tmp.path().to_path_buf()).unwrap();
+        let count = DISCOVERY_BUFFER * 2 + 1;
+        for i in 0..count {
+            fs_err::write(root.join(format!("{}.txt", i)), "").unwrap();
+        }
+        assert_eq!(discover_all(vec![root]).await.len(), count);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_discover_missing_path() {
+        let tmp = TempDir::new().unwrap();
+        let missing = Utf8PathBuf::from_path_buf(tmp.path().join("missing")).unwrap();
+        assert!(discover_files(vec![missing]).await.is_err());
+    }
+
+    #[rstest]
+    #[case("chris/uploads/data", Some("chris/uploads/data"))]
+    #[case("/chris/uploads/data/", Some("chris/uploads/data"))]
+    #[case("chris/uploads/missing", None)]
+    #[tokio::test]
+    async fn test_from_existing_folder(#[case] given: &str, #[case] expected: Option<&str>) {
+        let mock = MockCube::start().await;
+        mock.add_file("chris/uploads/data/a.txt", "a");
+        let client = mock.client("chris").await;
+        let actual = existing_folder(&client, given).await;
+        match expected {
+            Some(expected) => assert_eq!(actual.unwrap(), expected),
+            None => assert!(actual.unwrap_err().to_string().contains("Path not found")),
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_from_with_local_paths_is_rejected() {
+        let mock = MockCube::start().await;
+        let client = mock.client("chris").await;
+        let connected = mock.requests().len();
+        let args =
+            UploadArgs::try_parse_from(["upload", "--from", "chris/uploads/data", "local.txt"])
+                .unwrap();
+        let concurrency = Concurrency::of(&args, ProgressFormat::Quiet, 0);
+        let error = upload_logged_in(client, None, None, args, concurrency, None, false)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("--from"), "{error}");
+        assert_eq!(mock.requests().len(), connected, "nothing was done");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_skip_existing() {
+        let mock = MockCube::start().await;
+        mock.add_file("chris/uploads/resume/a.txt", "aa");
+        mock.add_file("chris/uploads/resume/b.txt", "b");
+        let client = mock.client("chris").await;
+        let tmp = TempDir::new().unwrap();
+        let root = Utf8PathBuf::from_path_buf(tmp.path().to_path_buf()).unwrap();
+        fs_err::create_dir_all(root.join("sub")).unwrap();
+        fs_err::write(root.join("a.txt"), "aa").unwrap(); // same size, skipped
+        fs_err::write(root.join("b.txt"), "bbb").unwrap(); // different size
+        fs_err::write(root.join("sub/c.txt"), "c").unwrap(); // not uploaded
+        let files = discover_files(vec![root])
+            .await
+            .unwrap()
+            .map_err(eyre::Error::new);
+        let skipped = AtomicUsize::new(0);
+        let remaining: Vec<_> = skip_existing(&client, files, "chris/uploads/resume", 2, &skipped)
+            .map_ok(|f| f.to_relative())
+            .try_collect()
+            .await
+            .unwrap();
+        let remaining: Vec<_> = remaining.into_iter().sorted().collect();
+        assert_eq!(remaining, vec!["b.txt", "sub/c.txt"]);
+        assert_eq!(skipped.into_inner(), 1);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_retried_upload_succeeds() {
+        let mock = MockCube::start().await;
+        mock.fail_next_posts(1);
+        let client = mock.client("chris").await;
+        let tmp = TempDir::new().unwrap();
+        let root = Utf8PathBuf::from_path_buf(tmp.path().to_path_buf()).unwrap();
+        let path = root.join("hello.txt");
+        fs_err::write(&path, "hello").unwrap();
+        let file = DiscoveredFile {
+            metadata: fs_err::metadata(&path).unwrap(),
+            path,
+            src: root,
+        };
+        let (tx, mut rx) = unbounded_channel();
+        let sha256 = upload_with_events(&client, "chris/uploads/test", file, 0, tx, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(mock.requests_of("POST").len(), 2);
+        let mut retried = false;
+        while let Ok(event) = rx.try_recv() {
+            retried |= matches!(
+                event,
+                FileTransferEvent::Retry {
+                    id: 0,
+                    discarded: 5
+                }
+            );
+        }
+        assert!(retried);
+        let uploaded = client
+            .userfiles()
+            .fname_exact("chris/uploads/test/hello.txt")
+            .search()
+            .get_first()
+            .await
+            .unwrap();
+        assert!(uploaded.is_some());
+    }
+
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_upload_and_run_pipeline(credentials: &Credentials) {
+        let client = credentials
+            .clone()
+            .get_client(NO_ARGS)
+            .await
+            .unwrap()
+            .0
+            .logged_in()
+            .unwrap();
+        let pipeline_name = uuid_name("upload pipeline");
+        let yaml = format!(
+            r#"
+name: "{}"
+authors: chrs
+category: test
+description: Run after chrs upload
+locked: false
+plugin_tree:
+  - title: convert
+    plugin: pl-dcm2niix v0.1.0
+    previous: null
+"#,
+            pipeline_name
+        );
+        let pipeline: TitleIndexedPipeline = serde_yaml::from_str(&yaml).unwrap();
+        client
+            .create_pipeline(&CanonPipeline::try_from(pipeline).unwrap())
+            .await
+            .unwrap();
+
+        let tmp = TempDir::new().unwrap();
+        let file = Utf8PathBuf::from_path_buf(tmp.path().join("hello.txt")).unwrap();
+        fs_err::write(&file, "hello").unwrap();
+        let feed_name = uuid_name("upload with pipeline");
+        let args = UploadArgs::try_parse_from([
+            "upload",
+            "--feed",
+            &feed_name,
+            "--pipeline",
+            &pipeline_name,
+            file.as_str(),
+        ])
+        .unwrap();
+        upload(credentials.clone(), args, ProgressFormat::Bars)
+            .await
+            .unwrap();
+
+        let feed = client
+            .feeds()
+            .name_exact(&feed_name)
+            .search()
+            .get_only()
+            .await
+            .unwrap();
+        let count = feed.get_plugin_instances().get_count().await.unwrap();
+        // pl-dircopy, pl-unstack-folders, and the piping of the pipeline
+        assert_eq!(count, 3);
+    }
 }
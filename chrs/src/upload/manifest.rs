@@ -0,0 +1,296 @@
+//! Batch-creation of feeds from a CSV manifest, see `chrs upload --manifest`.
+
+use std::io::{Read, Write};
+
+use color_eyre::eyre::{self, bail, eyre};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use chris::types::{FeedId, PluginInstanceId};
+
+/// Columns of a manifest. The first two are required.
+const MANIFEST_COLUMNS: [&str; 3] = ["path", "feed_name", "pipeline"];
+
+/// A row of the manifest CSV.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub(crate) struct ManifestRow {
+    /// Local path to upload.
+    pub path: String,
+    /// Name of the feed to create.
+    pub feed_name: String,
+    /// Pipeline to run after the files are ingested.
+    #[serde(default)]
+    pub pipeline: Option<String>,
+}
+
+/// A [ManifestRow] and the line of the manifest it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ManifestEntry {
+    pub line: u64,
+    pub row: ManifestRow,
+}
+
+/// What was created in _CUBE_ for a row.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Ingested {
+    pub feed: FeedId,
+    /// The last plugin instance, i.e. of the pipeline if one was run.
+    pub plugin_instance: PluginInstanceId,
+}
+
+/// Creates a feed for a row of the manifest.
+pub(crate) trait FeedIngester {
+    async fn ingest(&self, row: &ManifestRow) -> eyre::Result<Ingested>;
+}
+
+/// A row of the results CSV.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct RowResult {
+    pub line: u64,
+    pub path: String,
+    pub feed_name: String,
+    pub feed_id: Option<u32>,
+    pub plugin_instance_id: Option<u32>,
+    pub status: RowStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RowStatus {
+    Created,
+    Failed,
+}
+
+impl RowResult {
+    fn new(entry: ManifestEntry, result: eyre::Result<Ingested>) -> Self {
+        let (ingested, error) = match result {
+            Ok(ingested) => (Some(ingested), None),
+            // alternate formatting includes the causes of the error on one line
+            Err(e) => (None, Some(format!("{e:#}"))),
+        };
+        Self {
+            line: entry.line,
+            path: entry.row.path,
+            feed_name: entry.row.feed_name,
+            feed_id: ingested.map(|i| i.feed.0),
+            plugin_instance_id: ingested.map(|i| i.plugin_instance.0),
+            status: if error.is_none() {
+                RowStatus::Created
+            } else {
+                RowStatus::Failed
+            },
+            error,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == RowStatus::Created
+    }
+}
+
+/// Read and validate a manifest.
+pub(crate) fn read_manifest(reader: impl Read) -> eyre::Result<Vec<ManifestEntry>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    if let Some(unknown) = headers.iter().find(|h| !MANIFEST_COLUMNS.contains(h)) {
+        bail!(
+            "line 1: unknown column \"{}\", columns must be: {}",
+            unknown,
+            MANIFEST_COLUMNS.join(", ")
+        )
+    }
+    if let Some(missing) = MANIFEST_COLUMNS[..2]
+        .iter()
+        .find(|c| !headers.iter().any(|h| &h == *c))
+    {
+        bail!("line 1: missing required column \"{}\"", missing)
+    }
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let row: ManifestRow = record
+            .deserialize(Some(&headers))
+            .map_err(|e| eyre!("line {}: {}", line, e))?;
+        if row.path.is_empty() {
+            bail!("line {}: path is empty", line)
+        }
+        if row.feed_name.is_empty() {
+            bail!("line {}: feed_name is empty", line)
+        }
+        entries.push(ManifestEntry { line, row });
+    }
+    if entries.is_empty() {
+        bail!("Manifest has no rows")
+    }
+    Ok(entries)
+}
+
+fn csv_error(error: csv::Error) -> eyre::Error {
+    if let Some(line) = error.position().map(|p| p.line()) {
+        eyre!("line {}: {}", line, error)
+    } else {
+        eyre::Error::new(error)
+    }
+}
+
+/// Create a feed for every entry, with up to `parallel` at a time. Failure to create
+/// a feed does not stop the others from being created.
+///
+/// `on_done` is called as each row is finished. Results are returned in manifest order.
+pub(crate) async fn run_manifest(
+    ingester: &impl FeedIngester,
+    entries: Vec<ManifestEntry>,
+    parallel: usize,
+    mut on_done: impl FnMut(&RowResult),
+) -> Vec<RowResult> {
+    futures::stream::iter(entries)
+        .map(|entry| async move {
+            let result = ingester.ingest(&entry.row).await;
+            RowResult::new(entry, result)
+        })
+        .buffered(parallel.max(1))
+        .inspect(|result| on_done(result))
+        .collect()
+        .await
+}
+
+/// Write the results CSV.
+pub(crate) fn write_results(writer: impl Write, results: &[RowResult]) -> eyre::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for result in results {
+        writer.serialize(result)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::cell::RefCell;
+
+    /// Creates feeds whose ID is the number in the path, e.g. "sub-03" creates feed/3.
+    /// Fails for paths which start with "bad".
+    #[derive(Default)]
+    struct FakeCube {
+        created: RefCell<Vec<ManifestRow>>,
+    }
+
+    impl FeedIngester for FakeCube {
+        async fn ingest(&self, row: &ManifestRow) -> eyre::Result<Ingested> {
+            tokio::task::yield_now().await;
+            if row.path.starts_with("bad") {
+                bail!("No such file or directory: {}", row.path)
+            }
+            self.created.borrow_mut().push(row.clone());
+            let n: u32 = row.path["sub-".len()..].parse().unwrap();
+            let plugin_instance = if row.pipeline.is_some() {
+                n * 10 + 9
+            } else {
+                n * 10
+            };
+            Ok(Ingested {
+                feed: FeedId(n),
+                plugin_instance: PluginInstanceId(plugin_instance),
+            })
+        }
+    }
+
+    fn entry(line: u64, path: &str, feed_name: &str, pipeline: Option<&str>) -> ManifestEntry {
+        ManifestEntry {
+            line,
+            row: ManifestRow {
+                path: path.to_string(),
+                feed_name: feed_name.to_string(),
+                pipeline: pipeline.map(|s| s.to_string()),
+            },
+        }
+    }
+
+    #[rstest]
+    fn test_read_manifest() {
+        let csv = "path,feed_name,pipeline\n\
+                   sub-01, Subject 01 ,\n\
+                   \"sub-02\",\"Subject 02, session 1\",Fetal Brain Reconstruction\n";
+        let actual = read_manifest(csv.as_bytes()).unwrap();
+        let expected = vec![
+            entry(2, "sub-01", "Subject 01", None),
+            entry(
+                3,
+                "sub-02",
+                "Subject 02, session 1",
+                Some("Fetal Brain Reconstruction"),
+            ),
+        ];
+        assert_eq!(actual, expected)
+    }
+
+    #[rstest]
+    fn test_read_manifest_pipeline_column_is_optional() {
+        let csv = "feed_name,path\nSubject 01,sub-01\n";
+        let actual = read_manifest(csv.as_bytes()).unwrap();
+        assert_eq!(actual, vec![entry(2, "sub-01", "Subject 01", None)])
+    }
+
+    #[rstest]
+    #[case("path,name\nsub-01,Subject 01\n", "line 1: unknown column \"name\"")]
+    #[case("path\nsub-01\n", "line 1: missing required column \"feed_name\"")]
+    #[case("path,feed_name\nsub-01,a\nsub-02\n", "line 3:")]
+    #[case("path,feed_name\nsub-01,a\n,b\n", "line 3: path is empty")]
+    #[case("path,feed_name\nsub-01,\n", "line 2: feed_name is empty")]
+    #[case("path,feed_name\n", "no rows")]
+    fn test_read_bad_manifest(#[case] csv: &str, #[case] expected: &str) {
+        let error = read_manifest(csv.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains(expected), "{error}")
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(3)]
+    #[tokio::test]
+    async fn test_run_manifest_continues_past_failures(#[case] parallel: usize) {
+        let cube = FakeCube::default();
+        let entries = vec![
+            entry(2, "sub-01", "Subject 01", None),
+            entry(3, "bad-02", "Subject 02", None),
+            entry(4, "sub-03", "Subject 03", Some("my pipeline")),
+        ];
+        let mut done = 0;
+        let results = run_manifest(&cube, entries, parallel, |_| done += 1).await;
+        assert_eq!(done, 3);
+        assert_eq!(cube.created.borrow().len(), 2);
+
+        let lines: Vec<_> = results.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![2, 3, 4]);
+        assert!(results[0].is_ok());
+        assert_eq!(results[0].feed_id, Some(1));
+        assert_eq!(results[0].plugin_instance_id, Some(10));
+        assert!(!results[1].is_ok());
+        assert_eq!(results[1].feed_id, None);
+        assert!(results[1].error.as_ref().unwrap().contains("bad-02"));
+        assert_eq!(results[2].feed_id, Some(3));
+        assert_eq!(results[2].plugin_instance_id, Some(39));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_write_results() {
+        let cube = FakeCube::default();
+        let entries = vec![
+            entry(2, "sub-01", "Subject 01", None),
+            entry(3, "bad-02", "Subject, 02", None),
+        ];
+        let results = run_manifest(&cube, entries, 1, |_| ()).await;
+        let mut out = Vec::new();
+        write_results(&mut out, &results).unwrap();
+        let expected = "line,path,feed_name,feed_id,plugin_instance_id,status,error\n\
+                        2,sub-01,Subject 01,1,10,created,\n\
+                        3,bad-02,\"Subject, 02\",,,failed,No such file or directory: bad-02\n";
+        assert_eq!(String::from_utf8(out).unwrap(), expected)
+    }
+}
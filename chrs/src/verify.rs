@@ -0,0 +1,237 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{bail, eyre, Result};
+use color_eyre::owo_colors::OwoColorize;
+
+use crate::file_transfer::{Checksums, Manifest, ManifestStatus, Verification, MANIFEST_NAME};
+
+/// `chrs verify` command: check local files against checksums written by
+/// `chrs upload --checksum` or `chrs download --checksum`, or their sizes against
+/// the manifest written by `chrs download --manifest`. Does not use the network.
+pub async fn verify(manifest: Utf8PathBuf, dir: Option<Utf8PathBuf>) -> Result<()> {
+    let manifest = if fs_err::tokio::metadata(&manifest).await?.is_dir() {
+        manifest.join(MANIFEST_NAME)
+    } else {
+        manifest
+    };
+    if manifest.file_name() == Some(MANIFEST_NAME) {
+        return verify_sizes(manifest, dir).await;
+    }
+    let text = fs_err::tokio::read_to_string(&manifest).await?;
+    let checksums =
+        Checksums::parse(&text).map_err(|e| eyre!("Invalid checksums file {}: {}", manifest, e))?;
+    let dir = dir.unwrap_or_else(|| default_dir_of(&manifest));
+    let verification = checksums.verify(&dir).await?;
+    for path in &verification.mismatched {
+        println!("{}: {}", path, "FAILED".red());
+    }
+    for path in &verification.missing {
+        println!("{}: {}", path, "MISSING".red());
+    }
+    summarize(&verification)
+}
+
+/// Check the sizes of downloaded files against a manifest written by `chrs download --manifest`.
+async fn verify_sizes(manifest: Utf8PathBuf, dir: Option<Utf8PathBuf>) -> Result<()> {
+    let text = fs_err::tokio::read_to_string(&manifest).await?;
+    let parsed =
+        Manifest::parse(&text).map_err(|e| eyre!("Invalid manifest {}: {}", manifest, e))?;
+    if let Some(reason) = incomplete_reason(parsed.status) {
+        eprintln!(
+            "{} {}, so the manifest might not list every file",
+            "warning:".yellow(),
+            reason
+        );
+    }
+    let dir = dir.unwrap_or_else(|| default_dir_of(&manifest));
+    let mut verification = Verification::default();
+    for file in &parsed.files {
+        match fs_err::tokio::metadata(dir.join(&file.path)).await {
+            Ok(metadata) if metadata.len() == file.fsize => verification.ok += 1,
+            Ok(metadata) => {
+                verification.mismatched.push(file.path.clone());
+                println!(
+                    "{}: {} (expected {} bytes, found {})",
+                    file.path,
+                    "FAILED".red(),
+                    file.fsize,
+                    metadata.len()
+                );
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                verification.missing.push(file.path.clone());
+                println!("{}: {}", file.path, "MISSING".red());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    summarize(&verification)
+}
+
+/// Why a download might not have written every file to its manifest.
+fn incomplete_reason(status: Option<ManifestStatus>) -> Option<&'static str> {
+    match status {
+        Some(ManifestStatus::Complete) => None,
+        Some(ManifestStatus::Failed) => Some("some files failed to download"),
+        Some(ManifestStatus::Interrupted) => Some("the download was interrupted"),
+        None => Some("the download did not finish"),
+    }
+}
+
+fn summarize(verification: &Verification) -> Result<()> {
+    let total = verification.ok + verification.mismatched.len() + verification.missing.len();
+    if verification.is_ok() {
+        eprintln!("{} of {} files OK", verification.ok, total);
+        Ok(())
+    } else {
+        bail!(
+            "{} of {} files failed verification ({} mismatched, {} missing)",
+            total - verification.ok,
+            total,
+            verification.mismatched.len(),
+            verification.missing.len()
+        )
+    }
+}
+
+/// The directory containing `manifest`.
+fn default_dir_of(manifest: &Utf8Path) -> Utf8PathBuf {
+    manifest
+        .parent()
+        .filter(|p| !p.as_str().is_empty())
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(|| Utf8PathBuf::from("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_transfer::{sha256_file, Checksum};
+    use rstest::*;
+    use tempfile::TempDir;
+
+    async fn write_tree(dir: &Utf8Path) -> Utf8PathBuf {
+        fs_err::tokio::create_dir_all(dir.join("data/sub"))
+            .await
+            .unwrap();
+        let mut checksums = Checksums::default();
+        for (path, content) in [("data/a.txt", "alpha"), ("data/sub/b.txt", "beta")] {
+            fs_err::tokio::write(dir.join(path), content).await.unwrap();
+            checksums.files.push(Checksum {
+                sha256: sha256_file(dir.join(path)).await.unwrap(),
+                path: path.strip_prefix("data/").unwrap().to_string(),
+            });
+        }
+        let manifest = dir.join("data").join(crate::file_transfer::CHECKSUMS_NAME);
+        fs_err::tokio::write(&manifest, checksums.to_text())
+            .await
+            .unwrap();
+        manifest
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_verify_intact() {
+        let tmp = TempDir::new().unwrap();
+        let manifest = write_tree(Utf8Path::from_path(tmp.path()).unwrap()).await;
+        verify(manifest, None).await.unwrap();
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_verify_other_dir() {
+        let tmp = TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        let manifest = write_tree(dir).await;
+        let copy = dir.join("copy");
+        fs_err::tokio::create_dir_all(copy.join("sub"))
+            .await
+            .unwrap();
+        fs_err::tokio::write(copy.join("a.txt"), "alpha")
+            .await
+            .unwrap();
+        fs_err::tokio::write(copy.join("sub/b.txt"), "bet")
+            .await
+            .unwrap();
+        let error = verify(manifest, Some(copy)).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1 of 2 files failed verification (1 mismatched, 0 missing)"
+        )
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_verify_invalid_manifest() {
+        let tmp = TempDir::new().unwrap();
+        let manifest = Utf8Path::from_path(tmp.path()).unwrap().join("bad.sha256");
+        fs_err::tokio::write(&manifest, "not a checksum\n")
+            .await
+            .unwrap();
+        let error = verify(manifest, None).await.unwrap_err();
+        assert!(error.to_string().contains("line 1"), "{error}")
+    }
+
+    async fn write_download(dir: &Utf8Path, end: &str) {
+        fs_err::tokio::create_dir_all(dir.join("sub"))
+            .await
+            .unwrap();
+        fs_err::tokio::write(dir.join("a.txt"), "alpha")
+            .await
+            .unwrap();
+        fs_err::tokio::write(dir.join("sub/b.txt"), "beta")
+            .await
+            .unwrap();
+        let manifest = format!(
+            r#"{{
+  "chrs_version": "0.0.0",
+  "cube": "https://cube.example.com/api/v1/",
+  "path": "chris/feed_1",
+  "files": [
+    {{"fname":"chris/feed_1/pl-dircopy_1/data/a.txt","path":"a.txt","fsize":5,"creation_date":null}},
+    {{"fname":"chris/feed_1/pl-dircopy_1/data/sub/b.txt","path":"sub/b.txt","fsize":4,"creation_date":null}}{end}"#
+        );
+        fs_err::tokio::write(dir.join(MANIFEST_NAME), manifest)
+            .await
+            .unwrap();
+    }
+
+    #[rstest]
+    #[case("\n  ],\n  \"status\": \"complete\"\n}\n")]
+    // not finished by chrs
+    #[case("")]
+    #[tokio::test]
+    async fn test_verify_download_manifest(#[case] end: &str) {
+        let tmp = TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        write_download(dir, end).await;
+        verify(dir.to_path_buf(), None).await.unwrap();
+        verify(dir.join(MANIFEST_NAME), None).await.unwrap();
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_verify_download_manifest_sizes() {
+        let tmp = TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        write_download(dir, "").await;
+        fs_err::tokio::write(dir.join("a.txt"), "alphabet")
+            .await
+            .unwrap();
+        fs_err::tokio::remove_file(dir.join("sub/b.txt"))
+            .await
+            .unwrap();
+        let error = verify(dir.to_path_buf(), None).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2 of 2 files failed verification (1 mismatched, 1 missing)"
+        )
+    }
+
+    #[rstest]
+    #[case("SHA256SUMS", ".")]
+    #[case("out/SHA256SUMS", "out")]
+    #[case("/tmp/x.sha256", "/tmp")]
+    fn test_default_dir_of(#[case] manifest: &str, #[case] expected: &str) {
+        assert_eq!(default_dir_of(Utf8Path::new(manifest)), expected)
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt::Display;
+
+use color_eyre::eyre::Result;
+use color_eyre::owo_colors::OwoColorize;
+
+use chris::BaseChrisClient;
+
+use crate::credentials::{Credentials, NO_ARGS};
+
+/// A feature of `chrs` which needs a minimum version of _CUBE_ to work fully.
+#[derive(Debug, PartialEq)]
+pub struct Requirement {
+    /// Name of the _CUBE_ feature
+    pub feature: &'static str,
+    /// Minimum _CUBE_ version which has the feature
+    pub minimum: CubeVersion,
+    /// What happens in `chrs` if _CUBE_ does not have the feature
+    pub degraded: &'static str,
+}
+
+/// Features of _CUBE_ which `chrs` uses, and the versions of _CUBE_ which introduced them.
+pub const COMPATIBILITY: &[Requirement] = &[
+    Requirement {
+        feature: "plugin instance splits",
+        minimum: CubeVersion::new(3, 0, 0),
+        degraded: "chrs run cannot create topological copies",
+    },
+    Requirement {
+        feature: "workflow nodes_info",
+        minimum: CubeVersion::new(4, 0, 0),
+        degraded: "chrs run --set cannot set per-piping options of pipelines",
+    },
+    Requirement {
+        feature: "public feeds",
+        minimum: CubeVersion::new(4, 0, 0),
+        degraded: "chrs list does not show whether feeds are public",
+    },
+    Requirement {
+        feature: "plugin instance output_path",
+        minimum: CubeVersion::new(4, 0, 0),
+        degraded: "chrs ls, cd, and download cannot find the files of plugin instances by path",
+    },
+    Requirement {
+        feature: "filebrowser v2",
+        minimum: CubeVersion::new(6, 0, 0),
+        degraded: "chrs ls and chrs download use slower file searches",
+    },
+];
+
+/// A version number of _CUBE_.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CubeVersion {
+    major: u32,
+    minor: u32,
+    patch: u32,
+}
+
+impl CubeVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse a version string such as "6.1.0", "v5.0", or "6.0.0-rc.2".
+    /// Pre-release suffixes are ignored.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let value = value.strip_prefix('v').unwrap_or(value);
+        let release = value.split(['-', '+']).next()?;
+        let mut numbers = release.split('.').map(|n| n.parse::<u32>());
+        let major = numbers.next()?.ok()?;
+        let minor = numbers.next().unwrap_or(Ok(0)).ok()?;
+        let patch = numbers.next().unwrap_or(Ok(0)).ok()?;
+        if numbers.next().is_some() {
+            return None;
+        }
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl Display for CubeVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Get the requirements which are not satisfied by the given version of _CUBE_.
+pub fn degraded_features(cube: CubeVersion) -> Vec<&'static Requirement> {
+    COMPATIBILITY
+        .iter()
+        .filter(|requirement| cube < requirement.minimum)
+        .collect()
+}
+
+/// `chrs version` command
+pub async fn version(credentials: Credentials, check: bool) -> Result<()> {
+    println!("chrs {}", env!("CARGO_PKG_VERSION"));
+    println!("chris {}", chris::VERSION);
+    if !check {
+        return Ok(());
+    }
+    let (client, _, _) = credentials.get_client(NO_ARGS).await?;
+    let advertised = client.server_version();
+    println!(
+        "CUBE {} {}",
+        client.url().as_str(),
+        advertised.unwrap_or("(version unknown)")
+    );
+    if let Some(cube_version) = advertised.and_then(CubeVersion::parse) {
+        print_compatibility(cube_version)
+    } else {
+        eprintln!("Could not determine the version of CUBE, so compatibility cannot be checked.")
+    }
+    Ok(())
+}
+
+fn print_compatibility(cube_version: CubeVersion) {
+    let degraded = degraded_features(cube_version);
+    if degraded.is_empty() {
+        println!("{} all features are supported", "compatible:".green());
+        return;
+    }
+    println!(
+        "{} {} features need a newer CUBE",
+        "degraded:".yellow(),
+        degraded.len()
+    );
+    for requirement in degraded {
+        println!(
+            "  - {} (CUBE {}+): {}",
+            requirement.feature.bold(),
+            requirement.minimum,
+            requirement.degraded
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("6.1.0", Some(CubeVersion::new(6, 1, 0)))]
+    #[case("v5.0", Some(CubeVersion::new(5, 0, 0)))]
+    #[case("4", Some(CubeVersion::new(4, 0, 0)))]
+    #[case(" 6.0.0-rc.2 ", Some(CubeVersion::new(6, 0, 0)))]
+    #[case("6.0.0+build.5", Some(CubeVersion::new(6, 0, 0)))]
+    #[case("1.2.3.4", None)]
+    #[case("latest", None)]
+    #[case("", None)]
+    fn test_parse(#[case] value: &str, #[case] expected: Option<CubeVersion>) {
+        assert_eq!(CubeVersion::parse(value), expected)
+    }
+
+    #[rstest]
+    #[case(CubeVersion::new(2, 9, 9), vec!["plugin instance splits", "workflow nodes_info", "public feeds", "plugin instance output_path", "filebrowser v2"])]
+    #[case(CubeVersion::new(3, 5, 0), vec!["workflow nodes_info", "public feeds", "plugin instance output_path", "filebrowser v2"])]
+    #[case(CubeVersion::new(4, 0, 0), vec!["filebrowser v2"])]
+    #[case(CubeVersion::new(5, 99, 0), vec!["filebrowser v2"])]
+    #[case(CubeVersion::new(6, 0, 0), vec![])]
+    #[case(CubeVersion::new(7, 0, 0), vec![])]
+    fn test_degraded_features(#[case] cube: CubeVersion, #[case] expected: Vec<&str>) {
+        let actual: Vec<_> = degraded_features(cube)
+            .into_iter()
+            .map(|r| r.feature)
+            .collect();
+        assert_eq!(actual, expected)
+    }
+}
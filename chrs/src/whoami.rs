@@ -1,25 +1,62 @@
-use crate::credentials::Credentials;
+use crate::credentials::{Credentials, NO_ARGS};
 use crate::login::state::ChrsSessions;
-use color_eyre::eyre::{bail, Result};
+use chris::EitherClient;
+use color_eyre::eyre::{bail, Result, WrapErr};
 use color_eyre::owo_colors::OwoColorize;
 
-pub fn whoami(
-    Credentials {
-        cube_url,
-        username,
-        config_path: config_name,
-        ..
-    }: Credentials,
-) -> Result<()> {
-    let sessions = ChrsSessions::load(config_name)?;
-    if let Some(login) = sessions.get_cube(cube_url.as_ref(), username.as_ref()) {
-        println!(
-            "Logged into ChRIS {} as user \"{}\"",
-            login.cube.cyan(),
-            login.username.green()
-        );
-        Ok(())
+pub async fn whoami(credentials: Credentials, check: bool) -> Result<()> {
+    let sessions = ChrsSessions::load(credentials.config_path.clone())?;
+    if let Some(login) =
+        sessions.get_cube(credentials.cube_url.as_ref(), credentials.username.as_ref())
+    {
+        if login.is_anonymous() {
+            println!("{} @ {}", "anonymous".dimmed(), login.cube.cyan());
+        } else {
+            println!(
+                "Logged into ChRIS {} as user \"{}\"",
+                login.cube.cyan(),
+                login.username.green()
+            );
+        }
     } else {
         bail!("You are not logged in.")
     }
+    if check {
+        check_login(credentials).await
+    } else {
+        Ok(())
+    }
+}
+
+/// Contact _CUBE_ to check whether the saved token is valid, and print the details
+/// of the account.
+async fn check_login(credentials: Credentials) -> Result<()> {
+    let (client, _, _) = credentials
+        .get_client(NO_ARGS)
+        .await
+        .wrap_err("Token is not valid, or ChRIS could not be reached")?;
+    let client = match client {
+        EitherClient::Anon(_) => {
+            println!("{}", "ChRIS is reachable.".green());
+            return Ok(());
+        }
+        EitherClient::LoggedIn(client) => client,
+    };
+    let user = client
+        .user()
+        .await
+        .wrap_err("Token is not valid, or ChRIS could not be reached")?;
+    let feeds = client.feeds().search().get_count().await?;
+    println!("{}", "Token is valid.".green());
+    if let Some(user) = user {
+        println!("{:>8}: {}", "Email", user.object.email);
+        let is_staff = match user.object.is_staff {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "unknown",
+        };
+        println!("{:>8}: {}", "Staff", is_staff);
+    }
+    println!("{:>8}: {}", "Feeds", feeds);
+    Ok(())
 }
@@ -0,0 +1,238 @@
+//! `chrs workflow`: inspect workflows, i.e. runs of pipelines.
+
+use std::collections::{HashMap, HashSet};
+
+use clap::Subcommand;
+use color_eyre::eyre::{bail, eyre, OptionExt, Result};
+use color_eyre::owo_colors::OwoColorize;
+use futures::TryStreamExt;
+
+use chris::types::{FeedId, SimplifiedStatus, WorkflowId};
+use chris::{BaseChrisClient, ChrisClient, PluginInstanceResponse, RwAccess, Workflow};
+
+use crate::arg::GivenDataNode;
+use crate::credentials::Credentials;
+use crate::sanitize::sanitize_for_terminal;
+use crate::status::symbol_for;
+
+/// Maximum number of workflows to check at the same time when finding the
+/// workflows of a feed.
+const CONCURRENCY: usize = 4;
+
+#[derive(Subcommand)]
+pub enum WorkflowCommand {
+    /// Show the plugin instances created by a workflow, with their piping titles
+    /// and statuses. Exits with an error if any of them failed.
+    Status {
+        /// Workflow, e.g. `workflow/5`, or a feed to show the workflows of
+        /// [default: feed of the current plugin instance]
+        workflow_or_feed: Option<String>,
+    },
+}
+
+/// `chrs workflow` command
+pub async fn workflow(credentials: Credentials, command: WorkflowCommand) -> Result<()> {
+    match command {
+        WorkflowCommand::Status { workflow_or_feed } => {
+            workflow_status(credentials, workflow_or_feed).await
+        }
+    }
+}
+
+/// A workflow or feed given on the command line.
+#[derive(Debug)]
+enum GivenWorkflowOrFeed {
+    Workflow(WorkflowId),
+    Feed(GivenDataNode),
+}
+
+impl From<String> for GivenWorkflowOrFeed {
+    fn from(value: String) -> Self {
+        value
+            .strip_prefix("workflow/")
+            .or_else(|| value.strip_prefix("w/"))
+            .and_then(|id| id.parse().ok())
+            .map(|id| Self::Workflow(WorkflowId(id)))
+            .unwrap_or_else(|| Self::Feed(GivenDataNode::from(value)))
+    }
+}
+
+async fn workflow_status(credentials: Credentials, given: Option<String>) -> Result<()> {
+    let (client, old, _) = credentials.get_client(given.as_slice()).await?;
+    let client = client
+        .logged_in()
+        .ok_or_eyre("You must be logged in to see workflows.")?;
+    let workflows = match given.map(GivenWorkflowOrFeed::from) {
+        Some(GivenWorkflowOrFeed::Workflow(id)) => {
+            let workflow = client
+                .workflows()
+                .id(id)
+                .search()
+                .get_only()
+                .await
+                .map_err(|e| eyre!("Could not get workflow/{}: {}", id.0, e))?;
+            vec![workflow]
+        }
+        Some(GivenWorkflowOrFeed::Feed(feed)) => {
+            let feed = feed.into_feed_rw(&client, old).await?;
+            workflows_of_feed(&client, feed.object.id).await?
+        }
+        None => {
+            let id = old.ok_or_eyre("missing operand")?;
+            let feed_id = client.get_plugin_instance(id).await?.object.feed_id;
+            workflows_of_feed(&client, feed_id).await?
+        }
+    };
+
+    let mut failed = 0;
+    for workflow in workflows {
+        let plugin_instances: Vec<_> = workflow.plugin_instances().stream().try_collect().await?;
+        failed += plugin_instances
+            .iter()
+            .filter(|p| {
+                matches!(
+                    p.status.simplify(),
+                    SimplifiedStatus::Error | SimplifiedStatus::Cancelled
+                )
+            })
+            .count();
+        println!("{}", describe_workflow(&workflow));
+        for (depth, plinst) in topological_order(plugin_instances) {
+            println!(
+                "{}{} {} {} {}",
+                "  ".repeat(depth + 1),
+                symbol_for(&plinst),
+                format!("plugininstance/{}", plinst.id.0).bold(),
+                plinst.status.as_str().dimmed(),
+                sanitize_for_terminal(&plinst.title)
+            );
+        }
+    }
+    if failed > 0 {
+        bail!(
+            "{} plugin instance{} did not finish successfully",
+            failed,
+            if failed == 1 { "" } else { "s" }
+        )
+    }
+    Ok(())
+}
+
+/// Find the workflows of the logged in user which created plugin instances in a feed.
+async fn workflows_of_feed(
+    client: &ChrisClient,
+    feed_id: FeedId,
+) -> Result<Vec<Workflow<RwAccess>>> {
+    let workflows: Vec<_> = client
+        .workflows()
+        .owner_username(client.username())
+        .search()
+        .stream_connected()
+        .map_ok(|workflow| async move {
+            let first = workflow.plugin_instances().get_first().await?;
+            let is_in_feed = first.is_some_and(|p| p.object.feed_id == feed_id);
+            Ok::<_, chris::errors::CubeError>(is_in_feed.then_some(workflow))
+        })
+        .try_buffered(CONCURRENCY)
+        .try_filter_map(futures::future::ok)
+        .try_collect()
+        .await?;
+    if workflows.is_empty() {
+        bail!("No workflows of yours found in feed/{}", feed_id.0)
+    }
+    Ok(workflows)
+}
+
+fn describe_workflow(workflow: &Workflow<RwAccess>) -> String {
+    let w = &workflow.object;
+    format!(
+        "{} {} {} {} {}",
+        format!("workflow/{}", w.id.0).bold(),
+        sanitize_for_terminal(&w.title),
+        "of pipeline".dimmed(),
+        sanitize_for_terminal(&w.pipeline_name).blue(),
+        format!("(by {})", w.owner_username.as_str()).dimmed()
+    )
+}
+
+/// Order plugin instances so that every plugin instance comes after its previous,
+/// paired with its depth, i.e. how many of its ancestors are in `plugin_instances`.
+/// Siblings are ordered by ID.
+fn topological_order(
+    plugin_instances: Vec<PluginInstanceResponse>,
+) -> Vec<(usize, PluginInstanceResponse)> {
+    let ids: HashSet<_> = plugin_instances.iter().map(|p| p.id.0).collect();
+    let mut children: HashMap<Option<u32>, Vec<PluginInstanceResponse>> = HashMap::new();
+    for plinst in plugin_instances {
+        let parent = plinst
+            .previous_id
+            .map(|id| id.0)
+            .filter(|id| ids.contains(id));
+        children.entry(parent).or_default().push(plinst);
+    }
+    // siblings are popped from the stack below, so they are sorted in reverse
+    children
+        .values_mut()
+        .for_each(|siblings| siblings.sort_by_key(|p| std::cmp::Reverse(p.id.0)));
+    let mut ordered = Vec::with_capacity(ids.len());
+    let mut stack: Vec<_> = children
+        .remove(&None)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| (0, p))
+        .collect();
+    while let Some((depth, plinst)) = stack.pop() {
+        if let Some(next) = children.remove(&Some(plinst.id.0)) {
+            stack.extend(next.into_iter().map(|p| (depth + 1, p)));
+        }
+        ordered.push((depth, plinst));
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chris::testing::MockCube;
+    use chris::types::PluginInstanceId;
+    use rstest::*;
+
+    #[rstest]
+    #[case("workflow/5", Some(5))]
+    #[case("w/12", Some(12))]
+    #[case("feed/3", None)]
+    #[case("workflow/name", None)]
+    fn test_parse_given(#[case] given: &str, #[case] expected_workflow: Option<u32>) {
+        let actual = match GivenWorkflowOrFeed::from(given.to_string()) {
+            GivenWorkflowOrFeed::Workflow(id) => Some(id.0),
+            GivenWorkflowOrFeed::Feed(_) => None,
+        };
+        assert_eq!(actual, expected_workflow)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_topological_order() {
+        let mock = MockCube::start().await;
+        let plugin = mock.plugin(1, "pl-simpledsapp", "2.1.0");
+        let feed = mock.feed(1, "Workflow");
+        let plinst = |id, previous_id| PluginInstanceResponse {
+            previous_id: Some(PluginInstanceId(previous_id)),
+            ..mock.plugin_instance(id, &plugin, &feed, None)
+        };
+        // 10 is the input of the workflow, created before the workflow
+        let plugin_instances = vec![
+            plinst(14, 12),
+            plinst(13, 11),
+            plinst(12, 11),
+            plinst(11, 10),
+            plinst(15, 13),
+        ];
+        let actual: Vec<_> = topological_order(plugin_instances)
+            .into_iter()
+            .map(|(depth, p)| (depth, p.id.0))
+            .collect();
+        let expected = vec![(0, 11), (1, 12), (2, 14), (1, 13), (2, 15)];
+        assert_eq!(actual, expected)
+    }
+}